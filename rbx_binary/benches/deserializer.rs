@@ -1,5 +1,11 @@
 use criterion::{criterion_group, criterion_main, Criterion};
 
+use rbx_binary::{CompressionType, Serializer};
+use rbx_dom_weak::{
+    types::{CFrame, Color3uint8, Matrix3, Vector3},
+    InstanceBuilder, WeakDom,
+};
+
 pub fn de_folders_100(c: &mut Criterion) {
     static BUFFER: &[u8] = include_bytes!("../bench-files/folders-100.rbxm");
 
@@ -30,6 +36,38 @@ pub fn de_modulescripts_100_lines_100(c: &mut Criterion) {
     });
 }
 
+pub fn de_parts_10000(c: &mut Criterion) {
+    let mut tree = WeakDom::new(InstanceBuilder::new("Folder"));
+    let root_ref = tree.root_ref();
+
+    for i in 0..10_000 {
+        let position = Vector3::new(i as f32, (i % 100) as f32 * 2.5, -(i as f32) / 3.0);
+
+        tree.insert(
+            root_ref,
+            InstanceBuilder::new("Part")
+                .with_property("CFrame", CFrame::new(position, Matrix3::identity()))
+                .with_property("Size", Vector3::new(4.0, 1.2, 2.0))
+                .with_property("Color3uint8", Color3uint8::new(i as u8, 128, 255))
+                .with_property("Transparency", (i % 10) as f32 / 10.0),
+        );
+    }
+
+    // Chunks are left uncompressed so that decoding the values themselves is
+    // what gets measured.
+    let mut buffer = Vec::new();
+    Serializer::new()
+        .compression(CompressionType::None)
+        .serialize(&mut buffer, &tree, &[root_ref])
+        .unwrap();
+
+    c.bench_function("Deserialize 10,000 Parts", |b| {
+        b.iter(|| {
+            deserialize_bench(&buffer);
+        });
+    });
+}
+
 #[inline(always)]
 fn deserialize_bench(buffer: &[u8]) {
     rbx_binary::from_reader(buffer).unwrap();
@@ -39,6 +77,7 @@ criterion_group!(
     deserializer,
     de_folders_100,
     de_deep_folders_100,
-    de_modulescripts_100_lines_100
+    de_modulescripts_100_lines_100,
+    de_parts_10000
 );
 criterion_main!(deserializer);
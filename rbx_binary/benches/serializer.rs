@@ -1,6 +1,10 @@
 use criterion::{criterion_group, criterion_main, Criterion};
 
-use rbx_dom_weak::{InstanceBuilder, WeakDom};
+use rbx_binary::{CompressionType, Serializer};
+use rbx_dom_weak::{
+    types::{CFrame, Color3uint8, Matrix3, Vector3},
+    InstanceBuilder, WeakDom,
+};
 
 pub fn ser_folders_100(c: &mut Criterion) {
     let mut tree = WeakDom::new(InstanceBuilder::new("Folder").with_name("Container"));
@@ -27,5 +31,43 @@ pub fn ser_folders_100(c: &mut Criterion) {
     });
 }
 
-criterion_group!(serializer, ser_folders_100);
+pub fn ser_parts_10000(c: &mut Criterion) {
+    let mut tree = WeakDom::new(InstanceBuilder::new("Folder"));
+    let root_ref = tree.root_ref();
+
+    for i in 0..10_000 {
+        let position = Vector3::new(i as f32, (i % 100) as f32 * 2.5, -(i as f32) / 3.0);
+
+        tree.insert(
+            root_ref,
+            InstanceBuilder::new("Part")
+                .with_property("CFrame", CFrame::new(position, Matrix3::identity()))
+                .with_property("Size", Vector3::new(4.0, 1.2, 2.0))
+                .with_property("Color3uint8", Color3uint8::new(i as u8, 128, 255))
+                .with_property("Transparency", (i % 10) as f32 / 10.0),
+        );
+    }
+
+    // Chunks are left uncompressed so that encoding the values themselves is
+    // what gets measured.
+    let serializer = Serializer::new().compression(CompressionType::None);
+    let mut buffer = Vec::new();
+
+    // Encode once into the buffer to pre-size it.
+    serializer
+        .serialize(&mut buffer, &tree, &[root_ref])
+        .unwrap();
+    buffer.clear();
+
+    c.bench_function("Serialize 10,000 Parts", |b| {
+        b.iter(|| {
+            serializer
+                .serialize(&mut buffer, &tree, &[root_ref])
+                .unwrap();
+            buffer.clear();
+        });
+    });
+}
+
+criterion_group!(serializer, ser_folders_100, ser_parts_10000);
 criterion_main!(serializer);
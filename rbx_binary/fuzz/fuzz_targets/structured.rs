@@ -0,0 +1,11 @@
+//! Decodes files that are built out of real chunks, which gets much deeper
+//! into the decoder than arbitrary bytes usually do.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rbx_binary_fuzz::File;
+
+fuzz_target!(|file: File| {
+    rbx_binary_fuzz::check_round_trip(&file.to_bytes());
+});
@@ -0,0 +1,408 @@
+//! Shared code for rbx_binary's fuzz targets.
+
+use arbitrary::Arbitrary;
+use rbx_binary::{ChunkReader, Deserializer, Serializer};
+use rbx_dom_weak::WeakDom;
+
+/// Decodes `data` every way rbx_binary can, none of which should panic. If it
+/// decodes, checks that the result can be encoded, and that encoding what's
+/// decoded from that output gives the same output again. Panics if any of
+/// that doesn't hold.
+pub fn check_round_trip(data: &[u8]) {
+    let deserializer = Deserializer::new();
+
+    if let Ok(reader) = ChunkReader::new(data) {
+        reader.for_each(drop);
+    }
+
+    if let Ok(stream) = deserializer.stream(data) {
+        stream.for_each(drop);
+    }
+
+    if let Ok(document) = deserializer.deserialize_lazy(data) {
+        let _ = document.into_tree();
+    }
+
+    let _ = deserializer.deserialize_recovering(data);
+
+    let tree = match deserializer.deserialize(data) {
+        Ok(tree) => tree,
+        Err(_) => return,
+    };
+
+    let encode = |tree: &WeakDom| {
+        let mut output = Vec::new();
+        Serializer::new()
+            .serialize(&mut output, tree, tree.root().children())
+            .map(|_| output)
+    };
+
+    let first = encode(&tree)
+        .unwrap_or_else(|err| panic!("couldn't encode a file that decoded: {}", err));
+
+    let redecoded = deserializer
+        .deserialize(first.as_slice())
+        .unwrap_or_else(|err| panic!("couldn't decode rbx_binary's own output: {}", err));
+
+    let second = encode(&redecoded)
+        .unwrap_or_else(|err| panic!("couldn't re-encode rbx_binary's own output: {}", err));
+
+    assert!(first == second, "encoding changed after a round trip");
+}
+
+/// A file built out of the chunks rbx_binary understands, with arbitrary
+/// contents. Counts and lengths are filled in to match, so that decoding
+/// usually gets past them.
+#[derive(Debug, Arbitrary)]
+pub struct File {
+    num_types: u8,
+    num_instances: u8,
+    chunks: Vec<Chunk>,
+    end: bool,
+}
+
+impl File {
+    /// Writes the file out in the binary format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut output = Vec::new();
+        output.extend_from_slice(b"<roblox!\x89\xff\x0d\x0a\x1a\x0a");
+        output.extend_from_slice(&0u16.to_le_bytes());
+        write_u32(&mut output, self.num_types as u32);
+        write_u32(&mut output, self.num_instances as u32);
+        output.extend_from_slice(&[0; 8]);
+
+        for chunk in &self.chunks {
+            chunk.write(&mut output);
+        }
+
+        if self.end {
+            Chunk {
+                compressed: false,
+                contents: Contents::End,
+            }
+            .write(&mut output);
+        }
+
+        output
+    }
+}
+
+#[derive(Debug, Arbitrary)]
+struct Chunk {
+    compressed: bool,
+    contents: Contents,
+}
+
+impl Chunk {
+    fn write(&self, output: &mut Vec<u8>) {
+        let mut data = Vec::new();
+        let name = self.contents.write(&mut data);
+
+        output.extend_from_slice(name);
+
+        if self.compressed {
+            let compressed = lz4::block::compress(&data, None, false).unwrap();
+            write_u32(output, compressed.len() as u32);
+            write_u32(output, data.len() as u32);
+            write_u32(output, 0);
+            output.extend_from_slice(&compressed);
+        } else {
+            write_u32(output, 0);
+            write_u32(output, data.len() as u32);
+            write_u32(output, 0);
+            output.extend_from_slice(&data);
+        }
+    }
+}
+
+#[derive(Debug, Arbitrary)]
+enum Contents {
+    Meta(Vec<(String, String)>),
+    Sstr(Vec<Vec<u8>>),
+    Inst {
+        type_id: u8,
+        class: Class,
+        service: bool,
+        referents: Vec<u8>,
+    },
+    Prop {
+        type_id: u8,
+        name: PropertyName,
+        values: Values,
+    },
+    Prnt(Vec<(u8, Option<u8>)>),
+    Sign(Vec<u8>),
+    End,
+    Unknown([u8; 4], Vec<u8>),
+}
+
+impl Contents {
+    /// Writes the contents of the chunk, returning its name.
+    fn write(&self, output: &mut Vec<u8>) -> &[u8; 4] {
+        match self {
+            Contents::Meta(entries) => {
+                write_u32(output, entries.len() as u32);
+
+                for (key, value) in entries {
+                    write_string(output, key.as_bytes());
+                    write_string(output, value.as_bytes());
+                }
+
+                b"META"
+            }
+            Contents::Sstr(strings) => {
+                write_u32(output, 0);
+                write_u32(output, strings.len() as u32);
+
+                for string in strings {
+                    output.extend_from_slice(&[0; 16]);
+                    write_string(output, string);
+                }
+
+                b"SSTR"
+            }
+            Contents::Inst {
+                type_id,
+                class,
+                service,
+                referents,
+            } => {
+                write_u32(output, *type_id as u32);
+                write_string(output, format!("{:?}", class).as_bytes());
+                output.push(*service as u8);
+                write_u32(output, referents.len() as u32);
+                write_referents(output, referents.iter().map(|&referent| referent as i32));
+
+                if *service {
+                    output.extend(referents.iter().map(|_| 1));
+                }
+
+                b"INST"
+            }
+            Contents::Prop {
+                type_id,
+                name,
+                values,
+            } => {
+                write_u32(output, *type_id as u32);
+                write_string(output, format!("{:?}", name).as_bytes());
+                values.write(output);
+
+                b"PROP"
+            }
+            Contents::Prnt(pairs) => {
+                output.push(0);
+                write_u32(output, pairs.len() as u32);
+                write_referents(output, pairs.iter().map(|&(referent, _)| referent as i32));
+                write_referents(
+                    output,
+                    pairs
+                        .iter()
+                        .map(|&(_, parent)| parent.map_or(-1, |parent| parent as i32)),
+                );
+
+                b"PRNT"
+            }
+            Contents::Sign(data) => {
+                output.extend_from_slice(data);
+                b"SIGN"
+            }
+            Contents::End => {
+                output.extend_from_slice(b"</roblox>");
+                b"END\0"
+            }
+            Contents::Unknown(name, data) => {
+                output.extend_from_slice(data);
+                name
+            }
+        }
+    }
+}
+
+#[derive(Debug, Arbitrary)]
+enum Class {
+    Folder,
+    Model,
+    Part,
+    ObjectValue,
+    StringValue,
+    Script,
+    Terrain,
+    Workspace,
+}
+
+/// Real property names, so that values are often, but not always, written
+/// with the type the reflection database expects.
+#[derive(Debug, Arbitrary)]
+enum PropertyName {
+    Name,
+    Value,
+    Anchored,
+    Size,
+    Color,
+    Color3uint8,
+    CFrame,
+    Transparency,
+    Source,
+    Tags,
+    AttributesSerialize,
+    PrimaryPart,
+    MaterialColors,
+    Material,
+    WorldPivotData,
+}
+
+/// An array of property values, one for each instance of a class. The
+/// number of values doesn't always match the number of instances.
+#[derive(Debug, Arbitrary)]
+enum Values {
+    String(Vec<Vec<u8>>),
+    Bool(Vec<bool>),
+    Int32(Vec<i32>),
+    Float32(Vec<f32>),
+    Float64(Vec<f64>),
+    Vector3(Vec<[f32; 3]>),
+    CFrame(Vec<(u8, [f32; 9], [f32; 3])>),
+    Enum(Vec<u32>),
+    Ref(Vec<i32>),
+    Color3uint8(Vec<[u8; 3]>),
+    Int64(Vec<i64>),
+    SharedString(Vec<u32>),
+    Other(u8, Vec<u8>),
+}
+
+impl Values {
+    fn write(&self, output: &mut Vec<u8>) {
+        match self {
+            Values::String(values) => {
+                output.push(0x01);
+
+                for value in values {
+                    write_string(output, value);
+                }
+            }
+            Values::Bool(values) => {
+                output.push(0x02);
+                output.extend(values.iter().map(|&value| value as u8));
+            }
+            Values::Int32(values) => {
+                output.push(0x03);
+                write_interleaved(output, values.iter().map(|&value| transform_i32(value)));
+            }
+            Values::Float32(values) => {
+                output.push(0x04);
+                write_floats(output, values.iter().copied());
+            }
+            Values::Float64(values) => {
+                output.push(0x05);
+
+                for value in values {
+                    output.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            Values::Vector3(values) => {
+                output.push(0x0e);
+                write_vector3s(output, values);
+            }
+            Values::CFrame(values) => {
+                output.push(0x10);
+
+                for (id, rotation, _) in values {
+                    // Mostly real rotation IDs, with a few that aren't.
+                    let id = id % 0x26;
+                    output.push(id);
+
+                    if id == 0 {
+                        for component in rotation {
+                            output.extend_from_slice(&component.to_le_bytes());
+                        }
+                    }
+                }
+
+                let positions: Vec<_> = values.iter().map(|&(_, _, position)| position).collect();
+                write_vector3s(output, &positions);
+            }
+            Values::Enum(values) => {
+                output.push(0x12);
+                write_interleaved(output, values.iter().map(|value| value.to_be_bytes()));
+            }
+            Values::Ref(values) => {
+                output.push(0x13);
+                write_referents(output, values.iter().copied());
+            }
+            Values::Color3uint8(values) => {
+                output.push(0x1a);
+
+                for channel in 0..3 {
+                    output.extend(values.iter().map(|value| value[channel]));
+                }
+            }
+            Values::Int64(values) => {
+                output.push(0x1b);
+                write_interleaved(
+                    output,
+                    values
+                        .iter()
+                        .map(|&value| ((value << 1) ^ (value >> 63)).to_be_bytes()),
+                );
+            }
+            Values::SharedString(values) => {
+                output.push(0x1c);
+                write_interleaved(output, values.iter().map(|value| value.to_be_bytes()));
+            }
+            Values::Other(type_id, data) => {
+                output.push(*type_id);
+                output.extend_from_slice(data);
+            }
+        }
+    }
+}
+
+fn write_u32(output: &mut Vec<u8>, value: u32) {
+    output.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_string(output: &mut Vec<u8>, value: &[u8]) {
+    write_u32(output, value.len() as u32);
+    output.extend_from_slice(value);
+}
+
+fn transform_i32(value: i32) -> [u8; 4] {
+    ((value << 1) ^ (value >> 31)).to_be_bytes()
+}
+
+/// Writes values with the first bytes of each value together, then the second
+/// bytes, and so on.
+fn write_interleaved<const N: usize>(output: &mut Vec<u8>, values: impl Iterator<Item = [u8; N]>) {
+    let values: Vec<_> = values.collect();
+
+    for byte in 0..N {
+        output.extend(values.iter().map(|value| value[byte]));
+    }
+}
+
+fn write_floats(output: &mut Vec<u8>, values: impl Iterator<Item = f32>) {
+    write_interleaved(
+        output,
+        values.map(|value| value.to_bits().rotate_left(1).to_be_bytes()),
+    );
+}
+
+fn write_vector3s(output: &mut Vec<u8>, values: &[[f32; 3]]) {
+    for axis in 0..3 {
+        write_floats(output, values.iter().map(|value| value[axis]));
+    }
+}
+
+fn write_referents(output: &mut Vec<u8>, referents: impl Iterator<Item = i32>) {
+    let mut last = 0i32;
+
+    write_interleaved(
+        output,
+        referents.map(|referent| {
+            let delta = referent.wrapping_sub(last);
+            last = referent;
+            transform_i32(delta)
+        }),
+    );
+}
@@ -4,7 +4,13 @@ use std::{
     str,
 };
 
-use crate::core::{RbxReadExt, RbxWriteExt};
+use lz4::block::CompressionMode;
+
+use crate::core::{RbxReadExt, RbxWriteExt, MAX_PREALLOCATION};
+
+/// Compressed chunks that start with these bytes are compressed with ZSTD
+/// instead of LZ4.
+const ZSTD_MAGIC_NUMBER: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
 
 /// Represents one chunk from a binary model file.
 #[derive(Debug)]
@@ -15,25 +21,56 @@ pub struct Chunk {
 
 impl Chunk {
     /// Reads and decodes a `Chunk` from the given reader.
-    pub fn decode<R: Read>(mut reader: R) -> io::Result<Chunk> {
+    pub fn decode<R: Read>(reader: R) -> io::Result<Chunk> {
+        Self::decode_with_limit(reader, usize::MAX)
+    }
+
+    /// Reads and decodes a `Chunk` from the given reader, failing before
+    /// anything is allocated if the chunk is longer than `limit` bytes either
+    /// compressed or decompressed.
+    pub fn decode_with_limit<R: Read>(mut reader: R, limit: usize) -> io::Result<Chunk> {
         let header = decode_chunk_header(&mut reader)?;
 
         log::trace!("{}", header);
 
+        let longest = header.len.max(header.compressed_len);
+        if longest as usize > limit {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Chunk {} is {} bytes long, which is over the limit of {} bytes",
+                    header.display_name(),
+                    longest,
+                    limit
+                ),
+            ));
+        }
+
         let data = if header.compressed_len == 0 {
-            let mut data = Vec::with_capacity(header.len as usize);
+            let mut data = Vec::with_capacity((header.len as usize).min(MAX_PREALLOCATION));
             reader.take(header.len as u64).read_to_end(&mut data)?;
             data
         } else {
-            let mut compressed_data = Vec::with_capacity(header.compressed_len as usize);
+            let mut compressed_data =
+                Vec::with_capacity((header.compressed_len as usize).min(MAX_PREALLOCATION));
             reader
                 .take(header.compressed_len as u64)
                 .read_to_end(&mut compressed_data)?;
 
-            lz4::block::decompress(&compressed_data, Some(header.len as i32))?
+            decompress(&header, &compressed_data)?
         };
 
-        assert_eq!(data.len(), header.len as usize);
+        if data.len() != header.len as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!(
+                    "Chunk {} should be {} bytes long, but only {} bytes could be read",
+                    header.display_name(),
+                    header.len,
+                    data.len()
+                ),
+            ));
+        }
 
         Ok(Chunk {
             name: header.name,
@@ -42,16 +79,43 @@ impl Chunk {
     }
 }
 
+/// A chunk that rbx_binary doesn't understand, kept as-is so that it can be
+/// written back out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownChunk {
+    /// The name of the chunk. Names shorter than four bytes are padded with
+    /// zeros.
+    pub name: [u8; 4],
+
+    /// The decompressed contents of the chunk.
+    pub data: Vec<u8>,
+}
+
 /// The compression format of a chunk in the binary model format.
 #[derive(Debug, Clone, Copy)]
 pub enum ChunkCompression {
-    /// The contents of the chunk should be LZ4 compressed.
-    Compressed,
+    /// The contents of the chunk should be compressed using the given codec.
+    /// If `only_if_smaller` is set, the chunk is written uncompressed when
+    /// compressing it wouldn't make it smaller.
+    Compressed {
+        codec: ChunkCodec,
+        only_if_smaller: bool,
+    },
 
     /// The contents of the chunk should be uncompressed.
     Uncompressed,
 }
 
+/// The algorithm that compressed chunks are compressed with.
+#[derive(Debug, Clone, Copy)]
+pub enum ChunkCodec {
+    /// LZ4 using the given mode.
+    Lz4(CompressionMode),
+
+    /// ZSTD at the given compression level.
+    Zstd(i32),
+}
+
 /// Holds a chunk that is currently being written.
 ///
 /// This type intended to be written into via io::Write and then dumped into the
@@ -59,7 +123,7 @@ pub enum ChunkCompression {
 /// automatically.
 #[must_use]
 pub struct ChunkBuilder {
-    chunk_name: &'static [u8],
+    chunk_name: [u8; 4],
     compression: ChunkCompression,
     buffer: Vec<u8>,
 }
@@ -67,37 +131,83 @@ pub struct ChunkBuilder {
 impl ChunkBuilder {
     /// Creates a new `ChunkBuilder` with the given name and compression
     /// setting.
-    pub fn new(chunk_name: &'static [u8], compression: ChunkCompression) -> Self {
+    pub fn new(chunk_name: &[u8; 4], compression: ChunkCompression) -> Self {
         ChunkBuilder {
-            chunk_name,
+            chunk_name: *chunk_name,
             compression,
             buffer: Vec::new(),
         }
     }
 
-    /// Consume the chunk and write it to the given writer.
-    pub fn dump<W: Write>(self, mut writer: W) -> io::Result<()> {
-        writer.write_all(self.chunk_name)?;
+    /// The number of bytes that have been written into the chunk so far.
+    pub fn len(&self) -> usize {
+        self.buffer.len()
+    }
 
-        match self.compression {
-            ChunkCompression::Compressed => {
-                let compressed = lz4::block::compress(&self.buffer, None, false)?;
+    /// Consume the chunk and write it to the given writer.
+    pub fn dump<W: Write>(self, writer: W) -> io::Result<()> {
+        self.finish()?.dump(writer)
+    }
 
-                writer.write_le_u32(compressed.len() as u32)?;
-                writer.write_le_u32(self.buffer.len() as u32)?;
-                writer.write_le_u32(0)?;
+    /// Consume the chunk and compress it, if it should be compressed, without
+    /// writing it anywhere yet. This is the slow part of writing a chunk, so
+    /// it's kept separate to be done on other threads.
+    pub fn finish(self) -> io::Result<FinishedChunk> {
+        if let ChunkCompression::Compressed {
+            codec,
+            only_if_smaller,
+        } = self.compression
+        {
+            let compressed = match codec {
+                ChunkCodec::Lz4(mode) => lz4::block::compress(&self.buffer, Some(mode), false)?,
+                ChunkCodec::Zstd(level) => zstd::bulk::compress(&self.buffer, level)?,
+            };
 
-                writer.write_all(&compressed)?;
+            if !only_if_smaller || compressed.len() < self.buffer.len() {
+                return Ok(FinishedChunk {
+                    chunk_name: self.chunk_name,
+                    len: self.buffer.len(),
+                    compressed: true,
+                    data: compressed,
+                });
             }
-            ChunkCompression::Uncompressed => {
-                writer.write_le_u32(0)?;
-                writer.write_le_u32(self.buffer.len() as u32)?;
-                writer.write_le_u32(0)?;
+        }
 
-                writer.write_all(&self.buffer)?;
-            }
+        Ok(FinishedChunk {
+            chunk_name: self.chunk_name,
+            len: self.buffer.len(),
+            compressed: false,
+            data: self.buffer,
+        })
+    }
+}
+
+/// A chunk that's been compressed by [`ChunkBuilder::finish`], and is ready to
+/// be written.
+#[must_use]
+pub struct FinishedChunk {
+    chunk_name: [u8; 4],
+    len: usize,
+    compressed: bool,
+    data: Vec<u8>,
+}
+
+impl FinishedChunk {
+    /// Consume the chunk and write it to the given writer.
+    pub fn dump<W: Write>(self, mut writer: W) -> io::Result<()> {
+        writer.write_all(&self.chunk_name)?;
+
+        if self.compressed {
+            writer.write_le_u32(self.data.len() as u32)?;
+        } else {
+            writer.write_le_u32(0)?;
         }
 
+        writer.write_le_u32(self.len as u32)?;
+        writer.write_le_u32(0)?;
+
+        writer.write_all(&self.data)?;
+
         Ok(())
     }
 }
@@ -113,29 +223,36 @@ impl Write for ChunkBuilder {
 }
 
 #[derive(Debug)]
-struct ChunkHeader {
+pub(crate) struct ChunkHeader {
     /// 4-byte short name for the chunk, like "INST" or "PRNT"
-    name: [u8; 4],
+    pub(crate) name: [u8; 4],
 
     /// The length of the chunk's compressed data. For uncompressed chunks, this
     /// is always zero.
-    compressed_len: u32,
+    pub(crate) compressed_len: u32,
 
     /// The length that the chunk's data will have when decompressed. For
     /// uncompressed chunks, this is their length as-is.
-    len: u32,
+    pub(crate) len: u32,
 
     /// Always zero.
-    reserved: u32,
+    pub(crate) reserved: u32,
 }
 
-impl fmt::Display for ChunkHeader {
-    fn fmt(&self, output: &mut fmt::Formatter) -> fmt::Result {
-        let name = if let Ok(name) = str::from_utf8(&self.name) {
+impl ChunkHeader {
+    /// The chunk's name as a string, if it's valid UTF-8.
+    pub(crate) fn display_name(&self) -> String {
+        if let Ok(name) = str::from_utf8(&self.name) {
             name.to_owned()
         } else {
             format!("{:?}", self.name)
-        };
+        }
+    }
+}
+
+impl fmt::Display for ChunkHeader {
+    fn fmt(&self, output: &mut fmt::Formatter) -> fmt::Result {
+        let name = self.display_name();
 
         write!(
             output,
@@ -145,7 +262,25 @@ impl fmt::Display for ChunkHeader {
     }
 }
 
-fn decode_chunk_header<R: Read>(source: &mut R) -> io::Result<ChunkHeader> {
+/// Reads a chunk header, failing if its reserved field isn't zero.
+pub(crate) fn decode_chunk_header<R: Read>(source: &mut R) -> io::Result<ChunkHeader> {
+    let header = read_chunk_header(source)?;
+
+    if header.reserved != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Chunk reserved space was not zero, it was {}. This chunk may be malformed.",
+                header.reserved
+            ),
+        ));
+    }
+
+    Ok(header)
+}
+
+/// Reads a chunk header as-is, without checking that it makes sense.
+pub(crate) fn read_chunk_header<R: Read>(source: &mut R) -> io::Result<ChunkHeader> {
     let mut name = [0; 4];
     source.read_exact(&mut name)?;
 
@@ -153,13 +288,6 @@ fn decode_chunk_header<R: Read>(source: &mut R) -> io::Result<ChunkHeader> {
     let len = source.read_le_u32()?;
     let reserved = source.read_le_u32()?;
 
-    if reserved != 0 {
-        panic!(
-            "Chunk reserved space was not zero, it was {}. This chunk may be malformed.",
-            reserved
-        );
-    }
-
     Ok(ChunkHeader {
         name,
         compressed_len,
@@ -167,3 +295,213 @@ fn decode_chunk_header<R: Read>(source: &mut R) -> io::Result<ChunkHeader> {
         reserved,
     })
 }
+
+/// Returns the start of a chunk's decompressed contents, as much as can be
+/// found without decompressing it. This is usually enough to tell what a chunk
+/// is about without paying to decompress all of it.
+///
+/// LZ4 blocks always start with a run of plain bytes, which is what this
+/// returns for LZ4 compressed chunks. Nothing can be found in ZSTD compressed
+/// chunks without decompressing them.
+pub(crate) fn peek_contents<'a>(header: &ChunkHeader, data: &'a [u8]) -> &'a [u8] {
+    if header.compressed_len == 0 {
+        return data;
+    }
+
+    if data.starts_with(ZSTD_MAGIC_NUMBER) {
+        return &[];
+    }
+
+    let token = match data.first() {
+        Some(token) => *token,
+        None => return &[],
+    };
+
+    // The high half of the token is the number of plain bytes. If it's 15,
+    // it's followed by bytes to add to it, up to and including the first byte
+    // that isn't 255.
+    let mut len = (token >> 4) as usize;
+    let mut position = 1;
+
+    if len == 15 {
+        loop {
+            let byte = match data.get(position) {
+                Some(byte) => *byte,
+                None => return &[],
+            };
+
+            position += 1;
+            len += byte as usize;
+
+            if byte != 255 {
+                break;
+            }
+        }
+    }
+
+    let end = (position + len).min(data.len());
+    &data[position.min(end)..end]
+}
+
+/// Decompresses the contents of the chunk with the given header.
+pub(crate) fn decompress(header: &ChunkHeader, compressed_data: &[u8]) -> io::Result<Vec<u8>> {
+    if compressed_data.starts_with(ZSTD_MAGIC_NUMBER) {
+        // ZSTD data can decompress to far more than LZ4 data can, so rather
+        // than trusting the header, this reads at most one byte more than it
+        // says to find out whether it's too long.
+        let mut data = Vec::with_capacity((header.len as usize).min(MAX_PREALLOCATION));
+        zstd::stream::read::Decoder::with_buffer(compressed_data)?
+            .take(header.len as u64 + 1)
+            .read_to_end(&mut data)?;
+
+        if data.len() != header.len as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Chunk {} should decompress to {} bytes, but decompressed to {}",
+                    header.display_name(),
+                    header.len,
+                    if data.len() > header.len as usize {
+                        "more".to_owned()
+                    } else {
+                        format!("{} bytes", data.len())
+                    }
+                ),
+            ));
+        }
+
+        return Ok(data);
+    }
+
+    // Every byte of LZ4 data decompresses to at most 255 bytes, so a longer
+    // length can't be right. Checking saves allocating for it.
+    if header.len as u64 > compressed_data.len() as u64 * 255 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "Chunk {} can't decompress to {} bytes from {} bytes",
+                header.display_name(),
+                header.len,
+                compressed_data.len()
+            ),
+        ));
+    }
+
+    lz4::block::decompress(compressed_data, Some(header.len as i32))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn zstd_round_trip() {
+        let contents: Vec<u8> = (0..=255).cycle().take(2000).collect();
+
+        let mut chunk = ChunkBuilder::new(
+            b"PROP",
+            ChunkCompression::Compressed {
+                codec: ChunkCodec::Zstd(3),
+                only_if_smaller: false,
+            },
+        );
+        chunk.write_all(&contents).unwrap();
+
+        let mut bytes = Vec::new();
+        chunk.dump(&mut bytes).unwrap();
+        assert_eq!(&bytes[16..20], ZSTD_MAGIC_NUMBER);
+
+        let decoded = Chunk::decode(bytes.as_slice()).unwrap();
+        assert_eq!(&decoded.name, b"PROP");
+        assert_eq!(decoded.data, contents);
+    }
+
+    #[test]
+    fn zstd_length_mismatch() {
+        let compressed = zstd::bulk::compress(&[7; 100], 3).unwrap();
+
+        for len in [99, 101] {
+            let header = ChunkHeader {
+                name: *b"PROP",
+                compressed_len: compressed.len() as u32,
+                len,
+                reserved: 0,
+            };
+
+            let error = decompress(&header, &compressed).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn peeked_contents_are_a_prefix() {
+        let mut contents: Vec<u8> = (0..40).collect();
+        contents.extend([7; 100]);
+        contents.extend(0..=255);
+
+        let compressed = lz4::block::compress(&contents, None, false).unwrap();
+        let header = ChunkHeader {
+            name: *b"PROP",
+            compressed_len: compressed.len() as u32,
+            len: contents.len() as u32,
+            reserved: 0,
+        };
+
+        let peeked = peek_contents(&header, &compressed);
+        assert!(peeked.len() >= 40);
+        assert!(contents.starts_with(peeked));
+    }
+
+    #[test]
+    fn chunk_length_limit() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"META");
+        bytes.write_le_u32(0).unwrap();
+        bytes.write_le_u32(4).unwrap();
+        bytes.write_le_u32(0).unwrap();
+        bytes.write_le_u32(0).unwrap();
+
+        let error = Chunk::decode_with_limit(bytes.as_slice(), 3).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        let chunk = Chunk::decode_with_limit(bytes.as_slice(), 4).unwrap();
+        assert_eq!(&chunk.name, b"META");
+        assert_eq!(chunk.data, [0; 4]);
+    }
+
+    #[test]
+    fn reserved_space_is_an_error() {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"META");
+        bytes.write_le_u32(0).unwrap();
+        bytes.write_le_u32(0).unwrap();
+        bytes.write_le_u32(1).unwrap();
+
+        let error = Chunk::decode(bytes.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn impossible_decompressed_lengths() {
+        let contents = vec![0; 4 * 1024 * 1024];
+        let compressed = lz4::block::compress(&contents, None, false).unwrap();
+
+        let encode = |len: u32| {
+            let mut bytes = Vec::new();
+            bytes.extend_from_slice(b"PROP");
+            bytes.write_le_u32(compressed.len() as u32).unwrap();
+            bytes.write_le_u32(len).unwrap();
+            bytes.write_le_u32(0).unwrap();
+            bytes.extend_from_slice(&compressed);
+            bytes
+        };
+
+        let chunk = Chunk::decode(encode(contents.len() as u32).as_slice()).unwrap();
+        assert_eq!(chunk.data, contents);
+
+        // A header claiming a huge length fails before anything is allocated
+        // for it.
+        let error = Chunk::decode(encode(u32::MAX).as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
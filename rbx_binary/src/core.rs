@@ -1,7 +1,4 @@
-use std::{
-    io::{self, Read, Write},
-    mem,
-};
+use std::io::{self, Read, Write};
 
 use rbx_reflection::{
     ClassDescriptor, PropertyDescriptor, PropertyKind, PropertySerialization, ReflectionDatabase,
@@ -11,6 +8,11 @@ pub static FILE_MAGIC_HEADER: &[u8] = b"<roblox!";
 pub static FILE_SIGNATURE: &[u8] = b"\x89\xff\x0d\x0a\x1a\x0a";
 pub const FILE_VERSION: u16 = 0;
 
+/// The most bytes set aside up front for a buffer whose length was read from
+/// a file. Files can claim any length they like, so longer buffers grow as
+/// they're read into instead.
+pub const MAX_PREALLOCATION: usize = 1024 * 1024;
+
 pub trait RbxReadExt: Read {
     fn read_le_u32(&mut self) -> io::Result<u32> {
         let mut buffer = [0; 4];
@@ -77,7 +79,7 @@ pub trait RbxReadExt: Read {
     fn read_binary_string(&mut self) -> io::Result<Vec<u8>> {
         let length = self.read_le_u32()?;
 
-        let mut value = Vec::with_capacity(length as usize);
+        let mut value = Vec::with_capacity((length as usize).min(MAX_PREALLOCATION));
         self.take(length as u64).read_to_end(&mut value)?;
 
         Ok(value)
@@ -88,7 +90,7 @@ pub trait RbxReadExt: Read {
     /// generally aren't dilligent about data being valid Unicode.
     fn read_string(&mut self) -> io::Result<String> {
         let length = self.read_le_u32()?;
-        let mut value = String::with_capacity(length as usize);
+        let mut value = String::with_capacity((length as usize).min(MAX_PREALLOCATION));
         self.take(length as u64).read_to_string(&mut value)?;
 
         Ok(value)
@@ -103,65 +105,41 @@ pub trait RbxReadExt: Read {
     ///
     /// This function allocates `N * output.len()` bytes before reading.
     fn read_interleaved_bytes<const N: usize>(&mut self, output: &mut [[u8; N]]) -> io::Result<()> {
-        let len = output.len();
-        let mut buffer = vec![0; len * N];
+        let mut buffer = vec![0; output.len() * N];
         self.read_exact(&mut buffer)?;
 
-        for (i, array) in output.iter_mut().enumerate() {
-            for (j, byte) in array.iter_mut().enumerate() {
-                *byte = buffer[i + len * j];
-            }
-        }
+        deinterleave(&buffer, output);
 
         Ok(())
     }
 
     fn read_interleaved_i32_array(&mut self, output: &mut [i32]) -> io::Result<()> {
-        let mut buffer = vec![0; output.len() * mem::size_of::<i32>()];
-        self.read_exact(&mut buffer)?;
-
-        for i in 0..output.len() {
-            let v0 = buffer[i] as i32;
-            let v1 = buffer[i + output.len()] as i32;
-            let v2 = buffer[i + output.len() * 2] as i32;
-            let v3 = buffer[i + output.len() * 3] as i32;
+        let values = read_interleaved_values::<_, 4>(self, output.len())?;
 
-            output[i] = untransform_i32((v0 << 24) | (v1 << 16) | (v2 << 8) | v3);
+        for (value, bytes) in output.iter_mut().zip(values) {
+            *value = untransform_i32(i32::from_be_bytes(bytes));
         }
 
         Ok(())
     }
 
     fn read_interleaved_u32_array(&mut self, output: &mut [u32]) -> io::Result<()> {
-        let mut buffer = vec![0; output.len() * mem::size_of::<u32>()];
-        self.read_exact(&mut buffer)?;
+        let values = read_interleaved_values::<_, 4>(self, output.len())?;
 
-        for i in 0..output.len() {
-            let bytes = [
-                buffer[i],
-                buffer[i + output.len()],
-                buffer[i + output.len() * 2],
-                buffer[i + output.len() * 3],
-            ];
-
-            output[i] = u32::from_be_bytes(bytes);
+        for (value, bytes) in output.iter_mut().zip(values) {
+            *value = u32::from_be_bytes(bytes);
         }
 
         Ok(())
     }
 
     fn read_interleaved_f32_array(&mut self, output: &mut [f32]) -> io::Result<()> {
-        let mut buf = vec![0; output.len() * mem::size_of::<f32>()];
-        self.read_exact(&mut buf)?;
-
-        for i in 0..output.len() {
-            let v0 = buf[i] as u32;
-            let v1 = buf[i + output.len()] as u32;
-            let v2 = buf[i + output.len() * 2] as u32;
-            let v3 = buf[i + output.len() * 3] as u32;
+        let values = read_interleaved_values::<_, 4>(self, output.len())?;
 
-            output[i] = f32::from_bits(((v0 << 24) | (v1 << 16) | (v2 << 8) | v3).rotate_right(1));
+        for (value, bytes) in output.iter_mut().zip(values) {
+            *value = f32::from_bits(u32::from_be_bytes(bytes).rotate_right(1));
         }
+
         Ok(())
     }
 
@@ -179,29 +157,10 @@ pub trait RbxReadExt: Read {
     }
 
     fn read_interleaved_i64_array(&mut self, output: &mut [i64]) -> io::Result<()> {
-        let mut buf = vec![0; output.len() * mem::size_of::<i64>()];
-        self.read_exact(&mut buf)?;
-
-        for i in 0..output.len() {
-            let z0 = buf[i] as i64;
-            let z1 = buf[i + output.len()] as i64;
-            let z2 = buf[i + output.len() * 2] as i64;
-            let z3 = buf[i + output.len() * 3] as i64;
-            let z4 = buf[i + output.len() * 4] as i64;
-            let z5 = buf[i + output.len() * 5] as i64;
-            let z6 = buf[i + output.len() * 6] as i64;
-            let z7 = buf[i + output.len() * 7] as i64;
-
-            output[i] = untransform_i64(
-                (z0 << 56)
-                    | (z1 << 48)
-                    | (z2 << 40)
-                    | (z3 << 32)
-                    | (z4 << 24)
-                    | (z5 << 16)
-                    | (z6 << 8)
-                    | z7,
-            );
+        let values = read_interleaved_values::<_, 8>(self, output.len())?;
+
+        for (value, bytes) in output.iter_mut().zip(values) {
+            *value = untransform_i64(i64::from_be_bytes(bytes));
         }
 
         Ok(())
@@ -267,14 +226,7 @@ pub trait RbxWriteExt: Write {
     ///
     /// This function allocates `N * values.len()` bytes before writing.
     fn write_interleaved_bytes<const N: usize>(&mut self, values: &[[u8; N]]) -> io::Result<()> {
-        let len = values.len();
-        let mut blob = vec![0; len * N];
-        for (x, bytes) in values.iter().enumerate() {
-            for (y, byte) in bytes.iter().enumerate() {
-                blob[y + x * N] = *byte;
-            }
-        }
-        self.write_all(&blob)?;
+        self.write_all(&interleave(values))?;
 
         Ok(())
     }
@@ -283,43 +235,28 @@ pub trait RbxWriteExt: Write {
     where
         I: Iterator<Item = i32>,
     {
-        let values: Vec<_> = values.collect();
+        let values: Vec<_> = values
+            .map(|value| transform_i32(value).to_be_bytes())
+            .collect();
 
-        for shift in &[24, 16, 8, 0] {
-            for value in values.iter().copied() {
-                let encoded = transform_i32(value) >> shift;
-                self.write_u8(encoded as u8)?;
-            }
-        }
-
-        Ok(())
+        self.write_interleaved_bytes(&values)
     }
 
     fn write_interleaved_u32_array(&mut self, values: &[u32]) -> io::Result<()> {
-        for shift in &[24, 16, 8, 0] {
-            for value in values.iter().copied() {
-                let encoded = value >> shift;
-                self.write_u8(encoded as u8)?;
-            }
-        }
+        let values: Vec<_> = values.iter().map(|value| value.to_be_bytes()).collect();
 
-        Ok(())
+        self.write_interleaved_bytes(&values)
     }
 
     fn write_interleaved_f32_array<I>(&mut self, values: I) -> io::Result<()>
     where
         I: Iterator<Item = f32>,
     {
-        let values: Vec<_> = values.collect();
-
-        for shift in &[24, 16, 8, 0] {
-            for value in values.iter().copied() {
-                let encoded = value.to_bits().rotate_left(1) >> shift;
-                self.write_u8(encoded as u8)?;
-            }
-        }
+        let values: Vec<_> = values
+            .map(|value| value.to_bits().rotate_left(1).to_be_bytes())
+            .collect();
 
-        Ok(())
+        self.write_interleaved_bytes(&values)
     }
 
     fn write_referent_array<I>(&mut self, values: I) -> io::Result<()>
@@ -340,21 +277,87 @@ pub trait RbxWriteExt: Write {
     where
         I: Iterator<Item = i64>,
     {
-        let values: Vec<_> = values.collect();
+        let values: Vec<_> = values
+            .map(|value| transform_i64(value).to_be_bytes())
+            .collect();
 
-        for shift in &[56, 48, 40, 32, 24, 16, 8, 0] {
-            for value in values.iter().copied() {
-                let encoded = transform_i64(value) >> shift;
-                self.write_u8(encoded as u8)?;
-            }
-        }
-
-        Ok(())
+        self.write_interleaved_bytes(&values)
     }
 }
 
 impl<W> RbxWriteExt for W where W: Write {}
 
+/// Fails unless `data` is long enough to hold `count` values of `size` bytes
+/// each, so that counts read from a file can't make us allocate far more than
+/// the file holds. Returns `count`.
+pub fn check_count(data: &[u8], count: u32, size: usize) -> io::Result<usize> {
+    let count = count as usize;
+
+    if count.saturating_mul(size) > data.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!(
+                "Expected {} values of {} bytes, but only {} bytes are left",
+                count,
+                size,
+                data.len()
+            ),
+        ));
+    }
+
+    Ok(count)
+}
+
+/// Reads `len` values of `N` bytes each, deinterleaving them in the process.
+fn read_interleaved_values<R: Read + ?Sized, const N: usize>(
+    reader: &mut R,
+    len: usize,
+) -> io::Result<Vec<[u8; N]>> {
+    let mut buffer = vec![0; len * N];
+    reader.read_exact(&mut buffer)?;
+
+    let mut values = vec![[0; N]; len];
+    deinterleave(&buffer, &mut values);
+
+    Ok(values)
+}
+
+/// Splits `buffer`, which holds `output.len()` values of `N` bytes each with
+/// their bytes interleaved, back into the values.
+///
+/// Each pass copies one column of the buffer, a contiguous run of bytes, into
+/// the same position of every value. Walking the buffer in order like this
+/// avoids bounds checks and lets the compiler vectorize the copy, which
+/// jumping between columns for every value doesn't.
+fn deinterleave<const N: usize>(buffer: &[u8], output: &mut [[u8; N]]) {
+    if output.is_empty() {
+        return;
+    }
+
+    for (i, column) in buffer.chunks_exact(output.len()).enumerate() {
+        for (value, byte) in output.iter_mut().zip(column) {
+            value[i] = *byte;
+        }
+    }
+}
+
+/// The inverse of `deinterleave`.
+fn interleave<const N: usize>(values: &[[u8; N]]) -> Vec<u8> {
+    let mut buffer = vec![0; values.len() * N];
+
+    if values.is_empty() {
+        return buffer;
+    }
+
+    for (i, column) in buffer.chunks_exact_mut(values.len()).enumerate() {
+        for (byte, value) in column.iter_mut().zip(values) {
+            *byte = value[i];
+        }
+    }
+
+    buffer
+}
+
 /// Applies the integer transformation generally used in property data in the
 /// Roblox binary format.
 pub fn transform_i32(value: i32) -> i32 {
@@ -507,3 +510,76 @@ fn find_serialized_from_canonical<'a>(
         _ => None,
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn interleaving() {
+        // The example from the format's documentation: `A0 A1 B0 B1 C0 C1` is
+        // stored as `A0 B0 C0 A1 B1 C1`.
+        let values = [[0xA0, 0xA1], [0xB0, 0xB1], [0xC0, 0xC1]];
+        let interleaved = [0xA0, 0xB0, 0xC0, 0xA1, 0xB1, 0xC1];
+
+        assert_eq!(interleave(&values), interleaved);
+
+        let mut output = [[0; 2]; 3];
+        deinterleave(&interleaved, &mut output);
+        assert_eq!(output, values);
+
+        assert!(interleave::<4>(&[]).is_empty());
+        deinterleave::<4>(&[], &mut []);
+    }
+
+    #[test]
+    fn typed_arrays_round_trip() {
+        let ints = [0, 1, -1, i32::MIN, i32::MAX, 12345];
+        let mut buffer = Vec::new();
+        buffer
+            .write_interleaved_i32_array(ints.iter().copied())
+            .unwrap();
+        let mut output = [0; 6];
+        buffer
+            .as_slice()
+            .read_interleaved_i32_array(&mut output)
+            .unwrap();
+        assert_eq!(output, ints);
+
+        let floats = [0.0, -0.0, 1.5, f32::MIN, f32::INFINITY, -3.25];
+        let mut buffer = Vec::new();
+        buffer
+            .write_interleaved_f32_array(floats.iter().copied())
+            .unwrap();
+        let mut output = [0.0; 6];
+        buffer
+            .as_slice()
+            .read_interleaved_f32_array(&mut output)
+            .unwrap();
+        assert_eq!(output.map(f32::to_bits), floats.map(f32::to_bits));
+
+        let longs = [0, -1, i64::MIN, i64::MAX, 1 << 40];
+        let mut buffer = Vec::new();
+        buffer
+            .write_interleaved_i64_array(longs.iter().copied())
+            .unwrap();
+        let mut output = [0; 5];
+        buffer
+            .as_slice()
+            .read_interleaved_i64_array(&mut output)
+            .unwrap();
+        assert_eq!(output, longs);
+    }
+
+    #[test]
+    fn i32_layout() {
+        // 1 and -1 transform to 2 and 1, whose big-endian bytes are
+        // interleaved with each other.
+        let mut buffer = Vec::new();
+        buffer
+            .write_interleaved_i32_array([1, -1].iter().copied())
+            .unwrap();
+
+        assert_eq!(buffer, [0, 0, 0, 0, 0, 0, 2, 1]);
+    }
+}
@@ -1,20 +1,134 @@
-use std::io;
+use std::{fmt, io};
 
 use thiserror::Error;
 
 use crate::types::InvalidTypeError;
 
 /// Represents an error that occurred during deserialization.
-#[derive(Debug, Error)]
-#[error(transparent)]
+///
+/// Errors found while reading chunks say where in the file they were found,
+/// which is included in their message and can be inspected with methods like
+/// [`Error::chunk_name`] and [`Error::property_name`].
+#[derive(Debug)]
 pub struct Error {
     source: Box<InnerError>,
+    location: Option<Box<Location>>,
+}
+
+impl Error {
+    /// Attaches the part of the file that was being read when this error
+    /// happened, unless it's already known.
+    pub(crate) fn with_location(mut self, location: Location) -> Self {
+        if self.location.is_none() {
+            self.location = Some(Box::new(location));
+        }
+
+        self
+    }
+
+    fn location(&self) -> Option<&Location> {
+        self.location.as_deref()
+    }
+
+    /// The name of the chunk that was being read, like `INST` or `PROP`, if
+    /// its header could be read.
+    pub fn chunk_name(&self) -> Option<&str> {
+        self.location()?.chunk_name.as_deref()
+    }
+
+    /// The position of the chunk that was being read among the file's chunks,
+    /// starting from zero.
+    pub fn chunk_index(&self) -> Option<usize> {
+        self.location()?.chunk_index
+    }
+
+    /// The byte offset from the start of the file of the chunk that was being
+    /// read.
+    pub fn chunk_offset(&self) -> Option<u64> {
+        self.location()?.chunk_offset
+    }
+
+    /// The class of the instances the chunk being read was for, if it was an
+    /// `INST` or `PROP` chunk.
+    pub fn class_name(&self) -> Option<&str> {
+        self.location()?.class_name.as_deref()
+    }
+
+    /// The name of the property being read, if the chunk was a `PROP` chunk.
+    pub fn property_name(&self) -> Option<&str> {
+        self.location()?.property_name.as_deref()
+    }
+
+    /// Whether decoding stopped because the callback set with
+    /// [`Deserializer::on_progress`][crate::Deserializer::on_progress] asked
+    /// it to.
+    pub fn is_cancelled(&self) -> bool {
+        matches!(*self.source, InnerError::Cancelled)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, output: &mut fmt::Formatter) -> fmt::Result {
+        write!(output, "{}", self.source)?;
+
+        match self.location() {
+            Some(location) if location.chunk_index.is_some() => {
+                write!(output, " ({})", location)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        // Like #[error(transparent)], the inner error is this error.
+        std::error::Error::source(&*self.source)
+    }
 }
 
 impl From<InnerError> for Error {
     fn from(inner: InnerError) -> Self {
         Self {
             source: Box::new(inner),
+            location: None,
+        }
+    }
+}
+
+/// The part of a file being read when an error happened, as far as it's
+/// known.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Location {
+    pub(crate) chunk_name: Option<String>,
+    pub(crate) chunk_index: Option<usize>,
+    pub(crate) chunk_offset: Option<u64>,
+    pub(crate) class_name: Option<String>,
+    pub(crate) property_name: Option<String>,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, output: &mut fmt::Formatter) -> fmt::Result {
+        write!(output, "in chunk")?;
+
+        if let Some(name) = &self.chunk_name {
+            write!(output, " {}", name)?;
+        }
+
+        if let Some(index) = self.chunk_index {
+            write!(output, " #{}", index)?;
+        }
+
+        if let Some(offset) = self.chunk_offset {
+            write!(output, " at byte {}", offset)?;
+        }
+
+        match (&self.class_name, &self.property_name) {
+            (Some(class_name), Some(property_name)) => {
+                write!(output, ", property {}.{}", class_name, property_name)
+            }
+            (Some(class_name), None) => write!(output, ", class {}", class_name),
+            _ => Ok(()),
         }
     }
 }
@@ -33,6 +147,12 @@ pub(crate) enum InnerError {
     #[error("Unknown file version {version}. Known versions are: 0")]
     UnknownFileVersion { version: u16 },
 
+    #[error("File version {version} is newer than the known versions (0), so it may not have been decoded correctly")]
+    NewerFileVersion { version: u16 },
+
+    #[error("Unknown chunk {chunk_name} couldn't be decoded")]
+    UnknownChunk { chunk_name: String },
+
     #[error("Unknown version {version} for chunk {chunk_name}")]
     UnknownChunkVersion {
         chunk_name: &'static str,
@@ -66,6 +186,12 @@ pub(crate) enum InnerError {
     #[error("File referred to type ID {type_id}, which was not declared")]
     InvalidTypeId { type_id: u32 },
 
+    #[error("File referred to instance {referent} as a parent, which was not declared")]
+    InvalidParent { referent: i32 },
+
+    #[error("Chunk {chunk_name} has a malformed header, so the rest of the file can't be read")]
+    MalformedChunkHeader { chunk_name: String },
+
     #[error("Invalid property data: CFrame property {type_name}.{prop_name} had an invalid rotation ID {id:02x}")]
     BadRotationId {
         type_name: String,
@@ -79,4 +205,41 @@ pub(crate) enum InnerError {
         expected_type_id: u8,
         actual_type_id: u8,
     },
+
+    #[error("Decoding was cancelled")]
+    Cancelled,
+
+    #[error("File header said there were {header} {counted}, but {actual} were declared")]
+    HeaderCountMismatch {
+        counted: &'static str,
+        header: u32,
+        actual: usize,
+    },
+
+    #[error("Type ID {type_id} was declared more than once")]
+    DuplicateTypeId { type_id: u32 },
+
+    #[error("Instance {referent} was declared more than once")]
+    DuplicateReferent { referent: i32 },
+
+    #[error("Chunk header said it was {header_len} bytes long, but it was {actual_len} bytes after decompressing")]
+    ChunkLengthMismatch { header_len: u32, actual_len: usize },
+
+    #[error("Chunk has {count} bytes left over after its contents")]
+    TrailingBytes { count: usize },
+
+    #[error("Property was already given values by an earlier PROP chunk")]
+    DuplicateProperty,
+
+    #[error("PRNT chunk gave a parent to instance {referent}, which was not declared")]
+    UndeclaredInstance { referent: i32 },
+
+    #[error("Instance {referent} was given a parent more than once")]
+    DuplicateParent { referent: i32 },
+
+    #[error("{count} instances were never given a parent, like instance {referent}")]
+    MissingParents { referent: i32, count: usize },
+
+    #[error("Instance {referent} is its own ancestor")]
+    ParentCycle { referent: i32 },
 }
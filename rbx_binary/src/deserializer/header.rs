@@ -14,10 +14,32 @@ pub(crate) struct FileHeader {
 
     /// The total number of instances described by this file.
     pub(crate) num_instances: u32,
+
+    /// The version of the format the file says it's in, which is only ever
+    /// something other than `FILE_VERSION` if newer versions were allowed.
+    pub(crate) version: u16,
 }
 
 impl FileHeader {
-    pub(crate) fn decode<R: Read>(mut source: R) -> Result<Self, InnerError> {
+    /// The length of the header in bytes.
+    pub(crate) const LEN: u64 = 32;
+
+    pub(crate) fn decode<R: Read>(source: R) -> Result<Self, InnerError> {
+        let header = Self::decode_any_version(source)?;
+
+        if header.version != FILE_VERSION {
+            return Err(InnerError::UnknownFileVersion {
+                version: header.version,
+            });
+        }
+
+        Ok(header)
+    }
+
+    /// Like `decode`, but accepts versions of the format newer than the one
+    /// rbx_binary knows about, leaving it to the caller to decide what to do
+    /// with them.
+    pub(crate) fn decode_any_version<R: Read>(mut source: R) -> Result<Self, InnerError> {
         let mut magic_header = [0; 8];
         source.read_exact(&mut magic_header)?;
 
@@ -34,10 +56,6 @@ impl FileHeader {
 
         let version = source.read_le_u16()?;
 
-        if version != FILE_VERSION {
-            return Err(InnerError::UnknownFileVersion { version });
-        }
-
         let num_types = source.read_le_u32()?;
         let num_instances = source.read_le_u32()?;
 
@@ -51,6 +69,7 @@ impl FileHeader {
         Ok(Self {
             num_types,
             num_instances,
+            version,
         })
     }
 }
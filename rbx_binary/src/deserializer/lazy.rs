@@ -0,0 +1,229 @@
+use std::{
+    collections::HashMap,
+    io::{self, Read},
+};
+
+use rbx_dom_weak::{
+    types::{Ref, Variant},
+    WeakDom,
+};
+
+use crate::{chunk::Chunk, core::RbxReadExt};
+
+use super::{
+    error::{Error, InnerError, Location},
+    state::{CountingReader, DeserializerState},
+    Deserializer,
+};
+
+/// A PROP chunk that hasn't been decoded yet.
+struct PendingChunk {
+    /// The name of the property that the chunk's values are decoded into.
+    property_name: String,
+
+    /// Whether the chunk's values are migrated from a deprecated property, in
+    /// which case they don't replace values that are already there.
+    migrated: bool,
+
+    /// The decompressed contents of the chunk.
+    data: Vec<u8>,
+
+    /// Where the chunk was in the file, for errors found while decoding it.
+    location: Location,
+}
+
+/// A Roblox binary model or place whose properties are decoded only when
+/// they're first asked for. Created with [`Deserializer::deserialize_lazy`].
+///
+/// The tree, including the names of every instance, is decoded up front.
+/// Every other property is decoded the first time it's asked for on any
+/// instance of the same class, by decoding the chunk holding that property for
+/// all of those instances at once. Tools that look at only a few properties
+/// skip the cost of decoding the rest.
+///
+/// Files are still read and decompressed in full up front, and `SIGN` chunks
+/// and chunks that rbx_binary doesn't understand are dropped.
+///
+/// ## Example
+/// ```no_run
+/// use std::fs::File;
+/// use std::io::BufReader;
+///
+/// use rbx_binary::Deserializer;
+///
+/// let input = BufReader::new(File::open("Scripts.rbxm")?);
+/// let mut document = Deserializer::new().deserialize_lazy(input)?;
+///
+/// // Only the Source of these scripts is decoded, and none of their other
+/// // properties.
+/// let roots = document.tree().root().children().to_vec();
+/// for referent in roots {
+///     if let Some(source) = document.property(referent, "Source")? {
+///         println!("{:?}", source);
+///     }
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct LazyDocument<'a> {
+    state: DeserializerState<'a, io::Empty>,
+
+    /// The instances in the file, with any properties decoded so far.
+    tree: WeakDom,
+
+    /// The type ID in the file of each instance, which is used to find the
+    /// chunks holding its properties.
+    type_ids: HashMap<Ref, u32>,
+
+    /// The PROP chunks that haven't been decoded yet, by type ID, in the order
+    /// they appeared in the file.
+    pending: HashMap<u32, Vec<PendingChunk>>,
+}
+
+impl<'a> LazyDocument<'a> {
+    pub(super) fn decode<R: Read>(
+        deserializer: &Deserializer<'a>,
+        mut reader: R,
+    ) -> Result<Self, Error> {
+        let header = deserializer.read_header(&mut reader)?;
+        let mut state = DeserializerState::from_header(deserializer, header, io::empty());
+
+        let mut type_ids = HashMap::new();
+        let mut pending = HashMap::new();
+
+        loop {
+            let finished = read_chunk(&mut state, &mut reader, &mut type_ids, &mut pending)
+                .map_err(|err| state.error(err))?;
+
+            if finished {
+                break;
+            }
+        }
+
+        let tree = state.take_tree();
+
+        Ok(LazyDocument {
+            state,
+            tree,
+            type_ids,
+            pending,
+        })
+    }
+
+    /// The instances contained in the file, which are children of the tree's
+    /// root. Only the properties that have been decoded so far are present.
+    pub fn tree(&self) -> &WeakDom {
+        &self.tree
+    }
+
+    /// Returns the value of the given property on the given instance, decoding
+    /// it first if it hasn't been yet. Properties are named the same way they
+    /// are in a `WeakDom` from [`Deserializer::deserialize`].
+    ///
+    /// Returns `None` if the instance doesn't exist or doesn't have the
+    /// property.
+    pub fn property(&mut self, referent: Ref, name: &str) -> Result<Option<&Variant>, Error> {
+        if let Some(&type_id) = self.type_ids.get(&referent) {
+            self.decode_pending(type_id, |chunk| chunk.property_name == name)?;
+        }
+
+        Ok(self
+            .tree
+            .get_by_ref(referent)
+            .and_then(|instance| instance.properties.get(name)))
+    }
+
+    /// Decodes every property that hasn't been decoded yet and returns the
+    /// finished tree.
+    pub fn into_tree(mut self) -> Result<WeakDom, Error> {
+        let type_ids: Vec<u32> = self.pending.keys().copied().collect();
+
+        for type_id in type_ids {
+            self.decode_pending(type_id, |_| true)?;
+        }
+
+        Ok(self.tree)
+    }
+
+    /// Decodes the pending chunks of the given type that match `filter` into
+    /// the tree.
+    fn decode_pending(
+        &mut self,
+        type_id: u32,
+        filter: impl Fn(&PendingChunk) -> bool,
+    ) -> Result<(), Error> {
+        let chunks = match self.pending.get_mut(&type_id) {
+            Some(chunks) => chunks,
+            None => return Ok(()),
+        };
+
+        let (to_decode, rest): (Vec<_>, Vec<_>) = chunks.drain(..).partition(|chunk| filter(chunk));
+        *chunks = rest;
+
+        for chunk in to_decode {
+            self.state.set_location(chunk.location);
+            self.state
+                .apply_prop_chunk(&mut self.tree, type_id, &chunk.data, chunk.migrated)
+                .map_err(|err| self.state.error(err))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads the next chunk from `reader`, decoding it into `state` if it's part of
+/// the tree and setting it aside in `pending` otherwise. Returns whether it was
+/// the END chunk.
+fn read_chunk<R: Read>(
+    state: &mut DeserializerState<io::Empty>,
+    reader: R,
+    type_ids: &mut HashMap<Ref, u32>,
+    pending: &mut HashMap<u32, Vec<PendingChunk>>,
+) -> Result<bool, InnerError> {
+    state.start_chunk();
+
+    let mut input = CountingReader::new(reader);
+    let chunk = Chunk::decode(&mut input);
+    state.finish_chunk(input.count(), chunk.as_ref().ok().map(|chunk| &chunk.name));
+    let chunk = chunk?;
+    let mut finished = false;
+
+    match &chunk.name {
+        b"META" => state.decode_meta_chunk(&chunk.data)?,
+        b"SSTR" => state.decode_sstr_chunk(&chunk.data)?,
+        b"INST" => {
+            state.decode_inst_chunk(&chunk.data)?;
+
+            let type_id = chunk.data.as_slice().read_le_u32()?;
+            if let Some((_, referents)) = state.instances_of_type(type_id) {
+                type_ids.extend(referents.into_iter().map(|referent| (referent, type_id)));
+            }
+        }
+        b"PROP" => {
+            let (type_id, prop_name) = state.read_prop_chunk_header(&chunk.data)?;
+
+            // Names were decoded into the tree already.
+            let decoded = match prop_name.as_str() {
+                "Name" => None,
+                _ => state.decoded_property_name(type_id, &prop_name),
+            };
+
+            if let Some((property_name, migrated)) = decoded {
+                pending.entry(type_id).or_default().push(PendingChunk {
+                    property_name,
+                    migrated,
+                    data: chunk.data,
+                    location: state.location().clone(),
+                });
+            }
+        }
+        b"PRNT" => state.decode_prnt_chunk(&chunk.data)?,
+        b"END\0" => {
+            state.decode_end_chunk(&chunk.data)?;
+            finished = true;
+        }
+        _ => state.skip_unknown_chunk(&chunk.name),
+    }
+
+    state.report_progress()?;
+    Ok(finished)
+}
@@ -1,17 +1,41 @@
 mod error;
 mod header;
+mod lazy;
+mod patch;
+mod raw;
+mod slice;
 mod state;
+mod stream;
+mod validate;
 
-use std::{io::Read, str};
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, Read},
+    ops::ControlFlow,
+    sync::Arc,
+};
 
-use rbx_dom_weak::WeakDom;
+use rbx_dom_weak::{types::Ref, WeakDom};
 use rbx_reflection::ReflectionDatabase;
 
-use self::state::DeserializerState;
+use crate::{
+    chunk::{Chunk, UnknownChunk},
+    progress::{Progress, ProgressCallback},
+};
+
+use self::{error::InnerError, state::DeserializerState};
 
 pub(crate) use self::header::FileHeader;
 
-pub use self::error::Error;
+pub use self::{
+    error::Error,
+    lazy::LazyDocument,
+    patch::FilePatch,
+    raw::{ChunkReader, RawChunk},
+    slice::{SliceBytes, SliceDecoder, SliceEvent, SliceValue},
+    stream::{StreamDecoder, StreamEvent},
+    validate::ValidationReport,
+};
 
 /// A configurable deserializer for Roblox binary models and places.
 ///
@@ -38,8 +62,29 @@ pub use self::error::Error;
 ///
 /// # Ok::<(), Box<dyn std::error::Error>>(())
 /// ```
+///
+/// ## Memory use
+/// Values in a `WeakDom` own their contents, so decoding into one always
+/// copies `BinaryString` and `SharedString` payloads out of the file. Each
+/// distinct `SharedString` is only kept once, no matter how many properties
+/// use it.
+///
+/// For files that are already in memory, [`Deserializer::stream_slice`] hands
+/// payloads out as [`SliceBytes`] instead. Payloads in uncompressed chunks
+/// point straight into the file, and each compressed chunk is decompressed
+/// once into a buffer its payloads share. Roblox compresses nearly every
+/// chunk, so this saves the most on files written with
+/// [`CompressionType::None`][crate::CompressionType::None].
+///
+/// To keep less of a big file in memory, [`Deserializer::only_classes`] skips
+/// the properties of classes that aren't needed,
+/// [`Deserializer::deserialize_lazy`] only decodes properties when they're
+/// asked for, and [`Deserializer::stream`] never builds a tree at all.
 pub struct Deserializer<'a> {
     database: Option<&'a ReflectionDatabase<'a>>,
+    class_filter: Option<HashSet<String>>,
+    progress_callback: Option<ProgressCallback<'a>>,
+    allow_newer_versions: bool,
 }
 
 impl<'a> Deserializer<'a> {
@@ -47,38 +92,318 @@ impl<'a> Deserializer<'a> {
     pub fn new() -> Self {
         Self {
             database: Some(rbx_reflection_database::get()),
+            class_filter: None,
+            progress_callback: None,
+            allow_newer_versions: false,
+        }
+    }
+
+    /// Only decode the properties of instances of the given classes, or of
+    /// their subclasses. Instances of other classes are still decoded with
+    /// their names and their places in the tree, but their other properties
+    /// are skipped, usually without even being decompressed. This makes
+    /// pulling a few classes out of a big file much faster.
+    ///
+    /// By default, the properties of every instance are decoded.
+    pub fn only_classes<I, S>(self, classes: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        Self {
+            class_filter: Some(classes.into_iter().map(Into::into).collect()),
+            ..self
+        }
+    }
+
+    /// Calls `callback` after each chunk of the file is decoded, with how many
+    /// chunks and instances have been read so far, so that long decodes can
+    /// report their progress. If `callback` returns `ControlFlow::Break`,
+    /// decoding stops and fails with an error whose
+    /// [`Error::is_cancelled`] is `true`.
+    ///
+    /// The properties decoded later by a [`LazyDocument`] aren't reported.
+    pub fn on_progress<F>(self, callback: F) -> Self
+    where
+        F: Fn(Progress) -> ControlFlow<()> + Send + Sync + 'a,
+    {
+        Self {
+            progress_callback: Some(Arc::new(callback)),
+            ..self
+        }
+    }
+
+    /// Try to decode files that say they're in a newer version of the format
+    /// than rbx_binary knows about, instead of failing right away. The chunks
+    /// rbx_binary understands are decoded as usual, on the assumption that
+    /// they haven't changed, and chunks it doesn't understand are kept.
+    ///
+    /// Decoding a newer file can still fail, or produce a tree that's missing
+    /// things, if the format changed in a way that matters. The version and
+    /// each unknown chunk are logged as warnings, and are also reported in
+    /// [`DecodedDocument::warnings`] by the methods that return one, so that
+    /// tools can tell people to update them.
+    ///
+    /// This applies to every method but [`Deserializer::patch`] and
+    /// [`Deserializer::validate`], which only accept files in a known version.
+    /// By default, files in newer versions fail to decode.
+    pub fn allow_newer_versions(self, allow: bool) -> Self {
+        Self {
+            allow_newer_versions: allow,
+            ..self
         }
     }
 
     /// Deserialize a Roblox binary model or place from the given stream using
     /// this deserializer.
     pub fn deserialize<R: Read>(&self, reader: R) -> Result<WeakDom, Error> {
+        self.deserialize_document(reader)
+            .map(|document| document.tree)
+    }
+
+    /// Deserialize a Roblox binary model or place from the given stream using
+    /// this deserializer, keeping the parts of the file that aren't part of
+    /// its instances.
+    pub fn deserialize_document<R: Read>(&self, reader: R) -> Result<DecodedDocument, Error> {
         profiling::scope!("rbx_binary::deserialize");
 
         let mut deserializer = DeserializerState::new(self, reader)?;
 
         loop {
-            let chunk = deserializer.next_chunk()?;
-
-            match &chunk.name {
-                b"META" => deserializer.decode_meta_chunk(&chunk.data)?,
-                b"SSTR" => deserializer.decode_sstr_chunk(&chunk.data)?,
-                b"INST" => deserializer.decode_inst_chunk(&chunk.data)?,
-                b"PROP" => deserializer.decode_prop_chunk(&chunk.data)?,
-                b"PRNT" => deserializer.decode_prnt_chunk(&chunk.data)?,
-                b"END\0" => {
-                    deserializer.decode_end_chunk(&chunk.data)?;
+            let finished = deserializer
+                .next_chunk()
+                .and_then(|chunk| decode_chunk(&mut deserializer, chunk))
+                .map_err(|err| deserializer.error(err))?;
+
+            if finished {
+                break;
+            }
+        }
+
+        Ok(deserializer.finish())
+    }
+
+    /// Deserialize a Roblox binary model or place from the given stream using
+    /// this deserializer, leaving everything but its tree and the names of its
+    /// instances to be decoded when it's asked for.
+    pub fn deserialize_lazy<R: Read>(&self, reader: R) -> Result<LazyDocument<'a>, Error> {
+        profiling::scope!("rbx_binary::deserialize_lazy");
+
+        LazyDocument::decode(self, reader)
+    }
+
+    /// Deserialize as much of a damaged Roblox binary model or place as
+    /// possible, like one left behind by a crash, from the given stream using
+    /// this deserializer.
+    ///
+    /// Chunks that can't be decoded are skipped, and decoding stops early if
+    /// the file is truncated or a chunk header is malformed. Instances whose
+    /// parents are unknown are put at the top of the tree. Everything that went
+    /// wrong is returned alongside the instances that could be recovered.
+    ///
+    /// This only returns an error if the file header can't be read.
+    pub fn deserialize_recovering<R: Read>(&self, reader: R) -> Result<RecoveredDocument, Error> {
+        profiling::scope!("rbx_binary::deserialize_recovering");
+
+        let mut deserializer = DeserializerState::new(self, reader)?;
+        let mut errors = Vec::new();
+
+        loop {
+            let raw_chunk = match deserializer.next_raw_chunk() {
+                Ok(raw_chunk) => raw_chunk,
+                Err(err) => {
+                    errors.push(deserializer.error(err));
                     break;
                 }
-                _ => match str::from_utf8(&chunk.name) {
-                    Ok(name) => log::info!("Unknown binary chunk name {}", name),
-                    Err(_) => log::info!("Unknown binary chunk name {:?}", chunk.name),
-                },
+            };
+
+            // A header like this probably means that we've lost track of where
+            // chunks start, so nothing after it can be trusted.
+            if raw_chunk.reserved != 0 {
+                errors.push(deserializer.error(InnerError::MalformedChunkHeader {
+                    chunk_name: String::from_utf8_lossy(&raw_chunk.name).into_owned(),
+                }));
+                break;
+            }
+
+            let data = match raw_chunk.decompressed_inner() {
+                Ok(data) => data.into_owned(),
+                Err(err) => {
+                    errors.push(deserializer.error(err));
+                    continue;
+                }
+            };
+
+            let chunk = Chunk {
+                name: raw_chunk.name,
+                data,
+            };
+
+            match decode_chunk(&mut deserializer, chunk) {
+                Ok(true) => break,
+                Ok(false) => {}
+                Err(err @ InnerError::Cancelled) => return Err(deserializer.error(err)),
+                Err(err) => errors.push(deserializer.error(err)),
             }
         }
 
-        Ok(deserializer.finish())
+        Ok(RecoveredDocument {
+            document: deserializer.finish_recovering(),
+            errors,
+        })
     }
+
+    /// Read a Roblox binary model or place from the given stream to be
+    /// changed and written out again with
+    /// [`Serializer::serialize_patch`][crate::Serializer::serialize_patch],
+    /// without decoding any more of it than is needed.
+    ///
+    /// Every class is read, even if [`Deserializer::only_classes`] was used,
+    /// since the patched file has to keep all of them.
+    pub fn patch<R: Read>(&self, reader: R) -> Result<FilePatch<'a>, Error> {
+        profiling::scope!("rbx_binary::patch");
+
+        let deserializer = Deserializer {
+            database: self.database,
+            class_filter: None,
+            progress_callback: self.progress_callback.clone(),
+            allow_newer_versions: false,
+        };
+
+        FilePatch::read(&deserializer, reader)
+    }
+
+    /// Start decoding a Roblox binary model or place from the given stream as
+    /// a series of events, without building a `WeakDom`. The header of the
+    /// file is read right away.
+    pub fn stream<R: Read>(&self, reader: R) -> Result<StreamDecoder<'a, R>, Error> {
+        let state = DeserializerState::new(self, reader)?;
+        Ok(StreamDecoder::new(state))
+    }
+
+    /// Start decoding a Roblox binary model or place that's already in memory
+    /// as a series of events, like [`Deserializer::stream`] does, but without
+    /// copying `BinaryString` and `SharedString` values out of it. The header
+    /// of the file is read right away.
+    pub fn stream_slice<'b>(&self, input: &'b [u8]) -> Result<SliceDecoder<'a, 'b>, Error> {
+        let mut rest = input;
+        let header = self.read_header(&mut rest)?;
+        let state = DeserializerState::from_header(self, header, io::empty());
+
+        Ok(SliceDecoder::new(state, input, input.len() - rest.len()))
+    }
+
+    /// Check a Roblox binary model or place from the given stream for
+    /// problems, without building a tree. This is meant for working out why
+    /// Roblox Studio refuses to open a file.
+    ///
+    /// Chunk lengths and reserved fields are checked, along with the contents
+    /// of `INST`, `PROP`, and `PRNT` chunks: that types and instances are only
+    /// declared once, that properties are only given values once and have no
+    /// bytes left over, and that every instance is given exactly one parent
+    /// that exists, without any cycles. The counts in the file header are
+    /// checked against what the file declares.
+    ///
+    /// Every class is checked, even if [`Deserializer::only_classes`] was
+    /// used. This only returns an error if the file header can't be read or
+    /// [`Deserializer::on_progress`] cancels the check.
+    pub fn validate<R: Read>(&self, reader: R) -> Result<ValidationReport, Error> {
+        profiling::scope!("rbx_binary::validate");
+
+        let deserializer = Deserializer {
+            database: self.database,
+            class_filter: None,
+            progress_callback: self.progress_callback.clone(),
+            allow_newer_versions: false,
+        };
+
+        validate::validate(&deserializer, reader)
+    }
+
+    /// Reads the header of a file, which may be in a newer version of the
+    /// format if this deserializer allows it.
+    fn read_header<R: Read>(&self, reader: R) -> Result<FileHeader, InnerError> {
+        if self.allow_newer_versions {
+            FileHeader::decode_any_version(reader)
+        } else {
+            FileHeader::decode(reader)
+        }
+    }
+}
+
+/// A Roblox binary model or place, along with the parts of the file that
+/// aren't part of its instances. Returned by
+/// [`Deserializer::deserialize_document`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct DecodedDocument {
+    /// The instances contained in the file, which are children of the tree's
+    /// root.
+    pub tree: WeakDom,
+
+    /// The entries in the file's `META` chunk, like `ExplicitAutoJoints`. They
+    /// can be written back out with
+    /// [`Serializer::metadata`][crate::Serializer::metadata].
+    pub metadata: HashMap<String, String>,
+
+    /// The referent that each instance in the tree had in the file. These can
+    /// be kept when the tree is written back out with
+    /// [`ReferentStrategy::Preserve`][crate::ReferentStrategy::Preserve].
+    pub referents: HashMap<Ref, i32>,
+
+    /// The contents of the file's `SIGN` chunk, if it had one. Roblox uses
+    /// this to sign files containing precompiled bytecode, and it can be
+    /// written back out with [`Serializer::signature`][crate::Serializer::signature].
+    pub signature: Option<Vec<u8>>,
+
+    /// Chunks that rbx_binary doesn't understand, in the order they appeared
+    /// in the file. They can be written back out with
+    /// [`Serializer::unknown_chunks`][crate::Serializer::unknown_chunks].
+    pub unknown_chunks: Vec<UnknownChunk>,
+
+    /// Things that might have gone wrong with decoding a file in a newer
+    /// version of the format, which is only possible with
+    /// [`Deserializer::allow_newer_versions`]. This is empty for files in a
+    /// known version.
+    pub warnings: Vec<Error>,
+}
+
+/// Decodes a chunk into the deserializer's state and reports progress,
+/// returning whether it was the END chunk.
+fn decode_chunk<R: Read>(
+    deserializer: &mut DeserializerState<R>,
+    chunk: Chunk,
+) -> Result<bool, InnerError> {
+    let mut finished = false;
+
+    match &chunk.name {
+        b"META" => deserializer.decode_meta_chunk(&chunk.data)?,
+        b"SSTR" => deserializer.decode_sstr_chunk(&chunk.data)?,
+        b"INST" => deserializer.decode_inst_chunk(&chunk.data)?,
+        b"PROP" => deserializer.decode_prop_chunk(&chunk.data)?,
+        b"PRNT" => deserializer.decode_prnt_chunk(&chunk.data)?,
+        b"SIGN" => deserializer.decode_sign_chunk(chunk.data),
+        b"END\0" => {
+            deserializer.decode_end_chunk(&chunk.data)?;
+            finished = true;
+        }
+        _ => deserializer.decode_unknown_chunk(chunk),
+    }
+
+    deserializer.report_progress()?;
+    Ok(finished)
+}
+
+/// A Roblox binary model or place recovered from a damaged file, returned by
+/// [`Deserializer::deserialize_recovering`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct RecoveredDocument {
+    /// Everything that could be recovered from the file.
+    pub document: DecodedDocument,
+
+    /// The problems found in the file, in the order they were found.
+    pub errors: Vec<Error>,
 }
 
 impl<'a> Default for Deserializer<'a> {
@@ -0,0 +1,423 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    io::{self, Read},
+};
+
+use rbx_dom_weak::{types::Ref, InstanceBuilder, WeakDom};
+
+use crate::core::{check_count, RbxReadExt};
+
+use super::{
+    error::{Error, InnerError, Location},
+    header::FileHeader,
+    raw::{read_raw_chunk, RawChunk},
+    state::{read_prnt_chunk, CountingReader, DeserializerState},
+    Deserializer,
+};
+
+/// A chunk from the file being patched, kept exactly as it was read so that it
+/// can be copied into the patched file.
+struct StoredChunk {
+    raw: RawChunk,
+
+    /// The type ID that the chunk is for, if it's an INST or PROP chunk.
+    type_id: Option<u32>,
+
+    /// The name of the property the chunk holds, if it's a PROP chunk.
+    prop_name: Option<String>,
+
+    /// Where the chunk was in the file, for errors found while decoding it.
+    location: Location,
+}
+
+/// A type of instance declared by an INST chunk in the file being patched.
+struct StoredType {
+    class: String,
+    referents: Vec<i32>,
+}
+
+/// A Roblox binary model or place that's being changed without decoding and
+/// encoding all of it again. Created with [`Deserializer::patch`], and
+/// written out with [`Serializer::serialize_patch`][crate::Serializer::serialize_patch].
+///
+/// Only the tree and the names of its instances are decoded up front. New
+/// instances can be inserted anywhere in the tree, and instances can be
+/// removed or replaced. When the patched file is written, every chunk that
+/// isn't affected is copied over exactly as it was, without being
+/// decompressed or compressed again, so writing a small change to a big place
+/// is much faster than decoding and encoding the whole thing.
+///
+/// Inserted instances are written in chunks of their own. Removing an
+/// instance that was in the file means the chunks of its class have to be
+/// rewritten, so every property of that class is decoded when it's removed.
+/// The other instances of the class keep their referents, so `Ref`
+/// properties pointing at them from elsewhere in the file still work.
+///
+/// Some things don't survive being patched:
+/// - `SIGN` chunks are dropped, since the file they signed has changed.
+/// - `Ref` properties that pointed to removed instances become null.
+/// - Properties of rewritten classes that rbx_binary can't decode are lost.
+///
+/// ## Example
+/// ```no_run
+/// use std::fs::{self, File};
+/// use std::io::BufWriter;
+///
+/// use rbx_binary::{Deserializer, Serializer};
+/// use rbx_dom_weak::InstanceBuilder;
+///
+/// let input = fs::read("Place.rbxl")?;
+/// let mut patch = Deserializer::new().patch(input.as_slice())?;
+///
+/// let workspace = patch
+///     .tree()
+///     .root()
+///     .children()
+///     .iter()
+///     .copied()
+///     .find(|&referent| patch.tree().get_by_ref(referent).unwrap().class == "Workspace")
+///     .unwrap();
+///
+/// patch.insert(workspace, InstanceBuilder::new("Part").with_name("NewPart"));
+///
+/// let output = BufWriter::new(File::create("Place.rbxl")?);
+/// Serializer::new().serialize_patch(output, &patch)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct FilePatch<'a> {
+    state: DeserializerState<'a, io::Empty>,
+
+    /// The instances in the file and every instance inserted since. Only the
+    /// properties of rewritten classes are decoded.
+    tree: WeakDom,
+
+    /// Every chunk in the file apart from its END chunk, in order.
+    chunks: Vec<StoredChunk>,
+
+    /// The types declared in the file, by type ID.
+    types: BTreeMap<u32, StoredType>,
+
+    /// Every pair of instance and parent referents from the file's PRNT
+    /// chunks.
+    parents: Vec<(i32, i32)>,
+
+    /// The referent in the file of every instance that was in it, including
+    /// ones that have been removed from the tree since.
+    file_referents: HashMap<Ref, i32>,
+
+    /// The type ID in the file of every instance that was in it.
+    type_ids: HashMap<Ref, u32>,
+
+    /// The types whose chunks have to be rewritten, because some of their
+    /// instances were removed.
+    rewritten_types: BTreeSet<u32>,
+}
+
+impl<'a> FilePatch<'a> {
+    pub(super) fn read<R: Read>(
+        deserializer: &Deserializer<'a>,
+        mut reader: R,
+    ) -> Result<Self, Error> {
+        let header = FileHeader::decode(&mut reader)?;
+        let state = DeserializerState::from_header(deserializer, header, io::empty());
+
+        let mut patch = FilePatch {
+            state,
+            tree: WeakDom::new(InstanceBuilder::new("DataModel")),
+            chunks: Vec::new(),
+            types: BTreeMap::new(),
+            parents: Vec::new(),
+            file_referents: HashMap::new(),
+            type_ids: HashMap::new(),
+            rewritten_types: BTreeSet::new(),
+        };
+
+        loop {
+            let finished = patch
+                .read_chunk(&mut reader)
+                .map_err(|err| patch.state.error(err))?;
+
+            if finished {
+                break;
+            }
+        }
+
+        patch.tree = patch.state.take_tree();
+
+        Ok(patch)
+    }
+
+    /// Reads the next chunk from `reader`, decoding as much of it as is needed
+    /// for the tree and keeping it to be written out again. Returns whether it
+    /// was the END chunk.
+    fn read_chunk<R: Read>(&mut self, reader: R) -> Result<bool, InnerError> {
+        self.state.start_chunk();
+
+        let mut input = CountingReader::new(reader);
+        let raw = read_raw_chunk(&mut input);
+        self.state
+            .finish_chunk(input.count(), raw.as_ref().ok().map(|raw| &raw.name));
+        let raw = raw?;
+
+        let data = raw.decompressed_inner()?;
+        let mut type_id = None;
+        let mut prop_name = None;
+
+        match &raw.name {
+            b"META" => self.state.decode_meta_chunk(&data)?,
+            b"SSTR" => self.state.decode_sstr_chunk(&data)?,
+            b"INST" => {
+                self.state.decode_inst_chunk(&data)?;
+
+                let mut contents = &*data;
+                let id = contents.read_le_u32()?;
+                let class = contents.read_string()?;
+                contents.read_u8()?;
+                let number_instances = contents.read_le_u32()?;
+
+                let mut referents = vec![0; check_count(contents, number_instances, 4)?];
+                contents.read_referent_array(&mut referents)?;
+
+                if let Some((_, refs)) = self.state.instances_of_type(id) {
+                    for (instance_ref, &referent) in refs.into_iter().zip(&referents) {
+                        self.file_referents.insert(instance_ref, referent);
+                        self.type_ids.insert(instance_ref, id);
+                    }
+                }
+
+                self.types.insert(id, StoredType { class, referents });
+                type_id = Some(id);
+            }
+            b"PROP" => {
+                let (id, name) = self.state.read_prop_chunk_header(&data)?;
+
+                type_id = Some(id);
+                prop_name = Some(name);
+            }
+            b"PRNT" => {
+                self.state.decode_prnt_chunk(&data)?;
+                self.parents.extend(read_prnt_chunk(&data)?);
+            }
+            b"END\0" => {
+                self.state.decode_end_chunk(&data)?;
+                self.state.report_progress()?;
+                return Ok(true);
+            }
+            _ => {}
+        }
+
+        drop(data);
+
+        self.chunks.push(StoredChunk {
+            raw,
+            type_id,
+            prop_name,
+            location: self.state.location().clone(),
+        });
+
+        self.state.report_progress()?;
+        Ok(false)
+    }
+
+    /// The instances in the file, which are children of the tree's root,
+    /// along with any that have been inserted. Properties other than names
+    /// are only present for classes that have had instances removed.
+    pub fn tree(&self) -> &WeakDom {
+        &self.tree
+    }
+
+    /// Inserts a new instance into the tree with the given parent, which can
+    /// be the root of the tree to put it at the top of the file.
+    ///
+    /// ## Panics
+    /// Panics if `parent` does not refer to an instance in the tree.
+    pub fn insert(&mut self, parent: Ref, builder: InstanceBuilder) -> Ref {
+        self.tree.insert(parent, builder)
+    }
+
+    /// Removes the given instance and all of its descendants.
+    ///
+    /// If any of them were in the file, the rest of the properties of their
+    /// classes are decoded, which is where errors come from.
+    ///
+    /// ## Panics
+    /// Panics if `referent` does not refer to an instance in the tree, or
+    /// refers to the root of the tree.
+    pub fn remove(&mut self, referent: Ref) -> Result<(), Error> {
+        if referent == self.tree.root_ref() {
+            panic!("cannot remove the root instance of a FilePatch");
+        }
+
+        let mut to_visit = vec![referent];
+        let mut new_types = BTreeSet::new();
+
+        while let Some(referent) = to_visit.pop() {
+            let instance = self
+                .tree
+                .get_by_ref(referent)
+                .unwrap_or_else(|| panic!("cannot remove an instance that does not exist"));
+
+            if let Some(&type_id) = self.type_ids.get(&referent) {
+                if !self.rewritten_types.contains(&type_id) {
+                    new_types.insert(type_id);
+                }
+            }
+
+            to_visit.extend(instance.children());
+        }
+
+        for type_id in new_types {
+            self.decode_type(type_id)?;
+            self.rewritten_types.insert(type_id);
+        }
+
+        self.tree.destroy(referent);
+
+        Ok(())
+    }
+
+    /// Replaces the given instance and all of its descendants with a new
+    /// instance, which is given the same parent. Returns the new instance's
+    /// referent.
+    ///
+    /// ## Panics
+    /// Panics if `referent` does not refer to an instance in the tree, or
+    /// refers to the root of the tree.
+    pub fn replace(&mut self, referent: Ref, builder: InstanceBuilder) -> Result<Ref, Error> {
+        let parent = self
+            .tree
+            .get_by_ref(referent)
+            .unwrap_or_else(|| panic!("cannot replace an instance that does not exist"))
+            .parent();
+
+        self.remove(referent)?;
+
+        Ok(self.tree.insert(parent, builder))
+    }
+
+    /// Decodes every property of the given type into the tree.
+    fn decode_type(&mut self, type_id: u32) -> Result<(), Error> {
+        for chunk in &self.chunks {
+            let prop_name = match &chunk.prop_name {
+                Some(prop_name) if chunk.type_id == Some(type_id) => prop_name,
+                _ => continue,
+            };
+
+            // Names are already in the tree.
+            if prop_name == "Name" {
+                continue;
+            }
+
+            let migrated = match self.state.decoded_property_name(type_id, prop_name) {
+                Some((_, migrated)) => migrated,
+                None => continue,
+            };
+
+            self.state.set_location(chunk.location.clone());
+
+            let data = chunk
+                .raw
+                .decompressed_inner()
+                .map_err(|err| self.state.error(err))?;
+            self.state
+                .apply_prop_chunk(&mut self.tree, type_id, &data, migrated)
+                .map_err(|err| self.state.error(err))?;
+        }
+
+        Ok(())
+    }
+
+    /// The chunks with the given name that can be copied into the patched
+    /// file as-is, in the order they were in the file.
+    pub(crate) fn kept_chunks<'p>(
+        &'p self,
+        name: &'p [u8; 4],
+    ) -> impl Iterator<Item = &'p RawChunk> + 'p {
+        self.chunks
+            .iter()
+            .filter(move |chunk| &chunk.raw.name == name)
+            .filter(move |chunk| match chunk.type_id {
+                Some(type_id) => !self.rewritten_types.contains(&type_id),
+                None => true,
+            })
+            .map(|chunk| &chunk.raw)
+    }
+
+    /// The chunks that aren't part of the tree, which are copied into the
+    /// patched file after it. `SIGN` chunks are left out.
+    pub(crate) fn other_chunks(&self) -> impl Iterator<Item = &RawChunk> {
+        self.chunks.iter().map(|chunk| &chunk.raw).filter(|raw| {
+            !matches!(
+                &raw.name,
+                b"META" | b"SSTR" | b"INST" | b"PROP" | b"PRNT" | b"SIGN"
+            )
+        })
+    }
+
+    /// The type ID that's free to be given to a new type.
+    pub(crate) fn next_type_id(&self) -> u32 {
+        self.types
+            .keys()
+            .next_back()
+            .map_or(0, |&type_id| type_id.saturating_add(1))
+    }
+
+    /// The number of SharedStrings in the file's SSTR chunks.
+    pub(crate) fn shared_string_count(&self) -> u32 {
+        self.state.shared_string_count() as u32
+    }
+
+    /// The IDs and class names of the types whose chunks have to be rewritten.
+    pub(crate) fn rewritten_types(&self) -> impl Iterator<Item = (u32, &str)> {
+        self.rewritten_types
+            .iter()
+            .map(move |&type_id| (type_id, self.types[&type_id].class.as_str()))
+    }
+
+    /// The referents of every instance that was in the file, including ones
+    /// that have been removed.
+    pub(crate) fn file_referents(&self) -> &HashMap<Ref, i32> {
+        &self.file_referents
+    }
+
+    /// Whether the given instance was in the file, and has to be written
+    /// again because its class is being rewritten.
+    pub(crate) fn is_rewritten(&self, referent: Ref) -> bool {
+        match self.type_ids.get(&referent) {
+            Some(type_id) => self.rewritten_types.contains(type_id),
+            None => false,
+        }
+    }
+
+    /// The number of instances from the file whose chunks are copied as-is.
+    pub(crate) fn kept_instance_count(&self) -> usize {
+        self.types
+            .iter()
+            .filter(|(type_id, _)| !self.rewritten_types.contains(type_id))
+            .map(|(_, stored)| stored.referents.len())
+            .sum()
+    }
+
+    /// The file's parent relationships for the instances whose chunks are
+    /// copied as-is, or `None` if nothing is being rewritten and the PRNT
+    /// chunks can be copied as-is too.
+    pub(crate) fn kept_parents(&self) -> Option<Vec<(i32, i32)>> {
+        if self.rewritten_types.is_empty() {
+            return None;
+        }
+
+        let rewritten: HashSet<i32> = self
+            .rewritten_types
+            .iter()
+            .flat_map(|type_id| self.types[type_id].referents.iter().copied())
+            .collect();
+
+        Some(
+            self.parents
+                .iter()
+                .copied()
+                .filter(|(referent, _)| !rewritten.contains(referent))
+                .collect(),
+        )
+    }
+}
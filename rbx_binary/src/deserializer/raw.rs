@@ -0,0 +1,195 @@
+use std::{
+    borrow::Cow,
+    io::{self, Read, Write},
+};
+
+use crate::{
+    chunk::{decompress, peek_contents, read_chunk_header, ChunkHeader},
+    core::RbxWriteExt,
+};
+
+use super::{error::InnerError, header::FileHeader, Error};
+
+/// A chunk read by a [`ChunkReader`], exactly as it appears in the file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RawChunk {
+    /// The name of the chunk, like `INST` or `PROP`. Names shorter than four
+    /// bytes are padded with zeros.
+    pub name: [u8; 4],
+
+    /// The length of the chunk's compressed contents, or zero if the chunk
+    /// isn't compressed.
+    pub compressed_len: u32,
+
+    /// The length of the chunk's contents once they're decompressed.
+    pub len: u32,
+
+    /// The four bytes in the chunk's header after its lengths, which should
+    /// always be zero.
+    pub reserved: u32,
+
+    /// The contents of the chunk as they're stored in the file, which are
+    /// compressed if `compressed_len` isn't zero.
+    pub data: Vec<u8>,
+}
+
+impl RawChunk {
+    /// Whether the chunk's contents are compressed.
+    pub fn is_compressed(&self) -> bool {
+        self.compressed_len != 0
+    }
+
+    /// The contents of the chunk, decompressed if they need to be.
+    pub fn decompressed(&self) -> Result<Cow<'_, [u8]>, Error> {
+        Ok(self.decompressed_inner()?)
+    }
+
+    pub(super) fn decompressed_inner(&self) -> Result<Cow<'_, [u8]>, InnerError> {
+        if !self.is_compressed() {
+            return Ok(Cow::Borrowed(&self.data));
+        }
+
+        Ok(Cow::Owned(decompress(&self.header(), &self.data)?))
+    }
+
+    /// Writes the chunk out exactly as it was read.
+    pub(crate) fn write_to<W: Write>(&self, mut output: W) -> io::Result<()> {
+        output.write_all(&self.name)?;
+        output.write_le_u32(self.compressed_len)?;
+        output.write_le_u32(self.len)?;
+        output.write_le_u32(self.reserved)?;
+        output.write_all(&self.data)
+    }
+
+    /// As much of the start of the chunk's contents as can be found without
+    /// decompressing it.
+    pub(super) fn peek_contents(&self) -> &[u8] {
+        peek_contents(&self.header(), &self.data)
+    }
+
+    fn header(&self) -> ChunkHeader {
+        ChunkHeader {
+            name: self.name,
+            compressed_len: self.compressed_len,
+            len: self.len,
+            reserved: self.reserved,
+        }
+    }
+}
+
+/// Reads the chunks of a binary model or place one at a time without
+/// interpreting them, for tools that need to look at the structure of a file.
+///
+/// Apart from the file header, nothing is checked, so malformed chunks are
+/// returned as-is. The reader stops after the `END` chunk. After an error is
+/// returned, the reader produces no more chunks.
+///
+/// ## Example
+/// ```no_run
+/// use std::fs::File;
+/// use std::io::BufReader;
+///
+/// use rbx_binary::ChunkReader;
+///
+/// let input = BufReader::new(File::open("Place.rbxl")?);
+///
+/// for chunk in ChunkReader::new(input)? {
+///     let chunk = chunk?;
+///     println!(
+///         "{}: {} bytes, {} compressed",
+///         String::from_utf8_lossy(&chunk.name),
+///         chunk.len,
+///         chunk.compressed_len,
+///     );
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct ChunkReader<R> {
+    input: R,
+    header: FileHeader,
+    finished: bool,
+}
+
+impl<R: Read> ChunkReader<R> {
+    /// Creates a `ChunkReader`, reading the file header from the source right
+    /// away.
+    pub fn new(mut input: R) -> Result<Self, Error> {
+        let header = FileHeader::decode(&mut input)?;
+
+        Ok(ChunkReader {
+            input,
+            header,
+            finished: false,
+        })
+    }
+
+    /// The number of instance types that the file header says there are.
+    pub fn num_types(&self) -> u32 {
+        self.header.num_types
+    }
+
+    /// The number of instances that the file header says there are.
+    pub fn num_instances(&self) -> u32 {
+        self.header.num_instances
+    }
+}
+
+/// Reads one chunk as-is from the given source, failing only if the file
+/// ends partway through it.
+pub(super) fn read_raw_chunk<R: Read>(mut input: R) -> Result<RawChunk, InnerError> {
+    let header = read_chunk_header(&mut input)?;
+
+    let stored_len = if header.compressed_len == 0 {
+        header.len
+    } else {
+        header.compressed_len
+    };
+
+    let mut data = Vec::new();
+    (&mut input)
+        .take(stored_len as u64)
+        .read_to_end(&mut data)?;
+
+    if data.len() != stored_len as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            format!(
+                "Chunk {} should be {} bytes long, but the file ended after {} bytes",
+                header.display_name(),
+                stored_len,
+                data.len()
+            ),
+        )
+        .into());
+    }
+
+    Ok(RawChunk {
+        name: header.name,
+        compressed_len: header.compressed_len,
+        len: header.len,
+        reserved: header.reserved,
+        data,
+    })
+}
+
+impl<R: Read> Iterator for ChunkReader<R> {
+    type Item = Result<RawChunk, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        match read_raw_chunk(&mut self.input) {
+            Ok(chunk) => {
+                self.finished = &chunk.name == b"END\0";
+                Some(Ok(chunk))
+            }
+            Err(err) => {
+                self.finished = true;
+                Some(Err(err.into()))
+            }
+        }
+    }
+}
@@ -0,0 +1,546 @@
+use std::{
+    collections::VecDeque,
+    fmt, io,
+    ops::{Deref, Range},
+    sync::Arc,
+};
+
+use rbx_dom_weak::types::{BinaryString, Ref, SharedString, Variant, VariantType};
+
+use crate::{
+    chunk::{decode_chunk_header, decompress},
+    core::RbxReadExt,
+};
+
+use super::{
+    error::{Error, InnerError},
+    state::DeserializerState,
+};
+
+/// The length of the header in front of every chunk.
+const CHUNK_HEADER_LEN: usize = 16;
+
+/// An event produced by a [`SliceDecoder`]. These are the same as the
+/// [`StreamEvent`][crate::StreamEvent]s that [`StreamDecoder`] produces,
+/// except that property values can point into the file instead of owning their
+/// contents.
+///
+/// [`StreamDecoder`]: crate::StreamDecoder
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum SliceEvent<'b> {
+    /// An entry in the file's metadata.
+    Metadata {
+        /// The metadata key, like `ExplicitAutoJoints`.
+        key: String,
+
+        /// The value of the entry.
+        value: String,
+    },
+
+    /// The file declares instances of a class. Each instance is given a `Ref`
+    /// that later events use to refer to it.
+    Instances {
+        /// The class name shared by all of the instances.
+        class_name: String,
+
+        /// The `Ref`s given to the instances, in the order they appear in the
+        /// file.
+        referents: Vec<Ref>,
+    },
+
+    /// An instance has a property with the given value.
+    Property {
+        /// The instance the property belongs to.
+        referent: Ref,
+
+        /// The name of the property.
+        name: String,
+
+        /// The value of the property.
+        value: SliceValue<'b>,
+    },
+
+    /// An instance has the given parent. Instances at the top of the file have
+    /// `Ref::none()` as their parent.
+    Parent {
+        /// The instance whose parent is given.
+        referent: Ref,
+
+        /// The instance's parent.
+        parent: Ref,
+    },
+}
+
+/// The value of a property decoded by a [`SliceDecoder`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum SliceValue<'b> {
+    /// A `BinaryString` value, which points to where it's stored.
+    BinaryString(SliceBytes<'b>),
+
+    /// A `SharedString` value, which points to where it's stored. Every
+    /// property that uses the same `SharedString` points to the same bytes.
+    SharedString(SliceBytes<'b>),
+
+    /// A value of any other type, decoded as usual.
+    Variant(Variant),
+}
+
+impl SliceValue<'_> {
+    /// Turns the value into a `Variant`, copying it if it's a `BinaryString`
+    /// or `SharedString`.
+    pub fn into_variant(self) -> Variant {
+        match self {
+            SliceValue::BinaryString(bytes) => BinaryString::from(bytes.to_vec()).into(),
+            SliceValue::SharedString(bytes) => SharedString::new(bytes.to_vec()).into(),
+            SliceValue::Variant(value) => value,
+        }
+    }
+}
+
+/// The contents of a `BinaryString` or `SharedString` decoded by a
+/// [`SliceDecoder`].
+///
+/// Values from uncompressed chunks borrow straight from the file. Values from
+/// compressed chunks are held in the chunk's decompressed contents, which are
+/// shared by every value from the same chunk and freed once the last of them
+/// is dropped. Either way, cloning a `SliceBytes` never copies its contents.
+#[derive(Clone)]
+pub struct SliceBytes<'b> {
+    source: Source<'b>,
+    range: Range<usize>,
+}
+
+#[derive(Clone)]
+enum Source<'b> {
+    Input(&'b [u8]),
+    Decompressed(Arc<Vec<u8>>),
+}
+
+impl<'b> SliceBytes<'b> {
+    /// The bytes of the value.
+    pub fn as_bytes(&self) -> &[u8] {
+        match &self.source {
+            Source::Input(input) => &input[self.range.clone()],
+            Source::Decompressed(data) => &data[self.range.clone()],
+        }
+    }
+
+    /// The bytes of the value borrowed from the file, if it came from a chunk
+    /// that isn't compressed.
+    pub fn borrowed(&self) -> Option<&'b [u8]> {
+        match &self.source {
+            Source::Input(input) => Some(&input[self.range.clone()]),
+            Source::Decompressed(_) => None,
+        }
+    }
+
+    /// Where the value is in the file, if it came from a chunk that isn't
+    /// compressed.
+    pub fn input_range(&self) -> Option<Range<usize>> {
+        match &self.source {
+            Source::Input(_) => Some(self.range.clone()),
+            Source::Decompressed(_) => None,
+        }
+    }
+
+    /// The part of these bytes in the given range, which is relative to their
+    /// start.
+    fn slice(&self, range: Range<usize>) -> Self {
+        SliceBytes {
+            source: self.source.clone(),
+            range: self.range.start + range.start..self.range.start + range.end,
+        }
+    }
+}
+
+impl Deref for SliceBytes<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl AsRef<[u8]> for SliceBytes<'_> {
+    fn as_ref(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl PartialEq for SliceBytes<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_bytes() == other.as_bytes()
+    }
+}
+
+impl Eq for SliceBytes<'_> {}
+
+impl fmt::Debug for SliceBytes<'_> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter
+            .debug_struct("SliceBytes")
+            .field("len", &self.len())
+            .field("input_range", &self.input_range())
+            .finish()
+    }
+}
+
+/// Decodes a binary model or place that's already in memory, like a file
+/// that's been read in whole or memory-mapped, as a series of
+/// [`SliceEvent`]s. Created with [`Deserializer::stream_slice`].
+///
+/// This works like a [`StreamDecoder`], except that `BinaryString` and
+/// `SharedString` values are handed out as [`SliceBytes`] instead of being
+/// copied out of the file. Values in chunks that aren't compressed point
+/// straight into the file, and each compressed chunk is decompressed once
+/// into a buffer that its values share.
+///
+/// `SIGN` chunks and chunks that rbx_binary doesn't understand are skipped.
+/// After an error is returned, the decoder produces no more events.
+///
+/// ## Example
+/// ```no_run
+/// use rbx_binary::{Deserializer, SliceEvent, SliceValue};
+///
+/// let input = std::fs::read("Place.rbxl")?;
+///
+/// let mut largest = 0;
+/// for event in Deserializer::new().stream_slice(&input)? {
+///     if let SliceEvent::Property {
+///         value: SliceValue::SharedString(bytes),
+///         ..
+///     } = event?
+///     {
+///         largest = largest.max(bytes.len());
+///     }
+/// }
+///
+/// println!("Largest SharedString is {} bytes", largest);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+///
+/// [`Deserializer::stream_slice`]: crate::Deserializer::stream_slice
+/// [`StreamDecoder`]: crate::StreamDecoder
+pub struct SliceDecoder<'a, 'b> {
+    state: DeserializerState<'a, io::Empty>,
+    input: &'b [u8],
+    position: usize,
+    shared_strings: Vec<SliceBytes<'b>>,
+    pending: VecDeque<SliceEvent<'b>>,
+    finished: bool,
+}
+
+impl<'a, 'b> SliceDecoder<'a, 'b> {
+    pub(super) fn new(
+        state: DeserializerState<'a, io::Empty>,
+        input: &'b [u8],
+        position: usize,
+    ) -> Self {
+        SliceDecoder {
+            state,
+            input,
+            position,
+            shared_strings: Vec::new(),
+            pending: VecDeque::new(),
+            finished: false,
+        }
+    }
+
+    /// Reads the next chunk's header, returning its name and contents. The
+    /// contents are decompressed if they need to be.
+    fn next_chunk(&mut self) -> Result<([u8; 4], SliceBytes<'b>), InnerError> {
+        self.state.start_chunk();
+
+        let mut rest = &self.input[self.position..];
+        let header = match decode_chunk_header(&mut rest) {
+            Ok(header) => header,
+            Err(err) => {
+                self.state.finish_chunk(0, None);
+                return Err(err.into());
+            }
+        };
+
+        let stored_len = if header.compressed_len == 0 {
+            header.len
+        } else {
+            header.compressed_len
+        } as usize;
+
+        let start = self.position + CHUNK_HEADER_LEN;
+        self.state
+            .finish_chunk((CHUNK_HEADER_LEN + stored_len) as u64, Some(&header.name));
+
+        if stored_len > rest.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!(
+                    "Chunk {} should be {} bytes long, but only {} bytes could be read",
+                    header.display_name(),
+                    stored_len,
+                    rest.len()
+                ),
+            )
+            .into());
+        }
+
+        self.position = start + stored_len;
+
+        let contents = if header.compressed_len == 0 {
+            SliceBytes {
+                source: Source::Input(self.input),
+                range: start..start + stored_len,
+            }
+        } else {
+            let data = decompress(&header, &rest[..stored_len])?;
+
+            if data.len() != header.len as usize {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Chunk was a different length than its header said after decompressing",
+                )
+                .into());
+            }
+
+            SliceBytes {
+                range: 0..data.len(),
+                source: Source::Decompressed(Arc::new(data)),
+            }
+        };
+
+        Ok((header.name, contents))
+    }
+
+    /// Reads the next chunk, queueing up any events it produces.
+    fn decode_next_chunk(&mut self) -> Result<(), InnerError> {
+        let (name, contents) = self.next_chunk()?;
+        let data = contents.as_bytes();
+
+        match &name {
+            b"META" => {
+                self.state.decode_meta_chunk(data)?;
+
+                let mut metadata: Vec<_> = self.state.take_metadata().into_iter().collect();
+                metadata.sort();
+
+                self.pending.extend(
+                    metadata
+                        .into_iter()
+                        .map(|(key, value)| SliceEvent::Metadata { key, value }),
+                );
+            }
+            b"SSTR" => self.decode_sstr_chunk(&contents)?,
+            b"INST" => {
+                self.state.decode_inst_chunk(data)?;
+
+                let type_id = contents.as_bytes().read_le_u32()?;
+                if let Some((class_name, referents)) = self.state.instances_of_type(type_id) {
+                    self.pending.push_back(SliceEvent::Instances {
+                        class_name: class_name.to_owned(),
+                        referents,
+                    });
+                }
+            }
+            b"PROP" => self.decode_prop_chunk(&contents)?,
+            b"PRNT" => {
+                let parents = self.state.decode_prnt_chunk_streaming(data)?;
+
+                self.pending.extend(
+                    parents
+                        .into_iter()
+                        .map(|(referent, parent)| SliceEvent::Parent { referent, parent }),
+                );
+            }
+            b"END\0" => {
+                self.state.decode_end_chunk(data)?;
+                self.finished = true;
+            }
+            _ => self.state.skip_unknown_chunk(&name),
+        }
+
+        self.state.report_progress()
+    }
+
+    /// Keeps where each of the SharedStrings in an SSTR chunk are, without
+    /// copying them.
+    fn decode_sstr_chunk(&mut self, contents: &SliceBytes<'b>) -> Result<(), InnerError> {
+        let mut reader = ContentsReader::new(contents);
+
+        let version = reader.read_le_u32()?;
+        if version != 0 {
+            return Err(InnerError::UnknownChunkVersion {
+                chunk_name: "SSTR",
+                version,
+            });
+        }
+
+        let num_entries = reader.read_le_u32()?;
+
+        for _ in 0..num_entries {
+            reader.skip(16)?; // We don't do anything with the hash.
+            let value = reader.read_binary_string()?;
+            self.shared_strings.push(value);
+        }
+
+        Ok(())
+    }
+
+    /// Decodes a PROP chunk, handing out `BinaryString` and `SharedString`
+    /// values as `SliceBytes` and everything else as it would be streamed.
+    fn decode_prop_chunk(&mut self, contents: &SliceBytes<'b>) -> Result<(), InnerError> {
+        let mut reader = ContentsReader::new(contents);
+        let type_id = reader.read_le_u32()?;
+        let prop_name = reader.read_string()?;
+
+        let payload = match reader.read_u8() {
+            Ok(binary_type) => self
+                .state
+                .payload_property(type_id, &prop_name, binary_type),
+            Err(_) => None,
+        };
+
+        let (name, ty) = match payload {
+            Some(payload) => payload,
+            None => {
+                self.state.decode_prop_chunk(contents.as_bytes())?;
+
+                self.pending
+                    .extend(self.state.take_properties(type_id).into_iter().map(
+                        |(referent, name, value)| SliceEvent::Property {
+                            referent,
+                            name,
+                            value: SliceValue::Variant(value),
+                        },
+                    ));
+
+                return Ok(());
+            }
+        };
+
+        let (class_name, referents) = match self.state.instances_of_type(type_id) {
+            Some((class_name, referents)) => (class_name.to_owned(), referents),
+            None => return Err(InnerError::InvalidTypeId { type_id }),
+        };
+
+        let values = if ty == VariantType::SharedString {
+            let mut indices = vec![0; referents.len()];
+            reader.rest().read_interleaved_u32_array(&mut indices)?;
+
+            indices
+                .into_iter()
+                .map(|index| match self.shared_strings.get(index as usize) {
+                    Some(value) => Ok(SliceValue::SharedString(value.clone())),
+                    None => Err(InnerError::InvalidPropData {
+                        type_name: class_name.clone(),
+                        prop_name: prop_name.clone(),
+                        valid_value: "a valid SharedString",
+                        actual_value: format!("{:?}", index),
+                    }),
+                })
+                .collect::<Result<Vec<_>, _>>()?
+        } else {
+            referents
+                .iter()
+                .map(|_| Ok(SliceValue::BinaryString(reader.read_binary_string()?)))
+                .collect::<Result<Vec<_>, InnerError>>()?
+        };
+
+        self.pending
+            .extend(referents.into_iter().zip(values).map(|(referent, value)| {
+                SliceEvent::Property {
+                    referent,
+                    name: name.clone(),
+                    value,
+                }
+            }));
+
+        Ok(())
+    }
+}
+
+impl<'b> Iterator for SliceDecoder<'_, 'b> {
+    type Item = Result<SliceEvent<'b>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(Ok(event));
+            }
+
+            if self.finished {
+                return None;
+            }
+
+            if let Err(err) = self.decode_next_chunk() {
+                self.finished = true;
+                self.pending.clear();
+                return Some(Err(self.state.error(err)));
+            }
+        }
+    }
+}
+
+/// Reads through the contents of a chunk, keeping track of where it is so
+/// that values can be handed out as `SliceBytes`.
+struct ContentsReader<'c, 'b> {
+    contents: &'c SliceBytes<'b>,
+    position: usize,
+}
+
+impl<'c, 'b> ContentsReader<'c, 'b> {
+    fn new(contents: &'c SliceBytes<'b>) -> Self {
+        ContentsReader {
+            contents,
+            position: 0,
+        }
+    }
+
+    /// The contents that haven't been read yet.
+    fn rest(&self) -> &'c [u8] {
+        &self.contents.as_bytes()[self.position..]
+    }
+
+    fn skip(&mut self, len: usize) -> io::Result<()> {
+        if len > self.rest().len() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                format!(
+                    "Expected {} bytes, but only {} bytes are left",
+                    len,
+                    self.rest().len()
+                ),
+            ));
+        }
+
+        self.position += len;
+        Ok(())
+    }
+
+    fn read_u8(&mut self) -> io::Result<u8> {
+        let value = self.rest().read_u8()?;
+        self.position += 1;
+        Ok(value)
+    }
+
+    fn read_le_u32(&mut self) -> io::Result<u32> {
+        let value = self.rest().read_le_u32()?;
+        self.position += 4;
+        Ok(value)
+    }
+
+    fn read_string(&mut self) -> io::Result<String> {
+        let mut rest = self.rest();
+        let value = rest.read_string()?;
+        self.position = self.contents.len() - rest.len();
+        Ok(value)
+    }
+
+    /// Reads a length-prefixed string of bytes without copying it.
+    fn read_binary_string(&mut self) -> io::Result<SliceBytes<'b>> {
+        let len = self.read_le_u32()? as usize;
+        let start = self.position;
+        self.skip(len)?;
+
+        Ok(self.contents.slice(start..start + len))
+    }
+}
@@ -1,33 +1,45 @@
 use std::{
     collections::{HashMap, HashSet, VecDeque},
     convert::TryInto,
-    io::Read,
+    io::{self, Read},
 };
 
 use rbx_dom_weak::{
     types::{
         Attributes, Axes, BinaryString, BrickColor, CFrame, Color3, Color3uint8, ColorSequence,
         ColorSequenceKeypoint, Content, CustomPhysicalProperties, Enum, Faces, Font, FontStyle,
-        FontWeight, Matrix3, NumberRange, NumberSequence, NumberSequenceKeypoint,
-        PhysicalProperties, Ray, Rect, Ref, SharedString, Tags, UDim, UDim2, UniqueId, Variant,
-        VariantType, Vector2, Vector3, Vector3int16,
+        FontWeight, MaterialColors, Matrix3, NumberRange, NumberSequence, NumberSequenceKeypoint,
+        PhysicalProperties, Ray, Rect, Ref, SecurityCapabilities, SharedString, SmoothGrid, Tags,
+        UDim, UDim2, UniqueId, Variant, VariantType, Vector2, Vector3, Vector3int16,
     },
     InstanceBuilder, WeakDom,
 };
-use rbx_reflection::{perform_migration, DataType, PropertyKind, PropertySerialization};
+use rbx_reflection::{
+    perform_migration, DataType, PropertyKind, PropertySerialization, ReflectionDatabase,
+};
 
 use crate::{
     cframe,
-    chunk::Chunk,
-    core::{find_property_descriptors, RbxReadExt},
+    chunk::{Chunk, UnknownChunk},
+    core::{check_count, find_property_descriptors, RbxReadExt, FILE_VERSION, MAX_PREALLOCATION},
+    progress::ProgressReporter,
     types::Type,
 };
 
-use super::{error::InnerError, header::FileHeader, Deserializer};
+use super::{
+    error::{Error, InnerError, Location},
+    header::FileHeader,
+    raw::{read_raw_chunk, RawChunk},
+    DecodedDocument, Deserializer,
+};
 
 pub(super) struct DeserializerState<'a, R> {
-    /// The user-provided configuration that we should use.
-    deserializer: &'a Deserializer<'a>,
+    /// The reflection database to use when decoding properties.
+    database: &'a ReflectionDatabase<'a>,
+
+    /// If set, only instances of these classes and their subclasses have
+    /// properties other than `Name` decoded.
+    class_filter: Option<HashSet<String>>,
 
     /// The input data encoded as a binary model.
     input: R,
@@ -54,10 +66,67 @@ pub(super) struct DeserializerState<'a, R> {
     /// in the file.
     root_instance_refs: Vec<i32>,
 
+    /// The referent each instance had in the file, filled in as instances are
+    /// added to the tree.
+    file_referents: HashMap<Ref, i32>,
+
     /// Contains a set of unknown type IDs that we've encountered so far while
     /// deserializing this file. We use this map in order to ensure we only
     /// print one warning per unknown type ID when deserializing a file.
     unknown_type_ids: HashSet<u8>,
+
+    /// The contents of the file's SIGN chunk, if it had one.
+    signature: Option<Vec<u8>>,
+
+    /// The chunks in the file that we don't understand.
+    unknown_chunks: Vec<UnknownChunk>,
+
+    /// The version of the format the file is in.
+    version: u16,
+
+    /// Problems that might have come from the file being in a newer version of
+    /// the format.
+    warnings: Vec<Error>,
+
+    /// The number of chunks read from the file so far.
+    chunks_read: usize,
+
+    /// The number of bytes read from the file so far, including its header.
+    position: u64,
+
+    /// The part of the file being decoded, which is attached to errors.
+    location: Location,
+
+    /// The number of bytes left over at the end of the last PROP chunk whose
+    /// values were decoded.
+    unread_prop_bytes: usize,
+
+    /// Passes on how far decoding has gotten to the user's callback, if any.
+    reporter: ProgressReporter<'a>,
+}
+
+/// Counts the bytes read through it, so that we know where chunks start.
+pub(super) struct CountingReader<R> {
+    inner: R,
+    count: u64,
+}
+
+impl<R> CountingReader<R> {
+    pub(super) fn new(inner: R) -> Self {
+        CountingReader { inner, count: 0 }
+    }
+
+    pub(super) fn count(&self) -> u64 {
+        self.count
+    }
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.count += len as u64;
+        Ok(len)
+    }
 }
 
 /// Represents a unique instance class. Binary models define all their instance
@@ -72,6 +141,10 @@ struct TypeInfo {
 
     /// A list of the instances described by this file that are this type.
     referents: Vec<i32>,
+
+    /// Whether properties other than `Name` should be skipped for this type,
+    /// since it's been filtered out.
+    skip_properties: bool,
 }
 
 /// Contains all the information we need to gather in order to construct an
@@ -81,24 +154,54 @@ struct Instance {
     /// A work-in-progress builder that will be used to construct this instance.
     builder: InstanceBuilder,
 
+    /// The referent of the instance once it's in the tree, which is kept here
+    /// for decoding Refs after the builder is gone.
+    referent: Ref,
+
+    /// The properties decoded for this instance so far, including its `Name`.
+    /// These are kept outside of the builder so that streaming decodes can
+    /// take them back out after every chunk.
+    properties: HashMap<String, Variant>,
+
     /// Document-defined IDs for the children of this instance.
     children: Vec<i32>,
 }
 
 impl<'a, R: Read> DeserializerState<'a, R> {
-    pub(super) fn new(
-        deserializer: &'a Deserializer<'a>,
-        mut input: R,
-    ) -> Result<Self, InnerError> {
-        let tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+    pub(super) fn new(deserializer: &Deserializer<'a>, mut input: R) -> Result<Self, InnerError> {
+        let header = deserializer.read_header(&mut input)?;
 
-        let header = FileHeader::decode(&mut input)?;
+        Ok(Self::from_header(deserializer, header, input))
+    }
+
+    /// Creates a `DeserializerState` for a file whose header has already been
+    /// read from `input`.
+    pub(super) fn from_header(
+        deserializer: &Deserializer<'a>,
+        header: FileHeader,
+        input: R,
+    ) -> Self {
+        let tree = WeakDom::new(InstanceBuilder::new("DataModel"));
 
-        let type_infos = HashMap::with_capacity(header.num_types as usize);
-        let instances_by_ref = HashMap::with_capacity(1 + header.num_instances as usize);
+        // The counts in the header are only hints, so they can't be trusted
+        // not to be huge.
+        let max_capacity = MAX_PREALLOCATION / 64;
+        let type_infos = HashMap::with_capacity((header.num_types as usize).min(max_capacity));
+        let instances_by_ref =
+            HashMap::with_capacity(1 + (header.num_instances as usize).min(max_capacity));
+
+        let mut warnings = Vec::new();
+        if header.version != FILE_VERSION {
+            let warning = Error::from(InnerError::NewerFileVersion {
+                version: header.version,
+            });
+            log::warn!("{}", warning);
+            warnings.push(warning);
+        }
 
-        Ok(DeserializerState {
-            deserializer,
+        DeserializerState {
+            database: deserializer.database.unwrap(),
+            class_filter: deserializer.class_filter.clone(),
             input,
             tree,
             metadata: HashMap::new(),
@@ -106,12 +209,179 @@ impl<'a, R: Read> DeserializerState<'a, R> {
             type_infos,
             instances_by_ref,
             root_instance_refs: Vec::new(),
+            file_referents: HashMap::new(),
             unknown_type_ids: HashSet::new(),
-        })
+            signature: None,
+            unknown_chunks: Vec::new(),
+            version: header.version,
+            warnings,
+            chunks_read: 0,
+            position: FileHeader::LEN,
+            location: Location::default(),
+            unread_prop_bytes: 0,
+            reporter: ProgressReporter::new(
+                deserializer.progress_callback.clone(),
+                Some(header.num_instances as usize),
+            ),
+        }
+    }
+
+    /// Notes that a new chunk is about to be read, so that errors point to it.
+    pub(super) fn start_chunk(&mut self) {
+        self.location = Location {
+            chunk_index: Some(self.chunks_read),
+            chunk_offset: Some(self.position),
+            ..Location::default()
+        };
+
+        self.chunks_read += 1;
+    }
+
+    /// Notes that `len` bytes of the current chunk were read, and its name if
+    /// its header could be read.
+    pub(super) fn finish_chunk(&mut self, len: u64, name: Option<&[u8; 4]>) {
+        self.position += len;
+
+        if let Some(name) = name {
+            self.location.chunk_name = Some(
+                String::from_utf8_lossy(name)
+                    .trim_end_matches('\0')
+                    .to_owned(),
+            );
+        }
+    }
+
+    /// The part of the file being decoded.
+    pub(super) fn location(&self) -> &Location {
+        &self.location
+    }
+
+    /// Replaces the part of the file being decoded, for decoding a chunk that
+    /// was read earlier.
+    pub(super) fn set_location(&mut self, location: Location) {
+        self.location = location;
+    }
+
+    /// Reports how far decoding has gotten, which should be done after each
+    /// chunk is decoded. Fails if the user asked for decoding to stop.
+    pub(super) fn report_progress(&mut self) -> Result<(), InnerError> {
+        self.reporter.progress.chunks = self.chunks_read;
+
+        if self.reporter.report() {
+            Ok(())
+        } else {
+            Err(InnerError::Cancelled)
+        }
+    }
+
+    /// Turns an error found while decoding into one that says where it was
+    /// found.
+    pub(super) fn error(&self, error: InnerError) -> Error {
+        Error::from(error).with_location(self.location.clone())
     }
 
     pub(super) fn next_chunk(&mut self) -> Result<Chunk, InnerError> {
-        Ok(Chunk::decode(&mut self.input)?)
+        if self.class_filter.is_none() {
+            return self.next_chunk_with_limit(usize::MAX);
+        }
+
+        // When some classes are filtered out, we try to skip their PROP chunks
+        // before they're decompressed.
+        loop {
+            let raw_chunk = self.next_raw_chunk()?;
+
+            if raw_chunk.reserved != 0 {
+                return Err(InnerError::MalformedChunkHeader {
+                    chunk_name: String::from_utf8_lossy(&raw_chunk.name).into_owned(),
+                });
+            }
+
+            if &raw_chunk.name == b"PROP" && self.can_skip_prop_chunk(raw_chunk.peek_contents()) {
+                continue;
+            }
+
+            let data = raw_chunk.decompressed_inner()?.into_owned();
+
+            if data.len() != raw_chunk.len as usize {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Chunk was a different length than its header said after decompressing",
+                )
+                .into());
+            }
+
+            return Ok(Chunk {
+                name: raw_chunk.name,
+                data,
+            });
+        }
+    }
+
+    /// Whether instances of the given class should have their properties
+    /// decoded.
+    fn includes_class(&self, class_name: &str) -> bool {
+        let class_filter = match &self.class_filter {
+            Some(class_filter) => class_filter,
+            None => return true,
+        };
+
+        if class_filter.contains(class_name) {
+            return true;
+        }
+
+        match self.database.classes.get(class_name) {
+            Some(descriptor) => self
+                .database
+                .superclasses(descriptor)
+                .any(|descriptor| class_filter.contains(descriptor.name.as_ref())),
+            None => false,
+        }
+    }
+
+    /// Whether a PROP chunk that starts with the given bytes can be skipped
+    /// because it belongs to a class that's filtered out. This is only true if
+    /// enough of the chunk is given to know that it isn't a `Name` chunk.
+    fn can_skip_prop_chunk(&self, mut contents: &[u8]) -> bool {
+        let type_id = match contents.read_le_u32() {
+            Ok(type_id) => type_id,
+            Err(_) => return false,
+        };
+
+        match self.type_infos.get(&type_id) {
+            Some(type_info) if type_info.skip_properties => {}
+            _ => return false,
+        }
+
+        let name_len = match contents.read_le_u32() {
+            Ok(len) => len as usize,
+            Err(_) => return false,
+        };
+
+        contents.len() >= name_len && &contents[..name_len] != b"Name"
+    }
+
+    /// Reads the next chunk without decompressing or checking it.
+    pub(super) fn next_raw_chunk(&mut self) -> Result<RawChunk, InnerError> {
+        self.start_chunk();
+
+        let mut input = CountingReader::new(&mut self.input);
+        let chunk = read_raw_chunk(&mut input);
+        let len = input.count();
+
+        self.finish_chunk(len, chunk.as_ref().ok().map(|chunk| &chunk.name));
+        chunk
+    }
+
+    /// Like `next_chunk`, but fails if the chunk is longer than `limit` bytes.
+    pub(super) fn next_chunk_with_limit(&mut self, limit: usize) -> Result<Chunk, InnerError> {
+        self.start_chunk();
+
+        let mut input = CountingReader::new(&mut self.input);
+        let chunk = Chunk::decode_with_limit(&mut input, limit);
+        let len = input.count();
+
+        self.finish_chunk(len, chunk.as_ref().ok().map(|chunk| &chunk.name));
+        Ok(chunk?)
     }
 
     #[profiling::function]
@@ -151,10 +421,50 @@ impl<'a, R: Read> DeserializerState<'a, R> {
         Ok(())
     }
 
+    /// Keeps the contents of a SIGN chunk as-is. We can't check signatures,
+    /// but they can be written back out unchanged.
+    pub(super) fn decode_sign_chunk(&mut self, chunk: Vec<u8>) {
+        if self.signature.is_some() {
+            log::warn!("File had more than one SIGN chunk, keeping the last one");
+        }
+
+        self.signature = Some(chunk);
+    }
+
+    /// Notes that a chunk rbx_binary doesn't understand was found, warning
+    /// about it if the file is in a newer version of the format.
+    pub(super) fn skip_unknown_chunk(&mut self, name: &[u8; 4]) {
+        let chunk_name = String::from_utf8_lossy(name)
+            .trim_end_matches('\0')
+            .to_owned();
+
+        // Chunks that rbx_binary doesn't know about turn up in files of the
+        // current version too, but in a newer file they're more likely to be
+        // something that matters.
+        if self.version == FILE_VERSION {
+            log::info!("Unknown binary chunk name {}", chunk_name);
+        } else {
+            let warning = self.error(InnerError::UnknownChunk { chunk_name });
+            log::warn!("{}", warning);
+            self.warnings.push(warning);
+        }
+    }
+
+    pub(super) fn decode_unknown_chunk(&mut self, chunk: Chunk) {
+        self.skip_unknown_chunk(&chunk.name);
+
+        self.unknown_chunks.push(UnknownChunk {
+            name: chunk.name,
+            data: chunk.data,
+        });
+    }
+
     #[profiling::function]
     pub(super) fn decode_inst_chunk(&mut self, mut chunk: &[u8]) -> Result<(), InnerError> {
         let type_id = chunk.read_le_u32()?;
         let type_name = chunk.read_string()?;
+        self.location.class_name = Some(type_name.clone());
+
         let object_format = chunk.read_u8()?;
         let number_instances = chunk.read_le_u32()?;
 
@@ -166,27 +476,36 @@ impl<'a, R: Read> DeserializerState<'a, R> {
             number_instances,
         );
 
-        let mut referents = vec![0; number_instances as usize];
+        let mut referents = vec![0; check_count(chunk, number_instances, 4)?];
         chunk.read_referent_array(&mut referents)?;
 
         // TODO: Check object_format and check for service markers if it's 1?
 
+        self.reporter.progress.instances += referents.len();
+
         for &referent in &referents {
+            let builder = InstanceBuilder::new(&type_name);
+
             self.instances_by_ref.insert(
                 referent,
                 Instance {
-                    builder: InstanceBuilder::new(&type_name),
+                    referent: builder.referent(),
+                    builder,
+                    properties: HashMap::new(),
                     children: Vec::new(),
                 },
             );
         }
 
+        let skip_properties = !self.includes_class(&type_name);
+
         self.type_infos.insert(
             type_id,
             TypeInfo {
                 type_id,
                 type_name,
                 referents,
+                skip_properties,
             },
         );
 
@@ -195,14 +514,20 @@ impl<'a, R: Read> DeserializerState<'a, R> {
 
     #[profiling::function]
     pub(super) fn decode_prop_chunk(&mut self, mut chunk: &[u8]) -> Result<(), InnerError> {
+        self.unread_prop_bytes = 0;
+
         let type_id = chunk.read_le_u32()?;
         let prop_name = chunk.read_string()?;
 
+        self.location.property_name = Some(prop_name.clone());
+
         let type_info = self
             .type_infos
             .get(&type_id)
             .ok_or(InnerError::InvalidTypeId { type_id })?;
 
+        self.location.class_name = Some(type_info.type_name.clone());
+
         // PROP chunks that contain no type byte are ignored by Roblox. This can
         // happen when a new type is introduced.
         //
@@ -240,6 +565,10 @@ impl<'a, R: Read> DeserializerState<'a, R> {
             type_id
         );
 
+        if type_info.skip_properties && prop_name != "Name" {
+            return Ok(());
+        }
+
         // The `Name` prop is special and is routed to a different spot for
         // rbx_dom_weak, so we handle it specially here.
         if prop_name == "Name" {
@@ -250,9 +579,10 @@ impl<'a, R: Read> DeserializerState<'a, R> {
             for referent in &type_info.referents {
                 let instance = self.instances_by_ref.get_mut(referent).unwrap();
                 let value = chunk.read_string()?;
-                instance.builder.set_name(value);
+                instance.properties.insert("Name".to_owned(), value.into());
             }
 
+            self.unread_prop_bytes = chunk.len();
             return Ok(());
         }
 
@@ -260,11 +590,7 @@ impl<'a, R: Read> DeserializerState<'a, R> {
         let canonical_type;
         let migrated_to;
 
-        match find_property_descriptors(
-            self.deserializer.database.unwrap(),
-            &type_info.type_name,
-            &prop_name,
-        ) {
+        match find_property_descriptors(self.database, &type_info.type_name, &prop_name) {
             Some(descriptors) => {
                 // If this descriptor is known but wasn't supposed to be
                 // serialized, we should skip it.
@@ -341,10 +667,10 @@ impl<'a, R: Read> DeserializerState<'a, R> {
         let add_property: Box<dyn Fn(&mut Instance, Variant) -> ()> = match &migrated_to {
             Some(_) => Box::new(|instance: &mut Instance, value| {
                 let (migration, property) = migrated_to.clone().unwrap();
-                if !instance.builder.has_property(property.as_ref()) {
+                if !instance.properties.contains_key(property.as_ref()) {
                     match perform_migration(*migration, &value) {
                         Ok(value) => {
-                            instance.builder.add_property(property.as_ref(), value);
+                            instance.properties.insert(property.to_string(), value);
                         }
                         Err(e) => {
                             log::warn!(
@@ -358,7 +684,7 @@ impl<'a, R: Read> DeserializerState<'a, R> {
                 }
             }),
             None => Box::new(|instance: &mut Instance, value| {
-                instance.builder.add_property(&canonical_name, value);
+                instance.properties.insert(canonical_name.clone(), value);
             }),
         };
 
@@ -402,6 +728,40 @@ impl<'a, R: Read> DeserializerState<'a, R> {
                         add_property(instance, value.into());
                     }
                 }
+                VariantType::MaterialColors => {
+                    for referent in &type_info.referents {
+                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
+                        let buffer = chunk.read_binary_string()?;
+
+                        let value = MaterialColors::decode(buffer.as_ref()).map_err(|err| {
+                            InnerError::InvalidPropData {
+                                type_name: type_info.type_name.clone(),
+                                prop_name: prop_name.clone(),
+                                valid_value: "a list of terrain material colors",
+                                actual_value: err.to_string(),
+                            }
+                        })?;
+
+                        add_property(instance, value.into());
+                    }
+                }
+                VariantType::SmoothGrid => {
+                    for referent in &type_info.referents {
+                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
+                        let buffer = chunk.read_binary_string()?;
+
+                        let value = SmoothGrid::decode(buffer.as_ref()).map_err(|err| {
+                            InnerError::InvalidPropData {
+                                type_name: type_info.type_name.clone(),
+                                prop_name: prop_name.clone(),
+                                valid_value: "terrain voxels",
+                                actual_value: err.to_string(),
+                            }
+                        })?;
+
+                        add_property(instance, value.into());
+                    }
+                }
                 VariantType::Attributes => {
                     for referent in &type_info.referents {
                         let instance = self.instances_by_ref.get_mut(referent).unwrap();
@@ -427,7 +787,8 @@ impl<'a, R: Read> DeserializerState<'a, R> {
                     return Err(InnerError::PropTypeMismatch {
                         type_name: type_info.type_name.clone(),
                         prop_name,
-                        valid_type_names: "String, Content, Tags, Attributes, or BinaryString",
+                        valid_type_names:
+                            "String, Content, Tags, Attributes, MaterialColors, SmoothGrid, or BinaryString",
                         actual_type_name: format!("{:?}", invalid_type),
                     });
                 }
@@ -459,11 +820,38 @@ impl<'a, R: Read> DeserializerState<'a, R> {
                         add_property(instance, value.into());
                     }
                 }
+                // Some properties, like user and asset IDs, were widened to
+                // Int64 after files were saved with them as Int32.
+                VariantType::Int64 => {
+                    let mut values = vec![0; type_info.referents.len()];
+                    chunk.read_interleaved_i32_array(&mut values)?;
+
+                    for (value, referent) in values.into_iter().zip(&type_info.referents) {
+                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
+                        add_property(instance, (value as i64).into());
+                    }
+                }
+                // BrickColors are sometimes stored as their numbers, like they
+                // are in XML files.
+                VariantType::BrickColor => {
+                    let mut values = vec![0; type_info.referents.len()];
+                    chunk.read_interleaved_i32_array(&mut values)?;
+
+                    for (value, referent) in values.into_iter().zip(&type_info.referents) {
+                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
+                        let color = brick_color_from_number(
+                            &type_info.type_name,
+                            &prop_name,
+                            value as u32,
+                        )?;
+                        add_property(instance, color.into());
+                    }
+                }
                 invalid_type => {
                     return Err(InnerError::PropTypeMismatch {
                         type_name: type_info.type_name.clone(),
                         prop_name,
-                        valid_type_names: "Int32",
+                        valid_type_names: "Int32, Int64, or BrickColor",
                         actual_type_name: format!("{:?}", invalid_type),
                     });
                 }
@@ -605,14 +993,9 @@ impl<'a, R: Read> DeserializerState<'a, R> {
                 VariantType::Faces => {
                     for referent in &type_info.referents {
                         let instance = self.instances_by_ref.get_mut(referent).unwrap();
-                        let value = chunk.read_u8()?;
-                        let faces =
-                            Faces::from_bits(value).ok_or_else(|| InnerError::InvalidPropData {
-                                type_name: type_info.type_name.clone(),
-                                prop_name: prop_name.clone(),
-                                valid_value: "less than 63",
-                                actual_value: value.to_string(),
-                            })?;
+                        // Only the low 6 bits of the byte mean anything, and
+                        // some older files have the others set.
+                        let faces = Faces::from_bits_truncate(chunk.read_u8()?);
 
                         add_property(instance, faces.into());
                     }
@@ -630,15 +1013,9 @@ impl<'a, R: Read> DeserializerState<'a, R> {
                 VariantType::Axes => {
                     for referent in &type_info.referents {
                         let instance = self.instances_by_ref.get_mut(referent).unwrap();
-                        let value = chunk.read_u8()?;
-
-                        let axes =
-                            Axes::from_bits(value).ok_or_else(|| InnerError::InvalidPropData {
-                                type_name: type_info.type_name.clone(),
-                                prop_name: prop_name.clone(),
-                                valid_value: "less than 7",
-                                actual_value: value.to_string(),
-                            })?;
+                        // Only the low 3 bits of the byte mean anything, and
+                        // some older files have the others set.
+                        let axes = Axes::from_bits_truncate(chunk.read_u8()?);
 
                         add_property(instance, axes.into());
                     }
@@ -653,31 +1030,30 @@ impl<'a, R: Read> DeserializerState<'a, R> {
                 }
             },
             Type::BrickColor => match canonical_type {
-                VariantType::BrickColor => {
+                // Older files store BasePart.BrickColor, which is now an alias
+                // of BasePart.Color, as a BrickColor. Those become the color
+                // that the BrickColor stands for.
+                VariantType::BrickColor | VariantType::Color3 => {
                     let mut values = vec![0; type_info.referents.len()];
                     chunk.read_interleaved_u32_array(&mut values)?;
 
                     for (value, referent) in values.into_iter().zip(&type_info.referents) {
                         let instance = self.instances_by_ref.get_mut(referent).unwrap();
-                        let color = value
-                            .try_into()
-                            .ok()
-                            .and_then(BrickColor::from_number)
-                            .ok_or_else(|| InnerError::InvalidPropData {
-                                type_name: type_info.type_name.clone(),
-                                prop_name: prop_name.clone(),
-                                valid_value: "a valid BrickColor",
-                                actual_value: value.to_string(),
-                            })?;
+                        let color =
+                            brick_color_from_number(&type_info.type_name, &prop_name, value)?;
 
-                        add_property(instance, color.into());
+                        if canonical_type == VariantType::Color3 {
+                            add_property(instance, color.to_color3uint8().into());
+                        } else {
+                            add_property(instance, color.into());
+                        }
                     }
                 }
                 invalid_type => {
                     return Err(InnerError::PropTypeMismatch {
                         type_name: type_info.type_name.clone(),
                         prop_name,
-                        valid_type_names: "BrickColor",
+                        valid_type_names: "BrickColor or Color3",
                         actual_type_name: format!("{:?}", invalid_type),
                     });
                 }
@@ -842,11 +1218,24 @@ impl<'a, R: Read> DeserializerState<'a, R> {
                         add_property(instance, Enum::from_u32(value).into());
                     }
                 }
+                // Very old files store some BrickColor properties as enums
+                // (tokens) holding the BrickColor's number.
+                VariantType::BrickColor => {
+                    let mut values = vec![0; type_info.referents.len()];
+                    chunk.read_interleaved_u32_array(&mut values)?;
+
+                    for (value, referent) in values.into_iter().zip(&type_info.referents) {
+                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
+                        let color =
+                            brick_color_from_number(&type_info.type_name, &prop_name, value)?;
+                        add_property(instance, color.into());
+                    }
+                }
                 invalid_type => {
                     return Err(InnerError::PropTypeMismatch {
                         type_name: type_info.type_name.clone(),
                         prop_name,
-                        valid_type_names: "Enum",
+                        valid_type_names: "Enum or BrickColor",
                         actual_type_name: format!("{:?}", invalid_type),
                     });
                 }
@@ -857,11 +1246,7 @@ impl<'a, R: Read> DeserializerState<'a, R> {
                     chunk.read_referent_array(&mut refs)?;
 
                     for (value, referent) in refs.into_iter().zip(&type_info.referents) {
-                        let rbx_value = if let Some(instance) = self.instances_by_ref.get(&value) {
-                            instance.builder.referent()
-                        } else {
-                            Ref::none()
-                        };
+                        let rbx_value = self.instance_ref(value);
 
                         let instance = self.instances_by_ref.get_mut(referent).unwrap();
                         add_property(instance, rbx_value.into());
@@ -942,7 +1327,8 @@ impl<'a, R: Read> DeserializerState<'a, R> {
                     for referent in &type_info.referents {
                         let instance = self.instances_by_ref.get_mut(referent).unwrap();
                         let keypoint_count = chunk.read_le_u32()?;
-                        let mut keypoints = Vec::with_capacity(keypoint_count as usize);
+                        let keypoint_count = check_count(chunk, keypoint_count, 12)?;
+                        let mut keypoints = Vec::with_capacity(keypoint_count);
 
                         for _ in 0..keypoint_count {
                             keypoints.push(NumberSequenceKeypoint::new(
@@ -968,7 +1354,8 @@ impl<'a, R: Read> DeserializerState<'a, R> {
                 VariantType::ColorSequence => {
                     for referent in &type_info.referents {
                         let instance = self.instances_by_ref.get_mut(referent).unwrap();
-                        let keypoint_count = chunk.read_le_u32()? as usize;
+                        let keypoint_count = chunk.read_le_u32()?;
+                        let keypoint_count = check_count(chunk, keypoint_count, 20)?;
                         let mut keypoints = Vec::with_capacity(keypoint_count);
 
                         for _ in 0..keypoint_count {
@@ -1120,11 +1507,29 @@ impl<'a, R: Read> DeserializerState<'a, R> {
                         add_property(instance, value.into());
                     }
                 }
+                // Int64 values are accepted for Int32 properties as long as
+                // they fit.
+                VariantType::Int32 => {
+                    let mut values = vec![0; type_info.referents.len()];
+                    chunk.read_interleaved_i64_array(&mut values)?;
+
+                    for (value, referent) in values.into_iter().zip(&type_info.referents) {
+                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
+                        let value: i32 =
+                            value.try_into().map_err(|_| InnerError::InvalidPropData {
+                                type_name: type_info.type_name.clone(),
+                                prop_name: prop_name.clone(),
+                                valid_value: "in the range of an Int32",
+                                actual_value: value.to_string(),
+                            })?;
+                        add_property(instance, value.into());
+                    }
+                }
                 invalid_type => {
                     return Err(InnerError::PropTypeMismatch {
                         type_name: type_info.type_name.clone(),
                         prop_name,
-                        valid_type_names: "Int64",
+                        valid_type_names: "Int64 or Int32",
                         actual_type_name: format!("{:?}", invalid_type),
                     });
                 }
@@ -1264,14 +1669,15 @@ impl<'a, R: Read> DeserializerState<'a, R> {
                     for (i, referent) in type_info.referents.iter().enumerate() {
                         let mut value = values[i].as_slice();
                         let instance = self.instances_by_ref.get_mut(referent).unwrap();
-                        instance.builder.add_property(
-                            &canonical_name,
+                        instance.properties.insert(
+                            canonical_name.clone(),
                             UniqueId::new(
                                 value.read_be_u32()?,
                                 value.read_be_u32()?,
                                 value.read_be_i64()?.rotate_right(1),
-                            ),
-                        )
+                            )
+                            .into(),
+                        );
                     }
                 }
                 invalid_type => {
@@ -1283,38 +1689,305 @@ impl<'a, R: Read> DeserializerState<'a, R> {
                     });
                 }
             },
+            Type::SecurityCapabilities => match canonical_type {
+                VariantType::SecurityCapabilities => {
+                    let mut values = vec![0; type_info.referents.len()];
+                    chunk.read_interleaved_i64_array(&mut values)?;
+
+                    for (value, referent) in values.into_iter().zip(&type_info.referents) {
+                        let instance = self.instances_by_ref.get_mut(referent).unwrap();
+                        add_property(
+                            instance,
+                            SecurityCapabilities::from_bits(value as u64).into(),
+                        );
+                    }
+                }
+                invalid_type => {
+                    return Err(InnerError::PropTypeMismatch {
+                        type_name: type_info.type_name.clone(),
+                        prop_name,
+                        valid_type_names: "SecurityCapabilities",
+                        actual_type_name: format!("{:?}", invalid_type),
+                    });
+                }
+            },
         }
 
+        self.unread_prop_bytes = chunk.len();
         Ok(())
     }
 
+    /// The number of bytes that were left over after decoding the values in
+    /// the last PROP chunk, or 0 if its values were skipped.
+    pub(super) fn unread_prop_bytes(&self) -> usize {
+        self.unread_prop_bytes
+    }
+
     #[profiling::function]
-    pub(super) fn decode_prnt_chunk(&mut self, mut chunk: &[u8]) -> Result<(), InnerError> {
-        let version = chunk.read_u8()?;
+    pub(super) fn decode_prnt_chunk(&mut self, chunk: &[u8]) -> Result<(), InnerError> {
+        // Instances with a parent that doesn't exist are skipped, so that a
+        // recovering decode keeps all of the other relationships.
+        let mut invalid_parent = None;
 
-        if version != 0 {
-            return Err(InnerError::UnknownChunkVersion {
-                chunk_name: "PRNT",
-                version: version as u32,
-            });
+        for (id, parent_ref) in read_prnt_chunk(chunk)? {
+            if parent_ref == -1 {
+                self.root_instance_refs.push(id);
+            } else if let Some(instance) = self.instances_by_ref.get_mut(&parent_ref) {
+                instance.children.push(id);
+            } else if invalid_parent.is_none() {
+                invalid_parent = Some(parent_ref);
+            }
         }
 
-        let number_objects = chunk.read_le_u32()?;
+        match invalid_parent {
+            Some(referent) => Err(InnerError::InvalidParent { referent }),
+            None => Ok(()),
+        }
+    }
 
-        log::trace!("PRNT chunk ({} instances)", number_objects);
+    /// Decodes a PRNT chunk into pairs of instances and their parents, without
+    /// recording them. Instances at the top of the file have `Ref::none()` as
+    /// their parent.
+    #[profiling::function]
+    pub(super) fn decode_prnt_chunk_streaming(
+        &self,
+        chunk: &[u8],
+    ) -> Result<Vec<(Ref, Ref)>, InnerError> {
+        Ok(read_prnt_chunk(chunk)?
+            .into_iter()
+            .map(|(id, parent_ref)| (self.instance_ref(id), self.instance_ref(parent_ref)))
+            .collect())
+    }
 
-        let mut subjects = vec![0; number_objects as usize];
-        let mut parents = vec![0; number_objects as usize];
+    /// The `Ref` given to the instance with the given referent in the file,
+    /// or `Ref::none()` if there isn't one.
+    fn instance_ref(&self, referent: i32) -> Ref {
+        match self.instances_by_ref.get(&referent) {
+            Some(instance) => instance.referent,
+            None => Ref::none(),
+        }
+    }
 
-        chunk.read_referent_array(&mut subjects)?;
-        chunk.read_referent_array(&mut parents)?;
+    /// The number of SharedStrings decoded so far.
+    pub(super) fn shared_string_count(&self) -> usize {
+        self.shared_strings.len()
+    }
 
-        for (id, parent_ref) in subjects.iter().copied().zip(parents.iter().copied()) {
-            if parent_ref == -1 {
-                self.root_instance_refs.push(id);
+    /// Takes all of the metadata decoded so far.
+    pub(super) fn take_metadata(&mut self) -> HashMap<String, String> {
+        std::mem::take(&mut self.metadata)
+    }
+
+    /// The class name and `Ref`s of the instances declared with the given type
+    /// ID.
+    pub(super) fn instances_of_type(&self, type_id: u32) -> Option<(&str, Vec<Ref>)> {
+        let type_info = self.type_infos.get(&type_id)?;
+        let refs = type_info
+            .referents
+            .iter()
+            .map(|&referent| self.instance_ref(referent))
+            .collect();
+
+        Some((&type_info.type_name, refs))
+    }
+
+    /// The name that values from a PROP chunk for the given type and property
+    /// are decoded into, and whether they're migrated from a deprecated
+    /// property. Returns `None` if the chunk would be skipped.
+    pub(super) fn decoded_property_name(
+        &self,
+        type_id: u32,
+        prop_name: &str,
+    ) -> Option<(String, bool)> {
+        let type_info = match self.type_infos.get(&type_id) {
+            Some(type_info) => type_info,
+            // Decoding this chunk will fail, which is good to find out about
+            // when its property is asked for.
+            None => return Some((prop_name.to_owned(), false)),
+        };
+
+        if type_info.skip_properties && prop_name != "Name" {
+            return None;
+        }
+
+        let descriptors =
+            match find_property_descriptors(self.database, &type_info.type_name, prop_name) {
+                Some(descriptors) => descriptors,
+                None => return Some((prop_name.to_owned(), false)),
+            };
+
+        match &descriptors.canonical.kind {
+            PropertyKind::Canonical {
+                serialization: PropertySerialization::DoesNotSerialize,
+            } => None,
+            PropertyKind::Canonical {
+                serialization: PropertySerialization::Migrate { property, .. },
+            } => Some((property.to_string(), true)),
+            _ => Some((descriptors.canonical.name.to_string(), false)),
+        }
+    }
+
+    /// If the values in a PROP chunk with the given type ID, property name,
+    /// and binary type are `BinaryString` or `SharedString` payloads that are
+    /// decoded as they're stored, returns the name and type they're decoded
+    /// into, so that they can be handed out without copying them. Returns
+    /// `None` for every other chunk.
+    pub(super) fn payload_property(
+        &mut self,
+        type_id: u32,
+        prop_name: &str,
+        binary_type_byte: u8,
+    ) -> Option<(String, VariantType)> {
+        let type_info = self.type_infos.get(&type_id)?;
+
+        let binary_type: Type = binary_type_byte.try_into().ok()?;
+        if !matches!(binary_type, Type::String | Type::SharedString) {
+            return None;
+        }
+
+        if type_info.skip_properties {
+            return None;
+        }
+
+        let (name, ty) =
+            match find_property_descriptors(self.database, &type_info.type_name, prop_name) {
+                Some(descriptors) => {
+                    if !matches!(
+                        &descriptors.canonical.kind,
+                        PropertyKind::Canonical {
+                            serialization: PropertySerialization::Serializes
+                                | PropertySerialization::SerializesAs(_)
+                        }
+                    ) {
+                        return None;
+                    }
+
+                    match &descriptors.canonical.data_type {
+                        DataType::Value(ty) => (descriptors.canonical.name.to_string(), *ty),
+                        _ => return None,
+                    }
+                }
+                None => (prop_name.to_owned(), binary_type.to_default_rbx_type()?),
+            };
+
+        match (binary_type, ty) {
+            (Type::String, VariantType::BinaryString)
+            | (Type::SharedString, VariantType::SharedString) => {}
+            _ => return None,
+        }
+
+        self.location.class_name = Some(type_info.type_name.clone());
+        self.location.property_name = Some(prop_name.to_owned());
+
+        Some((name, ty))
+    }
+
+    /// Builds the tree out of the instances decoded so far and takes it,
+    /// leaving enough of each instance behind that more of their properties
+    /// can be decoded with `take_properties`.
+    pub(super) fn take_tree(&mut self) -> WeakDom {
+        let referents: Vec<(i32, Ref)> = self
+            .instances_by_ref
+            .iter()
+            .map(|(&id, instance)| (id, instance.referent))
+            .collect();
+
+        let root_ref = self.tree.root_ref();
+        let instances_to_construct = self
+            .root_instance_refs
+            .iter()
+            .map(|&referent| (referent, root_ref))
+            .collect();
+        self.construct_instances(instances_to_construct);
+
+        for (id, referent) in referents {
+            self.instances_by_ref.insert(
+                id,
+                Instance {
+                    builder: InstanceBuilder::empty(),
+                    referent,
+                    properties: HashMap::new(),
+                    children: Vec::new(),
+                },
+            );
+        }
+
+        std::mem::replace(
+            &mut self.tree,
+            WeakDom::new(InstanceBuilder::new("DataModel")),
+        )
+    }
+
+    /// Takes all of the properties decoded so far for instances of the given
+    /// type ID, sorted by name for each instance.
+    pub(super) fn take_properties(&mut self, type_id: u32) -> Vec<(Ref, String, Variant)> {
+        let type_info = match self.type_infos.get(&type_id) {
+            Some(type_info) => type_info,
+            None => return Vec::new(),
+        };
+
+        let mut properties = Vec::new();
+
+        for referent in &type_info.referents {
+            let instance = self.instances_by_ref.get_mut(referent).unwrap();
+            let mut taken: Vec<_> = instance.properties.drain().collect();
+            taken.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+            let instance_ref = instance.referent;
+            properties.extend(
+                taken
+                    .into_iter()
+                    .map(|(name, value)| (instance_ref, name, value)),
+            );
+        }
+
+        properties
+    }
+
+    /// Reads which type and property a PROP chunk holds, returning the type ID
+    /// and property name as they're written in the file. Names are part of
+    /// the tree, so `Name` chunks are decoded right away; every other property
+    /// is left for [`apply_prop_chunk`][Self::apply_prop_chunk].
+    pub(super) fn read_prop_chunk_header(
+        &mut self,
+        chunk: &[u8],
+    ) -> Result<(u32, String), InnerError> {
+        let mut contents = chunk;
+        let type_id = contents.read_le_u32()?;
+        let prop_name = contents.read_string()?;
+
+        if prop_name == "Name" {
+            self.decode_prop_chunk(chunk)?;
+        }
+
+        Ok((type_id, prop_name))
+    }
+
+    /// Decodes a PROP chunk holding properties of the given type and moves
+    /// them onto the matching instances in `tree`. Instances that aren't in
+    /// `tree` are skipped.
+    ///
+    /// Values migrated from deprecated properties never replace the real
+    /// thing, no matter which chunk came first.
+    pub(super) fn apply_prop_chunk(
+        &mut self,
+        tree: &mut WeakDom,
+        type_id: u32,
+        chunk: &[u8],
+        migrated: bool,
+    ) -> Result<(), InnerError> {
+        self.decode_prop_chunk(chunk)?;
+
+        for (referent, name, value) in self.take_properties(type_id) {
+            let instance = match tree.get_by_ref_mut(referent) {
+                Some(instance) => instance,
+                None => continue,
+            };
+
+            if migrated {
+                instance.properties.entry(name).or_insert(value);
             } else {
-                let instance = self.instances_by_ref.get_mut(&parent_ref).unwrap();
-                instance.children.push(id);
+                instance.properties.insert(name, value);
             }
         }
 
@@ -1334,14 +2007,25 @@ impl<'a, R: Read> DeserializerState<'a, R> {
 
     /// Combines together all the decoded information to build and emplace
     /// instances in our tree.
+    pub(super) fn finish(self) -> DecodedDocument {
+        self.build_tree(false)
+    }
+
+    /// Like `finish`, but instances that never made it into the tree because
+    /// their parents weren't known are added to the top level of the tree.
+    pub(super) fn finish_recovering(self) -> DecodedDocument {
+        self.build_tree(true)
+    }
+
     #[profiling::function]
-    pub(super) fn finish(mut self) -> WeakDom {
+    fn build_tree(mut self, keep_orphans: bool) -> DecodedDocument {
         log::trace!("Constructing tree from deserialized data");
 
         // Track all the instances we need to construct. Order of construction
         // is important to preserve for both determinism and sometimes
         // functionality of models we handle.
         let mut instances_to_construct = VecDeque::new();
+        self.file_referents.reserve(self.instances_by_ref.len());
 
         // Any instance with a parent of -1 will be at the top level of the
         // tree. Because of the way rbx_dom_weak generally works, we need to
@@ -1351,15 +2035,104 @@ impl<'a, R: Read> DeserializerState<'a, R> {
             instances_to_construct.push_back((referent, root_ref));
         }
 
+        self.construct_instances(instances_to_construct);
+
+        if keep_orphans {
+            // Orphans that are children of other orphans are constructed along
+            // with their parents. Whatever's left after that is part of a
+            // cycle, which gets broken up arbitrarily.
+            let orphan_children: HashSet<i32> = self
+                .instances_by_ref
+                .values()
+                .flat_map(|instance| instance.children.iter().copied())
+                .collect();
+
+            let mut orphans: Vec<i32> = self.instances_by_ref.keys().copied().collect();
+            orphans
+                .sort_unstable_by_key(|referent| (orphan_children.contains(referent), *referent));
+
+            for referent in orphans {
+                if self.instances_by_ref.contains_key(&referent) {
+                    self.construct_instances(VecDeque::from(vec![(referent, root_ref)]));
+                }
+            }
+        }
+
+        DecodedDocument {
+            tree: self.tree,
+            metadata: self.metadata,
+            referents: self.file_referents,
+            signature: self.signature,
+            unknown_chunks: self.unknown_chunks,
+            warnings: self.warnings,
+        }
+    }
+
+    /// Builds the given instances and their descendants into the tree.
+    fn construct_instances(&mut self, mut instances_to_construct: VecDeque<(i32, Ref)>) {
         while let Some((referent, parent_ref)) = instances_to_construct.pop_front() {
-            let instance = self.instances_by_ref.remove(&referent).unwrap();
-            let id = self.tree.insert(parent_ref, instance.builder);
+            // An instance can only be missing here if the file has it as a
+            // child more than once, or as its own ancestor.
+            let mut instance = match self.instances_by_ref.remove(&referent) {
+                Some(instance) => instance,
+                None => continue,
+            };
+
+            if let Some(Variant::String(name)) = instance.properties.remove("Name") {
+                instance.builder.set_name(name);
+            }
+
+            let builder = instance.builder.with_properties(instance.properties);
+            let id = self.tree.insert(parent_ref, builder);
+            self.file_referents.insert(id, referent);
 
             for referent in instance.children {
                 instances_to_construct.push_back((referent, id));
             }
         }
+    }
+}
 
-        self.tree
+/// Finds the BrickColor with the given number, failing with an error about the
+/// given property if there isn't one.
+fn brick_color_from_number(
+    type_name: &str,
+    prop_name: &str,
+    number: u32,
+) -> Result<BrickColor, InnerError> {
+    number
+        .try_into()
+        .ok()
+        .and_then(BrickColor::from_number)
+        .ok_or_else(|| InnerError::InvalidPropData {
+            type_name: type_name.to_owned(),
+            prop_name: prop_name.to_owned(),
+            valid_value: "a valid BrickColor",
+            actual_value: number.to_string(),
+        })
+}
+
+/// Reads the pairs of instance and parent referents from a PRNT chunk.
+pub(super) fn read_prnt_chunk(mut chunk: &[u8]) -> Result<Vec<(i32, i32)>, InnerError> {
+    let version = chunk.read_u8()?;
+
+    if version != 0 {
+        return Err(InnerError::UnknownChunkVersion {
+            chunk_name: "PRNT",
+            version: version as u32,
+        });
     }
+
+    let number_objects = chunk.read_le_u32()?;
+
+    log::trace!("PRNT chunk ({} instances)", number_objects);
+
+    let number_objects = check_count(chunk, number_objects, 8)?;
+    let mut subjects = vec![0; number_objects];
+    let mut parents = vec![0; number_objects];
+
+    chunk.read_referent_array(&mut subjects)?;
+    chunk.read_referent_array(&mut parents)?;
+
+    Ok(subjects.into_iter().zip(parents).collect())
 }
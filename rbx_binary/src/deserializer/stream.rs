@@ -0,0 +1,205 @@
+use std::{collections::VecDeque, io::Read};
+
+use rbx_dom_weak::types::{Ref, Variant};
+
+use crate::core::RbxReadExt;
+
+use super::{
+    error::{Error, InnerError},
+    state::DeserializerState,
+};
+
+/// An event produced by a [`StreamDecoder`].
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum StreamEvent {
+    /// An entry in the file's metadata.
+    Metadata {
+        /// The metadata key, like `ExplicitAutoJoints`.
+        key: String,
+
+        /// The value of the entry.
+        value: String,
+    },
+
+    /// The file declares instances of a class. Each instance is given a `Ref`
+    /// that later events use to refer to it.
+    Instances {
+        /// The class name shared by all of the instances.
+        class_name: String,
+
+        /// The `Ref`s given to the instances, in the order they appear in the
+        /// file.
+        referents: Vec<Ref>,
+    },
+
+    /// An instance has a property with the given value. Names and values are
+    /// the same as they would be in a `WeakDom` decoded with the same
+    /// settings, including the instance's `Name` and any `Ref` properties.
+    Property {
+        /// The instance the property belongs to.
+        referent: Ref,
+
+        /// The name of the property.
+        name: String,
+
+        /// The value of the property.
+        value: Variant,
+    },
+
+    /// An instance has the given parent. Instances at the top of the file have
+    /// `Ref::none()` as their parent.
+    Parent {
+        /// The instance whose parent is given.
+        referent: Ref,
+
+        /// The instance's parent.
+        parent: Ref,
+    },
+}
+
+/// Decodes a binary model or place as a stream of [`StreamEvent`]s, without
+/// ever building a `WeakDom`. Created with [`Deserializer::stream`].
+///
+/// Chunks are read and decoded one at a time, and each chunk's properties are
+/// handed out before the next chunk is read. The only state kept between
+/// chunks is a small amount for each instance and the file's shared strings,
+/// so this is useful for tools that look through many large files.
+/// [`StreamDecoder::max_chunk_len`] can be used to put a bound on the rest.
+///
+/// `SIGN` chunks and chunks that rbx_binary doesn't understand are skipped.
+/// After an error is returned, the decoder produces no more events.
+///
+/// ## Example
+/// ```no_run
+/// use std::fs::File;
+/// use std::io::BufReader;
+///
+/// use rbx_binary::{Deserializer, StreamEvent};
+///
+/// let input = BufReader::new(File::open("Place.rbxl")?);
+///
+/// let mut script_count = 0;
+/// for event in Deserializer::new().stream(input)? {
+///     if let StreamEvent::Instances { class_name, referents } = event? {
+///         if class_name == "Script" {
+///             script_count += referents.len();
+///         }
+///     }
+/// }
+///
+/// println!("Place has {} scripts", script_count);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+///
+/// [`Deserializer::stream`]: crate::Deserializer::stream
+pub struct StreamDecoder<'a, R> {
+    state: DeserializerState<'a, R>,
+    pending: VecDeque<StreamEvent>,
+    max_chunk_len: usize,
+    finished: bool,
+}
+
+impl<'a, R: Read> StreamDecoder<'a, R> {
+    pub(super) fn new(state: DeserializerState<'a, R>) -> Self {
+        StreamDecoder {
+            state,
+            pending: VecDeque::new(),
+            max_chunk_len: usize::MAX,
+            finished: false,
+        }
+    }
+
+    /// Fails decoding when a chunk would be longer than `max_chunk_len`
+    /// bytes, compressed or decompressed, before any memory is set aside for
+    /// it. By default, chunks of any length are decoded.
+    pub fn max_chunk_len(self, max_chunk_len: usize) -> Self {
+        StreamDecoder {
+            max_chunk_len,
+            ..self
+        }
+    }
+
+    /// Reads the next chunk, queueing up any events it produces.
+    fn decode_next_chunk(&mut self) -> Result<(), InnerError> {
+        let chunk = self.state.next_chunk_with_limit(self.max_chunk_len)?;
+
+        match &chunk.name {
+            b"META" => {
+                self.state.decode_meta_chunk(&chunk.data)?;
+
+                let mut metadata: Vec<_> = self.state.take_metadata().into_iter().collect();
+                metadata.sort();
+
+                self.pending.extend(
+                    metadata
+                        .into_iter()
+                        .map(|(key, value)| StreamEvent::Metadata { key, value }),
+                );
+            }
+            b"SSTR" => self.state.decode_sstr_chunk(&chunk.data)?,
+            b"INST" => {
+                self.state.decode_inst_chunk(&chunk.data)?;
+
+                let type_id = chunk.data.as_slice().read_le_u32()?;
+                if let Some((class_name, referents)) = self.state.instances_of_type(type_id) {
+                    self.pending.push_back(StreamEvent::Instances {
+                        class_name: class_name.to_owned(),
+                        referents,
+                    });
+                }
+            }
+            b"PROP" => {
+                self.state.decode_prop_chunk(&chunk.data)?;
+
+                let type_id = chunk.data.as_slice().read_le_u32()?;
+                self.pending
+                    .extend(self.state.take_properties(type_id).into_iter().map(
+                        |(referent, name, value)| StreamEvent::Property {
+                            referent,
+                            name,
+                            value,
+                        },
+                    ));
+            }
+            b"PRNT" => {
+                let parents = self.state.decode_prnt_chunk_streaming(&chunk.data)?;
+
+                self.pending.extend(
+                    parents
+                        .into_iter()
+                        .map(|(referent, parent)| StreamEvent::Parent { referent, parent }),
+                );
+            }
+            b"END\0" => {
+                self.state.decode_end_chunk(&chunk.data)?;
+                self.finished = true;
+            }
+            _ => self.state.skip_unknown_chunk(&chunk.name),
+        }
+
+        self.state.report_progress()
+    }
+}
+
+impl<'a, R: Read> Iterator for StreamDecoder<'a, R> {
+    type Item = Result<StreamEvent, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(Ok(event));
+            }
+
+            if self.finished {
+                return None;
+            }
+
+            if let Err(err) = self.decode_next_chunk() {
+                self.finished = true;
+                self.pending.clear();
+                return Some(Err(self.state.error(err)));
+            }
+        }
+    }
+}
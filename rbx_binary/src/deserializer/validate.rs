@@ -0,0 +1,316 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    io::{self, Read},
+};
+
+use crate::core::{check_count, RbxReadExt};
+
+use super::{
+    error::{Error, InnerError},
+    header::FileHeader,
+    state::{read_prnt_chunk, DeserializerState},
+    Deserializer,
+};
+
+/// The results of [`Deserializer::validate`]. Its `Display` implementation
+/// lists every problem, for showing to people triaging a broken file.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct ValidationReport {
+    /// The number of chunks that were read.
+    pub chunks: usize,
+
+    /// The problems found in the file, in the order they were found. Problems
+    /// with a particular chunk say which chunk it was, and at most one is
+    /// reported for each chunk.
+    pub problems: Vec<Error>,
+}
+
+impl ValidationReport {
+    /// Returns whether no problems were found.
+    pub fn is_valid(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, output: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            output,
+            "{} chunks, {} problems",
+            self.chunks,
+            self.problems.len()
+        )?;
+
+        for problem in &self.problems {
+            write!(output, "\n{}", problem)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// What's been declared by the chunks checked so far.
+#[derive(Default)]
+struct Validator {
+    type_ids: HashSet<u32>,
+    referents: HashSet<i32>,
+    properties: HashSet<(u32, String)>,
+    parents: HashMap<i32, i32>,
+}
+
+pub(super) fn validate<R: Read>(
+    deserializer: &Deserializer,
+    mut reader: R,
+) -> Result<ValidationReport, Error> {
+    let header = FileHeader::decode(&mut reader)?;
+    let num_types = header.num_types;
+    let num_instances = header.num_instances;
+
+    let mut state = DeserializerState::from_header(deserializer, header, reader);
+    let mut validator = Validator::default();
+    let mut problems = Vec::new();
+    let mut chunks = 0;
+
+    loop {
+        let raw_chunk = match state.next_raw_chunk() {
+            Ok(raw_chunk) => raw_chunk,
+            Err(err) => {
+                problems.push(state.error(err));
+                break;
+            }
+        };
+
+        chunks += 1;
+
+        // Like when recovering, nothing after a header like this can be
+        // trusted.
+        if raw_chunk.reserved != 0 {
+            problems.push(state.error(InnerError::MalformedChunkHeader {
+                chunk_name: String::from_utf8_lossy(&raw_chunk.name).into_owned(),
+            }));
+            break;
+        }
+
+        let data = match raw_chunk.decompressed_inner() {
+            Ok(data) => data,
+            Err(err) => {
+                problems.push(state.error(err));
+                continue;
+            }
+        };
+
+        if data.len() != raw_chunk.len as usize {
+            problems.push(state.error(InnerError::ChunkLengthMismatch {
+                header_len: raw_chunk.len,
+                actual_len: data.len(),
+            }));
+            continue;
+        }
+
+        let result = match &raw_chunk.name {
+            b"META" => state.decode_meta_chunk(&data),
+            b"SSTR" => state.decode_sstr_chunk(&data),
+            b"INST" => validator.check_inst_chunk(&mut state, &data),
+            b"PROP" => validator.check_prop_chunk(&mut state, &data),
+            b"PRNT" => validator.check_prnt_chunk(&data),
+            _ => Ok(()),
+        };
+
+        if let Err(err) = result {
+            problems.push(state.error(err));
+        }
+
+        state.report_progress().map_err(|err| state.error(err))?;
+
+        if &raw_chunk.name == b"END\0" {
+            break;
+        }
+    }
+
+    problems.extend(
+        validator
+            .check_file(num_types, num_instances)
+            .into_iter()
+            .map(Error::from),
+    );
+
+    Ok(ValidationReport { chunks, problems })
+}
+
+impl Validator {
+    fn check_inst_chunk<R: Read>(
+        &mut self,
+        state: &mut DeserializerState<R>,
+        chunk: &[u8],
+    ) -> Result<(), InnerError> {
+        state.decode_inst_chunk(chunk)?;
+
+        let mut contents = chunk;
+        let type_id = contents.read_le_u32()?;
+        contents.read_string()?;
+        let object_format = contents.read_u8()?;
+        let number_instances = contents.read_le_u32()?;
+
+        let mut referents = vec![0; check_count(contents, number_instances, 4)?];
+        contents.read_referent_array(&mut referents)?;
+
+        if !self.type_ids.insert(type_id) {
+            return Err(InnerError::DuplicateTypeId { type_id });
+        }
+
+        let mut duplicate = None;
+        for &referent in &referents {
+            if !self.referents.insert(referent) && duplicate.is_none() {
+                duplicate = Some(referent);
+            }
+        }
+
+        if let Some(referent) = duplicate {
+            return Err(InnerError::DuplicateReferent { referent });
+        }
+
+        // Services have a marker byte for each instance after their referents.
+        let markers = if object_format == 0 {
+            0
+        } else {
+            referents.len()
+        };
+
+        if contents.len() < markers {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "INST chunk ended before its service markers",
+            )
+            .into());
+        }
+
+        check_fully_read(contents.len() - markers)
+    }
+
+    fn check_prop_chunk<R: Read>(
+        &mut self,
+        state: &mut DeserializerState<R>,
+        chunk: &[u8],
+    ) -> Result<(), InnerError> {
+        state.decode_prop_chunk(chunk)?;
+
+        let mut contents = chunk;
+        let type_id = contents.read_le_u32()?;
+        let prop_name = contents.read_string()?;
+
+        // The values aren't needed once they've been decoded.
+        state.take_properties(type_id);
+
+        if !self.properties.insert((type_id, prop_name)) {
+            return Err(InnerError::DuplicateProperty);
+        }
+
+        check_fully_read(state.unread_prop_bytes())
+    }
+
+    fn check_prnt_chunk(&mut self, chunk: &[u8]) -> Result<(), InnerError> {
+        for (referent, parent) in read_prnt_chunk(chunk)? {
+            if !self.referents.contains(&referent) {
+                return Err(InnerError::UndeclaredInstance { referent });
+            }
+
+            if parent != -1 && !self.referents.contains(&parent) {
+                return Err(InnerError::InvalidParent { referent: parent });
+            }
+
+            if self.parents.insert(referent, parent).is_some() {
+                return Err(InnerError::DuplicateParent { referent });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks the file as a whole, once every chunk has been read.
+    fn check_file(&self, num_types: u32, num_instances: u32) -> Vec<InnerError> {
+        let mut problems = Vec::new();
+
+        if self.type_ids.len() != num_types as usize {
+            problems.push(InnerError::HeaderCountMismatch {
+                counted: "types",
+                header: num_types,
+                actual: self.type_ids.len(),
+            });
+        }
+
+        if self.referents.len() != num_instances as usize {
+            problems.push(InnerError::HeaderCountMismatch {
+                counted: "instances",
+                header: num_instances,
+                actual: self.referents.len(),
+            });
+        }
+
+        let mut referents: Vec<i32> = self.referents.iter().copied().collect();
+        referents.sort_unstable();
+
+        let orphans: Vec<i32> = referents
+            .iter()
+            .copied()
+            .filter(|referent| !self.parents.contains_key(referent))
+            .collect();
+
+        if let Some(&referent) = orphans.first() {
+            problems.push(InnerError::MissingParents {
+                referent,
+                count: orphans.len(),
+            });
+        }
+
+        if let Some(referent) = self.find_cycle(&referents) {
+            problems.push(InnerError::ParentCycle { referent });
+        }
+
+        problems
+    }
+
+    /// Returns an instance that's its own ancestor, if there are any.
+    fn find_cycle(&self, referents: &[i32]) -> Option<i32> {
+        // Instances that are known to be descendants of the top of the file.
+        let mut rooted = HashSet::new();
+
+        for &start in referents {
+            let mut path = Vec::new();
+            let mut on_path = HashSet::new();
+            let mut current = start;
+
+            loop {
+                if current == -1 || rooted.contains(&current) {
+                    break;
+                }
+
+                if !on_path.insert(current) {
+                    return Some(current);
+                }
+
+                path.push(current);
+
+                current = match self.parents.get(&current) {
+                    Some(&parent) => parent,
+                    None => break,
+                };
+            }
+
+            rooted.extend(path);
+        }
+
+        None
+    }
+}
+
+/// Checks that there's nothing left of a chunk once its contents have been
+/// read.
+fn check_fully_read(unread: usize) -> Result<(), InnerError> {
+    if unread == 0 {
+        Ok(())
+    } else {
+        Err(InnerError::TrailingBytes { count: unread })
+    }
+}
@@ -57,6 +57,7 @@ mod cframe;
 mod chunk;
 mod core;
 mod deserializer;
+mod progress;
 mod serializer;
 mod types;
 
@@ -77,8 +78,17 @@ pub mod text_format {
 }
 
 pub use crate::{
-    deserializer::{Deserializer, Error as DecodeError},
-    serializer::{Error as EncodeError, Serializer},
+    chunk::UnknownChunk,
+    deserializer::{
+        ChunkReader, DecodedDocument, Deserializer, Error as DecodeError, FilePatch, LazyDocument,
+        RawChunk, RecoveredDocument, SliceBytes, SliceDecoder, SliceEvent, SliceValue,
+        StreamDecoder, StreamEvent, ValidationReport,
+    },
+    progress::Progress,
+    serializer::{
+        ChunkOrder, CompressionType, Error as EncodeError, ReferentStrategy, SerializeReport,
+        Serializer, SkippedProperty, StreamEncoder, TypeSupport,
+    },
 };
 
 /// Deserialize a Roblox binary model or place from a stream.
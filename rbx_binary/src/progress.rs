@@ -0,0 +1,69 @@
+use std::{fmt, ops::ControlFlow, sync::Arc};
+
+/// How far a decode or encode has gotten, which is passed to the callback set
+/// with [`Deserializer::on_progress`][crate::Deserializer::on_progress] or
+/// [`Serializer::on_progress`][crate::Serializer::on_progress].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Progress {
+    /// The number of chunks read or written so far.
+    pub chunks: usize,
+
+    /// The number of instances read or written so far. Instances are counted
+    /// once their `INST` chunk has been read or written.
+    pub instances: usize,
+
+    /// The number of instances there are in total, if it's known. When
+    /// decoding, this is the count in the file header, which is usually, but
+    /// not always, right. It's unknown when encoding with a
+    /// [`StreamEncoder`][crate::StreamEncoder].
+    pub total_instances: Option<usize>,
+}
+
+pub(crate) type ProgressCallback<'a> = Arc<dyn Fn(Progress) -> ControlFlow<()> + Send + Sync + 'a>;
+
+/// Keeps track of the progress of a decode or encode, and passes it on to the
+/// user's callback whenever it's asked to.
+#[derive(Clone, Default)]
+pub(crate) struct ProgressReporter<'a> {
+    callback: Option<ProgressCallback<'a>>,
+    pub progress: Progress,
+}
+
+impl<'a> ProgressReporter<'a> {
+    pub fn new(callback: Option<ProgressCallback<'a>>, total_instances: Option<usize>) -> Self {
+        ProgressReporter {
+            callback,
+            progress: Progress {
+                total_instances,
+                ..Progress::default()
+            },
+        }
+    }
+
+    /// Adds to the counts of chunks and instances, then reports them.
+    pub fn add_chunks(&mut self, chunks: usize, instances: usize) -> bool {
+        self.progress.chunks += chunks;
+        self.progress.instances += instances;
+        self.report()
+    }
+
+    /// Passes the current progress to the callback, returning whether it
+    /// asked for the work to keep going.
+    pub fn report(&self) -> bool {
+        match &self.callback {
+            Some(callback) => callback(self.progress).is_continue(),
+            None => true,
+        }
+    }
+}
+
+impl fmt::Debug for ProgressReporter<'_> {
+    fn fmt(&self, output: &mut fmt::Formatter) -> fmt::Result {
+        output
+            .debug_struct("ProgressReporter")
+            .field("callback", &self.callback.as_ref().map(|_| ".."))
+            .field("progress", &self.progress)
+            .finish()
+    }
+}
@@ -10,6 +10,15 @@ pub struct Error {
     source: Box<InnerError>,
 }
 
+impl Error {
+    /// Whether encoding stopped because the callback set with
+    /// [`Serializer::on_progress`][crate::Serializer::on_progress] asked it
+    /// to.
+    pub fn is_cancelled(&self) -> bool {
+        matches!(*self.source, InnerError::Cancelled)
+    }
+}
+
 impl From<InnerError> for Error {
     fn from(inner: InnerError) -> Self {
         Self {
@@ -59,4 +68,10 @@ pub(super) enum InnerError {
 
     #[error("The instance with referent {referent:?} was not present in the dom.")]
     InvalidInstanceId { referent: Ref },
+
+    #[error("Unknown chunks can't be named {name}, since rbx_binary writes chunks with that name")]
+    KnownChunkName { name: String },
+
+    #[error("Encoding was cancelled")]
+    Cancelled,
 }
@@ -1,13 +1,30 @@
 mod error;
+mod patch;
 mod state;
+mod stream;
 
-use std::io::Write;
+use std::{
+    collections::{HashMap, HashSet},
+    io::{Seek, Write},
+    ops::ControlFlow,
+    sync::Arc,
+};
 
-use rbx_dom_weak::{types::Ref, WeakDom};
+use lz4::block::CompressionMode;
+use rbx_dom_weak::{
+    types::{Ref, VariantType},
+    WeakDom,
+};
+
+use crate::{
+    chunk::{ChunkCodec, ChunkCompression, UnknownChunk},
+    deserializer::FilePatch,
+    progress::{Progress, ProgressCallback},
+};
 
 use self::state::SerializerState;
 
-pub use self::error::Error;
+pub use self::{error::Error, stream::StreamEncoder};
 
 /// A configurable serializer for Roblox binary models and places.
 ///
@@ -27,37 +44,372 @@ pub use self::error::Error;
 ///
 /// # Ok::<(), Box<dyn std::error::Error>>(())
 /// ```
+///
+/// ## Deterministic output
+/// Serializing the same tree with the same settings always gives the same
+/// bytes, no matter what order properties were added to instances in, so
+/// files can be compared or hashed to find out whether a tree has changed.
+/// `INST` and `PROP` chunks are written in order of their class names, and
+/// `PROP` chunks for the same class in order of their property names, with
+/// values in the order instances were found in the tree. Shared strings and
+/// metadata are sorted too.
+///
+/// ## Parallel compression
+/// With the `rayon` feature enabled, `INST` and `PROP` chunks are compressed
+/// on rayon's thread pool instead of one at a time, which speeds up writing
+/// large places. Chunks are still written in the same order, so the output is
+/// the same as without the feature.
 // future settings:
 // * reflection_database: Option<ReflectionDatabase> = default
 // * recursive: bool = true
 #[non_exhaustive]
-pub struct Serializer {}
+pub struct Serializer {
+    metadata: HashMap<String, String>,
+    signature: Option<Vec<u8>>,
+    unknown_chunks: Vec<UnknownChunk>,
+    compression: CompressionType,
+    compress_only_if_smaller: bool,
+    skipped_types: HashSet<VariantType>,
+    type_support: TypeSupport,
+    chunk_order: ChunkOrder,
+    referents: ReferentStrategy,
+    progress_callback: Option<ProgressCallback<'static>>,
+}
+
+/// How a [`Serializer`] compresses the chunks it writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum CompressionType {
+    /// Compress chunks with LZ4, like Roblox does. This is the default.
+    Lz4,
+
+    /// Compress chunks with LZ4's fast mode, using the given acceleration.
+    /// Higher values compress faster, but produce bigger files.
+    Lz4Fast(i32),
+
+    /// Compress chunks with LZ4's high compression mode, using the given
+    /// compression level from 1 to 12. Higher levels produce smaller files, but
+    /// compress more slowly. Decompressing is just as fast either way.
+    Lz4High(i32),
+
+    /// Compress chunks with ZSTD, using the given compression level from 1 to
+    /// 22. ZSTD produces smaller files than LZ4 at the cost of slower
+    /// compression, and Roblox reads it just as well.
+    Zstd(i32),
+
+    /// Don't compress chunks. Files are bigger, but they're easier to inspect
+    /// and to compress or diff as a whole.
+    None,
+}
+
+/// Which of the binary format's property types a [`Serializer`] writes.
+///
+/// Properties whose values would be written with a type that isn't supported
+/// are left out, like the ones left out by
+/// [`Serializer::skip_types`], and reported by
+/// [`Serializer::serialize_with_report`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum TypeSupport {
+    /// Write every type rbx_binary can write. This is the default.
+    All,
+
+    /// Only write types whose type ID in the file is at most the given ID.
+    ///
+    /// Roblox gives each new type the next unused ID, so a reader written for
+    /// an older version of the format understands every type up to the newest
+    /// one it knows about. For example, `UpTo(0x1E)` leaves out `UniqueId`
+    /// (`0x1F`), `Font` (`0x20`), and `SecurityCapabilities` (`0x21`). The IDs
+    /// of each type are listed in the [format's documentation][docs].
+    ///
+    /// [docs]: https://github.com/rojo-rbx/rbx-dom/blob/master/docs/binary.md#data-types
+    UpTo(u8),
+}
+
+/// The order a [`Serializer`] writes the chunks declaring instances and their
+/// properties in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ChunkOrder {
+    /// Write every `INST` chunk, then every `PROP` chunk, like Roblox does.
+    /// This is the default.
+    Roblox,
+
+    /// Write each class's `INST` chunk followed by its `PROP` chunks, one
+    /// class at a time. Readers that handle one class at a time, like
+    /// [`Deserializer::stream`][crate::Deserializer::stream], can finish with
+    /// each class before the next one starts. Roblox never writes files in
+    /// this order, so only use it for readers known to accept it.
+    ByClass,
+}
+
+/// A property that a [`Serializer`] left out, because of
+/// [`Serializer::skip_types`] or [`Serializer::type_support`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SkippedProperty {
+    /// The instance the property belongs to.
+    pub instance: Ref,
+
+    /// The name of the property, as it's stored on the instance.
+    pub name: String,
+
+    /// The type that caused the property to be left out. This is either the
+    /// type of its value, or the type it would have been written as.
+    pub ty: VariantType,
+}
+
+/// What [`Serializer::serialize_with_report`] learned while writing a file.
+#[derive(Debug, Clone, Default)]
+#[non_exhaustive]
+pub struct SerializeReport {
+    /// Every property that was left out of the file, in the order instances
+    /// were found in the tree.
+    pub skipped_properties: Vec<SkippedProperty>,
+}
+
+/// How a [`Serializer`] picks the referents that identify instances in the
+/// file it writes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ReferentStrategy {
+    /// Number instances from zero in the order they're written, like Roblox
+    /// does. This is the default.
+    Sequential,
+
+    /// Give instances the referents in the map, like the ones read into
+    /// [`DecodedDocument::referents`][crate::DecodedDocument::referents], so
+    /// that tools writing a file back out keep its referents the same.
+    ///
+    /// Instances that aren't in the map, or whose referent is `-1` or is
+    /// already taken by an instance written before them, get the lowest
+    /// referent that isn't taken.
+    Preserve(HashMap<Ref, i32>),
+}
+
+impl CompressionType {
+    fn chunk_compression(self, only_if_smaller: bool) -> ChunkCompression {
+        let codec = match self {
+            CompressionType::Lz4 => ChunkCodec::Lz4(CompressionMode::DEFAULT),
+            CompressionType::Lz4Fast(acceleration) => {
+                ChunkCodec::Lz4(CompressionMode::FAST(acceleration))
+            }
+            CompressionType::Lz4High(level) => {
+                ChunkCodec::Lz4(CompressionMode::HIGHCOMPRESSION(level))
+            }
+            CompressionType::Zstd(level) => ChunkCodec::Zstd(level),
+            CompressionType::None => return ChunkCompression::Uncompressed,
+        };
+
+        ChunkCompression::Compressed {
+            codec,
+            only_if_smaller,
+        }
+    }
+}
 
 impl Serializer {
     /// Create a new `Serializer` with the default settings.
     pub fn new() -> Self {
-        Serializer {}
+        Serializer {
+            metadata: HashMap::new(),
+            signature: None,
+            unknown_chunks: Vec::new(),
+            compression: CompressionType::Lz4,
+            compress_only_if_smaller: false,
+            skipped_types: HashSet::new(),
+            type_support: TypeSupport::All,
+            chunk_order: ChunkOrder::Roblox,
+            referents: ReferentStrategy::Sequential,
+            progress_callback: None,
+        }
+    }
+
+    /// Determines how chunks are compressed. The `END` and `SIGN` chunks are
+    /// never compressed.
+    pub fn compression(self, compression: CompressionType) -> Self {
+        Serializer {
+            compression,
+            ..self
+        }
+    }
+
+    /// Determines whether chunks that compression wouldn't make any smaller,
+    /// like ones containing already-compressed data, are written uncompressed
+    /// instead. Defaults to `false`, which compresses every chunk like Roblox
+    /// does.
+    pub fn compress_only_if_smaller(self, compress_only_if_smaller: bool) -> Self {
+        Serializer {
+            compress_only_if_smaller,
+            ..self
+        }
+    }
+
+    /// Leaves out every property whose value has one of the given types, for
+    /// writing files that older versions of Roblox or other tools can read.
+    /// Those reject files containing property types they don't know about,
+    /// like `Font`, `UniqueId`, or `SecurityCapabilities`. By default, every
+    /// property is written.
+    ///
+    /// Properties are checked against both the type of their value and the
+    /// type they're written with. The properties that are left out are
+    /// reported by [`serialize_with_report`][Serializer::serialize_with_report].
+    pub fn skip_types<I: IntoIterator<Item = VariantType>>(self, types: I) -> Self {
+        Serializer {
+            skipped_types: types.into_iter().collect(),
+            ..self
+        }
+    }
+
+    /// Determines which of the format's property types are written, leaving
+    /// out properties of any other type. Defaults to [`TypeSupport::All`].
+    ///
+    /// This works alongside [`skip_types`][Serializer::skip_types]; a property
+    /// is written only if neither leaves it out.
+    pub fn type_support(self, type_support: TypeSupport) -> Self {
+        Serializer {
+            type_support,
+            ..self
+        }
+    }
+
+    /// Determines the order of the `INST` and `PROP` chunks. Defaults to
+    /// [`ChunkOrder::Roblox`].
+    ///
+    /// [`serialize_patch`][Serializer::serialize_patch] keeps the order of the
+    /// file being patched instead.
+    pub fn chunk_order(self, chunk_order: ChunkOrder) -> Self {
+        Serializer {
+            chunk_order,
+            ..self
+        }
+    }
+
+    /// Determines how instances' referents are picked. Defaults to
+    /// [`ReferentStrategy::Sequential`].
+    ///
+    /// [`StreamEncoder`] always numbers instances sequentially.
+    pub fn referents(self, referents: ReferentStrategy) -> Self {
+        Serializer { referents, ..self }
+    }
+
+    /// Sets the entries of the `META` chunk to write, like the ones read into
+    /// [`DecodedDocument::metadata`][crate::DecodedDocument::metadata]. They're
+    /// written in order of their keys. Defaults to none, which writes no `META`
+    /// chunk.
+    pub fn metadata(self, metadata: HashMap<String, String>) -> Self {
+        Serializer { metadata, ..self }
+    }
+
+    /// Sets the contents of the `SIGN` chunk to write, like the one read into
+    /// [`DecodedDocument::signature`][crate::DecodedDocument::signature].
+    /// Defaults to `None`, which writes no `SIGN` chunk.
+    ///
+    /// Roblox signs the rest of the file, so a signature is only valid if the
+    /// instances it's written with are unchanged from the file it came from.
+    pub fn signature(self, signature: Option<Vec<u8>>) -> Self {
+        Serializer { signature, ..self }
+    }
+
+    /// Sets chunks that rbx_binary doesn't understand to write as-is, like the
+    /// ones read into [`DecodedDocument::unknown_chunks`][crate::DecodedDocument::unknown_chunks].
+    /// They're written after the instances, before any `SIGN` chunk.
+    ///
+    /// Serializing returns an error if any of them have the name of a chunk
+    /// that rbx_binary writes itself.
+    pub fn unknown_chunks(self, unknown_chunks: Vec<UnknownChunk>) -> Self {
+        Serializer {
+            unknown_chunks,
+            ..self
+        }
+    }
+
+    /// Calls `callback` after each chunk is written, with how many chunks and
+    /// instances have been written so far, so that long encodes can report
+    /// their progress. If `callback` returns `ControlFlow::Break`, encoding
+    /// stops and fails with an error whose [`Error::is_cancelled`] is `true`,
+    /// leaving the output incomplete.
+    pub fn on_progress<F>(self, callback: F) -> Self
+    where
+        F: Fn(Progress) -> ControlFlow<()> + Send + Sync + 'static,
+    {
+        Serializer {
+            progress_callback: Some(Arc::new(callback)),
+            ..self
+        }
     }
 
     /// Serialize a Roblox binary model or place into the given stream using
     /// this serializer.
     pub fn serialize<W: Write>(&self, writer: W, dom: &WeakDom, refs: &[Ref]) -> Result<(), Error> {
+        self.serialize_with_report(writer, dom, refs)?;
+
+        Ok(())
+    }
+
+    /// Serialize a Roblox binary model or place into the given stream using
+    /// this serializer, like [`serialize`][Serializer::serialize], and report
+    /// what was left out of it.
+    pub fn serialize_with_report<W: Write>(
+        &self,
+        writer: W,
+        dom: &WeakDom,
+        refs: &[Ref],
+    ) -> Result<SerializeReport, Error> {
         profiling::scope!("rbx_binary::seserialize");
 
-        let mut serializer = SerializerState::new(dom, writer);
+        let mut serializer = SerializerState::new(dom, writer, self.chunk_compression());
+
+        serializer.skip_types(&self.skipped_types, self.type_support);
 
         serializer.add_instances(refs)?;
-        serializer.generate_referents();
+        serializer.report_progress(self.progress_callback.clone());
+        match &self.referents {
+            ReferentStrategy::Sequential => serializer.generate_referents(),
+            ReferentStrategy::Preserve(referents) => serializer.preserve_referents(referents),
+        }
         serializer.write_header()?;
-        serializer.serialize_metadata()?;
+        serializer.serialize_metadata(&self.metadata)?;
         serializer.serialize_shared_strings()?;
-        serializer.serialize_instances()?;
-        serializer.serialize_properties()?;
+        serializer.serialize_types(self.chunk_order)?;
         serializer.serialize_parents()?;
+        serializer.serialize_unknown_chunks(&self.unknown_chunks)?;
+        serializer.serialize_signature(self.signature.as_deref())?;
         serializer.serialize_end()?;
 
+        Ok(SerializeReport {
+            skipped_properties: serializer.take_skipped_properties(),
+        })
+    }
+
+    /// Write a file read with [`Deserializer::patch`][crate::Deserializer::patch]
+    /// into the given stream, along with the changes made to it.
+    ///
+    /// The chunks of the file that weren't affected are copied as they were,
+    /// and only new and rewritten chunks use this serializer's compression,
+    /// skipped types, and type support. The file keeps its own metadata and
+    /// unknown chunks, so the metadata, signature, unknown chunks, and
+    /// referent strategy set on this serializer aren't used.
+    pub fn serialize_patch<W: Write>(&self, writer: W, patch: &FilePatch) -> Result<(), Error> {
+        profiling::scope!("rbx_binary::serialize_patch");
+
+        patch::write_patch(self, writer, patch)?;
+
         Ok(())
     }
+
+    /// Start writing a Roblox binary model or place into the given stream one
+    /// batch of instances at a time, using this serializer. The header of the
+    /// file is written right away, and filled in by
+    /// [`StreamEncoder::finish`].
+    pub fn stream<W: Write + Seek>(&self, writer: W) -> Result<StreamEncoder<W>, Error> {
+        Ok(StreamEncoder::new(self, writer)?)
+    }
+
+    fn chunk_compression(&self) -> ChunkCompression {
+        self.compression
+            .chunk_compression(self.compress_only_if_smaller)
+    }
 }
 
 impl Default for Serializer {
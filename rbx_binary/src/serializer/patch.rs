@@ -0,0 +1,148 @@
+use std::{collections::HashMap, io::Write};
+
+use crate::{chunk::ChunkBuilder, core::RbxWriteExt, deserializer::FilePatch};
+
+use super::{
+    error::InnerError,
+    state::{self, Progress, SerializerState},
+    Serializer,
+};
+
+/// Writes out a patched file. Chunks are grouped by kind, with the file's
+/// unchanged chunks of each kind first and the new ones after them, so that
+/// every instance is declared before any property or parent refers to it.
+pub(super) fn write_patch<W: Write>(
+    serializer: &Serializer,
+    output: W,
+    patch: &FilePatch,
+) -> Result<(), InnerError> {
+    let tree = patch.tree();
+
+    // Instances from the file whose class isn't being rewritten keep their
+    // chunks, and so their referents. So do removed instances, so that their
+    // referents aren't given to anything else that `Ref`s in the file could
+    // end up pointing to.
+    let mut kept_referents = HashMap::new();
+    let mut rewritten_referents = HashMap::new();
+
+    for (&instance_ref, &referent) in patch.file_referents() {
+        if patch.is_rewritten(instance_ref) && tree.get_by_ref(instance_ref).is_some() {
+            rewritten_referents.insert(instance_ref, referent);
+        } else {
+            kept_referents.insert(instance_ref, referent);
+        }
+    }
+
+    let kept_referent_count = kept_referents.len();
+
+    // Rewritten classes keep their type IDs. If a class was split over more
+    // than one type, the lowest ID is used, and the others are left empty.
+    let mut reused_type_ids = HashMap::new();
+    for (type_id, class) in patch.rewritten_types() {
+        reused_type_ids.entry(class.to_owned()).or_insert(type_id);
+    }
+
+    let mut state = SerializerState::new(tree, output, serializer.chunk_compression());
+
+    state.skip_types(&serializer.skipped_types, serializer.type_support);
+    state.reuse_type_ids(reused_type_ids);
+    state.resume(Progress {
+        next_type_id: patch.next_type_id(),
+        referents: kept_referents,
+        next_shared_string_id: patch.shared_string_count(),
+        ..Progress::default()
+    });
+    state.add_instances_where(tree.root().children(), |instance| {
+        let referent = instance.referent();
+        !patch.file_referents().contains_key(&referent) || patch.is_rewritten(referent)
+    })?;
+    state.preserve_referents(&rewritten_referents);
+
+    let written_type_ids = state.type_ids();
+    let empty_types: Vec<(u32, &str)> = patch
+        .rewritten_types()
+        .filter(|(type_id, _)| !written_type_ids.contains(type_id))
+        .collect();
+
+    let num_types = state.next_type_id();
+    let num_instances =
+        patch.kept_instance_count() + (state.referent_count() - kept_referent_count);
+    state::write_header(state.output_mut(), num_types, num_instances as u32)?;
+
+    for raw in patch.kept_chunks(b"META") {
+        raw.write_to(state.output_mut())?;
+    }
+
+    for raw in patch.kept_chunks(b"SSTR") {
+        raw.write_to(state.output_mut())?;
+    }
+    state.serialize_shared_strings()?;
+
+    for raw in patch.kept_chunks(b"INST") {
+        raw.write_to(state.output_mut())?;
+    }
+    state.serialize_instances(None)?;
+    for (type_id, class) in empty_types {
+        write_empty_inst(state.output_mut(), type_id, class, serializer)?;
+    }
+
+    for raw in patch.kept_chunks(b"PROP") {
+        raw.write_to(state.output_mut())?;
+    }
+    state.serialize_properties(None)?;
+
+    match patch.kept_parents() {
+        Some(parents) => write_parents(state.output_mut(), &parents, serializer)?,
+        None => {
+            for raw in patch.kept_chunks(b"PRNT") {
+                raw.write_to(state.output_mut())?;
+            }
+        }
+    }
+    state.serialize_parents()?;
+
+    for raw in patch.other_chunks() {
+        raw.write_to(state.output_mut())?;
+    }
+    state.serialize_end()?;
+
+    Ok(())
+}
+
+/// Writes an INST chunk declaring a type with no instances, so that a type ID
+/// that's no longer used doesn't leave a gap.
+fn write_empty_inst<W: Write>(
+    output: W,
+    type_id: u32,
+    class: &str,
+    serializer: &Serializer,
+) -> Result<(), InnerError> {
+    let mut chunk = ChunkBuilder::new(b"INST", serializer.chunk_compression());
+
+    chunk.write_le_u32(type_id)?;
+    chunk.write_string(class)?;
+    chunk.write_bool(false)?;
+    chunk.write_le_u32(0)?;
+
+    chunk.dump(output)?;
+
+    Ok(())
+}
+
+/// Writes a PRNT chunk with the given pairs of instance and parent referents.
+fn write_parents<W: Write>(
+    output: W,
+    parents: &[(i32, i32)],
+    serializer: &Serializer,
+) -> Result<(), InnerError> {
+    let mut chunk = ChunkBuilder::new(b"PRNT", serializer.chunk_compression());
+
+    chunk.write_u8(0)?; // PRNT version 0
+    chunk.write_le_u32(parents.len() as u32)?;
+    chunk.write_referent_array(parents.iter().map(|&(referent, _)| referent))?;
+    chunk.write_referent_array(parents.iter().map(|&(_, parent)| parent))?;
+
+    chunk.dump(output)?;
+
+    Ok(())
+}
@@ -2,16 +2,18 @@ use std::{
     borrow::{Borrow, Cow},
     collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
     convert::TryInto,
-    io::Write,
-    u32,
+    io::{self, Write},
+    mem, u32,
 };
 
+use md5::{Digest, Md5};
 use rbx_dom_weak::{
     types::{
         Attributes, Axes, BinaryString, BrickColor, CFrame, Color3, Color3uint8, ColorSequence,
-        ColorSequenceKeypoint, Content, Enum, Faces, Font, Matrix3, NumberRange, NumberSequence,
-        NumberSequenceKeypoint, PhysicalProperties, Ray, Rect, Ref, SharedString, Tags, UDim,
-        UDim2, UniqueId, Variant, VariantType, Vector2, Vector3, Vector3int16,
+        ColorSequenceKeypoint, Content, Enum, Faces, Font, MaterialColors, Matrix3, NumberRange,
+        NumberSequence, NumberSequenceKeypoint, PhysicalProperties, Ray, Rect, Ref,
+        SecurityCapabilities, SharedString, SmoothGrid, Tags, UDim, UDim2, UniqueId, Variant,
+        VariantType, Vector2, Vector3, Vector3int16,
     },
     Instance, WeakDom,
 };
@@ -20,17 +22,23 @@ use rbx_reflection::{ClassDescriptor, ClassTag, DataType};
 
 use crate::{
     cframe,
-    chunk::{ChunkBuilder, ChunkCompression},
+    chunk::{ChunkBuilder, ChunkCompression, FinishedChunk, UnknownChunk},
     core::{
         find_property_descriptors, RbxWriteExt, FILE_MAGIC_HEADER, FILE_SIGNATURE, FILE_VERSION,
     },
+    progress::{ProgressCallback, ProgressReporter},
     types::Type,
 };
 
-use super::error::InnerError;
+use super::{error::InnerError, ChunkOrder, SkippedProperty, TypeSupport};
 
 static FILE_FOOTER: &[u8] = b"</roblox>";
 
+/// The names of the chunks that the serializer writes itself.
+static KNOWN_CHUNK_NAMES: &[&[u8; 4]] = &[
+    b"META", b"SSTR", b"INST", b"PROP", b"PRNT", b"SIGN", b"END\0",
+];
+
 /// Represents all of the state during a single serialization session. A new
 /// `BinarySerializer` object should be created every time we want to serialize
 /// a binary model file.
@@ -60,6 +68,43 @@ pub(super) struct SerializerState<'dom, W> {
     /// A map of SharedStrings to where it is in the SSTR chunk. This is used
     /// for writing PROP chunks.
     shared_string_ids: HashMap<SharedString, u32>,
+
+    /// How chunks should be compressed, apart from the ones that must never
+    /// be compressed.
+    compression: ChunkCompression,
+
+    /// The ID that the first SharedString in the SSTR chunk has. This is only
+    /// nonzero when continuing on from an earlier batch of instances.
+    first_shared_string_id: u32,
+
+    /// The types of property that shouldn't be written at all.
+    skipped_types: HashSet<VariantType>,
+
+    /// Which of the format's types can be written.
+    type_support: TypeSupport,
+
+    /// The properties left out because of `skipped_types` or `type_support`.
+    skipped_properties: Vec<SkippedProperty>,
+
+    /// Passes on how far encoding has gotten to the user's callback, if any.
+    reporter: ProgressReporter<'static>,
+}
+
+/// The IDs used up by earlier batches of a streaming serialization, which a
+/// new `SerializerState` can continue on from.
+#[derive(Debug, Default)]
+pub(super) struct Progress {
+    /// The next type ID that should be assigned.
+    pub next_type_id: u32,
+
+    /// The referents of all of the instances written so far.
+    pub referents: HashMap<Ref, i32>,
+
+    /// The ID that the next SharedString should have.
+    pub next_shared_string_id: u32,
+
+    /// How many chunks and instances have been written so far.
+    pub reporter: ProgressReporter<'static>,
 }
 
 /// An instance class that our serializer knows about. We should have one struct
@@ -81,8 +126,8 @@ struct TypeInfo<'dom> {
     /// this type. Properties are keyed by their canonical name, and only one
     /// entry should be present for each logical property.
     ///
-    /// Stored in a sorted map to try to ensure that we write out properties in
-    /// a deterministic order.
+    /// Stored in a sorted map so that we write out properties in a
+    /// deterministic order, which `Serializer` promises.
     properties: BTreeMap<Cow<'static, str>, PropInfo>,
 
     /// A reference to the type's class descriptor from rbx_reflection, if this
@@ -93,6 +138,10 @@ struct TypeInfo<'dom> {
     /// processed. This helps us avoid traversing the reflection database
     /// multiple times if there are many copies of the same kind of instance.
     properties_visited: HashSet<(Cow<'static, str>, VariantType)>,
+
+    /// The visited properties that are left out because of the type they're
+    /// written as, along with that type.
+    properties_skipped: HashMap<(Cow<'static, str>, VariantType), VariantType>,
 }
 
 /// A property on a specific class that our serializer knows about.
@@ -145,13 +194,17 @@ struct TypeInfos<'dom> {
     /// A map containing one entry for each unique ClassName discovered in the
     /// DOM.
     ///
-    /// These are stored sorted so that we naturally iterate over them in order
-    /// and improve our chances of being deterministic.
+    /// These are stored sorted so that we naturally iterate over them in order,
+    /// which keeps our output deterministic.
     values: BTreeMap<String, TypeInfo<'dom>>,
 
     /// The next type ID that should be assigned if a type is discovered and
     /// added to the serializer.
     next_type_id: u32,
+
+    /// Type IDs that are given to particular classes instead of the next one,
+    /// for rewriting the chunks of a class in an existing file.
+    reused_type_ids: HashMap<String, u32>,
 }
 
 impl<'dom> TypeInfos<'dom> {
@@ -159,6 +212,7 @@ impl<'dom> TypeInfos<'dom> {
         Self {
             values: BTreeMap::new(),
             next_type_id: 0,
+            reused_type_ids: HashMap::new(),
         }
     }
 
@@ -166,8 +220,14 @@ impl<'dom> TypeInfos<'dom> {
     /// one and returns a reference to it if not.
     fn get_or_create(&mut self, class: &str) -> &mut TypeInfo<'dom> {
         if !self.values.contains_key(class) {
-            let type_id = self.next_type_id;
-            self.next_type_id += 1;
+            let type_id = match self.reused_type_ids.get(class) {
+                Some(&type_id) => type_id,
+                None => {
+                    let type_id = self.next_type_id;
+                    self.next_type_id += 1;
+                    type_id
+                }
+            };
 
             let class_descriptor = rbx_reflection_database::get().classes.get(class);
 
@@ -206,6 +266,7 @@ impl<'dom> TypeInfos<'dom> {
                     properties,
                     class_descriptor,
                     properties_visited: HashSet::new(),
+                    properties_skipped: HashMap::new(),
                 },
             );
         }
@@ -217,7 +278,7 @@ impl<'dom> TypeInfos<'dom> {
 }
 
 impl<'dom, W: Write> SerializerState<'dom, W> {
-    pub fn new(dom: &'dom WeakDom, output: W) -> Self {
+    pub fn new(dom: &'dom WeakDom, output: W, compression: ChunkCompression) -> Self {
         SerializerState {
             dom,
             output,
@@ -226,13 +287,111 @@ impl<'dom, W: Write> SerializerState<'dom, W> {
             type_infos: TypeInfos::new(),
             shared_strings: Vec::new(),
             shared_string_ids: HashMap::new(),
+            compression,
+            first_shared_string_id: 0,
+            skipped_types: HashSet::new(),
+            type_support: TypeSupport::All,
+            skipped_properties: Vec::new(),
+            reporter: ProgressReporter::default(),
+        }
+    }
+
+    /// Leave out every property with one of the given types, or that would be
+    /// written as a type outside of `type_support`.
+    ///
+    /// This must be called before any instances are added.
+    pub fn skip_types(&mut self, skipped_types: &HashSet<VariantType>, type_support: TypeSupport) {
+        self.skipped_types = skipped_types.clone();
+        self.type_support = type_support;
+    }
+
+    /// Takes the properties that have been left out so far.
+    pub fn take_skipped_properties(&mut self) -> Vec<SkippedProperty> {
+        mem::take(&mut self.skipped_properties)
+    }
+
+    /// Continue on from earlier batches of instances, so that the IDs given
+    /// out by this serializer don't overlap with theirs. Instances from earlier
+    /// batches can be referred to as parents and by Ref properties.
+    ///
+    /// This must be called before any instances are added.
+    pub fn resume(&mut self, progress: Progress) {
+        self.type_infos.next_type_id = progress.next_type_id;
+        self.id_to_referent = progress.referents;
+        self.first_shared_string_id = progress.next_shared_string_id;
+        self.reporter = progress.reporter;
+    }
+
+    /// Give instances of the given classes the given type IDs, instead of
+    /// new ones.
+    ///
+    /// This must be called before any instances are added.
+    pub fn reuse_type_ids(&mut self, type_ids: HashMap<String, u32>) {
+        self.type_infos.reused_type_ids = type_ids;
+    }
+
+    /// Report progress to the given callback after each chunk is written.
+    ///
+    /// This must be called after all instances are added.
+    pub fn report_progress(&mut self, callback: Option<ProgressCallback<'static>>) {
+        self.reporter = ProgressReporter::new(callback, Some(self.relevant_instances.len()));
+    }
+
+    /// The type ID that the next new type would be given.
+    pub fn next_type_id(&self) -> u32 {
+        self.type_infos.next_type_id
+    }
+
+    /// The type IDs of every type that will be written.
+    pub fn type_ids(&self) -> HashSet<u32> {
+        self.type_infos
+            .values
+            .values()
+            .map(|type_info| type_info.type_id)
+            .collect()
+    }
+
+    /// The number of instances that have been given referents, including
+    /// ones from earlier batches.
+    pub fn referent_count(&self) -> usize {
+        self.id_to_referent.len()
+    }
+
+    /// Where the binary output is being written, for writing chunks in
+    /// between the ones this serializer writes.
+    pub fn output_mut(&mut self) -> &mut W {
+        &mut self.output
+    }
+
+    /// The IDs used up by this serializer and any batches it continued on
+    /// from.
+    pub fn progress(self) -> Progress {
+        Progress {
+            next_type_id: self.type_infos.next_type_id,
+            referents: self.id_to_referent,
+            next_shared_string_id: self.first_shared_string_id + self.shared_strings.len() as u32,
+            reporter: self.reporter,
         }
     }
 
     /// Mark the given instance IDs and all of their descendants as intended for
     /// serialization with this serializer.
-    #[profiling::function]
     pub fn add_instances(&mut self, referents: &[Ref]) -> Result<(), InnerError> {
+        self.add_instances_where(referents, |_| true)
+    }
+
+    /// Like `add_instances`, but only the instances that `include` returns
+    /// true for are marked. The descendants of other instances are still
+    /// looked at.
+    #[profiling::function]
+    pub fn add_instances_where<F>(
+        &mut self,
+        referents: &[Ref],
+        include: F,
+    ) -> Result<(), InnerError>
+    where
+        F: Fn(&Instance) -> bool,
+    {
         let mut to_visit = VecDeque::new();
         to_visit.extend(referents);
 
@@ -242,17 +401,48 @@ impl<'dom, W: Write> SerializerState<'dom, W> {
                 .get_by_ref(referent)
                 .ok_or(InnerError::InvalidInstanceId { referent })?;
 
-            self.relevant_instances.push(referent);
-            self.collect_type_info(instance)?;
+            if include(instance) {
+                self.relevant_instances.push(referent);
+                self.collect_type_info(instance)?;
+            }
 
             to_visit.extend(instance.children());
         }
 
+        // Instances that are missing a SharedString property are written with
+        // its default value, so the default needs to be in the SSTR chunk too.
+        for type_info in self.type_infos.values.values() {
+            for (prop_name, prop_info) in &type_info.properties {
+                let default = match prop_info.default_value.as_ref() {
+                    Variant::SharedString(default) => default,
+                    _ => continue,
+                };
+
+                if self.shared_string_ids.contains_key(default) {
+                    continue;
+                }
+
+                let is_missing = type_info.instances.iter().any(|instance| {
+                    !instance.properties.contains_key(prop_name.as_ref())
+                        && !prop_info
+                            .aliases
+                            .iter()
+                            .any(|alias| instance.properties.contains_key(alias))
+                });
+
+                if is_missing {
+                    self.shared_string_ids.insert(default.clone(), 0);
+                    self.shared_strings.push(default.clone());
+                }
+            }
+        }
+
         // Sort shared_strings by their hash, to ensure they are deterministically added
         // into the SSTR chunk, then assign them corresponding ids
         self.shared_strings.sort_by_key(SharedString::hash);
         for (id, shared_string) in self.shared_strings.iter().cloned().enumerate() {
-            self.shared_string_ids.insert(shared_string, id as u32);
+            self.shared_string_ids
+                .insert(shared_string, self.first_shared_string_id + id as u32);
         }
 
         log::debug!("Type info discovered: {:#?}", self.type_infos);
@@ -271,6 +461,15 @@ impl<'dom, W: Write> SerializerState<'dom, W> {
         type_info.instances.push(instance);
 
         for (prop_name, prop_value) in &instance.properties {
+            if self.skipped_types.contains(&prop_value.ty()) {
+                self.skipped_properties.push(skipped_property(
+                    instance,
+                    prop_name,
+                    prop_value.ty(),
+                ));
+                continue;
+            }
+
             // Discover and track any shared strings we come across.
             if let Variant::SharedString(shared_string) = prop_value {
                 if !self.shared_string_ids.contains_key(shared_string) {
@@ -282,10 +481,13 @@ impl<'dom, W: Write> SerializerState<'dom, W> {
             }
 
             // Skip this property+value type pair if we've already seen it.
-            if type_info
-                .properties_visited
-                .contains(&(Cow::Borrowed(prop_name), prop_value.ty()))
-            {
+            let visited = (Cow::Borrowed(prop_name.as_str()), prop_value.ty());
+            if type_info.properties_visited.contains(&visited) {
+                if let Some(&ty) = type_info.properties_skipped.get(&visited) {
+                    self.skipped_properties
+                        .push(skipped_property(instance, prop_name, ty));
+                }
+
                 continue;
             }
 
@@ -335,6 +537,18 @@ impl<'dom, W: Write> SerializerState<'dom, W> {
                 }
             }
 
+            if self.skipped_types.contains(&serialized_ty)
+                || !is_supported(self.type_support, serialized_ty)
+            {
+                type_info.properties_skipped.insert(
+                    (Cow::Owned(prop_name.clone()), prop_value.ty()),
+                    serialized_ty,
+                );
+                self.skipped_properties
+                    .push(skipped_property(instance, prop_name, serialized_ty));
+                continue;
+            }
+
             // In order to prevent cloning canonical_name in a rare branch,
             // we conditionally clone here if we'll need canonical_name after
             // it's inserted into type_info.properties.
@@ -407,35 +621,66 @@ impl<'dom, W: Write> SerializerState<'dom, W> {
     pub fn generate_referents(&mut self) {
         self.id_to_referent.reserve(self.relevant_instances.len());
 
+        // Referents from earlier batches are already taken.
+        let first_referent = self.id_to_referent.len();
+
         for (next_referent, id) in self.relevant_instances.iter().enumerate() {
             self.id_to_referent
-                .insert(*id, next_referent.try_into().unwrap());
+                .insert(*id, (first_referent + next_referent).try_into().unwrap());
         }
 
         log::trace!("Referents constructed: {:#?}", self.id_to_referent);
     }
 
-    pub fn write_header(&mut self) -> Result<(), InnerError> {
-        log::trace!("Writing header");
+    /// Like `generate_referents`, but instances are given the referents in
+    /// `referents` if they can be: if it has one for them that isn't -1, the
+    /// null referent, and that isn't taken already. Other instances get the
+    /// lowest referents that are free.
+    #[profiling::function]
+    pub fn preserve_referents(&mut self, referents: &HashMap<Ref, i32>) {
+        self.id_to_referent.reserve(self.relevant_instances.len());
 
-        self.output.write_all(FILE_MAGIC_HEADER)?;
-        self.output.write_all(FILE_SIGNATURE)?;
-        self.output.write_le_u16(FILE_VERSION)?;
+        let mut taken: HashSet<i32> = self.id_to_referent.values().copied().collect();
+        let mut unassigned = Vec::new();
 
-        self.output
-            .write_le_u32(self.type_infos.values.len() as u32)?;
-        self.output
-            .write_le_u32(self.relevant_instances.len() as u32)?;
-        self.output.write_all(&[0; 8])?;
+        for &id in &self.relevant_instances {
+            match referents.get(&id) {
+                Some(&referent) if referent != -1 && taken.insert(referent) => {
+                    self.id_to_referent.insert(id, referent);
+                }
+                _ => unassigned.push(id),
+            }
+        }
 
-        Ok(())
+        let mut next_referent = 0;
+
+        for id in unassigned {
+            while taken.contains(&next_referent) {
+                next_referent += 1;
+            }
+
+            taken.insert(next_referent);
+            self.id_to_referent.insert(id, next_referent);
+        }
+
+        log::trace!("Referents constructed: {:#?}", self.id_to_referent);
+    }
+
+    pub fn write_header(&mut self) -> Result<(), InnerError> {
+        write_header(
+            &mut self.output,
+            self.type_infos.values.len() as u32,
+            self.relevant_instances.len() as u32,
+        )
     }
 
     /// Write out any metadata about this file, stored in a chunk named META.
-    pub fn serialize_metadata(&mut self) -> Result<(), InnerError> {
-        log::trace!("Writing metadata (currently no-op)");
-        // TODO: There is no concept of metadata in a dom yet.
-        Ok(())
+    pub fn serialize_metadata(
+        &mut self,
+        metadata: &HashMap<String, String>,
+    ) -> Result<(), InnerError> {
+        let written = write_metadata(&mut self.output, metadata, self.compression)?;
+        report_chunks(&mut self.reporter, written as usize, 0)
     }
 
     /// Write out all of the SharedStrings in this file, if any exist,
@@ -448,36 +693,64 @@ impl<'dom, W: Write> SerializerState<'dom, W> {
             return Ok(());
         }
 
-        let mut chunk = ChunkBuilder::new(b"SSTR", ChunkCompression::Compressed);
+        let mut chunk = ChunkBuilder::new(b"SSTR", self.compression);
 
         chunk.write_le_u32(0)?; // SSTR version number
         chunk.write_le_u32(self.shared_strings.len() as u32)?;
 
         for shared_string in &self.shared_strings {
-            // Better to write nothing than write half a hash
-            chunk.write_all(&[0; 16])?;
+            // Roblox identifies shared strings by the MD5 hash of their
+            // contents, so we compute the same hash here.
+            chunk.write_all(&Md5::digest(shared_string.data()))?;
             chunk.write_binary_string(shared_string.data())?;
         }
 
         chunk.dump(&mut self.output)?;
+        report_chunks(&mut self.reporter, 1, 0)?;
 
         Ok(())
     }
 
-    /// Write out the declarations of all instances, stored in a series of
-    /// chunks named INST.
+    /// Write out the INST and PROP chunks for every type, in the given order.
+    pub fn serialize_types(&mut self, order: ChunkOrder) -> Result<(), InnerError> {
+        match order {
+            ChunkOrder::Roblox => {
+                self.serialize_instances(None)?;
+                self.serialize_properties(None)
+            }
+            ChunkOrder::ByClass => {
+                let type_names: Vec<String> = self.type_infos.values.keys().cloned().collect();
+
+                for type_name in &type_names {
+                    self.serialize_instances(Some(type_name))?;
+                    self.serialize_properties(Some(type_name))?;
+                }
+
+                Ok(())
+            }
+        }
+    }
+
+    /// Write out the declarations of all instances, or only those of the
+    /// given class, stored in a series of chunks named INST.
     #[profiling::function]
-    pub fn serialize_instances(&mut self) -> Result<(), InnerError> {
+    pub fn serialize_instances(&mut self, only_class: Option<&str>) -> Result<(), InnerError> {
         log::trace!("Writing instance chunks");
 
+        let mut queue = ChunkQueue::default();
+
         for (type_name, type_info) in &self.type_infos.values {
+            if only_class.is_some_and(|class| class != type_name) {
+                continue;
+            }
+
             log::trace!(
                 "Writing chunk for {} ({} instances)",
                 type_name,
                 type_info.instances.len()
             );
 
-            let mut chunk = ChunkBuilder::new(b"INST", ChunkCompression::Compressed);
+            let mut chunk = ChunkBuilder::new(b"INST", self.compression);
 
             chunk.write_le_u32(type_info.type_id)?;
             chunk.write_string(type_name)?;
@@ -512,20 +785,31 @@ impl<'dom, W: Write> SerializerState<'dom, W> {
                 }
             }
 
-            chunk.dump(&mut self.output)?;
+            queue.push(
+                &mut self.output,
+                &mut self.reporter,
+                chunk,
+                type_info.instances.len(),
+            )?;
         }
 
-        Ok(())
+        queue.flush(&mut self.output, &mut self.reporter)
     }
 
     /// Write out batch declarations of property values for the instances
-    /// previously defined in the INST chunks. Property data is contained in
-    /// chunks named PROP.
+    /// previously defined in the INST chunks, or only those of the given
+    /// class. Property data is contained in chunks named PROP.
     #[profiling::function]
-    pub fn serialize_properties(&mut self) -> Result<(), InnerError> {
+    pub fn serialize_properties(&mut self, only_class: Option<&str>) -> Result<(), InnerError> {
         log::trace!("Writing properties");
 
+        let mut queue = ChunkQueue::default();
+
         for (type_name, type_info) in &self.type_infos.values {
+            if only_class.is_some_and(|class| class != type_name) {
+                continue;
+            }
+
             for (prop_name, prop_info) in &type_info.properties {
                 profiling::scope!("serialize property", prop_name.borrow());
                 log::trace!(
@@ -535,7 +819,7 @@ impl<'dom, W: Write> SerializerState<'dom, W> {
                     prop_info.prop_type
                 );
 
-                let mut chunk = ChunkBuilder::new(b"PROP", ChunkCompression::Compressed);
+                let mut chunk = ChunkBuilder::new(b"PROP", self.compression);
 
                 chunk.write_le_u32(type_info.type_id)?;
                 chunk.write_string(&prop_info.serialized_name)?;
@@ -612,6 +896,14 @@ impl<'dom, W: Write> SerializerState<'dom, W> {
                                     let buf = value.encode();
                                     chunk.write_binary_string(&buf)?;
                                 }
+                                Variant::MaterialColors(value) => {
+                                    let buf = value.encode();
+                                    chunk.write_binary_string(&buf)?;
+                                }
+                                Variant::SmoothGrid(value) => {
+                                    let buf = value.encode();
+                                    chunk.write_binary_string(&buf)?;
+                                }
                                 Variant::Attributes(value) => {
                                     let mut buf = Vec::new();
 
@@ -625,7 +917,7 @@ impl<'dom, W: Write> SerializerState<'dom, W> {
                                     return type_mismatch(
                                         i,
                                         &rbx_value,
-                                        "String, Content, Tags, Attributes, or BinaryString",
+                                        "String, Content, Tags, Attributes, MaterialColors, SmoothGrid, or BinaryString",
                                     );
                                 }
                             }
@@ -739,7 +1031,7 @@ impl<'dom, W: Write> SerializerState<'dom, W> {
                                 chunk.write_le_f32(value.origin.z)?;
                                 chunk.write_le_f32(value.direction.x)?;
                                 chunk.write_le_f32(value.direction.y)?;
-                                chunk.write_le_f32(value.direction.x)?;
+                                chunk.write_le_f32(value.direction.z)?;
                             } else {
                                 return type_mismatch(i, &rbx_value, "Ray");
                             }
@@ -1131,13 +1423,26 @@ impl<'dom, W: Write> SerializerState<'dom, W> {
 
                         chunk.write_interleaved_bytes::<16>(&blobs)?;
                     }
+                    Type::SecurityCapabilities => {
+                        let mut buf = Vec::with_capacity(values.len());
+
+                        for (i, rbx_value) in values {
+                            if let Variant::SecurityCapabilities(value) = rbx_value.as_ref() {
+                                buf.push(value.bits() as i64);
+                            } else {
+                                return type_mismatch(i, &rbx_value, "SecurityCapabilities");
+                            }
+                        }
+
+                        chunk.write_interleaved_i64_array(buf.into_iter())?;
+                    }
                 }
 
-                chunk.dump(&mut self.output)?;
+                queue.push(&mut self.output, &mut self.reporter, chunk, 0)?;
             }
         }
 
-        Ok(())
+        queue.flush(&mut self.output, &mut self.reporter)
     }
 
     /// Write out the hierarchical relations between instances, stored in a
@@ -1146,7 +1451,7 @@ impl<'dom, W: Write> SerializerState<'dom, W> {
     pub fn serialize_parents(&mut self) -> Result<(), InnerError> {
         log::trace!("Writing parent relationships");
 
-        let mut chunk = ChunkBuilder::new(b"PRNT", ChunkCompression::Compressed);
+        let mut chunk = ChunkBuilder::new(b"PRNT", self.compression);
 
         chunk.write_u8(0)?; // PRNT version 0
         chunk.write_le_u32(self.relevant_instances.len() as u32)?;
@@ -1176,22 +1481,24 @@ impl<'dom, W: Write> SerializerState<'dom, W> {
         chunk.write_referent_array(parent_referents)?;
 
         chunk.dump(&mut self.output)?;
+        report_chunks(&mut self.reporter, 1, 0)?;
 
         Ok(())
     }
 
-    /// Write the fixed, uncompressed end chunk used to verify that the file
-    /// hasn't been truncated mistakenly. This chunk is named END\0, with a zero
-    /// byte at the end.
-    #[profiling::function]
-    pub fn serialize_end(&mut self) -> Result<(), InnerError> {
-        log::trace!("Writing file end");
+    pub fn serialize_unknown_chunks(&mut self, chunks: &[UnknownChunk]) -> Result<(), InnerError> {
+        write_unknown_chunks(&mut self.output, chunks, self.compression)?;
+        report_chunks(&mut self.reporter, chunks.len(), 0)
+    }
 
-        let mut end = ChunkBuilder::new(b"END\0", ChunkCompression::Uncompressed);
-        end.write_all(FILE_FOOTER)?;
-        end.dump(&mut self.output)?;
+    pub fn serialize_signature(&mut self, signature: Option<&[u8]>) -> Result<(), InnerError> {
+        write_signature(&mut self.output, signature)?;
+        report_chunks(&mut self.reporter, signature.is_some() as usize, 0)
+    }
 
-        Ok(())
+    pub fn serialize_end(&mut self) -> Result<(), InnerError> {
+        write_end(&mut self.output)?;
+        report_chunks(&mut self.reporter, 1, 0)
     }
 
     /// Equivalent to Instance:GetFullName() from Roblox.
@@ -1272,7 +1579,225 @@ impl<'dom, W: Write> SerializerState<'dom, W> {
             VariantType::Attributes => Variant::Attributes(Attributes::new()),
             VariantType::UniqueId => Variant::UniqueId(UniqueId::now().unwrap()),
             VariantType::Font => Variant::Font(Font::default()),
+            VariantType::MaterialColors => Variant::MaterialColors(MaterialColors::default()),
+            VariantType::SmoothGrid => Variant::SmoothGrid(SmoothGrid::new()),
+            VariantType::SecurityCapabilities => {
+                Variant::SecurityCapabilities(SecurityCapabilities::default())
+            }
             _ => return None,
         })
     }
 }
+
+/// Count `chunks` more chunks and `instances` more instances as written, and
+/// report it if any chunks were. Fails if the user asked for encoding to stop.
+pub(super) fn report_chunks(
+    reporter: &mut ProgressReporter,
+    chunks: usize,
+    instances: usize,
+) -> Result<(), InnerError> {
+    if chunks == 0 || reporter.add_chunks(chunks, instances) {
+        Ok(())
+    } else {
+        Err(InnerError::Cancelled)
+    }
+}
+
+/// How many bytes of chunks a [`ChunkQueue`] holds onto before compressing
+/// and writing them, so that encoding a large tree doesn't need a second copy
+/// of the whole file in memory.
+const MAX_QUEUED_BYTES: usize = 64 * 1024 * 1024;
+
+/// Chunks that have been built but not written yet, along with how many
+/// instances each declares. With the `rayon` feature, queued chunks are
+/// compressed on multiple threads, then written in the order they were
+/// queued in, so the output is the same either way. Without it, each chunk is
+/// written as soon as it's queued.
+#[derive(Default)]
+struct ChunkQueue {
+    chunks: Vec<(ChunkBuilder, usize)>,
+    queued_bytes: usize,
+}
+
+impl ChunkQueue {
+    fn push<W: Write>(
+        &mut self,
+        output: W,
+        reporter: &mut ProgressReporter,
+        chunk: ChunkBuilder,
+        instances: usize,
+    ) -> Result<(), InnerError> {
+        self.queued_bytes += chunk.len();
+        self.chunks.push((chunk, instances));
+
+        if cfg!(not(feature = "rayon")) || self.queued_bytes >= MAX_QUEUED_BYTES {
+            self.flush(output, reporter)?;
+        }
+
+        Ok(())
+    }
+
+    /// Compresses and writes every queued chunk.
+    fn flush<W: Write>(
+        &mut self,
+        mut output: W,
+        reporter: &mut ProgressReporter,
+    ) -> Result<(), InnerError> {
+        self.queued_bytes = 0;
+
+        for (chunk, instances) in finish_chunks(mem::take(&mut self.chunks)) {
+            chunk?.dump(&mut output)?;
+            report_chunks(reporter, 1, instances)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "rayon")]
+fn finish_chunks(chunks: Vec<(ChunkBuilder, usize)>) -> Vec<(io::Result<FinishedChunk>, usize)> {
+    use rayon::prelude::*;
+
+    chunks
+        .into_par_iter()
+        .map(|(chunk, instances)| (chunk.finish(), instances))
+        .collect()
+}
+
+#[cfg(not(feature = "rayon"))]
+fn finish_chunks(chunks: Vec<(ChunkBuilder, usize)>) -> Vec<(io::Result<FinishedChunk>, usize)> {
+    chunks
+        .into_iter()
+        .map(|(chunk, instances)| (chunk.finish(), instances))
+        .collect()
+}
+
+/// Whether properties written as the given type are allowed by `type_support`.
+fn is_supported(type_support: TypeSupport, ty: VariantType) -> bool {
+    match (type_support, Type::from_rbx_type(ty)) {
+        (TypeSupport::UpTo(max_id), Some(binary_type)) => binary_type as u8 <= max_id,
+        _ => true,
+    }
+}
+
+fn skipped_property(instance: &Instance, name: &str, ty: VariantType) -> SkippedProperty {
+    log::debug!(
+        "Leaving out property {}.{} of type {:?}",
+        instance.name,
+        name,
+        ty
+    );
+
+    SkippedProperty {
+        instance: instance.referent(),
+        name: name.to_owned(),
+        ty,
+    }
+}
+
+/// Write the file header, which says how many types and instances the file
+/// contains.
+pub(super) fn write_header<W: Write>(
+    mut output: W,
+    num_types: u32,
+    num_instances: u32,
+) -> Result<(), InnerError> {
+    log::trace!("Writing header");
+
+    output.write_all(FILE_MAGIC_HEADER)?;
+    output.write_all(FILE_SIGNATURE)?;
+    output.write_le_u16(FILE_VERSION)?;
+
+    output.write_le_u32(num_types)?;
+    output.write_le_u32(num_instances)?;
+    output.write_all(&[0; 8])?;
+
+    Ok(())
+}
+
+/// Write out the given metadata in a chunk named META, sorted by key so that
+/// the output is deterministic. Returns whether a chunk was written, which is
+/// only the case if there's any metadata.
+pub(super) fn write_metadata<W: Write>(
+    output: W,
+    metadata: &HashMap<String, String>,
+    compression: ChunkCompression,
+) -> Result<bool, InnerError> {
+    if metadata.is_empty() {
+        return Ok(false);
+    }
+
+    log::trace!("Writing metadata");
+
+    let mut entries: Vec<_> = metadata.iter().collect();
+    entries.sort();
+
+    let mut chunk = ChunkBuilder::new(b"META", compression);
+    chunk.write_le_u32(entries.len() as u32)?;
+
+    for (key, value) in entries {
+        chunk.write_string(key)?;
+        chunk.write_string(value)?;
+    }
+
+    chunk.dump(output)?;
+
+    Ok(true)
+}
+
+/// Write out chunks that rbx_binary doesn't understand as-is.
+#[profiling::function]
+pub(super) fn write_unknown_chunks<W: Write>(
+    mut output: W,
+    chunks: &[UnknownChunk],
+    compression: ChunkCompression,
+) -> Result<(), InnerError> {
+    for chunk in chunks {
+        if KNOWN_CHUNK_NAMES.contains(&&chunk.name) {
+            return Err(InnerError::KnownChunkName {
+                name: String::from_utf8_lossy(&chunk.name).into_owned(),
+            });
+        }
+
+        log::trace!("Writing unknown chunk {:?}", chunk.name);
+
+        let mut builder = ChunkBuilder::new(&chunk.name, compression);
+        builder.write_all(&chunk.data)?;
+        builder.dump(&mut output)?;
+    }
+
+    Ok(())
+}
+
+pub(super) fn write_signature<W: Write>(
+    output: W,
+    signature: Option<&[u8]>,
+) -> Result<(), InnerError> {
+    let signature = match signature {
+        Some(signature) => signature,
+        None => return Ok(()),
+    };
+
+    log::trace!("Writing signature");
+
+    // Signatures are effectively random data, so compressing them wouldn't
+    // save anything.
+    let mut chunk = ChunkBuilder::new(b"SIGN", ChunkCompression::Uncompressed);
+    chunk.write_all(signature)?;
+    chunk.dump(output)?;
+
+    Ok(())
+}
+
+/// Write the fixed, uncompressed end chunk used to verify that the file
+/// hasn't been truncated mistakenly. This chunk is named END\0, with a zero
+/// byte at the end.
+pub(super) fn write_end<W: Write>(output: W) -> Result<(), InnerError> {
+    log::trace!("Writing file end");
+
+    let mut end = ChunkBuilder::new(b"END\0", ChunkCompression::Uncompressed);
+    end.write_all(FILE_FOOTER)?;
+    end.dump(output)?;
+
+    Ok(())
+}
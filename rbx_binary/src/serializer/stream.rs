@@ -0,0 +1,163 @@
+use std::{
+    collections::HashSet,
+    io::{Seek, SeekFrom, Write},
+};
+
+use rbx_dom_weak::{
+    types::{Ref, VariantType},
+    WeakDom,
+};
+
+use crate::{
+    chunk::{ChunkCompression, UnknownChunk},
+    core::RbxWriteExt,
+    progress::ProgressReporter,
+};
+
+use super::{
+    error::{Error, InnerError},
+    state::{self, Progress, SerializerState},
+    ChunkOrder, Serializer, TypeSupport,
+};
+
+/// The offset of the type and instance counts from the start of the file.
+const HEADER_COUNTS_OFFSET: u64 = 16;
+
+/// Encodes a binary model or place one batch of instances at a time, writing
+/// out each batch's chunks as it goes. Created with [`Serializer::stream`].
+///
+/// Every batch is written with its own set of chunks, so only the batch being
+/// written has to be kept in memory. Between batches, the encoder remembers a
+/// small amount for each instance written, so that later batches can refer
+/// to earlier ones:
+///
+/// - An instance whose parent was written in an earlier batch is written as a
+///   child of it. Otherwise, the instance is written at the top of the file.
+///   A generator can keep its containers in a `WeakDom`, writing and
+///   destroying their contents a batch at a time.
+/// - `Ref` properties can point to instances in the same batch or earlier
+///   batches. Refs to anything else are written as null.
+///
+/// The file header has to contain the number of instances in the file, so it
+/// is filled in by [`StreamEncoder::finish`], which must be called for the
+/// file to be valid.
+///
+/// ## Example
+/// ```no_run
+/// use std::fs::File;
+/// use std::io::BufWriter;
+///
+/// use rbx_binary::Serializer;
+/// use rbx_dom_weak::{InstanceBuilder, WeakDom};
+///
+/// let mut dom = WeakDom::new(InstanceBuilder::new("Folder").with_name("Parts"));
+/// let folder = dom.root_ref();
+///
+/// let output = BufWriter::new(File::create("ManyParts.rbxm")?);
+/// let mut encoder = Serializer::new().stream(output)?;
+/// encoder.write_batch(&dom, &[folder])?;
+///
+/// for _ in 0..1000 {
+///     let parts: Vec<_> = (0..1000)
+///         .map(|_| dom.insert(folder, InstanceBuilder::new("Part")))
+///         .collect();
+///
+///     encoder.write_batch(&dom, &parts)?;
+///
+///     for part in parts {
+///         dom.destroy(part);
+///     }
+/// }
+///
+/// encoder.finish()?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct StreamEncoder<W> {
+    compression: ChunkCompression,
+    unknown_chunks: Vec<UnknownChunk>,
+    signature: Option<Vec<u8>>,
+    skipped_types: HashSet<VariantType>,
+    type_support: TypeSupport,
+    chunk_order: ChunkOrder,
+    output: W,
+    header_position: u64,
+    progress: Progress,
+}
+
+impl<W: Write + Seek> StreamEncoder<W> {
+    pub(super) fn new(serializer: &Serializer, mut output: W) -> Result<Self, InnerError> {
+        let header_position = output.stream_position()?;
+
+        // The counts in the header are filled in once we know them.
+        state::write_header(&mut output, 0, 0)?;
+
+        let mut reporter = ProgressReporter::new(serializer.progress_callback.clone(), None);
+        let wrote_metadata = state::write_metadata(
+            &mut output,
+            &serializer.metadata,
+            serializer.chunk_compression(),
+        )?;
+        state::report_chunks(&mut reporter, wrote_metadata as usize, 0)?;
+
+        Ok(StreamEncoder {
+            compression: serializer.chunk_compression(),
+            unknown_chunks: serializer.unknown_chunks.clone(),
+            signature: serializer.signature.clone(),
+            skipped_types: serializer.skipped_types.clone(),
+            type_support: serializer.type_support,
+            chunk_order: serializer.chunk_order,
+            output,
+            header_position,
+            progress: Progress {
+                reporter,
+                ..Progress::default()
+            },
+        })
+    }
+
+    /// Write the given instances and all of their descendants to the file.
+    ///
+    /// Each instance should only be written once.
+    pub fn write_batch(&mut self, dom: &WeakDom, refs: &[Ref]) -> Result<(), Error> {
+        profiling::scope!("rbx_binary::StreamEncoder::write_batch");
+
+        let mut serializer = SerializerState::new(dom, &mut self.output, self.compression);
+
+        serializer.skip_types(&self.skipped_types, self.type_support);
+        serializer.resume(std::mem::take(&mut self.progress));
+        serializer.add_instances(refs)?;
+        serializer.generate_referents();
+        serializer.serialize_shared_strings()?;
+        serializer.serialize_types(self.chunk_order)?;
+        serializer.serialize_parents()?;
+
+        self.progress = serializer.progress();
+
+        Ok(())
+    }
+
+    /// Finish the file, filling in its header, and return the writer.
+    pub fn finish(self) -> Result<W, Error> {
+        Ok(self.finish_inner()?)
+    }
+
+    fn finish_inner(mut self) -> Result<W, InnerError> {
+        state::write_unknown_chunks(&mut self.output, &self.unknown_chunks, self.compression)?;
+        state::write_signature(&mut self.output, self.signature.as_deref())?;
+        state::write_end(&mut self.output)?;
+
+        let trailing_chunks = self.unknown_chunks.len() + self.signature.is_some() as usize + 1;
+        state::report_chunks(&mut self.progress.reporter, trailing_chunks, 0)?;
+
+        let end_position = self.output.stream_position()?;
+
+        self.output
+            .seek(SeekFrom::Start(self.header_position + HEADER_COUNTS_OFFSET))?;
+        self.output.write_le_u32(self.progress.next_type_id)?;
+        self.output
+            .write_le_u32(self.progress.referents.len() as u32)?;
+        self.output.seek(SeekFrom::Start(end_position))?;
+
+        Ok(self.output)
+    }
+}
@@ -1,9 +1,19 @@
+use std::convert::TryInto;
+
 use rbx_dom_weak::{
-    types::{Color3, Color3uint8, Ref, Region3, Vector3},
-    InstanceBuilder, WeakDom,
+    types::{
+        BinaryString, CFrame, Color3, Color3uint8, Faces, Font, FontStyle, FontWeight,
+        MaterialColors, Matrix3, Ray, Ref, Region3, SecurityCapabilities, SharedString, SmoothGrid,
+        Tags, TerrainMaterial, UniqueId, Variant, VariantType, Vector3, Voxel,
+    },
+    InstanceBuilder, RoundTrip, WeakDom,
 };
 
-use crate::{text_deserializer::DecodedModel, to_writer};
+use crate::{
+    chunk::Chunk, deserializer::FileHeader, text_deserializer::DecodedModel, to_writer, ChunkOrder,
+    ChunkReader, CompressionType, Deserializer, Serializer, SliceEvent, SliceValue, StreamEvent,
+    TypeSupport, UnknownChunk,
+};
 
 /// A basic test to make sure we can serialize the simplest instance: a Folder.
 #[test]
@@ -153,3 +163,2130 @@ fn part_color() {
     let decoded = DecodedModel::from_reader(buf.as_slice());
     insta::assert_yaml_snapshot!(decoded);
 }
+
+/// Ensures that the order of children is preserved when round-tripping,
+/// including after being changed with `WeakDom::sort_children_by`.
+#[test]
+fn child_order() {
+    let mut tree = WeakDom::new(InstanceBuilder::new("Folder").with_children(vec![
+        InstanceBuilder::new("Folder").with_name("C"),
+        InstanceBuilder::new("Folder").with_name("A"),
+        InstanceBuilder::new("Folder").with_name("B"),
+    ]));
+    let root_ref = tree.root_ref();
+    tree.sort_children_by(root_ref, |a, b| b.name.cmp(&a.name));
+
+    let mut buffer = Vec::new();
+    to_writer(&mut buffer, &tree, &[root_ref]).expect("failed to encode model");
+
+    let decoded = crate::from_reader(buffer.as_slice()).expect("failed to decode model");
+    let folder = decoded.get_by_ref(decoded.root().children()[0]).unwrap();
+    let names: Vec<&str> = folder
+        .children()
+        .iter()
+        .map(|child| decoded.get_by_ref(*child).unwrap().name.as_str())
+        .collect();
+
+    assert_eq!(names, ["C", "B", "A"]);
+}
+
+/// Ensures that Terrain's MaterialColors round-trip as a typed value.
+#[test]
+fn terrain_material_colors() {
+    let mut colors = MaterialColors::default();
+    colors.set(TerrainMaterial::Rock, Color3uint8::new(12, 34, 56));
+
+    let tree = WeakDom::new(
+        InstanceBuilder::new("Folder")
+            .with_child(InstanceBuilder::new("Terrain").with_property("MaterialColors", colors)),
+    );
+    let folder = tree.root();
+
+    let mut buffer = Vec::new();
+    to_writer(&mut buffer, &tree, folder.children()).expect("failed to encode model");
+
+    let decoded = crate::from_reader(buffer.as_slice()).expect("failed to decode model");
+    let terrain = decoded.get_by_ref(decoded.root().children()[0]).unwrap();
+
+    assert_eq!(
+        terrain.properties.get("MaterialColors"),
+        Some(&Variant::MaterialColors(colors))
+    );
+}
+
+/// Ensures that Terrain's SmoothGrid round-trips as typed voxels, and that
+/// PhysicsGrid, which isn't decoded, round-trips unchanged.
+#[test]
+fn terrain_smooth_grid() {
+    let mut grid = SmoothGrid::new();
+    grid.set_voxel([0, 0, 0], Voxel::full(TerrainMaterial::Rock));
+    grid.set_voxel([-40, 3, 70], Voxel::full(TerrainMaterial::Grass));
+
+    let physics_grid = BinaryString::from(b"\x02\x03Physics".to_vec());
+
+    let tree = WeakDom::new(
+        InstanceBuilder::new("Folder").with_child(
+            InstanceBuilder::new("Terrain")
+                .with_property("SmoothGrid", grid.clone())
+                .with_property("PhysicsGrid", physics_grid.clone()),
+        ),
+    );
+    let folder = tree.root();
+
+    let mut buffer = Vec::new();
+    to_writer(&mut buffer, &tree, folder.children()).expect("failed to encode model");
+
+    let decoded = crate::from_reader(buffer.as_slice()).expect("failed to decode model");
+    let terrain = decoded.get_by_ref(decoded.root().children()[0]).unwrap();
+
+    assert_eq!(
+        terrain.properties.get("SmoothGrid"),
+        Some(&Variant::SmoothGrid(grid))
+    );
+    assert_eq!(
+        terrain.properties.get("PhysicsGrid"),
+        Some(&Variant::BinaryString(physics_grid))
+    );
+}
+
+/// Ensures that Model pivots round-trip, whether or not they're set.
+#[test]
+fn model_world_pivot() {
+    let pivot = CFrame::new(
+        Vector3::new(1.0, 2.0, 3.0),
+        Matrix3::new(
+            Vector3::new(0.0, 0.0, 1.0),
+            Vector3::new(0.0, 1.0, 0.0),
+            Vector3::new(-1.0, 0.0, 0.0),
+        ),
+    );
+
+    let tree = WeakDom::new(
+        InstanceBuilder::new("Folder")
+            .with_child(
+                InstanceBuilder::new("Model")
+                    .with_name("Pivoted")
+                    .with_property("WorldPivotData", Some(pivot)),
+            )
+            .with_child(
+                InstanceBuilder::new("Model")
+                    .with_name("Unpivoted")
+                    .with_property("WorldPivotData", Variant::OptionalCFrame(None)),
+            ),
+    );
+    let folder = tree.root();
+
+    let mut buffer = Vec::new();
+    to_writer(&mut buffer, &tree, folder.children()).expect("failed to encode model");
+
+    let decoded = crate::from_reader(buffer.as_slice()).expect("failed to decode model");
+    let pivots: Vec<_> = decoded
+        .root()
+        .children()
+        .iter()
+        .map(|&referent| {
+            decoded
+                .get_by_ref(referent)
+                .unwrap()
+                .properties
+                .get("WorldPivotData")
+        })
+        .collect();
+
+    assert_eq!(
+        pivots,
+        [
+            Some(&Variant::OptionalCFrame(Some(pivot))),
+            Some(&Variant::OptionalCFrame(None)),
+        ]
+    );
+}
+
+/// Ensures that axis-aligned rotations are written with the one-byte IDs that
+/// Roblox uses, and that other rotations, even ones that are close, are
+/// written in full.
+#[test]
+fn cframe_rotation_ids() {
+    let quarter_turn = Matrix3::new(
+        Vector3::new(1.0, 0.0, 0.0),
+        Vector3::new(0.0, 0.0, -1.0),
+        Vector3::new(0.0, 1.0, 0.0),
+    );
+    let scaled = Matrix3::new(
+        Vector3::new(0.5, 0.0, 0.0),
+        Vector3::new(0.0, 1.0, 0.0),
+        Vector3::new(0.0, 0.0, 1.0),
+    );
+
+    let encode = |orientation: Matrix3| {
+        let cframe = CFrame::new(Vector3::new(1.0, 2.0, 3.0), orientation);
+        let tree = WeakDom::new(
+            InstanceBuilder::new("Folder")
+                .with_child(InstanceBuilder::new("CFrameValue").with_property("Value", cframe)),
+        );
+
+        let mut buffer = Vec::new();
+        Serializer::new()
+            .compression(CompressionType::None)
+            .serialize(&mut buffer, &tree, tree.root().children())
+            .expect("failed to encode model");
+
+        let decoded = crate::from_reader(buffer.as_slice()).expect("failed to decode model");
+        let value = decoded
+            .get_by_ref(decoded.root().children()[0])
+            .unwrap()
+            .properties
+            .get("Value")
+            .cloned();
+        assert_eq!(value, Some(Variant::CFrame(cframe)));
+
+        buffer.len()
+    };
+
+    let compact_len = encode(quarter_turn);
+    let full_len = encode(scaled);
+
+    // A full rotation is nine floats instead of nothing but the ID.
+    assert_eq!(full_len - compact_len, 9 * 4);
+}
+
+/// Ensures that fonts round-trip, with and without a cached face ID.
+#[test]
+fn text_font_faces() {
+    let bold = Font {
+        family: "rbxasset://fonts/families/GothamSSm.json".to_owned(),
+        weight: FontWeight::Bold,
+        style: FontStyle::Italic,
+        cached_face_id: Some("rbxasset://fonts/GothamSSm-BoldItalic.otf".to_owned()),
+    };
+    let regular = Font::default();
+
+    let tree = WeakDom::new(
+        InstanceBuilder::new("Folder")
+            .with_child(InstanceBuilder::new("TextLabel").with_property("FontFace", bold.clone()))
+            .with_child(
+                InstanceBuilder::new("TextLabel").with_property("FontFace", regular.clone()),
+            ),
+    );
+    let folder = tree.root();
+
+    let mut buffer = Vec::new();
+    to_writer(&mut buffer, &tree, folder.children()).expect("failed to encode model");
+
+    let decoded = crate::from_reader(buffer.as_slice()).expect("failed to decode model");
+    let fonts: Vec<_> = decoded
+        .root()
+        .children()
+        .iter()
+        .map(|&referent| {
+            decoded
+                .get_by_ref(referent)
+                .unwrap()
+                .properties
+                .get("FontFace")
+        })
+        .collect();
+
+    assert_eq!(
+        fonts,
+        [Some(&Variant::Font(bold)), Some(&Variant::Font(regular))]
+    );
+}
+
+/// Ensures that every instance keeps its own UniqueId across a round trip.
+#[test]
+fn unique_ids() {
+    let ids = [
+        UniqueId::new(0, 0, 0),
+        UniqueId::new(1, 94_608_000, i64::MAX),
+        UniqueId::new(u32::MAX, u32::MAX, -1),
+    ];
+
+    let mut tree = WeakDom::new(InstanceBuilder::new("Folder"));
+    for id in ids {
+        tree.insert(
+            tree.root_ref(),
+            InstanceBuilder::new("Folder").with_property("UniqueId", id),
+        );
+    }
+    let folder = tree.root();
+
+    let mut buffer = Vec::new();
+    to_writer(&mut buffer, &tree, folder.children()).expect("failed to encode model");
+
+    let decoded = crate::from_reader(buffer.as_slice()).expect("failed to decode model");
+    let decoded_ids: Vec<_> = decoded
+        .root()
+        .children()
+        .iter()
+        .map(|&referent| {
+            decoded
+                .get_by_ref(referent)
+                .unwrap()
+                .properties
+                .get("UniqueId")
+                .cloned()
+        })
+        .collect();
+
+    let expected: Vec<_> = ids.iter().map(|&id| Some(Variant::UniqueId(id))).collect();
+    assert_eq!(decoded_ids, expected);
+}
+
+/// Ensures that SecurityCapabilities round-trip, including values that don't
+/// fit in a signed integer.
+#[test]
+fn security_capabilities() {
+    let values = [0, 0b1011, u64::MAX];
+
+    let mut tree = WeakDom::new(InstanceBuilder::new("Folder"));
+    for value in values {
+        tree.insert(
+            tree.root_ref(),
+            InstanceBuilder::new("Script")
+                .with_property("Capabilities", SecurityCapabilities::from_bits(value)),
+        );
+    }
+    let folder = tree.root();
+
+    let mut buffer = Vec::new();
+    to_writer(&mut buffer, &tree, folder.children()).expect("failed to encode model");
+
+    let decoded = crate::from_reader(buffer.as_slice()).expect("failed to decode model");
+    let decoded_values: Vec<_> = decoded
+        .root()
+        .children()
+        .iter()
+        .map(|&referent| {
+            decoded
+                .get_by_ref(referent)
+                .unwrap()
+                .properties
+                .get("Capabilities")
+                .cloned()
+        })
+        .collect();
+
+    let expected: Vec<_> = values
+        .iter()
+        .map(|&value| Some(SecurityCapabilities::from_bits(value).into()))
+        .collect();
+    assert_eq!(decoded_values, expected);
+}
+
+/// Ensures that properties of skipped types are left out of the file,
+/// including SharedStrings, which have a chunk of their own.
+#[test]
+fn skipped_types() {
+    let tree = WeakDom::new(
+        InstanceBuilder::new("Folder").with_child(
+            InstanceBuilder::new("TextLabel")
+                .with_property("FontFace", Font::default())
+                .with_property("Text", "Hello")
+                .with_property("UniqueId", UniqueId::new(1, 2, 3))
+                .with_property("Data", SharedString::new(b"shared".to_vec())),
+        ),
+    );
+    let folder = tree.root();
+
+    let mut buffer = Vec::new();
+    let report = Serializer::new()
+        .skip_types([
+            VariantType::Font,
+            VariantType::UniqueId,
+            VariantType::SharedString,
+        ])
+        .serialize_with_report(&mut buffer, &tree, folder.children())
+        .expect("failed to encode model");
+
+    let mut skipped: Vec<_> = report
+        .skipped_properties
+        .iter()
+        .map(|property| {
+            assert_eq!(property.instance, folder.children()[0]);
+            (property.name.as_str(), property.ty)
+        })
+        .collect();
+    skipped.sort_unstable_by_key(|(name, _)| *name);
+    assert_eq!(
+        skipped,
+        [
+            ("Data", VariantType::SharedString),
+            ("FontFace", VariantType::Font),
+            ("UniqueId", VariantType::UniqueId),
+        ]
+    );
+
+    let chunk_names: Vec<_> = ChunkReader::new(buffer.as_slice())
+        .unwrap()
+        .map(|chunk| chunk.unwrap().name)
+        .collect();
+    assert!(!chunk_names.contains(b"SSTR"));
+
+    let decoded = crate::from_reader(buffer.as_slice()).expect("failed to decode model");
+    let label = decoded.get_by_ref(decoded.root().children()[0]).unwrap();
+
+    let mut names: Vec<_> = label.properties.keys().map(String::as_str).collect();
+    names.sort_unstable();
+    assert_eq!(names, ["Text"]);
+}
+
+/// Ensures that types newer than the supported level are left out, and that
+/// every instance missing out on them is reported.
+#[test]
+fn type_support() {
+    let label = || {
+        InstanceBuilder::new("TextLabel")
+            .with_property("FontFace", Font::default())
+            .with_property("Text", "Hello")
+    };
+    let tree = WeakDom::new(InstanceBuilder::new("Folder").with_children([label(), label()]));
+    let labels = tree.root().children();
+
+    let mut buffer = Vec::new();
+    let report = Serializer::new()
+        .type_support(TypeSupport::UpTo(0x1E))
+        .serialize_with_report(&mut buffer, &tree, labels)
+        .expect("failed to encode model");
+
+    let skipped: Vec<_> = report
+        .skipped_properties
+        .iter()
+        .map(|property| (property.instance, property.name.as_str(), property.ty))
+        .collect();
+    assert_eq!(
+        skipped,
+        [
+            (labels[0], "FontFace", VariantType::Font),
+            (labels[1], "FontFace", VariantType::Font),
+        ]
+    );
+
+    let decoded = crate::from_reader(buffer.as_slice()).expect("failed to decode model");
+    for &referent in decoded.root().children() {
+        let label = decoded.get_by_ref(referent).unwrap();
+        assert!(!label.properties.contains_key("FontFace"));
+        assert_eq!(
+            label.properties.get("Text"),
+            Some(&Variant::String("Hello".to_owned()))
+        );
+    }
+
+    let mut buffer = Vec::new();
+    let report = Serializer::new()
+        .type_support(TypeSupport::UpTo(0x20))
+        .serialize_with_report(&mut buffer, &tree, labels)
+        .expect("failed to encode model");
+    assert!(report.skipped_properties.is_empty());
+}
+
+/// Ensures that chunks can be written one class at a time, and that they
+/// decode to the same tree either way.
+#[test]
+fn chunk_order_by_class() {
+    let tree = WeakDom::new(
+        InstanceBuilder::new("Folder")
+            .with_child(InstanceBuilder::new("Part").with_name("A"))
+            .with_child(InstanceBuilder::new("StringValue").with_property("Value", "B")),
+    );
+
+    let encode = |order: ChunkOrder| {
+        let mut buffer = Vec::new();
+        Serializer::new()
+            .chunk_order(order)
+            .serialize(&mut buffer, &tree, tree.root().children())
+            .expect("failed to encode model");
+        buffer
+    };
+
+    let chunk_names = |buffer: &[u8]| -> Vec<[u8; 4]> {
+        let mut names: Vec<[u8; 4]> = ChunkReader::new(buffer)
+            .unwrap()
+            .map(|chunk| chunk.unwrap().name)
+            .collect();
+        names.dedup();
+        names
+    };
+
+    let roblox = encode(ChunkOrder::Roblox);
+    let by_class = encode(ChunkOrder::ByClass);
+
+    assert_eq!(
+        chunk_names(&roblox),
+        [*b"INST", *b"PROP", *b"PRNT", *b"END\0"]
+    );
+    assert_eq!(
+        chunk_names(&by_class),
+        [*b"INST", *b"PROP", *b"INST", *b"PROP", *b"PRNT", *b"END\0"]
+    );
+
+    let decoded = crate::from_reader(by_class.as_slice()).expect("failed to decode model");
+    let names: Vec<_> = decoded
+        .root()
+        .children()
+        .iter()
+        .map(|&referent| decoded.get_by_ref(referent).unwrap().name.as_str())
+        .collect();
+    assert_eq!(names, ["A", "StringValue"]);
+}
+
+/// Ensures that SharedStrings are written with the same MD5 hashes that
+/// Roblox uses to identify them.
+#[test]
+fn shared_string_hashes() {
+    let data = b"Hello, world!".to_vec();
+    let tree = WeakDom::new(
+        InstanceBuilder::new("Folder").with_child(
+            InstanceBuilder::new("MeshPart")
+                .with_property("PhysicalConfigData", SharedString::new(data)),
+        ),
+    );
+
+    let mut buffer = Vec::new();
+    to_writer(&mut buffer, &tree, tree.root().children()).expect("failed to encode model");
+
+    let mut reader = buffer.as_slice();
+    FileHeader::decode(&mut reader).expect("failed to decode header");
+
+    let sstr = loop {
+        let chunk = Chunk::decode(&mut reader).expect("failed to decode chunk");
+        match &chunk.name {
+            b"SSTR" => break chunk.data,
+            b"END\0" => panic!("model had no SSTR chunk"),
+            _ => {}
+        }
+    };
+
+    // The version and number of entries come before the first hash.
+    assert_eq!(&sstr[..8], &[0, 0, 0, 0, 1, 0, 0, 0]);
+    assert_eq!(
+        &sstr[8..24],
+        &[
+            0x6c, 0xd3, 0x55, 0x6d, 0xeb, 0x0d, 0xa5, 0x4b, 0xca, 0x06, 0x0b, 0x4c, 0x39, 0x47,
+            0x98, 0x39
+        ]
+    );
+}
+
+/// Ensures that a SIGN chunk is kept when decoding and only written back out
+/// when asked for.
+#[test]
+fn signature_passthrough() {
+    let tree =
+        WeakDom::new(InstanceBuilder::new("Folder").with_child(InstanceBuilder::new("Folder")));
+    let signature = vec![1, 2, 3, 4, 5];
+
+    let mut buffer = Vec::new();
+    Serializer::new()
+        .signature(Some(signature.clone()))
+        .serialize(&mut buffer, &tree, tree.root().children())
+        .expect("failed to encode model");
+
+    let document = Deserializer::new()
+        .deserialize_document(buffer.as_slice())
+        .expect("failed to decode model");
+    assert_eq!(document.signature, Some(signature));
+
+    let mut buffer = Vec::new();
+    to_writer(&mut buffer, &document.tree, document.tree.root().children())
+        .expect("failed to encode model");
+
+    let document = Deserializer::new()
+        .deserialize_document(buffer.as_slice())
+        .expect("failed to decode model");
+    assert_eq!(document.signature, None);
+}
+
+/// Ensures that chunks rbx_binary doesn't understand survive a round trip.
+#[test]
+fn unknown_chunk_passthrough() {
+    let tree =
+        WeakDom::new(InstanceBuilder::new("Folder").with_child(InstanceBuilder::new("Folder")));
+    let chunks = vec![
+        UnknownChunk {
+            name: *b"NEW\0",
+            data: b"some new data".to_vec(),
+        },
+        UnknownChunk {
+            name: *b"NEWR",
+            data: Vec::new(),
+        },
+    ];
+
+    let mut buffer = Vec::new();
+    Serializer::new()
+        .unknown_chunks(chunks.clone())
+        .serialize(&mut buffer, &tree, tree.root().children())
+        .expect("failed to encode model");
+
+    let document = Deserializer::new()
+        .deserialize_document(buffer.as_slice())
+        .expect("failed to decode model");
+    assert_eq!(document.unknown_chunks, chunks);
+
+    let known = UnknownChunk {
+        name: *b"PROP",
+        data: Vec::new(),
+    };
+    let result = Serializer::new().unknown_chunks(vec![known]).serialize(
+        &mut Vec::new(),
+        &tree,
+        tree.root().children(),
+    );
+    assert!(result.is_err());
+}
+
+/// Ensures that models can be written without compressing any chunks, and
+/// still read back in.
+#[test]
+fn uncompressed_chunks() {
+    let tree =
+        WeakDom::new(InstanceBuilder::new("Folder").with_child(
+            InstanceBuilder::new("StringValue").with_property("Value", "Hello, world!"),
+        ));
+
+    let mut buffer = Vec::new();
+    Serializer::new()
+        .compression(CompressionType::None)
+        .serialize(&mut buffer, &tree, tree.root().children())
+        .expect("failed to encode model");
+
+    // Every chunk header's compressed length should be zero.
+    let mut reader = &buffer[32..];
+    while !reader.is_empty() {
+        let name = &reader[..4];
+        let compressed_len = u32::from_le_bytes(reader[4..8].try_into().unwrap());
+        let len = u32::from_le_bytes(reader[8..12].try_into().unwrap());
+        assert_eq!(compressed_len, 0, "chunk {:?} was compressed", name);
+        reader = &reader[16 + len as usize..];
+    }
+
+    let decoded = crate::from_reader(buffer.as_slice()).expect("failed to decode model");
+    let value = decoded.get_by_ref(decoded.root().children()[0]).unwrap();
+    assert_eq!(
+        value.properties.get("Value"),
+        Some(&Variant::String("Hello, world!".to_owned()))
+    );
+}
+
+/// Ensures that each compression mode writes models that can be read back in,
+/// and that chunks can be left uncompressed when compressing doesn't help.
+#[test]
+fn compression_modes() {
+    let value = "Hello, world! ".repeat(100);
+    let tree = WeakDom::new(
+        InstanceBuilder::new("Folder")
+            .with_child(InstanceBuilder::new("StringValue").with_property("Value", value.as_str())),
+    );
+
+    let encode = |serializer: Serializer| {
+        let mut buffer = Vec::new();
+        serializer
+            .serialize(&mut buffer, &tree, tree.root().children())
+            .expect("failed to encode model");
+
+        let decoded = crate::from_reader(buffer.as_slice()).expect("failed to decode model");
+        let instance = decoded.get_by_ref(decoded.root().children()[0]).unwrap();
+        assert_eq!(
+            instance.properties.get("Value"),
+            Some(&Variant::String(value.clone()))
+        );
+
+        buffer
+    };
+
+    let default = encode(Serializer::new());
+    let fast = encode(Serializer::new().compression(CompressionType::Lz4Fast(16)));
+    let high = encode(Serializer::new().compression(CompressionType::Lz4High(12)));
+    assert!(high.len() <= default.len());
+    assert!(default.len() <= fast.len());
+
+    // Tiny chunks like PRNT get bigger when they're compressed.
+    let uncompressed = encode(Serializer::new().compression(CompressionType::None));
+
+    let zstd = encode(Serializer::new().compression(CompressionType::Zstd(19)));
+    assert!(zstd.len() < uncompressed.len());
+    let only_if_smaller = encode(Serializer::new().compress_only_if_smaller(true));
+    assert!(only_if_smaller.len() < default.len());
+    assert!(only_if_smaller.len() < uncompressed.len());
+}
+
+/// Ensures that streaming a model gives the same instances, properties, and
+/// parents as decoding it into a tree.
+#[test]
+fn stream_events() {
+    let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+    let folder = tree.insert(
+        tree.root_ref(),
+        InstanceBuilder::new("Folder").with_name("Outer"),
+    );
+    tree.insert(
+        folder,
+        InstanceBuilder::new("ObjectValue")
+            .with_name("Pointer")
+            .with_property("Value", folder),
+    );
+
+    let mut buffer = Vec::new();
+    to_writer(&mut buffer, &tree, &[folder]).expect("failed to encode model");
+
+    let events = Deserializer::new()
+        .stream(buffer.as_slice())
+        .expect("failed to read header")
+        .collect::<Result<Vec<_>, _>>()
+        .expect("failed to stream model");
+
+    let mut classes = Vec::new();
+    let mut names = Vec::new();
+    let mut values = Vec::new();
+    let mut parents = Vec::new();
+
+    for event in events {
+        match event {
+            StreamEvent::Instances {
+                class_name,
+                referents,
+            } => {
+                for referent in referents {
+                    classes.push((class_name.clone(), referent));
+                }
+            }
+            StreamEvent::Property {
+                referent,
+                name,
+                value,
+            } => match name.as_str() {
+                "Name" => names.push((referent, value)),
+                "Value" => values.push((referent, value)),
+                _ => {}
+            },
+            StreamEvent::Parent { referent, parent } => parents.push((referent, parent)),
+            _ => {}
+        }
+    }
+
+    let class_ref = |class: &str| {
+        classes
+            .iter()
+            .find(|(class_name, _)| class_name == class)
+            .unwrap()
+            .1
+    };
+    let outer = class_ref("Folder");
+    let pointer = class_ref("ObjectValue");
+
+    assert_eq!(classes.len(), 2);
+    assert!(names.contains(&(outer, Variant::String("Outer".to_owned()))));
+    assert!(names.contains(&(pointer, Variant::String("Pointer".to_owned()))));
+    assert_eq!(values, [(pointer, Variant::Ref(outer))]);
+    assert!(parents.contains(&(outer, Ref::none())));
+    assert!(parents.contains(&(pointer, outer)));
+}
+
+/// Ensures that decoding a file in memory hands out BinaryStrings and
+/// SharedStrings that point into the file when chunks aren't compressed, and
+/// otherwise gives the same events as streaming it.
+#[test]
+fn stream_slice() {
+    let shared = SharedString::new(b"Shared contents".to_vec());
+    let tree = WeakDom::new(
+        InstanceBuilder::new("Folder")
+            .with_child(
+                InstanceBuilder::new("Terrain")
+                    .with_property("PhysicsGrid", BinaryString::from(b"Grid".to_vec())),
+            )
+            .with_child(InstanceBuilder::new("Part").with_property("Value", shared.clone()))
+            .with_child(InstanceBuilder::new("Part").with_property("Value", shared)),
+    );
+
+    for compression in [CompressionType::None, CompressionType::Lz4] {
+        let mut buffer = Vec::new();
+        Serializer::new()
+            .compression(compression)
+            .serialize(&mut buffer, &tree, &[tree.root_ref()])
+            .expect("failed to encode model");
+
+        let events = Deserializer::new()
+            .stream_slice(&buffer)
+            .expect("failed to read header")
+            .collect::<Result<Vec<_>, _>>()
+            .expect("failed to decode model");
+
+        let mut binary_strings = Vec::new();
+        let mut shared_strings = Vec::new();
+
+        for event in &events {
+            match event {
+                SliceEvent::Property {
+                    value: SliceValue::BinaryString(bytes),
+                    name,
+                    ..
+                } => {
+                    assert_eq!(name, "PhysicsGrid");
+                    binary_strings.push(bytes.clone());
+                }
+                SliceEvent::Property {
+                    value: SliceValue::SharedString(bytes),
+                    name,
+                    ..
+                } => {
+                    assert_eq!(name, "Value");
+                    shared_strings.push(bytes.clone());
+                }
+                _ => {}
+            }
+        }
+
+        assert_eq!(binary_strings.len(), 1);
+        assert_eq!(binary_strings[0].as_bytes(), b"Grid");
+        assert_eq!(shared_strings.len(), 2);
+        assert_eq!(shared_strings[0].as_bytes(), b"Shared contents");
+
+        if matches!(compression, CompressionType::None) {
+            for bytes in binary_strings.iter().chain(&shared_strings) {
+                let range = bytes.input_range().expect("value should be in the file");
+                assert_eq!(&buffer[range], bytes.as_bytes());
+                assert_eq!(
+                    bytes.borrowed().unwrap().as_ptr(),
+                    buffer[bytes.input_range().unwrap()].as_ptr()
+                );
+            }
+
+            assert_eq!(
+                shared_strings[0].input_range(),
+                shared_strings[1].input_range()
+            );
+        } else {
+            assert!(binary_strings[0].borrowed().is_none());
+            assert!(shared_strings[0].input_range().is_none());
+        }
+
+        // Refs are different every time a file is decoded, so they're left
+        // out of the comparison.
+        let summarize_slice = |event: SliceEvent| match event {
+            SliceEvent::Instances { class_name, .. } => Some(format!("{} instances", class_name)),
+            SliceEvent::Property { name, value, .. } => {
+                Some(format!("{} = {:?}", name, value.into_variant()))
+            }
+            _ => None,
+        };
+        let summarize_stream = |event: StreamEvent| match event {
+            StreamEvent::Instances { class_name, .. } => Some(format!("{} instances", class_name)),
+            StreamEvent::Property { name, value, .. } => Some(format!("{} = {:?}", name, value)),
+            _ => None,
+        };
+
+        let streamed: Vec<_> = Deserializer::new()
+            .stream(buffer.as_slice())
+            .unwrap()
+            .map(|event| summarize_stream(event.unwrap()))
+            .collect();
+        let sliced: Vec<_> = events.into_iter().map(summarize_slice).collect();
+        assert_eq!(sliced, streamed);
+    }
+}
+
+/// Ensures that streaming stops at chunks over the length limit instead of
+/// reading them in.
+#[test]
+fn stream_chunk_limit() {
+    let tree = WeakDom::new(InstanceBuilder::new("Folder"));
+
+    let mut buffer = Vec::new();
+    Serializer::new()
+        .compression(CompressionType::None)
+        .serialize(&mut buffer, &tree, &[tree.root_ref()])
+        .expect("failed to encode model");
+
+    let results: Vec<_> = Deserializer::new()
+        .stream(buffer.as_slice())
+        .expect("failed to read header")
+        .max_chunk_len(8)
+        .collect();
+
+    assert!(results.last().unwrap().is_err());
+    assert!(results
+        .iter()
+        .all(|result| { !matches!(result, Ok(StreamEvent::Property { .. })) }));
+}
+
+/// Ensures that instances written a batch at a time decode like any other
+/// file, with parents and Refs that point into earlier batches.
+#[test]
+fn stream_batches() {
+    let mut dom = WeakDom::new(InstanceBuilder::new("Folder").with_name("Root"));
+    let root = dom.root_ref();
+
+    let mut encoder = Serializer::new()
+        .stream(std::io::Cursor::new(Vec::new()))
+        .expect("failed to write header");
+    encoder
+        .write_batch(&dom, &[root])
+        .expect("failed to write batch");
+
+    let shared_string = SharedString::new(b"shared between batches".to_vec());
+
+    for i in 0..3 {
+        let value = dom.insert(
+            root,
+            InstanceBuilder::new("ObjectValue")
+                .with_name(format!("Value{}", i))
+                .with_property("Value", root)
+                .with_child(
+                    InstanceBuilder::new("Model")
+                        .with_property("ModelMeshData", shared_string.clone()),
+                ),
+        );
+
+        encoder
+            .write_batch(&dom, &[value])
+            .expect("failed to write batch");
+
+        dom.destroy(value);
+    }
+
+    let buffer = encoder.finish().expect("failed to finish").into_inner();
+
+    let header = FileHeader::decode(buffer.as_slice()).expect("failed to decode header");
+    assert_eq!(header.num_types, 7);
+    assert_eq!(header.num_instances, 7);
+
+    let decoded = Deserializer::new()
+        .deserialize(buffer.as_slice())
+        .expect("failed to decode model");
+
+    let decoded_root_ref = decoded.root().children()[0];
+    let decoded_root = decoded.get_by_ref(decoded_root_ref).unwrap();
+    assert_eq!(decoded_root.name, "Root");
+    assert_eq!(decoded_root.children().len(), 3);
+
+    for (i, &child_ref) in decoded_root.children().iter().enumerate() {
+        let child = decoded.get_by_ref(child_ref).unwrap();
+        assert_eq!(child.name, format!("Value{}", i));
+        assert_eq!(
+            child.properties.get("Value"),
+            Some(&Variant::Ref(decoded_root_ref))
+        );
+
+        let model = decoded.get_by_ref(child.children()[0]).unwrap();
+        assert_eq!(
+            model.properties.get("ModelMeshData"),
+            Some(&Variant::SharedString(shared_string.clone()))
+        );
+    }
+}
+
+/// Ensures that ChunkReader gives back the chunks of a file as they were
+/// written, compressed or not.
+#[test]
+fn raw_chunks() {
+    let tree =
+        WeakDom::new(InstanceBuilder::new("Folder").with_child(InstanceBuilder::new("Part")));
+
+    let read_chunks = |compression| {
+        let mut buffer = Vec::new();
+        Serializer::new()
+            .compression(compression)
+            .serialize(&mut buffer, &tree, &[tree.root_ref()])
+            .expect("failed to encode model");
+
+        let reader = ChunkReader::new(buffer.as_slice()).expect("failed to read header");
+        assert_eq!(reader.num_types(), 2);
+        assert_eq!(reader.num_instances(), 2);
+
+        reader
+            .collect::<Result<Vec<_>, _>>()
+            .expect("failed to read chunks")
+    };
+
+    let uncompressed = read_chunks(CompressionType::None);
+    let compressed = read_chunks(CompressionType::Lz4);
+
+    assert_eq!(uncompressed.len(), compressed.len());
+    assert_eq!(&uncompressed.last().unwrap().name, b"END\0");
+
+    for (uncompressed, compressed) in uncompressed.iter().zip(&compressed) {
+        assert_eq!(uncompressed.name, compressed.name);
+        assert!(!uncompressed.is_compressed());
+        assert_eq!(uncompressed.len as usize, uncompressed.data.len());
+
+        if &compressed.name != b"END\0" {
+            assert!(compressed.is_compressed());
+            assert_eq!(compressed.compressed_len as usize, compressed.data.len());
+        }
+
+        assert_eq!(
+            compressed.decompressed().unwrap(),
+            uncompressed.decompressed().unwrap()
+        );
+    }
+}
+
+/// Encodes a Folder with a few children with the given compression, then
+/// rebuilds the file from its chunks after passing them through `damage`.
+fn damaged_model(
+    compression: CompressionType,
+    damage: impl Fn(&mut Vec<crate::RawChunk>),
+) -> Vec<u8> {
+    let tree = WeakDom::new(
+        InstanceBuilder::new("Folder")
+            .with_name("Outer")
+            .with_child(InstanceBuilder::new("StringValue").with_property("Value", "Hello"))
+            .with_child(InstanceBuilder::new("Part")),
+    );
+
+    rewritten_model(&tree, compression, damage)
+}
+
+/// Encodes `tree`, lets `rewrite` change its chunks, then puts the file back
+/// together.
+fn rewritten_model(
+    tree: &WeakDom,
+    compression: CompressionType,
+    rewrite: impl Fn(&mut Vec<crate::RawChunk>),
+) -> Vec<u8> {
+    let mut buffer = Vec::new();
+    Serializer::new()
+        .compression(compression)
+        .serialize(&mut buffer, tree, &[tree.root_ref()])
+        .expect("failed to encode model");
+
+    let mut chunks = ChunkReader::new(buffer.as_slice())
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    rewrite(&mut chunks);
+
+    let mut output = buffer[..32].to_vec();
+    for chunk in chunks {
+        output.extend_from_slice(&chunk.name);
+        output.extend_from_slice(&chunk.compressed_len.to_le_bytes());
+        output.extend_from_slice(&chunk.len.to_le_bytes());
+        output.extend_from_slice(&chunk.reserved.to_le_bytes());
+        output.extend_from_slice(&chunk.data);
+    }
+
+    output
+}
+
+/// Ensures that recovering a truncated file keeps everything before the point
+/// where it was cut off.
+#[test]
+fn recover_truncated() {
+    let mut buffer = damaged_model(CompressionType::Lz4, |_| {});
+    buffer.truncate(buffer.len() - 5);
+
+    assert!(Deserializer::new().deserialize(buffer.as_slice()).is_err());
+
+    let recovered = Deserializer::new()
+        .deserialize_recovering(buffer.as_slice())
+        .expect("failed to read header");
+    assert_eq!(recovered.errors.len(), 1);
+
+    let tree = recovered.document.tree;
+    let outer = tree.get_by_ref(tree.root().children()[0]).unwrap();
+    assert_eq!(outer.name, "Outer");
+    assert_eq!(outer.children().len(), 2);
+}
+
+/// Ensures that instances are kept when the PRNT chunk can't be read.
+#[test]
+fn recover_damaged_parents() {
+    let buffer = damaged_model(CompressionType::None, |chunks| {
+        let prnt = chunks
+            .iter_mut()
+            .find(|chunk| &chunk.name == b"PRNT")
+            .unwrap();
+        prnt.data[0] = 5;
+    });
+
+    assert!(Deserializer::new().deserialize(buffer.as_slice()).is_err());
+
+    let recovered = Deserializer::new()
+        .deserialize_recovering(buffer.as_slice())
+        .expect("failed to read header");
+    assert_eq!(recovered.errors.len(), 1);
+
+    let tree = recovered.document.tree;
+    let mut classes: Vec<_> = tree
+        .root()
+        .children()
+        .iter()
+        .map(|&referent| tree.get_by_ref(referent).unwrap().class.as_str())
+        .collect();
+    classes.sort_unstable();
+
+    assert_eq!(classes, ["Folder", "Part", "StringValue"]);
+}
+
+/// Ensures that a chunk that can't be decompressed is skipped without losing
+/// the rest of the file.
+#[test]
+fn recover_bad_compressed_chunk() {
+    let buffer = damaged_model(CompressionType::Lz4, |chunks| {
+        let value = chunks
+            .iter_mut()
+            .filter(|chunk| &chunk.name == b"PROP")
+            .find(|chunk| {
+                let data = chunk.decompressed().unwrap();
+                data.windows(5).any(|window| window == b"Hello")
+            })
+            .unwrap();
+
+        for byte in &mut value.data {
+            *byte = 0xff;
+        }
+    });
+
+    assert!(Deserializer::new().deserialize(buffer.as_slice()).is_err());
+
+    let recovered = Deserializer::new()
+        .deserialize_recovering(buffer.as_slice())
+        .expect("failed to read header");
+    assert_eq!(recovered.errors.len(), 1);
+
+    let tree = recovered.document.tree;
+    let outer = tree.get_by_ref(tree.root().children()[0]).unwrap();
+    assert_eq!(outer.children().len(), 2);
+
+    for &child in outer.children() {
+        let child = tree.get_by_ref(child).unwrap();
+        assert_eq!(child.properties.get("Value"), None);
+    }
+}
+
+/// Ensures that errors found while decoding a chunk say where it was.
+#[test]
+fn error_locations() {
+    let buffer = damaged_model(CompressionType::None, |chunks| {
+        let value = chunks
+            .iter_mut()
+            .filter(|chunk| &chunk.name == b"PROP")
+            .find(|chunk| chunk.data.windows(5).any(|window| window == b"Hello"))
+            .unwrap();
+
+        // Cut the chunk off partway through the length of the string.
+        value.data.truncate(value.data.len() - 7);
+        value.len = value.data.len() as u32;
+    });
+
+    let chunks = ChunkReader::new(buffer.as_slice())
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let index = chunks
+        .iter()
+        .position(|chunk| &chunk.name == b"PROP" && chunk.data[4..].starts_with(b"\x05\0\0\0Value"))
+        .unwrap();
+    let offset = 32
+        + chunks[..index]
+            .iter()
+            .map(|chunk| 16 + chunk.data.len() as u64)
+            .sum::<u64>();
+
+    let check = |error: crate::DecodeError| {
+        assert_eq!(error.chunk_name(), Some("PROP"));
+        assert_eq!(error.chunk_index(), Some(index));
+        assert_eq!(error.chunk_offset(), Some(offset));
+        assert_eq!(error.class_name(), Some("StringValue"));
+        assert_eq!(error.property_name(), Some("Value"));
+
+        let message = error.to_string();
+        assert!(
+            message.ends_with(&format!(
+                "(in chunk PROP #{} at byte {}, property StringValue.Value)",
+                index, offset
+            )),
+            "unexpected message: {}",
+            message
+        );
+    };
+
+    check(
+        Deserializer::new()
+            .deserialize(buffer.as_slice())
+            .unwrap_err(),
+    );
+
+    let lazy = Deserializer::new()
+        .deserialize_lazy(buffer.as_slice())
+        .expect("failed to decode tree");
+    check(lazy.into_tree().unwrap_err());
+
+    check(
+        Deserializer::new()
+            .stream(buffer.as_slice())
+            .unwrap()
+            .find_map(Result::err)
+            .unwrap(),
+    );
+
+    let recovered = Deserializer::new()
+        .deserialize_recovering(buffer.as_slice())
+        .unwrap();
+    assert_eq!(recovered.errors.len(), 1);
+    check(recovered.errors.into_iter().next().unwrap());
+
+    // Errors in the file header aren't in any chunk.
+    let error = Deserializer::new().deserialize(&buffer[..10]).unwrap_err();
+    assert_eq!(error.chunk_index(), None);
+    assert_eq!(error.chunk_name(), None);
+}
+
+/// Ensures that instances missing a SharedString property that other instances
+/// of their class have can be written.
+#[test]
+fn missing_shared_strings() {
+    let tree = WeakDom::new(
+        InstanceBuilder::new("Folder")
+            .with_child(
+                InstanceBuilder::new("Part")
+                    .with_property("Value", SharedString::new(b"Hello".to_vec())),
+            )
+            .with_child(InstanceBuilder::new("Part")),
+    );
+
+    let mut buffer = Vec::new();
+    to_writer(&mut buffer, &tree, &[tree.root_ref()]).expect("failed to encode model");
+
+    let decoded = Deserializer::new().deserialize(buffer.as_slice()).unwrap();
+    let folder = decoded.get_by_ref(decoded.root().children()[0]).unwrap();
+    let values: Vec<_> = folder
+        .children()
+        .iter()
+        .map(
+            |&child| match &decoded.get_by_ref(child).unwrap().properties["Value"] {
+                Variant::SharedString(value) => value.data().to_vec(),
+                other => panic!("expected a SharedString, got {:?}", other),
+            },
+        )
+        .collect();
+
+    assert_eq!(values, [b"Hello".to_vec(), Vec::new()]);
+}
+
+/// Ensures that counts in a file that are far larger than the file itself are
+/// reported as errors instead of being allocated for.
+#[test]
+fn huge_counts() {
+    let with_count = |name: &[u8; 4], count_offset: usize| {
+        damaged_model(CompressionType::None, |chunks| {
+            let chunk = chunks
+                .iter_mut()
+                .find(|chunk| {
+                    &chunk.name == name
+                        && (name != b"INST" || chunk.data[8..].starts_with(b"Folder"))
+                })
+                .unwrap();
+            chunk.data[count_offset..count_offset + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        })
+    };
+
+    // The INST chunk for the Folder, whose class name is 6 bytes long.
+    let inst = with_count(b"INST", 4 + 4 + 6 + 1);
+    Deserializer::new()
+        .deserialize(inst.as_slice())
+        .unwrap_err();
+
+    let prnt = with_count(b"PRNT", 1);
+    Deserializer::new()
+        .deserialize(prnt.as_slice())
+        .unwrap_err();
+
+    let mut header = damaged_model(CompressionType::None, |_| {});
+    header[16..24].copy_from_slice(&[0xff; 8]);
+    Deserializer::new().deserialize(header.as_slice()).unwrap();
+}
+
+/// Ensures that rbx_binary can be checked with rbx_dom_weak's round trip
+/// harness.
+#[test]
+fn round_trip_harness() {
+    let tree = WeakDom::new(
+        InstanceBuilder::new("Folder")
+            .with_child(InstanceBuilder::new("StringValue").with_property("Value", "Hello"))
+            .with_child(InstanceBuilder::new("Part")),
+    );
+
+    let mut buffer = Vec::new();
+    to_writer(&mut buffer, &tree, &[tree.root_ref()]).unwrap();
+
+    let round_trip = RoundTrip::new(
+        |contents| Deserializer::new().deserialize(contents),
+        |dom, refs| {
+            let mut output = Vec::new();
+            to_writer(&mut output, dom, refs).map(|_| output)
+        },
+    );
+
+    round_trip.check(&buffer).unwrap();
+    assert!(round_trip.check(&buffer[..40]).is_err());
+}
+
+/// Ensures that only the properties of the requested classes are decoded,
+/// whether or not chunks are compressed.
+#[test]
+fn class_filter() {
+    let tree = WeakDom::new(
+        InstanceBuilder::new("Folder")
+            .with_name("Outer")
+            .with_child(
+                InstanceBuilder::new("Script")
+                    .with_name("Main")
+                    .with_property("Source", "print('hi')"),
+            )
+            .with_child(
+                InstanceBuilder::new("Part")
+                    .with_name("Floor")
+                    .with_property("Anchored", true),
+            ),
+    );
+
+    for compression in [CompressionType::Lz4, CompressionType::None] {
+        let mut buffer = Vec::new();
+        Serializer::new()
+            .compression(compression)
+            .serialize(&mut buffer, &tree, &[tree.root_ref()])
+            .expect("failed to encode model");
+
+        let decoded = Deserializer::new()
+            .only_classes(["LuaSourceContainer"])
+            .deserialize(buffer.as_slice())
+            .expect("failed to decode model");
+
+        let outer = decoded.get_by_ref(decoded.root().children()[0]).unwrap();
+        assert_eq!(outer.name, "Outer");
+
+        for &child in outer.children() {
+            let child = decoded.get_by_ref(child).unwrap();
+
+            match child.class.as_str() {
+                "Script" => {
+                    assert_eq!(child.name, "Main");
+                    assert_eq!(
+                        child.properties.get("Source"),
+                        Some(&Variant::String("print('hi')".to_owned()))
+                    );
+                }
+                "Part" => {
+                    assert_eq!(child.name, "Floor");
+                    assert!(child.properties.is_empty());
+                }
+                other => panic!("unexpected class {}", other),
+            }
+        }
+    }
+}
+
+/// Ensures that a lazily decoded document has the same instances and
+/// properties as one decoded all at once.
+#[test]
+fn lazy_properties() {
+    let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+    let folder = tree.insert(
+        tree.root_ref(),
+        InstanceBuilder::new("Folder").with_name("Outer"),
+    );
+    tree.insert(
+        folder,
+        InstanceBuilder::new("ObjectValue")
+            .with_name("Pointer")
+            .with_property("Value", folder),
+    );
+    tree.insert(
+        folder,
+        InstanceBuilder::new("Part")
+            .with_name("Floor")
+            .with_property("Anchored", true)
+            .with_property("Size", Vector3::new(1.0, 2.0, 3.0)),
+    );
+    let mut buffer = Vec::new();
+    to_writer(&mut buffer, &tree, &[folder]).expect("failed to encode model");
+
+    let eager = Deserializer::new()
+        .deserialize(buffer.as_slice())
+        .expect("failed to decode model");
+
+    let mut lazy = Deserializer::new()
+        .deserialize_lazy(buffer.as_slice())
+        .expect("failed to decode model");
+
+    let outer_ref = lazy.tree().root().children()[0];
+    let outer = lazy.tree().get_by_ref(outer_ref).unwrap();
+    assert_eq!(outer.name, "Outer");
+    assert!(outer.properties.is_empty());
+
+    let children = outer.children().to_vec();
+    let pointer_ref = children
+        .iter()
+        .copied()
+        .find(|&child| lazy.tree().get_by_ref(child).unwrap().name == "Pointer")
+        .unwrap();
+    let floor_ref = children
+        .iter()
+        .copied()
+        .find(|&child| lazy.tree().get_by_ref(child).unwrap().name == "Floor")
+        .unwrap();
+
+    assert_eq!(
+        lazy.property(pointer_ref, "Value").unwrap(),
+        Some(&Variant::Ref(outer_ref))
+    );
+    assert_eq!(
+        lazy.property(floor_ref, "Anchored").unwrap(),
+        Some(&Variant::Bool(true))
+    );
+
+    // Only the property that was asked for should have been decoded.
+    let floor = lazy.tree().get_by_ref(floor_ref).unwrap();
+    assert_eq!(floor.properties.len(), 1);
+
+    let lazy = lazy.into_tree().expect("failed to decode properties");
+    let eager_floor = eager
+        .get_by_ref(
+            eager
+                .get_by_ref(eager.root().children()[0])
+                .unwrap()
+                .children()[1],
+        )
+        .unwrap();
+    let lazy_floor = lazy.get_by_ref(floor_ref).unwrap();
+    assert_eq!(lazy_floor.properties, eager_floor.properties);
+}
+
+/// Ensures that progress is reported for every chunk read and written, and
+/// that the progress callback can cancel decoding and encoding.
+#[test]
+fn progress_and_cancellation() {
+    use std::{
+        ops::ControlFlow,
+        sync::{Arc, Mutex},
+    };
+
+    use crate::Progress;
+
+    let tree = WeakDom::new(InstanceBuilder::new("Folder").with_children(vec![
+        InstanceBuilder::new("Part"),
+        InstanceBuilder::new("Part"),
+        InstanceBuilder::new("StringValue").with_property("Value", "Hello"),
+    ]));
+
+    let written = Arc::new(Mutex::new(Vec::new()));
+    let mut buffer = Vec::new();
+    Serializer::new()
+        .on_progress({
+            let written = Arc::clone(&written);
+            move |progress| {
+                written.lock().unwrap().push(progress);
+                ControlFlow::Continue(())
+            }
+        })
+        .serialize(&mut buffer, &tree, &[tree.root_ref()])
+        .expect("failed to encode model");
+
+    let num_chunks = ChunkReader::new(buffer.as_slice()).unwrap().count();
+    let written = written.lock().unwrap();
+    assert_eq!(written.len(), num_chunks);
+
+    let last = written.last().unwrap();
+    assert_eq!(last.chunks, num_chunks);
+    assert_eq!(last.instances, 4);
+    assert_eq!(last.total_instances, Some(4));
+
+    let read = Mutex::new(Vec::new());
+    Deserializer::new()
+        .on_progress(|progress| {
+            read.lock().unwrap().push(progress);
+            ControlFlow::Continue(())
+        })
+        .deserialize(buffer.as_slice())
+        .expect("failed to decode model");
+
+    let read = read.into_inner().unwrap();
+    let chunks: Vec<usize> = read.iter().map(|progress| progress.chunks).collect();
+    assert_eq!(chunks, (1..=num_chunks).collect::<Vec<_>>());
+    assert_eq!(read.last().unwrap().instances, 4);
+    assert_eq!(read.last().unwrap().total_instances, Some(4));
+
+    // Stop as soon as any instances have been read.
+    let deserializer = Deserializer::new().on_progress(|progress: Progress| {
+        if progress.instances > 0 {
+            ControlFlow::Break(())
+        } else {
+            ControlFlow::Continue(())
+        }
+    });
+
+    let error = deserializer.deserialize(buffer.as_slice()).unwrap_err();
+    assert!(error.is_cancelled());
+    assert_eq!(error.chunk_name(), Some("INST"));
+
+    let error = deserializer
+        .deserialize_recovering(buffer.as_slice())
+        .unwrap_err();
+    assert!(error.is_cancelled());
+
+    let error = deserializer
+        .deserialize_lazy(buffer.as_slice())
+        .err()
+        .unwrap();
+    assert!(error.is_cancelled());
+
+    let events: Vec<_> = deserializer.stream(buffer.as_slice()).unwrap().collect();
+    assert!(events.last().unwrap().as_ref().unwrap_err().is_cancelled());
+
+    let error = Serializer::new()
+        .on_progress(|_| ControlFlow::Break(()))
+        .serialize(Vec::new(), &tree, &[tree.root_ref()])
+        .unwrap_err();
+    assert!(error.is_cancelled());
+}
+
+/// Ensures that metadata is written in a META chunk and read back out.
+#[test]
+fn metadata() {
+    use std::collections::HashMap;
+
+    let tree = WeakDom::new(InstanceBuilder::new("Folder"));
+
+    let mut metadata = HashMap::new();
+    metadata.insert("ExplicitAutoJoints".to_owned(), "true".to_owned());
+    metadata.insert("Author".to_owned(), "rbx_binary".to_owned());
+
+    let mut buffer = Vec::new();
+    Serializer::new()
+        .metadata(metadata.clone())
+        .serialize(&mut buffer, &tree, &[tree.root_ref()])
+        .expect("failed to encode model");
+
+    let names: Vec<_> = ChunkReader::new(buffer.as_slice())
+        .unwrap()
+        .map(|chunk| chunk.unwrap().name)
+        .collect();
+    assert_eq!(&names[0], b"META");
+
+    let document = Deserializer::new()
+        .deserialize_document(buffer.as_slice())
+        .expect("failed to decode model");
+    assert_eq!(document.metadata, metadata);
+
+    // Files without metadata don't get an empty META chunk.
+    let mut buffer = Vec::new();
+    to_writer(&mut buffer, &tree, &[tree.root_ref()]).expect("failed to encode model");
+    assert!(ChunkReader::new(buffer.as_slice())
+        .unwrap()
+        .all(|chunk| &chunk.unwrap().name != b"META"));
+}
+
+/// Ensures that referents read from a file can be written back out, and that
+/// instances without a usable referent get free ones.
+#[test]
+fn preserved_referents() {
+    use std::collections::HashMap;
+
+    use crate::ReferentStrategy;
+
+    let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+    let folder = tree.insert(tree.root_ref(), InstanceBuilder::new("Folder"));
+    let first = tree.insert(folder, InstanceBuilder::new("Part").with_name("First"));
+    let second = tree.insert(folder, InstanceBuilder::new("Part").with_name("Second"));
+    tree.insert(folder, InstanceBuilder::new("Part").with_name("Third"));
+
+    let mut referents = HashMap::new();
+    referents.insert(folder, 100);
+    referents.insert(first, 7);
+    // This is already taken by the first part, and the third part has none.
+    referents.insert(second, 7);
+
+    let mut buffer = Vec::new();
+    Serializer::new()
+        .referents(ReferentStrategy::Preserve(referents))
+        .serialize(&mut buffer, &tree, &[folder])
+        .expect("failed to encode model");
+
+    let document = Deserializer::new()
+        .deserialize_document(buffer.as_slice())
+        .expect("failed to decode model");
+
+    let by_name: HashMap<_, _> = document
+        .referents
+        .iter()
+        .map(|(&referent, &file_referent)| {
+            let instance = document.tree.get_by_ref(referent).unwrap();
+            (instance.name.as_str(), file_referent)
+        })
+        .collect();
+    assert_eq!(by_name["Folder"], 100);
+    assert_eq!(by_name["First"], 7);
+    assert_eq!(by_name["Second"], 0);
+    assert_eq!(by_name["Third"], 1);
+
+    // Writing the file back out with the referents it was read with doesn't
+    // change it.
+    let mut rewritten = Vec::new();
+    Serializer::new()
+        .referents(ReferentStrategy::Preserve(document.referents.clone()))
+        .serialize(
+            &mut rewritten,
+            &document.tree,
+            document.tree.root().children(),
+        )
+        .expect("failed to encode model");
+    assert_eq!(rewritten, buffer);
+}
+
+/// Ensures that validating a file finds problems in its chunks and in the file
+/// as a whole.
+#[test]
+fn validate() {
+    let valid = damaged_model(CompressionType::Lz4, |_| {});
+    let report = Deserializer::new()
+        .validate(valid.as_slice())
+        .expect("failed to read header");
+    assert!(report.is_valid(), "{}", report);
+    assert_eq!(
+        report.chunks,
+        ChunkReader::new(valid.as_slice()).unwrap().count()
+    );
+
+    let damaged = damaged_model(CompressionType::None, |chunks| {
+        let value = chunks
+            .iter_mut()
+            .filter(|chunk| &chunk.name == b"PROP")
+            .find(|chunk| chunk.data.windows(5).any(|window| window == b"Hello"))
+            .unwrap();
+        value.data.extend_from_slice(&[0; 3]);
+        value.len += 3;
+
+        let prnt = chunks
+            .iter()
+            .position(|chunk| &chunk.name == b"PRNT")
+            .unwrap();
+        let duplicate = chunks[prnt].clone();
+        chunks.insert(prnt, duplicate);
+    });
+
+    // Extra bytes at the end of a chunk don't stop the file from decoding.
+    assert!(Deserializer::new().deserialize(damaged.as_slice()).is_ok());
+
+    let report = Deserializer::new()
+        .validate(damaged.as_slice())
+        .expect("failed to read header");
+    assert_eq!(report.problems.len(), 2, "{}", report);
+
+    let trailing = &report.problems[0];
+    assert_eq!(trailing.chunk_name(), Some("PROP"));
+    assert_eq!(trailing.property_name(), Some("Value"));
+    assert!(trailing.to_string().contains("3 bytes left over"));
+
+    let parents = &report.problems[1];
+    assert_eq!(parents.chunk_name(), Some("PRNT"));
+    assert!(parents
+        .to_string()
+        .contains("given a parent more than once"));
+}
+
+/// Ensures that validating a file finds instances that are never given a
+/// parent.
+#[test]
+fn validate_missing_parents() {
+    let buffer = damaged_model(CompressionType::Lz4, |chunks| {
+        chunks.retain(|chunk| &chunk.name != b"PRNT");
+    });
+
+    let report = Deserializer::new()
+        .validate(buffer.as_slice())
+        .expect("failed to read header");
+    assert_eq!(report.problems.len(), 1, "{}", report);
+
+    let problem = &report.problems[0];
+    assert_eq!(problem.chunk_name(), None);
+    assert!(problem
+        .to_string()
+        .starts_with("3 instances were never given a parent"));
+}
+
+/// Ensures that compressing chunks, which may happen on multiple threads,
+/// doesn't change the order or contents of the chunks that are written.
+#[test]
+fn compressed_chunk_order() {
+    let mut root = InstanceBuilder::new("Folder");
+    for class in ["Part", "Model", "StringValue", "Folder", "Script"] {
+        for i in 0..50 {
+            root = root.with_child(
+                InstanceBuilder::new(class)
+                    .with_name(format!("{}{}", class, i))
+                    .with_property("Tags", Tags::from(vec![format!("tag {}", i)])),
+            );
+        }
+    }
+    let tree = WeakDom::new(root);
+
+    let read_chunks = |compression| {
+        let mut buffer = Vec::new();
+        Serializer::new()
+            .compression(compression)
+            .serialize(&mut buffer, &tree, &[tree.root_ref()])
+            .expect("failed to encode model");
+
+        ChunkReader::new(buffer.as_slice())
+            .unwrap()
+            .map(|chunk| {
+                let chunk = chunk.unwrap();
+                (chunk.name, chunk.decompressed().unwrap().into_owned())
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let uncompressed = read_chunks(CompressionType::None);
+    let compressed = read_chunks(CompressionType::Lz4);
+
+    assert!(uncompressed.len() > 10);
+    assert!(uncompressed == compressed);
+}
+
+/// Ensures that the same tree is always written the same way, with PROP chunks
+/// sorted by class and property name, even when its properties were added in
+/// a different order.
+#[test]
+fn deterministic_output() {
+    use std::collections::HashMap;
+
+    use crate::core::RbxReadExt;
+
+    let properties: Vec<(&str, Variant)> = vec![
+        ("Anchored", true.into()),
+        ("Transparency", 0.5f32.into()),
+        ("Locked", true.into()),
+        ("CastShadow", false.into()),
+        ("Reflectance", 0.25f32.into()),
+        ("UnknownProperty", "Hello".into()),
+    ];
+
+    let build = |reversed: bool| {
+        let mut ordered = properties.clone();
+        if reversed {
+            ordered.reverse();
+        }
+
+        let part = ordered
+            .into_iter()
+            .fold(InstanceBuilder::new("Part"), |part, (name, value)| {
+                part.with_property(name, value)
+            });
+
+        WeakDom::new(
+            InstanceBuilder::new("Model")
+                .with_child(part)
+                .with_child(InstanceBuilder::new("StringValue").with_property("Value", "Hi"))
+                .with_child(InstanceBuilder::new("Folder")),
+        )
+    };
+
+    let encode = |tree: &WeakDom| {
+        let mut buffer = Vec::new();
+        Serializer::new()
+            .serialize(&mut buffer, tree, &[tree.root_ref()])
+            .expect("failed to encode model");
+        buffer
+    };
+
+    let first = encode(&build(false));
+    for _ in 0..4 {
+        assert_eq!(encode(&build(false)), first);
+        assert_eq!(encode(&build(true)), first);
+    }
+
+    let mut classes = HashMap::new();
+    let mut prop_chunks = Vec::new();
+
+    for chunk in ChunkReader::new(first.as_slice()).unwrap() {
+        let chunk = chunk.unwrap();
+        let data = chunk.decompressed().unwrap();
+        let mut contents: &[u8] = &data;
+
+        match &chunk.name {
+            b"INST" => {
+                let type_id = contents.read_le_u32().unwrap();
+                classes.insert(type_id, contents.read_string().unwrap());
+            }
+            b"PROP" => {
+                let type_id = contents.read_le_u32().unwrap();
+                let name = contents.read_string().unwrap();
+                prop_chunks.push((classes[&type_id].clone(), name));
+            }
+            _ => {}
+        }
+    }
+
+    let mut sorted = prop_chunks.clone();
+    sorted.sort();
+    assert_eq!(prop_chunks, sorted);
+}
+
+/// Ensures that `Ray` values keep all of their components.
+#[test]
+fn ray_round_trip() {
+    let ray = Ray::new(Vector3::new(1.0, 2.0, 3.0), Vector3::new(4.0, 5.0, 6.0));
+    let tree = WeakDom::new(InstanceBuilder::new("RayValue").with_property("Value", ray));
+
+    let mut buffer = Vec::new();
+    Serializer::new()
+        .serialize(&mut buffer, &tree, &[tree.root_ref()])
+        .expect("failed to encode model");
+
+    let decoded = Deserializer::new()
+        .deserialize(buffer.as_slice())
+        .expect("failed to decode model");
+    let value = decoded.get_by_ref(decoded.root().children()[0]).unwrap();
+    assert_eq!(value.properties.get("Value"), Some(&Variant::Ray(ray)));
+}
+
+/// Ensures that properties stored the way older versions of Roblox stored
+/// them can be decoded.
+#[test]
+fn legacy_property_types() {
+    let tree = WeakDom::new(
+        InstanceBuilder::new("Folder")
+            .with_child(
+                InstanceBuilder::new("Part").with_property("Color", Color3uint8::new(1, 2, 3)),
+            )
+            .with_child(InstanceBuilder::new("IntValue").with_property("Value", 0i64))
+            .with_child(InstanceBuilder::new("Frame").with_property("ZIndex", 0i32))
+            .with_child(InstanceBuilder::new("Handles").with_property("Faces", Faces::empty())),
+    );
+
+    // Replaces the PROP chunk for the property named `old_name` with one for
+    // `new_name` containing the given type ID and values.
+    let replace =
+        |chunks: &mut Vec<crate::RawChunk>, old_name: &[u8], new_name: &str, values: &[u8]| {
+            let chunk = chunks
+                .iter_mut()
+                .filter(|chunk| &chunk.name == b"PROP")
+                .find(|chunk| chunk.data[8..].starts_with(old_name))
+                .unwrap();
+
+            let mut data = chunk.data[..4].to_vec();
+            data.extend_from_slice(&(new_name.len() as u32).to_le_bytes());
+            data.extend_from_slice(new_name.as_bytes());
+            data.extend_from_slice(values);
+
+            chunk.len = data.len() as u32;
+            chunk.data = data;
+        };
+
+    let buffer = rewritten_model(&tree, CompressionType::None, |chunks| {
+        // Part.BrickColor as a BrickColor, Really red (1004).
+        replace(
+            chunks,
+            b"Color3uint8",
+            "BrickColor",
+            &[0x0b, 0x00, 0x00, 0x03, 0xec],
+        );
+
+        // IntValue.Value as an Int32, -5.
+        replace(chunks, b"Value", "Value", &[0x03, 0x00, 0x00, 0x00, 0x09]);
+
+        // Frame.ZIndex as an Int64, 3.
+        replace(
+            chunks,
+            b"ZIndex",
+            "ZIndex",
+            &[0x1b, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x06],
+        );
+
+        // Handles.Faces with every bit set, including unused ones.
+        replace(chunks, b"Faces", "Faces", &[0x09, 0xff]);
+    });
+
+    let decoded = Deserializer::new()
+        .deserialize(buffer.as_slice())
+        .expect("failed to decode model");
+    let folder = decoded.get_by_ref(decoded.root().children()[0]).unwrap();
+
+    let property = |class: &str, name: &str| {
+        let referent = folder
+            .children()
+            .iter()
+            .copied()
+            .find(|&referent| decoded.get_by_ref(referent).unwrap().class == class)
+            .unwrap();
+        decoded
+            .get_by_ref(referent)
+            .unwrap()
+            .properties
+            .get(name)
+            .cloned()
+    };
+
+    assert_eq!(
+        property("Part", "Color"),
+        Some(Color3uint8::new(255, 0, 0).into())
+    );
+    assert_eq!(property("IntValue", "Value"), Some(Variant::Int64(-5)));
+    assert_eq!(property("Frame", "ZIndex"), Some(Variant::Int32(3)));
+    assert_eq!(property("Handles", "Faces"), Some(Faces::all().into()));
+}
+
+/// Encodes a folder with a few instances in it for patching, returning the
+/// file and its raw chunks.
+fn patchable_model() -> (Vec<u8>, Vec<crate::RawChunk>) {
+    let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+    let folder = tree.insert(
+        tree.root_ref(),
+        InstanceBuilder::new("Folder").with_name("Outer"),
+    );
+    tree.insert(
+        folder,
+        InstanceBuilder::new("Part")
+            .with_name("Floor")
+            .with_property("Anchored", true),
+    );
+    let wall = tree.insert(
+        folder,
+        InstanceBuilder::new("Part")
+            .with_name("Wall")
+            .with_property("Size", Vector3::new(1.0, 2.0, 3.0)),
+    );
+    tree.insert(
+        folder,
+        InstanceBuilder::new("ObjectValue")
+            .with_name("Pointer")
+            .with_property("Value", wall),
+    );
+    tree.insert(
+        folder,
+        InstanceBuilder::new("StringValue")
+            .with_name("Label")
+            .with_property("Value", "Hello"),
+    );
+
+    let mut buffer = Vec::new();
+    to_writer(&mut buffer, &tree, &[folder]).expect("failed to encode model");
+
+    let chunks = ChunkReader::new(buffer.as_slice())
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+
+    (buffer, chunks)
+}
+
+/// Finds the child of `parent` with the given name.
+fn child_named(tree: &WeakDom, parent: Ref, name: &str) -> Ref {
+    tree.get_by_ref(parent)
+        .unwrap()
+        .children()
+        .iter()
+        .copied()
+        .find(|&child| tree.get_by_ref(child).unwrap().name == name)
+        .unwrap()
+}
+
+/// Writes out a patch, checks that the file is valid, and decodes it.
+fn write_patch(patch: &crate::FilePatch) -> (WeakDom, Vec<crate::RawChunk>) {
+    let mut buffer = Vec::new();
+    Serializer::new()
+        .serialize_patch(&mut buffer, patch)
+        .expect("failed to encode patch");
+
+    let report = Deserializer::new()
+        .validate(buffer.as_slice())
+        .expect("failed to read header");
+    assert!(report.is_valid(), "{}", report);
+
+    let chunks = ChunkReader::new(buffer.as_slice())
+        .unwrap()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    let tree = Deserializer::new()
+        .deserialize(buffer.as_slice())
+        .expect("failed to decode patched model");
+
+    (tree, chunks)
+}
+
+/// Ensures that instances inserted into a patched file are added to it, while
+/// every chunk that was already there is copied over unchanged.
+#[test]
+fn patch_insert() {
+    let (buffer, original_chunks) = patchable_model();
+
+    let mut patch = Deserializer::new()
+        .patch(buffer.as_slice())
+        .expect("failed to read model");
+    let outer = patch.tree().root().children()[0];
+    patch.insert(
+        outer,
+        InstanceBuilder::new("StringValue")
+            .with_name("Added")
+            .with_property("Value", "World")
+            .with_child(InstanceBuilder::new("Folder").with_name("Nested")),
+    );
+
+    let (tree, chunks) = write_patch(&patch);
+
+    for chunk in original_chunks
+        .iter()
+        .filter(|chunk| &chunk.name != b"END\0")
+    {
+        assert!(chunks.contains(chunk), "chunk was changed or dropped");
+    }
+
+    let outer = tree.root().children()[0];
+    assert_eq!(tree.get_by_ref(outer).unwrap().children().len(), 5);
+
+    let added = tree.get_by_ref(child_named(&tree, outer, "Added")).unwrap();
+    assert_eq!(
+        added.properties.get("Value"),
+        Some(&Variant::String("World".to_owned()))
+    );
+    assert_eq!(added.children().len(), 1);
+
+    let label = tree.get_by_ref(child_named(&tree, outer, "Label")).unwrap();
+    assert_eq!(
+        label.properties.get("Value"),
+        Some(&Variant::String("Hello".to_owned()))
+    );
+
+    let pointer = tree
+        .get_by_ref(child_named(&tree, outer, "Pointer"))
+        .unwrap();
+    assert_eq!(
+        pointer.properties.get("Value"),
+        Some(&Variant::Ref(child_named(&tree, outer, "Wall")))
+    );
+}
+
+/// Ensures that removing and replacing instances in a patched file rewrites
+/// only the chunks of their classes, and that `Ref`s from elsewhere in the file
+/// to instances in rewritten chunks still point to them.
+#[test]
+fn patch_remove_and_replace() {
+    let (buffer, original_chunks) = patchable_model();
+
+    let original = Deserializer::new()
+        .deserialize(buffer.as_slice())
+        .expect("failed to decode model");
+    let original_outer = original.root().children()[0];
+    let original_wall = original
+        .get_by_ref(child_named(&original, original_outer, "Wall"))
+        .unwrap();
+
+    let mut patch = Deserializer::new()
+        .patch(buffer.as_slice())
+        .expect("failed to read model");
+    let outer = patch.tree().root().children()[0];
+
+    let floor = child_named(patch.tree(), outer, "Floor");
+    patch.remove(floor).expect("failed to remove instance");
+
+    let label = child_named(patch.tree(), outer, "Label");
+    patch
+        .replace(
+            label,
+            InstanceBuilder::new("Folder").with_name("Replacement"),
+        )
+        .expect("failed to replace instance");
+
+    let (tree, chunks) = write_patch(&patch);
+
+    let outer = tree.root().children()[0];
+    let mut names: Vec<_> = tree
+        .get_by_ref(outer)
+        .unwrap()
+        .children()
+        .iter()
+        .map(|&child| tree.get_by_ref(child).unwrap().name.as_str())
+        .collect();
+    names.sort_unstable();
+    assert_eq!(names, ["Pointer", "Replacement", "Wall"]);
+
+    let wall_ref = child_named(&tree, outer, "Wall");
+    let wall = tree.get_by_ref(wall_ref).unwrap();
+    assert_eq!(wall.properties, original_wall.properties);
+
+    let pointer = tree
+        .get_by_ref(child_named(&tree, outer, "Pointer"))
+        .unwrap();
+    assert_eq!(
+        pointer.properties.get("Value"),
+        Some(&Variant::Ref(wall_ref))
+    );
+
+    // The ObjectValue's chunks weren't touched, so they're copied over as
+    // they were.
+    let pointer_chunks: Vec<_> = original_chunks
+        .iter()
+        .filter(|chunk| chunk.data.windows(7).any(|window| window == b"Pointer"))
+        .collect();
+    assert!(!pointer_chunks.is_empty());
+    for chunk in pointer_chunks {
+        assert!(chunks.contains(chunk), "chunk was changed or dropped");
+    }
+}
+
+/// Ensures that files in a newer version of the format only decode when that's
+/// allowed, and that what might have gone wrong is reported.
+#[test]
+fn newer_version() {
+    let with_unknown_chunk = |version: u16| {
+        let mut buffer = damaged_model(CompressionType::Lz4, |chunks| {
+            let end = chunks.len() - 1;
+            chunks.insert(
+                end,
+                crate::RawChunk {
+                    name: *b"NEWC",
+                    compressed_len: 0,
+                    len: 3,
+                    reserved: 0,
+                    data: vec![1, 2, 3],
+                },
+            );
+        });
+        buffer[14..16].copy_from_slice(&version.to_le_bytes());
+        buffer
+    };
+
+    let current = with_unknown_chunk(0);
+    let newer = with_unknown_chunk(1);
+
+    let error = Deserializer::new()
+        .deserialize(newer.as_slice())
+        .unwrap_err();
+    assert!(error.to_string().contains("Unknown file version 1"));
+
+    let deserializer = Deserializer::new().allow_newer_versions(true);
+
+    // Unknown chunks in files of the current version aren't a problem.
+    let expected = deserializer
+        .deserialize_document(current.as_slice())
+        .expect("failed to decode model");
+    assert!(expected.warnings.is_empty());
+
+    let document = deserializer
+        .deserialize_document(newer.as_slice())
+        .expect("failed to decode newer model");
+    assert_eq!(document.referents.len(), expected.referents.len());
+    assert_eq!(document.metadata, expected.metadata);
+    assert_eq!(document.unknown_chunks.len(), 1);
+    assert_eq!(document.warnings.len(), 2);
+
+    let version = &document.warnings[0];
+    assert_eq!(version.chunk_name(), None);
+    assert!(version.to_string().contains("File version 1 is newer"));
+
+    let chunk = &document.warnings[1];
+    assert_eq!(chunk.chunk_name(), Some("NEWC"));
+    assert!(chunk.to_string().contains("Unknown chunk NEWC"));
+
+    let mut lazy = deserializer
+        .deserialize_lazy(newer.as_slice())
+        .expect("failed to decode newer model lazily");
+    let outer = lazy.tree().root().children()[0];
+    let value = lazy.tree().get_by_ref(outer).unwrap().children()[0];
+    assert_eq!(
+        lazy.property(value, "Value").unwrap(),
+        Some(&Variant::String("Hello".to_owned()))
+    );
+}
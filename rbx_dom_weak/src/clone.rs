@@ -0,0 +1,123 @@
+use std::collections::HashMap;
+
+use rbx_types::{Ref, Variant};
+
+use crate::{Instance, InstanceBuilder, WeakDom};
+
+impl WeakDom {
+    /// Clones the instance with the given referent and those of its
+    /// descendants that pass `filter` into a new `WeakDom`.
+    ///
+    /// The root instance is always cloned. If a descendant doesn't pass the
+    /// filter, none of its descendants are cloned either.
+    ///
+    /// Cloned instances are given new referents. `Ref` properties pointing to
+    /// instances that were cloned are rewritten to point to their clones, and
+    /// all other `Ref` properties are set to null, since they would not point
+    /// to anything in the new DOM.
+    ///
+    /// ## Panics
+    /// Panics if `referent` does not refer to an instance in the DOM.
+    pub fn clone_filtered<F>(&self, referent: Ref, filter: F) -> WeakDom
+    where
+        F: Fn(&Instance) -> bool,
+    {
+        let root = self
+            .get_by_ref(referent)
+            .unwrap_or_else(|| panic!("cannot clone an instance that does not exist"));
+
+        let root_builder = clone_instance(root);
+        let mut referents = HashMap::new();
+        referents.insert(root.referent(), root_builder.referent());
+
+        let mut dom = WeakDom::new(root_builder);
+
+        for (_, instance) in self.preorder(root).into_iter().skip(1) {
+            let parent = match referents.get(&instance.parent()) {
+                Some(parent) => *parent,
+                None => continue,
+            };
+
+            if !filter(instance) {
+                continue;
+            }
+
+            let new_referent = dom.insert(parent, clone_instance(instance));
+            referents.insert(instance.referent(), new_referent);
+        }
+
+        for new_referent in referents.values() {
+            let instance = dom.get_by_ref_mut(*new_referent).unwrap();
+
+            for value in instance.properties.values_mut() {
+                if let Variant::Ref(target) = value {
+                    *target = referents.get(target).copied().unwrap_or_else(Ref::none);
+                }
+            }
+        }
+
+        dom
+    }
+}
+
+fn clone_instance(instance: &Instance) -> InstanceBuilder {
+    InstanceBuilder::new(instance.class.as_str())
+        .with_name(instance.name.as_str())
+        .with_properties(instance.properties.clone())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::DomViewer;
+
+    #[test]
+    fn skips_filtered() {
+        let door = InstanceBuilder::new("Part").with_name("Door");
+        let door_ref = door.referent();
+
+        let dom = WeakDom::new(
+            InstanceBuilder::new("Model")
+                .with_property("PrimaryPart", door_ref)
+                .with_child(
+                    door.with_child(InstanceBuilder::new("Script"))
+                        .with_child(InstanceBuilder::new("Attachment")),
+                )
+                .with_child(
+                    InstanceBuilder::new("Script")
+                        .with_name("Skipped")
+                        .with_child(InstanceBuilder::new("Folder").with_name("Also Skipped")),
+                ),
+        );
+
+        let cloned = dom.clone_filtered(dom.root_ref(), |instance| instance.class != "Script");
+
+        // This snapshot should contain Door and its Attachment, with
+        // PrimaryPart pointing to Door.
+        insta::assert_yaml_snapshot!(DomViewer::new().view(&cloned));
+    }
+
+    #[test]
+    fn nulls_outside_refs() {
+        let outside = InstanceBuilder::new("Folder");
+        let outside_ref = outside.referent();
+
+        let target = InstanceBuilder::new("ObjectValue").with_property("Value", outside_ref);
+        let target_ref = target.referent();
+
+        let dom = WeakDom::new(
+            InstanceBuilder::new("Folder")
+                .with_child(outside)
+                .with_child(target),
+        );
+
+        let cloned = dom.clone_filtered(target_ref, |_| true);
+
+        assert_ne!(cloned.root_ref(), target_ref);
+        assert_eq!(
+            cloned.root().properties.get("Value"),
+            Some(&Variant::Ref(Ref::none()))
+        );
+    }
+}
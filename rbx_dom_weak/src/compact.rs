@@ -0,0 +1,85 @@
+use std::collections::HashSet;
+
+use rbx_types::Ref;
+
+use crate::WeakDom;
+
+impl WeakDom {
+    /// Returns the referents of all instances in the DOM that cannot be
+    /// reached by following children from the root, in no particular order.
+    ///
+    /// A DOM that is only ever changed through the methods on `WeakDom` never
+    /// contains unreachable instances, so this is primarily useful for
+    /// diagnosing bugs.
+    pub fn find_orphans(&self) -> Vec<Ref> {
+        let reachable = self.reachable();
+
+        self.instances
+            .keys()
+            .filter(|referent| !reachable.contains(referent))
+            .copied()
+            .collect()
+    }
+
+    /// Removes all instances that cannot be reached from the root, then
+    /// shrinks the DOM's internal storage to fit the instances that remain.
+    /// Returns the number of instances that were removed.
+    ///
+    /// Removing unreachable instances is not recorded in transactions or undo
+    /// history.
+    pub fn compact(&mut self) -> usize {
+        let orphans = self.find_orphans();
+
+        for referent in &orphans {
+            self.instances.remove(referent);
+            self.indices.remove(*referent);
+        }
+
+        self.instances.shrink_to_fit();
+        for instance in self.instances.values_mut() {
+            instance.children.shrink_to_fit();
+            instance.properties.shrink_to_fit();
+        }
+
+        orphans.len()
+    }
+
+    fn reachable(&self) -> HashSet<Ref> {
+        self.preorder(self.root())
+            .into_iter()
+            .map(|(_, instance)| instance.referent())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::InstanceBuilder;
+
+    #[test]
+    fn compact_orphans() {
+        let child = InstanceBuilder::new("Folder").with_child(InstanceBuilder::new("Part"));
+        let child_ref = child.referent();
+
+        let mut dom = WeakDom::new(InstanceBuilder::new("DataModel").with_child(child));
+        dom.enable_name_index();
+        assert!(dom.find_orphans().is_empty());
+
+        // Simulate a bug that unlinks an instance from its parent without
+        // removing it from the DOM.
+        let root_ref = dom.root_ref();
+        dom.instances.get_mut(&root_ref).unwrap().children.clear();
+
+        let orphans: HashSet<Ref> = dom.find_orphans().into_iter().collect();
+        let grandchild_ref = dom.get_by_ref(child_ref).unwrap().children()[0];
+        assert_eq!(orphans, HashSet::from([child_ref, grandchild_ref]));
+
+        assert_eq!(dom.compact(), 2);
+        assert!(dom.find_orphans().is_empty());
+        assert!(dom.get_by_ref(child_ref).is_none());
+        assert!(dom.find_all_named("Folder").is_empty());
+        assert_eq!(dom.compact(), 0);
+    }
+}
@@ -0,0 +1,151 @@
+use std::collections::BTreeMap;
+
+use rbx_types::Variant;
+
+use crate::Instance;
+
+/// The float tolerance used by [`diff_properties`].
+pub use rbx_types::DEFAULT_FLOAT_TOLERANCE;
+
+/// The differences between the properties of two instances, as returned by
+/// [`diff_properties`]. Each map is keyed by property name.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[non_exhaustive]
+pub struct PropertyDiff<'a> {
+    /// Properties that are only present on the second instance, with their
+    /// values.
+    pub added: BTreeMap<&'a str, &'a Variant>,
+
+    /// Properties that are only present on the first instance, with their
+    /// values.
+    pub removed: BTreeMap<&'a str, &'a Variant>,
+
+    /// Properties that are present on both instances with different values.
+    pub changed: BTreeMap<&'a str, ChangedProperty<'a>>,
+}
+
+impl<'a> PropertyDiff<'a> {
+    /// Returns whether the two instances had the same properties.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// A property whose value differs between two instances.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub struct ChangedProperty<'a> {
+    /// The value of the property on the first instance.
+    pub old: &'a Variant,
+
+    /// The value of the property on the second instance.
+    pub new: &'a Variant,
+}
+
+/// Compares the properties of two instances, treating floating point
+/// components that differ by no more than [`DEFAULT_FLOAT_TOLERANCE`] as
+/// equal. Names and classes are not compared.
+///
+/// `SharedString` values are compared by their hash, and `Ref` values are
+/// compared directly.
+///
+/// ## Example
+/// ```
+/// use rbx_dom_weak::{diff_properties, InstanceBuilder, WeakDom};
+///
+/// let a = WeakDom::new(InstanceBuilder::new("Part").with_property("Anchored", false));
+/// let b = WeakDom::new(InstanceBuilder::new("Part").with_property("Anchored", true));
+///
+/// let diff = diff_properties(a.root(), b.root());
+/// assert!(diff.changed.contains_key("Anchored"));
+/// ```
+pub fn diff_properties<'a>(a: &'a Instance, b: &'a Instance) -> PropertyDiff<'a> {
+    diff_properties_with_tolerance(a, b, DEFAULT_FLOAT_TOLERANCE)
+}
+
+/// Like [`diff_properties`], but uses the given tolerance when comparing
+/// floating point components. See [`Variant::approx_eq`].
+pub fn diff_properties_with_tolerance<'a>(
+    a: &'a Instance,
+    b: &'a Instance,
+    float_tolerance: f32,
+) -> PropertyDiff<'a> {
+    let mut diff = PropertyDiff::default();
+
+    for (name, old) in &a.properties {
+        match b.properties.get(name) {
+            Some(new) => {
+                if !old.approx_eq(new, float_tolerance) {
+                    diff.changed
+                        .insert(name.as_str(), ChangedProperty { old, new });
+                }
+            }
+            None => {
+                diff.removed.insert(name.as_str(), old);
+            }
+        }
+    }
+
+    for (name, new) in &b.properties {
+        if !a.properties.contains_key(name) {
+            diff.added.insert(name.as_str(), new);
+        }
+    }
+
+    diff
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rbx_types::SharedString;
+
+    use crate::{InstanceBuilder, WeakDom};
+
+    #[test]
+    fn added_removed_changed() {
+        let a = WeakDom::new(
+            InstanceBuilder::new("Part")
+                .with_property("Anchored", false)
+                .with_property("Locked", true)
+                .with_property("Transparency", 0.5f32),
+        );
+        let b = WeakDom::new(
+            InstanceBuilder::new("Part")
+                .with_property("Anchored", true)
+                .with_property("CastShadow", false)
+                .with_property("Transparency", 0.500001f32),
+        );
+
+        let diff = diff_properties(a.root(), b.root());
+
+        assert_eq!(diff.added.keys().collect::<Vec<_>>(), [&"CastShadow"]);
+        assert_eq!(diff.removed.keys().collect::<Vec<_>>(), [&"Locked"]);
+        assert_eq!(
+            diff.changed["Anchored"],
+            ChangedProperty {
+                old: &Variant::Bool(false),
+                new: &Variant::Bool(true),
+            }
+        );
+        assert_eq!(diff.changed.len(), 1);
+
+        let exact = diff_properties_with_tolerance(a.root(), b.root(), 0.0);
+        assert!(exact.changed.contains_key("Transparency"));
+    }
+
+    #[test]
+    fn shared_strings() {
+        let a = WeakDom::new(
+            InstanceBuilder::new("MeshPart")
+                .with_property("PhysicsData", SharedString::new(vec![1, 2, 3])),
+        );
+        let b = WeakDom::new(
+            InstanceBuilder::new("MeshPart")
+                .with_property("PhysicsData", SharedString::new(vec![1, 2, 3])),
+        );
+
+        assert!(diff_properties(a.root(), b.root()).is_empty());
+    }
+}
@@ -1,8 +1,17 @@
-use std::collections::{HashMap, VecDeque};
+use std::{
+    cmp::Ordering,
+    collections::{HashMap, VecDeque},
+};
 
 use rbx_types::Ref;
 
-use crate::instance::{Instance, InstanceBuilder};
+use crate::{
+    error::{Error, InnerError},
+    index::{self, Indices, StringIndex},
+    instance::{Instance, InstanceBuilder},
+    subtree::DetachedSubtree,
+    transaction::{Change, Journal},
+};
 
 /// Represents a DOM containing one or more Roblox instances.
 ///
@@ -13,8 +22,10 @@ use crate::instance::{Instance, InstanceBuilder};
 /// objects and insert them into the tree.
 #[derive(Debug)]
 pub struct WeakDom {
-    instances: HashMap<Ref, Instance>,
+    pub(crate) instances: HashMap<Ref, Instance>,
     root_ref: Ref,
+    pub(crate) indices: Indices,
+    pub(crate) journal: Journal,
 }
 
 impl WeakDom {
@@ -38,10 +49,12 @@ impl WeakDom {
         let mut dom = WeakDom {
             instances,
             root_ref,
+            indices: Indices::default(),
+            journal: Journal::default(),
         };
 
         for child in builder.children {
-            dom.insert(root_ref, child);
+            dom.insert_inner(root_ref, child);
         }
 
         dom
@@ -54,6 +67,23 @@ impl WeakDom {
         (self.root_ref, self.instances)
     }
 
+    /// Copies the DOM, keeping every instance's referent and the enabled
+    /// indices. The copy starts with no transactions or undo history.
+    pub(crate) fn copy_for_snapshot(&self) -> WeakDom {
+        let instances = self
+            .instances
+            .iter()
+            .map(|(referent, instance)| (*referent, instance.duplicate()))
+            .collect();
+
+        WeakDom {
+            instances,
+            root_ref: self.root_ref,
+            indices: self.indices.clone(),
+            journal: Journal::default(),
+        }
+    }
+
     /// Returns the referent of the root instance of the `WeakDom`.
     pub fn root_ref(&self) -> Ref {
         self.root_ref
@@ -66,7 +96,7 @@ impl WeakDom {
 
     /// Returns a _mutable_ reference to the root instance of the `WeakDom`.
     pub fn root_mut(&mut self) -> &mut Instance {
-        self.instances.get_mut(&self.root_ref).unwrap()
+        self.get_by_ref_mut(self.root_ref).unwrap()
     }
 
     /// Returns a reference to an instance by referent, or `None` if it is not
@@ -78,7 +108,109 @@ impl WeakDom {
     /// Returns a _mutable_ reference to an instance by referent, or `None` if
     /// it is not found.
     pub fn get_by_ref_mut(&mut self, referent: Ref) -> Option<&mut Instance> {
-        self.instances.get_mut(&referent)
+        self.indices.flush(&self.instances);
+        self.indices.mark_dirty(referent);
+
+        let instance = self.instances.get_mut(&referent)?;
+        self.journal.record_modified(instance);
+
+        Some(instance)
+    }
+
+    /// Enables an index of instances by name, making
+    /// [`WeakDom::find_all_named`] fast at the cost of some memory and some
+    /// extra work when instances are added or removed.
+    ///
+    /// The index is kept up to date as the DOM changes, including when
+    /// instances are renamed through [`WeakDom::get_by_ref_mut`].
+    pub fn enable_name_index(&mut self) {
+        if self.indices.name.is_none() {
+            self.indices.name = Some(StringIndex::new(index::name_key, &self.instances));
+        }
+    }
+
+    /// Disables the index of instances by name, freeing the memory it used.
+    pub fn disable_name_index(&mut self) {
+        self.indices.name = None;
+    }
+
+    /// Returns the referents of all instances in the DOM with the given name,
+    /// in no particular order.
+    ///
+    /// This searches every instance in the DOM unless the name index has been
+    /// turned on with [`WeakDom::enable_name_index`].
+    pub fn find_all_named(&self, name: &str) -> Vec<Ref> {
+        match &self.indices.name {
+            Some(index) => index.get(name, &self.instances),
+            None => self
+                .instances
+                .values()
+                .filter(|instance| instance.name == name)
+                .map(|instance| instance.referent)
+                .collect(),
+        }
+    }
+
+    /// Returns an iterator over all of the instances in the DOM that have the
+    /// given tag, in no particular order.
+    pub fn instances_with_tag<'a>(&'a self, tag: &'a str) -> impl Iterator<Item = &'a Instance> {
+        self.instances
+            .values()
+            .filter(move |instance| instance.has_tag(tag))
+    }
+
+    /// Returns the given instance followed by all of its descendants in
+    /// depth-first pre-order, paired with their depth relative to `root`.
+    pub(crate) fn preorder<'a>(&'a self, root: &'a Instance) -> Vec<(usize, &'a Instance)> {
+        let mut order = Vec::new();
+        let mut to_visit = vec![(0, root)];
+
+        while let Some((depth, instance)) = to_visit.pop() {
+            order.push((depth, instance));
+            to_visit.extend(
+                instance
+                    .children
+                    .iter()
+                    .rev()
+                    .map(|child| (depth + 1, self.instances.get(child).unwrap())),
+            );
+        }
+
+        order
+    }
+
+    /// Enables an index of instances by class, making
+    /// [`WeakDom::instances_of_class`] fast at the cost of some memory and some
+    /// extra work when instances are added or removed.
+    ///
+    /// The index is kept up to date as the DOM changes, including when
+    /// instances have their class changed through [`WeakDom::get_by_ref_mut`].
+    pub fn enable_class_index(&mut self) {
+        if self.indices.class.is_none() {
+            self.indices.class = Some(StringIndex::new(index::class_key, &self.instances));
+        }
+    }
+
+    /// Disables the index of instances by class, freeing the memory it used.
+    pub fn disable_class_index(&mut self) {
+        self.indices.class = None;
+    }
+
+    /// Returns the referents of all instances in the DOM with the given
+    /// ClassName, in no particular order. Subclasses are not included.
+    ///
+    /// This searches every instance in the DOM unless the class index has been
+    /// turned on with [`WeakDom::enable_class_index`].
+    pub fn instances_of_class(&self, class: &str) -> Vec<Ref> {
+        match &self.indices.class {
+            Some(index) => index.get(class, &self.instances),
+            None => self
+                .instances
+                .values()
+                .filter(|instance| instance.class == class)
+                .map(|instance| instance.referent)
+                .collect(),
+        }
     }
 
     /// Insert a new instance into the DOM with the given parent.
@@ -86,6 +218,15 @@ impl WeakDom {
     /// ## Panics
     /// Panics if `parent_ref` does not refer to an instance in the DOM.
     pub fn insert(&mut self, parent_ref: Ref, builder: InstanceBuilder) -> Ref {
+        self.indices.flush(&self.instances);
+
+        let referent = self.insert_inner(parent_ref, builder);
+        self.journal.record(Change::Added { referent });
+
+        referent
+    }
+
+    fn insert_inner(&mut self, parent_ref: Ref, builder: InstanceBuilder) -> Ref {
         let referent = builder.referent;
 
         self.instances.insert(
@@ -106,9 +247,10 @@ impl WeakDom {
             .unwrap_or_else(|| panic!("cannot insert into parent that does not exist"));
 
         parent.children.push(referent);
+        self.indices.insert(&self.instances[&referent]);
 
         for child in builder.children {
-            self.insert(referent, child);
+            self.insert_inner(referent, child);
         }
 
         referent
@@ -126,22 +268,77 @@ impl WeakDom {
             panic!("cannot destroy the root instance of a WeakDom");
         }
 
+        self.indices.flush(&self.instances);
+
         let instance = self
             .instances
             .get(&referent)
             .unwrap_or_else(|| panic!("cannot destroy an instance that does not exist"));
 
         let parent_ref = instance.parent;
+        let index = self.child_index(parent_ref, referent);
         let parent = self.instances.get_mut(&parent_ref).unwrap();
         parent.children.retain(|&child| child != referent);
 
+        let mut removed = Vec::new();
         let mut to_remove = VecDeque::new();
         to_remove.push_back(referent);
 
         while let Some(referent) = to_remove.pop_front() {
             let instance = self.instances.remove(&referent).unwrap();
-            to_remove.extend(instance.children);
+            to_remove.extend(instance.children.iter().copied());
+            self.indices.remove(referent);
+
+            if self.journal.is_recording() {
+                removed.push(instance);
+            }
         }
+
+        self.journal.record(Change::Removed {
+            parent: parent_ref,
+            index,
+            root: referent,
+            instances: removed,
+        });
+    }
+
+    /// Returns the position of `child_ref` in the children of `parent_ref`.
+    fn child_index(&self, parent_ref: Ref, child_ref: Ref) -> usize {
+        self.instances[&parent_ref]
+            .children
+            .iter()
+            .position(|&child| child == child_ref)
+            .unwrap()
+    }
+
+    /// Records that the instance with the given referent and its descendants
+    /// are about to be moved out of the DOM. The moved instances live on
+    /// somewhere else, so rolling back or undoing this puts copies of them
+    /// back where they were, with the same referents.
+    fn record_moved_out(&mut self, referent: Ref) {
+        if !self.journal.is_recording() {
+            return;
+        }
+
+        let parent = self.instances[&referent].parent;
+        let index = self.child_index(parent, referent);
+
+        let mut instances = Vec::new();
+        let mut to_copy = VecDeque::new();
+        to_copy.push_back(referent);
+
+        while let Some(referent) = to_copy.pop_front() {
+            let instance = &self.instances[&referent];
+            to_copy.extend(instance.children.iter().copied());
+            instances.push(instance.duplicate());
+        }
+
+        self.journal.record(Change::Removed {
+            parent,
+            index,
+            root: referent,
+            instances,
+        });
     }
 
     /// Move the instance with the given referent to a new `WeakDom`, parenting
@@ -156,11 +353,25 @@ impl WeakDom {
     ///
     /// Will also panic if `referent` refers to the root instance in this
     /// `WeakDom`.
+    ///
+    /// If a transaction is open or undo history is enabled on this `WeakDom`,
+    /// the move is recorded like a [`destroy`][WeakDom::destroy]. Rolling it
+    /// back or undoing it puts copies of the moved instances back in this
+    /// `WeakDom` with the same referents, and leaves `dest` alone.
     pub fn transfer(&mut self, referent: Ref, dest: &mut WeakDom, dest_parent_ref: Ref) {
         if referent == self.root_ref {
             panic!("cannot transfer the root instance of WeakDom");
         }
 
+        self.indices.flush(&self.instances);
+        dest.indices.flush(&dest.instances);
+
+        if !self.instances.contains_key(&referent) {
+            panic!("cannot move an instance that does not exist");
+        }
+
+        self.record_moved_out(referent);
+
         let mut instance = self
             .instances
             .remove(&referent)
@@ -182,12 +393,16 @@ impl WeakDom {
         // Bye-bye, instance!
         instance.parent = dest_parent_ref;
         dest.instances.insert(referent, instance);
+        self.indices.remove(referent);
+        dest.indices.insert(&dest.instances[&referent]);
 
         // Transfer all of the descendants of the moving instance breadth-first.
         while let Some(referent) = to_move.pop_front() {
             let instance = self.instances.remove(&referent).unwrap();
             to_move.extend(instance.children.iter().copied());
             dest.instances.insert(referent, instance);
+            self.indices.remove(referent);
+            dest.indices.insert(&dest.instances[&referent]);
         }
 
         // Finally, notify the new parent instance that their adoption is
@@ -196,6 +411,229 @@ impl WeakDom {
             panic!("cannot move an instance into an instance that does not exist")
         });
         dest_parent.children.push(referent);
+
+        dest.journal.record(Change::Added { referent });
+    }
+
+    /// Remove the instance with the given referent and all of its descendants
+    /// from the DOM, returning them as a [`DetachedSubtree`] that can be
+    /// attached again later with [`WeakDom::attach`].
+    ///
+    /// ## Panics
+    /// Panics if `referent` does not refer to an instance in the DOM.
+    ///
+    /// Will also panic if `referent` refers to the root instance in this
+    /// `WeakDom`.
+    ///
+    /// If a transaction is open or undo history is enabled, detaching is
+    /// recorded like a [`destroy`][WeakDom::destroy]. Rolling it back or
+    /// undoing it puts copies of the detached instances back with the same
+    /// referents, so the `DetachedSubtree` can't be attached to this `WeakDom`
+    /// again until they're removed.
+    pub fn detach(&mut self, referent: Ref) -> DetachedSubtree {
+        if referent == self.root_ref {
+            panic!("cannot detach the root instance of a WeakDom");
+        }
+
+        self.indices.flush(&self.instances);
+
+        if !self.instances.contains_key(&referent) {
+            panic!("cannot detach an instance that does not exist");
+        }
+
+        self.record_moved_out(referent);
+
+        let mut instance = self.instances.remove(&referent).unwrap();
+
+        let parent = self.instances.get_mut(&instance.parent).unwrap();
+        parent.children.retain(|&child| child != referent);
+
+        let mut to_move = VecDeque::new();
+        to_move.extend(instance.children.iter().copied());
+
+        instance.parent = Ref::none();
+
+        let mut instances = HashMap::new();
+        instances.insert(referent, instance);
+        self.indices.remove(referent);
+
+        while let Some(referent) = to_move.pop_front() {
+            let instance = self.instances.remove(&referent).unwrap();
+            to_move.extend(instance.children.iter().copied());
+            instances.insert(referent, instance);
+            self.indices.remove(referent);
+        }
+
+        DetachedSubtree {
+            root_ref: referent,
+            instances,
+        }
+    }
+
+    /// Insert a [`DetachedSubtree`] into the DOM with the given parent,
+    /// returning the referent of the subtree's root.
+    ///
+    /// ## Panics
+    /// Panics if `parent_ref` does not refer to an instance in the DOM, or if
+    /// any instance in the subtree has the same referent as an instance already
+    /// in the DOM.
+    pub fn attach(&mut self, subtree: DetachedSubtree, parent_ref: Ref) -> Ref {
+        if !self.instances.contains_key(&parent_ref) {
+            panic!("cannot attach into parent that does not exist");
+        }
+
+        if subtree
+            .instances
+            .keys()
+            .any(|referent| self.instances.contains_key(referent))
+        {
+            panic!("cannot attach a subtree containing instances already in the DOM");
+        }
+
+        self.indices.flush(&self.instances);
+
+        let root_ref = subtree.root_ref;
+        let mut instances = subtree.instances;
+        instances.get_mut(&root_ref).unwrap().parent = parent_ref;
+
+        let referents: Vec<Ref> = instances.keys().copied().collect();
+        self.instances.extend(instances);
+
+        for referent in referents {
+            self.indices.insert(&self.instances[&referent]);
+        }
+        self.instances
+            .get_mut(&parent_ref)
+            .unwrap()
+            .children
+            .push(root_ref);
+
+        self.journal.record(Change::Added { referent: root_ref });
+
+        root_ref
+    }
+
+    /// Change the parent of the instance with the given referent to another
+    /// instance in the same `WeakDom`.
+    ///
+    /// Unlike [`WeakDom::transfer_within`], this checks that the move is valid
+    /// first and returns an error instead of panicking or corrupting the tree.
+    /// An error is returned if either instance does not exist, if `referent`
+    /// is the root instance, or if `new_parent_ref` is `referent` or one of its
+    /// descendants. Setting an instance's parent to its current parent does
+    /// nothing.
+    pub fn set_parent(&mut self, referent: Ref, new_parent_ref: Ref) -> Result<(), Error> {
+        let instance = self
+            .instances
+            .get(&referent)
+            .ok_or(InnerError::InstanceNotFound { referent })?;
+
+        if referent == self.root_ref {
+            return Err(InnerError::ReparentRoot.into());
+        }
+
+        if instance.parent == new_parent_ref {
+            return Ok(());
+        }
+
+        if !self.instances.contains_key(&new_parent_ref) {
+            return Err(InnerError::InstanceNotFound {
+                referent: new_parent_ref,
+            }
+            .into());
+        }
+
+        let mut ancestor_ref = new_parent_ref;
+        while !ancestor_ref.is_none() {
+            if ancestor_ref == referent {
+                return Err(InnerError::CyclicParent {
+                    child: referent,
+                    parent: new_parent_ref,
+                }
+                .into());
+            }
+
+            let ancestor =
+                self.instances
+                    .get(&ancestor_ref)
+                    .ok_or(InnerError::InstanceNotFound {
+                        referent: ancestor_ref,
+                    })?;
+            ancestor_ref = ancestor.parent;
+        }
+
+        self.transfer_within(referent, new_parent_ref);
+        Ok(())
+    }
+
+    /// Move the instance with the given referent to a different position among
+    /// its siblings. After this call, it will be at position `index` in its
+    /// parent's list of children.
+    ///
+    /// Child order is preserved by rbx_binary and rbx_xml when serializing.
+    ///
+    /// ## Panics
+    /// Panics if `referent` does not refer to an instance in the DOM, refers to
+    /// the root instance, or if `index` is not less than the number of children
+    /// of the instance's parent.
+    pub fn move_to_index(&mut self, referent: Ref, index: usize) {
+        if referent == self.root_ref {
+            panic!("cannot move the root instance of a WeakDom");
+        }
+
+        let parent_ref = self
+            .instances
+            .get(&referent)
+            .unwrap_or_else(|| panic!("cannot move an instance that does not exist"))
+            .parent;
+
+        let old_index = self.child_index(parent_ref, referent);
+        let parent = self.instances.get_mut(&parent_ref).unwrap();
+
+        if index >= parent.children.len() {
+            panic!(
+                "cannot move instance to index {} of a parent with {} children",
+                index,
+                parent.children.len()
+            );
+        }
+
+        parent.children.remove(old_index);
+        parent.children.insert(index, referent);
+
+        self.journal.record(Change::Moved {
+            referent,
+            parent: parent_ref,
+            index: old_index,
+        });
+    }
+
+    /// Sort the children of the instance with the given referent using the
+    /// given comparison function. The sort is stable, so children that compare
+    /// as equal keep their relative order.
+    ///
+    /// Child order is preserved by rbx_binary and rbx_xml when serializing.
+    ///
+    /// ## Panics
+    /// Panics if `parent_ref` does not refer to an instance in the DOM.
+    pub fn sort_children_by<F>(&mut self, parent_ref: Ref, mut compare: F)
+    where
+        F: FnMut(&Instance, &Instance) -> Ordering,
+    {
+        let parent = self.instances.get(&parent_ref).unwrap_or_else(|| {
+            panic!("cannot sort the children of an instance that does not exist")
+        });
+
+        let mut children = parent.children.clone();
+        children.sort_by(|a, b| compare(&self.instances[a], &self.instances[b]));
+
+        let parent = self.instances.get_mut(&parent_ref).unwrap();
+        let old_children = std::mem::replace(&mut parent.children, children);
+
+        self.journal.record(Change::Reordered {
+            parent: parent_ref,
+            children: old_children,
+        });
     }
 
     /// Move the instance with the given referent to a new parent within the
@@ -225,6 +663,7 @@ impl WeakDom {
         instance.parent = dest_parent_ref;
 
         // Remove the instance's referent from its parent's list of children.
+        let index = self.child_index(parent_ref, referent);
         let parent = self.instances.get_mut(&parent_ref).unwrap();
         parent.children.retain(|&child| child != referent);
 
@@ -234,6 +673,25 @@ impl WeakDom {
             .get_mut(&dest_parent_ref)
             .unwrap_or_else(|| panic!("cannot move into an instance that does not exist"));
         dest_parent.children.push(referent);
+
+        self.journal.record(Change::Moved {
+            referent,
+            parent: parent_ref,
+            index,
+        });
+    }
+}
+
+impl From<DetachedSubtree> for WeakDom {
+    /// Construct a new `WeakDom` whose root is the root of the given
+    /// [`DetachedSubtree`].
+    fn from(subtree: DetachedSubtree) -> WeakDom {
+        WeakDom {
+            instances: subtree.instances,
+            root_ref: subtree.root_ref,
+            indices: Indices::default(),
+            journal: Journal::default(),
+        }
     }
 }
 
@@ -269,6 +727,244 @@ mod test {
         insta::assert_yaml_snapshot!(viewer.view_children(&dest));
     }
 
+    #[test]
+    fn instances_with_tag() {
+        let enemy = InstanceBuilder::new("Model").with_name("Enemy");
+        let enemy_ref = enemy.referent;
+
+        let mut dom = WeakDom::new(
+            InstanceBuilder::new("Folder")
+                .with_child(enemy)
+                .with_child(InstanceBuilder::new("Model").with_name("Ally")),
+        );
+
+        dom.get_by_ref_mut(enemy_ref).unwrap().add_tag("Enemy");
+
+        let tagged: Vec<Ref> = dom
+            .instances_with_tag("Enemy")
+            .map(|instance| instance.referent())
+            .collect();
+
+        assert_eq!(tagged, vec![enemy_ref]);
+    }
+
+    #[test]
+    fn detach_attach() {
+        let target = InstanceBuilder::new("Folder")
+            .with_name("Target")
+            .with_child(InstanceBuilder::new("Part").with_name("Some Child"));
+        let target_ref = target.referent;
+
+        let mut source = WeakDom::new(InstanceBuilder::new("Folder").with_child(target));
+        let mut dest = WeakDom::new(InstanceBuilder::new("DataModel"));
+
+        let subtree = source.detach(target_ref);
+        assert_eq!(subtree.root_ref(), target_ref);
+        assert_eq!(subtree.len(), 2);
+        assert!(subtree.root().parent().is_none());
+        assert!(source.get_by_ref(target_ref).is_none());
+        assert!(source.root().children().is_empty());
+
+        let dest_root = dest.root_ref();
+        assert_eq!(dest.attach(subtree, dest_root), target_ref);
+
+        let mut viewer = DomViewer::new();
+
+        // This snapshot should contain Target and Some Child
+        insta::assert_yaml_snapshot!(viewer.view_children(&dest));
+    }
+
+    #[test]
+    fn detached_subtree_into_dom() {
+        let target = InstanceBuilder::new("Folder")
+            .with_name("Target")
+            .with_child(InstanceBuilder::new("Part").with_name("Some Child"));
+        let target_ref = target.referent;
+
+        let mut source = WeakDom::new(InstanceBuilder::new("Folder").with_child(target));
+        let dom = WeakDom::from(source.detach(target_ref));
+
+        assert_eq!(dom.root_ref(), target_ref);
+        assert_eq!(dom.root().name, "Target");
+        assert_eq!(dom.root().children().len(), 1);
+    }
+
+    #[test]
+    fn name_index() {
+        let door = InstanceBuilder::new("Part").with_name("Door");
+        let door_ref = door.referent;
+
+        let mut dom = WeakDom::new(
+            InstanceBuilder::new("Folder")
+                .with_child(door)
+                .with_child(InstanceBuilder::new("Part").with_name("Wall")),
+        );
+        dom.enable_name_index();
+
+        assert_eq!(dom.find_all_named("Door"), vec![door_ref]);
+        assert!(dom.find_all_named("Window").is_empty());
+
+        // Renames through get_by_ref_mut should be visible right away.
+        dom.get_by_ref_mut(door_ref).unwrap().name = "Window".to_owned();
+        assert!(dom.find_all_named("Door").is_empty());
+        assert_eq!(dom.find_all_named("Window"), vec![door_ref]);
+
+        let root_ref = dom.root_ref();
+        let new_door = dom.insert(root_ref, InstanceBuilder::new("Part").with_name("Door"));
+        assert_eq!(dom.find_all_named("Door"), vec![new_door]);
+        assert_eq!(dom.find_all_named("Window"), vec![door_ref]);
+
+        dom.destroy(door_ref);
+        assert!(dom.find_all_named("Window").is_empty());
+
+        let subtree = dom.detach(new_door);
+        assert!(dom.find_all_named("Door").is_empty());
+        dom.attach(subtree, root_ref);
+        assert_eq!(dom.find_all_named("Door"), vec![new_door]);
+
+        let mut other = WeakDom::new(InstanceBuilder::new("Folder"));
+        other.enable_name_index();
+        let other_root = other.root_ref();
+        dom.transfer(new_door, &mut other, other_root);
+        assert!(dom.find_all_named("Door").is_empty());
+        assert_eq!(other.find_all_named("Door"), vec![new_door]);
+    }
+
+    #[test]
+    fn find_all_named_without_index() {
+        let mut dom = WeakDom::new(
+            InstanceBuilder::new("Folder")
+                .with_child(InstanceBuilder::new("Part").with_name("Door"))
+                .with_child(InstanceBuilder::new("Part").with_name("Door")),
+        );
+
+        assert_eq!(dom.find_all_named("Door").len(), 2);
+
+        dom.enable_name_index();
+        assert_eq!(dom.find_all_named("Door").len(), 2);
+
+        dom.disable_name_index();
+        assert_eq!(dom.find_all_named("Door").len(), 2);
+    }
+
+    #[test]
+    fn class_index() {
+        let script = InstanceBuilder::new("Script");
+        let script_ref = script.referent;
+
+        let mut dom = WeakDom::new(
+            InstanceBuilder::new("Folder")
+                .with_child(script)
+                .with_child(InstanceBuilder::new("Part")),
+        );
+        dom.enable_class_index();
+
+        assert_eq!(dom.instances_of_class("Script"), vec![script_ref]);
+        assert!(dom.instances_of_class("LocalScript").is_empty());
+
+        dom.get_by_ref_mut(script_ref).unwrap().class = "LocalScript".to_owned();
+        assert!(dom.instances_of_class("Script").is_empty());
+        assert_eq!(dom.instances_of_class("LocalScript"), vec![script_ref]);
+
+        let root_ref = dom.root_ref();
+        let new_script = dom.insert(root_ref, InstanceBuilder::new("Script"));
+        assert_eq!(dom.instances_of_class("Script"), vec![new_script]);
+
+        dom.destroy(script_ref);
+        assert!(dom.instances_of_class("LocalScript").is_empty());
+
+        dom.disable_class_index();
+        assert_eq!(dom.instances_of_class("Script"), vec![new_script]);
+    }
+
+    #[test]
+    fn set_parent() {
+        let child = InstanceBuilder::new("Part");
+        let child_ref = child.referent;
+
+        let parent = InstanceBuilder::new("Model").with_child(child);
+        let parent_ref = parent.referent;
+
+        let other = InstanceBuilder::new("Folder");
+        let other_ref = other.referent;
+
+        let mut dom = WeakDom::new(
+            InstanceBuilder::new("Folder")
+                .with_child(parent)
+                .with_child(other),
+        );
+        let root_ref = dom.root_ref();
+
+        assert!(dom.set_parent(parent_ref, parent_ref).is_err());
+        assert!(dom.set_parent(parent_ref, child_ref).is_err());
+        assert!(dom.set_parent(root_ref, other_ref).is_err());
+        assert!(dom.set_parent(Ref::new(), other_ref).is_err());
+        assert!(dom.set_parent(child_ref, Ref::new()).is_err());
+        assert!(dom.set_parent(child_ref, Ref::none()).is_err());
+
+        // A referent to an instance that's been destroyed is stale.
+        let stale_ref = dom.insert(root_ref, InstanceBuilder::new("Folder"));
+        dom.destroy(stale_ref);
+        assert!(dom.set_parent(child_ref, stale_ref).is_err());
+
+        // None of the failed calls should have changed anything.
+        assert_eq!(dom.root().children(), &[parent_ref, other_ref]);
+        assert_eq!(dom.get_by_ref(parent_ref).unwrap().children(), &[child_ref]);
+
+        dom.set_parent(parent_ref, other_ref).unwrap();
+        assert_eq!(dom.root().children(), &[other_ref]);
+        assert_eq!(dom.get_by_ref(parent_ref).unwrap().parent(), other_ref);
+
+        // Reparenting to the current parent should not reorder anything.
+        dom.insert(other_ref, InstanceBuilder::new("Part"));
+        dom.set_parent(parent_ref, other_ref).unwrap();
+        assert_eq!(dom.get_by_ref(other_ref).unwrap().children()[0], parent_ref);
+    }
+
+    #[test]
+    fn child_order() {
+        let mut dom = WeakDom::new(InstanceBuilder::new("Folder").with_children(vec![
+            InstanceBuilder::new("Part").with_name("C"),
+            InstanceBuilder::new("Part").with_name("A"),
+            InstanceBuilder::new("Part").with_name("B"),
+        ]));
+        let root_ref = dom.root_ref();
+
+        let names = |dom: &WeakDom| -> Vec<String> {
+            dom.root()
+                .children()
+                .iter()
+                .map(|child| dom.get_by_ref(*child).unwrap().name.clone())
+                .collect()
+        };
+
+        dom.sort_children_by(root_ref, |a, b| a.name.cmp(&b.name));
+        assert_eq!(names(&dom), ["A", "B", "C"]);
+
+        let c = dom.root().children()[2];
+        dom.move_to_index(c, 0);
+        assert_eq!(names(&dom), ["C", "A", "B"]);
+
+        dom.move_to_index(c, 2);
+        assert_eq!(names(&dom), ["A", "B", "C"]);
+
+        dom.begin_transaction();
+        dom.move_to_index(c, 1);
+        dom.sort_children_by(root_ref, |a, b| b.name.cmp(&a.name));
+        dom.rollback_transaction();
+        assert_eq!(names(&dom), ["A", "B", "C"]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn move_to_index_out_of_bounds() {
+        let child = InstanceBuilder::new("Part");
+        let child_ref = child.referent;
+
+        let mut dom = WeakDom::new(InstanceBuilder::new("Folder").with_child(child));
+        dom.move_to_index(child_ref, 1);
+    }
+
     #[test]
     fn transfer_within() {
         let subject = InstanceBuilder::new("Folder")
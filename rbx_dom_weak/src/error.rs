@@ -0,0 +1,40 @@
+use rbx_types::Ref;
+use thiserror::Error;
+
+/// Represents an error that occurred when using a fallible method.
+#[derive(Debug, Error)]
+#[error(transparent)]
+pub struct Error {
+    source: Box<InnerError>,
+}
+
+impl From<InnerError> for Error {
+    fn from(source: InnerError) -> Self {
+        Self {
+            source: Box::new(source),
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub(crate) enum InnerError {
+    #[error("{name:?} is not a valid property name")]
+    InvalidPropertyName { name: String },
+
+    #[error("no instance with referent {referent} exists in the DOM")]
+    InstanceNotFound { referent: Ref },
+
+    #[error("the root instance of a WeakDom cannot be reparented")]
+    ReparentRoot,
+
+    #[error(
+        "cannot parent {child} to {parent}, which is the same instance or one of its descendants"
+    )]
+    CyclicParent { child: Ref, parent: Ref },
+
+    #[error("no script with the path {path:?} exists in the DOM")]
+    ScriptNotFound { path: String },
+
+    #[error("more than one script has the path {path:?}")]
+    AmbiguousScriptPath { path: String },
+}
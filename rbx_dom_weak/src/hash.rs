@@ -0,0 +1,459 @@
+use std::collections::HashMap;
+
+use blake3::Hasher;
+use rbx_types::{
+    CFrame, Color3, PhysicalProperties, Ref, UDim, Variant, Vector2, Vector3, Vector3int16,
+};
+
+use crate::WeakDom;
+
+impl WeakDom {
+    /// Computes a stable hash of the instance with the given referent and all
+    /// of its descendants.
+    ///
+    /// The hash covers each instance's class, name, properties, and the order
+    /// of its children. It does not depend on the values of referents: `Ref`
+    /// properties pointing within the subtree are hashed by the position of
+    /// their target, and `Ref` properties pointing outside of the subtree are
+    /// all hashed the same. This means two subtrees with the same contents will
+    /// hash the same even if they live in different DOMs.
+    ///
+    /// Each type of value is hashed with a fixed tag and byte layout, so the
+    /// hash of a subtree stays the same across versions of rbx_dom_weak and
+    /// rbx_types.
+    ///
+    /// ## Panics
+    /// Panics if `referent` does not refer to an instance in the DOM.
+    pub fn hash_subtree(&self, referent: Ref) -> [u8; 32] {
+        let root = self
+            .get_by_ref(referent)
+            .unwrap_or_else(|| panic!("cannot hash an instance that does not exist"));
+        let order = self.preorder(root);
+        let indices: HashMap<Ref, usize> = order
+            .iter()
+            .enumerate()
+            .map(|(index, (_, instance))| (instance.referent(), index))
+            .collect();
+
+        let mut hasher = Hasher::new();
+
+        for (_, instance) in order {
+            write_str(&mut hasher, &instance.class);
+            write_str(&mut hasher, &instance.name);
+
+            let mut properties: Vec<_> = instance.properties.iter().collect();
+            properties.sort_unstable_by_key(|(name, _)| name.as_str());
+
+            write_len(&mut hasher, properties.len());
+            for (name, value) in properties {
+                write_str(&mut hasher, name);
+                write_variant(&mut hasher, value, &indices);
+            }
+
+            write_len(&mut hasher, instance.children().len());
+        }
+
+        *hasher.finalize().as_bytes()
+    }
+}
+
+fn write_len(hasher: &mut Hasher, len: usize) {
+    hasher.update(&(len as u64).to_le_bytes());
+}
+
+fn write_str(hasher: &mut Hasher, value: &str) {
+    write_len(hasher, value.len());
+    hasher.update(value.as_bytes());
+}
+
+/// Writes a value as a tag for its type followed by its contents, laid out
+/// the same way no matter how `rbx_types` derives `Debug` or serde for it.
+///
+/// Tags and layouts must never change, since that would change the hash of
+/// every subtree holding a value of that type. New types get new tags.
+fn write_variant(hasher: &mut Hasher, value: &Variant, indices: &HashMap<Ref, usize>) {
+    match value {
+        Variant::Axes(value) => {
+            hasher.update(&[0, value.bits()]);
+        }
+        Variant::BinaryString(value) => {
+            hasher.update(&[1]);
+            write_bytes(hasher, value.as_ref());
+        }
+        Variant::Bool(value) => {
+            hasher.update(&[2, *value as u8]);
+        }
+        Variant::BrickColor(value) => {
+            hasher.update(&[3]);
+            hasher.update(&(*value as u16).to_le_bytes());
+        }
+        Variant::CFrame(value) => {
+            hasher.update(&[4]);
+            write_cframe(hasher, value);
+        }
+        Variant::Color3(value) => {
+            hasher.update(&[5]);
+            write_color3(hasher, value);
+        }
+        Variant::Color3uint8(value) => {
+            hasher.update(&[6, value.r, value.g, value.b]);
+        }
+        Variant::ColorSequence(value) => {
+            hasher.update(&[7]);
+            write_len(hasher, value.keypoints.len());
+            for keypoint in &value.keypoints {
+                write_f32(hasher, keypoint.time);
+                write_color3(hasher, &keypoint.color);
+            }
+        }
+        Variant::Content(value) => {
+            hasher.update(&[8]);
+            write_str(hasher, value.as_ref());
+        }
+        Variant::Enum(value) => {
+            hasher.update(&[9]);
+            hasher.update(&value.to_u32().to_le_bytes());
+        }
+        Variant::Faces(value) => {
+            hasher.update(&[10, value.bits()]);
+        }
+        Variant::Float32(value) => {
+            hasher.update(&[11]);
+            write_f32(hasher, *value);
+        }
+        Variant::Float64(value) => {
+            hasher.update(&[12]);
+            hasher.update(&value.to_bits().to_le_bytes());
+        }
+        Variant::Int32(value) => {
+            hasher.update(&[13]);
+            hasher.update(&value.to_le_bytes());
+        }
+        Variant::Int64(value) => {
+            hasher.update(&[14]);
+            hasher.update(&value.to_le_bytes());
+        }
+        Variant::NumberRange(value) => {
+            hasher.update(&[15]);
+            write_f32(hasher, value.min);
+            write_f32(hasher, value.max);
+        }
+        Variant::NumberSequence(value) => {
+            hasher.update(&[16]);
+            write_len(hasher, value.keypoints.len());
+            for keypoint in &value.keypoints {
+                write_f32(hasher, keypoint.time);
+                write_f32(hasher, keypoint.value);
+                write_f32(hasher, keypoint.envelope);
+            }
+        }
+        Variant::PhysicalProperties(value) => {
+            hasher.update(&[17]);
+            match value {
+                PhysicalProperties::Custom(custom) => {
+                    hasher.update(&[1]);
+                    write_f32(hasher, custom.density);
+                    write_f32(hasher, custom.friction);
+                    write_f32(hasher, custom.elasticity);
+                    write_f32(hasher, custom.friction_weight);
+                    write_f32(hasher, custom.elasticity_weight);
+                }
+                PhysicalProperties::Default => {
+                    hasher.update(&[0]);
+                }
+            }
+        }
+        Variant::Ray(value) => {
+            hasher.update(&[18]);
+            write_vector3(hasher, &value.origin);
+            write_vector3(hasher, &value.direction);
+        }
+        Variant::Rect(value) => {
+            hasher.update(&[19]);
+            write_vector2(hasher, &value.min);
+            write_vector2(hasher, &value.max);
+        }
+        Variant::Ref(referent) => {
+            hasher.update(&[20]);
+
+            if referent.is_none() {
+                hasher.update(&[0]);
+            } else if let Some(index) = indices.get(referent) {
+                hasher.update(&[1]);
+                write_len(hasher, *index);
+            } else {
+                hasher.update(&[2]);
+            }
+        }
+        Variant::Region3(value) => {
+            hasher.update(&[21]);
+            write_vector3(hasher, &value.min);
+            write_vector3(hasher, &value.max);
+        }
+        Variant::Region3int16(value) => {
+            hasher.update(&[22]);
+            write_vector3int16(hasher, &value.min);
+            write_vector3int16(hasher, &value.max);
+        }
+        // SharedStrings carry a hash of their contents that we can use instead
+        // of hashing the contents again.
+        Variant::SharedString(value) => {
+            hasher.update(&[23]);
+            hasher.update(value.hash().as_bytes());
+        }
+        Variant::String(value) => {
+            hasher.update(&[24]);
+            write_str(hasher, value);
+        }
+        Variant::UDim(value) => {
+            hasher.update(&[25]);
+            write_udim(hasher, value);
+        }
+        Variant::UDim2(value) => {
+            hasher.update(&[26]);
+            write_udim(hasher, &value.x);
+            write_udim(hasher, &value.y);
+        }
+        Variant::Vector2(value) => {
+            hasher.update(&[27]);
+            write_vector2(hasher, value);
+        }
+        Variant::Vector2int16(value) => {
+            hasher.update(&[28]);
+            hasher.update(&value.x.to_le_bytes());
+            hasher.update(&value.y.to_le_bytes());
+        }
+        Variant::Vector3(value) => {
+            hasher.update(&[29]);
+            write_vector3(hasher, value);
+        }
+        Variant::Vector3int16(value) => {
+            hasher.update(&[30]);
+            write_vector3int16(hasher, value);
+        }
+        Variant::OptionalCFrame(value) => {
+            hasher.update(&[31]);
+            match value {
+                Some(value) => {
+                    hasher.update(&[1]);
+                    write_cframe(hasher, value);
+                }
+                None => {
+                    hasher.update(&[0]);
+                }
+            }
+        }
+        Variant::Tags(value) => {
+            hasher.update(&[32]);
+            let tags: Vec<_> = value.iter().collect();
+            write_len(hasher, tags.len());
+            for tag in tags {
+                write_str(hasher, tag);
+            }
+        }
+        Variant::Attributes(value) => {
+            hasher.update(&[33]);
+
+            // Attributes are kept sorted by name.
+            let attributes: Vec<_> = value.iter().collect();
+            write_len(hasher, attributes.len());
+            for (name, value) in attributes {
+                write_str(hasher, name);
+                write_variant(hasher, value, indices);
+            }
+        }
+        Variant::Font(value) => {
+            hasher.update(&[34]);
+            write_str(hasher, &value.family);
+            hasher.update(&value.weight.as_u16().to_le_bytes());
+            hasher.update(&[value.style.as_u8()]);
+            match &value.cached_face_id {
+                Some(id) => {
+                    hasher.update(&[1]);
+                    write_str(hasher, id);
+                }
+                None => {
+                    hasher.update(&[0]);
+                }
+            }
+        }
+        Variant::UniqueId(value) => {
+            hasher.update(&[35]);
+            hasher.update(&value.index().to_le_bytes());
+            hasher.update(&value.time().to_le_bytes());
+            hasher.update(&value.random().to_le_bytes());
+        }
+        // MaterialColors and SmoothGrid values are hashed in the same format
+        // Roblox stores them in, which is fixed.
+        Variant::MaterialColors(value) => {
+            hasher.update(&[36]);
+            write_bytes(hasher, &value.encode());
+        }
+        Variant::SecurityCapabilities(value) => {
+            hasher.update(&[37]);
+            hasher.update(&value.bits().to_le_bytes());
+        }
+        Variant::SmoothGrid(value) => {
+            hasher.update(&[38]);
+            write_bytes(hasher, &value.encode());
+        }
+        // Types added to rbx_types after this was written all hash the same
+        // until they're given a tag and layout here.
+        _ => {
+            hasher.update(&[255]);
+        }
+    }
+}
+
+fn write_bytes(hasher: &mut Hasher, value: &[u8]) {
+    write_len(hasher, value.len());
+    hasher.update(value);
+}
+
+fn write_f32(hasher: &mut Hasher, value: f32) {
+    hasher.update(&value.to_bits().to_le_bytes());
+}
+
+fn write_vector2(hasher: &mut Hasher, value: &Vector2) {
+    write_f32(hasher, value.x);
+    write_f32(hasher, value.y);
+}
+
+fn write_vector3(hasher: &mut Hasher, value: &Vector3) {
+    write_f32(hasher, value.x);
+    write_f32(hasher, value.y);
+    write_f32(hasher, value.z);
+}
+
+fn write_vector3int16(hasher: &mut Hasher, value: &Vector3int16) {
+    hasher.update(&value.x.to_le_bytes());
+    hasher.update(&value.y.to_le_bytes());
+    hasher.update(&value.z.to_le_bytes());
+}
+
+fn write_cframe(hasher: &mut Hasher, value: &CFrame) {
+    write_vector3(hasher, &value.position);
+    write_vector3(hasher, &value.orientation.x);
+    write_vector3(hasher, &value.orientation.y);
+    write_vector3(hasher, &value.orientation.z);
+}
+
+fn write_color3(hasher: &mut Hasher, value: &Color3) {
+    write_f32(hasher, value.r);
+    write_f32(hasher, value.g);
+    write_f32(hasher, value.b);
+}
+
+fn write_udim(hasher: &mut Hasher, value: &UDim) {
+    write_f32(hasher, value.scale);
+    hasher.update(&value.offset.to_le_bytes());
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rbx_types::{Attributes, Matrix3, Tags};
+
+    use crate::InstanceBuilder;
+
+    fn build() -> WeakDom {
+        let part = InstanceBuilder::new("Part").with_property("Anchored", true);
+        let part_ref = part.referent();
+
+        WeakDom::new(
+            InstanceBuilder::new("Model")
+                .with_property("PrimaryPart", part_ref)
+                .with_child(part)
+                .with_child(InstanceBuilder::new("Folder")),
+        )
+    }
+
+    #[test]
+    fn independent_of_referents() {
+        let a = build();
+        let b = build();
+
+        assert_ne!(a.root_ref(), b.root_ref());
+        assert_eq!(a.hash_subtree(a.root_ref()), b.hash_subtree(b.root_ref()));
+    }
+
+    #[test]
+    fn sensitive_to_changes() {
+        let a = build();
+        let original = a.hash_subtree(a.root_ref());
+
+        let mut b = build();
+        b.root_mut().name = "Renamed".to_owned();
+        assert_ne!(original, b.hash_subtree(b.root_ref()));
+
+        let mut c = build();
+        let child = c.root().children()[0];
+        c.get_by_ref_mut(child)
+            .unwrap()
+            .properties
+            .insert("Anchored".to_owned(), false.into());
+        assert_ne!(original, c.hash_subtree(c.root_ref()));
+
+        let mut d = build();
+        let folder = d.root().children()[1];
+        d.transfer_within(d.root().children()[0], folder);
+        assert_ne!(original, d.hash_subtree(d.root_ref()));
+    }
+
+    /// The hash must not change between versions, so this checks it against
+    /// one computed earlier for a tree holding values of many types.
+    #[test]
+    fn stable() {
+        let dom = WeakDom::new(
+            InstanceBuilder::new("Part")
+                .with_property("Anchored", true)
+                .with_property("Size", Vector3::new(4.0, 1.0, 2.0))
+                .with_property("Transparency", 0.5f32)
+                .with_property("Color", Color3::new(1.0, 0.0, 0.5))
+                .with_property(
+                    "CFrame",
+                    CFrame::new(Vector3::new(1.0, 2.0, 3.0), Matrix3::identity()),
+                )
+                .with_property("Tags", Tags::from(vec!["A".to_owned(), "B".to_owned()]))
+                .with_property("Attributes", Attributes::new().with("Health", 100.0f64))
+                .with_property("Count", 7i32)
+                .with_property("Hidden", Variant::Ref(Ref::none())),
+        );
+
+        assert_eq!(
+            blake3::Hash::from(dom.hash_subtree(dom.root_ref()))
+                .to_hex()
+                .as_str(),
+            "725b502306b543f5f5be0d3ff71649a1c477a9df182639928a21f8caf7018542"
+        );
+    }
+
+    #[test]
+    fn types_are_tagged() {
+        let int = WeakDom::new(InstanceBuilder::new("Folder").with_property("Value", 1i32));
+        let float =
+            WeakDom::new(InstanceBuilder::new("Folder").with_property("Value", f32::from_bits(1)));
+
+        assert_ne!(
+            int.hash_subtree(int.root_ref()),
+            float.hash_subtree(float.root_ref())
+        );
+    }
+
+    #[test]
+    fn child_order() {
+        let a = WeakDom::new(
+            InstanceBuilder::new("Folder")
+                .with_child(InstanceBuilder::new("Part"))
+                .with_child(InstanceBuilder::new("Model")),
+        );
+        let b = WeakDom::new(
+            InstanceBuilder::new("Folder")
+                .with_child(InstanceBuilder::new("Model"))
+                .with_child(InstanceBuilder::new("Part")),
+        );
+
+        assert_ne!(a.hash_subtree(a.root_ref()), b.hash_subtree(b.root_ref()));
+    }
+}
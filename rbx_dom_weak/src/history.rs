@@ -0,0 +1,288 @@
+use std::collections::HashSet;
+
+use rbx_types::Ref;
+
+use crate::{transaction::Change, WeakDom};
+
+/// A group of changes that are undone or redone together. Changes are reverted
+/// starting from the end.
+type Step = Vec<Change>;
+
+/// The undo and redo stacks of a [`WeakDom`].
+#[derive(Debug, Default)]
+pub(crate) struct History {
+    /// Changes made since the last waypoint.
+    pending: Step,
+
+    /// The instances whose contents have been recorded in `pending`.
+    modified: HashSet<Ref>,
+
+    undo: Vec<Step>,
+    redo: Vec<Step>,
+}
+
+impl History {
+    pub fn record(&mut self, change: Change) {
+        self.pending.push(change);
+        self.redo.clear();
+    }
+
+    pub fn modified_mut(&mut self) -> &mut HashSet<Ref> {
+        &mut self.modified
+    }
+
+    fn set_waypoint(&mut self) {
+        if !self.pending.is_empty() {
+            self.undo.push(std::mem::take(&mut self.pending));
+        }
+
+        self.modified.clear();
+    }
+}
+
+impl WeakDom {
+    /// Enables undo history. From now on, changes made to the DOM are recorded
+    /// so that they can be undone with [`WeakDom::undo`] and redone with
+    /// [`WeakDom::redo`].
+    ///
+    /// Changes are grouped into steps separated by calls to
+    /// [`WeakDom::set_waypoint`], similar to Roblox's `ChangeHistoryService`.
+    /// Changes made inside of a transaction are only recorded once the
+    /// transaction is committed.
+    ///
+    /// ## Example
+    /// ```
+    /// use rbx_dom_weak::{InstanceBuilder, WeakDom};
+    ///
+    /// let mut dom = WeakDom::new(InstanceBuilder::new("Folder"));
+    /// dom.enable_history();
+    ///
+    /// dom.root_mut().name = "Renamed".to_owned();
+    /// dom.set_waypoint();
+    ///
+    /// assert!(dom.undo());
+    /// assert_eq!(dom.root().name, "Folder");
+    ///
+    /// assert!(dom.redo());
+    /// assert_eq!(dom.root().name, "Renamed");
+    /// ```
+    pub fn enable_history(&mut self) {
+        if self.journal.history.is_none() {
+            self.journal.history = Some(History::default());
+        }
+    }
+
+    /// Disables undo history, discarding everything that has been recorded.
+    pub fn disable_history(&mut self) {
+        self.journal.history = None;
+    }
+
+    /// Finishes the current undo step, so that the changes made since the last
+    /// waypoint are undone together by the next call to [`WeakDom::undo`].
+    ///
+    /// Does nothing if undo history is not enabled.
+    pub fn set_waypoint(&mut self) {
+        if let Some(history) = &mut self.journal.history {
+            history.set_waypoint();
+        }
+    }
+
+    /// Undoes the changes made in the most recent undo step, returning whether
+    /// there was anything to undo. Changes made since the last waypoint are
+    /// treated as their own step.
+    ///
+    /// ## Panics
+    /// Panics if a transaction is open.
+    pub fn undo(&mut self) -> bool {
+        self.step_history(true)
+    }
+
+    /// Redoes the most recently undone step, returning whether there was
+    /// anything to redo. Making any other change to the DOM after undoing
+    /// clears the steps that can be redone.
+    ///
+    /// ## Panics
+    /// Panics if a transaction is open.
+    pub fn redo(&mut self) -> bool {
+        self.step_history(false)
+    }
+
+    fn step_history(&mut self, undo: bool) -> bool {
+        if self.in_transaction() {
+            panic!("cannot undo or redo while a transaction is open");
+        }
+
+        let mut step = match &mut self.journal.history {
+            Some(history) => {
+                history.set_waypoint();
+
+                let stack = if undo {
+                    &mut history.undo
+                } else {
+                    &mut history.redo
+                };
+
+                match stack.pop() {
+                    Some(step) => step,
+                    None => return false,
+                }
+            }
+            None => return false,
+        };
+
+        self.indices.flush(&self.instances);
+
+        let mut inverse = Vec::with_capacity(step.len());
+        while let Some(change) = step.pop() {
+            inverse.push(self.revert(change));
+        }
+
+        let history = self.journal.history.as_mut().unwrap();
+        if undo {
+            history.redo.push(inverse);
+        } else {
+            history.undo.push(inverse);
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{InstanceBuilder, WeakDom};
+
+    fn build() -> WeakDom {
+        let mut dom = WeakDom::new(
+            InstanceBuilder::new("Folder")
+                .with_child(InstanceBuilder::new("Model").with_name("A"))
+                .with_child(InstanceBuilder::new("Model").with_name("B")),
+        );
+        dom.enable_history();
+        dom
+    }
+
+    #[test]
+    fn undo_redo() {
+        let mut dom = build();
+        let root_ref = dom.root_ref();
+        let a = dom.root().children()[0];
+        let b = dom.root().children()[1];
+
+        let part = dom.insert(a, InstanceBuilder::new("Part"));
+        dom.set_waypoint();
+
+        dom.transfer_within(part, b);
+        dom.get_by_ref_mut(part).unwrap().name = "Moved".to_owned();
+        dom.set_waypoint();
+
+        dom.destroy(a);
+
+        // Undo the destroy, which hasn't had a waypoint set yet.
+        assert!(dom.undo());
+        assert_eq!(dom.root().children(), &[a, b]);
+
+        // Undo the move and rename together.
+        assert!(dom.undo());
+        assert_eq!(dom.get_by_ref(a).unwrap().children(), &[part]);
+        assert_eq!(dom.get_by_ref(part).unwrap().name, "Part");
+
+        // Undo the insert.
+        assert!(dom.undo());
+        assert!(dom.get_by_ref(part).is_none());
+        assert!(!dom.undo());
+
+        assert!(dom.redo());
+        assert_eq!(dom.get_by_ref(a).unwrap().children(), &[part]);
+
+        assert!(dom.redo());
+        assert_eq!(dom.get_by_ref(b).unwrap().children(), &[part]);
+        assert_eq!(dom.get_by_ref(part).unwrap().name, "Moved");
+
+        assert!(dom.redo());
+        assert_eq!(dom.root().children(), &[b]);
+        assert!(!dom.redo());
+
+        assert!(dom.undo());
+        assert_eq!(dom.root().children(), &[a, b]);
+        assert_eq!(root_ref, dom.root_ref());
+    }
+
+    #[test]
+    fn new_changes_clear_redo() {
+        let mut dom = build();
+        let root_ref = dom.root_ref();
+
+        dom.insert(root_ref, InstanceBuilder::new("Part"));
+        dom.set_waypoint();
+        assert!(dom.undo());
+
+        dom.root_mut().name = "Changed".to_owned();
+        assert!(!dom.redo());
+        assert_eq!(dom.root().children().len(), 2);
+    }
+
+    #[test]
+    fn transactions() {
+        let mut dom = build();
+        let root_ref = dom.root_ref();
+
+        dom.begin_transaction();
+        dom.insert(root_ref, InstanceBuilder::new("Discarded"));
+        dom.rollback_transaction();
+
+        dom.begin_transaction();
+        dom.insert(root_ref, InstanceBuilder::new("Kept"));
+        dom.commit_transaction();
+        dom.set_waypoint();
+
+        assert_eq!(dom.root().children().len(), 3);
+        assert!(dom.undo());
+        assert_eq!(dom.root().children().len(), 2);
+        assert!(!dom.undo());
+    }
+
+    #[test]
+    fn detach_keeps_history() {
+        let mut dom = build();
+        let a = dom.root().children()[0];
+        let b = dom.root().children()[1];
+
+        let part = dom.insert(a, InstanceBuilder::new("Part"));
+        dom.set_waypoint();
+
+        let detached = dom.detach(a);
+        assert_eq!(dom.root().children(), &[b]);
+
+        // Undoing the detach puts a copy of the detached instances back, and
+        // the steps before it can still be undone.
+        assert!(dom.undo());
+        assert_eq!(dom.root().children(), &[a, b]);
+        assert_eq!(dom.get_by_ref(a).unwrap().children(), &[part]);
+
+        assert!(dom.undo());
+        assert!(dom.get_by_ref(part).is_none());
+
+        assert!(dom.redo());
+        assert!(dom.redo());
+        assert_eq!(dom.root().children(), &[b]);
+
+        // With the copies gone again, the detached instances can be attached
+        // without clashing with them.
+        let root_ref = dom.root_ref();
+        dom.attach(detached, root_ref);
+        assert_eq!(dom.root().children(), &[b, a]);
+    }
+
+    #[test]
+    fn disabled() {
+        let mut dom = build();
+        dom.disable_history();
+
+        dom.root_mut().name = "Changed".to_owned();
+        dom.set_waypoint();
+
+        assert!(!dom.undo());
+        assert_eq!(dom.root().name, "Changed");
+    }
+}
@@ -0,0 +1,148 @@
+use std::collections::{HashMap, HashSet};
+
+use rbx_types::Ref;
+
+use crate::Instance;
+
+/// A secondary index over the instances in a [`WeakDom`][crate::WeakDom],
+/// keyed by a string on each instance like its name or class.
+///
+/// Because the keys are public fields on `Instance`, they can change without
+/// the index being told. To deal with this, the DOM marks any instance it
+/// hands out a mutable reference to as dirty. Lookups check dirty instances
+/// directly, and dirty instances are re-indexed the next time the DOM is
+/// mutated.
+#[derive(Debug, Clone)]
+pub(crate) struct StringIndex {
+    key: fn(&Instance) -> &str,
+    entries: HashMap<String, HashSet<Ref>>,
+    keys: HashMap<Ref, String>,
+    dirty: Vec<Ref>,
+}
+
+impl StringIndex {
+    pub fn new(key: fn(&Instance) -> &str, instances: &HashMap<Ref, Instance>) -> Self {
+        let mut index = Self {
+            key,
+            entries: HashMap::new(),
+            keys: HashMap::new(),
+            dirty: Vec::new(),
+        };
+
+        for instance in instances.values() {
+            index.insert(instance);
+        }
+
+        index
+    }
+
+    pub fn insert(&mut self, instance: &Instance) {
+        let key = (self.key)(instance);
+
+        match self.keys.get(&instance.referent) {
+            Some(existing) if existing == key => return,
+            Some(_) => self.remove(instance.referent),
+            None => {}
+        }
+
+        self.entries
+            .entry(key.to_owned())
+            .or_default()
+            .insert(instance.referent);
+        self.keys.insert(instance.referent, key.to_owned());
+    }
+
+    pub fn remove(&mut self, referent: Ref) {
+        if let Some(key) = self.keys.remove(&referent) {
+            if let Some(entry) = self.entries.get_mut(&key) {
+                entry.remove(&referent);
+
+                if entry.is_empty() {
+                    self.entries.remove(&key);
+                }
+            }
+        }
+    }
+
+    pub fn mark_dirty(&mut self, referent: Ref) {
+        self.dirty.push(referent);
+    }
+
+    /// Re-indexes every instance marked as dirty since the last flush.
+    pub fn flush(&mut self, instances: &HashMap<Ref, Instance>) {
+        for referent in std::mem::take(&mut self.dirty) {
+            match instances.get(&referent) {
+                Some(instance) => self.insert(instance),
+                None => self.remove(referent),
+            }
+        }
+    }
+
+    /// Returns the referents of all instances with the given key, in no
+    /// particular order.
+    pub fn get(&self, key: &str, instances: &HashMap<Ref, Instance>) -> Vec<Ref> {
+        let mut found: Vec<Ref> = self
+            .entries
+            .get(key)
+            .into_iter()
+            .flatten()
+            .copied()
+            .filter(|referent| !self.dirty.contains(referent))
+            .collect();
+
+        for referent in &self.dirty {
+            if let Some(instance) = instances.get(referent) {
+                if (self.key)(instance) == key && !found.contains(referent) {
+                    found.push(*referent);
+                }
+            }
+        }
+
+        found
+    }
+}
+
+/// The set of optional indices kept by a [`WeakDom`][crate::WeakDom].
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Indices {
+    pub name: Option<StringIndex>,
+    pub class: Option<StringIndex>,
+}
+
+impl Indices {
+    fn iter_mut(&mut self) -> impl Iterator<Item = &mut StringIndex> {
+        self.name.iter_mut().chain(self.class.iter_mut())
+    }
+
+    pub fn insert(&mut self, instance: &Instance) {
+        for index in self.iter_mut() {
+            index.insert(instance);
+        }
+    }
+
+    pub fn remove(&mut self, referent: Ref) {
+        for index in self.iter_mut() {
+            index.remove(referent);
+        }
+    }
+
+    pub fn mark_dirty(&mut self, referent: Ref) {
+        for index in self.iter_mut() {
+            index.mark_dirty(referent);
+        }
+    }
+
+    pub fn flush(&mut self, instances: &HashMap<Ref, Instance>) {
+        for index in self.iter_mut() {
+            index.flush(instances);
+        }
+    }
+}
+
+pub(crate) fn name_key(instance: &Instance) -> &str {
+    &instance.name
+}
+
+pub(crate) fn class_key(instance: &Instance) -> &str {
+    &instance.class
+}
@@ -1,6 +1,6 @@
 use std::collections::HashMap;
 
-use rbx_types::{Ref, Variant};
+use rbx_types::{Attributes, Ref, Tags, Variant};
 
 /**
 Represents an instance that can be turned into a new
@@ -213,4 +213,160 @@ impl Instance {
     pub fn parent(&self) -> Ref {
         self.parent
     }
+
+    /// Creates an exact copy of this instance, including its referent.
+    pub(crate) fn duplicate(&self) -> Instance {
+        Instance {
+            referent: self.referent,
+            children: self.children.clone(),
+            parent: self.parent,
+            name: self.name.clone(),
+            class: self.class.clone(),
+            properties: self.properties.clone(),
+        }
+    }
+
+    /// Returns the value of the attribute with the given name, if it exists.
+    ///
+    /// Attributes are read from the instance's `Attributes` property. If that
+    /// property is missing or isn't an [`Attributes`] value, this returns
+    /// `None`.
+    pub fn get_attribute(&self, name: &str) -> Option<&Variant> {
+        match self.properties.get("Attributes") {
+            Some(Variant::Attributes(attributes)) => attributes.get(name),
+            _ => None,
+        }
+    }
+
+    /// Sets the attribute with the given name, returning the value that was
+    /// there before if one existed.
+    ///
+    /// If the instance's `Attributes` property is missing or isn't an
+    /// [`Attributes`] value, it will be replaced with a new one.
+    pub fn set_attribute<K: Into<String>, V: Into<Variant>>(
+        &mut self,
+        name: K,
+        value: V,
+    ) -> Option<Variant> {
+        match self.properties.get_mut("Attributes") {
+            Some(Variant::Attributes(attributes)) => attributes.insert(name.into(), value.into()),
+            _ => {
+                let attributes = Attributes::new().with(name, value);
+                self.properties
+                    .insert("Attributes".to_owned(), attributes.into());
+
+                None
+            }
+        }
+    }
+
+    /// Removes the attribute with the given name, returning its value if it
+    /// existed.
+    pub fn remove_attribute(&mut self, name: &str) -> Option<Variant> {
+        match self.properties.get_mut("Attributes") {
+            Some(Variant::Attributes(attributes)) => attributes.remove(name),
+            _ => None,
+        }
+    }
+
+    /// Returns whether this instance has the given tag in its `Tags` property.
+    pub fn has_tag(&self, tag: &str) -> bool {
+        match self.properties.get("Tags") {
+            Some(Variant::Tags(tags)) => tags.contains(tag),
+            _ => false,
+        }
+    }
+
+    /// Adds the given tag to this instance's `Tags` property, returning `false`
+    /// if the instance already had the tag.
+    ///
+    /// If the instance's `Tags` property is missing or isn't a [`Tags`] value,
+    /// it will be replaced with a new one.
+    pub fn add_tag(&mut self, tag: &str) -> bool {
+        match self.properties.get_mut("Tags") {
+            Some(Variant::Tags(tags)) => {
+                if tags.contains(tag) {
+                    return false;
+                }
+
+                tags.push(tag);
+            }
+            _ => {
+                let mut tags = Tags::new();
+                tags.push(tag);
+                self.properties.insert("Tags".to_owned(), tags.into());
+            }
+        }
+
+        true
+    }
+
+    /// Removes the given tag from this instance's `Tags` property, returning
+    /// whether the instance had the tag.
+    pub fn remove_tag(&mut self, tag: &str) -> bool {
+        match self.properties.get_mut("Tags") {
+            Some(Variant::Tags(tags)) => tags.remove(tag),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::WeakDom;
+
+    #[test]
+    fn attributes() {
+        let mut dom = WeakDom::new(InstanceBuilder::new("Folder"));
+        let instance = dom.root_mut();
+
+        assert_eq!(instance.get_attribute("Health"), None);
+        assert_eq!(instance.set_attribute("Health", 100.0f64), None);
+        assert_eq!(
+            instance.get_attribute("Health"),
+            Some(&Variant::Float64(100.0))
+        );
+        assert_eq!(
+            instance.set_attribute("Health", 50.0f64),
+            Some(Variant::Float64(100.0))
+        );
+        assert_eq!(
+            instance.remove_attribute("Health"),
+            Some(Variant::Float64(50.0))
+        );
+        assert_eq!(instance.get_attribute("Health"), None);
+    }
+
+    #[test]
+    fn tags() {
+        let mut dom = WeakDom::new(InstanceBuilder::new("Folder"));
+        let instance = dom.root_mut();
+
+        assert!(!instance.has_tag("Enemy"));
+        assert!(instance.add_tag("Enemy"));
+        assert!(!instance.add_tag("Enemy"));
+        assert!(instance.has_tag("Enemy"));
+        assert!(instance.remove_tag("Enemy"));
+        assert!(!instance.remove_tag("Enemy"));
+        assert!(!instance.has_tag("Enemy"));
+    }
+
+    #[test]
+    fn set_attribute_replaces_invalid_property() {
+        let mut dom = WeakDom::new(
+            InstanceBuilder::new("Folder").with_property("Attributes", Variant::Bool(true)),
+        );
+        let instance = dom.root_mut();
+
+        assert_eq!(instance.get_attribute("Health"), None);
+        assert_eq!(instance.remove_attribute("Health"), None);
+
+        instance.set_attribute("Health", 100.0f64);
+        assert_eq!(
+            instance.properties.get("Attributes"),
+            Some(&Attributes::new().with("Health", 100.0f64).into())
+        );
+    }
 }
@@ -41,14 +41,46 @@
 
 #![deny(missing_docs)]
 
+mod clone;
+mod compact;
+mod diff;
 mod dom;
+mod error;
+mod hash;
+mod history;
+mod index;
 mod instance;
+mod macros;
+mod path;
+mod remap;
+mod roundtrip;
+mod scripts;
+mod stats;
+mod structural;
+mod subtree;
+mod sync;
+mod template;
+mod transaction;
+mod update;
 mod viewer;
 
 pub use rbx_types as types;
 
 pub use crate::{
+    diff::{
+        diff_properties, diff_properties_with_tolerance, ChangedProperty, PropertyDiff,
+        DEFAULT_FLOAT_TOLERANCE,
+    },
     dom::WeakDom,
+    error::Error,
     instance::{Instance, InstanceBuilder},
+    remap::UnmappedRef,
+    roundtrip::{RoundTrip, RoundTripFailure, RoundTripReport},
+    scripts::ScriptSource,
+    stats::{BinaryStringStat, DomStats},
+    structural::StructuralEq,
+    subtree::DetachedSubtree,
+    sync::{SyncWeakDom, SyncWeakDomWriteGuard},
+    template::{Template, TemplateOverrides},
     viewer::{DomViewer, ViewedInstance},
 };
@@ -0,0 +1,114 @@
+/**
+Constructs an [`InstanceBuilder`][crate::InstanceBuilder] from a declarative
+description of an instance tree.
+
+Each instance is written as its ClassName followed by an optional block. Inside
+the block, `Key: value` pairs set properties and nested instances become
+children. Entries are separated by commas. `Name` is treated specially and sets
+the instance's name instead of adding a property. Property names that aren't
+valid identifiers can be written as string literals.
+
+## Examples
+```
+use rbx_dom_weak::{rbx_tree, WeakDom};
+
+let assets = rbx_tree! {
+    "Folder" {
+        Name: "Assets",
+        "Part" {
+            Anchored: true,
+        },
+        "Model",
+    }
+};
+
+let dom = WeakDom::new(assets);
+assert_eq!(dom.root().name, "Assets");
+assert_eq!(dom.root().children().len(), 2);
+```
+*/
+#[macro_export]
+macro_rules! rbx_tree {
+    ($class:literal { $($body:tt)* }) => {{
+        #[allow(unused_mut)]
+        let mut builder = $crate::InstanceBuilder::new($class);
+        $crate::__rbx_tree_body!(builder; $($body)*);
+        builder
+    }};
+
+    ($class:literal) => {
+        $crate::InstanceBuilder::new($class)
+    };
+}
+
+/// Implementation detail of [`rbx_tree!`]. Applies each entry of an instance's
+/// block to the builder named by `$builder`.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __rbx_tree_body {
+    ($builder:ident;) => {};
+
+    ($builder:ident; Name: $value:expr $(, $($rest:tt)*)?) => {
+        $builder.set_name($value);
+        $crate::__rbx_tree_body!($builder; $($($rest)*)?);
+    };
+
+    ($builder:ident; $key:ident: $value:expr $(, $($rest:tt)*)?) => {
+        $builder.add_property(stringify!($key), $value);
+        $crate::__rbx_tree_body!($builder; $($($rest)*)?);
+    };
+
+    ($builder:ident; $key:literal: $value:expr $(, $($rest:tt)*)?) => {
+        $builder.add_property($key, $value);
+        $crate::__rbx_tree_body!($builder; $($($rest)*)?);
+    };
+
+    ($builder:ident; $class:literal { $($body:tt)* } $(, $($rest:tt)*)?) => {
+        $builder.add_child($crate::rbx_tree!($class { $($body)* }));
+        $crate::__rbx_tree_body!($builder; $($($rest)*)?);
+    };
+
+    ($builder:ident; $class:literal $(, $($rest:tt)*)?) => {
+        $builder.add_child($crate::InstanceBuilder::new($class));
+        $crate::__rbx_tree_body!($builder; $($($rest)*)?);
+    };
+}
+
+#[cfg(test)]
+mod test {
+    use rbx_types::{Variant, Vector3};
+
+    use crate::{DomViewer, WeakDom};
+
+    #[test]
+    fn nested() {
+        let size = Vector3::new(4.0, 1.0, 2.0);
+
+        let dom = WeakDom::new(rbx_tree! {
+            "Folder" {
+                Name: "Assets",
+                "Part" {
+                    Name: format!("Part{}", 1),
+                    Anchored: true,
+                    Size: size,
+                    "Attachment",
+                },
+                "Model" {},
+                "Folder" {
+                    "Tags": Variant::Bool(false)
+                }
+            }
+        });
+
+        insta::assert_yaml_snapshot!(DomViewer::new().view(&dom));
+    }
+
+    #[test]
+    fn bare_class() {
+        let dom = WeakDom::new(rbx_tree!("Workspace"));
+
+        assert_eq!(dom.root().class, "Workspace");
+        assert_eq!(dom.root().name, "Workspace");
+        assert!(dom.root().properties.is_empty());
+    }
+}
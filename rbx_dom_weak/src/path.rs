@@ -0,0 +1,288 @@
+use std::collections::HashSet;
+
+use rbx_types::Ref;
+
+use crate::WeakDom;
+
+impl WeakDom {
+    /// Returns the full name of the instance with the given referent, or
+    /// `None` if it is not in the DOM.
+    ///
+    /// This matches Roblox's `Instance:GetFullName`: the names of the instance
+    /// and its ancestors are joined with `.`, starting from the top. A
+    /// `DataModel` ancestor is left out, so the full name of a part in
+    /// Workspace is `Workspace.Part`, not `Game.Workspace.Part`.
+    ///
+    /// ## Example
+    /// ```
+    /// use rbx_dom_weak::{InstanceBuilder, WeakDom};
+    ///
+    /// let door = InstanceBuilder::new("Part").with_name("Door");
+    /// let door_ref = door.referent();
+    ///
+    /// let dom = WeakDom::new(
+    ///     InstanceBuilder::new("DataModel")
+    ///         .with_child(InstanceBuilder::new("Workspace").with_child(door)),
+    /// );
+    ///
+    /// assert_eq!(dom.full_name(door_ref).unwrap(), "Workspace.Door");
+    /// ```
+    pub fn full_name(&self, referent: Ref) -> Option<String> {
+        let instance = self.get_by_ref(referent)?;
+        let mut names = vec![instance.name.as_str()];
+        let mut ancestor_ref = instance.parent();
+
+        while let Some(ancestor) = self.get_by_ref(ancestor_ref) {
+            if ancestor.class == "DataModel" {
+                break;
+            }
+
+            names.push(&ancestor.name);
+            ancestor_ref = ancestor.parent();
+        }
+
+        names.reverse();
+        Some(names.join("."))
+    }
+}
+
+impl WeakDom {
+    /// Returns the referents of all descendants of the instance with the given
+    /// referent whose path from it matches `pattern`, in tree order.
+    ///
+    /// Patterns are made of instance names separated by `/`. Within a name,
+    /// `*` matches any number of characters and `?` matches exactly one. A
+    /// segment that is exactly `**` matches any number of levels of the tree,
+    /// including none.
+    ///
+    /// ## Example
+    /// ```
+    /// use rbx_dom_weak::{InstanceBuilder, WeakDom};
+    ///
+    /// let spawn = InstanceBuilder::new("SpawnLocation").with_name("SpawnA");
+    /// let spawn_ref = spawn.referent();
+    ///
+    /// let dom = WeakDom::new(InstanceBuilder::new("DataModel").with_child(
+    ///     InstanceBuilder::new("Workspace")
+    ///         .with_child(InstanceBuilder::new("Model").with_name("Map").with_child(spawn)),
+    /// ));
+    ///
+    /// let found = dom.find_all_by_path(dom.root_ref(), "Workspace/**/Spawn*");
+    /// assert_eq!(found, vec![spawn_ref]);
+    /// ```
+    ///
+    /// ## Panics
+    /// Panics if `referent` does not refer to an instance in the DOM.
+    pub fn find_all_by_path(&self, referent: Ref, pattern: &str) -> Vec<Ref> {
+        let root = self
+            .get_by_ref(referent)
+            .unwrap_or_else(|| panic!("cannot search from an instance that does not exist"));
+
+        let segments: Vec<&str> = pattern
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .collect();
+
+        let mut matcher = PathMatcher {
+            dom: self,
+            segments: &segments,
+            visited: HashSet::new(),
+            found: HashSet::new(),
+        };
+        matcher.visit(referent, 0);
+
+        // Collect matches in tree order. The root itself is only matched by
+        // an empty pattern or one made of `**`, which we skip since the root
+        // is not a descendant of itself.
+        let found = matcher.found;
+        self.preorder(root)
+            .into_iter()
+            .skip(1)
+            .map(|(_, instance)| instance.referent())
+            .filter(|referent| found.contains(referent))
+            .collect()
+    }
+}
+
+struct PathMatcher<'a> {
+    dom: &'a WeakDom,
+    segments: &'a [&'a str],
+
+    /// The `(instance, segment index)` pairs that have already been visited.
+    /// Patterns with more than one `**` can reach the same pair many times.
+    visited: HashSet<(Ref, usize)>,
+    found: HashSet<Ref>,
+}
+
+impl PathMatcher<'_> {
+    fn visit(&mut self, referent: Ref, index: usize) {
+        if !self.visited.insert((referent, index)) {
+            return;
+        }
+
+        let segment = match self.segments.get(index) {
+            Some(segment) => *segment,
+            None => {
+                self.found.insert(referent);
+                return;
+            }
+        };
+
+        let children = self.dom.get_by_ref(referent).unwrap().children();
+
+        if segment == "**" {
+            self.visit(referent, index + 1);
+
+            for &child in children {
+                self.visit(child, index);
+            }
+        } else {
+            for &child in children {
+                let name = &self.dom.get_by_ref(child).unwrap().name;
+
+                if glob_match(segment, name) {
+                    self.visit(child, index + 1);
+                }
+            }
+        }
+    }
+}
+
+/// Matches `name` against a pattern where `*` matches any number of
+/// characters and `?` matches exactly one.
+fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    let (mut p, mut n) = (0, 0);
+
+    // The position of the last `*` seen in the pattern, and the position in
+    // the name it was matched against, so we can backtrack to it.
+    let mut star: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some('?') => {
+                p += 1;
+                n += 1;
+            }
+            Some(c) if *c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::InstanceBuilder;
+
+    #[test]
+    fn full_name() {
+        let hinge = InstanceBuilder::new("HingeConstraint").with_name("Hinge");
+        let hinge_ref = hinge.referent();
+
+        let map = InstanceBuilder::new("Model").with_name("Map").with_child(
+            InstanceBuilder::new("Part")
+                .with_name("Door")
+                .with_child(hinge),
+        );
+        let map_ref = map.referent();
+
+        let mut dom = WeakDom::new(
+            InstanceBuilder::new("DataModel")
+                .with_name("Game")
+                .with_child(InstanceBuilder::new("Workspace").with_child(map)),
+        );
+
+        assert_eq!(
+            dom.full_name(hinge_ref).unwrap(),
+            "Workspace.Map.Door.Hinge"
+        );
+        assert_eq!(dom.full_name(dom.root_ref()).unwrap(), "Game");
+        assert_eq!(dom.full_name(Ref::new()), None);
+
+        // Without a DataModel at the top, the root's name is included.
+        let mut model = WeakDom::new(InstanceBuilder::new("Folder").with_name("Root"));
+        let root_ref = model.root_ref();
+        model.attach(dom.detach(map_ref), root_ref);
+
+        assert_eq!(model.full_name(hinge_ref).unwrap(), "Root.Map.Door.Hinge");
+    }
+
+    #[test]
+    fn globs() {
+        assert!(glob_match("Spawn*", "SpawnLocation"));
+        assert!(glob_match("Spawn*", "Spawn"));
+        assert!(glob_match("*Location", "SpawnLocation"));
+        assert!(glob_match("S?awn", "Spawn"));
+        assert!(glob_match("*a*a*", "banana"));
+        assert!(glob_match("*", ""));
+        assert!(!glob_match("Spawn*", "Respawn"));
+        assert!(!glob_match("S?awn", "Sawn"));
+        assert!(!glob_match("Door", "Doors"));
+    }
+
+    #[test]
+    fn find_all_by_path() {
+        let a = InstanceBuilder::new("SpawnLocation").with_name("SpawnA");
+        let a_ref = a.referent();
+        let b = InstanceBuilder::new("SpawnLocation").with_name("SpawnB");
+        let b_ref = b.referent();
+        let c = InstanceBuilder::new("SpawnLocation").with_name("SpawnC");
+        let c_ref = c.referent();
+
+        let map = InstanceBuilder::new("Model")
+            .with_name("Map")
+            .with_child(a)
+            .with_child(
+                InstanceBuilder::new("Folder")
+                    .with_name("Points")
+                    .with_child(b),
+            );
+        let map_ref = map.referent();
+
+        let dom = WeakDom::new(
+            InstanceBuilder::new("DataModel")
+                .with_child(InstanceBuilder::new("Workspace").with_child(map))
+                .with_child(InstanceBuilder::new("Lighting").with_child(c)),
+        );
+        let root_ref = dom.root_ref();
+
+        assert_eq!(
+            dom.find_all_by_path(root_ref, "Workspace/**/Spawn*"),
+            vec![a_ref, b_ref]
+        );
+        assert_eq!(
+            dom.find_all_by_path(root_ref, "**/Spawn?"),
+            vec![a_ref, b_ref, c_ref]
+        );
+        assert_eq!(
+            dom.find_all_by_path(root_ref, "Workspace/Map"),
+            vec![map_ref]
+        );
+        assert_eq!(dom.find_all_by_path(root_ref, "*/*/SpawnA"), vec![a_ref]);
+        assert_eq!(dom.find_all_by_path(map_ref, "**/**/SpawnB"), vec![b_ref]);
+        assert!(dom
+            .find_all_by_path(root_ref, "Workspace/Spawn*")
+            .is_empty());
+        assert_eq!(dom.find_all_by_path(root_ref, "**").len(), 7);
+    }
+}
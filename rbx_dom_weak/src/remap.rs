@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+
+use rbx_types::{Ref, Variant};
+
+use crate::WeakDom;
+
+/// Describes a `Ref` property that [`WeakDom::remap_refs`] could not remap
+/// because its value was not present in the mapping.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct UnmappedRef {
+    /// The referent of the instance that has the property.
+    pub referent: Ref,
+
+    /// The name of the property.
+    pub property: String,
+
+    /// The value of the property, which was left unchanged.
+    pub value: Ref,
+}
+
+impl WeakDom {
+    /// Rewrites every `Ref` property on the instance with the given referent
+    /// and its descendants according to `mapping`.
+    ///
+    /// Null refs are left alone. Refs that aren't keys in `mapping`, including
+    /// references to instances within the subtree that weren't given a new
+    /// referent, are also left unchanged and are returned so that the caller
+    /// can decide what to do with them.
+    ///
+    /// ## Panics
+    /// Panics if `referent` does not refer to an instance in the DOM.
+    pub fn remap_refs(&mut self, referent: Ref, mapping: &HashMap<Ref, Ref>) -> Vec<UnmappedRef> {
+        let root = self
+            .get_by_ref(referent)
+            .unwrap_or_else(|| panic!("cannot remap refs of an instance that does not exist"));
+
+        let mut to_update = Vec::new();
+        let mut unmapped = Vec::new();
+
+        for (_, instance) in self.preorder(root) {
+            let mut changed = false;
+
+            for (name, value) in &instance.properties {
+                if let Variant::Ref(value) = value {
+                    if value.is_none() {
+                        continue;
+                    }
+
+                    if mapping.contains_key(value) {
+                        changed = true;
+                    } else {
+                        unmapped.push(UnmappedRef {
+                            referent: instance.referent(),
+                            property: name.clone(),
+                            value: *value,
+                        });
+                    }
+                }
+            }
+
+            if changed {
+                to_update.push(instance.referent());
+            }
+        }
+
+        // Instances are only borrowed mutably if they actually change, which
+        // keeps transactions and undo history from recording extra copies.
+        for referent in to_update {
+            let instance = self.get_by_ref_mut(referent).unwrap();
+
+            for value in instance.properties.values_mut() {
+                if let Variant::Ref(value) = value {
+                    if let Some(new_value) = mapping.get(value) {
+                        *value = *new_value;
+                    }
+                }
+            }
+        }
+
+        unmapped
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::InstanceBuilder;
+
+    #[test]
+    fn remap() {
+        let old_target = Ref::new();
+        let new_target = Ref::new();
+        let unknown = Ref::new();
+
+        let child = InstanceBuilder::new("ObjectValue")
+            .with_property("Value", old_target)
+            .with_property("Other", unknown)
+            .with_property("Empty", Ref::none());
+        let child_ref = child.referent();
+
+        let mut dom = WeakDom::new(
+            InstanceBuilder::new("Folder")
+                .with_property("Value", old_target)
+                .with_child(child),
+        );
+        let root_ref = dom.root_ref();
+
+        let mut mapping = HashMap::new();
+        mapping.insert(old_target, new_target);
+
+        let unmapped = dom.remap_refs(root_ref, &mapping);
+
+        assert_eq!(
+            unmapped,
+            vec![UnmappedRef {
+                referent: child_ref,
+                property: "Other".to_owned(),
+                value: unknown,
+            }]
+        );
+
+        let child = dom.get_by_ref(child_ref).unwrap();
+        assert_eq!(child.properties["Value"], Variant::Ref(new_target));
+        assert_eq!(child.properties["Other"], Variant::Ref(unknown));
+        assert_eq!(child.properties["Empty"], Variant::Ref(Ref::none()));
+        assert_eq!(dom.root().properties["Value"], Variant::Ref(new_target));
+    }
+
+    #[test]
+    fn subtree_only() {
+        let old_target = Ref::new();
+        let new_target = Ref::new();
+
+        let child = InstanceBuilder::new("ObjectValue").with_property("Value", old_target);
+        let child_ref = child.referent();
+
+        let mut dom = WeakDom::new(
+            InstanceBuilder::new("Folder")
+                .with_property("Value", old_target)
+                .with_child(child),
+        );
+
+        let mut mapping = HashMap::new();
+        mapping.insert(old_target, new_target);
+
+        assert!(dom.remap_refs(child_ref, &mapping).is_empty());
+        assert_eq!(dom.root().properties["Value"], Variant::Ref(old_target));
+    }
+}
@@ -0,0 +1,321 @@
+use std::{
+    error::Error as StdError,
+    fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use rbx_types::Ref;
+
+use crate::{StructuralEq, WeakDom};
+
+type BoxedError = Box<dyn StdError + Send + Sync>;
+type DecodeFn<'a> = dyn Fn(&[u8]) -> Result<WeakDom, BoxedError> + 'a;
+type EncodeFn<'a> = dyn Fn(&WeakDom, &[Ref]) -> Result<Vec<u8>, BoxedError> + 'a;
+
+/// Checks that files survive being decoded, encoded, and decoded again without
+/// any changes to their tree, for testing serializers against a corpus of
+/// files.
+///
+/// A `RoundTrip` is made from a pair of functions: one that decodes a file
+/// into a `WeakDom`, and one that encodes the given children of a `WeakDom`'s
+/// root into a file. These don't have to come from the same crate, so a new
+/// serializer can be checked by decoding files with a known-good deserializer.
+///
+/// The trees from the first and second decodes are compared with
+/// [`StructuralEq`], so referents don't need to be kept.
+///
+/// ## Example
+/// ```no_run
+/// use rbx_dom_weak::RoundTrip;
+///
+/// # fn decode(_: &[u8]) -> Result<rbx_dom_weak::WeakDom, std::io::Error> { todo!() }
+/// # fn encode(_: &rbx_dom_weak::WeakDom, _: &[rbx_dom_weak::types::Ref]) -> Result<Vec<u8>, std::io::Error> { todo!() }
+/// let report = RoundTrip::new(decode, encode)
+///     .extensions(&["rbxm", "rbxl"])
+///     .check_dir("test-files")?;
+///
+/// assert!(report.is_success(), "{}", report);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct RoundTrip<'a> {
+    decode: Box<DecodeFn<'a>>,
+    encode: Box<EncodeFn<'a>>,
+    extensions: Vec<String>,
+    comparison: StructuralEq,
+}
+
+impl<'a> RoundTrip<'a> {
+    /// Creates a `RoundTrip` that decodes files with `decode` and encodes them
+    /// again with `encode`.
+    pub fn new<D, DE, E, EE>(decode: D, encode: E) -> Self
+    where
+        D: Fn(&[u8]) -> Result<WeakDom, DE> + 'a,
+        DE: Into<BoxedError>,
+        E: Fn(&WeakDom, &[Ref]) -> Result<Vec<u8>, EE> + 'a,
+        EE: Into<BoxedError>,
+    {
+        RoundTrip {
+            decode: Box::new(move |contents| decode(contents).map_err(Into::into)),
+            encode: Box::new(move |dom, refs| encode(dom, refs).map_err(Into::into)),
+            extensions: Vec::new(),
+            comparison: StructuralEq::new(),
+        }
+    }
+
+    /// Only checks files with the given extensions when checking a directory.
+    /// By default, every file is checked.
+    pub fn extensions<S: AsRef<str>>(self, extensions: &[S]) -> Self {
+        Self {
+            extensions: extensions
+                .iter()
+                .map(|extension| extension.as_ref().to_owned())
+                .collect(),
+            ..self
+        }
+    }
+
+    /// Sets how trees are compared, like to allow some difference in floating
+    /// point values. By default, they must match exactly.
+    pub fn comparison(self, comparison: StructuralEq) -> Self {
+        Self { comparison, ..self }
+    }
+
+    /// Decodes, encodes, and decodes the given file, checking that the tree
+    /// didn't change.
+    pub fn check(&self, contents: &[u8]) -> Result<(), RoundTripFailure> {
+        let decoded = (self.decode)(contents).map_err(RoundTripFailure::Decode)?;
+        let encoded =
+            (self.encode)(&decoded, decoded.root().children()).map_err(RoundTripFailure::Encode)?;
+        let redecoded = (self.decode)(&encoded).map_err(RoundTripFailure::Redecode)?;
+
+        match self.comparison.difference(&decoded, &redecoded) {
+            Some(difference) => Err(RoundTripFailure::Mismatch(difference)),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks every file in the given directory and its subdirectories, in
+    /// order of their paths. Only fails if the directory or one of its files
+    /// can't be read.
+    pub fn check_dir<P: AsRef<Path>>(&self, path: P) -> io::Result<RoundTripReport> {
+        let mut paths = Vec::new();
+        self.collect_paths(path.as_ref(), &mut paths)?;
+        paths.sort();
+
+        let mut report = RoundTripReport::default();
+
+        for path in paths {
+            let contents = fs::read(&path)?;
+
+            match self.check(&contents) {
+                Ok(()) => report.passed.push(path),
+                Err(failure) => report.failed.push((path, failure)),
+            }
+        }
+
+        Ok(report)
+    }
+
+    fn collect_paths(&self, dir: &Path, paths: &mut Vec<PathBuf>) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+
+            if path.is_dir() {
+                self.collect_paths(&path, paths)?;
+            } else if self.includes(&path) {
+                paths.push(path);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn includes(&self, path: &Path) -> bool {
+        if self.extensions.is_empty() {
+            return true;
+        }
+
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some(extension) => self.extensions.iter().any(|wanted| wanted == extension),
+            None => false,
+        }
+    }
+}
+
+/// The reason a file failed a [`RoundTrip`] check.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum RoundTripFailure {
+    /// The file couldn't be decoded.
+    Decode(BoxedError),
+
+    /// The decoded tree couldn't be encoded.
+    Encode(BoxedError),
+
+    /// The encoded file couldn't be decoded.
+    Redecode(BoxedError),
+
+    /// Decoding the encoded file gave a different tree, with the first
+    /// difference described by [`StructuralEq::difference`].
+    Mismatch(String),
+}
+
+impl fmt::Display for RoundTripFailure {
+    fn fmt(&self, output: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Decode(err) => write!(output, "failed to decode: {}", err),
+            Self::Encode(err) => write!(output, "failed to encode: {}", err),
+            Self::Redecode(err) => write!(output, "failed to decode after encoding: {}", err),
+            Self::Mismatch(difference) => {
+                write!(output, "tree changed after encoding: {}", difference)
+            }
+        }
+    }
+}
+
+impl StdError for RoundTripFailure {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Self::Decode(err) | Self::Encode(err) | Self::Redecode(err) => Some(&**err),
+            Self::Mismatch(_) => None,
+        }
+    }
+}
+
+/// The results of [`RoundTrip::check_dir`]. Its `Display` implementation
+/// lists every failure, for use in test output.
+#[derive(Debug, Default)]
+#[non_exhaustive]
+pub struct RoundTripReport {
+    /// The files that passed.
+    pub passed: Vec<PathBuf>,
+
+    /// The files that failed and why.
+    pub failed: Vec<(PathBuf, RoundTripFailure)>,
+}
+
+impl RoundTripReport {
+    /// Returns whether every file passed.
+    pub fn is_success(&self) -> bool {
+        self.failed.is_empty()
+    }
+}
+
+impl fmt::Display for RoundTripReport {
+    fn fmt(&self, output: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            output,
+            "{} passed, {} failed",
+            self.passed.len(),
+            self.failed.len()
+        )?;
+
+        for (path, failure) in &self.failed {
+            write!(output, "\n{}: {}", path.display(), failure)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::cell::RefCell;
+
+    use crate::InstanceBuilder;
+
+    /// A pretend format whose files are indices into a list of trees.
+    struct Store {
+        trees: RefCell<Vec<WeakDom>>,
+    }
+
+    impl Store {
+        fn new(tree: WeakDom) -> Self {
+            Store {
+                trees: RefCell::new(vec![tree]),
+            }
+        }
+
+        fn decode(&self, contents: &[u8]) -> Result<WeakDom, String> {
+            let trees = self.trees.borrow();
+            let tree = contents
+                .first()
+                .and_then(|&index| trees.get(index as usize))
+                .ok_or_else(|| "not a tree".to_owned())?;
+
+            Ok(tree.clone_filtered(tree.root_ref(), |_| true))
+        }
+
+        /// Encodes the whole tree, assuming that `refs` are the children of
+        /// its root, dropping `Value` properties unless `keep_values` is set.
+        fn encode(&self, dom: &WeakDom, keep_values: bool) -> Vec<u8> {
+            let mut tree = dom.clone_filtered(dom.root_ref(), |_| true);
+
+            if !keep_values {
+                for referent in tree.root().children().to_vec() {
+                    let instance = tree.get_by_ref_mut(referent).unwrap();
+                    instance.properties.remove("Value");
+                }
+            }
+
+            let mut trees = self.trees.borrow_mut();
+            trees.push(tree);
+            vec![(trees.len() - 1) as u8]
+        }
+    }
+
+    fn sample() -> WeakDom {
+        WeakDom::new(
+            InstanceBuilder::new("DataModel")
+                .with_child(InstanceBuilder::new("StringValue").with_property("Value", "Hello")),
+        )
+    }
+
+    #[test]
+    fn passes_and_fails() {
+        let store = Store::new(sample());
+
+        let lossless = RoundTrip::new(
+            |contents| store.decode(contents),
+            |dom, _| Ok::<_, String>(store.encode(dom, true)),
+        );
+        assert!(lossless.check(&[0]).is_ok());
+        assert!(matches!(
+            lossless.check(&[100]),
+            Err(RoundTripFailure::Decode(_))
+        ));
+
+        let lossy = RoundTrip::new(
+            |contents| store.decode(contents),
+            |dom, _| Ok::<_, String>(store.encode(dom, false)),
+        );
+        match lossy.check(&[0]) {
+            Err(RoundTripFailure::Mismatch(difference)) => {
+                assert_eq!(
+                    difference,
+                    "StringValue: property Value is missing from the other DOM"
+                );
+            }
+            other => panic!("expected a mismatch, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn report() {
+        let report = RoundTripReport {
+            passed: vec![PathBuf::from("a.rbxm")],
+            failed: vec![(
+                PathBuf::from("b.rbxm"),
+                RoundTripFailure::Mismatch("Part: 0 children differs from 1".to_owned()),
+            )],
+        };
+
+        assert!(!report.is_success());
+        assert_eq!(
+            report.to_string(),
+            "1 passed, 1 failed\nb.rbxm: tree changed after encoding: Part: 0 children differs from 1"
+        );
+    }
+}
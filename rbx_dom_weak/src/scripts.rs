@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+
+use rbx_types::{Ref, Variant};
+
+use crate::{
+    error::{Error, InnerError},
+    WeakDom,
+};
+
+/// The classes that inherit from `LuaSourceContainer` and store their code in
+/// a `Source` property.
+const SCRIPT_CLASSES: &[&str] = &["Script", "LocalScript", "ModuleScript", "CoreScript"];
+
+/// The source of a script found by [`WeakDom::script_sources`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ScriptSource<'a> {
+    /// The referent of the script.
+    pub referent: Ref,
+
+    /// The full name of the script. See [`WeakDom::full_name`].
+    pub path: String,
+
+    /// The contents of the script's `Source` property.
+    pub source: &'a str,
+}
+
+impl WeakDom {
+    /// Returns the source of every script in the DOM, in tree order.
+    ///
+    /// Scripts are instances of `Script`, `LocalScript`, `ModuleScript`, or
+    /// `CoreScript`. A script without a `Source` property has an empty source.
+    /// Scripts whose `Source` is a `BinaryString` are included if it is valid
+    /// UTF-8 and skipped otherwise.
+    pub fn script_sources(&self) -> impl Iterator<Item = ScriptSource<'_>> {
+        self.preorder(self.root())
+            .into_iter()
+            .filter(|(_, instance)| SCRIPT_CLASSES.contains(&instance.class.as_str()))
+            .filter_map(move |(_, instance)| {
+                let source = match instance.properties.get("Source") {
+                    Some(Variant::String(source)) => source.as_str(),
+                    Some(Variant::BinaryString(source)) => {
+                        std::str::from_utf8(source.as_ref()).ok()?
+                    }
+                    _ => "",
+                };
+
+                Some(ScriptSource {
+                    referent: instance.referent(),
+                    path: self.full_name(instance.referent()).unwrap(),
+                    source,
+                })
+            })
+    }
+
+    /// Sets the sources of scripts in the DOM, with each script picked out by
+    /// the path that [`WeakDom::script_sources`] returned for it.
+    ///
+    /// Every path is checked before any sources are set, so either every
+    /// source is set or none are. A path that does not refer to exactly one
+    /// script is an error, which can happen if a script is renamed or has a
+    /// sibling with the same name.
+    pub fn set_script_sources<P, S, I>(&mut self, sources: I) -> Result<(), Error>
+    where
+        P: AsRef<str>,
+        S: Into<String>,
+        I: IntoIterator<Item = (P, S)>,
+    {
+        let mut scripts: HashMap<String, Vec<Ref>> = HashMap::new();
+        for script in self.script_sources() {
+            scripts
+                .entry(script.path)
+                .or_default()
+                .push(script.referent);
+        }
+
+        let mut updates = Vec::new();
+        for (path, source) in sources {
+            let path = path.as_ref();
+
+            match scripts.get(path).map(Vec::as_slice) {
+                Some([referent]) => updates.push((*referent, source.into())),
+                Some(_) => {
+                    return Err(InnerError::AmbiguousScriptPath {
+                        path: path.to_owned(),
+                    }
+                    .into())
+                }
+                None => {
+                    return Err(InnerError::ScriptNotFound {
+                        path: path.to_owned(),
+                    }
+                    .into())
+                }
+            }
+        }
+
+        for (referent, source) in updates {
+            let instance = self.get_by_ref_mut(referent).unwrap();
+            instance
+                .properties
+                .insert("Source".to_owned(), Variant::String(source));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::InstanceBuilder;
+
+    fn dom() -> WeakDom {
+        WeakDom::new(
+            InstanceBuilder::new("DataModel").with_child(
+                InstanceBuilder::new("ReplicatedStorage")
+                    .with_child(
+                        InstanceBuilder::new("ModuleScript")
+                            .with_name("Util")
+                            .with_property("Source", "return {}")
+                            .with_child(InstanceBuilder::new("ModuleScript").with_name("Math")),
+                    )
+                    .with_child(InstanceBuilder::new("Script").with_name("Dup"))
+                    .with_child(InstanceBuilder::new("LocalScript").with_name("Dup"))
+                    .with_child(InstanceBuilder::new("Folder").with_name("NotAScript")),
+            ),
+        )
+    }
+
+    #[test]
+    fn script_sources() {
+        let dom = dom();
+
+        let sources: Vec<_> = dom
+            .script_sources()
+            .map(|script| (script.path, script.source))
+            .collect();
+
+        assert_eq!(
+            sources,
+            [
+                ("ReplicatedStorage.Util".to_owned(), "return {}"),
+                ("ReplicatedStorage.Util.Math".to_owned(), ""),
+                ("ReplicatedStorage.Dup".to_owned(), ""),
+                ("ReplicatedStorage.Dup".to_owned(), ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn set_script_sources() {
+        let mut dom = dom();
+
+        dom.set_script_sources([("ReplicatedStorage.Util.Math", "return math")])
+            .unwrap();
+
+        let math = dom
+            .script_sources()
+            .find(|script| script.path == "ReplicatedStorage.Util.Math")
+            .unwrap();
+        assert_eq!(math.source, "return math");
+    }
+
+    #[test]
+    fn set_script_sources_errors() {
+        let mut dom = dom();
+
+        assert!(dom
+            .set_script_sources([
+                ("ReplicatedStorage.Util", "changed"),
+                ("ReplicatedStorage.Dup", "ambiguous"),
+            ])
+            .is_err());
+        assert!(dom
+            .set_script_sources([("ReplicatedStorage.Missing", "")])
+            .is_err());
+
+        // Nothing should have been changed by the failed calls.
+        let util = dom.script_sources().next().unwrap();
+        assert_eq!(util.source, "return {}");
+    }
+}
@@ -0,0 +1,182 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashSet},
+};
+
+use rbx_types::{Ref, Variant};
+
+use crate::WeakDom;
+
+/// The number of entries kept in [`DomStats::largest_binary_strings`].
+const LARGEST_BINARY_STRINGS: usize = 10;
+
+/// Statistics about the contents of a [`WeakDom`], useful for figuring out
+/// what is taking up space in a model or place.
+///
+/// Created with [`WeakDom::stats`].
+#[derive(Debug, Clone, Default, PartialEq)]
+#[non_exhaustive]
+pub struct DomStats {
+    /// The total number of instances visited.
+    pub instance_count: usize,
+
+    /// The number of instances of each class.
+    pub class_counts: BTreeMap<String, usize>,
+
+    /// The approximate number of bytes used by all property values, measured
+    /// as the size of each value when serialized compactly.
+    ///
+    /// Every reference to a `SharedString` counts its full length, even though
+    /// the contents are only stored once. See
+    /// [`unique_shared_string_bytes`][DomStats::unique_shared_string_bytes].
+    pub property_bytes: u64,
+
+    /// The number of properties holding a `SharedString`.
+    pub shared_string_count: usize,
+
+    /// The number of distinct `SharedString` values.
+    pub unique_shared_string_count: usize,
+
+    /// The number of bytes used by distinct `SharedString` values.
+    pub unique_shared_string_bytes: u64,
+
+    /// The largest `BinaryString` properties, ordered from largest to
+    /// smallest. At most 10 entries are kept.
+    pub largest_binary_strings: Vec<BinaryStringStat>,
+
+    /// The number of instances at each depth, where index 0 is the instance
+    /// the statistics were collected from.
+    pub depth_counts: Vec<usize>,
+}
+
+/// Describes a `BinaryString` property found while collecting [`DomStats`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BinaryStringStat {
+    /// The referent of the instance that has the property.
+    pub referent: Ref,
+
+    /// The name of the property.
+    pub property: String,
+
+    /// The length of the property's value in bytes.
+    pub len: usize,
+}
+
+impl WeakDom {
+    /// Collects statistics about the instance with the given referent and all
+    /// of its descendants.
+    ///
+    /// ## Panics
+    /// Panics if `referent` does not refer to an instance in the DOM.
+    pub fn stats(&self, referent: Ref) -> DomStats {
+        let root = self
+            .get_by_ref(referent)
+            .unwrap_or_else(|| panic!("cannot collect stats for an instance that does not exist"));
+
+        let mut stats = DomStats::default();
+        let mut shared_strings = HashSet::new();
+
+        for (depth, instance) in self.preorder(root) {
+            stats.instance_count += 1;
+            *stats
+                .class_counts
+                .entry(instance.class.clone())
+                .or_default() += 1;
+
+            if stats.depth_counts.len() <= depth {
+                stats.depth_counts.resize(depth + 1, 0);
+            }
+            stats.depth_counts[depth] += 1;
+
+            for (name, value) in &instance.properties {
+                stats.property_bytes += value_size(value);
+
+                match value {
+                    Variant::SharedString(value) => {
+                        stats.shared_string_count += 1;
+
+                        if shared_strings.insert(value.hash()) {
+                            stats.unique_shared_string_count += 1;
+                            stats.unique_shared_string_bytes += value.data().len() as u64;
+                        }
+                    }
+                    Variant::BinaryString(value) => {
+                        stats.largest_binary_strings.push(BinaryStringStat {
+                            referent: instance.referent(),
+                            property: name.clone(),
+                            len: AsRef::<[u8]>::as_ref(value).len(),
+                        });
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        stats
+            .largest_binary_strings
+            .sort_by_key(|stat| Reverse(stat.len));
+        stats
+            .largest_binary_strings
+            .truncate(LARGEST_BINARY_STRINGS);
+
+        stats
+    }
+}
+
+fn value_size(value: &Variant) -> u64 {
+    match value {
+        // SharedString values can't be serialized, so we count their contents
+        // directly instead.
+        Variant::SharedString(value) => value.data().len() as u64,
+        _ => bincode::serialized_size(value).unwrap_or(0),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rbx_types::{BinaryString, SharedString};
+
+    use crate::InstanceBuilder;
+
+    #[test]
+    fn counts() {
+        let mesh = SharedString::new(vec![1, 2, 3, 4]);
+
+        let dom = WeakDom::new(
+            InstanceBuilder::new("Folder").with_children([
+                InstanceBuilder::new("MeshPart")
+                    .with_property("MeshData", mesh.clone())
+                    .with_property("Tags", BinaryString::from(vec![0; 5])),
+                InstanceBuilder::new("MeshPart")
+                    .with_property("MeshData", mesh)
+                    .with_child(
+                        InstanceBuilder::new("Folder")
+                            .with_property("Data", BinaryString::from(vec![0; 20])),
+                    ),
+            ]),
+        );
+
+        let stats = dom.stats(dom.root_ref());
+
+        assert_eq!(stats.instance_count, 4);
+        assert_eq!(stats.class_counts["Folder"], 2);
+        assert_eq!(stats.class_counts["MeshPart"], 2);
+        assert_eq!(stats.depth_counts, vec![1, 2, 1]);
+
+        assert_eq!(stats.shared_string_count, 2);
+        assert_eq!(stats.unique_shared_string_count, 1);
+        assert_eq!(stats.unique_shared_string_bytes, 4);
+
+        let largest: Vec<_> = stats
+            .largest_binary_strings
+            .iter()
+            .map(|stat| (stat.property.as_str(), stat.len))
+            .collect();
+        assert_eq!(largest, vec![("Data", 20), ("Tags", 5)]);
+
+        assert!(stats.property_bytes >= 4 + 4 + 5 + 20);
+    }
+}
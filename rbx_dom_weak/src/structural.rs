@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+
+use rbx_types::{Ref, Variant};
+
+use crate::{Instance, WeakDom};
+
+/// Compares instance trees by their contents, ignoring the referents of the
+/// instances in them.
+///
+/// Two trees are structurally equal if each pair of corresponding instances
+/// has the same class, name, and properties, and the same children in the same
+/// order. `Ref` properties are equal if they point to corresponding instances
+/// within the trees being compared, if they are both null, or if they point to
+/// the same instance outside of the trees.
+///
+/// By default, floating point values must match exactly. A tolerance can be set
+/// with [`StructuralEq::float_tolerance`].
+///
+/// ## Example
+/// ```
+/// use rbx_dom_weak::{InstanceBuilder, StructuralEq, WeakDom};
+///
+/// let a = WeakDom::new(InstanceBuilder::new("Part").with_property("Transparency", 0.5f32));
+/// let b = WeakDom::new(InstanceBuilder::new("Part").with_property("Transparency", 0.500001f32));
+///
+/// assert!(!a.eq_structural(&b));
+/// assert!(StructuralEq::new().float_tolerance(1.0e-5).eq(&a, &b));
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StructuralEq {
+    float_tolerance: f32,
+}
+
+impl StructuralEq {
+    /// Creates a new `StructuralEq` that compares floating point values
+    /// exactly.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the tolerance used when comparing floating point components of
+    /// property values. See [`Variant::approx_eq`].
+    pub fn float_tolerance(self, float_tolerance: f32) -> Self {
+        Self { float_tolerance }
+    }
+
+    /// Returns whether two DOMs are structurally equal.
+    pub fn eq(&self, a: &WeakDom, b: &WeakDom) -> bool {
+        self.eq_subtree(a, a.root_ref(), b, b.root_ref())
+    }
+
+    /// Returns whether the subtree rooted at `a_ref` in `a` is structurally
+    /// equal to the subtree rooted at `b_ref` in `b`.
+    ///
+    /// ## Panics
+    /// Panics if `a_ref` does not refer to an instance in `a` or `b_ref` does
+    /// not refer to an instance in `b`.
+    pub fn eq_subtree(&self, a: &WeakDom, a_ref: Ref, b: &WeakDom, b_ref: Ref) -> bool {
+        self.difference_subtree(a, a_ref, b, b_ref).is_none()
+    }
+
+    /// Describes the first difference found between two DOMs, starting with
+    /// the full name of the instance in `a` where it was found. Returns `None`
+    /// if the DOMs are structurally equal.
+    pub fn difference(&self, a: &WeakDom, b: &WeakDom) -> Option<String> {
+        self.difference_subtree(a, a.root_ref(), b, b.root_ref())
+    }
+
+    /// Like [`StructuralEq::difference`], but compares the subtree rooted at
+    /// `a_ref` in `a` to the subtree rooted at `b_ref` in `b`.
+    ///
+    /// ## Panics
+    /// Panics if `a_ref` does not refer to an instance in `a` or `b_ref` does
+    /// not refer to an instance in `b`.
+    pub fn difference_subtree(
+        &self,
+        a: &WeakDom,
+        a_ref: Ref,
+        b: &WeakDom,
+        b_ref: Ref,
+    ) -> Option<String> {
+        let a_root = a
+            .get_by_ref(a_ref)
+            .unwrap_or_else(|| panic!("cannot compare an instance that does not exist"));
+        let b_root = b
+            .get_by_ref(b_ref)
+            .unwrap_or_else(|| panic!("cannot compare an instance that does not exist"));
+
+        let a_order = a.preorder(a_root);
+        let b_order = b.preorder(b_root);
+
+        let a_indices = indices(&a_order);
+        let b_indices = indices(&b_order);
+
+        // Child counts are compared along the way, so if every pair of
+        // instances matches, the trees have the same shape.
+        a_order
+            .iter()
+            .zip(&b_order)
+            .find_map(|((_, a_instance), (_, b_instance))| {
+                let difference =
+                    self.instance_difference(a_instance, &a_indices, b_instance, &b_indices)?;
+                let name = a.full_name(a_instance.referent()).unwrap_or_default();
+
+                Some(format!("{}: {}", name, difference))
+            })
+    }
+
+    fn instance_difference(
+        &self,
+        a: &Instance,
+        a_indices: &HashMap<Ref, usize>,
+        b: &Instance,
+        b_indices: &HashMap<Ref, usize>,
+    ) -> Option<String> {
+        if a.class != b.class {
+            return Some(format!("class {} differs from {}", a.class, b.class));
+        }
+
+        if a.name != b.name {
+            return Some(format!("name {:?} differs from {:?}", a.name, b.name));
+        }
+
+        if a.children().len() != b.children().len() {
+            return Some(format!(
+                "{} children differs from {}",
+                a.children().len(),
+                b.children().len()
+            ));
+        }
+
+        for (name, a_value) in &a.properties {
+            match b.properties.get(name) {
+                Some(b_value) => {
+                    if !self.value_eq(a_value, a_indices, b_value, b_indices) {
+                        return Some(format!(
+                            "property {} is {:?}, which differs from {:?}",
+                            name, a_value, b_value
+                        ));
+                    }
+                }
+                None => return Some(format!("property {} is missing from the other DOM", name)),
+            }
+        }
+
+        b.properties
+            .keys()
+            .find(|name| !a.properties.contains_key(*name))
+            .map(|name| format!("property {} is only present in the other DOM", name))
+    }
+
+    fn value_eq(
+        &self,
+        a: &Variant,
+        a_indices: &HashMap<Ref, usize>,
+        b: &Variant,
+        b_indices: &HashMap<Ref, usize>,
+    ) -> bool {
+        match (a, b) {
+            (Variant::Ref(a), Variant::Ref(b)) => match (a_indices.get(a), b_indices.get(b)) {
+                (Some(a_index), Some(b_index)) => a_index == b_index,
+                (None, None) => a == b,
+                _ => false,
+            },
+            _ => a.approx_eq(b, self.float_tolerance),
+        }
+    }
+}
+
+fn indices(order: &[(usize, &Instance)]) -> HashMap<Ref, usize> {
+    order
+        .iter()
+        .enumerate()
+        .map(|(index, (_, instance))| (instance.referent(), index))
+        .collect()
+}
+
+impl WeakDom {
+    /// Returns whether this DOM is structurally equal to another DOM,
+    /// comparing floating point values exactly. See [`StructuralEq`] for
+    /// details and more options.
+    pub fn eq_structural(&self, other: &WeakDom) -> bool {
+        StructuralEq::new().eq(self, other)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rbx_types::Vector3;
+
+    use crate::InstanceBuilder;
+
+    fn build(position: Vector3) -> WeakDom {
+        let part = InstanceBuilder::new("Part").with_property("Position", position);
+        let part_ref = part.referent();
+
+        WeakDom::new(
+            InstanceBuilder::new("Model")
+                .with_property("PrimaryPart", part_ref)
+                .with_property("Parent", Ref::none())
+                .with_child(part)
+                .with_child(InstanceBuilder::new("Folder")),
+        )
+    }
+
+    #[test]
+    fn ignores_referents() {
+        let a = build(Vector3::new(1.0, 2.0, 3.0));
+        let b = build(Vector3::new(1.0, 2.0, 3.0));
+
+        assert!(a.eq_structural(&b));
+        assert!(b.eq_structural(&a));
+    }
+
+    #[test]
+    fn float_tolerance() {
+        let a = build(Vector3::new(1.0, 2.0, 3.0));
+        let b = build(Vector3::new(1.0, 2.000001, 3.0));
+
+        assert!(!a.eq_structural(&b));
+        assert!(StructuralEq::new().float_tolerance(1.0e-5).eq(&a, &b));
+    }
+
+    #[test]
+    fn detects_differences() {
+        let a = build(Vector3::new(1.0, 2.0, 3.0));
+
+        let mut b = build(Vector3::new(1.0, 2.0, 3.0));
+        b.root_mut().properties.remove("Parent");
+        assert!(!a.eq_structural(&b));
+
+        let mut c = build(Vector3::new(1.0, 2.0, 3.0));
+        let folder = c.root().children()[1];
+        c.root_mut()
+            .properties
+            .insert("PrimaryPart".to_owned(), folder.into());
+        assert!(!a.eq_structural(&c));
+
+        let mut d = build(Vector3::new(1.0, 2.0, 3.0));
+        let part = d.root().children()[0];
+        let folder = d.root().children()[1];
+        d.transfer_within(part, folder);
+        assert!(!a.eq_structural(&d));
+    }
+
+    #[test]
+    fn differences() {
+        let a = build(Vector3::new(1.0, 2.0, 3.0));
+        assert_eq!(StructuralEq::new().difference(&a, &a), None);
+
+        let mut b = build(Vector3::new(1.0, 2.0, 3.0));
+        let part = b.root().children()[0];
+        b.get_by_ref_mut(part)
+            .unwrap()
+            .properties
+            .insert("Position".to_owned(), Vector3::new(1.0, 2.0, 4.0).into());
+
+        assert_eq!(
+            StructuralEq::new().difference(&a, &b).unwrap(),
+            "Model.Part: property Position is Vector3(Vector3 { x: 1.0, y: 2.0, z: 3.0 }), \
+             which differs from Vector3(Vector3 { x: 1.0, y: 2.0, z: 4.0 })"
+        );
+
+        let mut c = build(Vector3::new(1.0, 2.0, 3.0));
+        let folder = c.root().children()[1];
+        c.insert(folder, InstanceBuilder::new("Folder"));
+
+        assert_eq!(
+            StructuralEq::new().difference(&a, &c).unwrap(),
+            "Model.Folder: 0 children differs from 1"
+        );
+    }
+
+    #[test]
+    fn subtrees() {
+        let a = build(Vector3::new(1.0, 2.0, 3.0));
+        let b = WeakDom::new(InstanceBuilder::new("Folder"));
+
+        let folder = a.root().children()[1];
+        assert!(StructuralEq::new().eq_subtree(&a, folder, &b, b.root_ref()));
+        assert!(!StructuralEq::new().eq_subtree(&a, a.root_ref(), &b, b.root_ref()));
+    }
+}
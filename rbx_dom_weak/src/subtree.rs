@@ -0,0 +1,54 @@
+use std::collections::HashMap;
+
+use rbx_types::Ref;
+
+use crate::Instance;
+
+/// An instance and all of its descendants that have been removed from a
+/// [`WeakDom`][crate::WeakDom].
+///
+/// Instances in a `DetachedSubtree` keep their referents, so `Ref` properties
+/// that point between them stay valid. The subtree can be attached to the same
+/// DOM or a different one later with [`WeakDom::attach`][crate::WeakDom::attach],
+/// or turned into a new DOM with `WeakDom::from`.
+///
+/// Created with [`WeakDom::detach`][crate::WeakDom::detach].
+#[derive(Debug)]
+pub struct DetachedSubtree {
+    pub(crate) root_ref: Ref,
+    pub(crate) instances: HashMap<Ref, Instance>,
+}
+
+impl DetachedSubtree {
+    /// Returns the referent of the root instance of the subtree.
+    pub fn root_ref(&self) -> Ref {
+        self.root_ref
+    }
+
+    /// Returns a reference to the root instance of the subtree.
+    pub fn root(&self) -> &Instance {
+        self.instances.get(&self.root_ref).unwrap()
+    }
+
+    /// Returns a reference to an instance in the subtree by referent, or `None`
+    /// if it is not found.
+    pub fn get_by_ref(&self, referent: Ref) -> Option<&Instance> {
+        self.instances.get(&referent)
+    }
+
+    /// Returns a _mutable_ reference to an instance in the subtree by referent,
+    /// or `None` if it is not found.
+    pub fn get_by_ref_mut(&mut self, referent: Ref) -> Option<&mut Instance> {
+        self.instances.get_mut(&referent)
+    }
+
+    /// Returns the number of instances in the subtree, including the root.
+    pub fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    /// Always returns `false`, since a subtree contains at least its root.
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+}
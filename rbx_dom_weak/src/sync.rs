@@ -0,0 +1,343 @@
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock, RwLockReadGuard, RwLockWriteGuard,
+    },
+};
+
+use crate::WeakDom;
+
+/// A [`WeakDom`] that can be shared between threads, allowing any number of
+/// concurrent readers or a single writer at a time.
+///
+/// Readers that can't afford to wait for writers can take a
+/// [snapshot][SyncWeakDom::snapshot] instead. Snapshots are immutable copies of
+/// the DOM, and stay valid for as long as they're held, no matter what writers
+/// do to the DOM afterwards. Taking a snapshot only clones an `Arc`. Copying
+/// the DOM costs as much as its size, so it's only done when
+/// [`publish`][SyncWeakDom::publish] is called, which lets writers make any
+/// number of changes before paying for a copy.
+///
+/// `SyncWeakDom` also keeps a generation number that is bumped every time a
+/// writer finishes, which readers can use to cheaply check whether anything
+/// they've cached from the DOM may be out of date. Each snapshot is stored
+/// with the generation it was copied at, so the two always agree.
+///
+/// ## Example
+/// ```
+/// use std::{sync::Arc, thread};
+///
+/// use rbx_dom_weak::{InstanceBuilder, SyncWeakDom, WeakDom};
+///
+/// let dom = Arc::new(SyncWeakDom::new(WeakDom::new(InstanceBuilder::new("DataModel"))));
+///
+/// let reader = {
+///     let dom = Arc::clone(&dom);
+///     thread::spawn(move || dom.read().root().class.clone())
+/// };
+///
+/// assert_eq!(reader.join().unwrap(), "DataModel");
+///
+/// let before = dom.snapshot();
+///
+/// {
+///     let mut dom = dom.write();
+///     let root_ref = dom.root_ref();
+///     dom.insert(root_ref, InstanceBuilder::new("Workspace"));
+/// }
+///
+/// assert_eq!(dom.generation(), 1);
+/// assert_eq!(dom.snapshot().root().children().len(), 0);
+///
+/// dom.publish();
+///
+/// assert_eq!(before.root().children().len(), 0);
+/// assert_eq!(dom.snapshot().root().children().len(), 1);
+/// assert_eq!(dom.snapshot_with_generation().1, 1);
+/// ```
+#[derive(Debug)]
+pub struct SyncWeakDom {
+    dom: RwLock<WeakDom>,
+    snapshot: RwLock<(Arc<WeakDom>, u64)>,
+    generation: AtomicU64,
+}
+
+impl SyncWeakDom {
+    /// Wraps the given `WeakDom` so that it can be shared between threads.
+    pub fn new(dom: WeakDom) -> Self {
+        let snapshot = Arc::new(dom.copy_for_snapshot());
+
+        Self {
+            dom: RwLock::new(dom),
+            snapshot: RwLock::new((snapshot, 0)),
+            generation: AtomicU64::new(0),
+        }
+    }
+
+    /// Borrows the DOM for reading, blocking until no writer holds it. Any
+    /// number of readers can hold the DOM at once.
+    ///
+    /// ## Panics
+    /// Panics if a writer panicked while holding the DOM, since the DOM may
+    /// have been left in an inconsistent state.
+    pub fn read(&self) -> RwLockReadGuard<'_, WeakDom> {
+        self.dom
+            .read()
+            .unwrap_or_else(|_| panic!("a writer panicked while holding the SyncWeakDom"))
+    }
+
+    /// Returns an immutable snapshot of the DOM as it was the last time it was
+    /// [published][SyncWeakDom::publish]. This never waits for a writer that's
+    /// still holding the DOM.
+    ///
+    /// Snapshots have the same referents as the DOM they were taken from, and
+    /// keep its name and class indices, but have no undo history.
+    pub fn snapshot(&self) -> Arc<WeakDom> {
+        self.snapshot_with_generation().0
+    }
+
+    /// Like [`snapshot`][SyncWeakDom::snapshot], but also returns the
+    /// generation the DOM was at when the snapshot was copied.
+    pub fn snapshot_with_generation(&self) -> (Arc<WeakDom>, u64) {
+        let snapshot = self
+            .snapshot
+            .read()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        (Arc::clone(&snapshot.0), snapshot.1)
+    }
+
+    /// Copies the DOM into a new snapshot that later calls to
+    /// [`snapshot`][SyncWeakDom::snapshot] return, unless no writer has
+    /// finished since the last one was published. Returns the new snapshot.
+    ///
+    /// The DOM is only borrowed for reading while it's copied, so readers
+    /// aren't blocked, but writers have to wait for the copy to finish.
+    ///
+    /// ## Panics
+    /// Panics if a writer panicked while holding the DOM, since the DOM may
+    /// have been left in an inconsistent state.
+    pub fn publish(&self) -> Arc<WeakDom> {
+        let dom = self.read();
+
+        // Writers can't finish while we hold the DOM, so the generation can't
+        // change until we're done.
+        let generation = self.generation();
+
+        let current = self.snapshot_with_generation();
+        if current.1 == generation {
+            return current.0;
+        }
+
+        let snapshot = Arc::new(dom.copy_for_snapshot());
+
+        // Publishing while still holding the DOM keeps a slower publish of an
+        // older generation from replacing this one.
+        let mut published = self
+            .snapshot
+            .write()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if published.1 < generation {
+            *published = (Arc::clone(&snapshot), generation);
+        }
+
+        snapshot
+    }
+
+    /// Borrows the DOM for writing, blocking until no other reader or writer
+    /// holds it. The generation number is bumped when the guard is dropped.
+    ///
+    /// ## Panics
+    /// Panics if a writer panicked while holding the DOM, since the DOM may
+    /// have been left in an inconsistent state.
+    pub fn write(&self) -> SyncWeakDomWriteGuard<'_> {
+        let guard = self
+            .dom
+            .write()
+            .unwrap_or_else(|_| panic!("a writer panicked while holding the SyncWeakDom"));
+
+        SyncWeakDomWriteGuard {
+            guard,
+            generation: &self.generation,
+        }
+    }
+
+    /// Returns the number of times a writer has finished with the DOM.
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
+    }
+
+    /// Borrows the DOM for writing without locking it, since having a mutable
+    /// reference to the `SyncWeakDom` guarantees that nothing else can be
+    /// accessing it. This bumps the generation number like
+    /// [`write`][SyncWeakDom::write] does.
+    ///
+    /// ## Panics
+    /// Panics if a writer panicked while holding the DOM, since the DOM may
+    /// have been left in an inconsistent state.
+    pub fn get_mut(&mut self) -> &mut WeakDom {
+        *self.generation.get_mut() += 1;
+
+        self.dom
+            .get_mut()
+            .unwrap_or_else(|_| panic!("a writer panicked while holding the SyncWeakDom"))
+    }
+
+    /// Consumes the `SyncWeakDom`, returning the DOM inside of it. Snapshots
+    /// taken from it stay valid.
+    pub fn into_inner(self) -> WeakDom {
+        self.dom
+            .into_inner()
+            .unwrap_or_else(|_| panic!("a writer panicked while holding the SyncWeakDom"))
+    }
+}
+
+impl From<WeakDom> for SyncWeakDom {
+    fn from(dom: WeakDom) -> Self {
+        Self::new(dom)
+    }
+}
+
+/// Write access to the DOM inside of a [`SyncWeakDom`], returned by
+/// [`SyncWeakDom::write`].
+///
+/// When the guard is dropped, the `SyncWeakDom`'s generation number is bumped
+/// before the DOM is released, so readers never see the changes without the
+/// new generation.
+#[derive(Debug)]
+pub struct SyncWeakDomWriteGuard<'a> {
+    guard: RwLockWriteGuard<'a, WeakDom>,
+    generation: &'a AtomicU64,
+}
+
+impl Deref for SyncWeakDomWriteGuard<'_> {
+    type Target = WeakDom;
+
+    fn deref(&self) -> &WeakDom {
+        &self.guard
+    }
+}
+
+impl DerefMut for SyncWeakDomWriteGuard<'_> {
+    fn deref_mut(&mut self) -> &mut WeakDom {
+        &mut self.guard
+    }
+}
+
+impl Drop for SyncWeakDomWriteGuard<'_> {
+    fn drop(&mut self) {
+        // The lock is released after this runs, when `guard` is dropped.
+        self.generation.fetch_add(1, Ordering::AcqRel);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::{sync::Arc, thread};
+
+    use crate::InstanceBuilder;
+
+    #[test]
+    fn concurrent_readers() {
+        let dom = Arc::new(SyncWeakDom::new(WeakDom::new(
+            InstanceBuilder::new("Folder").with_child(InstanceBuilder::new("Part")),
+        )));
+
+        // Hold a read guard on this thread while other threads read too.
+        let guard = dom.read();
+
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let dom = Arc::clone(&dom);
+                thread::spawn(move || dom.read().root().children().len())
+            })
+            .collect();
+
+        for reader in readers {
+            assert_eq!(reader.join().unwrap(), 1);
+        }
+
+        drop(guard);
+    }
+
+    #[test]
+    fn writes_bump_generation() {
+        let mut dom = SyncWeakDom::from(WeakDom::new(InstanceBuilder::new("Folder")));
+        assert_eq!(dom.generation(), 0);
+
+        assert_eq!(dom.read().root().name, "Folder");
+        assert_eq!(dom.generation(), 0);
+
+        let mut writer = dom.write();
+        writer.root_mut().name = "Renamed".to_owned();
+        assert_eq!(dom.generation(), 0);
+        drop(writer);
+        assert_eq!(dom.generation(), 1);
+
+        dom.get_mut().root_mut().name = "Renamed Again".to_owned();
+        assert_eq!(dom.generation(), 2);
+
+        assert_eq!(dom.into_inner().root().name, "Renamed Again");
+    }
+
+    #[test]
+    fn snapshots_while_writing() {
+        let dom = Arc::new(SyncWeakDom::new(WeakDom::new(
+            InstanceBuilder::new("Folder").with_child(InstanceBuilder::new("Part")),
+        )));
+
+        let before = dom.snapshot();
+        let mut writer = dom.write();
+        writer.root_mut().name = "Renamed".to_owned();
+
+        // Snapshots can be taken on other threads while the writer holds the
+        // DOM, and see the DOM as it was before the write.
+        let reader = {
+            let dom = Arc::clone(&dom);
+            thread::spawn(move || dom.snapshot().root().name.clone())
+        };
+        assert_eq!(reader.join().unwrap(), "Folder");
+
+        drop(writer);
+
+        // Nothing is copied until the DOM is published.
+        assert!(Arc::ptr_eq(&dom.snapshot(), &before));
+
+        let after = dom.publish();
+        assert_eq!(before.root().name, "Folder");
+        assert_eq!(after.root().name, "Renamed");
+        assert_eq!(after.root_ref(), before.root_ref());
+        assert_eq!(after.root().children(), before.root().children());
+    }
+
+    #[test]
+    fn publish_batches_writes() {
+        let dom = SyncWeakDom::new(WeakDom::new(InstanceBuilder::new("Folder")));
+
+        for name in ["A", "B", "C"] {
+            let mut writer = dom.write();
+            let root_ref = writer.root_ref();
+            writer.insert(root_ref, InstanceBuilder::new("Part").with_name(name));
+        }
+
+        let first = dom.publish();
+        assert_eq!(first.root().children().len(), 3);
+        assert_eq!(dom.snapshot_with_generation().1, 3);
+
+        // Publishing again without any writes reuses the same snapshot.
+        assert!(Arc::ptr_eq(&dom.publish(), &first));
+    }
+
+    #[test]
+    fn snapshots_keep_indices() {
+        let mut dom =
+            WeakDom::new(InstanceBuilder::new("Folder").with_child(InstanceBuilder::new("Part")));
+        dom.enable_name_index();
+
+        let dom = SyncWeakDom::new(dom);
+        assert_eq!(dom.snapshot().find_all_named("Part").len(), 1);
+    }
+}
@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+
+use rbx_types::{Ref, Variant};
+
+use crate::{InstanceBuilder, WeakDom};
+
+/// A reusable copy of an instance and its descendants that can be
+/// instantiated into a [`WeakDom`] any number of times with
+/// [`WeakDom::instantiate`].
+///
+/// Instances in a template keep the referents they had when the template was
+/// created. These referents are used to pick out instances when specifying
+/// [`TemplateOverrides`], but each instantiation gets brand new referents.
+///
+/// ## Example
+/// ```
+/// use rbx_dom_weak::{InstanceBuilder, Template, TemplateOverrides, WeakDom};
+///
+/// let door = InstanceBuilder::new("Part")
+///     .with_name("Door")
+///     .with_property("Transparency", 0.0f32);
+/// let door_ref = door.referent();
+///
+/// let mut dom = WeakDom::new(InstanceBuilder::new("Folder").with_child(door));
+/// let template = Template::new(&dom, door_ref);
+///
+/// let overrides = TemplateOverrides::new()
+///     .with_name(template.root_ref(), "GlassDoor")
+///     .with_property(template.root_ref(), "Transparency", 0.5f32);
+///
+/// let root_ref = dom.root_ref();
+/// let glass_door = dom.instantiate(&template, root_ref, &overrides);
+///
+/// assert_eq!(dom.get_by_ref(glass_door).unwrap().name, "GlassDoor");
+/// ```
+#[derive(Debug, Clone)]
+pub struct Template {
+    /// The instances in the template in depth-first pre-order, so the root is
+    /// always first.
+    instances: Vec<TemplateInstance>,
+}
+
+#[derive(Debug, Clone)]
+struct TemplateInstance {
+    referent: Ref,
+    name: String,
+    class: String,
+    properties: HashMap<String, Variant>,
+    children: Vec<usize>,
+}
+
+impl Template {
+    /// Creates a template from the instance with the given referent and all of
+    /// its descendants.
+    ///
+    /// ## Panics
+    /// Panics if `referent` does not refer to an instance in `dom`.
+    pub fn new(dom: &WeakDom, referent: Ref) -> Self {
+        let root = dom.get_by_ref(referent).unwrap_or_else(|| {
+            panic!("cannot create a template from an instance that does not exist")
+        });
+
+        let order = dom.preorder(root);
+        let indices: HashMap<Ref, usize> = order
+            .iter()
+            .enumerate()
+            .map(|(index, (_, instance))| (instance.referent(), index))
+            .collect();
+
+        let instances = order
+            .iter()
+            .map(|(_, instance)| TemplateInstance {
+                referent: instance.referent(),
+                name: instance.name.clone(),
+                class: instance.class.clone(),
+                properties: instance.properties.clone(),
+                children: instance
+                    .children()
+                    .iter()
+                    .map(|child| indices[child])
+                    .collect(),
+            })
+            .collect();
+
+        Self { instances }
+    }
+
+    /// Returns the referent of the root instance of the template. This is the
+    /// referent the instance had when the template was created.
+    pub fn root_ref(&self) -> Ref {
+        self.instances[0].referent
+    }
+
+    /// Returns the number of instances in the template, including the root.
+    pub fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    /// Always returns `false`, since a template contains at least its root.
+    pub fn is_empty(&self) -> bool {
+        false
+    }
+
+    fn build(
+        &self,
+        index: usize,
+        overrides: &TemplateOverrides,
+        referents: &HashMap<Ref, Ref>,
+    ) -> InstanceBuilder {
+        let instance = &self.instances[index];
+        let new_referent = referents[&instance.referent];
+
+        let name = overrides
+            .names
+            .get(&instance.referent)
+            .unwrap_or(&instance.name);
+
+        let mut properties = instance.properties.clone();
+        if let Some(overridden) = overrides.properties.get(&instance.referent) {
+            properties.extend(overridden.clone());
+        }
+
+        for value in properties.values_mut() {
+            if let Variant::Ref(target) = value {
+                if let Some(new_target) = referents.get(target) {
+                    *target = *new_target;
+                }
+            }
+        }
+
+        let mut builder = InstanceBuilder::new(instance.class.as_str())
+            .with_name(name.as_str())
+            .with_properties(properties);
+        builder.referent = new_referent;
+
+        for &child in &instance.children {
+            builder.add_child(self.build(child, overrides, referents));
+        }
+
+        builder
+    }
+}
+
+/// Changes to make to specific instances when instantiating a [`Template`].
+///
+/// Instances are picked out by the referents they had when the template was
+/// created.
+#[derive(Debug, Clone, Default)]
+pub struct TemplateOverrides {
+    names: HashMap<Ref, String>,
+    properties: HashMap<Ref, HashMap<String, Variant>>,
+}
+
+impl TemplateOverrides {
+    /// Creates an empty set of overrides.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Override the name of the given template instance.
+    pub fn with_name<S: Into<String>>(mut self, referent: Ref, name: S) -> Self {
+        self.set_name(referent, name);
+        self
+    }
+
+    /// Override the name of the given template instance.
+    pub fn set_name<S: Into<String>>(&mut self, referent: Ref, name: S) {
+        self.names.insert(referent, name.into());
+    }
+
+    /// Override a property of the given template instance.
+    pub fn with_property<K: Into<String>, V: Into<Variant>>(
+        mut self,
+        referent: Ref,
+        key: K,
+        value: V,
+    ) -> Self {
+        self.add_property(referent, key, value);
+        self
+    }
+
+    /// Override a property of the given template instance.
+    pub fn add_property<K: Into<String>, V: Into<Variant>>(
+        &mut self,
+        referent: Ref,
+        key: K,
+        value: V,
+    ) {
+        self.properties
+            .entry(referent)
+            .or_default()
+            .insert(key.into(), value.into());
+    }
+}
+
+impl WeakDom {
+    /// Inserts a new copy of the given [`Template`] into the DOM with the
+    /// given parent, applying `overrides`, and returns the referent of the new
+    /// copy's root.
+    ///
+    /// Every instance in the copy gets a new referent. `Ref` properties that
+    /// point to instances in the template, including ones given in
+    /// `overrides`, are rewritten to point to the matching instances in the
+    /// copy. Other `Ref` properties are left alone.
+    ///
+    /// ## Panics
+    /// Panics if `parent_ref` does not refer to an instance in the DOM.
+    pub fn instantiate(
+        &mut self,
+        template: &Template,
+        parent_ref: Ref,
+        overrides: &TemplateOverrides,
+    ) -> Ref {
+        let referents: HashMap<Ref, Ref> = template
+            .instances
+            .iter()
+            .map(|instance| (instance.referent, Ref::new()))
+            .collect();
+
+        self.insert(parent_ref, template.build(0, overrides, &referents))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::DomViewer;
+
+    #[test]
+    fn instantiate() {
+        let hinge = InstanceBuilder::new("HingeConstraint");
+        let hinge_ref = hinge.referent();
+
+        let door = InstanceBuilder::new("Model")
+            .with_name("Door")
+            .with_property("PrimaryPart", hinge_ref)
+            .with_child(hinge.with_property("AngularSpeed", 1.0f32));
+        let door_ref = door.referent();
+
+        let mut dom = WeakDom::new(InstanceBuilder::new("Folder").with_child(door));
+        let template = Template::new(&dom, door_ref);
+        dom.destroy(door_ref);
+
+        let root_ref = dom.root_ref();
+        dom.instantiate(&template, root_ref, &TemplateOverrides::new());
+        dom.instantiate(
+            &template,
+            root_ref,
+            &TemplateOverrides::new()
+                .with_name(door_ref, "FastDoor")
+                .with_property(hinge_ref, "AngularSpeed", 10.0f32),
+        );
+
+        // This snapshot should contain two doors, each with a PrimaryPart
+        // pointing to their own hinge. The second door should be named
+        // FastDoor and have a faster hinge.
+        insta::assert_yaml_snapshot!(DomViewer::new().view(&dom));
+    }
+
+    #[test]
+    fn new_referents() {
+        let mut dom = WeakDom::new(InstanceBuilder::new("Folder"));
+        let root_ref = dom.root_ref();
+        let template = Template::new(&dom, root_ref);
+
+        let copy = dom.instantiate(&template, root_ref, &TemplateOverrides::new());
+
+        assert_ne!(copy, root_ref);
+        assert_eq!(template.root_ref(), root_ref);
+        assert_eq!(template.len(), 1);
+    }
+}
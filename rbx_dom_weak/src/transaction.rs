@@ -0,0 +1,435 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use rbx_types::{Ref, Variant};
+
+use crate::{history::History, Instance, WeakDom};
+
+/// A change made to a [`WeakDom`] that knows how to undo itself.
+#[derive(Debug)]
+pub(crate) enum Change {
+    /// An instance and its descendants were added to the DOM. Reverting this
+    /// removes them.
+    Added { referent: Ref },
+
+    /// An instance and its descendants were removed from the DOM. Reverting
+    /// this puts them back at the same position in their parent.
+    Removed {
+        parent: Ref,
+        index: usize,
+        root: Ref,
+        instances: Vec<Instance>,
+    },
+
+    /// An instance was moved to a different parent. Reverting this moves it
+    /// back to the given parent and position.
+    Moved {
+        referent: Ref,
+        parent: Ref,
+        index: usize,
+    },
+
+    /// An instance had its children reordered. Reverting this restores the
+    /// given order.
+    Reordered { parent: Ref, children: Vec<Ref> },
+
+    /// An instance may have had its name, class, or properties changed.
+    /// Reverting this restores the given values.
+    Modified {
+        referent: Ref,
+        name: String,
+        class: String,
+        properties: HashMap<String, Variant>,
+    },
+}
+
+/// Records changes made to a [`WeakDom`] while a transaction is open or undo
+/// history is enabled.
+///
+/// Changes made inside of a transaction are kept here until the outermost
+/// transaction is committed, at which point they're handed to the undo history.
+#[derive(Debug, Default)]
+pub(crate) struct Journal {
+    changes: Vec<Change>,
+
+    /// For each open transaction, the length of `changes` when it began and
+    /// the instances whose contents have been recorded since then.
+    transactions: Vec<(usize, HashSet<Ref>)>,
+
+    pub(crate) history: Option<History>,
+}
+
+impl Journal {
+    pub fn is_recording(&self) -> bool {
+        !self.transactions.is_empty() || self.history.is_some()
+    }
+
+    pub fn in_transaction(&self) -> bool {
+        !self.transactions.is_empty()
+    }
+
+    pub fn record(&mut self, change: Change) {
+        if self.in_transaction() {
+            self.changes.push(change);
+        } else if let Some(history) = &mut self.history {
+            history.record(change);
+        }
+    }
+
+    /// Records the current contents of an instance that is about to be
+    /// handed out mutably. Each instance is only recorded once per
+    /// transaction, since only its contents at the start of the transaction
+    /// are needed to roll it back.
+    pub fn record_modified(&mut self, instance: &Instance) {
+        let modified = match self.transactions.last_mut() {
+            Some((_, modified)) => modified,
+            None => match &mut self.history {
+                Some(history) => history.modified_mut(),
+                None => return,
+            },
+        };
+
+        if modified.insert(instance.referent) {
+            self.record(Change::Modified {
+                referent: instance.referent,
+                name: instance.name.clone(),
+                class: instance.class.clone(),
+                properties: instance.properties.clone(),
+            });
+        }
+    }
+}
+
+impl WeakDom {
+    /// Begins a new transaction. Changes made to the DOM from now on can be
+    /// undone all at once with [`WeakDom::rollback_transaction`], or kept with
+    /// [`WeakDom::commit_transaction`].
+    ///
+    /// Only the instances that are changed during the transaction are copied,
+    /// so beginning a transaction is cheap even for large DOMs.
+    ///
+    /// Transactions can be nested. Rolling back an inner transaction only
+    /// undoes the changes made since it began, and committing it merges its
+    /// changes into the outer transaction.
+    ///
+    /// ## Example
+    /// ```
+    /// use rbx_dom_weak::{InstanceBuilder, WeakDom};
+    ///
+    /// let mut dom = WeakDom::new(InstanceBuilder::new("Folder"));
+    /// let root_ref = dom.root_ref();
+    ///
+    /// dom.begin_transaction();
+    /// dom.insert(root_ref, InstanceBuilder::new("Part"));
+    /// dom.root_mut().name = "Renamed".to_owned();
+    /// dom.rollback_transaction();
+    ///
+    /// assert!(dom.root().children().is_empty());
+    /// assert_eq!(dom.root().name, "Folder");
+    /// ```
+    pub fn begin_transaction(&mut self) {
+        let start = self.journal.changes.len();
+        self.journal.transactions.push((start, HashSet::new()));
+    }
+
+    /// Commits the innermost open transaction, keeping all of the changes made
+    /// since it began.
+    ///
+    /// If undo history is enabled, committing the outermost transaction adds
+    /// its changes to the current undo step.
+    ///
+    /// ## Panics
+    /// Panics if there is no open transaction.
+    pub fn commit_transaction(&mut self) {
+        let (_, modified) = self
+            .journal
+            .transactions
+            .pop()
+            .unwrap_or_else(|| panic!("cannot commit a transaction that was never begun"));
+
+        match self.journal.transactions.last_mut() {
+            Some((_, outer_modified)) => outer_modified.extend(modified),
+            None => {
+                let changes = std::mem::take(&mut self.journal.changes);
+
+                if let Some(history) = &mut self.journal.history {
+                    for change in changes {
+                        history.record(change);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Rolls back the innermost open transaction, undoing all of the changes
+    /// made since it began.
+    ///
+    /// ## Panics
+    /// Panics if there is no open transaction.
+    pub fn rollback_transaction(&mut self) {
+        let (start, _) = self
+            .journal
+            .transactions
+            .pop()
+            .unwrap_or_else(|| panic!("cannot roll back a transaction that was never begun"));
+
+        self.indices.flush(&self.instances);
+
+        while self.journal.changes.len() > start {
+            let change = self.journal.changes.pop().unwrap();
+            self.revert(change);
+        }
+    }
+
+    /// Returns whether there is an open transaction.
+    pub fn in_transaction(&self) -> bool {
+        self.journal.in_transaction()
+    }
+
+    /// Undoes a change, returning a change that would redo it.
+    pub(crate) fn revert(&mut self, change: Change) -> Change {
+        match change {
+            Change::Added { referent } => {
+                let parent_ref = self.instances[&referent].parent;
+                let parent = self.instances.get_mut(&parent_ref).unwrap();
+                let index = parent
+                    .children
+                    .iter()
+                    .position(|&child| child == referent)
+                    .unwrap();
+                parent.children.remove(index);
+
+                let mut instances = Vec::new();
+                let mut to_remove = VecDeque::new();
+                to_remove.push_back(referent);
+
+                while let Some(referent) = to_remove.pop_front() {
+                    let instance = self.instances.remove(&referent).unwrap();
+                    to_remove.extend(instance.children.iter().copied());
+                    self.indices.remove(referent);
+                    instances.push(instance);
+                }
+
+                Change::Removed {
+                    parent: parent_ref,
+                    index,
+                    root: referent,
+                    instances,
+                }
+            }
+            Change::Removed {
+                parent,
+                index,
+                root,
+                instances,
+            } => {
+                for instance in instances {
+                    self.indices.insert(&instance);
+                    self.instances.insert(instance.referent, instance);
+                }
+
+                let parent = self.instances.get_mut(&parent).unwrap();
+                let index = index.min(parent.children.len());
+                parent.children.insert(index, root);
+
+                Change::Added { referent: root }
+            }
+            Change::Moved {
+                referent,
+                parent,
+                index,
+            } => {
+                let instance = self.instances.get_mut(&referent).unwrap();
+                let old_parent_ref = std::mem::replace(&mut instance.parent, parent);
+
+                let old_parent = self.instances.get_mut(&old_parent_ref).unwrap();
+                let old_index = old_parent
+                    .children
+                    .iter()
+                    .position(|&child| child == referent)
+                    .unwrap();
+                old_parent.children.remove(old_index);
+
+                let parent = self.instances.get_mut(&parent).unwrap();
+                let index = index.min(parent.children.len());
+                parent.children.insert(index, referent);
+
+                Change::Moved {
+                    referent,
+                    parent: old_parent_ref,
+                    index: old_index,
+                }
+            }
+            Change::Reordered { parent, children } => {
+                let instance = self.instances.get_mut(&parent).unwrap();
+                let children = std::mem::replace(&mut instance.children, children);
+
+                Change::Reordered { parent, children }
+            }
+            Change::Modified {
+                referent,
+                name,
+                class,
+                properties,
+            } => {
+                let instance = self.instances.get_mut(&referent).unwrap();
+                let name = std::mem::replace(&mut instance.name, name);
+                let class = std::mem::replace(&mut instance.class, class);
+                let properties = std::mem::replace(&mut instance.properties, properties);
+                self.indices.insert(instance);
+
+                Change::Modified {
+                    referent,
+                    name,
+                    class,
+                    properties,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::InstanceBuilder;
+
+    fn build() -> WeakDom {
+        WeakDom::new(
+            InstanceBuilder::new("Folder")
+                .with_child(
+                    InstanceBuilder::new("Model")
+                        .with_name("A")
+                        .with_child(InstanceBuilder::new("Part").with_name("A1")),
+                )
+                .with_child(InstanceBuilder::new("Model").with_name("B"))
+                .with_child(InstanceBuilder::new("Model").with_name("C")),
+        )
+    }
+
+    #[test]
+    fn rollback() {
+        let mut dom = build();
+
+        let root_ref = dom.root_ref();
+        let a = dom.root().children()[0];
+        let b = dom.root().children()[1];
+        let c = dom.root().children()[2];
+
+        dom.begin_transaction();
+        assert!(dom.in_transaction());
+
+        dom.insert(b, InstanceBuilder::new("Part").with_name("B1"));
+        dom.destroy(a);
+        dom.transfer_within(c, b);
+        dom.get_by_ref_mut(b)
+            .unwrap()
+            .properties
+            .insert("Hello".to_owned(), true.into());
+        dom.root_mut().name = "Renamed".to_owned();
+        dom.insert(root_ref, InstanceBuilder::new("Folder"));
+
+        dom.rollback_transaction();
+        assert!(!dom.in_transaction());
+
+        assert!(dom.eq_structural(&build()));
+        assert_eq!(dom.root().children(), &[a, b, c]);
+    }
+
+    #[test]
+    fn detach_in_transaction() {
+        let mut dom = build();
+        let a = dom.root().children()[0];
+        let b = dom.root().children()[1];
+
+        dom.begin_transaction();
+        dom.get_by_ref_mut(a).unwrap().name = "Renamed".to_owned();
+        let detached = dom.detach(a);
+        dom.rollback_transaction();
+
+        assert!(dom.eq_structural(&build()));
+        assert_eq!(dom.root().children()[..2], [a, b]);
+
+        // The detached instances are left as they were when detached.
+        let mut other = WeakDom::new(InstanceBuilder::new("Folder"));
+        let other_root = other.root_ref();
+        other.attach(detached, other_root);
+        assert_eq!(other.get_by_ref(a).unwrap().name, "Renamed");
+    }
+
+    #[test]
+    fn transfer_in_transaction() {
+        let mut dom = build();
+        let a = dom.root().children()[0];
+        let mut other = WeakDom::new(InstanceBuilder::new("Folder"));
+        let other_root = other.root_ref();
+
+        dom.begin_transaction();
+        dom.transfer(a, &mut other, other_root);
+        assert!(dom.get_by_ref(a).is_none());
+        dom.rollback_transaction();
+
+        assert!(dom.eq_structural(&build()));
+        assert_eq!(dom.root().children()[0], a);
+        assert_eq!(other.root().children(), &[a]);
+    }
+
+    #[test]
+    fn commit() {
+        let mut dom = build();
+        let root_ref = dom.root_ref();
+
+        dom.begin_transaction();
+        dom.insert(root_ref, InstanceBuilder::new("Part"));
+        dom.commit_transaction();
+
+        assert!(!dom.in_transaction());
+        assert_eq!(dom.root().children().len(), 4);
+    }
+
+    #[test]
+    fn nested() {
+        let mut dom = build();
+        let root_ref = dom.root_ref();
+
+        dom.begin_transaction();
+        dom.root_mut().name = "Outer".to_owned();
+
+        dom.begin_transaction();
+        dom.root_mut().name = "Inner".to_owned();
+        dom.insert(root_ref, InstanceBuilder::new("Part"));
+        dom.rollback_transaction();
+
+        assert_eq!(dom.root().name, "Outer");
+        assert_eq!(dom.root().children().len(), 3);
+
+        dom.begin_transaction();
+        dom.root_mut().name = "Inner".to_owned();
+        dom.commit_transaction();
+
+        dom.rollback_transaction();
+        assert_eq!(dom.root().name, "Folder");
+    }
+
+    #[test]
+    fn rollback_keeps_indices() {
+        let mut dom = build();
+        dom.enable_name_index();
+        let a = dom.root().children()[0];
+
+        dom.begin_transaction();
+        dom.destroy(a);
+        dom.get_by_ref_mut(dom.root_ref()).unwrap().name = "A".to_owned();
+        assert_eq!(dom.find_all_named("A"), vec![dom.root_ref()]);
+        dom.rollback_transaction();
+
+        assert_eq!(dom.find_all_named("A"), vec![a]);
+        assert_eq!(dom.find_all_named("Folder"), vec![dom.root_ref()]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn commit_without_begin() {
+        build().commit_transaction();
+    }
+}
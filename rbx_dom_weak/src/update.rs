@@ -0,0 +1,152 @@
+use rbx_types::{Ref, Variant};
+
+use crate::{
+    error::{Error, InnerError},
+    WeakDom,
+};
+
+impl WeakDom {
+    /// Sets many properties on the instance with the given referent at once.
+    ///
+    /// All of the property names are checked before any of them are applied,
+    /// so either every property is set or none are. `Name` and `ClassName` are
+    /// not valid property names, since they are stored as fields on
+    /// [`Instance`][crate::Instance] instead.
+    ///
+    /// ## Panics
+    /// Panics if `referent` does not refer to an instance in the DOM.
+    pub fn update_properties<K, V, I>(&mut self, referent: Ref, properties: I) -> Result<(), Error>
+    where
+        K: Into<String>,
+        V: Into<Variant>,
+        I: IntoIterator<Item = (K, V)>,
+    {
+        let properties = collect_properties(properties)?;
+
+        let instance = self
+            .get_by_ref_mut(referent)
+            .unwrap_or_else(|| panic!("cannot update an instance that does not exist"));
+
+        instance.properties.reserve(properties.len());
+        instance.properties.extend(properties);
+
+        Ok(())
+    }
+
+    /// Sets the same properties on every instance in `referents` at once.
+    ///
+    /// Property names are only checked once, before any instances are changed.
+    /// See [`WeakDom::update_properties`] for which names are valid.
+    ///
+    /// ## Panics
+    /// Panics if any of `referents` do not refer to an instance in the DOM.
+    /// Instances visited before the missing one will have been updated.
+    pub fn update_properties_many<K, V, I, R>(
+        &mut self,
+        referents: R,
+        properties: I,
+    ) -> Result<(), Error>
+    where
+        K: Into<String>,
+        V: Into<Variant>,
+        I: IntoIterator<Item = (K, V)>,
+        R: IntoIterator<Item = Ref>,
+    {
+        let properties = collect_properties(properties)?;
+
+        for referent in referents {
+            let instance = self
+                .get_by_ref_mut(referent)
+                .unwrap_or_else(|| panic!("cannot update an instance that does not exist"));
+
+            instance.properties.reserve(properties.len());
+            instance.properties.extend(properties.iter().cloned());
+        }
+
+        Ok(())
+    }
+}
+
+fn collect_properties<K, V, I>(properties: I) -> Result<Vec<(String, Variant)>, Error>
+where
+    K: Into<String>,
+    V: Into<Variant>,
+    I: IntoIterator<Item = (K, V)>,
+{
+    properties
+        .into_iter()
+        .map(|(key, value)| {
+            let key = key.into();
+
+            match key.as_str() {
+                "" | "Name" | "ClassName" => {
+                    Err(InnerError::InvalidPropertyName { name: key }.into())
+                }
+                _ => Ok((key, value.into())),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::InstanceBuilder;
+
+    #[test]
+    fn update_properties() {
+        let mut dom = WeakDom::new(InstanceBuilder::new("Part").with_property("Anchored", false));
+        let root_ref = dom.root_ref();
+
+        dom.update_properties(root_ref, vec![("Anchored", true), ("CanCollide", false)])
+            .unwrap();
+
+        let properties = &dom.root().properties;
+        assert_eq!(properties.len(), 2);
+        assert_eq!(properties["Anchored"], Variant::Bool(true));
+        assert_eq!(properties["CanCollide"], Variant::Bool(false));
+    }
+
+    #[test]
+    fn invalid_names_are_rejected() {
+        let mut dom = WeakDom::new(InstanceBuilder::new("Part"));
+        let root_ref = dom.root_ref();
+
+        let result = dom.update_properties(
+            root_ref,
+            vec![
+                ("Anchored", Variant::Bool(true)),
+                ("Name", Variant::String("Oops".to_owned())),
+            ],
+        );
+
+        assert!(result.is_err());
+        assert!(dom.root().properties.is_empty());
+        assert_eq!(dom.root().name, "Part");
+    }
+
+    #[test]
+    fn update_properties_many() {
+        let mut dom = WeakDom::new(
+            InstanceBuilder::new("Model")
+                .with_child(InstanceBuilder::new("Part"))
+                .with_child(InstanceBuilder::new("Part"))
+                .with_child(InstanceBuilder::new("Part")),
+        );
+        let parts = dom.root().children()[..2].to_vec();
+
+        dom.update_properties_many(parts.iter().copied(), vec![("Anchored", true)])
+            .unwrap();
+
+        let children = dom.root().children().to_vec();
+        for (index, referent) in children.into_iter().enumerate() {
+            let instance = dom.get_by_ref(referent).unwrap();
+            assert_eq!(instance.properties.contains_key("Anchored"), index < 2);
+        }
+
+        assert!(dom
+            .update_properties_many(parts, vec![("ClassName", "Folder")])
+            .is_err());
+    }
+}
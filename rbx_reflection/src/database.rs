@@ -39,6 +39,34 @@ impl<'a> ReflectionDatabase<'a> {
             enums: HashMap::new(),
         }
     }
+
+    /// Returns an iterator over the given class descriptor followed by each of
+    /// its superclasses, ending with the root of the class hierarchy.
+    pub fn superclasses<'db>(
+        &'db self,
+        descriptor: &'db ClassDescriptor<'a>,
+    ) -> impl Iterator<Item = &'db ClassDescriptor<'a>> + 'db {
+        std::iter::successors(Some(descriptor), move |descriptor| {
+            let superclass = descriptor.superclass.as_ref()?;
+            self.classes.get(superclass.as_ref())
+        })
+    }
+
+    /// Finds the descriptor for the property with the given name on the given
+    /// class, searching through superclasses if needed.
+    ///
+    /// The descriptor returned may be an alias; no attempt is made to resolve
+    /// it to its canonical form.
+    pub fn find_property<'db>(
+        &'db self,
+        class_name: &str,
+        property_name: &str,
+    ) -> Option<&'db PropertyDescriptor<'a>> {
+        let descriptor = self.classes.get(class_name)?;
+
+        self.superclasses(descriptor)
+            .find_map(|class| class.properties.get(property_name))
+    }
 }
 
 /// Describes a class of Instance, its properties, and its relation to other
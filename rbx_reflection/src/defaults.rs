@@ -0,0 +1,283 @@
+use std::collections::{HashMap, HashSet};
+
+use rbx_types::Variant;
+
+use crate::{PropertyDescriptor, PropertyKind, PropertySerialization, ReflectionDatabase};
+
+/// The float tolerance used by [`StripDefaults`] unless configured otherwise.
+pub use rbx_types::DEFAULT_FLOAT_TOLERANCE;
+
+/// Removes properties whose values are equal to the default value given by a
+/// reflection database. This is the inverse of filling in default values, and
+/// is useful for minimizing file size and diff noise.
+///
+/// Properties that are unknown to the database, or that have no known default
+/// value, are never removed. Properties whose value would be repopulated by a
+/// migration from another property present on the same instance are also kept,
+/// since stripping them would change the meaning of the instance.
+///
+/// ## Example
+/// ```
+/// use std::collections::HashMap;
+///
+/// use rbx_reflection::{ReflectionDatabase, StripDefaults};
+/// use rbx_types::Variant;
+///
+/// let database = ReflectionDatabase::new();
+/// let mut properties: HashMap<String, Variant> = HashMap::new();
+///
+/// let removed = StripDefaults::new(&database)
+///     .float_tolerance(1.0e-4)
+///     .strip("Part", &mut properties);
+///
+/// assert_eq!(removed, 0);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct StripDefaults<'db, 'a> {
+    database: &'db ReflectionDatabase<'a>,
+    float_tolerance: f32,
+}
+
+impl<'db, 'a> StripDefaults<'db, 'a> {
+    /// Creates a new `StripDefaults` pass that uses the given reflection
+    /// database.
+    pub fn new(database: &'db ReflectionDatabase<'a>) -> Self {
+        Self {
+            database,
+            float_tolerance: DEFAULT_FLOAT_TOLERANCE,
+        }
+    }
+
+    /// Sets the tolerance used when comparing floating point components of
+    /// values against their defaults. See [`Variant::approx_eq`].
+    pub fn float_tolerance(self, float_tolerance: f32) -> Self {
+        Self {
+            float_tolerance,
+            ..self
+        }
+    }
+
+    /// Returns whether the given property set on an instance of the given
+    /// class is equal to that property's default value.
+    pub fn is_default(&self, class_name: &str, property_name: &str, value: &Variant) -> bool {
+        let class = match self.database.classes.get(class_name) {
+            Some(class) => class,
+            None => return false,
+        };
+
+        let canonical_name = match self.canonical_descriptor(class_name, property_name) {
+            Some(descriptor) => descriptor.name.as_ref(),
+            None => return false,
+        };
+
+        match class.default_properties.get(canonical_name) {
+            Some(default) => self.value_eq(value, default),
+            None => false,
+        }
+    }
+
+    /// Removes all of the properties from `properties` that are equal to their
+    /// defaults for the given class, returning the number of properties that
+    /// were removed.
+    pub fn strip(&self, class_name: &str, properties: &mut HashMap<String, Variant>) -> usize {
+        if !self.database.classes.contains_key(class_name) {
+            return 0;
+        }
+
+        // Any property that is the target of a migration from another property
+        // on this instance must be kept. Deserializers only run a migration
+        // when the target property is absent, so removing it would cause the
+        // legacy value to be migrated over it.
+        let mut migration_targets = HashSet::new();
+        for property_name in properties.keys() {
+            if let Some(PropertyDescriptor {
+                kind:
+                    PropertyKind::Canonical {
+                        serialization: PropertySerialization::Migrate { property, .. },
+                    },
+                ..
+            }) = self.canonical_descriptor(class_name, property_name)
+            {
+                migration_targets.insert(property.as_ref());
+            }
+        }
+
+        let to_remove: Vec<String> = properties
+            .iter()
+            .filter(|(name, value)| {
+                let canonical_name = match self.canonical_descriptor(class_name, name) {
+                    Some(descriptor) => descriptor.name.as_ref(),
+                    None => return false,
+                };
+
+                !migration_targets.contains(canonical_name)
+                    && self.is_default(class_name, name, value)
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        for name in &to_remove {
+            properties.remove(name);
+        }
+
+        to_remove.len()
+    }
+
+    /// Finds the canonical descriptor for a property, resolving aliases like
+    /// `BasePart.size` to `BasePart.Size`.
+    fn canonical_descriptor(
+        &self,
+        class_name: &str,
+        property_name: &str,
+    ) -> Option<&'db PropertyDescriptor<'a>> {
+        let descriptor = self.database.find_property(class_name, property_name)?;
+
+        match &descriptor.kind {
+            PropertyKind::Alias { alias_for } => {
+                self.database.find_property(class_name, alias_for.as_ref())
+            }
+            _ => Some(descriptor),
+        }
+    }
+
+    fn value_eq(&self, value: &Variant, default: &Variant) -> bool {
+        match (value, default) {
+            // The cached face ID of a Font is populated by Roblox as a side
+            // effect of loading the font, so it doesn't carry any meaning.
+            (Variant::Font(value), Variant::Font(default)) => {
+                value.family == default.family
+                    && value.weight == default.weight
+                    && value.style == default.style
+            }
+            _ => value.approx_eq(default, self.float_tolerance),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rbx_types::{Enum, Font, VariantType, Vector3};
+
+    use crate::{ClassDescriptor, DataType, PropertyMigration};
+
+    fn database() -> ReflectionDatabase<'static> {
+        let mut instance = ClassDescriptor::new("Instance");
+        instance.properties.insert(
+            "Archivable".into(),
+            PropertyDescriptor::new("Archivable", DataType::Value(VariantType::Bool)),
+        );
+
+        let mut label = ClassDescriptor::new("TextLabel");
+        label.superclass = Some("Instance".into());
+
+        let mut size = PropertyDescriptor::new("Size", DataType::Value(VariantType::Vector3));
+        size.kind = PropertyKind::Canonical {
+            serialization: PropertySerialization::SerializesAs("size".into()),
+        };
+        label.properties.insert("Size".into(), size);
+
+        let mut size_alias = PropertyDescriptor::new("size", DataType::Value(VariantType::Vector3));
+        size_alias.kind = PropertyKind::Alias {
+            alias_for: "Size".into(),
+        };
+        label.properties.insert("size".into(), size_alias);
+
+        let mut font = PropertyDescriptor::new("Font", DataType::Enum("Font".into()));
+        font.kind = PropertyKind::Canonical {
+            serialization: PropertySerialization::Migrate {
+                property: "FontFace".into(),
+                migration: PropertyMigration::FontToFontFace,
+            },
+        };
+        label.properties.insert("Font".into(), font);
+        label.properties.insert(
+            "FontFace".into(),
+            PropertyDescriptor::new("FontFace", DataType::Value(VariantType::Font)),
+        );
+
+        label
+            .default_properties
+            .insert("Archivable".into(), Variant::Bool(true));
+        label
+            .default_properties
+            .insert("Size".into(), Vector3::new(1.0, 2.0, 3.0).into());
+        label
+            .default_properties
+            .insert("FontFace".into(), Font::default().into());
+
+        let mut database = ReflectionDatabase::new();
+        database.classes.insert("Instance".into(), instance);
+        database.classes.insert("TextLabel".into(), label);
+        database
+    }
+
+    #[test]
+    fn strips_defaults_with_tolerance() {
+        let database = database();
+
+        let mut properties = HashMap::new();
+        properties.insert("Archivable".to_owned(), Variant::Bool(true));
+        properties.insert("Size".to_owned(), Vector3::new(1.0, 2.000001, 3.0).into());
+        properties.insert("Unknown".to_owned(), Variant::Bool(true));
+
+        let removed = StripDefaults::new(&database).strip("TextLabel", &mut properties);
+
+        assert_eq!(removed, 2);
+        assert_eq!(properties.len(), 1);
+        assert!(properties.contains_key("Unknown"));
+    }
+
+    #[test]
+    fn keeps_non_defaults() {
+        let database = database();
+
+        let mut properties = HashMap::new();
+        properties.insert("Archivable".to_owned(), Variant::Bool(false));
+        properties.insert("size".to_owned(), Vector3::new(1.0, 2.1, 3.0).into());
+
+        let removed = StripDefaults::new(&database).strip("TextLabel", &mut properties);
+
+        assert_eq!(removed, 0);
+    }
+
+    #[test]
+    fn resolves_aliases() {
+        let database = database();
+
+        let mut properties = HashMap::new();
+        properties.insert("size".to_owned(), Vector3::new(1.0, 2.0, 3.0).into());
+
+        let removed = StripDefaults::new(&database).strip("TextLabel", &mut properties);
+
+        assert_eq!(removed, 1);
+        assert!(properties.is_empty());
+    }
+
+    #[test]
+    fn keeps_migration_targets() {
+        let database = database();
+
+        let mut properties = HashMap::new();
+        properties.insert("Font".to_owned(), Enum::from_u32(3).into());
+        properties.insert("FontFace".to_owned(), Font::default().into());
+
+        let removed = StripDefaults::new(&database).strip("TextLabel", &mut properties);
+
+        assert_eq!(removed, 0);
+        assert_eq!(properties.len(), 2);
+    }
+
+    #[test]
+    fn unknown_class() {
+        let database = database();
+
+        let mut properties = HashMap::new();
+        properties.insert("Archivable".to_owned(), Variant::Bool(true));
+
+        let removed = StripDefaults::new(&database).strip("Part", &mut properties);
+
+        assert_eq!(removed, 0);
+    }
+}
@@ -1,10 +1,12 @@
 mod class_tag;
 mod database;
+mod defaults;
 mod migration;
 mod property_tag;
 mod serde_util;
 
 pub use class_tag::*;
 pub use database::*;
+pub use defaults::*;
 pub use migration::*;
 pub use property_tag::*;
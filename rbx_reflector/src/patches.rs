@@ -2,16 +2,19 @@ use std::{borrow::Cow, collections::HashMap, fs, path::Path};
 
 use anyhow::{anyhow, bail, Context};
 use rbx_reflection::{
-    DataType, PropertyKind, PropertySerialization, ReflectionDatabase, Scriptability,
+    DataType, PropertyDescriptor, PropertyKind, PropertySerialization, ReflectionDatabase,
+    Scriptability,
 };
 use serde::Deserialize;
 
 pub struct Patches {
+    add: HashMap<String, HashMap<String, PropertyAdd>>,
     change: HashMap<String, HashMap<String, PropertyChange>>,
 }
 
 impl Patches {
     pub fn load(dir: &Path) -> anyhow::Result<Self> {
+        let mut add = HashMap::new();
         let mut change = HashMap::new();
 
         for entry in fs::read_dir(dir)? {
@@ -20,13 +23,47 @@ impl Patches {
             let patch: Patch = serde_yaml::from_str(&contents)
                 .with_context(|| format!("Error parsing patch file {}", entry.path().display()))?;
 
+            add.extend(patch.add);
             change.extend(patch.change);
         }
 
-        Ok(Self { change })
+        Ok(Self { add, change })
     }
 
     pub fn apply(self, database: &mut ReflectionDatabase) -> anyhow::Result<()> {
+        for (class_name, class_adds) in &self.add {
+            let class = database
+                .classes
+                .get_mut(class_name.as_str())
+                .ok_or_else(|| {
+                    anyhow!(
+                        "Class {} modified in patch file does not exist in database",
+                        class_name
+                    )
+                })?;
+
+            for (property_name, property_add) in class_adds {
+                if class.properties.contains_key(property_name.as_str()) {
+                    bail!(
+                        "Property {}.{} added in patch file already exists in database",
+                        class_name,
+                        property_name
+                    );
+                }
+
+                let mut property =
+                    PropertyDescriptor::new(property_name.clone(), property_add.data_type.clone());
+                property.scriptability = property_add.scriptability;
+                property.kind = PropertyKind::Canonical {
+                    serialization: property_add.serialization.clone().into(),
+                };
+
+                class
+                    .properties
+                    .insert(Cow::Owned(property_name.clone()), property);
+            }
+        }
+
         for (class_name, class_changes) in &self.change {
             let class = database
                 .classes
@@ -94,10 +131,20 @@ impl Patches {
 #[derive(Deserialize)]
 #[serde(rename_all = "PascalCase", deny_unknown_fields)]
 struct Patch {
+    #[serde(default)]
+    add: HashMap<String, HashMap<String, PropertyAdd>>,
     #[serde(default)]
     change: HashMap<String, HashMap<String, PropertyChange>>,
 }
 
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase", deny_unknown_fields)]
+struct PropertyAdd {
+    data_type: DataType<'static>,
+    serialization: Serialization,
+    scriptability: Scriptability,
+}
+
 #[derive(Deserialize)]
 #[serde(rename_all = "PascalCase", deny_unknown_fields)]
 struct PropertyChange {
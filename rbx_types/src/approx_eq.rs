@@ -0,0 +1,185 @@
+use crate::{
+    CFrame, Color3, ColorSequenceKeypoint, Matrix3, NumberSequenceKeypoint, PhysicalProperties,
+    Rect, UDim, UDim2, Variant, Vector2, Vector3,
+};
+
+/// The float tolerance that values are compared with when no other tolerance
+/// is asked for, such as when diffing instances or stripping default values.
+/// See [`Variant::approx_eq`].
+pub const DEFAULT_FLOAT_TOLERANCE: f32 = 1.0e-5;
+
+impl Variant {
+    /// Compares two values for equality, treating any floating point
+    /// components that differ by no more than `epsilon` as equal.
+    ///
+    /// The tolerance is relative for components larger than `1.0` in
+    /// magnitude and absolute otherwise. Two NaN components are considered
+    /// equal. Values of differing types are never equal.
+    pub fn approx_eq(&self, other: &Variant, epsilon: f32) -> bool {
+        let f = |a: f32, b: f32| float_eq(a, b, epsilon);
+
+        match (self, other) {
+            (Variant::Float32(a), Variant::Float32(b)) => f(*a, *b),
+            (Variant::Float64(a), Variant::Float64(b)) => double_eq(*a, *b, epsilon as f64),
+            (Variant::Vector2(a), Variant::Vector2(b)) => vector2_eq(a, b, epsilon),
+            (Variant::Vector3(a), Variant::Vector3(b)) => vector3_eq(a, b, epsilon),
+            (Variant::CFrame(a), Variant::CFrame(b)) => cframe_eq(a, b, epsilon),
+            (Variant::OptionalCFrame(a), Variant::OptionalCFrame(b)) => match (a, b) {
+                (Some(a), Some(b)) => cframe_eq(a, b, epsilon),
+                (None, None) => true,
+                _ => false,
+            },
+            (Variant::Color3(a), Variant::Color3(b)) => color3_eq(a, b, epsilon),
+            (Variant::UDim(a), Variant::UDim(b)) => udim_eq(a, b, epsilon),
+            (Variant::UDim2(a), Variant::UDim2(b)) => udim2_eq(a, b, epsilon),
+            (Variant::Rect(a), Variant::Rect(b)) => rect_eq(a, b, epsilon),
+            (Variant::NumberRange(a), Variant::NumberRange(b)) => {
+                f(a.min, b.min) && f(a.max, b.max)
+            }
+            (Variant::Ray(a), Variant::Ray(b)) => {
+                vector3_eq(&a.origin, &b.origin, epsilon)
+                    && vector3_eq(&a.direction, &b.direction, epsilon)
+            }
+            (Variant::Region3(a), Variant::Region3(b)) => {
+                vector3_eq(&a.min, &b.min, epsilon) && vector3_eq(&a.max, &b.max, epsilon)
+            }
+            (Variant::NumberSequence(a), Variant::NumberSequence(b)) => {
+                a.keypoints.len() == b.keypoints.len()
+                    && a.keypoints
+                        .iter()
+                        .zip(&b.keypoints)
+                        .all(|(a, b)| number_keypoint_eq(a, b, epsilon))
+            }
+            (Variant::ColorSequence(a), Variant::ColorSequence(b)) => {
+                a.keypoints.len() == b.keypoints.len()
+                    && a.keypoints
+                        .iter()
+                        .zip(&b.keypoints)
+                        .all(|(a, b)| color_keypoint_eq(a, b, epsilon))
+            }
+            (Variant::PhysicalProperties(a), Variant::PhysicalProperties(b)) => match (a, b) {
+                (PhysicalProperties::Custom(a), PhysicalProperties::Custom(b)) => {
+                    f(a.density, b.density)
+                        && f(a.friction, b.friction)
+                        && f(a.elasticity, b.elasticity)
+                        && f(a.friction_weight, b.friction_weight)
+                        && f(a.elasticity_weight, b.elasticity_weight)
+                }
+                (PhysicalProperties::Default, PhysicalProperties::Default) => true,
+                _ => false,
+            },
+            (a, b) => a == b,
+        }
+    }
+}
+
+fn float_eq(a: f32, b: f32, epsilon: f32) -> bool {
+    if a.is_nan() || b.is_nan() {
+        return a.is_nan() && b.is_nan();
+    }
+
+    if a == b {
+        return true;
+    }
+
+    let scale = a.abs().max(b.abs()).max(1.0);
+    (a - b).abs() <= epsilon * scale
+}
+
+fn double_eq(a: f64, b: f64, epsilon: f64) -> bool {
+    if a.is_nan() || b.is_nan() {
+        return a.is_nan() && b.is_nan();
+    }
+
+    if a == b {
+        return true;
+    }
+
+    let scale = a.abs().max(b.abs()).max(1.0);
+    (a - b).abs() <= epsilon * scale
+}
+
+fn vector2_eq(a: &Vector2, b: &Vector2, epsilon: f32) -> bool {
+    float_eq(a.x, b.x, epsilon) && float_eq(a.y, b.y, epsilon)
+}
+
+fn vector3_eq(a: &Vector3, b: &Vector3, epsilon: f32) -> bool {
+    float_eq(a.x, b.x, epsilon) && float_eq(a.y, b.y, epsilon) && float_eq(a.z, b.z, epsilon)
+}
+
+fn matrix3_eq(a: &Matrix3, b: &Matrix3, epsilon: f32) -> bool {
+    vector3_eq(&a.x, &b.x, epsilon)
+        && vector3_eq(&a.y, &b.y, epsilon)
+        && vector3_eq(&a.z, &b.z, epsilon)
+}
+
+fn cframe_eq(a: &CFrame, b: &CFrame, epsilon: f32) -> bool {
+    vector3_eq(&a.position, &b.position, epsilon)
+        && matrix3_eq(&a.orientation, &b.orientation, epsilon)
+}
+
+fn color3_eq(a: &Color3, b: &Color3, epsilon: f32) -> bool {
+    float_eq(a.r, b.r, epsilon) && float_eq(a.g, b.g, epsilon) && float_eq(a.b, b.b, epsilon)
+}
+
+fn udim_eq(a: &UDim, b: &UDim, epsilon: f32) -> bool {
+    float_eq(a.scale, b.scale, epsilon) && a.offset == b.offset
+}
+
+fn udim2_eq(a: &UDim2, b: &UDim2, epsilon: f32) -> bool {
+    udim_eq(&a.x, &b.x, epsilon) && udim_eq(&a.y, &b.y, epsilon)
+}
+
+fn rect_eq(a: &Rect, b: &Rect, epsilon: f32) -> bool {
+    vector2_eq(&a.min, &b.min, epsilon) && vector2_eq(&a.max, &b.max, epsilon)
+}
+
+fn number_keypoint_eq(
+    a: &NumberSequenceKeypoint,
+    b: &NumberSequenceKeypoint,
+    epsilon: f32,
+) -> bool {
+    float_eq(a.time, b.time, epsilon)
+        && float_eq(a.value, b.value, epsilon)
+        && float_eq(a.envelope, b.envelope, epsilon)
+}
+
+fn color_keypoint_eq(a: &ColorSequenceKeypoint, b: &ColorSequenceKeypoint, epsilon: f32) -> bool {
+    float_eq(a.time, b.time, epsilon) && color3_eq(&a.color, &b.color, epsilon)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn floats_within_tolerance() {
+        let a = Variant::Vector3(Vector3::new(1.0, 2.0, 3.0));
+        let b = Variant::Vector3(Vector3::new(1.0, 2.000001, 3.0));
+        let c = Variant::Vector3(Vector3::new(1.0, 2.1, 3.0));
+
+        assert!(a.approx_eq(&b, 1.0e-5));
+        assert!(!a.approx_eq(&c, 1.0e-5));
+        assert!(!a.approx_eq(&b, 0.0));
+    }
+
+    #[test]
+    fn tolerance_is_relative_for_large_values() {
+        let a = Variant::Float32(100_000.0);
+        let b = Variant::Float32(100_000.5);
+
+        assert!(a.approx_eq(&b, 1.0e-5));
+    }
+
+    #[test]
+    fn nan_equals_nan() {
+        assert!(Variant::Float64(f64::NAN).approx_eq(&Variant::Float64(f64::NAN), 0.0));
+        assert!(!Variant::Float64(f64::NAN).approx_eq(&Variant::Float64(0.0), 1.0));
+    }
+
+    #[test]
+    fn mismatched_types() {
+        assert!(!Variant::Float32(1.0).approx_eq(&Variant::Float64(1.0), 1.0));
+        assert!(Variant::Bool(true).approx_eq(&Variant::Bool(true), 0.0));
+    }
+}
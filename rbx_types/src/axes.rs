@@ -58,6 +58,14 @@ impl Axes {
         AxisFlags::from_bits(bits).map(|flags| Self { flags })
     }
 
+    /// Like `from_bits`, but bits that don't stand for any of the axes are
+    /// ignored instead of being rejected.
+    pub const fn from_bits_truncate(bits: u8) -> Self {
+        Self {
+            flags: AxisFlags::from_bits_truncate(bits),
+        }
+    }
+
     #[cfg(feature = "serde")]
     fn len(self) -> usize {
         self.bits().count_ones() as usize
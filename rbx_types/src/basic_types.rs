@@ -1,3 +1,5 @@
+use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
 /// Represents any Roblox enum value.
 ///
 /// Roblox enums are not strongly typed, so the meaning of a value depends on
@@ -25,6 +27,205 @@ impl Enum {
     }
 }
 
+/// Implements the arithmetic operators and the methods shared by the float
+/// vector types. Like in Roblox, multiplying or dividing two vectors does it
+/// component by component.
+macro_rules! float_vector_ops {
+    ($type: ident { $( $field: ident ),+ }) => {
+        impl $type {
+            /// Returns the dot product of this vector and `rhs`.
+            pub fn dot(self, rhs: $type) -> f32 {
+                0.0 $( + self.$field * rhs.$field )+
+            }
+
+            /// Returns the length of this vector.
+            pub fn magnitude(self) -> f32 {
+                self.dot(self).sqrt()
+            }
+
+            /// Returns this vector scaled to a length of one. Like in Roblox,
+            /// the components of the unit vector of a vector with no length
+            /// are NaN.
+            pub fn unit(self) -> $type {
+                self / self.magnitude()
+            }
+
+            /// Returns the vector `alpha` of the way from this vector to
+            /// `goal`, where `0.0` is this vector and `1.0` is `goal`.
+            pub fn lerp(self, goal: $type, alpha: f32) -> $type {
+                self + (goal - self) * alpha
+            }
+        }
+
+        impl Add for $type {
+            type Output = $type;
+
+            fn add(self, rhs: $type) -> $type {
+                $type { $( $field: self.$field + rhs.$field ),+ }
+            }
+        }
+
+        impl Sub for $type {
+            type Output = $type;
+
+            fn sub(self, rhs: $type) -> $type {
+                $type { $( $field: self.$field - rhs.$field ),+ }
+            }
+        }
+
+        impl Mul for $type {
+            type Output = $type;
+
+            fn mul(self, rhs: $type) -> $type {
+                $type { $( $field: self.$field * rhs.$field ),+ }
+            }
+        }
+
+        impl Mul<f32> for $type {
+            type Output = $type;
+
+            fn mul(self, rhs: f32) -> $type {
+                $type { $( $field: self.$field * rhs ),+ }
+            }
+        }
+
+        impl Mul<$type> for f32 {
+            type Output = $type;
+
+            fn mul(self, rhs: $type) -> $type {
+                rhs * self
+            }
+        }
+
+        impl Div for $type {
+            type Output = $type;
+
+            fn div(self, rhs: $type) -> $type {
+                $type { $( $field: self.$field / rhs.$field ),+ }
+            }
+        }
+
+        impl Div<f32> for $type {
+            type Output = $type;
+
+            fn div(self, rhs: f32) -> $type {
+                $type { $( $field: self.$field / rhs ),+ }
+            }
+        }
+
+        impl Neg for $type {
+            type Output = $type;
+
+            fn neg(self) -> $type {
+                $type { $( $field: -self.$field ),+ }
+            }
+        }
+
+        impl AddAssign for $type {
+            fn add_assign(&mut self, rhs: $type) {
+                *self = *self + rhs;
+            }
+        }
+
+        impl SubAssign for $type {
+            fn sub_assign(&mut self, rhs: $type) {
+                *self = *self - rhs;
+            }
+        }
+
+        impl MulAssign<f32> for $type {
+            fn mul_assign(&mut self, rhs: f32) {
+                *self = *self * rhs;
+            }
+        }
+
+        impl DivAssign<f32> for $type {
+            fn div_assign(&mut self, rhs: f32) {
+                *self = *self / rhs;
+            }
+        }
+    };
+}
+
+/// Implements checked arithmetic, conversions to and from `$float`, and the
+/// ordering helpers for the 16-bit integer vector types.
+macro_rules! int16_vector_ops {
+    ($type: ident, $float: ident { $( $field: ident ),+ }) => {
+        impl $type {
+            /// Adds two vectors, returning `None` if any component overflows.
+            pub fn checked_add(self, rhs: $type) -> Option<$type> {
+                Some($type { $( $field: self.$field.checked_add(rhs.$field)? ),+ })
+            }
+
+            /// Subtracts `rhs` from this vector, returning `None` if any
+            /// component overflows.
+            pub fn checked_sub(self, rhs: $type) -> Option<$type> {
+                Some($type { $( $field: self.$field.checked_sub(rhs.$field)? ),+ })
+            }
+
+            /// Multiplies two vectors component by component, returning `None`
+            /// if any component overflows.
+            pub fn checked_mul(self, rhs: $type) -> Option<$type> {
+                Some($type { $( $field: self.$field.checked_mul(rhs.$field)? ),+ })
+            }
+
+            /// Multiplies every component of this vector by `rhs`, returning
+            /// `None` if any component overflows.
+            pub fn checked_scale(self, rhs: i16) -> Option<$type> {
+                Some($type { $( $field: self.$field.checked_mul(rhs)? ),+ })
+            }
+
+            /// Divides this vector by `rhs` component by component, rounding
+            /// towards zero. Returns `None` if any component of `rhs` is zero
+            /// or the division overflows.
+            pub fn checked_div(self, rhs: $type) -> Option<$type> {
+                Some($type { $( $field: self.$field.checked_div(rhs.$field)? ),+ })
+            }
+
+            /// Negates this vector, returning `None` if any component is
+            /// `i16::MIN`.
+            pub fn checked_neg(self) -> Option<$type> {
+                Some($type { $( $field: self.$field.checked_neg()? ),+ })
+            }
+
+            /// Returns a vector made of the smaller of each component of this
+            /// vector and `other`.
+            pub fn component_min(self, other: $type) -> $type {
+                $type { $( $field: self.$field.min(other.$field) ),+ }
+            }
+
+            /// Returns a vector made of the larger of each component of this
+            /// vector and `other`.
+            pub fn component_max(self, other: $type) -> $type {
+                $type { $( $field: self.$field.max(other.$field) ),+ }
+            }
+
+            /// Converts a float vector into this type, rounding each component
+            /// towards zero. Returns `None` if any component is NaN or doesn't
+            /// fit in an `i16` once it's rounded.
+            pub fn checked_from(vector: $float) -> Option<$type> {
+                let convert = |value: f32| {
+                    let value = value.trunc();
+
+                    if value >= i16::MIN as f32 && value <= i16::MAX as f32 {
+                        Some(value as i16)
+                    } else {
+                        None
+                    }
+                };
+
+                Some($type { $( $field: convert(vector.$field)? ),+ })
+            }
+        }
+
+        impl From<$type> for $float {
+            fn from(vector: $type) -> $float {
+                $float { $( $field: vector.$field as f32 ),+ }
+            }
+        }
+    };
+}
+
 /// The standard 2D vector type used in Roblox.
 ///
 /// ## See Also
@@ -40,17 +241,28 @@ impl Vector2 {
     pub fn new(x: f32, y: f32) -> Self {
         Self { x, y }
     }
+
+    /// Returns the Z component of the cross product of this vector and `rhs`
+    /// as 3D vectors, like Roblox's `Vector2:Cross`. It's positive when `rhs`
+    /// is counterclockwise from this vector.
+    pub fn cross(self, rhs: Vector2) -> f32 {
+        self.x * rhs.y - self.y * rhs.x
+    }
 }
 
+float_vector_ops!(Vector2 { x, y });
+
 /// A version of [`Vector2`][Vector2] whose coordinates are signed 16-bit
 /// integers.
 ///
+/// Vectors are ordered by their `x` components, then their `y` components.
+///
 /// ## See Also
 /// * [`Vector2`][Vector2], which is used for most values.
 /// * [Vector2int16 on Roblox Developer Hub](https://developer.roblox.com/en-us/api-reference/datatype/Vector2int16)
 ///
 /// [Vector2]: struct.Vector2.html
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Vector2int16 {
     pub x: i16,
     pub y: i16,
@@ -62,6 +274,8 @@ impl Vector2int16 {
     }
 }
 
+int16_vector_ops!(Vector2int16, Vector2 { x, y });
+
 /// The standard 3D vector type used in Roblox.
 ///
 /// ## See Also
@@ -77,7 +291,7 @@ pub struct Vector3 {
 fn approx_unit_or_zero(value: f32) -> Option<i32> {
     if value.abs() <= std::f32::EPSILON {
         Some(0)
-    } else if value.abs() - 1.0 <= std::f32::EPSILON {
+    } else if (value.abs() - 1.0).abs() <= std::f32::EPSILON {
         Some(1.0f32.copysign(value) as i32)
     } else {
         None
@@ -89,6 +303,16 @@ impl Vector3 {
         Self { x, y, z }
     }
 
+    /// Returns the cross product of this vector and `rhs`, which is
+    /// perpendicular to both of them.
+    pub fn cross(self, rhs: Vector3) -> Vector3 {
+        Vector3::new(
+            self.y * rhs.z - self.z * rhs.y,
+            self.z * rhs.x - self.x * rhs.z,
+            self.x * rhs.y - self.y * rhs.x,
+        )
+    }
+
     /// If the vector is a positive or negative basis vector, returns
     /// its corresponding ID. Otherwise, returns None.
     /// The mapping goes like this:
@@ -123,15 +347,20 @@ impl Vector3 {
     }
 }
 
+float_vector_ops!(Vector3 { x, y, z });
+
 /// A version of [`Vector3`][Vector3] whose coordinates are signed 16-bit
 /// integers. `Vector3int16` is often used when working with Terrain.
 ///
+/// Vectors are ordered by their `x` components, then their `y` components,
+/// then their `z` components.
+///
 /// ## See Also
 /// * [`Vector3`][Vector3], which is used for most values.
 /// * [Vector3int16 on Roblox Developer Hub](https://developer.roblox.com/en-us/api-reference/datatype/Vector3int16)
 ///
 /// [Vector3]: struct.Vector3.html
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Vector3int16 {
     pub x: i16,
     pub y: i16,
@@ -144,6 +373,8 @@ impl Vector3int16 {
     }
 }
 
+int16_vector_ops!(Vector3int16, Vector3 { x, y, z });
+
 /// Represents a position and orientation in 3D space.
 ///
 /// ## See Also
@@ -166,6 +397,162 @@ impl CFrame {
             orientation,
         }
     }
+
+    /// Creates a `CFrame` at the origin that's rotated by `angle` radians
+    /// around `axis`, like Roblox's `CFrame.fromAxisAngle`.
+    pub fn from_axis_angle(axis: Vector3, angle: f32) -> Self {
+        Self::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            Matrix3::from_axis_angle(axis, angle),
+        )
+    }
+
+    /// Returns the axis and angle in radians of this `CFrame`'s rotation, like
+    /// Roblox's `CFrame:ToAxisAngle`. See [`Matrix3::to_axis_angle`].
+    pub fn to_axis_angle(&self) -> (Vector3, f32) {
+        self.orientation.to_axis_angle()
+    }
+
+    /// Creates a `CFrame` at `position` that faces towards `target`, with its
+    /// top facing as close to `up` as it can, like Roblox's `CFrame.lookAt`.
+    /// Roblox uses `(0, 1, 0)` for `up` by default.
+    ///
+    /// If `target` is straight along `up` from `position`, the `CFrame` faces
+    /// it with its top facing whichever of the X, Y, and Z axes is furthest
+    /// from that direction, preferring X. This matches Roblox when `up` is
+    /// `(0, 1, 0)`, where the top faces `(1, 0, 0)`. If `target` is the same as
+    /// `position`, the `CFrame` isn't rotated at all.
+    pub fn look_at(position: Vector3, target: Vector3, up: Vector3) -> Self {
+        let back = position - target;
+        if back.magnitude() == 0.0 {
+            return Self::new(position, Matrix3::identity());
+        }
+
+        let back = back.unit();
+        let right = up.cross(back);
+
+        let right = if right.magnitude() > 0.0 {
+            right.unit()
+        } else {
+            // `up` is parallel to the direction we're facing, so use the axis
+            // that's closest to perpendicular to it instead.
+            let axis = [
+                Vector3::new(1.0, 0.0, 0.0),
+                Vector3::new(0.0, 1.0, 0.0),
+                Vector3::new(0.0, 0.0, 1.0),
+            ]
+            .iter()
+            .copied()
+            .min_by(|a, b| a.dot(back).abs().total_cmp(&b.dot(back).abs()))
+            .unwrap();
+
+            axis.cross(back).unit()
+        };
+        let top = back.cross(right);
+
+        // The axes of the `CFrame` are the columns of its orientation.
+        Self::new(position, Matrix3::new(right, top, back).transpose())
+    }
+
+    /// Creates a `CFrame` at the origin that's rotated by `rx`, `ry`, and `rz`
+    /// radians around the X, Y, and Z axes, in Z, Y, X order. This is the same
+    /// as Roblox's `CFrame.Angles` and `CFrame.fromEulerAnglesXYZ`.
+    pub fn from_euler_angles_xyz(rx: f32, ry: f32, rz: f32) -> Self {
+        Self::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            Matrix3::from_euler_angles_xyz(rx, ry, rz),
+        )
+    }
+
+    /// Creates a `CFrame` at the origin that's rotated by `rx`, `ry`, and `rz`
+    /// radians around the X, Y, and Z axes, in Z, X, Y order. This is the same
+    /// as Roblox's `CFrame.fromOrientation` and `CFrame.fromEulerAnglesYXZ`.
+    pub fn from_euler_angles_yxz(rx: f32, ry: f32, rz: f32) -> Self {
+        Self::new(
+            Vector3::new(0.0, 0.0, 0.0),
+            Matrix3::from_euler_angles_yxz(rx, ry, rz),
+        )
+    }
+
+    /// Returns the angles that [`CFrame::from_euler_angles_xyz`] would need to
+    /// create this `CFrame`'s rotation, like Roblox's
+    /// `CFrame:ToEulerAnglesXYZ`.
+    pub fn to_euler_angles_xyz(&self) -> (f32, f32, f32) {
+        self.orientation.to_euler_angles_xyz()
+    }
+
+    /// Returns the angles that [`CFrame::from_euler_angles_yxz`] would need to
+    /// create this `CFrame`'s rotation, like Roblox's `CFrame:ToOrientation`
+    /// and `CFrame:ToEulerAnglesYXZ`.
+    pub fn to_euler_angles_yxz(&self) -> (f32, f32, f32) {
+        self.orientation.to_euler_angles_yxz()
+    }
+
+    /// Returns the inverse of this `CFrame`, which undoes it when multiplied
+    /// with it. Like the rest of the math on `CFrame`, this assumes that the
+    /// orientation is orthonormal, which every `CFrame` Roblox creates is.
+    pub fn inverse(&self) -> Self {
+        let orientation = self.orientation.transpose();
+
+        Self::new(-(orientation * self.position), orientation)
+    }
+
+    /// Transforms a `CFrame` relative to this one into world space, which is
+    /// the same as `self * cframe`.
+    pub fn to_world_space(&self, cframe: CFrame) -> CFrame {
+        *self * cframe
+    }
+
+    /// Transforms a `CFrame` in world space into one relative to this one,
+    /// which is the same as `self.inverse() * cframe`.
+    pub fn to_object_space(&self, cframe: CFrame) -> CFrame {
+        self.inverse() * cframe
+    }
+
+    /// Transforms a point relative to this `CFrame` into world space, which is
+    /// the same as `self * point`.
+    pub fn point_to_world_space(&self, point: Vector3) -> Vector3 {
+        self.orientation * point + self.position
+    }
+
+    /// Transforms a point in world space into one relative to this `CFrame`.
+    pub fn point_to_object_space(&self, point: Vector3) -> Vector3 {
+        self.inverse().point_to_world_space(point)
+    }
+
+    /// Rotates a direction relative to this `CFrame` into world space, without
+    /// moving it by the `CFrame`'s position.
+    pub fn vector_to_world_space(&self, vector: Vector3) -> Vector3 {
+        self.orientation * vector
+    }
+
+    /// Rotates a direction in world space into one relative to this `CFrame`,
+    /// without moving it by the `CFrame`'s position.
+    pub fn vector_to_object_space(&self, vector: Vector3) -> Vector3 {
+        self.orientation.transpose() * vector
+    }
+}
+
+impl Mul for CFrame {
+    type Output = CFrame;
+
+    /// Composes two `CFrame`s, so that `rhs` is treated as being relative to
+    /// `self`, like multiplying `CFrame`s in Roblox.
+    fn mul(self, rhs: CFrame) -> CFrame {
+        CFrame::new(
+            self.point_to_world_space(rhs.position),
+            self.orientation * rhs.orientation,
+        )
+    }
+}
+
+impl Mul<Vector3> for CFrame {
+    type Output = Vector3;
+
+    /// Transforms a point relative to this `CFrame` into world space.
+    fn mul(self, rhs: Vector3) -> Vector3 {
+        self.point_to_world_space(rhs)
+    }
 }
 
 /// Used to represent the `orientation` field of `CFrame` and not a standalone
@@ -197,8 +584,162 @@ impl Matrix3 {
             z: Vector3::new(self.x.z, self.y.z, self.z.z),
         }
     }
+
+    /// Creates a rotation of `angle` radians around `axis`, which doesn't need
+    /// to be a unit vector. Positive angles rotate counterclockwise when
+    /// looking down the axis towards the origin, like they do in Roblox.
+    pub fn from_axis_angle(axis: Vector3, angle: f32) -> Self {
+        if axis.magnitude() == 0.0 {
+            return Self::identity();
+        }
+
+        let Vector3 { x, y, z } = axis.unit();
+        let (sin, cos) = angle.sin_cos();
+        let t = 1.0 - cos;
+
+        Self {
+            x: Vector3::new(cos + x * x * t, x * y * t - z * sin, x * z * t + y * sin),
+            y: Vector3::new(y * x * t + z * sin, cos + y * y * t, y * z * t - x * sin),
+            z: Vector3::new(z * x * t - y * sin, z * y * t + x * sin, cos + z * z * t),
+        }
+    }
+
+    /// Returns the unit axis and the angle in radians, between `0` and `pi`,
+    /// of this rotation. A rotation of zero has an axis of `(1, 0, 0)`.
+    pub fn to_axis_angle(&self) -> (Vector3, f32) {
+        let [x, y, z, w] = self.to_quaternion();
+        let axis = Vector3::new(x, y, z);
+        let length = axis.magnitude();
+
+        if length <= f32::EPSILON {
+            return (Vector3::new(1.0, 0.0, 0.0), 0.0);
+        }
+
+        (axis / length, 2.0 * length.atan2(w))
+    }
+
+    /// Creates a rotation of `rx`, `ry`, and `rz` radians around the X, Y, and
+    /// Z axes, applied in Z, Y, X order. See [`CFrame::from_euler_angles_xyz`].
+    pub fn from_euler_angles_xyz(rx: f32, ry: f32, rz: f32) -> Self {
+        let (x, y, z) = Self::basis_rotations(rx, ry, rz);
+        x * y * z
+    }
+
+    /// Creates a rotation of `rx`, `ry`, and `rz` radians around the X, Y, and
+    /// Z axes, applied in Z, X, Y order. See [`CFrame::from_euler_angles_yxz`].
+    pub fn from_euler_angles_yxz(rx: f32, ry: f32, rz: f32) -> Self {
+        let (x, y, z) = Self::basis_rotations(rx, ry, rz);
+        y * x * z
+    }
+
+    /// Returns the angles that [`Matrix3::from_euler_angles_xyz`] would need to
+    /// create this rotation. When the rotation around Y is a quarter turn, the
+    /// X and Z rotations can't be told apart, so all of it is given to X.
+    pub fn to_euler_angles_xyz(&self) -> (f32, f32, f32) {
+        let ry = self.x.z.clamp(-1.0, 1.0).asin();
+
+        if self.x.z.abs() < 1.0 - GIMBAL_LOCK_EPSILON {
+            ((-self.y.z).atan2(self.z.z), ry, (-self.x.y).atan2(self.x.x))
+        } else {
+            (self.z.y.atan2(self.y.y), ry, 0.0)
+        }
+    }
+
+    /// Returns the angles that [`Matrix3::from_euler_angles_yxz`] would need to
+    /// create this rotation. When the rotation around X is a quarter turn, the
+    /// Y and Z rotations can't be told apart, so all of it is given to Y.
+    pub fn to_euler_angles_yxz(&self) -> (f32, f32, f32) {
+        let rx = (-self.y.z).clamp(-1.0, 1.0).asin();
+
+        if self.y.z.abs() < 1.0 - GIMBAL_LOCK_EPSILON {
+            (rx, self.x.z.atan2(self.z.z), self.y.x.atan2(self.y.y))
+        } else {
+            (rx, (-self.z.x).atan2(self.x.x), 0.0)
+        }
+    }
+
+    fn basis_rotations(rx: f32, ry: f32, rz: f32) -> (Self, Self, Self) {
+        (
+            Self::from_axis_angle(Vector3::new(1.0, 0.0, 0.0), rx),
+            Self::from_axis_angle(Vector3::new(0.0, 1.0, 0.0), ry),
+            Self::from_axis_angle(Vector3::new(0.0, 0.0, 1.0), rz),
+        )
+    }
+
+    /// Returns this rotation as a unit quaternion `[x, y, z, w]` with a
+    /// non-negative `w`.
+    fn to_quaternion(self) -> [f32; 4] {
+        let m = self;
+        let trace = m.x.x + m.y.y + m.z.z;
+
+        // Dividing by the largest of these keeps this accurate for every
+        // rotation, including ones of nearly `pi` radians.
+        let quaternion = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            [
+                (m.z.y - m.y.z) / s,
+                (m.x.z - m.z.x) / s,
+                (m.y.x - m.x.y) / s,
+                s / 4.0,
+            ]
+        } else if m.x.x > m.y.y && m.x.x > m.z.z {
+            let s = (1.0 + m.x.x - m.y.y - m.z.z).sqrt() * 2.0;
+            [
+                s / 4.0,
+                (m.x.y + m.y.x) / s,
+                (m.x.z + m.z.x) / s,
+                (m.z.y - m.y.z) / s,
+            ]
+        } else if m.y.y > m.z.z {
+            let s = (1.0 + m.y.y - m.x.x - m.z.z).sqrt() * 2.0;
+            [
+                (m.x.y + m.y.x) / s,
+                s / 4.0,
+                (m.y.z + m.z.y) / s,
+                (m.x.z - m.z.x) / s,
+            ]
+        } else {
+            let s = (1.0 + m.z.z - m.x.x - m.y.y).sqrt() * 2.0;
+            [
+                (m.x.z + m.z.x) / s,
+                (m.y.z + m.z.y) / s,
+                s / 4.0,
+                (m.y.x - m.x.y) / s,
+            ]
+        };
+
+        if quaternion[3] < 0.0 {
+            quaternion.map(|component| -component)
+        } else {
+            quaternion
+        }
+    }
+}
+
+impl Mul for Matrix3 {
+    type Output = Matrix3;
+
+    fn mul(self, rhs: Matrix3) -> Matrix3 {
+        let columns = rhs.transpose();
+        let row =
+            |row: Vector3| Vector3::new(row.dot(columns.x), row.dot(columns.y), row.dot(columns.z));
+
+        Matrix3::new(row(self.x), row(self.y), row(self.z))
+    }
+}
+
+impl Mul<Vector3> for Matrix3 {
+    type Output = Vector3;
+
+    fn mul(self, rhs: Vector3) -> Vector3 {
+        Vector3::new(self.x.dot(rhs), self.y.dot(rhs), self.z.dot(rhs))
+    }
 }
 
+/// How close to a quarter turn the middle rotation of a set of Euler angles
+/// has to be before the other two are treated as the same axis.
+const GIMBAL_LOCK_EPSILON: f32 = 1.0e-6;
+
 /// Represents any color, including HDR colors.
 ///
 /// ## See Also
@@ -492,6 +1033,359 @@ serde_tuple! {
     Matrix3(x: Vector3, y: Vector3, z: Vector3),
 }
 
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::f32::consts::{FRAC_PI_2, PI};
+
+    use crate::Variant;
+
+    fn assert_cframe_eq(a: CFrame, b: CFrame) {
+        assert!(
+            Variant::CFrame(a).approx_eq(&Variant::CFrame(b), 1.0e-5),
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    fn assert_vector3_eq(a: Vector3, b: Vector3) {
+        assert!(
+            Variant::Vector3(a).approx_eq(&Variant::Vector3(b), 1.0e-5),
+            "{:?} != {:?}",
+            a,
+            b
+        );
+    }
+
+    fn turned(position: Vector3) -> CFrame {
+        CFrame::new(
+            position,
+            Matrix3::from_axis_angle(Vector3::new(0.0, 1.0, 0.0), FRAC_PI_2),
+        )
+    }
+
+    #[test]
+    fn vector3_operators() {
+        let a = Vector3::new(1.0, 2.0, 3.0);
+        let b = Vector3::new(4.0, -5.0, 0.5);
+
+        assert_eq!(a + b, Vector3::new(5.0, -3.0, 3.5));
+        assert_eq!(a - b, Vector3::new(-3.0, 7.0, 2.5));
+        assert_eq!(a * b, Vector3::new(4.0, -10.0, 1.5));
+        assert_eq!(a / b, Vector3::new(0.25, -0.4, 6.0));
+        assert_eq!(a * 2.0, Vector3::new(2.0, 4.0, 6.0));
+        assert_eq!(2.0 * a, a * 2.0);
+        assert_eq!(a / 2.0, Vector3::new(0.5, 1.0, 1.5));
+        assert_eq!(-a, Vector3::new(-1.0, -2.0, -3.0));
+
+        let mut c = a;
+        c += b;
+        c -= a;
+        c *= 4.0;
+        c /= 2.0;
+        assert_eq!(c, b * 2.0);
+    }
+
+    #[test]
+    fn vector3_geometry() {
+        let a = Vector3::new(1.0, 2.0, 3.0);
+        let b = Vector3::new(4.0, -5.0, 0.5);
+
+        assert_eq!(a.dot(b), -4.5);
+        assert_eq!(
+            Vector3::new(1.0, 0.0, 0.0).cross(Vector3::new(0.0, 1.0, 0.0)),
+            Vector3::new(0.0, 0.0, 1.0)
+        );
+        assert_eq!(a.cross(b).dot(a), 0.0);
+
+        assert_eq!(Vector3::new(2.0, 3.0, 6.0).magnitude(), 7.0);
+        assert_vector3_eq(
+            Vector3::new(2.0, 3.0, 6.0).unit(),
+            Vector3::new(2.0 / 7.0, 3.0 / 7.0, 6.0 / 7.0),
+        );
+        assert!(Vector3::new(0.0, 0.0, 0.0).unit().x.is_nan());
+
+        assert_eq!(a.lerp(b, 0.0), a);
+        assert_eq!(a.lerp(b, 1.0), b);
+        assert_eq!(a.lerp(b, 0.5), Vector3::new(2.5, -1.5, 1.75));
+    }
+
+    #[test]
+    fn vector2_math() {
+        let a = Vector2::new(3.0, 4.0);
+        let b = Vector2::new(-1.0, 2.0);
+
+        assert_eq!(a + b, Vector2::new(2.0, 6.0));
+        assert_eq!(a - b, Vector2::new(4.0, 2.0));
+        assert_eq!(a * b, Vector2::new(-3.0, 8.0));
+        assert_eq!(a / 2.0, Vector2::new(1.5, 2.0));
+        assert_eq!(-b, Vector2::new(1.0, -2.0));
+
+        assert_eq!(a.dot(b), 5.0);
+        assert_eq!(a.cross(b), 10.0);
+        assert_eq!(b.cross(a), -10.0);
+        assert_eq!(a.magnitude(), 5.0);
+        assert_eq!(a.unit(), Vector2::new(0.6, 0.8));
+        assert_eq!(a.lerp(b, 0.25), Vector2::new(2.0, 3.5));
+    }
+
+    #[test]
+    fn int16_vector_math() {
+        let a = Vector3int16::new(100, -200, 300);
+        let b = Vector3int16::new(7, 8, -9);
+
+        assert_eq!(a.checked_add(b), Some(Vector3int16::new(107, -192, 291)));
+        assert_eq!(a.checked_sub(b), Some(Vector3int16::new(93, -208, 309)));
+        assert_eq!(a.checked_mul(b), Some(Vector3int16::new(700, -1600, -2700)));
+        assert_eq!(a.checked_scale(3), Some(Vector3int16::new(300, -600, 900)));
+        assert_eq!(a.checked_div(b), Some(Vector3int16::new(14, -25, -33)));
+        assert_eq!(a.checked_neg(), Some(Vector3int16::new(-100, 200, -300)));
+
+        let max = Vector3int16::new(i16::MAX, 0, 0);
+        assert_eq!(max.checked_add(Vector3int16::new(1, 0, 0)), None);
+        assert_eq!(a.checked_scale(200), None);
+        assert_eq!(a.checked_div(Vector3int16::new(1, 0, 1)), None);
+        assert_eq!(Vector3int16::new(0, i16::MIN, 0).checked_neg(), None);
+
+        let c = Vector2int16::new(-5, 9);
+        let d = Vector2int16::new(4, -3);
+        assert_eq!(c.checked_add(d), Some(Vector2int16::new(-1, 6)));
+        assert_eq!(
+            Vector2int16::new(i16::MIN, 0).checked_sub(Vector2int16::new(1, 0)),
+            None
+        );
+    }
+
+    #[test]
+    fn int16_vector_conversions() {
+        let vector = Vector3int16::new(-3, 0, 32767);
+        assert_eq!(Vector3::from(vector), Vector3::new(-3.0, 0.0, 32767.0));
+        assert_eq!(
+            Vector3int16::checked_from(Vector3::from(vector)),
+            Some(vector)
+        );
+
+        assert_eq!(
+            Vector3int16::checked_from(Vector3::new(1.9, -1.9, -32768.5)),
+            Some(Vector3int16::new(1, -1, -32768))
+        );
+        assert_eq!(
+            Vector3int16::checked_from(Vector3::new(32768.0, 0.0, 0.0)),
+            None
+        );
+        assert_eq!(
+            Vector3int16::checked_from(Vector3::new(0.0, f32::NAN, 0.0)),
+            None
+        );
+
+        assert_eq!(
+            Vector2::from(Vector2int16::new(12, -34)),
+            Vector2::new(12.0, -34.0)
+        );
+        assert_eq!(
+            Vector2int16::checked_from(Vector2::new(-0.5, 99.99)),
+            Some(Vector2int16::new(0, 99))
+        );
+    }
+
+    #[test]
+    fn int16_vector_ordering() {
+        let a = Vector3int16::new(1, 5, -2);
+        let b = Vector3int16::new(3, -4, 0);
+
+        assert_eq!(a.component_min(b), Vector3int16::new(1, -4, -2));
+        assert_eq!(a.component_max(b), Vector3int16::new(3, 5, 0));
+
+        let mut vectors = vec![b, a, Vector3int16::new(1, 5, -3)];
+        vectors.sort();
+        assert_eq!(vectors, [Vector3int16::new(1, 5, -3), a, b]);
+
+        assert_eq!(
+            Vector2int16::new(2, 3).component_min(Vector2int16::new(1, 4)),
+            Vector2int16::new(1, 3)
+        );
+        assert!(Vector2int16::new(0, 9) < Vector2int16::new(1, -9));
+    }
+
+    #[test]
+    fn cframe_multiply() {
+        let cframe = turned(Vector3::new(1.0, 2.0, 3.0));
+        let offset = CFrame::new(Vector3::new(1.0, 0.0, 0.0), Matrix3::identity());
+
+        // Turning a quarter of the way around Y points X towards -Z.
+        assert_cframe_eq(
+            cframe * offset,
+            CFrame::new(Vector3::new(1.0, 2.0, 2.0), cframe.orientation),
+        );
+        assert_cframe_eq(cframe * offset, cframe.to_world_space(offset));
+        assert_cframe_eq(
+            cframe * cframe,
+            CFrame::new(
+                Vector3::new(4.0, 4.0, 2.0),
+                Matrix3::from_axis_angle(Vector3::new(0.0, 1.0, 0.0), PI),
+            ),
+        );
+    }
+
+    #[test]
+    fn cframe_inverse() {
+        let cframe = CFrame::new(
+            Vector3::new(5.0, -1.0, 2.5),
+            Matrix3::from_axis_angle(Vector3::new(1.0, 2.0, 3.0), 1.2),
+        );
+        let identity = CFrame::new(Vector3::new(0.0, 0.0, 0.0), Matrix3::identity());
+
+        assert_cframe_eq(cframe * cframe.inverse(), identity);
+        assert_cframe_eq(cframe.inverse() * cframe, identity);
+        assert_cframe_eq(cframe.to_object_space(cframe), identity);
+    }
+
+    #[test]
+    fn cframe_transform_points() {
+        let cframe = turned(Vector3::new(1.0, 2.0, 3.0));
+        let point = Vector3::new(1.0, 0.0, 0.0);
+
+        assert_vector3_eq(cframe * point, Vector3::new(1.0, 2.0, 2.0));
+        assert_vector3_eq(cframe.point_to_world_space(point), cframe * point);
+        assert_vector3_eq(cframe.point_to_object_space(cframe * point), point);
+
+        assert_vector3_eq(
+            cframe.vector_to_world_space(point),
+            Vector3::new(0.0, 0.0, -1.0),
+        );
+        assert_vector3_eq(
+            cframe.vector_to_object_space(Vector3::new(0.0, 0.0, -1.0)),
+            point,
+        );
+    }
+
+    #[test]
+    fn axis_angle_round_trip() {
+        let length = 14.0f32.sqrt();
+        let axis = Vector3::new(1.0, 2.0, 3.0) / length;
+
+        for &angle in &[0.1, 1.0, FRAC_PI_2, 3.0, PI - 1.0e-3] {
+            let cframe = CFrame::from_axis_angle(axis, angle);
+            let (decoded_axis, decoded_angle) = cframe.to_axis_angle();
+
+            assert_vector3_eq(decoded_axis, axis);
+            assert!((decoded_angle - angle).abs() < 1.0e-4, "{}", angle);
+        }
+
+        // Angles past pi come back the other way around.
+        let (decoded_axis, decoded_angle) = CFrame::from_axis_angle(axis, PI + 1.0).to_axis_angle();
+        assert_vector3_eq(decoded_axis, -axis);
+        assert!((decoded_angle - (PI - 1.0)).abs() < 1.0e-4);
+
+        assert_eq!(
+            Matrix3::identity().to_axis_angle(),
+            (Vector3::new(1.0, 0.0, 0.0), 0.0)
+        );
+    }
+
+    #[test]
+    fn look_at() {
+        let origin = Vector3::new(1.0, 2.0, 3.0);
+        let up = Vector3::new(0.0, 1.0, 0.0);
+
+        // CFrames face down their -Z axis.
+        assert_cframe_eq(
+            CFrame::look_at(origin, Vector3::new(1.0, 2.0, -7.0), up),
+            CFrame::new(origin, Matrix3::identity()),
+        );
+
+        let facing_x = CFrame::look_at(origin, Vector3::new(6.0, 2.0, 3.0), up);
+        assert_cframe_eq(
+            facing_x,
+            CFrame::new(
+                origin,
+                Matrix3::from_axis_angle(Vector3::new(0.0, 1.0, 0.0), -FRAC_PI_2),
+            ),
+        );
+        assert_vector3_eq(
+            facing_x.vector_to_world_space(Vector3::new(0.0, 0.0, -1.0)),
+            Vector3::new(1.0, 0.0, 0.0),
+        );
+
+        let facing_up = CFrame::look_at(origin, Vector3::new(1.0, 5.0, 3.0), up);
+        assert_vector3_eq(
+            facing_up.vector_to_world_space(Vector3::new(0.0, 0.0, -1.0)),
+            Vector3::new(0.0, 1.0, 0.0),
+        );
+        assert_vector3_eq(
+            facing_up.vector_to_world_space(Vector3::new(0.0, 1.0, 0.0)),
+            Vector3::new(1.0, 0.0, 0.0),
+        );
+
+        assert_cframe_eq(
+            CFrame::look_at(origin, origin, up),
+            CFrame::new(origin, Matrix3::identity()),
+        );
+    }
+
+    #[test]
+    fn look_at_along_other_up() {
+        let origin = Vector3::new(1.0, 2.0, 3.0);
+        let up = Vector3::new(1.0, 0.0, 0.0);
+
+        let facing_x = CFrame::look_at(origin, Vector3::new(6.0, 2.0, 3.0), up);
+        assert_vector3_eq(
+            facing_x.vector_to_world_space(Vector3::new(0.0, 0.0, -1.0)),
+            Vector3::new(1.0, 0.0, 0.0),
+        );
+        assert_vector3_eq(
+            facing_x.vector_to_world_space(Vector3::new(0.0, 1.0, 0.0)),
+            Vector3::new(0.0, 1.0, 0.0),
+        );
+        assert_vector3_eq(
+            facing_x.vector_to_world_space(Vector3::new(1.0, 0.0, 0.0)),
+            Vector3::new(0.0, 0.0, 1.0),
+        );
+    }
+
+    #[test]
+    fn euler_angles() {
+        let (x, y, z) = (
+            Matrix3::from_axis_angle(Vector3::new(1.0, 0.0, 0.0), 0.3),
+            Matrix3::from_axis_angle(Vector3::new(0.0, 1.0, 0.0), -1.1),
+            Matrix3::from_axis_angle(Vector3::new(0.0, 0.0, 1.0), 2.0),
+        );
+        let origin = Vector3::new(0.0, 0.0, 0.0);
+
+        let xyz = CFrame::from_euler_angles_xyz(0.3, -1.1, 2.0);
+        assert_cframe_eq(xyz, CFrame::new(origin, x * y * z));
+
+        let yxz = CFrame::from_euler_angles_yxz(0.3, -1.1, 2.0);
+        assert_cframe_eq(yxz, CFrame::new(origin, y * x * z));
+
+        let assert_angles_eq = |a: (f32, f32, f32), b: (f32, f32, f32)| {
+            assert_vector3_eq(Vector3::new(a.0, a.1, a.2), Vector3::new(b.0, b.1, b.2));
+        };
+        assert_angles_eq(xyz.to_euler_angles_xyz(), (0.3, -1.1, 2.0));
+        assert_angles_eq(yxz.to_euler_angles_yxz(), (0.3, -1.1, 2.0));
+
+        // A quarter turn in the middle puts all of the other rotation on the
+        // first axis, and the angles still make the same rotation.
+        let locked = CFrame::from_euler_angles_xyz(0.5, FRAC_PI_2, 0.25);
+        let angles = locked.to_euler_angles_xyz();
+        assert_eq!(angles.2, 0.0);
+        assert_cframe_eq(
+            CFrame::from_euler_angles_xyz(angles.0, angles.1, angles.2),
+            locked,
+        );
+
+        let locked = CFrame::from_euler_angles_yxz(-FRAC_PI_2, 0.5, 0.25);
+        let angles = locked.to_euler_angles_yxz();
+        assert_eq!(angles.2, 0.0);
+        assert_cframe_eq(
+            CFrame::from_euler_angles_yxz(angles.0, angles.1, angles.2),
+            locked,
+        );
+    }
+}
+
 #[cfg(all(test, feature = "serde"))]
 mod serde_test {
     use super::*;
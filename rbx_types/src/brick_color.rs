@@ -1,5 +1,7 @@
 use std::fmt;
 
+use crate::Color3uint8;
+
 macro_rules! make_brick_color {
     ({
         $([
@@ -62,6 +64,16 @@ macro_rules! make_brick_color {
                     _ => None,
                 }
             }
+
+            /// The color that this BrickColor stands for, which is what
+            /// `Part.Color` is set to when a part's BrickColor is set.
+            pub fn to_color3uint8(self) -> Color3uint8 {
+                match self {
+                    $(
+                        BrickColor::$enum => Color3uint8::new($color3_r, $color3_g, $color3_b),
+                    )+
+                }
+            }
         }
 
         impl fmt::Display for BrickColor {
@@ -337,6 +349,23 @@ mod tests {
     }
 }
 
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn to_color3uint8() {
+        assert_eq!(
+            BrickColor::ReallyRed.to_color3uint8(),
+            Color3uint8::new(255, 0, 0)
+        );
+        assert_eq!(
+            BrickColor::MediumStoneGrey.to_color3uint8(),
+            Color3uint8::new(163, 162, 165)
+        );
+    }
+}
+
 #[cfg(all(test, feature = "serde"))]
 mod serde_test {
     use super::*;
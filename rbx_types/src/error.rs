@@ -1,6 +1,6 @@
 use thiserror::Error;
 
-use crate::AttributeError;
+use crate::{material_colors::MaterialColorsError, terrain::SmoothGridError, AttributeError};
 
 /// Represents an error that occurred when using a fallible method.
 #[derive(Debug, Error)]
@@ -17,8 +17,30 @@ impl From<AttributeError> for Error {
     }
 }
 
+impl From<SmoothGridError> for Error {
+    fn from(source: SmoothGridError) -> Self {
+        Self {
+            source: Box::new(source.into()),
+        }
+    }
+}
+
+impl From<MaterialColorsError> for Error {
+    fn from(source: MaterialColorsError) -> Self {
+        Self {
+            source: Box::new(source.into()),
+        }
+    }
+}
+
 #[derive(Debug, Error)]
 enum InnerError {
     #[error(transparent)]
-    AttributeError(#[from] AttributeError),
+    Attribute(#[from] AttributeError),
+
+    #[error(transparent)]
+    SmoothGrid(#[from] SmoothGridError),
+
+    #[error(transparent)]
+    MaterialColors(#[from] MaterialColorsError),
 }
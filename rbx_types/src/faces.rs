@@ -73,6 +73,14 @@ impl Faces {
         FaceFlags::from_bits(bits).map(|flags| Self { flags })
     }
 
+    /// Like `from_bits`, but bits that don't stand for any of the faces are
+    /// ignored instead of being rejected.
+    pub const fn from_bits_truncate(bits: u8) -> Self {
+        Self {
+            flags: FaceFlags::from_bits_truncate(bits),
+        }
+    }
+
     #[cfg(feature = "serde")]
     fn len(self) -> usize {
         self.bits().count_ones() as usize
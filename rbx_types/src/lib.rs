@@ -2,6 +2,7 @@
 #[macro_use]
 mod serde_util;
 
+mod approx_eq;
 mod attributes;
 mod axes;
 mod basic_types;
@@ -12,13 +13,17 @@ mod error;
 mod faces;
 mod font;
 mod lister;
+mod material_colors;
 mod physical_properties;
 mod referent;
+mod security_capabilities;
 mod shared_string;
 mod tags;
+mod terrain;
 mod unique_id;
 mod variant;
 
+pub use approx_eq::DEFAULT_FLOAT_TOLERANCE;
 pub use attributes::*;
 pub use axes::*;
 pub use basic_types::*;
@@ -28,9 +33,12 @@ pub use content::*;
 pub use error::*;
 pub use faces::*;
 pub use font::*;
+pub use material_colors::*;
 pub use physical_properties::*;
 pub use referent::*;
+pub use security_capabilities::*;
 pub use shared_string::*;
 pub use tags::*;
+pub use terrain::*;
 pub use unique_id::*;
 pub use variant::*;
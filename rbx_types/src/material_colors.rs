@@ -0,0 +1,194 @@
+use thiserror::Error;
+
+use crate::{Color3uint8, Error, TerrainMaterial};
+
+/// The materials that have a color in [`MaterialColors`], in the order they're
+/// written in the serialized form.
+const MATERIALS: [TerrainMaterial; 21] = [
+    TerrainMaterial::Grass,
+    TerrainMaterial::Slate,
+    TerrainMaterial::Concrete,
+    TerrainMaterial::Brick,
+    TerrainMaterial::Sand,
+    TerrainMaterial::WoodPlanks,
+    TerrainMaterial::Rock,
+    TerrainMaterial::Glacier,
+    TerrainMaterial::Snow,
+    TerrainMaterial::Sandstone,
+    TerrainMaterial::Mud,
+    TerrainMaterial::Basalt,
+    TerrainMaterial::Ground,
+    TerrainMaterial::CrackedLava,
+    TerrainMaterial::Asphalt,
+    TerrainMaterial::Cobblestone,
+    TerrainMaterial::Ice,
+    TerrainMaterial::LeafyGrass,
+    TerrainMaterial::Salt,
+    TerrainMaterial::Limestone,
+    TerrainMaterial::Pavement,
+];
+
+/// The serialized form starts with room for two colors that are always zero.
+const RESERVED_LEN: usize = 6;
+
+const ENCODED_LEN: usize = RESERVED_LEN + MATERIALS.len() * 3;
+
+/// The colors of each terrain material, as stored in `Terrain.MaterialColors`.
+///
+/// Every material except `Air` and `Water` has a color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct MaterialColors {
+    colors: [Color3uint8; 21],
+}
+
+impl MaterialColors {
+    /// Returns the color of the given material, or `None` if the material
+    /// doesn't have one.
+    pub fn get(&self, material: TerrainMaterial) -> Option<Color3uint8> {
+        index_of(material).map(|index| self.colors[index])
+    }
+
+    /// Sets the color of the given material.
+    ///
+    /// ## Panics
+    /// Panics if the material doesn't have a color, like `Air` or `Water`.
+    pub fn set(&mut self, material: TerrainMaterial, color: Color3uint8) {
+        let index = index_of(material)
+            .unwrap_or_else(|| panic!("terrain material {} has no color", material.name()));
+
+        self.colors[index] = color;
+    }
+
+    /// Returns an iterator over every material that has a color, along with
+    /// its color.
+    pub fn iter(&self) -> impl Iterator<Item = (TerrainMaterial, Color3uint8)> + '_ {
+        MATERIALS.iter().copied().zip(self.colors.iter().copied())
+    }
+
+    /// Decodes `MaterialColors` from the contents of `Terrain.MaterialColors`.
+    pub fn decode(buf: &[u8]) -> Result<Self, Error> {
+        Self::decode_inner(buf).map_err(Error::from)
+    }
+
+    fn decode_inner(buf: &[u8]) -> Result<Self, MaterialColorsError> {
+        if buf.len() != ENCODED_LEN {
+            return Err(MaterialColorsError::WrongLength(buf.len()));
+        }
+
+        let mut colors = [Color3uint8::new(0, 0, 0); 21];
+
+        for (color, rgb) in colors.iter_mut().zip(buf[RESERVED_LEN..].chunks_exact(3)) {
+            *color = Color3uint8::new(rgb[0], rgb[1], rgb[2]);
+        }
+
+        Ok(MaterialColors { colors })
+    }
+
+    /// Encodes `MaterialColors` into the form stored in
+    /// `Terrain.MaterialColors`.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(ENCODED_LEN);
+        buf.extend_from_slice(&[0; RESERVED_LEN]);
+
+        for color in &self.colors {
+            buf.extend_from_slice(&[color.r, color.g, color.b]);
+        }
+
+        buf
+    }
+}
+
+/// The colors that new terrain starts out with.
+impl Default for MaterialColors {
+    fn default() -> Self {
+        let colors = [
+            (106, 127, 63),
+            (63, 127, 107),
+            (127, 102, 63),
+            (138, 86, 62),
+            (143, 126, 95),
+            (139, 109, 79),
+            (102, 108, 111),
+            (101, 176, 234),
+            (195, 199, 218),
+            (137, 90, 71),
+            (58, 46, 36),
+            (30, 30, 37),
+            (102, 92, 59),
+            (232, 156, 74),
+            (115, 123, 107),
+            (132, 123, 90),
+            (129, 194, 224),
+            (115, 132, 74),
+            (198, 189, 181),
+            (206, 173, 148),
+            (148, 148, 140),
+        ]
+        .map(|(r, g, b)| Color3uint8::new(r, g, b));
+
+        MaterialColors { colors }
+    }
+}
+
+fn index_of(material: TerrainMaterial) -> Option<usize> {
+    MATERIALS
+        .iter()
+        .position(|candidate| *candidate == material)
+}
+
+/// An error that can occur when decoding [`MaterialColors`].
+#[derive(Debug, Error)]
+pub(crate) enum MaterialColorsError {
+    #[error("MaterialColors must be {ENCODED_LEN} bytes long, but was {0} bytes")]
+    WrongLength(usize),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut colors = MaterialColors::default();
+        colors.set(TerrainMaterial::Mud, Color3uint8::new(1, 2, 3));
+
+        let encoded = colors.encode();
+        assert_eq!(encoded.len(), ENCODED_LEN);
+        assert_eq!(&encoded[..RESERVED_LEN], &[0; RESERVED_LEN]);
+
+        let decoded = MaterialColors::decode(&encoded).unwrap();
+        assert_eq!(decoded, colors);
+        assert_eq!(
+            decoded.get(TerrainMaterial::Mud),
+            Some(Color3uint8::new(1, 2, 3))
+        );
+    }
+
+    #[test]
+    fn layout() {
+        let mut buf = vec![0; ENCODED_LEN];
+        buf[RESERVED_LEN..RESERVED_LEN + 3].copy_from_slice(&[10, 20, 30]);
+        buf[ENCODED_LEN - 3..].copy_from_slice(&[40, 50, 60]);
+
+        let colors = MaterialColors::decode(&buf).unwrap();
+        assert_eq!(
+            colors.get(TerrainMaterial::Grass),
+            Some(Color3uint8::new(10, 20, 30))
+        );
+        assert_eq!(
+            colors.get(TerrainMaterial::Pavement),
+            Some(Color3uint8::new(40, 50, 60))
+        );
+        assert_eq!(colors.get(TerrainMaterial::Water), None);
+    }
+
+    #[test]
+    fn wrong_length() {
+        assert!(MaterialColors::decode(&[0; 12]).is_err());
+    }
+}
@@ -0,0 +1,57 @@
+/// A set of security capabilities, like the ones held by
+/// `Instance.Capabilities` for scripts running in a sandbox.
+///
+/// Each capability is one bit of the underlying value. rbx_types doesn't know
+/// which bit is which, so the value is kept as-is.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
+pub struct SecurityCapabilities {
+    value: u64,
+}
+
+impl SecurityCapabilities {
+    /// Creates a `SecurityCapabilities` from the bits of its value.
+    pub fn from_bits(value: u64) -> Self {
+        Self { value }
+    }
+
+    /// Returns the bits of the value.
+    pub fn bits(&self) -> u64 {
+        self.value
+    }
+
+    /// Returns whether every capability in `other` is also in this set.
+    pub fn contains(&self, other: SecurityCapabilities) -> bool {
+        self.value & other.value == other.value
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn contains() {
+        let capabilities = SecurityCapabilities::from_bits(0b1011);
+
+        assert!(capabilities.contains(SecurityCapabilities::from_bits(0b0011)));
+        assert!(capabilities.contains(SecurityCapabilities::default()));
+        assert!(!capabilities.contains(SecurityCapabilities::from_bits(0b0100)));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        let capabilities = SecurityCapabilities::from_bits(u64::MAX);
+
+        let serialized = serde_json::to_string(&capabilities).unwrap();
+        assert_eq!(serialized, u64::MAX.to_string());
+
+        let deserialized: SecurityCapabilities = serde_json::from_str(&serialized).unwrap();
+        assert_eq!(deserialized, capabilities);
+    }
+}
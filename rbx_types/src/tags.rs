@@ -29,6 +29,20 @@ impl Tags {
         self.members.push(tag.to_owned());
     }
 
+    /// Returns whether the container has the given tag.
+    pub fn contains(&self, tag: &str) -> bool {
+        self.members.iter().any(|member| member == tag)
+    }
+
+    /// Removes every occurrence of the given tag from the container, returning
+    /// whether any were removed.
+    pub fn remove(&mut self, tag: &str) -> bool {
+        let len = self.members.len();
+        self.members.retain(|member| member != tag);
+
+        self.members.len() != len
+    }
+
     /// Returns an iterator over all of the tags in the container.
     pub fn iter(&self) -> TagsIter<'_> {
         TagsIter {
@@ -99,6 +113,17 @@ mod test {
         assert_eq!(tags.encode(), value);
     }
 
+    #[test]
+    fn contains_remove() {
+        let mut tags = Tags::decode(b"foo\0bar\0foo").unwrap();
+
+        assert!(tags.contains("foo"));
+        assert!(tags.remove("foo"));
+        assert!(!tags.contains("foo"));
+        assert!(!tags.remove("foo"));
+        assert_eq!(tags.iter().collect::<Vec<_>>(), &["bar"]);
+    }
+
     #[test]
     fn decode_empty() {
         let input = b"";
@@ -0,0 +1,589 @@
+use std::collections::BTreeMap;
+
+use thiserror::Error;
+
+use crate::Error;
+
+/// The number of voxels along each side of a chunk in a [`SmoothGrid`].
+pub const SMOOTH_GRID_CHUNK_SIZE: usize = 32;
+
+/// The power of two that `SMOOTH_GRID_CHUNK_SIZE` is, which is how it's
+/// written in the header.
+const CHUNK_SIZE_EXPONENT: u8 = 5;
+
+const CHUNK_VOXELS: usize =
+    SMOOTH_GRID_CHUNK_SIZE * SMOOTH_GRID_CHUNK_SIZE * SMOOTH_GRID_CHUNK_SIZE;
+
+const SMOOTH_GRID_VERSION: u8 = 1;
+
+const MATERIAL_MASK: u8 = 0b0011_1111;
+const HAS_OCCUPANCY: u8 = 0b0100_0000;
+const HAS_RUN_LENGTH: u8 = 0b1000_0000;
+
+/// The largest number of voxels a single run can cover.
+const MAX_RUN_LENGTH: usize = 256;
+
+macro_rules! terrain_materials {
+    ($($name: ident = $value: literal,)*) => {
+        /// A material that a voxel of terrain can be made of, numbered the
+        /// way the terrain formats number them.
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+        #[non_exhaustive]
+        pub enum TerrainMaterial {
+            $(
+                #[allow(missing_docs)]
+                $name = $value,
+            )*
+        }
+
+        impl TerrainMaterial {
+            /// Every terrain material, in order.
+            pub const ALL: &'static [TerrainMaterial] = &[$(TerrainMaterial::$name,)*];
+
+            /// Returns the material with the given number, if there is one.
+            pub fn from_u8(value: u8) -> Option<Self> {
+                match value {
+                    $($value => Some(TerrainMaterial::$name),)*
+                    _ => None,
+                }
+            }
+
+            /// Returns the number of this material.
+            pub fn to_u8(self) -> u8 {
+                self as u8
+            }
+
+            /// Returns the name of this material, like `LeafyGrass`.
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(TerrainMaterial::$name => stringify!($name),)*
+                }
+            }
+        }
+    };
+}
+
+terrain_materials! {
+    Air = 0,
+    Water = 1,
+    Grass = 2,
+    Slate = 3,
+    Concrete = 4,
+    Brick = 5,
+    Sand = 6,
+    WoodPlanks = 7,
+    Rock = 8,
+    Glacier = 9,
+    Snow = 10,
+    Sandstone = 11,
+    Mud = 12,
+    Basalt = 13,
+    Ground = 14,
+    CrackedLava = 15,
+    Asphalt = 16,
+    Cobblestone = 17,
+    Ice = 18,
+    LeafyGrass = 19,
+    Salt = 20,
+    Limestone = 21,
+    Pavement = 22,
+}
+
+/// A single voxel of terrain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Voxel {
+    /// The material the voxel is made of.
+    pub material: TerrainMaterial,
+
+    /// How much of the voxel is filled, from 0 for empty to 255 for full.
+    /// Use [`Voxel::occupancy`] to get this as a fraction.
+    pub occupancy: u8,
+}
+
+impl Voxel {
+    /// An empty voxel of air.
+    pub const AIR: Voxel = Voxel {
+        material: TerrainMaterial::Air,
+        occupancy: 0,
+    };
+
+    /// Creates a voxel that's completely filled with the given material.
+    /// Air voxels are always empty.
+    pub fn full(material: TerrainMaterial) -> Self {
+        Voxel {
+            material,
+            occupancy: implied_occupancy(material),
+        }
+    }
+
+    /// Returns how much of the voxel is filled, from 0.0 to 1.0.
+    pub fn occupancy(&self) -> f32 {
+        f32::from(self.occupancy) / 255.0
+    }
+}
+
+impl Default for Voxel {
+    fn default() -> Self {
+        Voxel::AIR
+    }
+}
+
+/// The occupancy a voxel of the given material has when the format doesn't
+/// give one explicitly.
+fn implied_occupancy(material: TerrainMaterial) -> u8 {
+    match material {
+        TerrainMaterial::Air => 0,
+        _ => 255,
+    }
+}
+
+/// The voxels that make up `Terrain.SmoothGrid`, which is stored in files as a
+/// binary string.
+///
+/// Voxels are addressed by their position in voxels, not studs, and are grouped
+/// into cubic chunks [`SMOOTH_GRID_CHUNK_SIZE`] voxels on a side. Voxels in
+/// chunks that aren't present are air.
+///
+/// The encoded form starts with a version byte, which must be 1, and a byte
+/// holding the chunk size as a power of two. Each chunk follows, starting with
+/// its position in chunks as three zigzag-encoded LEB128 integers, each
+/// relative to the previous chunk. Its voxels are then run-length encoded in
+/// order of X, then Y, then Z. Each run starts with a byte holding the material
+/// in its low six bits. If the next bit is set, a byte with the occupancy
+/// follows; otherwise, air is empty and any other material is full. If the top
+/// bit is set, a byte follows with the number of voxels in the run, minus one.
+///
+/// `Terrain.PhysicsGrid` holds a different representation of the same terrain.
+/// Its layout isn't documented, so it isn't decoded and stays a
+/// [`BinaryString`][crate::BinaryString] that's written back out unchanged.
+/// Changing the voxels in a `SmoothGrid` doesn't update it. See
+/// `docs/compatibility.md` for more.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SmoothGrid {
+    chunks: BTreeMap<[i32; 3], Box<[Voxel]>>,
+}
+
+impl SmoothGrid {
+    /// Creates a `SmoothGrid` with no terrain.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the voxel at the given position.
+    pub fn voxel(&self, position: [i32; 3]) -> Voxel {
+        let (chunk, index) = split_position(position);
+
+        self.chunks
+            .get(&chunk)
+            .map(|voxels| voxels[index])
+            .unwrap_or(Voxel::AIR)
+    }
+
+    /// Sets the voxel at the given position.
+    pub fn set_voxel(&mut self, position: [i32; 3], voxel: Voxel) {
+        let (chunk, index) = split_position(position);
+
+        if voxel == Voxel::AIR && !self.chunks.contains_key(&chunk) {
+            return;
+        }
+
+        self.chunks
+            .entry(chunk)
+            .or_insert_with(|| vec![Voxel::AIR; CHUNK_VOXELS].into_boxed_slice())[index] = voxel;
+    }
+
+    /// Returns an iterator over the positions and voxels of every voxel that
+    /// isn't air, ordered by chunk.
+    pub fn iter(&self) -> impl Iterator<Item = ([i32; 3], Voxel)> + '_ {
+        self.chunks.iter().flat_map(|(chunk, voxels)| {
+            voxels
+                .iter()
+                .enumerate()
+                .filter(|(_, voxel)| **voxel != Voxel::AIR)
+                .map(move |(index, voxel)| (join_position(*chunk, index), *voxel))
+        })
+    }
+
+    /// Returns the positions of the chunks that are present, in the units of
+    /// chunks.
+    pub fn chunk_positions(&self) -> impl Iterator<Item = [i32; 3]> + '_ {
+        self.chunks.keys().copied()
+    }
+
+    /// Decodes a `SmoothGrid` from the contents of `Terrain.SmoothGrid`.
+    pub fn decode(buf: &[u8]) -> Result<Self, Error> {
+        Self::decode_inner(buf).map_err(Error::from)
+    }
+
+    fn decode_inner(buf: &[u8]) -> Result<Self, SmoothGridError> {
+        let mut reader = Reader { buf, offset: 0 };
+
+        let version = reader.read_u8()?;
+        if version != SMOOTH_GRID_VERSION {
+            return Err(SmoothGridError::UnsupportedVersion(version));
+        }
+
+        let chunk_size_exponent = reader.read_u8()?;
+        if chunk_size_exponent != CHUNK_SIZE_EXPONENT {
+            return Err(SmoothGridError::UnsupportedChunkSize(chunk_size_exponent));
+        }
+
+        let mut grid = SmoothGrid::new();
+        let mut chunk = [0i32; 3];
+
+        while !reader.is_empty() {
+            for axis in &mut chunk {
+                *axis = axis.wrapping_add(reader.read_zigzag()?);
+            }
+
+            let mut voxels = Vec::with_capacity(CHUNK_VOXELS);
+
+            while voxels.len() < CHUNK_VOXELS {
+                let flags = reader.read_u8()?;
+
+                let material = TerrainMaterial::from_u8(flags & MATERIAL_MASK)
+                    .ok_or(SmoothGridError::UnknownMaterial(flags & MATERIAL_MASK))?;
+
+                let occupancy = if flags & HAS_OCCUPANCY != 0 {
+                    reader.read_u8()?
+                } else {
+                    implied_occupancy(material)
+                };
+
+                let run_length = if flags & HAS_RUN_LENGTH != 0 {
+                    usize::from(reader.read_u8()?) + 1
+                } else {
+                    1
+                };
+
+                if voxels.len() + run_length > CHUNK_VOXELS {
+                    return Err(SmoothGridError::RunOverflowsChunk);
+                }
+
+                voxels.resize(
+                    voxels.len() + run_length,
+                    Voxel {
+                        material,
+                        occupancy,
+                    },
+                );
+            }
+
+            if grid
+                .chunks
+                .insert(chunk, voxels.into_boxed_slice())
+                .is_some()
+            {
+                return Err(SmoothGridError::DuplicateChunk(chunk));
+            }
+        }
+
+        Ok(grid)
+    }
+
+    /// Encodes this `SmoothGrid` into the format used by `Terrain.SmoothGrid`.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut output = vec![SMOOTH_GRID_VERSION, CHUNK_SIZE_EXPONENT];
+        let mut previous = [0i32; 3];
+
+        for (chunk, voxels) in &self.chunks {
+            if voxels.iter().all(|voxel| *voxel == Voxel::AIR) {
+                continue;
+            }
+
+            for axis in 0..3 {
+                write_zigzag(&mut output, chunk[axis].wrapping_sub(previous[axis]));
+            }
+            previous = *chunk;
+
+            for run in Runs::new(voxels) {
+                let (voxel, run_length) = run;
+                let mut flags = voxel.material.to_u8();
+
+                let explicit_occupancy = voxel.occupancy != implied_occupancy(voxel.material);
+                if explicit_occupancy {
+                    flags |= HAS_OCCUPANCY;
+                }
+                if run_length > 1 {
+                    flags |= HAS_RUN_LENGTH;
+                }
+
+                output.push(flags);
+                if explicit_occupancy {
+                    output.push(voxel.occupancy);
+                }
+                if run_length > 1 {
+                    output.push((run_length - 1) as u8);
+                }
+            }
+        }
+
+        output
+    }
+}
+
+/// Splits a voxel position into the position of its chunk and its index in
+/// that chunk.
+fn split_position(position: [i32; 3]) -> ([i32; 3], usize) {
+    let size = SMOOTH_GRID_CHUNK_SIZE as i32;
+    let chunk = position.map(|axis| axis.div_euclid(size));
+    let [x, y, z] = position.map(|axis| axis.rem_euclid(size) as usize);
+
+    let index =
+        x + y * SMOOTH_GRID_CHUNK_SIZE + z * SMOOTH_GRID_CHUNK_SIZE * SMOOTH_GRID_CHUNK_SIZE;
+
+    (chunk, index)
+}
+
+/// The inverse of `split_position`.
+fn join_position(chunk: [i32; 3], index: usize) -> [i32; 3] {
+    let size = SMOOTH_GRID_CHUNK_SIZE;
+    let local = [index % size, (index / size) % size, index / (size * size)];
+
+    [
+        chunk[0] * size as i32 + local[0] as i32,
+        chunk[1] * size as i32 + local[1] as i32,
+        chunk[2] * size as i32 + local[2] as i32,
+    ]
+}
+
+/// Splits voxels into runs of identical voxels no longer than
+/// `MAX_RUN_LENGTH`.
+struct Runs<'a> {
+    voxels: &'a [Voxel],
+}
+
+impl<'a> Runs<'a> {
+    fn new(voxels: &'a [Voxel]) -> Self {
+        Runs { voxels }
+    }
+}
+
+impl Iterator for Runs<'_> {
+    type Item = (Voxel, usize);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let first = *self.voxels.first()?;
+        let run_length = self
+            .voxels
+            .iter()
+            .take(MAX_RUN_LENGTH)
+            .take_while(|voxel| **voxel == first)
+            .count();
+
+        self.voxels = &self.voxels[run_length..];
+        Some((first, run_length))
+    }
+}
+
+struct Reader<'a> {
+    buf: &'a [u8],
+    offset: usize,
+}
+
+impl Reader<'_> {
+    fn is_empty(&self) -> bool {
+        self.offset >= self.buf.len()
+    }
+
+    fn read_u8(&mut self) -> Result<u8, SmoothGridError> {
+        let value = *self
+            .buf
+            .get(self.offset)
+            .ok_or(SmoothGridError::UnexpectedEof)?;
+        self.offset += 1;
+
+        Ok(value)
+    }
+
+    fn read_zigzag(&mut self) -> Result<i32, SmoothGridError> {
+        let mut value: u32 = 0;
+
+        for shift in (0..35).step_by(7) {
+            let byte = self.read_u8()?;
+            value |= u32::from(byte & 0x7F).wrapping_shl(shift);
+
+            if byte & 0x80 == 0 {
+                return Ok(((value >> 1) as i32) ^ -((value & 1) as i32));
+            }
+        }
+
+        Err(SmoothGridError::InvalidChunkPosition)
+    }
+}
+
+fn write_zigzag(output: &mut Vec<u8>, value: i32) {
+    let mut value = ((value << 1) ^ (value >> 31)) as u32;
+
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+
+        if value == 0 {
+            output.push(byte);
+            break;
+        }
+
+        output.push(byte | 0x80);
+    }
+}
+
+/// An error that can occur when decoding a [`SmoothGrid`].
+#[derive(Debug, Error)]
+pub(crate) enum SmoothGridError {
+    #[error("SmoothGrid ended unexpectedly")]
+    UnexpectedEof,
+
+    #[error("SmoothGrid version {0} is not supported")]
+    UnsupportedVersion(u8),
+
+    #[error("SmoothGrid chunk size 2^{0} is not supported")]
+    UnsupportedChunkSize(u8),
+
+    #[error("SmoothGrid chunk position is invalid")]
+    InvalidChunkPosition,
+
+    #[error("SmoothGrid chunk {0:?} appears more than once")]
+    DuplicateChunk([i32; 3]),
+
+    #[error("terrain material {0} is unknown")]
+    UnknownMaterial(u8),
+
+    #[error("a run of voxels in SmoothGrid goes past the end of its chunk")]
+    RunOverflowsChunk,
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use super::*;
+
+    use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+
+    use crate::BinaryString;
+
+    // SmoothGrids are serialized in their encoded form, which is much smaller
+    // than a list of voxels.
+    impl Serialize for SmoothGrid {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            BinaryString::from(self.encode()).serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for SmoothGrid {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let encoded = BinaryString::deserialize(deserializer)?;
+            SmoothGrid::decode_inner(encoded.as_ref()).map_err(D::Error::custom)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn empty() {
+        let grid = SmoothGrid::new();
+        let encoded = grid.encode();
+
+        assert_eq!(encoded, [1, 5]);
+        assert_eq!(SmoothGrid::decode(&encoded).unwrap(), grid);
+    }
+
+    #[test]
+    fn voxels() {
+        let mut grid = SmoothGrid::new();
+        grid.set_voxel([0, 0, 0], Voxel::full(TerrainMaterial::Grass));
+        grid.set_voxel(
+            [1, 0, 0],
+            Voxel {
+                material: TerrainMaterial::Water,
+                occupancy: 128,
+            },
+        );
+        grid.set_voxel([-1, -40, 100], Voxel::full(TerrainMaterial::Rock));
+
+        assert_eq!(grid.voxel([0, 0, 0]), Voxel::full(TerrainMaterial::Grass));
+        assert_eq!(grid.voxel([1, 0, 0]).occupancy, 128);
+        assert_eq!(grid.voxel([2, 0, 0]), Voxel::AIR);
+        assert_eq!(grid.voxel([500, 0, 0]), Voxel::AIR);
+        assert_eq!(
+            grid.iter().collect::<Vec<_>>(),
+            [
+                ([-1, -40, 100], Voxel::full(TerrainMaterial::Rock)),
+                ([0, 0, 0], Voxel::full(TerrainMaterial::Grass)),
+                (
+                    [1, 0, 0],
+                    Voxel {
+                        material: TerrainMaterial::Water,
+                        occupancy: 128
+                    }
+                ),
+            ]
+        );
+
+        let decoded = SmoothGrid::decode(&grid.encode()).unwrap();
+        assert_eq!(decoded, grid);
+    }
+
+    #[test]
+    fn encoded_runs() {
+        let mut grid = SmoothGrid::new();
+        for x in 0..3 {
+            grid.set_voxel([x, 0, 0], Voxel::full(TerrainMaterial::Sand));
+        }
+
+        let encoded = grid.encode();
+
+        // Header, then the chunk at (0, 0, 0), a run of three sand voxels,
+        // and runs of air filling the rest of the chunk.
+        assert_eq!(
+            encoded[..8],
+            [1, 5, 0, 0, 0, 6 | HAS_RUN_LENGTH, 2, HAS_RUN_LENGTH]
+        );
+        assert_eq!(
+            encoded.len(),
+            8 + 2 * ((CHUNK_VOXELS - 3) / MAX_RUN_LENGTH) + 1
+        );
+    }
+
+    #[test]
+    fn zigzag() {
+        for value in [0, 1, -1, 63, -64, 64, i32::MAX, i32::MIN] {
+            let mut buf = Vec::new();
+            write_zigzag(&mut buf, value);
+
+            let mut reader = Reader {
+                buf: &buf,
+                offset: 0,
+            };
+            assert_eq!(reader.read_zigzag().unwrap(), value);
+            assert!(reader.is_empty());
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(SmoothGrid::decode(&[]).is_err());
+        assert!(SmoothGrid::decode(&[2, 5]).is_err());
+        assert!(SmoothGrid::decode(&[1, 4]).is_err());
+
+        // A chunk that ends before all of its voxels are given.
+        assert!(SmoothGrid::decode(&[1, 5, 0, 0, 0, 2]).is_err());
+
+        // An unknown material.
+        assert!(SmoothGrid::decode(&[1, 5, 0, 0, 0, 63]).is_err());
+    }
+
+    #[test]
+    fn materials() {
+        for material in TerrainMaterial::ALL {
+            assert_eq!(TerrainMaterial::from_u8(material.to_u8()), Some(*material));
+        }
+
+        assert_eq!(TerrainMaterial::LeafyGrass.name(), "LeafyGrass");
+        assert_eq!(TerrainMaterial::from_u8(23), None);
+    }
+}
@@ -0,0 +1,435 @@
+use std::{collections::HashMap, fmt, path::Path};
+
+use anyhow::Context;
+use rbx_binary::{ReferentStrategy, UnknownChunk};
+use rbx_dom_weak::{
+    types::{Ref, VariantType},
+    WeakDom,
+};
+use rbx_xml::{
+    DecodeOptions, DecodePropertyBehavior, DocumentMetadata, EncodeOptions, EncodePropertyBehavior,
+};
+
+/// Value types that rbx_xml can read, but rbx_binary can't write.
+const BINARY_UNSUPPORTED_TYPES: &[VariantType] = &[VariantType::Vector2int16];
+
+/// The formats that model and place files can be stored in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// Roblox's binary format, used by `.rbxm` and `.rbxl` files.
+    Binary,
+
+    /// Roblox's XML format, used by `.rbxmx` and `.rbxlx` files.
+    Xml,
+}
+
+impl Format {
+    /// Picks the format of a file from its extension, returning `None` if it
+    /// isn't the extension of a model or place.
+    pub fn from_path(path: &Path) -> Option<Format> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("rbxm") | Some("rbxl") => Some(Format::Binary),
+            Some("rbxmx") | Some("rbxlx") => Some(Format::Xml),
+            _ => None,
+        }
+    }
+}
+
+/// Something in the input file that couldn't be represented in the output
+/// file's format, and so was left out of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Loss {
+    /// The binary file's `SIGN` chunk. XML files can't be signed.
+    Signature,
+
+    /// A chunk in the binary file that rbx_binary doesn't understand.
+    UnknownChunk { name: String },
+
+    /// An `<External>` tag in the XML file.
+    External { contents: String },
+
+    /// Comments in the XML file, attached to the given instance, or at the end
+    /// of the document if there isn't one.
+    Comments { instance: Option<String> },
+
+    /// A property of the given instance whose value can't be written in the
+    /// output format, like one that rbx_xml kept as raw XML because it didn't
+    /// understand its type.
+    Property { instance: String, name: String },
+}
+
+impl fmt::Display for Loss {
+    fn fmt(&self, output: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Loss::Signature => write!(output, "the file's signature was dropped"),
+            Loss::UnknownChunk { name } => write!(output, "the unknown chunk {} was dropped", name),
+            Loss::External { contents } => {
+                write!(output, "the External tag {:?} was dropped", contents)
+            }
+            Loss::Comments {
+                instance: Some(instance),
+            } => write!(output, "comments on {} were dropped", instance),
+            Loss::Comments { instance: None } => {
+                write!(output, "comments at the end of the file were dropped")
+            }
+            Loss::Property { instance, name } => {
+                write!(output, "the property {}.{} was dropped", instance, name)
+            }
+        }
+    }
+}
+
+/// The result of [`convert`].
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Conversion {
+    /// The contents of the converted file.
+    pub output: Vec<u8>,
+
+    /// Everything that had to be left out of the converted file, in no
+    /// particular order.
+    pub losses: Vec<Loss>,
+}
+
+/// Converts a model or place from one format to another, keeping everything
+/// that both formats can hold.
+///
+/// Along with the instances in the file and their properties, this keeps
+/// shared strings, the file's metadata, and anything the libraries reading it
+/// don't understand that the output format has room for: properties of unknown
+/// types and comments when converting from XML to XML, and unknown chunks and
+/// signatures when converting from binary to binary, which also keeps
+/// referents the same. Anything that the output format can't hold is left out
+/// and reported in [`Conversion::losses`].
+///
+/// Properties of types that rbx_binary doesn't understand are dropped when
+/// reading binary files, so they can't be reported.
+pub fn convert(input: &[u8], from: Format, to: Format) -> anyhow::Result<Conversion> {
+    let document = Document::decode(input, from)?;
+    let mut losses = Vec::new();
+
+    let output = match to {
+        Format::Binary => document.encode_binary(&mut losses)?,
+        Format::Xml => document.encode_xml(&mut losses)?,
+    };
+
+    Ok(Conversion { output, losses })
+}
+
+/// Everything read from a file in either format.
+struct Document {
+    tree: WeakDom,
+    metadata: Vec<(String, String)>,
+    xml_metadata: DocumentMetadata,
+    signature: Option<Vec<u8>>,
+    unknown_chunks: Vec<UnknownChunk>,
+    binary_referents: HashMap<Ref, i32>,
+}
+
+impl Document {
+    fn decode(input: &[u8], format: Format) -> anyhow::Result<Document> {
+        match format {
+            Format::Binary => {
+                let document = rbx_binary::Deserializer::new()
+                    .deserialize_document(input)
+                    .context("Failed to read binary file")?;
+
+                let mut metadata: Vec<_> = document.metadata.into_iter().collect();
+                metadata.sort();
+
+                Ok(Document {
+                    tree: document.tree,
+                    metadata,
+                    xml_metadata: DocumentMetadata::new(),
+                    signature: document.signature,
+                    unknown_chunks: document.unknown_chunks,
+                    binary_referents: document.referents,
+                })
+            }
+
+            Format::Xml => {
+                let options = DecodeOptions::new()
+                    .property_behavior(DecodePropertyBehavior::ReadUnknown)
+                    .preserve_unknown_types(true)
+                    .preserve_comments(true);
+
+                let document = rbx_xml::from_reader_document(input, options)
+                    .context("Failed to read XML file")?;
+
+                Ok(Document {
+                    tree: document.tree,
+                    metadata: document
+                        .metadata
+                        .iter()
+                        .map(|(name, value)| (name.to_owned(), value.to_owned()))
+                        .collect(),
+                    xml_metadata: document.metadata,
+                    signature: None,
+                    unknown_chunks: Vec::new(),
+                    binary_referents: HashMap::new(),
+                })
+            }
+        }
+    }
+
+    fn encode_binary(mut self, losses: &mut Vec<Loss>) -> anyhow::Result<Vec<u8>> {
+        losses.extend(
+            self.xml_metadata
+                .externals()
+                .iter()
+                .map(|contents| Loss::External {
+                    contents: contents.clone(),
+                }),
+        );
+
+        if !self.xml_metadata.comments().is_empty() {
+            losses.push(Loss::Comments { instance: None });
+        }
+
+        strip_binary_unsupported(&mut self.tree, &self.xml_metadata, losses);
+
+        let mut output = Vec::new();
+        rbx_binary::Serializer::new()
+            .metadata(self.metadata.into_iter().collect::<HashMap<_, _>>())
+            .signature(self.signature)
+            .unknown_chunks(self.unknown_chunks)
+            .referents(ReferentStrategy::Preserve(self.binary_referents))
+            .serialize(&mut output, &self.tree, self.tree.root().children())
+            .context("Failed to write binary file")?;
+
+        Ok(output)
+    }
+
+    fn encode_xml(self, losses: &mut Vec<Loss>) -> anyhow::Result<Vec<u8>> {
+        if self.signature.is_some() {
+            losses.push(Loss::Signature);
+        }
+
+        losses.extend(self.unknown_chunks.iter().map(|chunk| {
+            Loss::UnknownChunk {
+                name: String::from_utf8_lossy(&chunk.name)
+                    .trim_end_matches('\0')
+                    .to_owned(),
+            }
+        }));
+
+        let mut metadata = self.xml_metadata;
+        for (name, value) in self.metadata {
+            metadata.insert(name, value);
+        }
+
+        let options = EncodeOptions::new()
+            .property_behavior(EncodePropertyBehavior::WriteUnknown)
+            .metadata(metadata);
+
+        rbx_xml::encode_to_vec(&self.tree, self.tree.root().children(), options)
+            .context("Failed to write XML file")
+    }
+}
+
+/// Removes the properties rbx_binary can't write from every instance in the
+/// tree, noting each one in `losses` along with the raw properties and
+/// comments that rbx_xml kept for the instance in `xml_metadata`.
+fn strip_binary_unsupported(
+    tree: &mut WeakDom,
+    xml_metadata: &DocumentMetadata,
+    losses: &mut Vec<Loss>,
+) {
+    let mut to_visit = tree.root().children().to_vec();
+
+    while let Some(referent) = to_visit.pop() {
+        let full_name = tree.full_name(referent).unwrap_or_default();
+        let instance = tree.get_by_ref_mut(referent).unwrap();
+        to_visit.extend_from_slice(instance.children());
+
+        let mut names: Vec<String> = instance
+            .properties
+            .iter()
+            .filter(|(_, value)| BINARY_UNSUPPORTED_TYPES.contains(&value.ty()))
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        for name in &names {
+            instance.properties.remove(name);
+        }
+
+        let instance_metadata = xml_metadata.instance(referent);
+        if let Some(instance_metadata) = instance_metadata {
+            names.extend(
+                instance_metadata
+                    .raw_properties()
+                    .map(|(name, _)| name.to_owned()),
+            );
+        }
+        names.sort();
+
+        losses.extend(names.into_iter().map(|name| Loss::Property {
+            instance: full_name.clone(),
+            name,
+        }));
+
+        let had_comments = instance_metadata.is_some_and(|instance_metadata| {
+            !instance_metadata.leading_comments().is_empty()
+                || !instance_metadata.trailing_comments().is_empty()
+        });
+
+        if had_comments {
+            losses.push(Loss::Comments {
+                instance: Some(full_name),
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rbx_dom_weak::{types::Vector2int16, InstanceBuilder};
+
+    fn binary_fixture() -> Vec<u8> {
+        let tree = WeakDom::new(
+            InstanceBuilder::new("DataModel").with_child(
+                InstanceBuilder::new("StringValue")
+                    .with_name("Greeting")
+                    .with_property("Value", "Hello"),
+            ),
+        );
+
+        let mut metadata = HashMap::new();
+        metadata.insert("ExplicitAutoJoints".to_owned(), "true".to_owned());
+
+        let mut output = Vec::new();
+        rbx_binary::Serializer::new()
+            .metadata(metadata)
+            .signature(Some(b"not a real signature".to_vec()))
+            .unknown_chunks(vec![UnknownChunk {
+                name: *b"NEW\0",
+                data: b"data".to_vec(),
+            }])
+            .serialize(&mut output, &tree, tree.root().children())
+            .unwrap();
+
+        output
+    }
+
+    #[test]
+    fn binary_to_binary_is_lossless() {
+        let input = binary_fixture();
+        let conversion = convert(&input, Format::Binary, Format::Binary).unwrap();
+
+        assert_eq!(conversion.losses, []);
+        assert_eq!(conversion.output, input);
+    }
+
+    #[test]
+    fn binary_to_xml() {
+        let conversion = convert(&binary_fixture(), Format::Binary, Format::Xml).unwrap();
+
+        assert_eq!(
+            conversion.losses,
+            [
+                Loss::Signature,
+                Loss::UnknownChunk {
+                    name: "NEW".to_owned()
+                }
+            ]
+        );
+
+        let (tree, metadata) =
+            rbx_xml::from_reader_with_metadata(conversion.output.as_slice(), DecodeOptions::new())
+                .unwrap();
+        assert_eq!(metadata.get("ExplicitAutoJoints"), Some("true"));
+
+        let greeting = tree.get_by_ref(tree.root().children()[0]).unwrap();
+        assert_eq!(greeting.name, "Greeting");
+        assert_eq!(
+            greeting.properties.get("Value"),
+            Some(&"Hello".to_owned().into())
+        );
+    }
+
+    #[test]
+    fn xml_to_binary() {
+        let input = r#"
+            <roblox version="4">
+                <Meta name="ExplicitAutoJoints">true</Meta>
+                <External>null</External>
+                <!-- A comment -->
+                <Item class="Folder" referent="RBX0">
+                    <Properties>
+                        <string name="Name">Stuff</string>
+                        <SomeNewType name="Future">1</SomeNewType>
+                    </Properties>
+                </Item>
+            </roblox>
+        "#;
+
+        let conversion = convert(input.as_bytes(), Format::Xml, Format::Binary).unwrap();
+
+        assert_eq!(
+            conversion.losses,
+            [
+                Loss::External {
+                    contents: "null".to_owned()
+                },
+                Loss::Property {
+                    instance: "Stuff".to_owned(),
+                    name: "Future".to_owned(),
+                },
+                Loss::Comments {
+                    instance: Some("Stuff".to_owned())
+                },
+            ]
+        );
+
+        let document = rbx_binary::Deserializer::new()
+            .deserialize_document(conversion.output.as_slice())
+            .unwrap();
+        assert_eq!(
+            document
+                .metadata
+                .get("ExplicitAutoJoints")
+                .map(String::as_str),
+            Some("true")
+        );
+
+        let folder = document
+            .tree
+            .get_by_ref(document.tree.root().children()[0])
+            .unwrap();
+        assert_eq!(folder.name, "Stuff");
+        assert!(folder.properties.is_empty());
+
+        // XML can hold everything it read itself.
+        let conversion = convert(input.as_bytes(), Format::Xml, Format::Xml).unwrap();
+        assert_eq!(conversion.losses, []);
+
+        let output = String::from_utf8(conversion.output).unwrap();
+        assert!(output.contains(r#"<SomeNewType name="Future">1</SomeNewType>"#));
+        assert!(output.contains("<!-- A comment -->"));
+    }
+
+    #[test]
+    fn unsupported_binary_types() {
+        let tree = WeakDom::new(InstanceBuilder::new("DataModel").with_child(
+            InstanceBuilder::new("Folder").with_property("Offset", Vector2int16::new(1, 2)),
+        ));
+        let input = rbx_xml::encode_to_vec(
+            &tree,
+            tree.root().children(),
+            EncodeOptions::new().property_behavior(EncodePropertyBehavior::WriteUnknown),
+        )
+        .unwrap();
+
+        let conversion = convert(&input, Format::Xml, Format::Binary).unwrap();
+        assert_eq!(
+            conversion.losses,
+            [Loss::Property {
+                instance: "Folder".to_owned(),
+                name: "Offset".to_owned(),
+            }]
+        );
+    }
+}
@@ -0,0 +1,6 @@
+//! Utilities for working with Roblox model and place files, used by the
+//! `rbx-util` command line tool.
+
+mod convert;
+
+pub use crate::convert::{convert, Conversion, Format, Loss};
@@ -6,6 +6,8 @@ use anyhow::{anyhow, bail, Context};
 use fs_err::File;
 use structopt::StructOpt;
 
+use rbx_util::Format;
+
 #[derive(Debug, StructOpt)]
 struct Options {
     #[structopt(subcommand)]
@@ -19,82 +21,47 @@ enum Subcommand {
 
     /// View a binary file as an undefined text representation.
     ViewBinary { input: PathBuf },
-}
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
-enum ModelKind {
-    Binary,
-    Xml,
+    /// Check a binary file for problems that could stop Roblox from opening
+    /// it.
+    ValidateBinary { input: PathBuf },
 }
 
-impl ModelKind {
-    fn from_path(path: &Path) -> anyhow::Result<ModelKind> {
-        match path.extension().and_then(|ext| ext.to_str()) {
-            Some("rbxm") | Some("rbxl") => Ok(ModelKind::Binary),
-            Some("rbxmx") | Some("rbxlx") => Ok(ModelKind::Xml),
-
-            _ => Err(anyhow!(
-                "not a Roblox model or place file: {}",
-                path.display()
-            )),
-        }
-    }
+fn format_of(path: &Path) -> anyhow::Result<Format> {
+    Format::from_path(path)
+        .ok_or_else(|| anyhow!("not a Roblox model or place file: {}", path.display()))
 }
 
 fn run(options: Options) -> anyhow::Result<()> {
     match options.subcommand {
         Subcommand::Convert { input, output } => convert(&input, &output)?,
         Subcommand::ViewBinary { input } => view_binary(&input)?,
+        Subcommand::ValidateBinary { input } => validate_binary(&input)?,
     }
 
     Ok(())
 }
 
 fn convert(input_path: &Path, output_path: &Path) -> anyhow::Result<()> {
-    let input_kind = ModelKind::from_path(input_path)?;
-    let output_kind = ModelKind::from_path(output_path)?;
-
-    let input_file = BufReader::new(File::open(input_path)?);
-
-    let dom = match input_kind {
-        ModelKind::Xml => {
-            let options = rbx_xml::DecodeOptions::new()
-                .property_behavior(rbx_xml::DecodePropertyBehavior::ReadUnknown);
-
-            rbx_xml::from_reader(input_file, options)
-                .with_context(|| format!("Failed to read {}", input_path.display()))?
-        }
+    let input_format = format_of(input_path)?;
+    let output_format = format_of(output_path)?;
 
-        ModelKind::Binary => rbx_binary::from_reader(input_file)
-            .with_context(|| format!("Failed to read {}", input_path.display()))?,
-    };
+    let input = fs_err::read(input_path)?;
 
-    let root_ids = dom.root().children();
+    let conversion = rbx_util::convert(&input, input_format, output_format)
+        .with_context(|| format!("Failed to convert {}", input_path.display()))?;
 
-    let output_file = BufWriter::new(File::create(output_path)?);
-
-    match output_kind {
-        ModelKind::Xml => {
-            let options = rbx_xml::EncodeOptions::new()
-                .property_behavior(rbx_xml::EncodePropertyBehavior::WriteUnknown);
-
-            rbx_xml::to_writer(output_file, &dom, root_ids, options)
-                .with_context(|| format!("Failed to write {}", output_path.display()))?;
-        }
-
-        ModelKind::Binary => {
-            rbx_binary::to_writer(output_file, &dom, root_ids)
-                .with_context(|| format!("Failed to write {}", output_path.display()))?;
-        }
+    for loss in &conversion.losses {
+        eprintln!("warning: {}", loss);
     }
 
+    fs_err::write(output_path, conversion.output)?;
+
     Ok(())
 }
 
 fn view_binary(input_path: &Path) -> anyhow::Result<()> {
-    let input_kind = ModelKind::from_path(input_path)?;
-
-    if input_kind != ModelKind::Binary {
+    if format_of(input_path)? != Format::Binary {
         bail!("not a binary model or place file: {}", input_path.display());
     }
 
@@ -109,6 +76,26 @@ fn view_binary(input_path: &Path) -> anyhow::Result<()> {
     Ok(())
 }
 
+fn validate_binary(input_path: &Path) -> anyhow::Result<()> {
+    if format_of(input_path)? != Format::Binary {
+        bail!("not a binary model or place file: {}", input_path.display());
+    }
+
+    let input_file = BufReader::new(File::open(input_path)?);
+
+    let report = rbx_binary::Deserializer::new()
+        .validate(input_file)
+        .with_context(|| format!("Failed to validate {}", input_path.display()))?;
+
+    println!("{}", report);
+
+    if !report.is_valid() {
+        process::exit(1);
+    }
+
+    Ok(())
+}
+
 fn main() {
     let options = Options::from_args();
 
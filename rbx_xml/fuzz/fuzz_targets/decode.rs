@@ -0,0 +1,9 @@
+//! Decodes arbitrary bytes as an XML document.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    rbx_xml_fuzz::check_round_trip(data);
+});
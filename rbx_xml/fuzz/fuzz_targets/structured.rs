@@ -0,0 +1,11 @@
+//! Decodes documents that are built out of real tags, which gets much deeper
+//! into the decoder than arbitrary bytes usually do.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rbx_xml_fuzz::Document;
+
+fuzz_target!(|document: Document| {
+    rbx_xml_fuzz::check_round_trip(document.to_xml().as_bytes());
+});
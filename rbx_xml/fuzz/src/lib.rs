@@ -0,0 +1,239 @@
+//! Shared code for rbx_xml's fuzz targets.
+
+use std::fmt::{self, Write};
+
+use arbitrary::Arbitrary;
+use rbx_xml::{DecodeOptions, EncodeOptions};
+
+/// Decodes `data`, and if that works, checks that the result can be encoded,
+/// and that encoding what's decoded from that output gives the same output
+/// again. Panics if any of that doesn't hold.
+pub fn check_round_trip(data: &[u8]) {
+    let decoded = match rbx_xml::from_reader_document(data, DecodeOptions::new()) {
+        Ok(decoded) => decoded,
+        Err(_) => return,
+    };
+
+    let options = || EncodeOptions::new().metadata(decoded.metadata.clone());
+
+    let first = rbx_xml::encode_to_vec(&decoded.tree, &decoded.roots, options())
+        .unwrap_or_else(|err| panic!("couldn't encode a document that decoded: {}", err));
+
+    let redecoded = rbx_xml::from_reader_document(first.as_slice(), DecodeOptions::new())
+        .unwrap_or_else(|err| {
+            panic!(
+                "couldn't decode rbx_xml's own output: {}\n{}",
+                err,
+                String::from_utf8_lossy(&first)
+            )
+        });
+
+    let second = rbx_xml::encode_to_vec(&redecoded.tree, &redecoded.roots, options())
+        .unwrap_or_else(|err| panic!("couldn't re-encode rbx_xml's own output: {}", err));
+
+    assert!(
+        first == second,
+        "encoding changed after a round trip\nfirst:\n{}\nsecond:\n{}",
+        String::from_utf8_lossy(&first),
+        String::from_utf8_lossy(&second)
+    );
+}
+
+/// A document built out of the tags rbx_xml understands, with arbitrary
+/// contents.
+#[derive(Debug, Arbitrary)]
+pub struct Document {
+    items: Vec<Item>,
+}
+
+impl Document {
+    /// Writes the document out as XML.
+    pub fn to_xml(&self) -> String {
+        let mut output = String::from(r#"<roblox version="4">"#);
+
+        for item in &self.items {
+            write!(output, "{}", item).unwrap();
+        }
+
+        output.push_str("</roblox>");
+        output
+    }
+}
+
+#[derive(Debug, Arbitrary)]
+struct Item {
+    class: Class,
+    referent: u8,
+    properties: Vec<Property>,
+    children: Vec<Item>,
+}
+
+impl fmt::Display for Item {
+    fn fmt(&self, output: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            output,
+            r#"<Item class="{:?}" referent="RBX{}"><Properties>"#,
+            self.class, self.referent
+        )?;
+
+        for property in &self.properties {
+            write!(output, "{}", property)?;
+        }
+
+        output.write_str("</Properties>")?;
+
+        for child in &self.children {
+            write!(output, "{}", child)?;
+        }
+
+        output.write_str("</Item>")
+    }
+}
+
+#[derive(Debug, Arbitrary)]
+enum Class {
+    Folder,
+    Model,
+    Part,
+    ObjectValue,
+    StringValue,
+    Script,
+    Terrain,
+    Workspace,
+}
+
+#[derive(Debug, Arbitrary)]
+struct Property {
+    name: PropertyName,
+    value: Value,
+}
+
+impl fmt::Display for Property {
+    fn fmt(&self, output: &mut fmt::Formatter) -> fmt::Result {
+        let tag = self.value.tag();
+
+        write!(output, r#"<{} name="{:?}">"#, tag, self.name)?;
+        self.value.write_contents(output)?;
+        write!(output, "</{}>", tag)
+    }
+}
+
+/// Real property names, so that values are often, but not always, written
+/// with the type the reflection database expects.
+#[derive(Debug, Arbitrary)]
+enum PropertyName {
+    Name,
+    Value,
+    Anchored,
+    Size,
+    Color,
+    CFrame,
+    Transparency,
+    Source,
+    Tags,
+    AttributesSerialize,
+    PrimaryPart,
+    MaterialColors,
+    Material,
+}
+
+#[derive(Debug, Arbitrary)]
+enum Value {
+    Bool(bool),
+    Int(i32),
+    Int64(i64),
+    Float(f32),
+    Double(f64),
+    String(String),
+    ProtectedString(String),
+    Token(u32),
+    Vector3(f32, f32, f32),
+    Color3(f32, f32, f32),
+    Color3uint8(u32),
+    CFrame([f32; 12]),
+    Ref(Option<u8>),
+    BinaryString(Vec<u8>),
+}
+
+impl Value {
+    fn tag(&self) -> &'static str {
+        match self {
+            Value::Bool(_) => "bool",
+            Value::Int(_) => "int",
+            Value::Int64(_) => "int64",
+            Value::Float(_) => "float",
+            Value::Double(_) => "double",
+            Value::String(_) => "string",
+            Value::ProtectedString(_) => "ProtectedString",
+            Value::Token(_) => "token",
+            Value::Vector3(..) => "Vector3",
+            Value::Color3(..) => "Color3",
+            Value::Color3uint8(_) => "Color3uint8",
+            Value::CFrame(_) => "CoordinateFrame",
+            Value::Ref(_) => "Ref",
+            Value::BinaryString(_) => "BinaryString",
+        }
+    }
+
+    fn write_contents(&self, output: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Bool(value) => write!(output, "{}", value),
+            Value::Int(value) => write!(output, "{}", value),
+            Value::Int64(value) => write!(output, "{}", value),
+            Value::Float(value) => write_float(output, f64::from(*value)),
+            Value::Double(value) => write_float(output, *value),
+            Value::String(value) => write_escaped(output, value),
+            Value::ProtectedString(value) => write!(output, "<![CDATA[{}]]>", value),
+            Value::Token(value) => write!(output, "{}", value),
+            Value::Vector3(x, y, z) => write_components(output, &["X", "Y", "Z"], &[*x, *y, *z]),
+            Value::Color3(r, g, b) => write_components(output, &["R", "G", "B"], &[*r, *g, *b]),
+            Value::Color3uint8(value) => write!(output, "{}", value),
+            Value::CFrame(components) => write_components(
+                output,
+                &[
+                    "X", "Y", "Z", "R00", "R01", "R02", "R10", "R11", "R12", "R20", "R21", "R22",
+                ],
+                components,
+            ),
+            Value::Ref(Some(referent)) => write!(output, "RBX{}", referent),
+            Value::Ref(None) => output.write_str("null"),
+            Value::BinaryString(value) => output.write_str(&base64::encode(value)),
+        }
+    }
+}
+
+/// Writes a float the way rbx_xml does, so that decoding usually succeeds.
+fn write_float(output: &mut fmt::Formatter, value: f64) -> fmt::Result {
+    if value.is_nan() {
+        output.write_str("NAN")
+    } else if value == f64::INFINITY {
+        output.write_str("INF")
+    } else if value == f64::NEG_INFINITY {
+        output.write_str("-INF")
+    } else {
+        write!(output, "{}", value)
+    }
+}
+
+fn write_components(output: &mut fmt::Formatter, tags: &[&str], values: &[f32]) -> fmt::Result {
+    for (tag, value) in tags.iter().zip(values) {
+        write!(output, "<{}>", tag)?;
+        write_float(output, f64::from(*value))?;
+        write!(output, "</{}>", tag)?;
+    }
+
+    Ok(())
+}
+
+fn write_escaped(output: &mut fmt::Formatter, value: &str) -> fmt::Result {
+    for c in value.chars() {
+        match c {
+            '&' => output.write_str("&amp;")?,
+            '<' => output.write_str("&lt;")?,
+            '>' => output.write_str("&gt;")?,
+            _ => output.write_char(c)?,
+        }
+    }
+
+    Ok(())
+}
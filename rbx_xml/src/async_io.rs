@@ -0,0 +1,276 @@
+use std::{panic, sync::Arc};
+
+use rbx_dom_weak::{types::Ref, WeakDom};
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    runtime::Handle,
+};
+
+use crate::{
+    deserializer::decode_internal,
+    error::{DecodeError, DecodeErrorKind, EncodeError, EncodeErrorKind},
+    serializer::{encode_internal, EncodeOptions},
+    DecodeOptions,
+};
+
+/// Decodes an XML-format model or place from something that implements
+/// tokio's `AsyncRead` trait.
+///
+/// The whole document is read before it's decoded, so this holds the document
+/// in memory. Decoding happens on tokio's blocking thread pool, so it doesn't
+/// hold up other tasks even for very large documents.
+///
+/// Because the options are sent to another thread, they must be `'static`,
+/// which rules out borrowing a reflection database that isn't `'static`. Use
+/// [`from_async_reader_inline`] for options that borrow.
+///
+/// ## Errors
+/// Returns an error without reading anything if the future isn't run inside
+/// of a tokio runtime.
+///
+/// This function is only available with the `tokio` feature.
+pub async fn from_async_reader<R: AsyncRead + Unpin>(
+    reader: R,
+    options: DecodeOptions<'static>,
+) -> Result<WeakDom, DecodeError> {
+    if Handle::try_current().is_err() {
+        return Err(DecodeError::new(DecodeErrorKind::NoTokioRuntime));
+    }
+
+    let contents = read_contents(reader).await?;
+    run_blocking(move || decode_internal(contents.as_slice(), options)).await
+}
+
+/// Like [`from_async_reader`], but decodes on the task that polls the future
+/// instead of on tokio's blocking thread pool.
+///
+/// This works with any executor and accepts options that borrow, but the task
+/// won't yield while the document is being decoded, which may hold up other
+/// tasks for large documents.
+///
+/// This function is only available with the `tokio` feature.
+pub async fn from_async_reader_inline<R: AsyncRead + Unpin>(
+    reader: R,
+    options: DecodeOptions<'_>,
+) -> Result<WeakDom, DecodeError> {
+    let contents = read_contents(reader).await?;
+    decode_internal(contents.as_slice(), options)
+}
+
+/// Serializes a subset of the given tree to an XML-format model or place,
+/// writing to something that implements tokio's `AsyncWrite` trait.
+///
+/// The document is encoded in memory on tokio's blocking thread pool, so it
+/// doesn't hold up other tasks, then written and flushed. The output is
+/// identical to [`to_writer`][crate::to_writer].
+///
+/// Because the tree and options are sent to another thread, the tree is taken
+/// as an `Arc` and the options must be `'static`. Snapshots from
+/// [`SyncWeakDom`][rbx_dom_weak::SyncWeakDom] can be passed in directly. Use
+/// [`to_async_writer_inline`] to encode a borrowed tree, or with options that
+/// borrow.
+///
+/// ## Errors
+/// Returns an error without writing anything if the future isn't run inside
+/// of a tokio runtime.
+///
+/// This function is only available with the `tokio` feature.
+pub async fn to_async_writer<W: AsyncWrite + Unpin>(
+    writer: W,
+    tree: Arc<WeakDom>,
+    ids: &[Ref],
+    options: EncodeOptions<'static>,
+) -> Result<(), EncodeError> {
+    if Handle::try_current().is_err() {
+        return Err(EncodeError::new(EncodeErrorKind::NoTokioRuntime));
+    }
+
+    let ids = ids.to_vec();
+    let contents = run_blocking(move || {
+        let mut contents = Vec::new();
+        encode_internal(&mut contents, &tree, &ids, options)?;
+        Ok(contents)
+    })
+    .await?;
+
+    write_contents(writer, &contents).await
+}
+
+/// Like [`to_async_writer`], but encodes on the task that polls the future
+/// instead of on tokio's blocking thread pool.
+///
+/// This works with any executor and accepts a borrowed tree and options that
+/// borrow, but the task won't yield while the document is being encoded, which
+/// may hold up other tasks for large trees.
+///
+/// This function is only available with the `tokio` feature.
+pub async fn to_async_writer_inline<W: AsyncWrite + Unpin>(
+    writer: W,
+    tree: &WeakDom,
+    ids: &[Ref],
+    options: EncodeOptions<'_>,
+) -> Result<(), EncodeError> {
+    let mut contents = Vec::new();
+    encode_internal(&mut contents, tree, ids, options)?;
+
+    write_contents(writer, &contents).await
+}
+
+async fn read_contents<R: AsyncRead + Unpin>(mut reader: R) -> Result<Vec<u8>, DecodeError> {
+    let mut contents = Vec::new();
+    reader
+        .read_to_end(&mut contents)
+        .await
+        .map_err(|err| DecodeError::new(DecodeErrorKind::Io(err)))?;
+
+    Ok(contents)
+}
+
+async fn write_contents<W: AsyncWrite + Unpin>(
+    mut writer: W,
+    contents: &[u8],
+) -> Result<(), EncodeError> {
+    let io_error = |err| EncodeError::new(EncodeErrorKind::Io(err));
+    writer.write_all(contents).await.map_err(io_error)?;
+    writer.flush().await.map_err(io_error)
+}
+
+/// Runs `work` on tokio's blocking thread pool, passing along any panic.
+async fn run_blocking<T, F>(work: F) -> T
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    match tokio::task::spawn_blocking(work).await {
+        Ok(output) => output,
+        Err(err) => match err.try_into_panic() {
+            Ok(payload) => panic::resume_unwind(payload),
+            Err(_) => panic!("the tokio runtime shut down while rbx_xml was working"),
+        },
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::{
+        future::Future,
+        task::{Context, Poll, Wake, Waker},
+        thread::{self, Thread},
+    };
+
+    use rbx_dom_weak::{types::Variant, InstanceBuilder};
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    /// Polls a future to completion on this thread, without a tokio runtime.
+    fn block_on_without_runtime<F: Future>(future: F) -> F::Output {
+        struct ThreadWaker(Thread);
+
+        impl Wake for ThreadWaker {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+        let mut context = Context::from_waker(&waker);
+        let mut future = Box::pin(future);
+
+        loop {
+            match future.as_mut().poll(&mut context) {
+                Poll::Ready(output) => return output,
+                Poll::Pending => thread::park(),
+            }
+        }
+    }
+
+    fn test_tree() -> (WeakDom, Ref) {
+        let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+        let value = tree.insert(
+            tree.root_ref(),
+            InstanceBuilder::new("IntValue").with_property("Value", 5i64),
+        );
+
+        (tree, value)
+    }
+
+    fn assert_send<T: Send>(_: &T) {}
+
+    #[test]
+    fn round_trip() {
+        let (tree, value) = test_tree();
+
+        let ids = [value];
+        let mut encoded = Vec::new();
+        let encode = to_async_writer(&mut encoded, Arc::new(tree), &ids, EncodeOptions::new());
+
+        // Futures need to be Send to be used with tokio::spawn.
+        assert_send(&encode);
+        block_on(encode).unwrap();
+
+        let decode = from_async_reader(encoded.as_slice(), DecodeOptions::new());
+        assert_send(&decode);
+        let decoded = block_on(decode).unwrap();
+
+        let instance = decoded.get_by_ref(decoded.root().children()[0]).unwrap();
+        assert_eq!(instance.properties.get("Value"), Some(&Variant::Int64(5)));
+    }
+
+    #[test]
+    fn round_trip_inline() {
+        let (tree, value) = test_tree();
+
+        // The inline functions can borrow the tree and don't need a runtime.
+        let mut encoded = Vec::new();
+        block_on_without_runtime(to_async_writer_inline(
+            &mut encoded,
+            &tree,
+            &[value],
+            EncodeOptions::new(),
+        ))
+        .unwrap();
+
+        let decoded = block_on_without_runtime(from_async_reader_inline(
+            encoded.as_slice(),
+            DecodeOptions::new(),
+        ))
+        .unwrap();
+
+        let instance = decoded.get_by_ref(decoded.root().children()[0]).unwrap();
+        assert_eq!(instance.properties.get("Value"), Some(&Variant::Int64(5)));
+    }
+
+    #[test]
+    fn no_runtime() {
+        let (tree, value) = test_tree();
+
+        let mut encoded = Vec::new();
+        let result = block_on_without_runtime(to_async_writer(
+            &mut encoded,
+            Arc::new(tree),
+            &[value],
+            EncodeOptions::new(),
+        ));
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "to_async_writer must be run inside of a tokio runtime"
+        );
+        assert!(encoded.is_empty());
+
+        let result = block_on_without_runtime(from_async_reader(
+            b"<roblox version=\"4\"></roblox>".as_slice(),
+            DecodeOptions::new(),
+        ));
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "from_async_reader must be run inside of a tokio runtime"
+        );
+    }
+}
@@ -4,13 +4,11 @@
 use std::borrow::{Borrow, Cow};
 use std::convert::TryInto;
 
-use rbx_dom_weak::types::{Attributes, BrickColor, Color3uint8, Tags, Variant, VariantType};
+use rbx_dom_weak::types::{
+    Attributes, BrickColor, Color3uint8, MaterialColors, SmoothGrid, Tags, Variant, VariantType,
+};
 
 pub trait ConvertVariant: Clone + Sized {
-    fn try_convert(self, target_type: VariantType) -> Result<Self, String> {
-        Self::try_convert_cow(Cow::Owned(self), target_type).map(|value| value.into_owned())
-    }
-
     fn try_convert_ref(&self, target_type: VariantType) -> Result<Cow<'_, Self>, String> {
         Self::try_convert_cow(Cow::Borrowed(self), target_type)
     }
@@ -50,10 +48,20 @@ impl ConvertVariant for Variant {
 
                 Ok(Cow::Owned(
                     Attributes::from_reader(bytes)
-                        .map_err(|_| "Unknown or invalid Attributes")?
+                        .map_err(|err| format!("Unknown or invalid Attributes: {}", err))?
                         .into(),
                 ))
             }
+            (Variant::BinaryString(value), VariantType::MaterialColors) => Ok(Cow::Owned(
+                MaterialColors::decode(value.as_ref())
+                    .map_err(|err| err.to_string())?
+                    .into(),
+            )),
+            (Variant::BinaryString(value), VariantType::SmoothGrid) => Ok(Cow::Owned(
+                SmoothGrid::decode(value.as_ref())
+                    .map_err(|err| err.to_string())?
+                    .into(),
+            )),
             (_, _) => Ok(value),
         }
     }
@@ -1,6 +1,6 @@
 use std::io::{Read, Write};
 
-use rbx_reflection::{PropertyDescriptor, PropertyKind, PropertySerialization};
+use rbx_reflection::{PropertyDescriptor, PropertyKind, PropertySerialization, ReflectionDatabase};
 
 use crate::{
     deserializer_core::XmlEventReader,
@@ -21,9 +21,18 @@ pub trait XmlType: Sized {
     fn write_xml<W: Write>(&self, writer: &mut XmlEventWriter<W>) -> Result<(), EncodeError>;
 
     fn read_outer_xml<R: Read>(reader: &mut XmlEventReader<R>) -> Result<Self, DecodeError> {
-        reader.expect_start_with_name(Self::XML_TAG_NAME)?;
+        Self::read_outer_xml_with_name(reader, Self::XML_TAG_NAME)
+    }
+
+    /// Reads a value of this type from a tag with a name other than
+    /// `XML_TAG_NAME`, like one of the names in `types::LEGACY_TYPE_NAMES`.
+    fn read_outer_xml_with_name<R: Read>(
+        reader: &mut XmlEventReader<R>,
+        tag_name: &str,
+    ) -> Result<Self, DecodeError> {
+        reader.expect_start_with_name(tag_name)?;
         let value = Self::read_xml(reader)?;
-        reader.expect_end_with_name(Self::XML_TAG_NAME)?;
+        reader.expect_end_with_name(tag_name)?;
 
         Ok(value)
     }
@@ -39,30 +48,56 @@ pub trait XmlType: Sized {
     }
 }
 
-pub fn find_canonical_property_descriptor(
+pub fn find_canonical_property_descriptor<'db>(
+    class_name: &str,
+    property_name: &str,
+    database: &'db ReflectionDatabase<'db>,
+) -> Option<&'db PropertyDescriptor<'db>> {
+    find_property_descriptors(class_name, property_name, database)
+        .map(|(canonical, _serialized)| canonical)
+}
+
+pub fn find_serialized_property_descriptor<'db>(
     class_name: &str,
     property_name: &str,
-) -> Option<&'static PropertyDescriptor<'static>> {
-    find_property_descriptors(class_name, property_name).map(|(canonical, _serialized)| canonical)
+    database: &'db ReflectionDatabase<'db>,
+) -> Option<&'db PropertyDescriptor<'db>> {
+    find_property_descriptors(class_name, property_name, database)
+        .map(|(_canonical, serialized)| serialized)
 }
 
-pub fn find_serialized_property_descriptor(
+/// Finds the descriptor for the property with exactly the given name, without
+/// translating it to its canonical or serialized name. Like the other lookups,
+/// this returns `None` for properties that don't serialize.
+pub fn find_exact_property_descriptor<'db>(
     class_name: &str,
     property_name: &str,
-) -> Option<&'static PropertyDescriptor<'static>> {
-    find_property_descriptors(class_name, property_name).map(|(_canonical, serialized)| serialized)
+    database: &'db ReflectionDatabase<'db>,
+) -> Option<&'db PropertyDescriptor<'db>> {
+    find_property_descriptors(class_name, property_name, database)?;
+
+    let mut current_class_descriptor = database.classes.get(class_name)?;
+
+    loop {
+        if let Some(descriptor) = current_class_descriptor.properties.get(property_name) {
+            return Some(descriptor);
+        }
+
+        current_class_descriptor = database
+            .classes
+            .get(current_class_descriptor.superclass.as_ref()?)
+            .expect("Superclass in reflection database didn't exist");
+    }
 }
 
 /// Find both the canonical and serialized property descriptors for a given
 /// class and property name pair. These might be the same descriptor!
-fn find_property_descriptors(
+fn find_property_descriptors<'db>(
     class_name: &str,
     property_name: &str,
-) -> Option<(
-    &'static PropertyDescriptor<'static>,
-    &'static PropertyDescriptor<'static>,
-)> {
-    let class_descriptor = rbx_reflection_database::get().classes.get(class_name)?;
+    database: &'db ReflectionDatabase<'db>,
+) -> Option<(&'db PropertyDescriptor<'db>, &'db PropertyDescriptor<'db>)> {
+    let class_descriptor = database.classes.get(class_name)?;
 
     let mut current_class_descriptor = class_descriptor;
 
@@ -133,7 +168,7 @@ fn find_property_descriptors(
             // If a property descriptor isn't found in our class, check
             // our superclass.
 
-            current_class_descriptor = rbx_reflection_database::get()
+            current_class_descriptor = database
                 .classes
                 .get(superclass_name)
                 .expect("Superclass in reflection database didn't exist");
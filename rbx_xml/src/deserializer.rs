@@ -1,5 +1,7 @@
 use std::{
-    collections::{HashMap, HashSet},
+    borrow::Cow,
+    collections::{hash_map::Entry, HashMap, HashSet},
+    fmt,
     io::Read,
 };
 
@@ -8,30 +10,91 @@ use rbx_dom_weak::{
     types::{Ref, SharedString, Variant, VariantType},
     InstanceBuilder, WeakDom,
 };
-use rbx_reflection::{perform_migration, DataType, PropertyKind, PropertySerialization};
+use rbx_reflection::{
+    perform_migration, DataType, PropertyKind, PropertySerialization, ReflectionDatabase,
+};
 
 use crate::{
     conversion::ConvertVariant,
-    core::find_canonical_property_descriptor,
-    error::{DecodeError, DecodeErrorKind},
-    types::read_value_xml,
+    core::{find_canonical_property_descriptor, find_exact_property_descriptor},
+    error::{DecodeError, DecodeErrorKind, DecodeWarning},
+    metadata::DocumentMetadata,
+    progress::{Progress, ProgressHook},
+    types::{is_plain_type, parse_unique_id_referent, read_plain_value_xml, read_value_xml},
 };
 
 use crate::deserializer_core::{XmlEventReader, XmlReadEvent};
 
 pub fn decode_internal<R: Read>(source: R, options: DecodeOptions) -> Result<WeakDom, DecodeError> {
+    decode_document(source, options).map(|document| document.tree)
+}
+
+/// Everything read from an XML-format model or place, returned by
+/// [`from_reader_document`][crate::from_reader_document].
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct DecodedDocument {
+    /// The decoded tree. Its root is a `DataModel` instance that holds the
+    /// document's top-level instances.
+    pub tree: WeakDom,
+
+    /// The referents of the document's top-level instances, in the order they
+    /// appear in the document. Models can have any number of these.
+    pub roots: Vec<Ref>,
+
+    /// The document's metadata.
+    pub metadata: DocumentMetadata,
+
+    /// The problems that were recovered from. These are only collected if
+    /// [`DecodeOptions::lenient`] is set.
+    pub warnings: Vec<DecodeWarning>,
+}
+
+impl DecodedDocument {
+    /// Returns the referent of the document's only top-level instance, or
+    /// `None` if it has no top-level instances or more than one.
+    pub fn single_root(&self) -> Option<Ref> {
+        match self.roots.as_slice() {
+            [root] => Some(*root),
+            _ => None,
+        }
+    }
+}
+
+pub fn decode_document<R: Read>(
+    source: R,
+    options: DecodeOptions,
+) -> Result<DecodedDocument, DecodeError> {
     let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
 
     let root_id = tree.root_ref();
 
     let mut iterator = XmlEventReader::from_source(source);
+    if options.preserve_comments {
+        iterator.collect_comments();
+    }
+
     let mut state = ParseState::new(&mut tree, options);
 
-    deserialize_root(&mut iterator, &mut state, root_id)?;
-    apply_referent_rewrites(&mut state);
+    deserialize_root(&mut iterator, &mut state, root_id).map_err(|err| {
+        err.with_context(
+            std::mem::take(&mut state.instance_path),
+            state.current_property.take(),
+        )
+    })?;
+    apply_referent_rewrites(&mut state)?;
     apply_shared_string_rewrites(&mut state);
 
-    Ok(tree)
+    let metadata = state.metadata;
+    let warnings = state.warnings;
+    let roots = tree.root().children().to_vec();
+
+    Ok(DecodedDocument {
+        tree,
+        roots,
+        metadata,
+        warnings,
+    })
 }
 
 /// Describes the strategy that rbx_xml should use when deserializing
@@ -67,18 +130,166 @@ pub enum DecodePropertyBehavior {
     NoReflection,
 }
 
+/// Describes the strategy that rbx_xml should use when deserializing
+/// instances whose classes aren't known by rbx_xml.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DecodeClassBehavior {
+    /// Read instances of unknown classes like any other instance.
+    ///
+    /// This is the default.
+    ReadUnknown,
+
+    /// Skips instances of unknown classes, along with all of their
+    /// descendants. Refs that point to skipped instances are null.
+    IgnoreUnknown,
+
+    /// Returns an error if any instances are found with classes that aren't
+    /// known by rbx_xml.
+    ErrorOnUnknown,
+}
+
+/// Describes what rbx_xml should do with instances that are left out by
+/// [`DecodeOptions::class_filter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DecodeFilterBehavior {
+    /// Skips left-out instances along with all of their descendants, without
+    /// reading any of them.
+    ///
+    /// This is the default, and the fastest option.
+    SkipDescendants,
+
+    /// Skips left-out instances, but still reads their descendants. Each
+    /// descendant that's kept is parented to its nearest ancestor that was
+    /// kept, or to the root of the tree if there isn't one.
+    KeepDescendants,
+}
+
+/// Describes the strategy that rbx_xml should use when a property's value
+/// has a type that can't be converted to the type the reflection database
+/// expects for that property.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DecodeMismatchBehavior {
+    /// Keeps the value with the type it had in the file.
+    ReadOriginal,
+
+    /// Skips the property.
+    IgnoreMismatch,
+
+    /// Returns an error.
+    ///
+    /// This is the default.
+    ErrorOnMismatch,
+}
+
+/// Describes the strategy that rbx_xml should use when a Ref property is null
+/// or doesn't point to any instance in the document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DecodeRefBehavior {
+    /// Sets the property to a null Ref.
+    ///
+    /// This is the default.
+    KeepNull,
+
+    /// Skips the property.
+    DropProperty,
+
+    /// Returns an error.
+    ErrorOnRef,
+}
+
+/// Describes the strategy that rbx_xml should use when more than one instance
+/// in a document has the same referent, which can happen in files that were
+/// corrupted or merged by hand.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum DecodeDuplicateReferentBehavior {
+    /// Returns an error.
+    ErrorOnDuplicate,
+
+    /// Ref properties pointing to the referent point to the last instance
+    /// with it.
+    ///
+    /// This is the default.
+    LastWins,
+
+    /// Treats every instance after the first with the referent as if it had
+    /// a new referent of its own, so Ref properties pointing to the referent
+    /// point to the first instance with it.
+    Renumber,
+}
+
 /// Options available for deserializing an XML-format model or place.
 #[derive(Debug, Clone)]
-pub struct DecodeOptions {
+pub struct DecodeOptions<'db> {
     property_behavior: DecodePropertyBehavior,
+    database: &'db ReflectionDatabase<'db>,
+    class_behavior: DecodeClassBehavior,
+    mismatch_behavior: DecodeMismatchBehavior,
+    preserve_unknown_types: bool,
+    preserve_comments: bool,
+    unique_id_referents: bool,
+    strict_types: bool,
+    lenient: bool,
+    null_ref_behavior: DecodeRefBehavior,
+    dangling_ref_behavior: DecodeRefBehavior,
+    duplicate_referent_behavior: DecodeDuplicateReferentBehavior,
+    external_ref_hook: Option<ExternalRefHook<'db>>,
+    canonical_property_names: bool,
+    migrate_properties: bool,
+    progress_hook: Option<ProgressHook<'db>>,
+    class_filter: Option<ClassFilter<'db>>,
+    filter_behavior: DecodeFilterBehavior,
+}
+
+/// Wraps the function given to `DecodeOptions::external_refs` so that
+/// `DecodeOptions` can still implement `Debug`.
+#[derive(Clone, Copy)]
+struct ExternalRefHook<'db>(&'db (dyn Fn(&str) -> Option<Ref> + Sync));
+
+impl fmt::Debug for ExternalRefHook<'_> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("ExternalRefHook")
+    }
+}
+
+/// Wraps the function given to `DecodeOptions::class_filter` so that
+/// `DecodeOptions` can still implement `Debug`.
+#[derive(Clone, Copy)]
+struct ClassFilter<'db>(&'db (dyn Fn(&str) -> bool + Sync));
+
+impl fmt::Debug for ClassFilter<'_> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("ClassFilter")
+    }
 }
 
-impl DecodeOptions {
+impl<'db> DecodeOptions<'db> {
     /// Constructs a `DecodeOptions` with all values set to their defaults.
     #[inline]
     pub fn new() -> Self {
         DecodeOptions {
             property_behavior: DecodePropertyBehavior::IgnoreUnknown,
+            database: rbx_reflection_database::get(),
+            class_behavior: DecodeClassBehavior::ReadUnknown,
+            mismatch_behavior: DecodeMismatchBehavior::ErrorOnMismatch,
+            preserve_unknown_types: false,
+            preserve_comments: false,
+            unique_id_referents: false,
+            strict_types: false,
+            lenient: false,
+            null_ref_behavior: DecodeRefBehavior::KeepNull,
+            dangling_ref_behavior: DecodeRefBehavior::KeepNull,
+            duplicate_referent_behavior: DecodeDuplicateReferentBehavior::LastWins,
+            external_ref_hook: None,
+            canonical_property_names: true,
+            migrate_properties: true,
+            progress_hook: None,
+            class_filter: None,
+            filter_behavior: DecodeFilterBehavior::SkipDescendants,
         }
     }
 
@@ -86,7 +297,258 @@ impl DecodeOptions {
     /// ones.
     #[inline]
     pub fn property_behavior(self, property_behavior: DecodePropertyBehavior) -> Self {
-        DecodeOptions { property_behavior }
+        DecodeOptions {
+            property_behavior,
+            ..self
+        }
+    }
+
+    /// Sets the reflection database that rbx_xml will use to deserialize
+    /// classes and properties. Defaults to the database bundled with
+    /// rbx_reflection_database.
+    #[inline]
+    pub fn reflection_database(self, database: &'db ReflectionDatabase<'db>) -> Self {
+        DecodeOptions { database, ..self }
+    }
+
+    /// Determines how rbx_xml will deserialize instances whose classes aren't
+    /// in the reflection database. This has no effect when the property
+    /// behavior is `DecodePropertyBehavior::NoReflection`.
+    #[inline]
+    pub fn class_behavior(self, class_behavior: DecodeClassBehavior) -> Self {
+        DecodeOptions {
+            class_behavior,
+            ..self
+        }
+    }
+
+    /// Determines how rbx_xml will deserialize properties whose values can't
+    /// be converted to the type given by the reflection database.
+    #[inline]
+    pub fn mismatch_behavior(self, mismatch_behavior: DecodeMismatchBehavior) -> Self {
+        DecodeOptions {
+            mismatch_behavior,
+            ..self
+        }
+    }
+
+    /// Determines whether rbx_xml will keep properties whose types it doesn't
+    /// understand. Defaults to `false`, which skips them.
+    ///
+    /// With this set, the raw XML of each such property is kept in the
+    /// [`InstanceMetadata`][crate::InstanceMetadata] of its instance, which is
+    /// part of the document's [`DocumentMetadata`]. When that metadata is
+    /// given to [`EncodeOptions::metadata`][crate::EncodeOptions::metadata],
+    /// the encoder writes these properties back out exactly as they were read,
+    /// so files that use types newer than rbx_xml can be passed through
+    /// safely.
+    #[inline]
+    pub fn preserve_unknown_types(self, preserve_unknown_types: bool) -> Self {
+        DecodeOptions {
+            preserve_unknown_types,
+            ..self
+        }
+    }
+
+    /// Determines whether rbx_xml will keep the comments in the document.
+    /// Defaults to `false`, which skips them.
+    ///
+    /// With this set, the comments before each instance and the comments
+    /// after its last property or child are kept in the
+    /// [`InstanceMetadata`][crate::InstanceMetadata] of the instance. Comments
+    /// after the last instance in the document are kept in the document's
+    /// [`DocumentMetadata`] itself. When that metadata is given to
+    /// [`EncodeOptions::metadata`][crate::EncodeOptions::metadata], the
+    /// encoder writes all of these back out, so comments survive being decoded
+    /// and encoded again.
+    ///
+    /// Comments are attached to the nearest instance, so comments in other
+    /// places, like among an instance's properties, are moved to the nearest
+    /// of these spots. Whitespace between tags isn't kept; the output's
+    /// formatting comes from [`EncodeOptions`][crate::EncodeOptions].
+    #[inline]
+    pub fn preserve_comments(self, preserve_comments: bool) -> Self {
+        DecodeOptions {
+            preserve_comments,
+            ..self
+        }
+    }
+
+    /// Determines whether rbx_xml will read UniqueIds back out of referents
+    /// written with
+    /// [`EncodeOptions::unique_id_referents`][crate::EncodeOptions::unique_id_referents].
+    /// Defaults to `false`.
+    ///
+    /// When enabled, instances that don't have a `UniqueId` property but whose
+    /// referent is `RBX` followed by a UniqueId are given that UniqueId. This
+    /// shouldn't be used with files from Roblox Studio, whose referents look
+    /// the same but aren't UniqueIds.
+    #[inline]
+    pub fn unique_id_referents(self, unique_id_referents: bool) -> Self {
+        DecodeOptions {
+            unique_id_referents,
+            ..self
+        }
+    }
+
+    /// Determines whether rbx_xml will check that every known property has the
+    /// type the reflection database declares for it. Defaults to `false`.
+    ///
+    /// Normally, a value that doesn't have its declared type is kept with
+    /// whatever type it had in the file if rbx_xml has no conversion for it,
+    /// like a `string` written for a `bool` property. When enabled, this
+    /// returns an error naming the property and both types instead. It
+    /// overrides [`mismatch_behavior`][DecodeOptions::mismatch_behavior], and
+    /// Attributes that can't be decoded are an error too.
+    #[inline]
+    pub fn strict_types(self, strict_types: bool) -> Self {
+        DecodeOptions {
+            strict_types,
+            ..self
+        }
+    }
+
+    /// Determines whether rbx_xml will continue past problems with individual
+    /// property values, like invalid base64 data, out-of-range enum values, or
+    /// referents that don't point to any instance. Defaults to `false`, which
+    /// returns an error for all of these except unresolved referents.
+    ///
+    /// With this set, each such property is given its default value from the
+    /// reflection database, or skipped if there isn't one. Referents that
+    /// don't point to any instance are left as null. Each problem is reported
+    /// as a [`DecodeWarning`], which can be retrieved by decoding with
+    /// [`from_reader_with_warnings`][crate::from_reader_with_warnings].
+    ///
+    /// Problems with the structure of the document itself, like malformed
+    /// XML, are always errors.
+    #[inline]
+    pub fn lenient(self, lenient: bool) -> Self {
+        DecodeOptions { lenient, ..self }
+    }
+
+    /// Determines whether properties are renamed to their canonical names, like
+    /// `size` to `Size` or `Color3uint8` to `Color`. Defaults to `true`.
+    ///
+    /// With this set to `false`, known properties keep the names they're
+    /// written with in the document, and their values are converted to the
+    /// types the reflection database gives for those names. Deprecated
+    /// properties are also kept as-is instead of being migrated to the
+    /// properties that replaced them.
+    #[inline]
+    pub fn canonical_property_names(self, canonical_property_names: bool) -> Self {
+        DecodeOptions {
+            canonical_property_names,
+            ..self
+        }
+    }
+
+    /// Determines whether deprecated properties are migrated to the properties
+    /// that replaced them, like `TextLabel.Font` to `TextLabel.FontFace`,
+    /// using the migrations in the reflection database. Defaults to `true`.
+    ///
+    /// Each migrated property is recorded as a warning. If the replacement is
+    /// already in the document, the deprecated property is dropped instead.
+    /// With this set to `false`, deprecated properties are kept as-is. This has
+    /// no effect if [`canonical_property_names`][DecodeOptions::canonical_property_names]
+    /// is `false`.
+    #[inline]
+    pub fn migrate_properties(self, migrate_properties: bool) -> Self {
+        DecodeOptions {
+            migrate_properties,
+            ..self
+        }
+    }
+
+    /// Determines how rbx_xml will deserialize Ref properties that are
+    /// explicitly null.
+    #[inline]
+    pub fn null_ref_behavior(self, null_ref_behavior: DecodeRefBehavior) -> Self {
+        DecodeOptions {
+            null_ref_behavior,
+            ..self
+        }
+    }
+
+    /// Determines how rbx_xml will deserialize Ref properties that point to
+    /// referents that aren't in the document, like references to instances
+    /// outside of an exported model.
+    ///
+    /// With `DecodeRefBehavior::ErrorOnRef`, this returns an error even if
+    /// [`lenient`][DecodeOptions::lenient] is set.
+    #[inline]
+    pub fn dangling_ref_behavior(self, dangling_ref_behavior: DecodeRefBehavior) -> Self {
+        DecodeOptions {
+            dangling_ref_behavior,
+            ..self
+        }
+    }
+
+    /// Determines what rbx_xml will do when more than one instance in the
+    /// document has the same referent. Every duplicate is recorded as a
+    /// warning unless this returns an error.
+    #[inline]
+    pub fn duplicate_referent_behavior(
+        self,
+        duplicate_referent_behavior: DecodeDuplicateReferentBehavior,
+    ) -> Self {
+        DecodeOptions {
+            duplicate_referent_behavior,
+            ..self
+        }
+    }
+
+    /// Sets a function that rbx_xml will call with each referent in a Ref
+    /// property that doesn't point to any instance in the document. If the
+    /// function returns a `Ref`, the property is set to it.
+    ///
+    /// This is the counterpart to
+    /// [`EncodeOptions::external_refs`][crate::EncodeOptions::external_refs],
+    /// and lets references to instances in other files be restored. Referents
+    /// that the function doesn't translate are handled by
+    /// [`dangling_ref_behavior`][DecodeOptions::dangling_ref_behavior].
+    #[inline]
+    pub fn external_refs(self, hook: &'db (dyn Fn(&str) -> Option<Ref> + Sync)) -> Self {
+        DecodeOptions {
+            external_ref_hook: Some(ExternalRefHook(hook)),
+            ..self
+        }
+    }
+
+    /// Sets a function that rbx_xml will call each time it finishes reading an
+    /// instance, with how much of the document has been read so far.
+    #[inline]
+    pub fn progress(self, hook: &'db (dyn Fn(Progress) + Sync)) -> Self {
+        DecodeOptions {
+            progress_hook: Some(ProgressHook(hook)),
+            ..self
+        }
+    }
+
+    /// Sets a function that rbx_xml will call with the class name of each
+    /// instance in the document. Instances whose class the function returns
+    /// `false` for are left out of the tree, which can make decoding much
+    /// faster when only a few classes are needed from a large place.
+    ///
+    /// What happens to the descendants of left-out instances is determined by
+    /// [`filter_behavior`][DecodeOptions::filter_behavior]. Refs that point to
+    /// left-out instances are handled by
+    /// [`dangling_ref_behavior`][DecodeOptions::dangling_ref_behavior].
+    #[inline]
+    pub fn class_filter(self, filter: &'db (dyn Fn(&str) -> bool + Sync)) -> Self {
+        DecodeOptions {
+            class_filter: Some(ClassFilter(filter)),
+            ..self
+        }
+    }
+
+    /// Determines what rbx_xml will do with the descendants of instances that
+    /// are left out by [`class_filter`][DecodeOptions::class_filter].
+    #[inline]
+    pub fn filter_behavior(self, filter_behavior: DecodeFilterBehavior) -> Self {
+        DecodeOptions {
+            filter_behavior,
+            ..self
+        }
     }
 
     /// A utility function to determine whether or not we should reference the
@@ -94,23 +556,36 @@ impl DecodeOptions {
     pub(crate) fn use_reflection(&self) -> bool {
         self.property_behavior != DecodePropertyBehavior::NoReflection
     }
+
+    pub(crate) fn preserves_unknown_types(&self) -> bool {
+        self.preserve_unknown_types
+    }
+
+    /// Whether instances of the given class should be added to the tree.
+    fn includes_class(&self, class_name: &str) -> bool {
+        match self.class_filter {
+            Some(ClassFilter(filter)) => filter(class_name),
+            None => true,
+        }
+    }
 }
 
-impl Default for DecodeOptions {
-    fn default() -> DecodeOptions {
+impl<'db> Default for DecodeOptions<'db> {
+    fn default() -> DecodeOptions<'db> {
         DecodeOptions::new()
     }
 }
 
 /// The state needed to deserialize an XML model into an `WeakDom`.
-pub struct ParseState<'a> {
+pub struct ParseState<'a, 'db> {
     tree: &'a mut WeakDom,
-    options: DecodeOptions,
+    options: DecodeOptions<'db>,
 
-    /// Metadata deserialized from 'Meta' fields in the file.
-    /// Known fields are:
+    /// Metadata deserialized from 'Meta' and 'External' fields in the file,
+    /// along with the raw properties and comments of each instance.
+    /// Known 'Meta' fields are:
     /// - ExplicitAutoJoints
-    metadata: HashMap<String, String>,
+    metadata: DocumentMetadata,
 
     /// A map referent strings to IDs. This map is filled up as instances are
     /// deserialized, and referred to when filling out Ref properties.
@@ -136,12 +611,28 @@ pub struct ParseState<'a> {
     /// Contains all of the unknown types that have been found so far. Tracking
     /// them here helps ensure that we only output a warning once per type.
     unknown_type_names: HashSet<String>,
+
+    /// The names of the instance currently being deserialized and each of its
+    /// ancestors, used to give context to errors. Entries start out as the
+    /// instance's class name until its Name property has been read.
+    instance_path: Vec<String>,
+
+    /// The name of the property currently being deserialized, used to give
+    /// context to errors.
+    current_property: Option<String>,
+
+    /// The problems that have been recovered from so far in lenient mode.
+    warnings: Vec<DecodeWarning>,
+
+    /// The number of instances that have been read so far.
+    instances_read: usize,
 }
 
 struct ReferentRewrite {
     id: Ref,
     property_name: String,
     referent_value: String,
+    unresolved_error: Option<DecodeError>,
 }
 
 struct SharedStringRewrite {
@@ -150,17 +641,21 @@ struct SharedStringRewrite {
     shared_string_hash: String,
 }
 
-impl<'a> ParseState<'a> {
-    fn new(tree: &mut WeakDom, options: DecodeOptions) -> ParseState {
+impl<'a, 'db> ParseState<'a, 'db> {
+    fn new(tree: &'a mut WeakDom, options: DecodeOptions<'db>) -> ParseState<'a, 'db> {
         ParseState {
             tree,
             options,
-            metadata: HashMap::new(),
+            metadata: DocumentMetadata::new(),
             referents_to_ids: HashMap::new(),
             referent_rewrites: Vec::new(),
             known_shared_strings: HashMap::new(),
             shared_string_rewrites: Vec::new(),
             unknown_type_names: HashSet::new(),
+            instance_path: Vec::new(),
+            current_property: None,
+            warnings: Vec::new(),
+            instances_read: 0,
         }
     }
 
@@ -182,18 +677,106 @@ impl<'a> ParseState<'a> {
         );
     }
 
+    /// Returns whether properties with unknown types should be kept with
+    /// `add_raw_property` instead of being skipped.
+    pub fn preserves_unknown_types(&self) -> bool {
+        self.options.preserves_unknown_types()
+    }
+
+    /// Keeps the raw XML of a property with an unknown type in the metadata
+    /// of its instance.
+    pub fn add_raw_property(&mut self, id: Ref, property_name: &str, xml: Vec<u8>) {
+        self.metadata
+            .instance_mut(id)
+            .insert_raw_property(property_name, xml);
+    }
+
     /// Marks that a property on this instance needs to be rewritten once we
     /// have a complete view of how referents map to Ref values.
     ///
     /// This is used to deserialize non-null Ref values correctly.
-    pub fn add_referent_rewrite(&mut self, id: Ref, property_name: String, referent_value: String) {
+    ///
+    /// If given, `unresolved_error` is reported if the referent doesn't end
+    /// up pointing to any instance.
+    pub fn add_referent_rewrite(
+        &mut self,
+        id: Ref,
+        property_name: String,
+        referent_value: String,
+        unresolved_error: Option<DecodeError>,
+    ) {
         self.referent_rewrites.push(ReferentRewrite {
             id,
             property_name,
             referent_value,
+            unresolved_error,
         });
     }
 
+    /// Returns whether a Ref property pointing to the given referent could end
+    /// up needing to be reported if the referent doesn't exist, in which case
+    /// `add_referent_rewrite` should be given an error.
+    pub fn reports_dangling_refs(&self) -> bool {
+        self.options.lenient || self.options.dangling_ref_behavior == DecodeRefBehavior::ErrorOnRef
+    }
+
+    /// Returns how Ref properties that are explicitly null should be handled.
+    pub fn null_ref_behavior(&self) -> DecodeRefBehavior {
+        self.options.null_ref_behavior
+    }
+
+    /// Returns whether problems with property values should be reported with
+    /// `add_warning` instead of returned as errors.
+    /// Returns what should happen to a property whose value can't be
+    /// converted to the type the reflection database declares for it.
+    fn mismatch_behavior(&self) -> DecodeMismatchBehavior {
+        if self.options.strict_types {
+            DecodeMismatchBehavior::ErrorOnMismatch
+        } else {
+            self.options.mismatch_behavior
+        }
+    }
+
+    pub fn is_lenient(&self) -> bool {
+        self.options.lenient
+    }
+
+    /// Attaches the instance and property that are currently being
+    /// deserialized to an error.
+    pub fn with_context(&self, error: DecodeError) -> DecodeError {
+        error.with_context(self.instance_path.clone(), self.current_property.clone())
+    }
+
+    /// Records that an instance has been read, reporting progress if
+    /// `DecodeOptions::progress` is set.
+    fn instance_finished(&mut self, bytes_read: u64) {
+        self.instances_read += 1;
+
+        if let Some(hook) = &self.options.progress_hook {
+            hook.report(bytes_read, self.instances_read);
+        }
+    }
+
+    /// Records a problem that was recovered from, like the ones skipped in
+    /// lenient mode.
+    pub fn add_warning(&mut self, error: DecodeError) {
+        let warning = DecodeWarning::new(self.with_context(error));
+        log::warn!("{}", warning);
+        self.warnings.push(warning);
+    }
+
+    /// Finds the default value of the given property from the reflection
+    /// database, if it has one.
+    fn default_value(&self, class_name: &str, property_name: &str) -> Option<Variant> {
+        let database = self.options.database;
+        let descriptor = database.classes.get(class_name)?;
+
+        database
+            .superclasses(descriptor)
+            .find_map(|descriptor| descriptor.default_properties.get(property_name))
+            .cloned()
+    }
+
     /// Marks that a property on this instance needs to be rewritten once we
     /// have a complete view of how referents map to Ref values.
     ///
@@ -212,11 +795,14 @@ impl<'a> ParseState<'a> {
     }
 }
 
-fn apply_referent_rewrites(state: &mut ParseState) {
-    for rewrite in &state.referent_rewrites {
-        let new_value = match state.referents_to_ids.get(&rewrite.referent_value) {
-            Some(id) => *id,
-            None => continue,
+fn apply_referent_rewrites(state: &mut ParseState) -> Result<(), DecodeError> {
+    for rewrite in std::mem::take(&mut state.referent_rewrites) {
+        let resolved = match state.referents_to_ids.get(&rewrite.referent_value) {
+            Some(id) => Some(*id),
+            None => state
+                .options
+                .external_ref_hook
+                .and_then(|ExternalRefHook(hook)| hook(&rewrite.referent_value)),
         };
 
         let instance = state
@@ -224,10 +810,39 @@ fn apply_referent_rewrites(state: &mut ParseState) {
             .get_by_ref_mut(rewrite.id)
             .expect("rbx_xml bug: had ID in referent rewrite list that didn't end up in the tree");
 
+        let new_value = match resolved {
+            Some(id) => id,
+            None => {
+                match state.options.dangling_ref_behavior {
+                    DecodeRefBehavior::KeepNull => {}
+                    DecodeRefBehavior::DropProperty => {
+                        instance.properties.remove(&rewrite.property_name);
+                    }
+                    DecodeRefBehavior::ErrorOnRef => {
+                        if let Some(error) = rewrite.unresolved_error {
+                            return Err(error);
+                        }
+                    }
+                }
+
+                if state.options.lenient {
+                    if let Some(error) = rewrite.unresolved_error {
+                        let warning = DecodeWarning::new(error);
+                        log::warn!("{}", warning);
+                        state.warnings.push(warning);
+                    }
+                }
+
+                continue;
+            }
+        };
+
         instance
             .properties
-            .insert(rewrite.property_name.clone(), Variant::Ref(new_value));
+            .insert(rewrite.property_name, Variant::Ref(new_value));
     }
+
+    Ok(())
 }
 
 fn apply_shared_string_rewrites(state: &mut ParseState) {
@@ -283,9 +898,10 @@ fn deserialize_root<R: Read>(
                         deserialize_instance(reader, state, parent_id)?;
                     }
                     "External" => {
-                        // This tag is always meaningless, there's nothing to do
-                        // here except skip it.
-                        reader.eat_unknown_tag()?;
+                        // This tag is meaningless, but we keep it around so
+                        // that it can be written back out.
+                        let contents = reader.read_tag_contents("External")?;
+                        state.metadata.push_external(contents);
                     }
                     "Meta" => {
                         deserialize_metadata(reader, state)?;
@@ -302,6 +918,11 @@ fn deserialize_root<R: Read>(
             XmlReadEvent::EndElement { name } => {
                 if name.local_name == "roblox" {
                     reader.expect_next().unwrap();
+
+                    for comment in reader.take_comments() {
+                        state.metadata.push_comment(comment);
+                    }
+
                     break;
                 } else {
                     let event = reader.expect_next().unwrap();
@@ -411,6 +1032,43 @@ fn deserialize_instance<R: Read>(
     state: &mut ParseState,
     parent_id: Ref,
 ) -> Result<(), DecodeError> {
+    let class_name = match reader.expect_peek()? {
+        XmlReadEvent::StartElement { attributes, .. } => attributes
+            .iter()
+            .find(|attribute| attribute.name.local_name == "class")
+            .map(|attribute| attribute.value.clone()),
+        _ => None,
+    };
+
+    if let Some(class_name) = class_name {
+        if state.options.use_reflection()
+            && !state
+                .options
+                .database
+                .classes
+                .contains_key(class_name.as_str())
+        {
+            match state.options.class_behavior {
+                DecodeClassBehavior::ReadUnknown => {}
+                DecodeClassBehavior::IgnoreUnknown => {
+                    return reader.eat_unknown_tag();
+                }
+                DecodeClassBehavior::ErrorOnUnknown => {
+                    return Err(reader.error(DecodeErrorKind::UnknownClass(class_name)));
+                }
+            }
+        }
+
+        if !state.options.includes_class(&class_name) {
+            return match state.options.filter_behavior {
+                DecodeFilterBehavior::SkipDescendants => reader.eat_unknown_tag(),
+                DecodeFilterBehavior::KeepDescendants => {
+                    deserialize_filtered_instance(reader, state, parent_id, class_name)
+                }
+            };
+        }
+    }
+
     let (class_name, referent) = {
         let attributes = reader.expect_start_with_name("Item")?;
 
@@ -433,11 +1091,20 @@ fn deserialize_instance<R: Read>(
 
     trace!("Class {} with referent {:?}", class_name, referent);
 
+    state.instance_path.push(class_name.clone());
+
     let builder = InstanceBuilder::new(class_name);
     let instance_id = state.tree.insert(parent_id, builder);
+    let leading_comments = reader.take_comments();
+
+    let referent_unique_id = if state.options.unique_id_referents {
+        referent.as_deref().and_then(parse_unique_id_referent)
+    } else {
+        None
+    };
 
     if let Some(referent) = referent {
-        state.referents_to_ids.insert(referent, instance_id);
+        add_referent(reader, state, referent, instance_id)?;
     }
 
     let mut properties: HashMap<String, Variant> = HashMap::new();
@@ -487,7 +1154,112 @@ fn deserialize_instance<R: Read>(
         None => instance.class.clone(),
     };
 
+    if state.options.preserve_comments {
+        let trailing_comments = reader.take_comments();
+
+        if !leading_comments.is_empty() || !trailing_comments.is_empty() {
+            let metadata = state.metadata.instance_mut(instance_id);
+
+            for comment in leading_comments {
+                metadata.push_leading_comment(comment);
+            }
+            for comment in trailing_comments {
+                metadata.push_trailing_comment(comment);
+            }
+        }
+    }
+
+    if let Some(unique_id) = referent_unique_id {
+        properties
+            .entry("UniqueId".to_owned())
+            .or_insert(Variant::UniqueId(unique_id));
+    }
+
     instance.properties = properties;
+    state.instance_path.pop();
+    state.instance_finished(reader.bytes_read());
+
+    Ok(())
+}
+
+/// Records that the instance with the given ID has the given referent,
+/// handling referents that are already taken as described by
+/// `DecodeOptions::duplicate_referent_behavior`.
+fn add_referent<R: Read>(
+    reader: &XmlEventReader<R>,
+    state: &mut ParseState,
+    referent: String,
+    instance_id: Ref,
+) -> Result<(), DecodeError> {
+    let referent = match state.referents_to_ids.entry(referent) {
+        Entry::Vacant(entry) => {
+            entry.insert(instance_id);
+            return Ok(());
+        }
+        Entry::Occupied(entry) => entry.key().clone(),
+    };
+
+    let error = reader.error(DecodeErrorKind::DuplicateReferent(referent.clone()));
+
+    match state.options.duplicate_referent_behavior {
+        DecodeDuplicateReferentBehavior::ErrorOnDuplicate => return Err(error),
+        DecodeDuplicateReferentBehavior::LastWins => {
+            state.referents_to_ids.insert(referent, instance_id);
+        }
+        DecodeDuplicateReferentBehavior::Renumber => {}
+    }
+
+    state.add_warning(error);
+
+    Ok(())
+}
+
+/// Reads an instance that was left out by `DecodeOptions::class_filter`,
+/// skipping its properties but still reading its children into `parent_id`.
+fn deserialize_filtered_instance<R: Read>(
+    reader: &mut XmlEventReader<R>,
+    state: &mut ParseState,
+    parent_id: Ref,
+    class_name: String,
+) -> Result<(), DecodeError> {
+    reader.expect_start_with_name("Item")?;
+
+    trace!("Skipping instance of filtered class {}", class_name);
+
+    state.instance_path.push(class_name);
+
+    loop {
+        match reader.expect_peek()? {
+            XmlReadEvent::StartElement { name, .. } => match name.local_name.as_str() {
+                "Properties" => {
+                    reader.eat_unknown_tag()?;
+                }
+                "Item" => {
+                    deserialize_instance(reader, state, parent_id)?;
+                }
+                _ => {
+                    let event = reader.expect_next().unwrap();
+                    return Err(reader.error(DecodeErrorKind::UnexpectedXmlEvent(event)));
+                }
+            },
+            XmlReadEvent::EndElement { name } => {
+                if name.local_name != "Item" {
+                    let event = reader.expect_next().unwrap();
+                    return Err(reader.error(DecodeErrorKind::UnexpectedXmlEvent(event)));
+                }
+
+                reader.expect_next().unwrap();
+
+                break;
+            }
+            _ => {
+                let event = reader.expect_next().unwrap();
+                return Err(reader.error(DecodeErrorKind::UnexpectedXmlEvent(event)));
+            }
+        }
+    }
+
+    state.instance_path.pop();
 
     Ok(())
 }
@@ -514,6 +1286,14 @@ fn deserialize_properties<R: Read>(
     );
 
     loop {
+        if state.current_property.take().as_deref() == Some("Name") {
+            if let (Some(Variant::String(name)), Some(path_name)) =
+                (props.get("Name"), state.instance_path.last_mut())
+            {
+                path_name.clone_from(name);
+            }
+        }
+
         let (xml_type_name, xml_property_name) = {
             match reader.expect_peek()? {
                 XmlReadEvent::StartElement {
@@ -551,6 +1331,11 @@ fn deserialize_properties<R: Read>(
             }
         };
 
+        // Peeking at the property's start tag leaves the reader positioned at
+        // it, which is where problems with its value are reported.
+        let property_position = reader.text_position();
+        state.current_property = Some(xml_property_name.clone());
+
         log::trace!(
             "Deserializing property {}.{}, of type {}",
             class_name,
@@ -558,19 +1343,30 @@ fn deserialize_properties<R: Read>(
             xml_type_name
         );
 
-        let maybe_descriptor = if state.options.use_reflection() {
-            find_canonical_property_descriptor(&class_name, &xml_property_name)
-        } else {
+        let maybe_descriptor = if !state.options.use_reflection() {
             None
+        } else if state.options.canonical_property_names {
+            find_canonical_property_descriptor(
+                &class_name,
+                &xml_property_name,
+                state.options.database,
+            )
+        } else {
+            find_exact_property_descriptor(&class_name, &xml_property_name, state.options.database)
         };
 
         if let Some(descriptor) = maybe_descriptor {
-            let value =
-                match read_value_xml(reader, state, &xml_type_name, instance_id, &descriptor.name)?
-                {
-                    Some(value) => value,
-                    None => continue,
-                };
+            let value = match read_property_value(
+                reader,
+                state,
+                &xml_type_name,
+                instance_id,
+                &class_name,
+                &descriptor.name,
+            )? {
+                Some(value) => value,
+                None => continue,
+            };
 
             let xml_ty = value.ty();
 
@@ -589,22 +1385,98 @@ fn deserialize_properties<R: Read>(
                 _ => unimplemented!(),
             };
 
-            let value = match value.try_convert(expected_type) {
-                Ok(value) => value,
-
-                // The property descriptor disagreed, and there was no
-                // conversion available. This is always an error.
-                Err(message) => {
-                    return Err(
-                        reader.error(DecodeErrorKind::UnsupportedPropertyConversion {
+            // Converting by reference lets us keep the original value around
+            // in case the conversion fails.
+            let converted = value
+                .try_convert_ref(expected_type)
+                .map(|converted| match converted {
+                    Cow::Owned(converted) => Some(converted),
+                    Cow::Borrowed(_) => None,
+                });
+
+            let value = match converted {
+                Ok(Some(converted)) => converted,
+                Ok(None) => value,
+
+                // Attributes can contain value types we don't understand
+                // yet. Rather than losing the whole file, keep the raw blob
+                // so that it can be written back out unchanged.
+                Err(message)
+                    if expected_type == VariantType::Attributes && !state.options.strict_types =>
+                {
+                    state.add_warning(reader.error(
+                        DecodeErrorKind::UnsupportedPropertyConversion {
                             class_name: class_name.clone(),
                             property_name: descriptor.name.to_string(),
                             expected_type,
                             actual_type: xml_ty,
                             message,
-                        }),
-                    );
+                        },
+                    ));
+                    props.insert(descriptor.name.to_string(), value);
+                    continue;
                 }
+
+                // The property descriptor disagreed, and there was no
+                // conversion available.
+                Err(message) => match state.mismatch_behavior() {
+                    DecodeMismatchBehavior::ReadOriginal => {
+                        props.insert(descriptor.name.to_string(), value);
+                        continue;
+                    }
+                    DecodeMismatchBehavior::IgnoreMismatch => continue,
+                    DecodeMismatchBehavior::ErrorOnMismatch => {
+                        return Err(
+                            reader.error(DecodeErrorKind::UnsupportedPropertyConversion {
+                                class_name: class_name.clone(),
+                                property_name: descriptor.name.to_string(),
+                                expected_type,
+                                actual_type: xml_ty,
+                                message,
+                            }),
+                        );
+                    }
+                },
+            };
+
+            if state.options.strict_types && value.ty() != expected_type {
+                return Err(reader.error(DecodeErrorKind::PropertyTypeMismatch {
+                    class_name: class_name.clone(),
+                    property_name: descriptor.name.to_string(),
+                    expected_type,
+                    actual_type: value.ty(),
+                }));
+            }
+
+            let value = match (&descriptor.data_type, &value) {
+                (DataType::Enum(enum_name), Variant::Enum(enum_value)) if state.is_lenient() => {
+                    let is_valid = match state.options.database.enums.get(enum_name.as_ref()) {
+                        Some(enum_descriptor) => enum_descriptor
+                            .items
+                            .values()
+                            .any(|&item| item == enum_value.to_u32()),
+                        None => true,
+                    };
+
+                    if is_valid {
+                        value
+                    } else {
+                        let error = DecodeError::new_from_position(
+                            DecodeErrorKind::InvalidEnumValue {
+                                enum_name: enum_name.to_string(),
+                                value: enum_value.to_u32(),
+                            },
+                            property_position,
+                        );
+                        state.add_warning(error);
+
+                        match state.default_value(&class_name, &descriptor.name) {
+                            Some(default) => default,
+                            None => continue,
+                        }
+                    }
+                }
+                _ => value,
             };
 
             match &descriptor.kind {
@@ -614,14 +1486,31 @@ fn deserialize_properties<R: Read>(
                             property,
                             migration,
                         },
-                } => {
+                } if state.options.canonical_property_names && state.options.migrate_properties => {
                     if !props.contains_key(property.as_ref()) {
                         match perform_migration(*migration, &value) {
                             Ok(migrated_value) => {
+                                state.add_warning(DecodeError::new_from_position(
+                                    DecodeErrorKind::MigratedProperty {
+                                        class_name: class_name.clone(),
+                                        property_name: descriptor.name.to_string(),
+                                        migrated_to: property.to_string(),
+                                    },
+                                    property_position,
+                                ));
                                 props.insert(property.to_string(), migrated_value);
                             }
                             Err(error) => {
-                                return Err(reader.error(DecodeErrorKind::MigrationError(error)));
+                                let error = DecodeError::new_from_position(
+                                    DecodeErrorKind::MigrationError(error),
+                                    property_position,
+                                );
+
+                                if !state.is_lenient() {
+                                    return Err(error);
+                                }
+
+                                state.add_warning(error);
                             }
                         }
                     }
@@ -636,11 +1525,12 @@ fn deserialize_properties<R: Read>(
                     // We don't care about this property, so we can read it and
                     // throw it into the void.
 
-                    read_value_xml(
+                    read_property_value(
                         reader,
                         state,
                         &xml_type_name,
                         instance_id,
+                        &class_name,
                         &xml_property_name,
                     )?;
                 }
@@ -648,11 +1538,12 @@ fn deserialize_properties<R: Read>(
                     // We'll take this value as-is with no conversions on either
                     // the name or value.
 
-                    let value = match read_value_xml(
+                    let value = match read_property_value(
                         reader,
                         state,
                         &xml_type_name,
                         instance_id,
+                        &class_name,
                         &xml_property_name,
                     )? {
                         Some(value) => value,
@@ -670,3 +1561,37 @@ fn deserialize_properties<R: Read>(
         }
     }
 }
+
+/// Reads the value of a property. In lenient mode, a value that can't be read
+/// is reported as a warning and replaced with the property's default value, or
+/// skipped if it doesn't have one.
+fn read_property_value<R: Read>(
+    reader: &mut XmlEventReader<R>,
+    state: &mut ParseState,
+    xml_type_name: &str,
+    instance_id: Ref,
+    class_name: &str,
+    property_name: &str,
+) -> Result<Option<Variant>, DecodeError> {
+    if !state.is_lenient() || !is_plain_type(xml_type_name) {
+        return read_value_xml(reader, state, xml_type_name, instance_id, property_name);
+    }
+
+    let position = reader.text_position();
+
+    // Reading the whole element before trying to decode it means that the
+    // reader is left in the right place even if the value is invalid.
+    let xml = reader.read_raw_element()?;
+    let mut value_reader = XmlEventReader::from_source(xml.as_slice());
+    let value = value_reader
+        .expect_next()
+        .and_then(|_start_document| read_plain_value_xml(&mut value_reader, xml_type_name));
+
+    match value {
+        Ok(value) => Ok(value),
+        Err(error) => {
+            state.add_warning(error.with_position(position));
+            Ok(state.default_value(class_name, property_name))
+        }
+    }
+}
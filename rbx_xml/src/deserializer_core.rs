@@ -1,7 +1,12 @@
-use std::io::Read;
+use std::io::{self, Read};
 
 use log::trace;
-use xml::{attribute::OwnedAttribute, reader::ParserConfig};
+use xml::{
+    attribute::OwnedAttribute,
+    common::{Position, TextPosition},
+    reader::ParserConfig,
+    writer::EmitterConfig,
+};
 
 use crate::{
     core::XmlType,
@@ -12,11 +17,223 @@ pub use xml::reader::Error as XmlReadError;
 pub use xml::reader::XmlEvent as XmlReadEvent;
 pub type XmlReadResult = Result<XmlReadEvent, XmlReadError>;
 
+/// A declaration that's put in front of documents that don't have one.
+///
+/// Roblox Studio writes characters that XML 1.0 doesn't allow, like control
+/// characters in scripts, as-is inside of CDATA sections. XML 1.1 allows them,
+/// so documents that don't declare a version of their own are read as XML 1.1.
+/// This is on the same line as the start of the document so that line numbers
+/// stay the same.
+const XML_1_1_DECLARATION: &[u8] = b"<?xml version=\"1.1\"?>";
+
+/// The UTF-8 byte order mark, which has to come before any declaration.
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
+
+/// The number of bytes read from the start of a document to detect its
+/// encoding and whether it has a declaration. This is enough for a UTF-16 byte
+/// order mark followed by `<?xml`.
+const START_LEN: usize = 12;
+
+/// The number of bytes read at a time from UTF-16 documents to transcode.
+const UTF16_CHUNK_LEN: usize = 8 * 1024;
+
+#[derive(Debug, Clone, Copy)]
+enum Utf16Order {
+    LittleEndian,
+    BigEndian,
+}
+
+/// A wrapper around a source that puts `XML_1_1_DECLARATION` in front of it if
+/// it doesn't start with a declaration.
+///
+/// Some tools write documents in UTF-16, which are transcoded to UTF-8 first
+/// so that the declaration can be added the same way. These are detected by
+/// their byte order mark, or by the first character being an ASCII `<`.
+struct VersionedSource<R> {
+    inner: R,
+
+    /// Output that has been produced but not read yet, like the declaration
+    /// or transcoded text.
+    buffer: Vec<u8>,
+    buffer_offset: usize,
+
+    started: bool,
+    declaration_added: bool,
+    bytes_read: u64,
+
+    /// The byte order of the document if it's UTF-16, in which case it's
+    /// transcoded as it's read.
+    utf16_order: Option<Utf16Order>,
+
+    /// Bytes at the end of the last chunk of a UTF-16 document that weren't a
+    /// whole character.
+    utf16_leftover: Vec<u8>,
+}
+
+impl<R: Read> VersionedSource<R> {
+    fn new(inner: R) -> Self {
+        VersionedSource {
+            inner,
+            buffer: Vec::new(),
+            buffer_offset: 0,
+            started: false,
+            declaration_added: false,
+            bytes_read: 0,
+            utf16_order: None,
+            utf16_leftover: Vec::new(),
+        }
+    }
+
+    fn start(&mut self) -> io::Result<()> {
+        let mut start = [0; START_LEN];
+        let len = self.read_inner(&mut start)?;
+        let start = &start[..len];
+
+        let (bom, rest) = if let Some(rest) = start.strip_prefix(UTF8_BOM) {
+            (UTF8_BOM, rest.to_vec())
+        } else if let Some(order) = detect_utf16(start) {
+            self.utf16_order = Some(order);
+
+            let rest = match start {
+                [0xFF, 0xFE, rest @ ..] | [0xFE, 0xFF, rest @ ..] => rest,
+                _ => start,
+            };
+
+            let mut transcoded = Vec::new();
+            self.transcode_utf16(rest, &mut transcoded)?;
+
+            (&[][..], transcoded)
+        } else {
+            (&[][..], start.to_vec())
+        };
+
+        self.buffer.extend_from_slice(bom);
+        if !rest.starts_with(b"<?xml") {
+            self.buffer.extend_from_slice(XML_1_1_DECLARATION);
+            self.declaration_added = true;
+        }
+        self.buffer.extend_from_slice(&rest);
+
+        self.started = true;
+        Ok(())
+    }
+
+    /// Fills as much of `buf` as possible from the inner source, stopping
+    /// early only at the end of the source.
+    fn read_inner(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut len = 0;
+
+        while len < buf.len() {
+            match self.inner.read(&mut buf[len..]) {
+                Ok(0) => break,
+                Ok(read) => len += read,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+
+        self.bytes_read += len as u64;
+        Ok(len)
+    }
+
+    /// Transcodes a chunk of a UTF-16 document to UTF-8, keeping any partial
+    /// character at the end for the next chunk.
+    fn transcode_utf16(&mut self, chunk: &[u8], output: &mut Vec<u8>) -> io::Result<()> {
+        let order = self.utf16_order.unwrap();
+
+        let mut bytes = std::mem::take(&mut self.utf16_leftover);
+        bytes.extend_from_slice(chunk);
+
+        let mut units: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|pair| match order {
+                Utf16Order::LittleEndian => u16::from_le_bytes([pair[0], pair[1]]),
+                Utf16Order::BigEndian => u16::from_be_bytes([pair[0], pair[1]]),
+            })
+            .collect();
+
+        // A high surrogate at the end is the first half of a character
+        // that's finished in the next chunk.
+        if let Some(0xD800..=0xDBFF) = units.last() {
+            units.pop();
+        }
+
+        self.utf16_leftover = bytes[units.len() * 2..].to_vec();
+
+        let mut encoded = [0; 4];
+        for c in char::decode_utf16(units) {
+            let c = c.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            output.extend_from_slice(c.encode_utf8(&mut encoded).as_bytes());
+        }
+
+        Ok(())
+    }
+}
+
+/// Detects whether the start of a document is UTF-16, and in which byte order.
+fn detect_utf16(start: &[u8]) -> Option<Utf16Order> {
+    match start {
+        [0xFF, 0xFE, ..] | [b'<', 0, ..] => Some(Utf16Order::LittleEndian),
+        [0xFE, 0xFF, ..] | [0, b'<', ..] => Some(Utf16Order::BigEndian),
+        _ => None,
+    }
+}
+
+impl<R: Read> Read for VersionedSource<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if !self.started {
+            self.start()?;
+        }
+
+        loop {
+            if self.buffer_offset < self.buffer.len() {
+                let remaining = &self.buffer[self.buffer_offset..];
+                let len = remaining.len().min(buf.len());
+                buf[..len].copy_from_slice(&remaining[..len]);
+                self.buffer_offset += len;
+
+                return Ok(len);
+            }
+
+            if self.utf16_order.is_none() {
+                let len = self.inner.read(buf)?;
+                self.bytes_read += len as u64;
+
+                return Ok(len);
+            }
+
+            let mut chunk = vec![0; UTF16_CHUNK_LEN];
+            let len = self.read_inner(&mut chunk)?;
+
+            if len == 0 {
+                if !self.utf16_leftover.is_empty() {
+                    return Err(io::Error::new(
+                        io::ErrorKind::UnexpectedEof,
+                        "document ended in the middle of a UTF-16 character",
+                    ));
+                }
+
+                return Ok(0);
+            }
+
+            let mut transcoded = std::mem::take(&mut self.buffer);
+            transcoded.clear();
+            self.buffer_offset = 0;
+            self.transcode_utf16(&chunk[..len], &mut transcoded)?;
+            self.buffer = transcoded;
+        }
+    }
+}
+
 /// A wrapper around an XML event iterator created by xml-rs.
 pub struct XmlEventReader<R: Read> {
-    reader: xml::EventReader<R>,
+    reader: xml::EventReader<VersionedSource<R>>,
     peeked: Option<Result<XmlReadEvent, xml::reader::Error>>,
     finished: bool,
+
+    /// The comments that have been read since they were last taken, if
+    /// comments are being collected. Comments are never returned as events.
+    comments: Option<Vec<String>>,
 }
 
 impl<R: Read> Iterator for XmlEventReader<R> {
@@ -35,6 +252,12 @@ impl<R: Read> Iterator for XmlEventReader<R> {
             match self.reader.next() {
                 Ok(item) => match item {
                     XmlReadEvent::Whitespace(_) => continue,
+                    XmlReadEvent::Comment(comment) => {
+                        if let Some(comments) = &mut self.comments {
+                            comments.push(comment);
+                        }
+                        continue;
+                    }
                     XmlReadEvent::EndDocument => {
                         self.finished = true;
                         return Some(Ok(item));
@@ -53,17 +276,37 @@ impl<R: Read> Iterator for XmlEventReader<R> {
 impl<R: Read> XmlEventReader<R> {
     /// Constructs a new `XmlEventReader` from a source that implements `Read`.
     pub fn from_source(source: R) -> XmlEventReader<R> {
+        // UTF-16 documents are transcoded to UTF-8, but still declare that
+        // they're UTF-16, so declarations that conflict with the encoding
+        // detected from the document itself are ignored.
         let reader = ParserConfig::new()
-            .ignore_comments(true)
-            .create_reader(source);
+            .ignore_comments(false)
+            .ignore_invalid_encoding_declarations(true)
+            .create_reader(VersionedSource::new(source));
 
         XmlEventReader {
             reader,
             peeked: None,
             finished: false,
+            comments: None,
         }
     }
 
+    /// Starts keeping the comments in the document so that they can be
+    /// retrieved with `take_comments`.
+    pub fn collect_comments(&mut self) {
+        self.comments.get_or_insert_with(Vec::new);
+    }
+
+    /// Returns the comments read since the last call to this method. This
+    /// includes any comments that came before a peeked event.
+    pub fn take_comments(&mut self) -> Vec<String> {
+        self.comments
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
     /// Borrows the next element from the event stream without consuming it.
     pub fn peek(&mut self) -> Option<&XmlReadResult> {
         if self.peeked.is_some() {
@@ -75,7 +318,26 @@ impl<R: Read> XmlEventReader<R> {
     }
 
     pub(crate) fn error<T: Into<DecodeErrorKind>>(&self, kind: T) -> NewDecodeError {
-        NewDecodeError::new_from_reader(kind.into(), &self.reader)
+        NewDecodeError::new_from_position(kind.into(), self.text_position())
+    }
+
+    /// The position in the document of the most recently read event.
+    pub fn text_position(&self) -> TextPosition {
+        let mut position = self.reader.position();
+
+        // The declaration we added isn't part of the document.
+        if position.row == 0 && self.reader.source().declaration_added {
+            position.column = position
+                .column
+                .saturating_sub(XML_1_1_DECLARATION.len() as u64);
+        }
+
+        position
+    }
+
+    /// The number of bytes that have been read from the source so far.
+    pub fn bytes_read(&self) -> u64 {
+        self.reader.source().bytes_read
     }
 
     pub fn expect_next(&mut self) -> Result<XmlReadEvent, NewDecodeError> {
@@ -272,6 +534,38 @@ impl<R: Read> XmlEventReader<R> {
         Ok(value)
     }
 
+    /// Consume events from the iterator until we reach the end of the next tag,
+    /// returning the XML text of the tag and all of its contents.
+    pub fn read_raw_element(&mut self) -> Result<Vec<u8>, NewDecodeError> {
+        let mut output = Vec::new();
+        let mut writer = EmitterConfig::new()
+            .write_document_declaration(false)
+            .normalize_empty_elements(false)
+            .create_writer(&mut output);
+
+        let mut depth = 0;
+
+        loop {
+            let event = self.expect_next()?;
+
+            match &event {
+                XmlReadEvent::StartElement { .. } => depth += 1,
+                XmlReadEvent::EndElement { .. } => depth -= 1,
+                _ => {}
+            }
+
+            if let Some(writer_event) = event.as_writer_event() {
+                writer.write(writer_event).unwrap();
+            }
+
+            if depth == 0 {
+                break;
+            }
+        }
+
+        Ok(output)
+    }
+
     /// Consume events from the iterator until we reach the end of the next tag.
     pub fn eat_unknown_tag(&mut self) -> Result<(), NewDecodeError> {
         let mut depth = 0;
@@ -1,9 +1,11 @@
 use std::{
     fmt,
-    io::{self, Read, Write},
+    io::{self, Write},
+    path::{Path, PathBuf},
 };
 
-use rbx_dom_weak::types::VariantType;
+use rbx_dom_weak::types::{Ref, VariantType};
+use xml::common::TextPosition;
 
 /// An error that can occur when deserializing an XML-format model or place.
 #[derive(Debug)]
@@ -15,23 +17,37 @@ pub struct DecodeError {
 }
 
 impl DecodeError {
-    pub(crate) fn new_from_reader<R: Read>(
-        kind: DecodeErrorKind,
-        reader: &xml::EventReader<R>,
-    ) -> DecodeError {
-        use xml::common::Position;
-
-        let pos = reader.position();
+    /// Creates an error that didn't occur at any particular location in the
+    /// document.
+    pub(crate) fn new(kind: DecodeErrorKind) -> DecodeError {
+        DecodeError::new_from_position(kind, TextPosition::new())
+    }
 
+    pub(crate) fn new_from_position(kind: DecodeErrorKind, pos: TextPosition) -> DecodeError {
         DecodeError {
             inner: Box::new(DecodeErrorImpl {
                 kind,
                 line: (pos.row + 1) as usize,
                 column: pos.column as usize,
+                instance_path: Vec::new(),
+                property_name: None,
+                file_path: None,
             }),
         }
     }
 
+    /// Attaches the instance and property that were being decoded when this
+    /// error occured.
+    pub(crate) fn with_context(
+        mut self,
+        instance_path: Vec<String>,
+        property_name: Option<String>,
+    ) -> DecodeError {
+        self.inner.instance_path = instance_path;
+        self.inner.property_name = property_name;
+        self
+    }
+
     /// 1-based line number in the document where the error occured.
     pub fn line(&self) -> usize {
         self.inner.line
@@ -41,15 +57,74 @@ impl DecodeError {
     pub fn column(&self) -> usize {
         self.inner.column
     }
+
+    /// The names of the instance that was being decoded when the error
+    /// occured and each of its ancestors, starting from the top-level
+    /// instance. Instances whose names had not been read yet are identified by
+    /// their class name instead.
+    ///
+    /// This is empty if the error occured outside of any instance.
+    pub fn instance_path(&self) -> &[String] {
+        &self.inner.instance_path
+    }
+
+    /// The name of the property that was being decoded when the error
+    /// occured, if any.
+    pub fn property_name(&self) -> Option<&str> {
+        self.inner.property_name.as_deref()
+    }
+
+    /// The path of the file that was being decoded when the error occured, if
+    /// it was decoded with [`decode_file`][crate::decode_file].
+    pub fn file_path(&self) -> Option<&Path> {
+        self.inner.file_path.as_deref()
+    }
+
+    pub(crate) fn with_file_path(mut self, path: &Path) -> DecodeError {
+        self.inner.file_path = Some(path.to_owned());
+        self
+    }
+
+    /// Changes where in the document this error is reported to have occured.
+    pub(crate) fn with_position(mut self, pos: TextPosition) -> DecodeError {
+        self.inner.line = (pos.row + 1) as usize;
+        self.inner.column = pos.column as usize;
+        self
+    }
 }
 
 impl fmt::Display for DecodeError {
     fn fmt(&self, output: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(file_path) = &self.inner.file_path {
+            write!(output, "{}: ", file_path.display())?;
+        }
+
+        // Errors with the file itself don't have a meaningful location.
+        if let DecodeErrorKind::Io(_) | DecodeErrorKind::BinaryFileExtension(_) = &self.inner.kind {
+            return write!(output, "{}", self.inner.kind);
+        }
+
+        // Neither do errors from not being run inside of a tokio runtime.
+        #[cfg(feature = "tokio")]
+        if let DecodeErrorKind::NoTokioRuntime = &self.inner.kind {
+            return write!(output, "{}", self.inner.kind);
+        }
+
         write!(
             output,
-            "line {}, column {}: {}",
-            self.inner.line, self.inner.column, self.inner.kind
-        )
+            "line {}, column {}",
+            self.inner.line, self.inner.column
+        )?;
+
+        if !self.inner.instance_path.is_empty() {
+            write!(output, ", in {}", self.inner.instance_path.join("."))?;
+        }
+
+        if let Some(property_name) = &self.inner.property_name {
+            write!(output, ", property {}", property_name)?;
+        }
+
+        write!(output, ": {}", self.inner.kind)
     }
 }
 
@@ -59,16 +134,61 @@ impl std::error::Error for DecodeError {
     }
 }
 
+/// A problem that was recovered from while decoding an XML-format model or
+/// place with [`DecodeOptions::lenient`][crate::DecodeOptions::lenient].
+#[derive(Debug)]
+pub struct DecodeWarning {
+    error: DecodeError,
+}
+
+impl DecodeWarning {
+    pub(crate) fn new(error: DecodeError) -> DecodeWarning {
+        DecodeWarning { error }
+    }
+
+    /// 1-based line number in the document where the problem occured.
+    pub fn line(&self) -> usize {
+        self.error.line()
+    }
+
+    /// 1-based column number in the document where the problem occured.
+    pub fn column(&self) -> usize {
+        self.error.column()
+    }
+
+    /// The names of the instance that was being decoded when the problem
+    /// occured and each of its ancestors. See [`DecodeError::instance_path`].
+    pub fn instance_path(&self) -> &[String] {
+        self.error.instance_path()
+    }
+
+    /// The name of the property that was being decoded when the problem
+    /// occured, if any.
+    pub fn property_name(&self) -> Option<&str> {
+        self.error.property_name()
+    }
+}
+
+impl fmt::Display for DecodeWarning {
+    fn fmt(&self, output: &mut fmt::Formatter) -> fmt::Result {
+        write!(output, "{}", self.error)
+    }
+}
+
 #[derive(Debug)]
 struct DecodeErrorImpl {
     kind: DecodeErrorKind,
     line: usize,
     column: usize,
+    instance_path: Vec<String>,
+    property_name: Option<String>,
+    file_path: Option<PathBuf>,
 }
 
 #[derive(Debug)]
 pub(crate) enum DecodeErrorKind {
     // Errors from other crates
+    Io(io::Error),
     Xml(xml::reader::Error),
     ParseFloat(std::num::ParseFloatError),
     ParseInt(std::num::ParseIntError),
@@ -79,7 +199,9 @@ pub(crate) enum DecodeErrorKind {
     WrongDocVersion(String),
     UnexpectedEof,
     UnexpectedXmlEvent(xml::reader::XmlEvent),
+    MalformedXml(String),
     MissingAttribute(&'static str),
+    UnknownClass(String),
     UnknownProperty {
         class_name: String,
         property_name: String,
@@ -93,10 +215,31 @@ pub(crate) enum DecodeErrorKind {
         actual_type: VariantType,
         message: String,
     },
+    PropertyTypeMismatch {
+        class_name: String,
+        property_name: String,
+        expected_type: VariantType,
+        actual_type: VariantType,
+    },
     InvalidPropertyData {
         property_type: &'static str,
         error: String,
     },
+    InvalidEnumValue {
+        enum_name: String,
+        value: u32,
+    },
+    MigratedProperty {
+        class_name: String,
+        property_name: String,
+        migrated_to: String,
+    },
+    UnresolvedReferent(String),
+    DuplicateReferent(String),
+    NullReferent,
+    BinaryFileExtension(String),
+    #[cfg(feature = "tokio")]
+    NoTokioRuntime,
 }
 
 impl fmt::Display for DecodeErrorKind {
@@ -104,6 +247,7 @@ impl fmt::Display for DecodeErrorKind {
         use self::DecodeErrorKind::*;
 
         match self {
+            Io(err) => write!(output, "{}", err),
             Xml(err) => write!(output, "{}", err),
             ParseFloat(err) => write!(output, "{}", err),
             ParseInt(err) => write!(output, "{}", err),
@@ -115,9 +259,11 @@ impl fmt::Display for DecodeErrorKind {
             }
             UnexpectedEof => write!(output, "Unexpected end-of-file"),
             UnexpectedXmlEvent(event) => write!(output, "Unexpected XML event {:?}", event),
+            MalformedXml(explain) => write!(output, "Malformed XML: {}", explain),
             MissingAttribute(attribute_name) => {
                 write!(output, "Missing attribute '{}'", attribute_name)
             }
+            UnknownClass(class_name) => write!(output, "Class {} is unknown", class_name),
             UnknownProperty {
                 class_name,
                 property_name,
@@ -144,6 +290,17 @@ impl fmt::Display for DecodeErrorKind {
                  When trying to convert, this error occured: {}",
                 class_name, property_name, expected_type, actual_type, message
             ),
+            PropertyTypeMismatch {
+                class_name,
+                property_name,
+                expected_type,
+                actual_type,
+            } => write!(
+                output,
+                "Property {}.{} is declared as type {:?} in the reflection database, \
+                 but it was of type {:?}",
+                class_name, property_name, expected_type, actual_type
+            ),
             InvalidPropertyData {
                 property_type,
                 error,
@@ -151,6 +308,43 @@ impl fmt::Display for DecodeErrorKind {
                 output,
                 "Could not decode property of type {property_type} because: {error}"
             ),
+            InvalidEnumValue { enum_name, value } => {
+                write!(
+                    output,
+                    "{} is not a valid value of enum {}",
+                    value, enum_name
+                )
+            }
+            MigratedProperty {
+                class_name,
+                property_name,
+                migrated_to,
+            } => write!(
+                output,
+                "Property {}.{} is deprecated and was migrated to {}",
+                class_name, property_name, migrated_to
+            ),
+            UnresolvedReferent(referent) => write!(
+                output,
+                "Referent '{}' does not refer to any instance in the document",
+                referent
+            ),
+            DuplicateReferent(referent) => write!(
+                output,
+                "More than one instance in the document has the referent '{}'",
+                referent
+            ),
+            NullReferent => write!(output, "Ref property is null"),
+            BinaryFileExtension(extension) => write!(
+                output,
+                "Files with the extension '{}' use Roblox's binary format, which rbx_xml can't read",
+                extension
+            ),
+            #[cfg(feature = "tokio")]
+            NoTokioRuntime => write!(
+                output,
+                "from_async_reader must be run inside of a tokio runtime"
+            ),
         }
     }
 }
@@ -160,6 +354,7 @@ impl std::error::Error for DecodeErrorKind {
         use self::DecodeErrorKind::*;
 
         match self {
+            Io(err) => Some(err),
             Xml(err) => Some(err),
             ParseFloat(err) => Some(err),
             ParseInt(err) => Some(err),
@@ -199,7 +394,7 @@ impl From<base64::DecodeError> for DecodeErrorKind {
 #[derive(Debug)]
 pub struct EncodeError {
     // This Box helps reduce the size of EncodeError a lot, which is important.
-    kind: Box<EncodeErrorKind>,
+    inner: Box<EncodeErrorImpl>,
 }
 
 impl EncodeError {
@@ -207,24 +402,52 @@ impl EncodeError {
         kind: EncodeErrorKind,
         _writer: &xml::EventWriter<W>,
     ) -> EncodeError {
+        EncodeError::new(kind)
+    }
+
+    pub(crate) fn new(kind: EncodeErrorKind) -> EncodeError {
         EncodeError {
-            kind: Box::new(kind),
+            inner: Box::new(EncodeErrorImpl {
+                kind,
+                file_path: None,
+            }),
         }
     }
+
+    /// The path of the file that was being encoded when the error occured, if
+    /// it was encoded with [`encode_file`][crate::encode_file].
+    pub fn file_path(&self) -> Option<&Path> {
+        self.inner.file_path.as_deref()
+    }
+
+    pub(crate) fn with_file_path(mut self, path: &Path) -> EncodeError {
+        self.inner.file_path = Some(path.to_owned());
+        self
+    }
 }
 
 impl fmt::Display for EncodeError {
     fn fmt(&self, output: &mut fmt::Formatter) -> fmt::Result {
-        write!(output, "{}", self.kind)
+        if let Some(file_path) = &self.inner.file_path {
+            write!(output, "{}: ", file_path.display())?;
+        }
+
+        write!(output, "{}", self.inner.kind)
     }
 }
 
 impl std::error::Error for EncodeError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-        self.kind.source()
+        self.inner.kind.source()
     }
 }
 
+#[derive(Debug)]
+struct EncodeErrorImpl {
+    kind: EncodeErrorKind,
+    file_path: Option<PathBuf>,
+}
+
 #[derive(Debug)]
 pub(crate) enum EncodeErrorKind {
     Io(io::Error),
@@ -236,6 +459,7 @@ pub(crate) enum EncodeErrorKind {
         property_name: String,
     },
     UnsupportedPropertyType(VariantType),
+    InvalidRawXml(xml::reader::Error),
     UnsupportedPropertyConversion {
         class_name: String,
         property_name: String,
@@ -243,6 +467,18 @@ pub(crate) enum EncodeErrorKind {
         actual_type: VariantType,
         message: String,
     },
+    BinaryFileExtension(String),
+    InvalidChar(char),
+    UnknownRoot(Ref),
+    DuplicateRoot(Ref),
+    NestedRoot {
+        id: Ref,
+        ancestor: Ref,
+    },
+    NotAService(String),
+    DuplicateService(String),
+    #[cfg(feature = "tokio")]
+    NoTokioRuntime,
 }
 
 impl fmt::Display for EncodeErrorKind {
@@ -265,6 +501,7 @@ impl fmt::Display for EncodeErrorKind {
             UnsupportedPropertyType(ty) => {
                 write!(output, "Properties of type {:?} cannot be encoded yet", ty)
             }
+            InvalidRawXml(err) => write!(output, "Invalid raw property XML: {}", err),
             UnsupportedPropertyConversion {
                 class_name,
                 property_name,
@@ -277,6 +514,40 @@ impl fmt::Display for EncodeErrorKind {
                  When trying to convert the value, this error occured: {}",
                 class_name, property_name, expected_type, actual_type, message
             ),
+            BinaryFileExtension(extension) => write!(
+                output,
+                "Files with the extension '{}' use Roblox's binary format, which rbx_xml can't write",
+                extension
+            ),
+            InvalidChar(c) => write!(
+                output,
+                "Strings containing the character U+{:04X} can't be written",
+                *c as u32
+            ),
+            UnknownRoot(id) => write!(output, "Instance {} is not in the tree", id),
+            DuplicateRoot(id) => write!(
+                output,
+                "Instance {} was given more than once as a top-level instance",
+                id
+            ),
+            NestedRoot { id, ancestor } => write!(
+                output,
+                "Instance {} was given as a top-level instance along with its ancestor {}",
+                id, ancestor
+            ),
+            NotAService(class_name) => write!(
+                output,
+                "{} can't be a top-level instance of a place because it isn't a service",
+                class_name
+            ),
+            DuplicateService(class_name) => {
+                write!(output, "A place can only have one top-level {}", class_name)
+            }
+            #[cfg(feature = "tokio")]
+            NoTokioRuntime => write!(
+                output,
+                "to_async_writer must be run inside of a tokio runtime"
+            ),
         }
     }
 }
@@ -289,10 +560,20 @@ impl std::error::Error for EncodeErrorKind {
             Io(err) => Some(err),
             Xml(err) => Some(err),
             Type(err) => Some(err),
+            InvalidRawXml(err) => Some(err),
 
             UnknownProperty { .. }
             | UnsupportedPropertyType(_)
-            | UnsupportedPropertyConversion { .. } => None,
+            | UnsupportedPropertyConversion { .. }
+            | BinaryFileExtension(_)
+            | InvalidChar(_)
+            | UnknownRoot(_)
+            | DuplicateRoot(_)
+            | NestedRoot { .. }
+            | NotAService(_)
+            | DuplicateService(_) => None,
+            #[cfg(feature = "tokio")]
+            NoTokioRuntime => None,
         }
     }
 }
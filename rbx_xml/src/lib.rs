@@ -11,7 +11,8 @@
 //! [`from_reader_default`][from_reader_default] if you're reading from a file,
 //! or [`from_str_default`][from_str_default] if you already have a string.
 //! These methods also have variants like [`from_str`][from_str] that let you
-//! pass in custom options.
+//! pass in custom options. [`decode_str`][decode_str] is the counterpart to
+//! [`encode_to_string`][encode_to_string] for documents kept in memory.
 //!
 //! ```
 //! use rbx_dom_weak::types::Variant;
@@ -40,8 +41,10 @@
 //! # Ok::<(), Box<dyn std::error::Error>>(())
 //! ```
 //!
-//! If you're decoding from a file, you'll want to do your own I/O buffering,
-//! like with [`BufReader`][BufReader]:
+//! To decode a file, use [`decode_file`][decode_file], which takes care of
+//! opening and buffering it. If you're reading from a file some other way,
+//! you'll want to do your own I/O buffering, like with
+//! [`BufReader`][BufReader]:
 //!
 //! ```no_run
 //! use std::{
@@ -54,6 +57,14 @@
 //! # Ok::<(), Box<dyn std::error::Error>>(())
 //! ```
 //!
+//! Input that's already in memory, like a memory-mapped file, can be passed
+//! to [`from_reader`][from_reader] as a byte slice. rbx_xml reads slices
+//! directly, so the input isn't copied into another buffer first. Decoded
+//! values are still copied out of the input, since every value in a `WeakDom`
+//! is owned by the tree. To look at a document without copying out of it,
+//! use [`SliceDecoder`], which borrows names and text from the input and only
+//! decodes the values that are asked for.
+//!
 //! Note that the `WeakDom` instance returned by the rbx_xml decode methods will
 //! have a root instance with the class name `DataModel`. This is great for
 //! deserializing a place, but kind of strange for deserializing a model.
@@ -63,9 +74,16 @@
 //! crate instead always creates a top-level `DataModel` instance which is
 //! pretty close to free.
 //!
+//! To get the top-level instances directly, use
+//! [`from_reader_document`][from_reader_document], whose result lists them
+//! and has a [`single_root`][DecodedDocument::single_root] helper for models
+//! that are expected to hold exactly one instance.
+//!
 //! ## Serialization
 //! To serialize an existing `WeakDom` instance, use methods like
 //! [`to_writer_default`][to_writer_default] or [`to_writer`][to_writer].
+//! To get the document in memory instead, use
+//! [`encode_to_vec`][encode_to_vec] or [`encode_to_string`][encode_to_string].
 //!
 //! For example, to re-save the place file we loaded above:
 //!
@@ -105,28 +123,62 @@
 
 #![deny(missing_docs)]
 
+#[cfg(feature = "tokio")]
+mod async_io;
 mod conversion;
 mod core;
 mod deserializer;
 mod deserializer_core;
 mod error;
+mod metadata;
+mod progress;
+mod schema;
 mod serializer;
 mod serializer_core;
+mod slice_deserializer;
+mod stream_deserializer;
+mod stream_serializer;
 mod types;
 
 #[cfg(test)]
 mod test_util;
 
-use std::io::{Read, Write};
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+    path::Path,
+};
 
 use rbx_dom_weak::{types::Ref, WeakDom};
 
-use crate::{deserializer::decode_internal, serializer::encode_internal};
+use crate::{
+    deserializer::{decode_document, decode_internal},
+    error::{DecodeErrorKind, EncodeErrorKind},
+    serializer::encode_internal,
+};
 
 pub use crate::{
-    deserializer::{DecodeOptions, DecodePropertyBehavior},
-    error::{DecodeError, EncodeError},
-    serializer::{EncodeOptions, EncodePropertyBehavior},
+    deserializer::{
+        DecodeClassBehavior, DecodeDuplicateReferentBehavior, DecodeFilterBehavior,
+        DecodeMismatchBehavior, DecodeOptions, DecodePropertyBehavior, DecodeRefBehavior,
+        DecodedDocument,
+    },
+    error::{DecodeError, DecodeWarning, EncodeError},
+    metadata::{DocumentMetadata, InstanceMetadata},
+    progress::Progress,
+    schema::xml_schema,
+    serializer::{
+        EncodeAttributeOrder, EncodeDocumentKind, EncodeIndentation, EncodeInvalidCharBehavior,
+        EncodeNewlineStyle, EncodeOptions, EncodePropertyBehavior,
+    },
+    slice_deserializer::{SliceDecoder, SliceElement, SliceEvent},
+    stream_deserializer::{StreamDecoder, StreamEvent},
+    stream_serializer::StreamEncoder,
+};
+
+#[cfg(feature = "tokio")]
+pub use crate::async_io::{
+    from_async_reader, from_async_reader_inline, to_async_writer, to_async_writer_inline,
 };
 
 /// Decodes an XML-format model or place from something that implements the
@@ -152,8 +204,55 @@ pub fn from_str_default<S: AsRef<str>>(reader: S) -> Result<WeakDom, DecodeError
     decode_internal(reader.as_ref().as_bytes(), DecodeOptions::default())
 }
 
+/// Decodes an XML-format model or place from a string. This is the
+/// counterpart to [`encode_to_string`].
+pub fn decode_str(source: &str, options: DecodeOptions) -> Result<WeakDom, DecodeError> {
+    decode_internal(source.as_bytes(), options)
+}
+
+/// Decodes an XML-format model or place from something that implements the
+/// `std::io::Read` trait, also returning any problems that were recovered from.
+/// Problems are only recovered from if [`DecodeOptions::lenient`] is set.
+pub fn from_reader_with_warnings<R: Read>(
+    reader: R,
+    options: DecodeOptions,
+) -> Result<(WeakDom, Vec<DecodeWarning>), DecodeError> {
+    decode_document(reader, options).map(|document| (document.tree, document.warnings))
+}
+
+/// Decodes an XML-format model or place from something that implements the
+/// `std::io::Read` trait, also returning the document's metadata.
+pub fn from_reader_with_metadata<R: Read>(
+    reader: R,
+    options: DecodeOptions,
+) -> Result<(WeakDom, DocumentMetadata), DecodeError> {
+    decode_document(reader, options).map(|document| (document.tree, document.metadata))
+}
+
+/// Decodes an XML-format model or place from something that implements the
+/// `std::io::Read` trait, returning everything read from the document,
+/// including the referents of its top-level instances.
+pub fn from_reader_document<R: Read>(
+    reader: R,
+    options: DecodeOptions,
+) -> Result<DecodedDocument, DecodeError> {
+    decode_document(reader, options)
+}
+
 /// Serializes a subset of the given tree to an XML format model or place,
 /// writing to something that implements the `std::io::Write` trait.
+///
+/// Each instance in `ids` becomes a top-level instance in the document. It's
+/// an error for `ids` to contain an instance that isn't in the tree, to
+/// contain the same instance more than once, or to contain both an instance
+/// and one of its ancestors, since that instance would be written twice.
+///
+/// The output is deterministic: the instances given by `ids` are written in
+/// the order given, their descendants are written in the order they appear in
+/// the tree, and each instance's properties are written sorted by name.
+/// Referents are numbered in the order they're first written rather than
+/// taken from the `Ref` values in the tree, so two trees with the same
+/// contents always produce identical output.
 pub fn to_writer<W: Write>(
     writer: W,
     tree: &WeakDom,
@@ -173,3 +272,107 @@ pub fn to_writer_default<W: Write>(
 ) -> Result<(), EncodeError> {
     encode_internal(writer, tree, ids, EncodeOptions::default())
 }
+
+/// Serializes a subset of the given tree to an XML-format model or place,
+/// returning the bytes of the document.
+///
+/// See [`to_writer`] for how `ids` are handled.
+pub fn encode_to_vec(
+    tree: &WeakDom,
+    ids: &[Ref],
+    options: EncodeOptions,
+) -> Result<Vec<u8>, EncodeError> {
+    let mut output = Vec::new();
+    encode_internal(&mut output, tree, ids, options)?;
+
+    Ok(output)
+}
+
+/// Serializes a subset of the given tree to an XML-format model or place,
+/// returning the document as a string. This is the counterpart to
+/// [`decode_str`].
+///
+/// See [`to_writer`] for how `ids` are handled.
+pub fn encode_to_string(
+    tree: &WeakDom,
+    ids: &[Ref],
+    options: EncodeOptions,
+) -> Result<String, EncodeError> {
+    let output = encode_to_vec(tree, ids, options)?;
+
+    // Everything rbx_xml writes is UTF-8 except for the raw XML of properties
+    // kept by `DecodeOptions::preserve_unknown_types`, which could have been
+    // replaced with arbitrary bytes.
+    String::from_utf8(output).map_err(|err| {
+        EncodeError::new(EncodeErrorKind::Io(io::Error::new(
+            io::ErrorKind::InvalidData,
+            err,
+        )))
+    })
+}
+
+/// Decodes the XML-format model or place at the given path using the default
+/// decoder options.
+///
+/// Errors include the path of the file. Files with the extensions `.rbxm` or
+/// `.rbxl` are rejected, since those extensions are used for Roblox's binary
+/// format, which is handled by rbx_binary.
+pub fn decode_file<P: AsRef<Path>>(path: P) -> Result<WeakDom, DecodeError> {
+    let path = path.as_ref();
+
+    let decode = || {
+        if let Some(extension) = binary_extension(path) {
+            return Err(DecodeError::new(DecodeErrorKind::BinaryFileExtension(
+                extension,
+            )));
+        }
+
+        let file = File::open(path).map_err(|err| DecodeError::new(DecodeErrorKind::Io(err)))?;
+
+        from_reader_default(BufReader::new(file))
+    };
+
+    decode().map_err(|err| err.with_file_path(path))
+}
+
+/// Serializes a subset of the given tree to an XML-format model or place at
+/// the given path using the default encoder options, replacing the file if
+/// it already exists.
+///
+/// Errors include the path of the file. Like [`decode_file`], paths with the
+/// extensions `.rbxm` or `.rbxl` are rejected.
+pub fn encode_file<P: AsRef<Path>>(
+    tree: &WeakDom,
+    ids: &[Ref],
+    path: P,
+) -> Result<(), EncodeError> {
+    let path = path.as_ref();
+
+    let encode = || {
+        if let Some(extension) = binary_extension(path) {
+            return Err(EncodeError::new(EncodeErrorKind::BinaryFileExtension(
+                extension,
+            )));
+        }
+
+        let io_error = |err| EncodeError::new(EncodeErrorKind::Io(err));
+
+        let mut output = BufWriter::new(File::create(path).map_err(io_error)?);
+        to_writer_default(&mut output, tree, ids)?;
+        output.flush().map_err(io_error)
+    };
+
+    encode().map_err(|err| err.with_file_path(path))
+}
+
+/// Returns the extension of the given path if it's one used for files in
+/// Roblox's binary format.
+fn binary_extension(path: &Path) -> Option<String> {
+    let extension = path.extension()?.to_str()?;
+
+    if extension.eq_ignore_ascii_case("rbxm") || extension.eq_ignore_ascii_case("rbxl") {
+        Some(format!(".{}", extension))
+    } else {
+        None
+    }
+}
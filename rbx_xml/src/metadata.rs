@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+
+use rbx_dom_weak::types::Ref;
+
+/// The information in an XML-format model or place that isn't part of the
+/// tree itself.
+///
+/// This includes `<Meta>` entries, like `ExplicitAutoJoints`, and the legacy
+/// `<External>` tags that Roblox Studio still writes. Both are kept in the
+/// order they appear in the document. It also holds any comments at the end
+/// of the document, and an [`InstanceMetadata`] for each instance that had
+/// something kept by
+/// [`DecodeOptions::preserve_unknown_types`][crate::DecodeOptions::preserve_unknown_types]
+/// or [`DecodeOptions::preserve_comments`][crate::DecodeOptions::preserve_comments].
+/// These are keyed by the instances' referents in the decoded tree, so they
+/// only apply when encoding that same tree.
+///
+/// Metadata can be read with
+/// [`from_reader_with_metadata`][crate::from_reader_with_metadata] and written
+/// with [`EncodeOptions::metadata`][crate::EncodeOptions::metadata].
+///
+/// ## Example
+/// ```
+/// use rbx_xml::DocumentMetadata;
+///
+/// let document = r#"
+///     <roblox version="4">
+///         <Meta name="ExplicitAutoJoints">true</Meta>
+///         <External>null</External>
+///         <External>nil</External>
+///     </roblox>
+/// "#;
+///
+/// let (_dom, metadata) = rbx_xml::from_reader_with_metadata(
+///     document.as_bytes(),
+///     rbx_xml::DecodeOptions::new(),
+/// )?;
+///
+/// assert_eq!(metadata.get("ExplicitAutoJoints"), Some("true"));
+/// assert_eq!(metadata.externals(), ["null", "nil"]);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DocumentMetadata {
+    entries: Vec<(String, String)>,
+    externals: Vec<String>,
+    comments: Vec<String>,
+    instances: HashMap<Ref, InstanceMetadata>,
+}
+
+impl DocumentMetadata {
+    /// Creates a `DocumentMetadata` with no entries or external tags.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the value of the `<Meta>` entry with the given name, if any.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(entry_name, _)| entry_name == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Sets the value of the `<Meta>` entry with the given name. An existing
+    /// entry keeps its position in the document, and new entries are added to
+    /// the end.
+    pub fn insert<N: Into<String>, V: Into<String>>(&mut self, name: N, value: V) {
+        let name = name.into();
+        let value = value.into();
+
+        match self
+            .entries
+            .iter_mut()
+            .find(|(entry_name, _)| *entry_name == name)
+        {
+            Some((_, existing)) => *existing = value,
+            None => self.entries.push((name, value)),
+        }
+    }
+
+    /// Removes the `<Meta>` entry with the given name, returning its value.
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        let index = self
+            .entries
+            .iter()
+            .position(|(entry_name, _)| entry_name == name)?;
+
+        Some(self.entries.remove(index).1)
+    }
+
+    /// Returns an iterator over the names and values of all `<Meta>` entries,
+    /// in document order.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.entries
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// Returns the contents of each `<External>` tag, in document order.
+    pub fn externals(&self) -> &[String] {
+        &self.externals
+    }
+
+    /// Adds an `<External>` tag with the given contents.
+    pub fn push_external<S: Into<String>>(&mut self, contents: S) {
+        self.externals.push(contents.into());
+    }
+
+    /// Returns the comments at the end of the document, after its last
+    /// instance. These are only read if
+    /// [`DecodeOptions::preserve_comments`][crate::DecodeOptions::preserve_comments]
+    /// is set.
+    pub fn comments(&self) -> &[String] {
+        &self.comments
+    }
+
+    /// Adds a comment to the end of the document.
+    pub fn push_comment<S: Into<String>>(&mut self, comment: S) {
+        self.comments.push(comment.into());
+    }
+
+    /// Returns the metadata of the instance with the given referent, if it
+    /// has any.
+    pub fn instance(&self, referent: Ref) -> Option<&InstanceMetadata> {
+        self.instances.get(&referent)
+    }
+
+    /// Returns the metadata of the instance with the given referent, adding
+    /// empty metadata for it if it doesn't have any yet.
+    pub fn instance_mut(&mut self, referent: Ref) -> &mut InstanceMetadata {
+        self.instances.entry(referent).or_default()
+    }
+
+    /// Removes the metadata of the instance with the given referent,
+    /// returning it.
+    pub fn remove_instance(&mut self, referent: Ref) -> Option<InstanceMetadata> {
+        self.instances.remove(&referent)
+    }
+
+    /// Returns an iterator over the referents of every instance with metadata
+    /// and their metadata, in no particular order.
+    pub fn instances(&self) -> impl Iterator<Item = (Ref, &InstanceMetadata)> {
+        self.instances
+            .iter()
+            .map(|(referent, metadata)| (*referent, metadata))
+    }
+
+    /// Returns whether there are no `<Meta>` entries, `<External>` tags,
+    /// comments, or instance metadata.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+            && self.externals.is_empty()
+            && self.comments.is_empty()
+            && self.instances.values().all(InstanceMetadata::is_empty)
+    }
+}
+
+/// The information about one instance in an XML-format model or place that
+/// can't be stored in its properties, held by a [`DocumentMetadata`].
+///
+/// ## Example
+/// ```
+/// let document = r#"
+///     <roblox version="4">
+///         <!-- Holds everything -->
+///         <Item class="Folder" referent="RBX0">
+///             <Properties>
+///                 <string name="Name">Stuff</string>
+///                 <SomeNewType name="Future">1</SomeNewType>
+///             </Properties>
+///         </Item>
+///     </roblox>
+/// "#;
+///
+/// let options = rbx_xml::DecodeOptions::new()
+///     .preserve_unknown_types(true)
+///     .preserve_comments(true);
+/// let (dom, metadata) = rbx_xml::from_reader_with_metadata(document.as_bytes(), options)?;
+///
+/// let folder = metadata.instance(dom.root().children()[0]).unwrap();
+/// assert_eq!(folder.leading_comments(), [" Holds everything "]);
+/// assert_eq!(
+///     folder.raw_property("Future"),
+///     Some(&br#"<SomeNewType name="Future">1</SomeNewType>"#[..])
+/// );
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InstanceMetadata {
+    raw_properties: Vec<(String, Vec<u8>)>,
+    leading_comments: Vec<String>,
+    trailing_comments: Vec<String>,
+}
+
+impl InstanceMetadata {
+    /// Creates an `InstanceMetadata` with no raw properties or comments.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the raw XML of the property with the given name, if it had a
+    /// type that rbx_xml didn't understand.
+    pub fn raw_property(&self, name: &str) -> Option<&[u8]> {
+        self.raw_properties
+            .iter()
+            .find(|(property_name, _)| property_name == name)
+            .map(|(_, xml)| xml.as_slice())
+    }
+
+    /// Sets the raw XML of the property with the given name. The encoder
+    /// writes it out exactly as given, after the instance's other properties.
+    /// An existing property keeps its position, and new properties are added
+    /// to the end.
+    pub fn insert_raw_property<N: Into<String>, X: Into<Vec<u8>>>(&mut self, name: N, xml: X) {
+        let name = name.into();
+        let xml = xml.into();
+
+        match self
+            .raw_properties
+            .iter_mut()
+            .find(|(property_name, _)| *property_name == name)
+        {
+            Some((_, existing)) => *existing = xml,
+            None => self.raw_properties.push((name, xml)),
+        }
+    }
+
+    /// Removes the raw XML of the property with the given name, returning it.
+    pub fn remove_raw_property(&mut self, name: &str) -> Option<Vec<u8>> {
+        let index = self
+            .raw_properties
+            .iter()
+            .position(|(property_name, _)| property_name == name)?;
+
+        Some(self.raw_properties.remove(index).1)
+    }
+
+    /// Returns an iterator over the names and raw XML of all raw properties,
+    /// in document order.
+    pub fn raw_properties(&self) -> impl Iterator<Item = (&str, &[u8])> {
+        self.raw_properties
+            .iter()
+            .map(|(name, xml)| (name.as_str(), xml.as_slice()))
+    }
+
+    /// Returns the comments that came before the instance.
+    pub fn leading_comments(&self) -> &[String] {
+        &self.leading_comments
+    }
+
+    /// Adds a comment before the instance.
+    pub fn push_leading_comment<S: Into<String>>(&mut self, comment: S) {
+        self.leading_comments.push(comment.into());
+    }
+
+    /// Returns the comments at the end of the instance, after its properties
+    /// and children.
+    pub fn trailing_comments(&self) -> &[String] {
+        &self.trailing_comments
+    }
+
+    /// Adds a comment at the end of the instance.
+    pub fn push_trailing_comment<S: Into<String>>(&mut self, comment: S) {
+        self.trailing_comments.push(comment.into());
+    }
+
+    /// Returns whether there are no raw properties or comments.
+    pub fn is_empty(&self) -> bool {
+        self.raw_properties.is_empty()
+            && self.leading_comments.is_empty()
+            && self.trailing_comments.is_empty()
+    }
+}
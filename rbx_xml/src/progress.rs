@@ -0,0 +1,36 @@
+use std::fmt;
+
+/// How far rbx_xml has gotten through decoding or encoding a document.
+///
+/// This is given to the function set with
+/// [`DecodeOptions::progress`][crate::DecodeOptions::progress] or
+/// [`EncodeOptions::progress`][crate::EncodeOptions::progress] each time an
+/// instance is finished.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Progress {
+    /// The number of bytes of the document that have been read or written so
+    /// far. When decoding, this can be slightly ahead of the instance that was
+    /// just finished, since the document is read in chunks.
+    pub bytes: u64,
+
+    /// The number of instances that have been decoded or encoded so far.
+    pub instances: usize,
+}
+
+/// Wraps the function given to `DecodeOptions::progress` or
+/// `EncodeOptions::progress` so that the options can still implement `Debug`.
+#[derive(Clone, Copy)]
+pub(crate) struct ProgressHook<'db>(pub &'db (dyn Fn(Progress) + Sync));
+
+impl ProgressHook<'_> {
+    pub fn report(&self, bytes: u64, instances: usize) {
+        (self.0)(Progress { bytes, instances })
+    }
+}
+
+impl fmt::Debug for ProgressHook<'_> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("ProgressHook")
+    }
+}
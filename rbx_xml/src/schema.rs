@@ -0,0 +1,515 @@
+//! Generates an XML Schema describing the documents rbx_xml reads.
+
+use std::fmt::Write;
+
+use rbx_reflection::ReflectionDatabase;
+
+use crate::types::LEGACY_TYPE_NAMES;
+
+/// The element name of each property type, paired with the name of the type
+/// that describes it in the schema.
+const PROPERTY_ELEMENTS: &[(&str, &str)] = &[
+    ("Axes", "AxesProperty"),
+    ("BinaryString", "BinaryStringProperty"),
+    ("bool", "BoolProperty"),
+    ("Color3", "Color3Property"),
+    ("Color3uint8", "UnsignedIntProperty"),
+    ("ColorSequence", "FloatListProperty"),
+    ("Content", "ContentProperty"),
+    ("CoordinateFrame", "CFrameProperty"),
+    ("double", "DoubleProperty"),
+    ("Faces", "FacesProperty"),
+    ("float", "FloatProperty"),
+    ("Font", "FontProperty"),
+    ("int", "IntProperty"),
+    ("int64", "Int64Property"),
+    ("NumberRange", "FloatListProperty"),
+    ("NumberSequence", "FloatListProperty"),
+    ("OptionalCoordinateFrame", "OptionalCFrameProperty"),
+    ("PhysicalProperties", "PhysicalPropertiesProperty"),
+    ("ProtectedString", "StringProperty"),
+    ("Ray", "RayProperty"),
+    ("Rect2D", "RectProperty"),
+    ("Ref", "StringProperty"),
+    ("SecurityCapabilities", "UnsignedLongProperty"),
+    ("SharedString", "StringProperty"),
+    ("string", "StringProperty"),
+    ("token", "UnsignedIntProperty"),
+    ("UDim", "UDimProperty"),
+    ("UDim2", "UDim2Property"),
+    ("UniqueId", "UniqueIdProperty"),
+    ("Vector2", "Vector2Property"),
+    ("Vector2int16", "Vector2int16Property"),
+    ("Vector3", "Vector3Property"),
+    ("Vector3int16", "Vector3int16Property"),
+];
+
+/// Definitions that don't depend on the reflection database.
+const FIXED_DEFINITIONS: &str = r#"  <xs:element name="roblox">
+    <xs:complexType>
+      <xs:choice minOccurs="0" maxOccurs="unbounded">
+        <xs:element name="Meta" type="Meta"/>
+        <xs:element name="External" type="xs:string"/>
+        <xs:element name="Item" type="Item"/>
+        <xs:element name="SharedStrings" type="SharedStrings"/>
+      </xs:choice>
+      <xs:attribute name="version" type="xs:string" use="required"/>
+    </xs:complexType>
+  </xs:element>
+
+  <xs:complexType name="Meta">
+    <xs:simpleContent>
+      <xs:extension base="xs:string">
+        <xs:attribute name="name" type="xs:string" use="required"/>
+      </xs:extension>
+    </xs:simpleContent>
+  </xs:complexType>
+
+  <xs:complexType name="SharedStrings">
+    <xs:sequence>
+      <xs:element name="SharedString" minOccurs="0" maxOccurs="unbounded">
+        <xs:complexType>
+          <xs:simpleContent>
+            <xs:extension base="xs:base64Binary">
+              <xs:attribute name="md5" type="xs:string" use="required"/>
+            </xs:extension>
+          </xs:simpleContent>
+        </xs:complexType>
+      </xs:element>
+    </xs:sequence>
+  </xs:complexType>
+
+  <xs:complexType name="Item">
+    <xs:choice minOccurs="0" maxOccurs="unbounded">
+      <xs:element name="Properties" type="Properties"/>
+      <xs:element name="Item" type="Item"/>
+    </xs:choice>
+    <xs:attribute name="class" type="ClassName" use="required"/>
+    <xs:attribute name="referent" type="xs:string"/>
+  </xs:complexType>
+
+  <xs:simpleType name="Float">
+    <xs:union memberTypes="xs:float">
+      <xs:simpleType>
+        <xs:restriction base="xs:string">
+          <xs:enumeration value="NAN"/>
+        </xs:restriction>
+      </xs:simpleType>
+    </xs:union>
+  </xs:simpleType>
+
+  <xs:simpleType name="Double">
+    <xs:union memberTypes="xs:double">
+      <xs:simpleType>
+        <xs:restriction base="xs:string">
+          <xs:enumeration value="NAN"/>
+        </xs:restriction>
+      </xs:simpleType>
+    </xs:union>
+  </xs:simpleType>
+
+  <xs:simpleType name="Bool">
+    <xs:restriction base="xs:string">
+      <xs:enumeration value="true"/>
+      <xs:enumeration value="false"/>
+    </xs:restriction>
+  </xs:simpleType>
+
+  <xs:simpleType name="FloatList">
+    <xs:list itemType="Float"/>
+  </xs:simpleType>
+
+  <xs:complexType name="Vector2">
+    <xs:sequence>
+      <xs:element name="X" type="Float"/>
+      <xs:element name="Y" type="Float"/>
+    </xs:sequence>
+  </xs:complexType>
+
+  <xs:complexType name="Vector3">
+    <xs:sequence>
+      <xs:element name="X" type="Float"/>
+      <xs:element name="Y" type="Float"/>
+      <xs:element name="Z" type="Float"/>
+    </xs:sequence>
+  </xs:complexType>
+
+  <xs:complexType name="CFrame">
+    <xs:all>
+      <xs:element name="X" type="Float"/>
+      <xs:element name="Y" type="Float"/>
+      <xs:element name="Z" type="Float"/>
+      <xs:element name="R00" type="Float"/>
+      <xs:element name="R01" type="Float"/>
+      <xs:element name="R02" type="Float"/>
+      <xs:element name="R10" type="Float"/>
+      <xs:element name="R11" type="Float"/>
+      <xs:element name="R12" type="Float"/>
+      <xs:element name="R20" type="Float"/>
+      <xs:element name="R21" type="Float"/>
+      <xs:element name="R22" type="Float"/>
+    </xs:all>
+  </xs:complexType>
+
+  <xs:complexType name="Content">
+    <xs:choice>
+      <xs:element name="null">
+        <xs:complexType/>
+      </xs:element>
+      <xs:element name="url" type="xs:string"/>
+    </xs:choice>
+  </xs:complexType>
+
+  <xs:attributeGroup name="PropertyName">
+    <xs:attribute name="name" type="xs:string" use="required"/>
+  </xs:attributeGroup>
+
+  <xs:complexType name="StringProperty">
+    <xs:simpleContent>
+      <xs:extension base="xs:string">
+        <xs:attributeGroup ref="PropertyName"/>
+      </xs:extension>
+    </xs:simpleContent>
+  </xs:complexType>
+
+  <xs:complexType name="BinaryStringProperty">
+    <xs:simpleContent>
+      <xs:extension base="xs:base64Binary">
+        <xs:attributeGroup ref="PropertyName"/>
+      </xs:extension>
+    </xs:simpleContent>
+  </xs:complexType>
+
+  <xs:complexType name="BoolProperty">
+    <xs:simpleContent>
+      <xs:extension base="Bool">
+        <xs:attributeGroup ref="PropertyName"/>
+      </xs:extension>
+    </xs:simpleContent>
+  </xs:complexType>
+
+  <xs:complexType name="IntProperty">
+    <xs:simpleContent>
+      <xs:extension base="xs:int">
+        <xs:attributeGroup ref="PropertyName"/>
+      </xs:extension>
+    </xs:simpleContent>
+  </xs:complexType>
+
+  <xs:complexType name="Int64Property">
+    <xs:simpleContent>
+      <xs:extension base="xs:long">
+        <xs:attributeGroup ref="PropertyName"/>
+      </xs:extension>
+    </xs:simpleContent>
+  </xs:complexType>
+
+  <xs:complexType name="UnsignedIntProperty">
+    <xs:simpleContent>
+      <xs:extension base="xs:unsignedInt">
+        <xs:attributeGroup ref="PropertyName"/>
+      </xs:extension>
+    </xs:simpleContent>
+  </xs:complexType>
+
+  <xs:complexType name="UnsignedLongProperty">
+    <xs:simpleContent>
+      <xs:extension base="xs:unsignedLong">
+        <xs:attributeGroup ref="PropertyName"/>
+      </xs:extension>
+    </xs:simpleContent>
+  </xs:complexType>
+
+  <xs:complexType name="FloatProperty">
+    <xs:simpleContent>
+      <xs:extension base="Float">
+        <xs:attributeGroup ref="PropertyName"/>
+      </xs:extension>
+    </xs:simpleContent>
+  </xs:complexType>
+
+  <xs:complexType name="DoubleProperty">
+    <xs:simpleContent>
+      <xs:extension base="Double">
+        <xs:attributeGroup ref="PropertyName"/>
+      </xs:extension>
+    </xs:simpleContent>
+  </xs:complexType>
+
+  <xs:complexType name="FloatListProperty">
+    <xs:simpleContent>
+      <xs:extension base="FloatList">
+        <xs:attributeGroup ref="PropertyName"/>
+      </xs:extension>
+    </xs:simpleContent>
+  </xs:complexType>
+
+  <xs:complexType name="UniqueIdProperty">
+    <xs:simpleContent>
+      <xs:extension base="UniqueId">
+        <xs:attributeGroup ref="PropertyName"/>
+      </xs:extension>
+    </xs:simpleContent>
+  </xs:complexType>
+
+  <xs:simpleType name="UniqueId">
+    <xs:restriction base="xs:string">
+      <xs:pattern value="[0-9a-fA-F]{32}"/>
+    </xs:restriction>
+  </xs:simpleType>
+
+  <xs:complexType name="AxesProperty">
+    <xs:sequence>
+      <xs:element name="axes" type="xs:unsignedByte"/>
+    </xs:sequence>
+    <xs:attributeGroup ref="PropertyName"/>
+  </xs:complexType>
+
+  <xs:complexType name="FacesProperty">
+    <xs:sequence>
+      <xs:element name="faces" type="xs:unsignedByte"/>
+    </xs:sequence>
+    <xs:attributeGroup ref="PropertyName"/>
+  </xs:complexType>
+
+  <xs:complexType name="Color3Property" mixed="true">
+    <xs:sequence minOccurs="0">
+      <xs:element name="R" type="Float"/>
+      <xs:element name="G" type="Float"/>
+      <xs:element name="B" type="Float"/>
+    </xs:sequence>
+    <xs:attributeGroup ref="PropertyName"/>
+  </xs:complexType>
+
+  <xs:complexType name="ContentProperty">
+    <xs:complexContent>
+      <xs:extension base="Content">
+        <xs:attributeGroup ref="PropertyName"/>
+      </xs:extension>
+    </xs:complexContent>
+  </xs:complexType>
+
+  <xs:complexType name="CFrameProperty">
+    <xs:complexContent>
+      <xs:extension base="CFrame">
+        <xs:attributeGroup ref="PropertyName"/>
+      </xs:extension>
+    </xs:complexContent>
+  </xs:complexType>
+
+  <xs:complexType name="OptionalCFrameProperty">
+    <xs:sequence>
+      <xs:element name="CFrame" type="CFrame" minOccurs="0"/>
+    </xs:sequence>
+    <xs:attributeGroup ref="PropertyName"/>
+  </xs:complexType>
+
+  <xs:complexType name="FontProperty">
+    <xs:sequence>
+      <xs:element name="Family" type="Content"/>
+      <xs:element name="Weight" type="xs:unsignedShort"/>
+      <xs:element name="Style">
+        <xs:simpleType>
+          <xs:restriction base="xs:string">
+            <xs:enumeration value="Normal"/>
+            <xs:enumeration value="Italic"/>
+          </xs:restriction>
+        </xs:simpleType>
+      </xs:element>
+      <xs:element name="CachedFaceId" type="Content" minOccurs="0"/>
+    </xs:sequence>
+    <xs:attributeGroup ref="PropertyName"/>
+  </xs:complexType>
+
+  <xs:complexType name="PhysicalPropertiesProperty">
+    <xs:sequence>
+      <xs:element name="CustomPhysics" type="Bool"/>
+      <xs:sequence minOccurs="0">
+        <xs:element name="Density" type="Float"/>
+        <xs:element name="Friction" type="Float"/>
+        <xs:element name="Elasticity" type="Float"/>
+        <xs:element name="FrictionWeight" type="Float"/>
+        <xs:element name="ElasticityWeight" type="Float"/>
+      </xs:sequence>
+    </xs:sequence>
+    <xs:attributeGroup ref="PropertyName"/>
+  </xs:complexType>
+
+  <xs:complexType name="RayProperty">
+    <xs:sequence>
+      <xs:element name="origin" type="Vector3"/>
+      <xs:element name="direction" type="Vector3"/>
+    </xs:sequence>
+    <xs:attributeGroup ref="PropertyName"/>
+  </xs:complexType>
+
+  <xs:complexType name="RectProperty">
+    <xs:sequence>
+      <xs:element name="min" type="Vector2"/>
+      <xs:element name="max" type="Vector2"/>
+    </xs:sequence>
+    <xs:attributeGroup ref="PropertyName"/>
+  </xs:complexType>
+
+  <xs:complexType name="UDimProperty">
+    <xs:sequence>
+      <xs:element name="S" type="Float"/>
+      <xs:element name="O" type="xs:int"/>
+    </xs:sequence>
+    <xs:attributeGroup ref="PropertyName"/>
+  </xs:complexType>
+
+  <xs:complexType name="UDim2Property">
+    <xs:sequence>
+      <xs:element name="XS" type="Float"/>
+      <xs:element name="XO" type="xs:int"/>
+      <xs:element name="YS" type="Float"/>
+      <xs:element name="YO" type="xs:int"/>
+    </xs:sequence>
+    <xs:attributeGroup ref="PropertyName"/>
+  </xs:complexType>
+
+  <xs:complexType name="Vector2Property">
+    <xs:complexContent>
+      <xs:extension base="Vector2">
+        <xs:attributeGroup ref="PropertyName"/>
+      </xs:extension>
+    </xs:complexContent>
+  </xs:complexType>
+
+  <xs:complexType name="Vector3Property">
+    <xs:complexContent>
+      <xs:extension base="Vector3">
+        <xs:attributeGroup ref="PropertyName"/>
+      </xs:extension>
+    </xs:complexContent>
+  </xs:complexType>
+
+  <xs:complexType name="Vector2int16Property">
+    <xs:sequence>
+      <xs:element name="X" type="xs:short"/>
+      <xs:element name="Y" type="xs:short"/>
+    </xs:sequence>
+    <xs:attributeGroup ref="PropertyName"/>
+  </xs:complexType>
+
+  <xs:complexType name="Vector3int16Property">
+    <xs:sequence>
+      <xs:element name="X" type="xs:short"/>
+      <xs:element name="Y" type="xs:short"/>
+      <xs:element name="Z" type="xs:short"/>
+    </xs:sequence>
+    <xs:attributeGroup ref="PropertyName"/>
+  </xs:complexType>
+"#;
+
+/// Generates an [XML Schema](https://www.w3.org/TR/xmlschema-1/) describing
+/// the XML-format models and places that rbx_xml reads, using the classes in
+/// the given reflection database.
+///
+/// This lets tools that don't use rbx_xml, like editors and validators, check
+/// files the same way it does. The schema is a little stricter than rbx_xml's
+/// default [`DecodeOptions`][crate::DecodeOptions]: instances with classes
+/// that aren't in the database and properties with types rbx_xml doesn't know
+/// are rejected instead of being read or skipped, like they are with
+/// `DecodeClassBehavior::ErrorOnUnknown`. XML Schema can't say which type each
+/// property should have, so any property can use any known type.
+///
+/// ```
+/// let schema = rbx_xml::xml_schema(rbx_reflection_database::get());
+/// assert!(schema.contains(r#"<xs:element name="roblox">"#));
+/// ```
+pub fn xml_schema(database: &ReflectionDatabase) -> String {
+    let mut schema = String::new();
+
+    schema.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    schema.push_str("<xs:schema xmlns:xs=\"http://www.w3.org/2001/XMLSchema\">\n");
+    schema.push_str(FIXED_DEFINITIONS);
+
+    schema.push_str("\n  <xs:complexType name=\"Properties\">\n");
+    schema.push_str("    <xs:choice minOccurs=\"0\" maxOccurs=\"unbounded\">\n");
+
+    let legacy_elements = LEGACY_TYPE_NAMES.iter().map(|(legacy_name, current_name)| {
+        let (_, type_name) = PROPERTY_ELEMENTS
+            .iter()
+            .find(|(element_name, _)| element_name == current_name)
+            .expect("legacy type names should refer to known types");
+
+        (*legacy_name, *type_name)
+    });
+
+    for (element_name, type_name) in PROPERTY_ELEMENTS.iter().copied().chain(legacy_elements) {
+        writeln!(
+            schema,
+            "      <xs:element name=\"{}\" type=\"{}\"/>",
+            element_name, type_name
+        )
+        .unwrap();
+    }
+
+    schema.push_str("    </xs:choice>\n");
+    schema.push_str("  </xs:complexType>\n");
+
+    let mut class_names: Vec<_> = database.classes.keys().collect();
+    class_names.sort_unstable();
+
+    schema.push_str("\n  <xs:simpleType name=\"ClassName\">\n");
+    schema.push_str("    <xs:restriction base=\"xs:string\">\n");
+
+    for class_name in class_names {
+        writeln!(schema, "      <xs:enumeration value=\"{}\"/>", class_name).unwrap();
+    }
+
+    schema.push_str("    </xs:restriction>\n");
+    schema.push_str("  </xs:simpleType>\n");
+    schema.push_str("</xs:schema>\n");
+
+    schema
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use xml::reader::{EventReader, XmlEvent};
+
+    #[test]
+    fn well_formed() {
+        let schema = xml_schema(rbx_reflection_database::get());
+        let mut defined_types = Vec::new();
+        let mut referenced_types = Vec::new();
+
+        for event in EventReader::new(schema.as_bytes()) {
+            if let XmlEvent::StartElement {
+                name, attributes, ..
+            } = event.unwrap()
+            {
+                for attribute in attributes {
+                    match attribute.name.local_name.as_str() {
+                        "name"
+                            if name.local_name == "simpleType"
+                                || name.local_name == "complexType" =>
+                        {
+                            defined_types.push(attribute.value)
+                        }
+                        "type" | "base" | "itemType" | "memberTypes" => {
+                            referenced_types.push(attribute.value)
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        for referenced in referenced_types {
+            assert!(
+                referenced.starts_with("xs:") || defined_types.contains(&referenced),
+                "type {} is used but never defined",
+                referenced
+            );
+        }
+
+        assert!(schema.contains(r#"<xs:enumeration value="Part"/>"#));
+        assert!(schema.contains(r#"<xs:element name="CFrame" type="CFrameProperty"/>"#));
+    }
+}
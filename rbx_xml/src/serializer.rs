@@ -1,22 +1,34 @@
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
     io::Write,
 };
 
 use rbx_dom_weak::{
-    types::{Ref, SharedString, SharedStringHash, Variant, VariantType},
+    types::{Ref, SharedString, SharedStringHash, UniqueId, Variant, VariantType},
     WeakDom,
 };
-use rbx_reflection::DataType;
+use rbx_reflection::{ClassTag, DataType, ReflectionDatabase, StripDefaults};
 
 use crate::{
     conversion::ConvertVariant,
-    core::find_serialized_property_descriptor,
+    core::{find_exact_property_descriptor, find_serialized_property_descriptor},
     error::{EncodeError as NewEncodeError, EncodeErrorKind},
-    types::write_value_xml,
+    metadata::{DocumentMetadata, InstanceMetadata},
+    progress::{Progress, ProgressHook},
+    types::{
+        unique_id_referent, write_name, write_protected_string, write_value_xml,
+        PROTECTED_STRING_PROPERTIES,
+    },
 };
 
-use crate::serializer_core::{XmlEventWriter, XmlWriteEvent};
+use crate::serializer_core::{XmlEventWriter, XmlFormat, XmlWriteEvent};
+
+/// The `<Meta>` entry Roblox Studio writes to every place.
+const EXPLICIT_AUTO_JOINTS: &str = "ExplicitAutoJoints";
+
+/// The number of characters per line Roblox Studio wraps base64 data at.
+const STUDIO_BASE64_LINE_WIDTH: usize = 72;
 
 pub fn encode_internal<W: Write>(
     output: W,
@@ -24,10 +36,25 @@ pub fn encode_internal<W: Write>(
     ids: &[Ref],
     options: EncodeOptions,
 ) -> Result<(), NewEncodeError> {
-    let mut writer = XmlEventWriter::from_output(output);
+    // A place is written from its DataModel, whose children are the
+    // top-level instances of the document.
+    let ids = match options.document_kind {
+        EncodeDocumentKind::Place if ids == [tree.root_ref()] => tree.root().children(),
+        _ => ids,
+    };
+
+    validate_roots(tree, ids)?;
+
+    if options.document_kind == EncodeDocumentKind::Place {
+        validate_place_roots(tree, ids, options.database)?;
+    }
+
+    let mut writer = options.create_writer(output);
     let mut state = EmitState::new(options);
+    state.reserve_unique_id_referents(tree, ids);
 
     writer.write(XmlWriteEvent::start_element("roblox").attr("version", "4"))?;
+    serialize_metadata(&mut writer, &mut state)?;
 
     let mut property_buffer = Vec::new();
     for id in ids {
@@ -35,12 +62,94 @@ pub fn encode_internal<W: Write>(
     }
 
     serialize_shared_strings(&mut writer, &mut state)?;
+    serialize_comments(&mut writer, state.metadata().comments())?;
 
     writer.write(XmlWriteEvent::end_element())?;
 
     Ok(())
 }
 
+/// Checks that the given instances can be written as the top-level instances
+/// of a document, which requires each of them to be written exactly once.
+fn validate_roots(tree: &WeakDom, ids: &[Ref]) -> Result<(), NewEncodeError> {
+    let mut roots = HashSet::with_capacity(ids.len());
+
+    for &id in ids {
+        if tree.get_by_ref(id).is_none() {
+            return Err(NewEncodeError::new(EncodeErrorKind::UnknownRoot(id)));
+        }
+
+        if !roots.insert(id) {
+            return Err(NewEncodeError::new(EncodeErrorKind::DuplicateRoot(id)));
+        }
+    }
+
+    for &id in ids {
+        let mut ancestor = tree.get_by_ref(id).unwrap().parent();
+
+        while let Some(instance) = tree.get_by_ref(ancestor) {
+            if roots.contains(&ancestor) {
+                return Err(NewEncodeError::new(EncodeErrorKind::NestedRoot {
+                    id,
+                    ancestor,
+                }));
+            }
+
+            ancestor = instance.parent();
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks that the given instances can be the top-level instances of a place,
+/// which must each be a different service. Classes that aren't in the
+/// reflection database are allowed, since we can't tell whether they're
+/// services.
+fn validate_place_roots(
+    tree: &WeakDom,
+    ids: &[Ref],
+    database: &ReflectionDatabase,
+) -> Result<(), NewEncodeError> {
+    let mut services = HashSet::with_capacity(ids.len());
+
+    for &id in ids {
+        let class_name = tree.get_by_ref(id).unwrap().class.as_str();
+
+        if let Some(descriptor) = database.classes.get(class_name) {
+            if !descriptor.tags.contains(&ClassTag::Service) {
+                return Err(NewEncodeError::new(EncodeErrorKind::NotAService(
+                    class_name.to_owned(),
+                )));
+            }
+        }
+
+        if !services.insert(class_name) {
+            return Err(NewEncodeError::new(EncodeErrorKind::DuplicateService(
+                class_name.to_owned(),
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+/// Describes whether rbx_xml is writing a model or a place.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum EncodeDocumentKind {
+    /// Writes a model (rbxmx), which can hold any instances.
+    ///
+    /// This is the default.
+    Model,
+
+    /// Writes a place (rbxlx). The top-level instances must each be a
+    /// different service, and passing just the tree's root writes its
+    /// children. The `ExplicitAutoJoints` `<Meta>` entry is written as `true`
+    /// unless the document's metadata sets it, like Roblox Studio does.
+    Place,
+}
+
 /// Describes the strategy that rbx_xml should use when serializing properties.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 #[non_exhaustive]
@@ -71,18 +180,127 @@ pub enum EncodePropertyBehavior {
     NoReflection,
 }
 
+/// Describes how rbx_xml should indent the XML it writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum EncodeIndentation {
+    /// Writes the whole document on one line, with no indentation or
+    /// newlines between tags.
+    None,
+
+    /// Indents each level with the given number of spaces.
+    ///
+    /// Two spaces is the default.
+    Spaces(usize),
+
+    /// Indents each level with the given number of tabs.
+    Tabs(usize),
+}
+
+/// Describes which line endings rbx_xml should use between tags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum EncodeNewlineStyle {
+    /// Uses `\n`.
+    ///
+    /// This is the default.
+    Lf,
+
+    /// Uses `\r\n`.
+    CrLf,
+}
+
+/// Describes the order rbx_xml should write the attributes of each tag in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum EncodeAttributeOrder {
+    /// Writes attributes in the same order as Roblox Studio, like `class`
+    /// before `referent` on instances.
+    ///
+    /// This is the default.
+    Studio,
+
+    /// Writes attributes sorted by name.
+    Alphabetical,
+}
+
+/// Describes how rbx_xml should write strings that contain characters XML 1.0
+/// doesn't allow, like most control characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum EncodeInvalidCharBehavior {
+    /// Writes these strings as-is inside of CDATA sections, like Roblox Studio
+    /// does. Documents like this can only be read as XML 1.1, which is how
+    /// rbx_xml reads documents that don't declare a version.
+    ///
+    /// This is the default.
+    Cdata,
+
+    /// Writes these characters as numeric character references, like `&#1;`,
+    /// which rbx_xml and other XML 1.1 readers turn back into the original
+    /// characters.
+    Escape,
+
+    /// Replaces these characters with U+FFFD REPLACEMENT CHARACTER. This loses
+    /// the original characters, but produces documents that any XML 1.0
+    /// reader can read.
+    Replace,
+
+    /// Returns an error if any strings contain these characters.
+    Error,
+}
+
 /// Options available for serializing an XML-format model or place.
 #[derive(Debug, Clone)]
-pub struct EncodeOptions {
+pub struct EncodeOptions<'db> {
     property_behavior: EncodePropertyBehavior,
+    document_kind: EncodeDocumentKind,
+    database: &'db ReflectionDatabase<'db>,
+    strip_defaults: bool,
+    property_allowlist: HashMap<String, HashSet<String>>,
+    metadata: DocumentMetadata,
+    indentation: EncodeIndentation,
+    newline_style: EncodeNewlineStyle,
+    attribute_order: EncodeAttributeOrder,
+    wrap_base64: bool,
+    invalid_char_behavior: EncodeInvalidCharBehavior,
+    serialized_property_names: bool,
+    unique_id_referents: bool,
+    external_ref_hook: Option<ExternalRefHook<'db>>,
+    progress_hook: Option<ProgressHook<'db>>,
 }
 
-impl EncodeOptions {
+/// Wraps the function given to `EncodeOptions::external_refs` so that
+/// `EncodeOptions` can still implement `Debug`.
+#[derive(Clone, Copy)]
+struct ExternalRefHook<'db>(&'db (dyn Fn(Ref) -> Option<String> + Sync));
+
+impl fmt::Debug for ExternalRefHook<'_> {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("ExternalRefHook")
+    }
+}
+
+impl<'db> EncodeOptions<'db> {
     /// Constructs a `EncodeOptions` with all values set to their defaults.
     #[inline]
     pub fn new() -> Self {
         EncodeOptions {
             property_behavior: EncodePropertyBehavior::IgnoreUnknown,
+            document_kind: EncodeDocumentKind::Model,
+            database: rbx_reflection_database::get(),
+            strip_defaults: false,
+            property_allowlist: HashMap::new(),
+            metadata: DocumentMetadata::new(),
+            indentation: EncodeIndentation::Spaces(2),
+            newline_style: EncodeNewlineStyle::Lf,
+            attribute_order: EncodeAttributeOrder::Studio,
+            wrap_base64: false,
+            invalid_char_behavior: EncodeInvalidCharBehavior::Cdata,
+            serialized_property_names: true,
+            unique_id_referents: false,
+            external_ref_hook: None,
+            progress_hook: None,
         }
     }
 
@@ -90,26 +308,294 @@ impl EncodeOptions {
     /// ones.
     #[inline]
     pub fn property_behavior(self, property_behavior: EncodePropertyBehavior) -> Self {
-        EncodeOptions { property_behavior }
+        EncodeOptions {
+            property_behavior,
+            ..self
+        }
+    }
+
+    /// Determines whether rbx_xml is writing a model or a place, which changes
+    /// which top-level instances are allowed. Places are only checked by
+    /// [`to_writer`][crate::to_writer], since
+    /// [`StreamEncoder`][crate::StreamEncoder] doesn't know ahead of time
+    /// which instances it'll write.
+    #[inline]
+    pub fn document_kind(self, document_kind: EncodeDocumentKind) -> Self {
+        EncodeOptions {
+            document_kind,
+            ..self
+        }
+    }
+
+    /// Sets the reflection database that rbx_xml will use to serialize
+    /// properties. Defaults to the database bundled with
+    /// rbx_reflection_database.
+    #[inline]
+    pub fn reflection_database(self, database: &'db ReflectionDatabase<'db>) -> Self {
+        EncodeOptions { database, ..self }
+    }
+
+    /// Determines whether rbx_xml will skip properties that are equal to their
+    /// default values in the reflection database. Defaults to `false`.
+    ///
+    /// Properties are compared with [`StripDefaults`], so small floating point
+    /// differences are ignored. This has no effect when the property behavior
+    /// is `EncodePropertyBehavior::NoReflection`.
+    #[inline]
+    pub fn strip_defaults(self, strip_defaults: bool) -> Self {
+        EncodeOptions {
+            strip_defaults,
+            ..self
+        }
+    }
+
+    /// Sets the `<Meta>` entries and `<External>` tags that rbx_xml will write
+    /// at the start of the document, the comments it will write at the end,
+    /// and the raw properties and comments of each instance. Defaults to none.
+    #[inline]
+    pub fn metadata(self, metadata: DocumentMetadata) -> Self {
+        EncodeOptions { metadata, ..self }
+    }
+
+    /// Determines how rbx_xml will indent the XML it writes.
+    #[inline]
+    pub fn indentation(self, indentation: EncodeIndentation) -> Self {
+        EncodeOptions {
+            indentation,
+            ..self
+        }
+    }
+
+    /// Determines which line endings rbx_xml will write between tags. This
+    /// has no effect with `EncodeIndentation::None`. Line endings inside of
+    /// property values are always written as they are.
+    #[inline]
+    pub fn newline_style(self, newline_style: EncodeNewlineStyle) -> Self {
+        EncodeOptions {
+            newline_style,
+            ..self
+        }
+    }
+
+    /// Determines the order rbx_xml will write the attributes of each tag in.
+    #[inline]
+    pub fn attribute_order(self, attribute_order: EncodeAttributeOrder) -> Self {
+        EncodeOptions {
+            attribute_order,
+            ..self
+        }
+    }
+
+    /// Determines whether rbx_xml will wrap base64-encoded data, like the
+    /// contents of BinaryStrings and SharedStrings, onto multiple lines at the
+    /// same width Roblox Studio does. Defaults to `false`, which writes each
+    /// value on one line.
+    #[inline]
+    pub fn wrap_base64(self, wrap_base64: bool) -> Self {
+        EncodeOptions {
+            wrap_base64,
+            ..self
+        }
+    }
+
+    /// Determines how rbx_xml will write strings that contain characters XML
+    /// 1.0 doesn't allow, like most control characters.
+    ///
+    /// The characters NUL, U+FFFE, and U+FFFF can't be written to XML at all,
+    /// so strings containing them are always an error unless this is set to
+    /// `EncodeInvalidCharBehavior::Replace`.
+    #[inline]
+    pub fn invalid_char_behavior(self, invalid_char_behavior: EncodeInvalidCharBehavior) -> Self {
+        EncodeOptions {
+            invalid_char_behavior,
+            ..self
+        }
+    }
+
+    /// Determines whether properties are renamed to the names Roblox serializes
+    /// them with, like `Size` to `size` or `Color` to `Color3uint8`. Defaults
+    /// to `true`.
+    ///
+    /// With this set to `false`, known properties are written with the names
+    /// they have in the tree, and their values are converted to the types the
+    /// reflection database gives for those names. This is the counterpart to
+    /// [`DecodeOptions::canonical_property_names`][crate::DecodeOptions::canonical_property_names].
+    #[inline]
+    pub fn serialized_property_names(self, serialized_property_names: bool) -> Self {
+        EncodeOptions {
+            serialized_property_names,
+            ..self
+        }
+    }
+
+    /// Determines whether rbx_xml will make each instance's referent from its
+    /// `UniqueId` property, like `RBX0123...`, instead of numbering instances
+    /// in the order they're written. Defaults to `false`.
+    ///
+    /// Referents made this way stay the same between saves, which keeps
+    /// diffs of the output small. Instances without a UniqueId, or whose
+    /// UniqueId is shared with another instance being written, are numbered
+    /// as usual. This only applies to [`to_writer`][crate::to_writer], since
+    /// [`StreamEncoder`][crate::StreamEncoder] doesn't know an instance's
+    /// properties when it writes its referent.
+    #[inline]
+    pub fn unique_id_referents(self, unique_id_referents: bool) -> Self {
+        EncodeOptions {
+            unique_id_referents,
+            ..self
+        }
+    }
+
+    /// Sets a function that rbx_xml will call with the target of each non-null
+    /// Ref property it writes. If the function returns an identifier, it's
+    /// written in place of the referent rbx_xml would normally generate.
+    ///
+    /// This lets references to instances in other files be written as
+    /// identifiers that outlive this tree, like paths or UniqueIds, which
+    /// [`DecodeOptions::external_refs`][crate::DecodeOptions::external_refs]
+    /// can translate back when decoding. Identifiers should be chosen so that
+    /// they can't be mistaken for the referents rbx_xml generates, which are
+    /// plain numbers or, with
+    /// [`unique_id_referents`][EncodeOptions::unique_id_referents], `RBX`
+    /// followed by a UniqueId, and can't be `null`.
+    #[inline]
+    pub fn external_refs(self, hook: &'db (dyn Fn(Ref) -> Option<String> + Sync)) -> Self {
+        EncodeOptions {
+            external_ref_hook: Some(ExternalRefHook(hook)),
+            ..self
+        }
+    }
+
+    /// Sets a function that rbx_xml will call each time it finishes writing an
+    /// instance, with how much of the document has been written so far.
+    #[inline]
+    pub fn progress(self, hook: &'db (dyn Fn(Progress) + Sync)) -> Self {
+        EncodeOptions {
+            progress_hook: Some(ProgressHook(hook)),
+            ..self
+        }
+    }
+
+    /// Restricts the properties written for instances of the given class to
+    /// only those listed. Calling this more than once for the same class adds
+    /// to its list.
+    ///
+    /// Lists apply to instances of subclasses too, so a list for `BasePart`
+    /// also applies to `Part`. If an instance's class and its superclasses
+    /// have more than one list, a property is written if it's in any of them.
+    /// Instances with no lists have all of their properties written. `Name`
+    /// is always written.
+    pub fn allow_properties<C, I, P>(mut self, class_name: C, property_names: I) -> Self
+    where
+        C: Into<String>,
+        I: IntoIterator<Item = P>,
+        P: Into<String>,
+    {
+        self.property_allowlist
+            .entry(class_name.into())
+            .or_default()
+            .extend(property_names.into_iter().map(Into::into));
+
+        self
     }
 
     pub(crate) fn use_reflection(&self) -> bool {
         self.property_behavior != EncodePropertyBehavior::NoReflection
     }
+
+    /// Creates an `XmlEventWriter` that formats its output according to these
+    /// options.
+    pub(crate) fn create_writer<W: Write>(&self, output: W) -> XmlEventWriter<W> {
+        let indent = match self.indentation {
+            EncodeIndentation::None => None,
+            EncodeIndentation::Spaces(width) => Some(" ".repeat(width)),
+            EncodeIndentation::Tabs(width) => Some("\t".repeat(width)),
+        };
+
+        let line_separator = match self.newline_style {
+            EncodeNewlineStyle::Lf => "\n",
+            EncodeNewlineStyle::CrLf => "\r\n",
+        };
+
+        let format = XmlFormat {
+            indent,
+            line_separator,
+            sort_attributes: self.attribute_order == EncodeAttributeOrder::Alphabetical,
+            base64_line_width: self.wrap_base64.then_some(STUDIO_BASE64_LINE_WIDTH),
+            invalid_char_behavior: self.invalid_char_behavior,
+        };
+
+        XmlEventWriter::with_format(output, format)
+    }
+
+    /// Returns whether the given property is allowed to be written by the
+    /// property allowlists.
+    fn is_allowed(&self, class_name: &str, property_name: &str) -> bool {
+        if self.property_allowlist.is_empty() {
+            return true;
+        }
+
+        let classes = match self.database.classes.get(class_name) {
+            Some(descriptor) => self
+                .database
+                .superclasses(descriptor)
+                .map(|descriptor| descriptor.name.as_ref())
+                .collect(),
+            None => vec![class_name],
+        };
+
+        let mut restricted = false;
+        for class in classes {
+            if let Some(allowed) = self.property_allowlist.get(class) {
+                if allowed.contains(property_name) {
+                    return true;
+                }
+
+                restricted = true;
+            }
+        }
+
+        !restricted
+    }
+
+    /// Returns whether the given property is one that Roblox serializes as a
+    /// ProtectedString.
+    fn is_protected_string(&self, class_name: &str, property_name: &str) -> bool {
+        let descriptor = match self.database.classes.get(class_name) {
+            Some(descriptor) => descriptor,
+            None => return false,
+        };
+
+        self.database.superclasses(descriptor).any(|superclass| {
+            PROTECTED_STRING_PROPERTIES
+                .iter()
+                .any(|(protected_class, protected_property)| {
+                    superclass.name == *protected_class && property_name == *protected_property
+                })
+        })
+    }
+
+    /// Returns whether the given property should be skipped because it's equal
+    /// to its default value.
+    fn is_stripped(&self, class_name: &str, property_name: &str, value: &Variant) -> bool {
+        self.strip_defaults
+            && self.use_reflection()
+            && StripDefaults::new(self.database).is_default(class_name, property_name, value)
+    }
 }
 
-impl Default for EncodeOptions {
-    fn default() -> EncodeOptions {
+impl<'db> Default for EncodeOptions<'db> {
+    fn default() -> EncodeOptions<'db> {
         EncodeOptions::new()
     }
 }
 
-pub struct EmitState {
-    options: EncodeOptions,
+pub struct EmitState<'db> {
+    options: EncodeOptions<'db>,
 
     /// A map of IDs written so far to the generated referent that they use.
     /// This map is used to correctly emit Ref properties.
-    referent_map: HashMap<Ref, u32>,
+    referent_map: HashMap<Ref, String>,
 
     /// The referent value that will be used for emitting the next instance.
     next_referent: u32,
@@ -117,30 +603,93 @@ pub struct EmitState {
     /// A map of all shared strings referenced so far while generating XML. This
     /// map will be written as the file's SharedString dictionary.
     shared_strings_to_emit: BTreeMap<SharedStringHash, SharedString>,
+
+    /// The number of instances that have been written so far.
+    instances_written: usize,
 }
 
-impl EmitState {
-    pub fn new(options: EncodeOptions) -> EmitState {
+impl<'db> EmitState<'db> {
+    pub fn new(options: EncodeOptions<'db>) -> EmitState<'db> {
         EmitState {
             options,
             referent_map: HashMap::new(),
             next_referent: 0,
             shared_strings_to_emit: BTreeMap::new(),
+            instances_written: 0,
+        }
+    }
+
+    /// Records that an instance has been written, reporting progress if
+    /// `EncodeOptions::progress` is set.
+    pub fn instance_finished(&mut self, bytes_written: u64) {
+        self.instances_written += 1;
+
+        if let Some(hook) = &self.options.progress_hook {
+            hook.report(bytes_written, self.instances_written);
         }
     }
 
-    pub fn map_id(&mut self, id: Ref) -> u32 {
+    /// Returns the external identifier that should be written for a Ref
+    /// property pointing to the given instance, if any.
+    pub fn external_ref(&self, id: Ref) -> Option<String> {
+        self.options
+            .external_ref_hook
+            .and_then(|ExternalRefHook(hook)| hook(id))
+    }
+
+    pub fn map_id(&mut self, id: Ref) -> String {
         match self.referent_map.get(&id) {
-            Some(&value) => value,
+            Some(value) => value.clone(),
             None => {
-                let referent = self.next_referent;
-                self.referent_map.insert(id, referent);
+                let referent = self.next_referent.to_string();
+                self.referent_map.insert(id, referent.clone());
                 self.next_referent += 1;
                 referent
             }
         }
     }
 
+    /// Assigns referents made from UniqueIds to the given instances and their
+    /// descendants, if `EncodeOptions::unique_id_referents` is set. This has
+    /// to happen before anything is written, since Ref properties can point
+    /// to instances that haven't been written yet.
+    pub fn reserve_unique_id_referents(&mut self, tree: &WeakDom, ids: &[Ref]) {
+        if !self.options.unique_id_referents {
+            return;
+        }
+
+        // Referents that are claimed by more than one instance map to `None`.
+        let mut owners: HashMap<String, Option<Ref>> = HashMap::new();
+        let mut to_visit = ids.to_vec();
+
+        while let Some(id) = to_visit.pop() {
+            let instance = tree.get_by_ref(id).unwrap();
+            to_visit.extend_from_slice(instance.children());
+
+            if let Some(Variant::UniqueId(unique_id)) = instance.properties.get("UniqueId") {
+                if *unique_id == UniqueId::new(0, 0, 0) {
+                    continue;
+                }
+
+                owners
+                    .entry(unique_id_referent(*unique_id))
+                    .and_modify(|owner| *owner = None)
+                    .or_insert(Some(id));
+            }
+        }
+
+        for (referent, owner) in owners {
+            if let Some(id) = owner {
+                self.referent_map.insert(id, referent);
+            }
+        }
+    }
+
+    /// Returns the metadata given by `EncodeOptions::metadata`.
+    pub fn metadata(&self) -> &DocumentMetadata {
+        &self.options.metadata
+    }
+
     pub fn add_shared_string(&mut self, value: SharedString) {
         self.shared_strings_to_emit.insert(value.hash(), value);
     }
@@ -160,87 +709,189 @@ fn serialize_instance<'a, W: Write>(
     let instance = tree.get_by_ref(id).unwrap();
     let mapped_id = state.map_id(id);
 
+    if let Some(metadata) = state.metadata().instance(id) {
+        serialize_comments(writer, metadata.leading_comments())?;
+    }
+
     writer.write(
         XmlWriteEvent::start_element("Item")
             .attr("class", &instance.class)
-            .attr("referent", &mapped_id.to_string()),
+            .attr("referent", &mapped_id),
     )?;
 
     writer.write(XmlWriteEvent::start_element("Properties"))?;
 
-    write_value_xml(
-        writer,
-        state,
-        "Name",
-        &Variant::String(instance.name.clone()),
-    )?;
+    write_name(writer, &instance.name)?;
 
     // Move references to our properties into property_buffer so we can sort
-    // them and iterate them in order.
-    property_buffer.extend(&instance.properties);
+    // them and iterate them in order. The properties of an instance are kept
+    // in a hash map, so this is what keeps our output deterministic.
+    property_buffer.extend(instance.properties.iter());
     property_buffer.sort_unstable_by_key(|(key, _)| *key);
 
     for (property_name, value) in property_buffer.drain(..) {
-        let maybe_serialized_descriptor = if state.options.use_reflection() {
-            find_serialized_property_descriptor(&instance.class, property_name)
-        } else {
-            None
+        serialize_property(writer, state, &instance.class, property_name, value)?;
+    }
+
+    if let Some(metadata) = state.metadata().instance(id) {
+        serialize_raw_properties(writer, state, &instance.class, metadata)?;
+    }
+
+    writer.write(XmlWriteEvent::end_element())?;
+
+    for child_id in instance.children() {
+        serialize_instance(writer, state, tree, *child_id, property_buffer)?;
+    }
+
+    if let Some(metadata) = state.metadata().instance(id) {
+        serialize_comments(writer, metadata.trailing_comments())?;
+    }
+
+    writer.write(XmlWriteEvent::end_element())?;
+    state.instance_finished(writer.bytes_written());
+
+    Ok(())
+}
+
+/// Writes comments kept by `DecodeOptions::preserve_comments`.
+pub fn serialize_comments<W: Write>(
+    writer: &mut XmlEventWriter<W>,
+    comments: &[String],
+) -> Result<(), NewEncodeError> {
+    for comment in comments {
+        writer.write(XmlWriteEvent::comment(comment))?;
+    }
+
+    Ok(())
+}
+
+/// Writes the properties kept by `DecodeOptions::preserve_unknown_types`
+/// exactly as they were read, skipping any that aren't allowed by the encode
+/// options.
+pub fn serialize_raw_properties<W: Write>(
+    writer: &mut XmlEventWriter<W>,
+    state: &EmitState,
+    class_name: &str,
+    metadata: &InstanceMetadata,
+) -> Result<(), NewEncodeError> {
+    for (property_name, xml) in metadata.raw_properties() {
+        if state.options.is_allowed(class_name, property_name) {
+            writer.write_raw_element(xml)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Serialize a single property of an instance with the given class, using the
+/// reflection database and the property behavior from the encode options.
+pub fn serialize_property<W: Write>(
+    writer: &mut XmlEventWriter<W>,
+    state: &mut EmitState,
+    class_name: &str,
+    property_name: &str,
+    value: &Variant,
+) -> Result<(), NewEncodeError> {
+    if !state.options.is_allowed(class_name, property_name)
+        || state.options.is_stripped(class_name, property_name, value)
+    {
+        return Ok(());
+    }
+
+    let maybe_serialized_descriptor = if !state.options.use_reflection() {
+        None
+    } else if state.options.serialized_property_names {
+        find_serialized_property_descriptor(class_name, property_name, state.options.database)
+    } else {
+        find_exact_property_descriptor(class_name, property_name, state.options.database)
+    };
+
+    if let Some(serialized_descriptor) = maybe_serialized_descriptor {
+        let data_type = match &serialized_descriptor.data_type {
+            DataType::Value(data_type) => *data_type,
+            DataType::Enum(_enum_name) => VariantType::Enum,
+            _ => unimplemented!(),
         };
 
-        if let Some(serialized_descriptor) = maybe_serialized_descriptor {
-            let data_type = match &serialized_descriptor.data_type {
-                DataType::Value(data_type) => *data_type,
-                DataType::Enum(_enum_name) => VariantType::Enum,
-                _ => unimplemented!(),
-            };
-
-            let converted_value = match value.try_convert_ref(data_type) {
-                Ok(value) => value,
-                Err(message) => {
-                    return Err(
-                        writer.error(EncodeErrorKind::UnsupportedPropertyConversion {
-                            class_name: instance.class.clone(),
-                            property_name: property_name.to_string(),
-                            expected_type: data_type,
-                            actual_type: value.ty(),
-                            message,
-                        }),
-                    )
-                }
-            };
+        // Attributes that couldn't be decoded are kept as their original
+        // blob, which is already in the form we need to write.
+        if data_type == VariantType::Attributes {
+            if let Variant::BinaryString(_) = value {
+                return write_value_xml(writer, state, &serialized_descriptor.name, value);
+            }
+        }
 
-            write_value_xml(writer, state, &serialized_descriptor.name, &converted_value)?;
-        } else {
-            match state.options.property_behavior {
-                EncodePropertyBehavior::IgnoreUnknown => {}
-                EncodePropertyBehavior::WriteUnknown | EncodePropertyBehavior::NoReflection => {
-                    // We'll take this value as-is with no conversions on
-                    // either the name or value.
+        let converted_value = match value.try_convert_ref(data_type) {
+            Ok(value) => value,
+            Err(message) => {
+                return Err(
+                    writer.error(EncodeErrorKind::UnsupportedPropertyConversion {
+                        class_name: class_name.to_owned(),
+                        property_name: property_name.to_owned(),
+                        expected_type: data_type,
+                        actual_type: value.ty(),
+                        message,
+                    }),
+                )
+            }
+        };
 
-                    write_value_xml(writer, state, property_name, value)?;
-                }
-                EncodePropertyBehavior::ErrorOnUnknown => {
-                    return Err(writer.error(EncodeErrorKind::UnknownProperty {
-                        class_name: instance.class.clone(),
-                        property_name: property_name.clone(),
-                    }));
-                }
+        if let Variant::String(value) = converted_value.as_ref() {
+            if state.options.is_protected_string(class_name, property_name) {
+                return write_protected_string(writer, &serialized_descriptor.name, value);
+            }
+        }
+
+        write_value_xml(writer, state, &serialized_descriptor.name, &converted_value)?;
+    } else {
+        match state.options.property_behavior {
+            EncodePropertyBehavior::IgnoreUnknown => {}
+            EncodePropertyBehavior::WriteUnknown | EncodePropertyBehavior::NoReflection => {
+                // We'll take this value as-is with no conversions on either
+                // the name or value.
+
+                write_value_xml(writer, state, property_name, value)?;
+            }
+            EncodePropertyBehavior::ErrorOnUnknown => {
+                return Err(writer.error(EncodeErrorKind::UnknownProperty {
+                    class_name: class_name.to_owned(),
+                    property_name: property_name.to_owned(),
+                }));
             }
         }
     }
 
-    writer.write(XmlWriteEvent::end_element())?;
+    Ok(())
+}
 
-    for child_id in instance.children() {
-        serialize_instance(writer, state, tree, *child_id, property_buffer)?;
+pub fn serialize_metadata<W: Write>(
+    writer: &mut XmlEventWriter<W>,
+    state: &mut EmitState,
+) -> Result<(), NewEncodeError> {
+    if state.options.document_kind == EncodeDocumentKind::Place
+        && state.options.metadata.get(EXPLICIT_AUTO_JOINTS).is_none()
+    {
+        writer.write(XmlWriteEvent::start_element("Meta").attr("name", EXPLICIT_AUTO_JOINTS))?;
+        writer.write_string("true")?;
+        writer.write(XmlWriteEvent::end_element())?;
     }
 
-    writer.write(XmlWriteEvent::end_element())?;
+    for (name, value) in state.options.metadata.iter() {
+        writer.write(XmlWriteEvent::start_element("Meta").attr("name", name))?;
+        writer.write_string(value)?;
+        writer.write(XmlWriteEvent::end_element())?;
+    }
+
+    for contents in state.options.metadata.externals() {
+        writer.write(XmlWriteEvent::start_element("External"))?;
+        writer.write_string(contents)?;
+        writer.write(XmlWriteEvent::end_element())?;
+    }
 
     Ok(())
 }
 
-fn serialize_shared_strings<W: Write>(
+pub fn serialize_shared_strings<W: Write>(
     writer: &mut XmlEventWriter<W>,
     state: &mut EmitState,
 ) -> Result<(), NewEncodeError> {
@@ -261,7 +912,7 @@ fn serialize_shared_strings<W: Write>(
                 .attr("md5", &base64::encode(truncated_hash)),
         )?;
 
-        writer.write_string(&base64::encode(value.data()))?;
+        writer.write_base64(value.data())?;
         writer.end_element()?;
     }
 
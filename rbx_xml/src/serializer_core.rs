@@ -1,36 +1,190 @@
-use std::{fmt::Write as FmtWrite, io::Write};
+use std::{
+    borrow::Cow,
+    fmt::Write as FmtWrite,
+    io::{self, Write},
+};
 
-use xml::writer::{EmitterConfig, EventWriter};
+use xml::{
+    reader::{ParserConfig, XmlEvent as XmlReadEvent},
+    writer::{EmitterConfig, EventWriter},
+};
 
 pub use xml::writer::XmlEvent as XmlWriteEvent;
 
 use crate::{
     core::XmlType,
     error::{EncodeError as NewEncodeError, EncodeErrorKind},
+    serializer::EncodeInvalidCharBehavior,
 };
 
+/// Describes how an `XmlEventWriter` formats its output.
+pub struct XmlFormat {
+    /// The string used for each level of indentation. If `None`, the output
+    /// is written without any indentation or newlines.
+    pub indent: Option<String>,
+
+    /// The line ending written between tags.
+    pub line_separator: &'static str,
+
+    /// Whether the attributes of each tag are sorted by name.
+    pub sort_attributes: bool,
+
+    /// The number of characters after which base64 data is wrapped onto a new
+    /// line, which must be a multiple of 4. If `None`, base64 data is written
+    /// on one line.
+    pub base64_line_width: Option<usize>,
+
+    /// How strings with characters that XML 1.0 doesn't allow are written.
+    pub invalid_char_behavior: EncodeInvalidCharBehavior,
+}
+
+impl Default for XmlFormat {
+    fn default() -> Self {
+        XmlFormat {
+            indent: Some("  ".to_owned()),
+            line_separator: "\n",
+            sort_attributes: false,
+            base64_line_width: None,
+            invalid_char_behavior: EncodeInvalidCharBehavior::Cdata,
+        }
+    }
+}
+
+/// A wrapper around an output that counts the bytes written to it.
+struct CountingWriter<W> {
+    inner: W,
+    bytes_written: u64,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        self.bytes_written += len as u64;
+
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
 /// A wrapper around an xml-rs `EventWriter` as well as other state kept around
 /// for performantly emitting XML.
+///
+/// Values are formatted into buffers that are reused between writes, so
+/// writing a document doesn't allocate for each number or base64 value in it.
 pub struct XmlEventWriter<W> {
-    inner: EventWriter<W>,
+    inner: EventWriter<CountingWriter<W>>,
     character_buffer: String,
+    base64_buffer: String,
+    sort_attributes: bool,
+    base64_line_width: Option<usize>,
+    invalid_char_behavior: EncodeInvalidCharBehavior,
 }
 
 impl<W: Write> XmlEventWriter<W> {
-    /// Constructs an `XmlEventWriter` from an output that implements `Write`.
+    /// Constructs an `XmlEventWriter` from an output that implements `Write`,
+    /// using the default formatting.
+    #[cfg(test)]
     pub fn from_output(output: W) -> XmlEventWriter<W> {
-        let inner = EmitterConfig::new()
-            .perform_indent(true)
+        Self::with_format(output, XmlFormat::default())
+    }
+
+    /// Constructs an `XmlEventWriter` that formats its output as described by
+    /// `format`.
+    pub fn with_format(output: W, format: XmlFormat) -> XmlEventWriter<W> {
+        let mut config = EmitterConfig::new()
+            .perform_indent(format.indent.is_some())
+            .line_separator(format.line_separator)
             .write_document_declaration(false)
-            .normalize_empty_elements(false)
-            .create_writer(output);
+            .normalize_empty_elements(false);
+
+        if let Some(indent) = format.indent {
+            config = config.indent_string(indent);
+        }
+
+        let output = CountingWriter {
+            inner: output,
+            bytes_written: 0,
+        };
 
         XmlEventWriter {
-            inner,
+            inner: config.create_writer(output),
             character_buffer: String::new(),
+            base64_buffer: String::new(),
+            sort_attributes: format.sort_attributes,
+            base64_line_width: format.base64_line_width,
+            invalid_char_behavior: format.invalid_char_behavior,
         }
     }
 
+    /// Encodes binary data as base64 into `base64_buffer`, wrapping it onto
+    /// multiple lines if this writer is configured to.
+    fn encode_base64(&mut self, data: &[u8]) {
+        self.base64_buffer.clear();
+
+        match self.base64_line_width {
+            Some(width) if width > 0 => {
+                debug_assert_eq!(width % 4, 0, "base64 line width must be a multiple of 4");
+
+                // Every 3 bytes of input become 4 characters of output, so
+                // encoding the input in pieces of this size gives full lines
+                // without needing to split the output afterwards.
+                let chunk_len = width / 4 * 3;
+
+                for (index, chunk) in data.chunks(chunk_len).enumerate() {
+                    if index > 0 {
+                        self.base64_buffer.push('\n');
+                    }
+                    base64::encode_config_buf(chunk, base64::STANDARD, &mut self.base64_buffer);
+                }
+            }
+            _ => base64::encode_config_buf(data, base64::STANDARD, &mut self.base64_buffer),
+        }
+    }
+
+    /// Writes binary data as base64 characters.
+    pub fn write_base64<T: AsRef<[u8]>>(&mut self, data: T) -> Result<(), NewEncodeError> {
+        self.encode_base64(data.as_ref());
+
+        // base64 never contains whitespace at either end or characters that
+        // need escaping, so there's no need to check for them.
+        self.inner
+            .write(XmlWriteEvent::characters(&self.base64_buffer))
+            .map_err(|e| NewEncodeError::new_from_writer(e.into(), &self.inner))
+    }
+
+    /// Writes binary data as base64 inside of a CDATA section.
+    pub fn write_base64_cdata<T: AsRef<[u8]>>(&mut self, data: T) -> Result<(), NewEncodeError> {
+        self.encode_base64(data.as_ref());
+
+        self.inner
+            .write(XmlWriteEvent::cdata(&self.base64_buffer))
+            .map_err(|e| NewEncodeError::new_from_writer(e.into(), &self.inner))
+    }
+
+    /// Writes an integer as characters. This is faster than `write_characters`
+    /// since it doesn't go through `std::fmt`, and integers never need to be
+    /// checked for characters that need escaping.
+    pub fn write_integer<T: itoa::Integer>(&mut self, value: T) -> Result<(), NewEncodeError> {
+        let mut buffer = itoa::Buffer::new();
+
+        self.inner
+            .write(XmlWriteEvent::characters(buffer.format(value)))
+            .map_err(|e| NewEncodeError::new_from_writer(e.into(), &self.inner))
+    }
+
+    /// Consumes the `XmlEventWriter`, returning the output it was writing to.
+    pub fn into_inner(self) -> W {
+        self.inner.into_inner().inner
+    }
+
+    /// The number of bytes that have been written to the output so far.
+    pub fn bytes_written(&self) -> u64 {
+        self.inner.inner_ref().bytes_written
+    }
+
     pub(crate) fn error<T: Into<EncodeErrorKind>>(&self, kind: T) -> NewEncodeError {
         NewEncodeError::new_from_writer(kind.into(), &self.inner)
     }
@@ -46,27 +200,77 @@ impl<W: Write> XmlEventWriter<W> {
     where
         E: Into<XmlWriteEvent<'a>>,
     {
+        let mut event = event.into();
+
+        if self.sort_attributes {
+            if let XmlWriteEvent::StartElement { attributes, .. } = &mut event {
+                if attributes.len() > 1 {
+                    attributes
+                        .to_mut()
+                        .sort_by(|a, b| a.name.local_name.cmp(b.name.local_name));
+                }
+            }
+        }
+
         self.inner.write(event).map_err(|e| self.error(e))
     }
 
+    /// Writes a string slice to the output stream as a CDATA section, even if
+    /// it doesn't need to be.
+    pub fn write_cdata(&mut self, value: &str) -> Result<(), NewEncodeError> {
+        write_cdata(&mut self.inner, value, self.invalid_char_behavior)
+    }
+
     /// Writes a string slice to the output stream as characters or CDATA.
     pub fn write_string(&mut self, value: &str) -> Result<(), NewEncodeError> {
-        write_characters_or_cdata(&mut self.inner, value)
+        write_characters_or_cdata(&mut self.inner, value, self.invalid_char_behavior)
     }
 
     /// Writes a value that implements `Display` as characters or CDATA. Resuses
     /// an internal buffer to avoid unnecessary allocations.
+    ///
+    /// Floats are written this way on purpose: `Display` already gives the
+    /// shortest representation that round-trips, and faster formatters like
+    /// ryu write some values differently, like `1.0` instead of `1`.
     pub fn write_characters<T: std::fmt::Display>(
         &mut self,
         value: T,
     ) -> Result<(), NewEncodeError> {
         write!(self.character_buffer, "{}", value).unwrap();
-        write_characters_or_cdata(&mut self.inner, &self.character_buffer)?;
+        write_characters_or_cdata(
+            &mut self.inner,
+            &self.character_buffer,
+            self.invalid_char_behavior,
+        )?;
         self.character_buffer.clear();
 
         Ok(())
     }
 
+    /// Writes an element that was read from a file as raw XML text, like the
+    /// output of `XmlEventReader::read_raw_element`.
+    pub fn write_raw_element(&mut self, xml: &[u8]) -> Result<(), NewEncodeError> {
+        let reader = ParserConfig::new()
+            .ignore_comments(false)
+            .trim_whitespace(true)
+            .create_reader(xml);
+
+        for event in reader {
+            let event = event.map_err(|e| self.error(EncodeErrorKind::InvalidRawXml(e)))?;
+
+            match event {
+                XmlReadEvent::StartDocument { .. } | XmlReadEvent::EndDocument => {}
+                event => {
+                    if let Some(event) = event.as_writer_event() {
+                        self.write(event)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     pub fn write_value<T: XmlType>(&mut self, value: &T) -> Result<(), NewEncodeError> {
         value.write_xml(self)
     }
@@ -111,16 +315,54 @@ impl<W: Write> XmlEventWriter<W> {
     }
 }
 
+/// Returns whether a character can only appear inside of a CDATA section in
+/// the XML 1.1 documents that rbx_xml reads.
+fn is_restricted_char(c: char) -> bool {
+    matches!(
+        c,
+        '\u{01}'..='\u{08}'
+            | '\u{0B}'..='\u{0C}'
+            | '\u{0E}'..='\u{1F}'
+            | '\u{7F}'..='\u{84}'
+            | '\u{86}'..='\u{9F}'
+    )
+}
+
+/// Returns whether a character can't appear in XML documents at all, even as
+/// a character reference.
+fn is_forbidden_char(c: char) -> bool {
+    matches!(c, '\u{00}' | '\u{FFFE}' | '\u{FFFF}')
+}
+
+/// Returns whether a character is one that `EncodeInvalidCharBehavior`
+/// applies to. This is every character that XML 1.0 doesn't allow.
+fn is_invalid_char(c: char) -> bool {
+    is_forbidden_char(c)
+        || matches!(
+            c,
+            '\u{01}'..='\u{08}' | '\u{0B}'..='\u{0C}' | '\u{0E}'..='\u{1F}'
+        )
+}
+
 /// Given a value, writes a `Characters` event or a `CData` event depending on
 /// whether the input string contains whitespace that needs to be explicitly
-/// preserved.
+/// preserved, handling characters that XML 1.0 doesn't allow as described by
+/// `behavior`.
 ///
 /// This method is extracted so that it can be used inside both `write_string`
 /// and `write_characters` without borrowing issues.
 fn write_characters_or_cdata<W: Write>(
-    writer: &mut EventWriter<W>,
+    writer: &mut EventWriter<CountingWriter<W>>,
     value: &str,
+    behavior: EncodeInvalidCharBehavior,
 ) -> Result<(), NewEncodeError> {
+    let value = &*apply_invalid_char_behavior(writer, value, behavior)?;
+    let has_restricted_chars = value.chars().any(is_restricted_char);
+
+    if has_restricted_chars && behavior == EncodeInvalidCharBehavior::Escape {
+        return write_escaped_characters(writer, value);
+    }
+
     let first_char = value.chars().next();
     let last_char = value.chars().next_back();
 
@@ -133,7 +375,9 @@ fn write_characters_or_cdata<W: Write>(
         (None, None) => false,
     };
 
-    if has_outer_whitespace {
+    // Characters that XML only allows inside of CDATA sections, like most
+    // control characters, also need to be written as CDATA.
+    if has_outer_whitespace || has_restricted_chars {
         writer
             .write(XmlWriteEvent::cdata(value))
             .map_err(|e| NewEncodeError::new_from_writer(e.into(), writer))?;
@@ -145,3 +389,129 @@ fn write_characters_or_cdata<W: Write>(
 
     Ok(())
 }
+
+/// Writes a `CData` event, handling characters that XML 1.0 doesn't allow as
+/// described by `behavior`. CDATA sections can't contain character
+/// references, so strings that need them are written as characters instead.
+fn write_cdata<W: Write>(
+    writer: &mut EventWriter<CountingWriter<W>>,
+    value: &str,
+    behavior: EncodeInvalidCharBehavior,
+) -> Result<(), NewEncodeError> {
+    let value = &*apply_invalid_char_behavior(writer, value, behavior)?;
+
+    if behavior == EncodeInvalidCharBehavior::Escape && value.chars().any(is_restricted_char) {
+        return write_escaped_characters(writer, value);
+    }
+
+    writer
+        .write(XmlWriteEvent::cdata(value))
+        .map_err(|e| NewEncodeError::new_from_writer(e.into(), writer))
+}
+
+/// Replaces or rejects the characters in a value that XML 1.0 doesn't allow,
+/// as described by `behavior`. Characters that can't be written at all are
+/// always rejected unless they're being replaced.
+fn apply_invalid_char_behavior<'a, W: Write>(
+    writer: &EventWriter<CountingWriter<W>>,
+    value: &'a str,
+    behavior: EncodeInvalidCharBehavior,
+) -> Result<Cow<'a, str>, NewEncodeError> {
+    let invalid_char = match value.chars().find(|c| is_invalid_char(*c)) {
+        Some(invalid_char) => invalid_char,
+        None => return Ok(Cow::Borrowed(value)),
+    };
+
+    let rejected_char = match behavior {
+        EncodeInvalidCharBehavior::Replace => {
+            return Ok(Cow::Owned(
+                value
+                    .chars()
+                    .map(|c| if is_invalid_char(c) { '\u{FFFD}' } else { c })
+                    .collect(),
+            ));
+        }
+        EncodeInvalidCharBehavior::Error => Some(invalid_char),
+        EncodeInvalidCharBehavior::Cdata | EncodeInvalidCharBehavior::Escape => {
+            value.chars().find(|c| is_forbidden_char(*c))
+        }
+    };
+
+    match rejected_char {
+        Some(c) => Err(NewEncodeError::new_from_writer(
+            EncodeErrorKind::InvalidChar(c),
+            writer,
+        )),
+        None => Ok(Cow::Borrowed(value)),
+    }
+}
+
+/// Writes a value as characters, with each restricted character written as a
+/// numeric character reference.
+///
+/// xml-rs escapes everything written through it, so the references are
+/// written to the output directly. Writing an empty `Characters` event first
+/// makes sure that xml-rs has finished writing any tag before them.
+fn write_escaped_characters<W: Write>(
+    writer: &mut EventWriter<CountingWriter<W>>,
+    value: &str,
+) -> Result<(), NewEncodeError> {
+    let io_error = |err: io::Error, writer: &EventWriter<CountingWriter<W>>| {
+        NewEncodeError::new_from_writer(EncodeErrorKind::Io(err), writer)
+    };
+
+    writer
+        .write(XmlWriteEvent::characters(""))
+        .map_err(|e| NewEncodeError::new_from_writer(e.into(), writer))?;
+
+    let mut start = 0;
+
+    for (index, c) in value.char_indices() {
+        if is_restricted_char(c) {
+            if start < index {
+                writer
+                    .write(XmlWriteEvent::characters(&value[start..index]))
+                    .map_err(|e| NewEncodeError::new_from_writer(e.into(), writer))?;
+            }
+
+            write!(writer.inner_mut(), "&#{};", c as u32).map_err(|err| io_error(err, writer))?;
+            start = index + c.len_utf8();
+        }
+    }
+
+    if start < value.len() {
+        writer
+            .write(XmlWriteEvent::characters(&value[start..]))
+            .map_err(|e| NewEncodeError::new_from_writer(e.into(), writer))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn wrapped_base64_matches_splitting_lines() {
+        let format = XmlFormat {
+            base64_line_width: Some(72),
+            ..XmlFormat::default()
+        };
+        let mut writer = XmlEventWriter::with_format(Vec::new(), format);
+
+        for len in 0..300 {
+            let data: Vec<u8> = (0..len).map(|i| (i * 7) as u8).collect();
+            let unwrapped = base64::encode(&data);
+            let expected = unwrapped
+                .as_bytes()
+                .chunks(72)
+                .map(|line| std::str::from_utf8(line).unwrap())
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            writer.encode_base64(&data);
+            assert_eq!(writer.base64_buffer, expected, "input of {} bytes", len);
+        }
+    }
+}
@@ -0,0 +1,1053 @@
+use std::borrow::Cow;
+
+use rbx_dom_weak::types::Variant;
+use xml::common::TextPosition;
+
+use crate::{
+    deserializer_core::XmlEventReader,
+    error::{DecodeError, DecodeErrorKind},
+    types::read_plain_value_xml,
+};
+
+/// An event produced by a [`SliceDecoder`]. Everything in it is borrowed from
+/// the decoder's input where possible.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum SliceEvent<'a> {
+    /// An instance has started. All events until the matching `EndInstance`
+    /// describe this instance, its properties, and its descendants.
+    StartInstance {
+        /// The instance's class name.
+        class: Cow<'a, str>,
+
+        /// The instance's referent in the file, if it has one.
+        referent: Option<Cow<'a, str>>,
+    },
+
+    /// The current instance has a property. Properties of every type are
+    /// given, including `Ref` and `SharedString` properties and types that
+    /// rbx_xml doesn't understand.
+    Property {
+        /// The name of the property.
+        name: Cow<'a, str>,
+
+        /// The element holding the property's value.
+        value: SliceElement<'a>,
+    },
+
+    /// The current instance has ended.
+    EndInstance,
+
+    /// An entry in the file's shared string dictionary. Its contents are the
+    /// base64 text of the element, which can be decoded with
+    /// [`SliceElement::base64`].
+    SharedString {
+        /// The hash of the shared string, as it appears in the file.
+        hash: Cow<'a, str>,
+
+        /// The element holding the shared string.
+        value: SliceElement<'a>,
+    },
+}
+
+/// A property or shared string element borrowed from the input of a
+/// [`SliceDecoder`], which is only decoded when asked to be.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SliceElement<'a> {
+    type_name: &'a str,
+    xml: &'a str,
+
+    /// The position of the start of the element in the document, used to
+    /// give the right position to errors.
+    row: u64,
+    column: u64,
+}
+
+impl<'a> SliceElement<'a> {
+    /// The name of the element, which is the type of the value as it appears
+    /// in the file, like `Vector3` or `Ref`.
+    pub fn type_name(&self) -> &'a str {
+        self.type_name
+    }
+
+    /// The element's XML, exactly as it appears in the input.
+    pub fn xml(&self) -> &'a [u8] {
+        self.xml.as_bytes()
+    }
+
+    /// The text inside of the element. Like the rest of rbx_xml, this leaves
+    /// out text that's only whitespace and joins CDATA sections with the text
+    /// around them.
+    ///
+    /// The text is borrowed from the input unless it has to be changed, like
+    /// when it contains escaped characters.
+    ///
+    /// `Ref` properties hold the referent of the instance they point to, or
+    /// `null`, and `SharedString` properties hold the hash of their shared
+    /// string.
+    pub fn text(&self) -> Result<Cow<'a, str>, DecodeError> {
+        let mut scanner = Scanner::at(self.xml, self.row, self.column);
+
+        if let Token::Start {
+            self_closing: true, ..
+        } = scanner.next_token()?
+        {
+            return Ok(Cow::Borrowed(""));
+        }
+
+        let mut text: Option<Cow<'a, str>> = None;
+
+        loop {
+            let start = scanner.offset;
+
+            let piece = match scanner.next_token()? {
+                Token::Text(piece) if is_whitespace(piece) => continue,
+                Token::Text(piece) => {
+                    unescape(piece).map_err(|explain| scanner.error(start, explain))?
+                }
+                Token::CData(piece) => normalize_newlines(piece),
+                Token::End { .. } => break,
+                _ => return Err(scanner.error(start, "expected text, found an element")),
+            };
+
+            text = Some(match text {
+                None => piece,
+                Some(text) => Cow::Owned(text.into_owned() + &piece),
+            });
+        }
+
+        Ok(text.unwrap_or(Cow::Borrowed("")))
+    }
+
+    /// Decodes the base64 text inside of the element, ignoring whitespace.
+    /// This is how shared strings and `BinaryString` properties are stored.
+    pub fn base64(&self) -> Result<Vec<u8>, DecodeError> {
+        let contents: String = self
+            .text()?
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .collect();
+
+        base64::decode(contents)
+            .map_err(|err| DecodeError::new_from_position(err.into(), self.position(1, 0)))
+    }
+
+    /// Decodes the element's value the same way [`StreamDecoder`] does,
+    /// without using any reflection information.
+    ///
+    /// Returns `None` for `Ref` and `SharedString` properties, whose values
+    /// depend on the rest of the document, and for types that rbx_xml doesn't
+    /// understand.
+    ///
+    /// [`StreamDecoder`]: crate::StreamDecoder
+    pub fn value(&self) -> Result<Option<Variant>, DecodeError> {
+        let mut reader = XmlEventReader::from_source(self.xml.as_bytes());
+
+        let value = reader
+            .expect_next()
+            .and_then(|_| read_plain_value_xml(&mut reader, self.type_name));
+
+        value.map_err(|err| {
+            let position = self.position(err.line(), err.column());
+            err.with_position(position)
+        })
+    }
+
+    /// Converts a 1-based line and a column within the element to a position
+    /// in the document.
+    fn position(&self, line: usize, column: usize) -> TextPosition {
+        if line <= 1 {
+            TextPosition {
+                row: self.row,
+                column: self.column + column as u64,
+            }
+        } else {
+            TextPosition {
+                row: self.row + line as u64 - 1,
+                column: column as u64,
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Context {
+    Root,
+    Item,
+    Properties,
+    SharedStrings,
+}
+
+/// Decodes an XML-format model or place that's already in memory as a stream
+/// of [`SliceEvent`]s, borrowing from the input instead of copying out of it.
+///
+/// This works like [`StreamDecoder`][crate::StreamDecoder], but class names,
+/// referents, and property names are given as `Cow<'a, str>`s that are only
+/// owned when they contain escaped characters, and property values are given
+/// as [`SliceElement`]s that point into the input. Values are only decoded
+/// when [`SliceElement::value`] is called, so tools that only look at a few
+/// properties of a large file, like a memory-mapped place, don't pay for
+/// decoding the rest of it.
+///
+/// The input has to be UTF-8. Like the other decoders, comments and the
+/// document's `<Meta>` and `<External>` tags are skipped.
+///
+/// After an error is returned, the decoder produces no more events.
+///
+/// ## Example
+/// ```
+/// use std::borrow::Cow;
+///
+/// use rbx_dom_weak::types::Variant;
+/// use rbx_xml::{SliceDecoder, SliceEvent};
+///
+/// let document = r#"
+/// <roblox version="4">
+///     <Item class="StringValue" referent="RBX0">
+///         <Properties>
+///             <string name="Name">Greeting</string>
+///             <string name="Value">Hello!</string>
+///         </Properties>
+///     </Item>
+/// </roblox>
+/// "#;
+///
+/// for event in SliceDecoder::new(document.as_bytes()) {
+///     if let SliceEvent::Property { name, value } = event? {
+///         if name == "Value" {
+///             // Text without escaped characters is borrowed from the input.
+///             assert!(matches!(value.text()?, Cow::Borrowed("Hello!")));
+///             assert_eq!(value.value()?, Some(Variant::String("Hello!".to_owned())));
+///         }
+///     }
+/// }
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct SliceDecoder<'a> {
+    input: &'a [u8],
+    scanner: Option<Scanner<'a>>,
+    stack: Vec<Context>,
+    finished: bool,
+
+    /// Whether the last instance started was a self-closing tag, which still
+    /// needs an `EndInstance` event.
+    pending_end: bool,
+}
+
+impl<'a> SliceDecoder<'a> {
+    /// Creates a `SliceDecoder` that reads from the given input.
+    pub fn new(input: &'a [u8]) -> Self {
+        SliceDecoder {
+            input,
+            scanner: None,
+            stack: Vec::new(),
+            finished: false,
+            pending_end: false,
+        }
+    }
+
+    /// The number of bytes of the input that have been read so far, which can
+    /// be used to report progress.
+    pub fn bytes_read(&self) -> u64 {
+        self.scanner
+            .as_ref()
+            .map_or(0, |scanner| scanner.offset as u64)
+    }
+
+    fn start(&mut self) -> Result<Scanner<'a>, DecodeError> {
+        let text = std::str::from_utf8(self.input).map_err(|err| {
+            DecodeError::new(DecodeErrorKind::MalformedXml(format!(
+                "SliceDecoder can only read UTF-8 documents: {}",
+                err
+            )))
+        })?;
+
+        let mut scanner = Scanner::at(text.strip_prefix('\u{FEFF}').unwrap_or(text), 0, 0);
+
+        loop {
+            let start = scanner.offset;
+
+            match scanner.next_token()? {
+                Token::Text(text) if is_whitespace(text) => {}
+                Token::Start {
+                    name: "roblox",
+                    attributes,
+                    self_closing,
+                } => {
+                    let version = find_attribute(attributes, "version")
+                        .map_err(|explain| scanner.error(start, explain))?
+                        .ok_or_else(|| {
+                            scanner.error_kind(start, DecodeErrorKind::MissingAttribute("version"))
+                        })?;
+
+                    if version != "4" {
+                        return Err(scanner.error_kind(
+                            start,
+                            DecodeErrorKind::WrongDocVersion(version.into_owned()),
+                        ));
+                    }
+
+                    if !self_closing {
+                        self.stack.push(Context::Root);
+                    }
+
+                    return Ok(scanner);
+                }
+                _ => return Err(scanner.error(start, "expected a <roblox> tag")),
+            }
+        }
+    }
+
+    fn next_event(&mut self) -> Result<Option<SliceEvent<'a>>, DecodeError> {
+        let mut scanner = match self.scanner.take() {
+            Some(scanner) => scanner,
+            None => self.start()?,
+        };
+
+        let event = self.next_event_from(&mut scanner);
+        self.scanner = Some(scanner);
+        event
+    }
+
+    fn next_event_from(
+        &mut self,
+        scanner: &mut Scanner<'a>,
+    ) -> Result<Option<SliceEvent<'a>>, DecodeError> {
+        if self.pending_end {
+            self.pending_end = false;
+            return Ok(Some(SliceEvent::EndInstance));
+        }
+
+        loop {
+            let context = match self.stack.last() {
+                Some(context) => *context,
+                None => return Ok(None),
+            };
+
+            let start = scanner.offset;
+            let token = scanner.next_token()?;
+
+            match (context, token) {
+                (_, Token::Text(text)) if is_whitespace(text) => {}
+                (
+                    Context::Root | Context::Item,
+                    Token::Start {
+                        name: "Item",
+                        attributes,
+                        self_closing,
+                    },
+                ) => {
+                    return self
+                        .start_instance(scanner, start, attributes, self_closing)
+                        .map(Some);
+                }
+                (
+                    Context::Root,
+                    Token::Start {
+                        name: name @ ("External" | "Meta"),
+                        self_closing,
+                        ..
+                    },
+                ) => {
+                    if !self_closing {
+                        scanner.skip_element(name)?;
+                    }
+                }
+                (
+                    Context::Root,
+                    Token::Start {
+                        name: "SharedStrings",
+                        self_closing,
+                        ..
+                    },
+                ) => {
+                    if !self_closing {
+                        self.stack.push(Context::SharedStrings);
+                    }
+                }
+                (
+                    Context::Item,
+                    Token::Start {
+                        name: "Properties",
+                        self_closing,
+                        ..
+                    },
+                ) => {
+                    if !self_closing {
+                        self.stack.push(Context::Properties);
+                    }
+                }
+                (Context::Item, Token::End { name: "Item" }) => {
+                    self.stack.pop();
+                    return Ok(Some(SliceEvent::EndInstance));
+                }
+                (
+                    Context::Properties,
+                    Token::Start {
+                        name,
+                        attributes,
+                        self_closing,
+                    },
+                ) => {
+                    let (name, value) =
+                        element(scanner, start, name, attributes, self_closing, "name")?;
+
+                    return Ok(Some(SliceEvent::Property { name, value }));
+                }
+                (
+                    Context::SharedStrings,
+                    Token::Start {
+                        name: "SharedString",
+                        attributes,
+                        self_closing,
+                    },
+                ) => {
+                    let (hash, value) = element(
+                        scanner,
+                        start,
+                        "SharedString",
+                        attributes,
+                        self_closing,
+                        "md5",
+                    )?;
+
+                    return Ok(Some(SliceEvent::SharedString { hash, value }));
+                }
+                (Context::Root, Token::End { name: "roblox" })
+                | (Context::Properties, Token::End { name: "Properties" })
+                | (
+                    Context::SharedStrings,
+                    Token::End {
+                        name: "SharedStrings",
+                    },
+                ) => {
+                    self.stack.pop();
+                }
+                (_, Token::Eof) => {
+                    return Err(scanner.error_kind(start, DecodeErrorKind::UnexpectedEof))
+                }
+                (_, token) => return Err(scanner.error(start, token.unexpected())),
+            }
+        }
+    }
+
+    fn start_instance(
+        &mut self,
+        scanner: &mut Scanner<'a>,
+        start: usize,
+        attributes: &'a str,
+        self_closing: bool,
+    ) -> Result<SliceEvent<'a>, DecodeError> {
+        let attribute = |scanner: &mut Scanner<'a>, name: &str| {
+            find_attribute(attributes, name).map_err(|explain| scanner.error(start, explain))
+        };
+
+        let class = attribute(scanner, "class")?
+            .ok_or_else(|| scanner.error_kind(start, DecodeErrorKind::MissingAttribute("class")))?;
+        let referent = attribute(scanner, "referent")?;
+
+        if self_closing {
+            self.pending_end = true;
+        } else {
+            self.stack.push(Context::Item);
+        }
+
+        Ok(SliceEvent::StartInstance { class, referent })
+    }
+}
+
+impl<'a> Iterator for SliceDecoder<'a> {
+    type Item = Result<SliceEvent<'a>, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        match self.next_event() {
+            Ok(Some(event)) => Some(Ok(event)),
+            Ok(None) => {
+                self.finished = true;
+                None
+            }
+            Err(err) => {
+                self.finished = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+/// Reads the rest of an element whose start tag was just read, returning the
+/// value of its `key_attribute` and the element itself.
+fn element<'a>(
+    scanner: &mut Scanner<'a>,
+    start: usize,
+    type_name: &'a str,
+    attributes: &'a str,
+    self_closing: bool,
+    key_attribute: &'static str,
+) -> Result<(Cow<'a, str>, SliceElement<'a>), DecodeError> {
+    let key = find_attribute(attributes, key_attribute)
+        .map_err(|explain| scanner.error(start, explain))?
+        .ok_or_else(|| {
+            scanner.error_kind(start, DecodeErrorKind::MissingAttribute(key_attribute))
+        })?;
+
+    let (row, column) = scanner.position_of(start);
+
+    if !self_closing {
+        scanner.skip_element(type_name)?;
+    }
+
+    let text = scanner.text;
+    let element = SliceElement {
+        type_name,
+        xml: &text[start..scanner.offset],
+        row,
+        column,
+    };
+
+    Ok((key, element))
+}
+
+/// A piece of a document read by a `Scanner`. Comments and processing
+/// instructions are skipped.
+#[derive(Debug)]
+enum Token<'a> {
+    Start {
+        name: &'a str,
+        attributes: &'a str,
+        self_closing: bool,
+    },
+    End {
+        name: &'a str,
+    },
+    Text(&'a str),
+    CData(&'a str),
+    Eof,
+}
+
+impl Token<'_> {
+    fn unexpected(&self) -> String {
+        match self {
+            Token::Start { name, .. } => format!("unexpected tag <{}>", name),
+            Token::End { name } => format!("unexpected end tag </{}>", name),
+            Token::Text(_) | Token::CData(_) => "unexpected text".to_owned(),
+            Token::Eof => "unexpected end of document".to_owned(),
+        }
+    }
+}
+
+/// Splits a document into tags and text without copying it, keeping track of
+/// line and column numbers for errors.
+struct Scanner<'a> {
+    text: &'a str,
+    offset: usize,
+
+    /// The position of `counted_offset` in the document. Positions are only
+    /// worked out when they're needed, continuing from the last one.
+    counted_offset: usize,
+    row: u64,
+    column: u64,
+}
+
+impl<'a> Scanner<'a> {
+    /// Creates a `Scanner` over text that starts at the given position in the
+    /// document.
+    fn at(text: &'a str, row: u64, column: u64) -> Self {
+        Scanner {
+            text,
+            offset: 0,
+            counted_offset: 0,
+            row,
+            column,
+        }
+    }
+
+    fn position_of(&mut self, offset: usize) -> (u64, u64) {
+        for c in self.text[self.counted_offset..offset].chars() {
+            if c == '\n' {
+                self.row += 1;
+                self.column = 0;
+            } else {
+                self.column += 1;
+            }
+        }
+
+        self.counted_offset = offset;
+        (self.row, self.column)
+    }
+
+    fn error<S: Into<String>>(&mut self, offset: usize, explain: S) -> DecodeError {
+        self.error_kind(offset, DecodeErrorKind::MalformedXml(explain.into()))
+    }
+
+    fn error_kind(&mut self, offset: usize, kind: DecodeErrorKind) -> DecodeError {
+        let (row, column) = self.position_of(offset.max(self.counted_offset));
+        DecodeError::new_from_position(kind, TextPosition { row, column })
+    }
+
+    /// Finds `pattern` after `from`, moving past it.
+    fn skip_past(&mut self, from: usize, pattern: &str, what: &str) -> Result<usize, DecodeError> {
+        match self.text[from..].find(pattern) {
+            Some(index) => {
+                let found = from + index;
+                self.offset = found + pattern.len();
+                Ok(found)
+            }
+            None => Err(self.error(self.offset, format!("unterminated {}", what))),
+        }
+    }
+
+    fn next_token(&mut self) -> Result<Token<'a>, DecodeError> {
+        let text = self.text;
+
+        loop {
+            let start = self.offset;
+            let rest = &text[start..];
+
+            if rest.is_empty() {
+                return Ok(Token::Eof);
+            } else if rest.starts_with("<!--") {
+                self.skip_past(start + 4, "-->", "comment")?;
+            } else if rest.starts_with("<![CDATA[") {
+                let end = self.skip_past(start + 9, "]]>", "CDATA section")?;
+                return Ok(Token::CData(&text[start + 9..end]));
+            } else if rest.starts_with("<?") {
+                self.skip_past(start + 2, "?>", "processing instruction")?;
+            } else if rest.starts_with("<!") {
+                return Err(self.error(start, "document type declarations are not supported"));
+            } else if let Some(tag) = rest.strip_prefix("</") {
+                let end = self.skip_past(start + 2, ">", "end tag")?;
+                let name = text[start + 2..end].trim_end();
+
+                if name.is_empty() || tag.starts_with(char::is_whitespace) {
+                    return Err(self.error(start, "invalid end tag"));
+                }
+
+                return Ok(Token::End { name });
+            } else if rest.starts_with('<') {
+                return self.start_tag(start);
+            } else {
+                let end = rest.find('<').map_or(text.len(), |index| start + index);
+                self.offset = end;
+                return Ok(Token::Text(&text[start..end]));
+            }
+        }
+    }
+
+    fn start_tag(&mut self, start: usize) -> Result<Token<'a>, DecodeError> {
+        let text = self.text;
+        let mut quote = None;
+        let mut end = None;
+
+        for (index, byte) in text.as_bytes()[start + 1..].iter().enumerate() {
+            match (quote, byte) {
+                (None, b'"' | b'\'') => quote = Some(*byte),
+                (Some(open), _) if open == *byte => quote = None,
+                (None, b'>') => {
+                    end = Some(start + 1 + index);
+                    break;
+                }
+                (None, b'<') => break,
+                _ => {}
+            }
+        }
+
+        let end = end.ok_or_else(|| self.error(start, "unterminated tag"))?;
+        self.offset = end + 1;
+
+        let tag = &text[start + 1..end];
+        let (tag, self_closing) = match tag.strip_suffix('/') {
+            Some(tag) => (tag, true),
+            None => (tag, false),
+        };
+
+        let name_end = tag.find(char::is_whitespace).unwrap_or(tag.len());
+        let (name, attributes) = tag.split_at(name_end);
+
+        if name.is_empty() {
+            return Err(self.error(start, "invalid tag"));
+        }
+
+        Ok(Token::Start {
+            name,
+            attributes,
+            self_closing,
+        })
+    }
+
+    /// Skips past the end of the element with the given name, whose start tag
+    /// was just read, checking that the tags inside of it are balanced.
+    fn skip_element(&mut self, name: &'a str) -> Result<(), DecodeError> {
+        let mut open = vec![name];
+
+        while let Some(&innermost) = open.last() {
+            let start = self.offset;
+
+            match self.next_token()? {
+                Token::Start {
+                    name,
+                    self_closing: false,
+                    ..
+                } => open.push(name),
+                Token::End { name } if name == innermost => {
+                    open.pop();
+                }
+                Token::Start { .. } | Token::Text(_) | Token::CData(_) => {}
+                Token::End { name } => {
+                    let explain = format!("expected </{}>, found </{}>", innermost, name);
+                    return Err(self.error(start, explain));
+                }
+                Token::Eof => return Err(self.error_kind(start, DecodeErrorKind::UnexpectedEof)),
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Finds the value of the attribute with the given name in the attributes of
+/// a start tag, unescaping it.
+fn find_attribute<'a>(
+    mut attributes: &'a str,
+    name: &str,
+) -> Result<Option<Cow<'a, str>>, &'static str> {
+    loop {
+        attributes = attributes.trim_start();
+        if attributes.is_empty() {
+            return Ok(None);
+        }
+
+        let (attribute_name, rest) = attributes
+            .split_once('=')
+            .ok_or("attribute is missing a value")?;
+        let rest = rest.trim_start();
+
+        let quote = match rest.chars().next() {
+            Some(quote @ ('"' | '\'')) => quote,
+            _ => return Err("attribute value is not quoted"),
+        };
+        let (value, rest) = rest[1..]
+            .split_once(quote)
+            .ok_or("unterminated attribute value")?;
+
+        if attribute_name.trim_end() == name {
+            return unescape(value).map(Some);
+        }
+
+        attributes = rest;
+    }
+}
+
+/// Replaces entity and character references in text, and normalizes its line
+/// endings, borrowing the text if nothing needs to change.
+fn unescape(text: &str) -> Result<Cow<'_, str>, &'static str> {
+    if !text.contains('&') {
+        return Ok(normalize_newlines(text));
+    }
+
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(index) = rest.find('&') {
+        output.push_str(&rest[..index]);
+
+        let (reference, after) = rest[index + 1..]
+            .split_once(';')
+            .ok_or("unterminated entity reference")?;
+
+        match reference {
+            "lt" => output.push('<'),
+            "gt" => output.push('>'),
+            "amp" => output.push('&'),
+            "quot" => output.push('"'),
+            "apos" => output.push('\''),
+            _ => {
+                let code = if let Some(hex) = reference
+                    .strip_prefix("#x")
+                    .or_else(|| reference.strip_prefix("#X"))
+                {
+                    u32::from_str_radix(hex, 16).ok()
+                } else if let Some(decimal) = reference.strip_prefix('#') {
+                    decimal.parse().ok()
+                } else {
+                    return Err("unknown entity reference");
+                };
+
+                output.push(
+                    code.and_then(char::from_u32)
+                        .ok_or("invalid character reference")?,
+                );
+            }
+        }
+
+        rest = after;
+    }
+
+    output.push_str(rest);
+
+    Ok(Cow::Owned(normalize_newlines(&output).into_owned()))
+}
+
+/// Turns `\r\n` and lone `\r` into `\n`, like XML parsers are required to.
+fn normalize_newlines(text: &str) -> Cow<'_, str> {
+    if text.contains('\r') {
+        Cow::Owned(text.replace("\r\n", "\n").replace('\r', "\n"))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// Whether text is only XML whitespace, which rbx_xml skips between tags.
+fn is_whitespace(text: &str) -> bool {
+    text.bytes()
+        .all(|byte| matches!(byte, b' ' | b'\t' | b'\r' | b'\n'))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rbx_dom_weak::types::Vector3;
+
+    fn events(document: &str) -> Vec<SliceEvent<'_>> {
+        SliceDecoder::new(document.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn nested_instances() {
+        let document = r#"
+            <?xml version="1.0" encoding="utf-8"?>
+            <roblox version="4">
+                <Meta name="ExplicitAutoJoints">true</Meta>
+                <External>null</External>
+                <!-- A <comment> -->
+                <Item class="Model" referent="RBX0">
+                    <Properties>
+                        <string name="Name">Fish &amp; Chips</string>
+                        <Vector3 name="Pivot"><X>1</X><Y>2</Y><Z>3</Z></Vector3>
+                        <Ref name="PrimaryPart">RBX1</Ref>
+                        <FancyNewType name="Fancy"><X>1</X></FancyNewType>
+                    </Properties>
+                    <Item class="Part" referent="RBX1"/>
+                </Item>
+                <SharedStrings>
+                    <SharedString md5="aGFzaA==">AQ
+                        ID</SharedString>
+                </SharedStrings>
+            </roblox>
+        "#;
+
+        let events = events(document);
+        assert_eq!(events.len(), 9);
+
+        assert_eq!(
+            events[0],
+            SliceEvent::StartInstance {
+                class: Cow::Borrowed("Model"),
+                referent: Some(Cow::Borrowed("RBX0")),
+            }
+        );
+
+        let properties: Vec<_> = events[1..5]
+            .iter()
+            .map(|event| match event {
+                SliceEvent::Property { name, value } => (name.as_ref(), *value),
+                _ => panic!("expected a property, found {:?}", event),
+            })
+            .collect();
+
+        let (name, value) = properties[0];
+        assert_eq!(name, "Name");
+        assert_eq!(value.text().unwrap(), "Fish & Chips");
+        assert!(matches!(value.text().unwrap(), Cow::Owned(_)));
+
+        let (name, value) = properties[1];
+        assert_eq!(name, "Pivot");
+        assert_eq!(
+            value.value().unwrap(),
+            Some(Variant::Vector3(Vector3::new(1.0, 2.0, 3.0)))
+        );
+
+        let (name, value) = properties[2];
+        assert_eq!(name, "PrimaryPart");
+        assert_eq!(value.type_name(), "Ref");
+        assert!(matches!(value.text().unwrap(), Cow::Borrowed("RBX1")));
+        assert_eq!(value.value().unwrap(), None);
+
+        let (name, value) = properties[3];
+        assert_eq!(name, "Fancy");
+        assert_eq!(
+            value.xml(),
+            br#"<FancyNewType name="Fancy"><X>1</X></FancyNewType>"#
+        );
+        assert!(value.text().is_err());
+
+        assert_eq!(
+            events[5],
+            SliceEvent::StartInstance {
+                class: Cow::Borrowed("Part"),
+                referent: Some(Cow::Borrowed("RBX1")),
+            }
+        );
+        assert_eq!(events[6], SliceEvent::EndInstance);
+        assert_eq!(events[7], SliceEvent::EndInstance);
+
+        match &events[8] {
+            SliceEvent::SharedString { hash, value } => {
+                assert_eq!(hash, "aGFzaA==");
+                assert_eq!(value.base64().unwrap(), [1, 2, 3]);
+            }
+            event => panic!("expected a shared string, found {:?}", event),
+        }
+    }
+
+    /// Ensures that values decode the same way they do with `StreamDecoder`.
+    #[test]
+    fn matches_stream_decoder() {
+        use rbx_dom_weak::{
+            types::{CFrame, Color3, Matrix3, SharedString, Tags, UDim, UDim2},
+            InstanceBuilder, WeakDom,
+        };
+
+        use crate::{EncodeOptions, EncodePropertyBehavior, StreamDecoder, StreamEvent};
+
+        let tree = WeakDom::new(
+            InstanceBuilder::new("DataModel").with_child(
+                InstanceBuilder::new("Folder")
+                    .with_name("<Weird & \"quoted\">")
+                    .with_property("Color", Color3::new(0.25, 0.5, 1.0))
+                    .with_property("Offset", UDim2::new(UDim::new(0.5, 1), UDim::new(0.25, -3)))
+                    .with_property(
+                        "Pivot",
+                        CFrame::new(Vector3::new(1.0, 2.0, 3.0), Matrix3::identity()),
+                    )
+                    .with_property("Tags", Tags::from(vec!["a".to_owned(), "b".to_owned()]))
+                    .with_property("Text", "line\r\nbreak ]]> done")
+                    .with_property("Data", SharedString::new(b"shared".to_vec()))
+                    .with_child(InstanceBuilder::new("Folder")),
+            ),
+        );
+
+        let options = EncodeOptions::new().property_behavior(EncodePropertyBehavior::NoReflection);
+        let document = crate::encode_to_vec(&tree, tree.root().children(), options).unwrap();
+
+        let stream: Vec<_> = StreamDecoder::new(document.as_slice())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let slice: Vec<_> = SliceDecoder::new(&document)
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(stream.len(), slice.len());
+
+        for (stream, slice) in stream.into_iter().zip(slice) {
+            match (stream, slice) {
+                (
+                    StreamEvent::StartInstance { class, referent },
+                    SliceEvent::StartInstance {
+                        class: slice_class,
+                        referent: slice_referent,
+                    },
+                ) => {
+                    assert_eq!(class, slice_class);
+                    assert_eq!(referent.as_deref(), slice_referent.as_deref());
+                }
+                (
+                    StreamEvent::Property { name, value },
+                    SliceEvent::Property { name: n, value: v },
+                ) => {
+                    assert_eq!(name, n);
+                    assert_eq!(Some(value), v.value().unwrap());
+                }
+                (
+                    StreamEvent::SharedStringProperty { name, hash },
+                    SliceEvent::Property { name: n, value: v },
+                ) => {
+                    assert_eq!(name, n);
+                    assert_eq!(hash, v.text().unwrap());
+                }
+                (
+                    StreamEvent::SharedString { hash, value },
+                    SliceEvent::SharedString { hash: h, value: v },
+                ) => {
+                    assert_eq!(hash, h);
+                    assert_eq!(value.data(), v.base64().unwrap());
+                }
+                (StreamEvent::EndInstance, SliceEvent::EndInstance) => {}
+                (stream, slice) => panic!("{:?} doesn't match {:?}", stream, slice),
+            }
+        }
+    }
+
+    #[test]
+    fn cdata_is_borrowed() {
+        let document = r#"<roblox version="4">
+            <Item class="Script">
+                <Properties>
+                    <ProtectedString name="Source"><![CDATA[print("<hi>")]]></ProtectedString>
+                </Properties>
+            </Item>
+        </roblox>"#;
+
+        match &events(document)[1] {
+            SliceEvent::Property { value, .. } => {
+                assert!(matches!(
+                    value.text().unwrap(),
+                    Cow::Borrowed(r#"print("<hi>")"#)
+                ));
+                assert_eq!(
+                    value.value().unwrap(),
+                    Some(Variant::String(r#"print("<hi>")"#.to_owned()))
+                );
+            }
+            event => panic!("expected a property, found {:?}", event),
+        }
+    }
+
+    #[test]
+    fn value_errors_have_document_positions() {
+        let document = "<roblox version=\"4\">\n<Item class=\"Part\">\n<Properties>\n  <float name=\"Size\">big</float>\n</Properties>\n</Item>\n</roblox>";
+
+        let value = match &events(document)[1] {
+            SliceEvent::Property { value, .. } => *value,
+            event => panic!("expected a property, found {:?}", event),
+        };
+
+        let err = value.value().unwrap_err();
+        assert_eq!(err.line(), 4);
+    }
+
+    #[test]
+    fn stops_after_error() {
+        let document = r#"
+            <roblox version="4">
+                <Item class="Folder">
+                    <Properties>
+                        <string name="Name">Oops</bool>
+                    </Properties>
+                </Item>
+            </roblox>
+        "#;
+
+        let mut decoder = SliceDecoder::new(document.as_bytes());
+        assert!(decoder.next().unwrap().is_ok());
+
+        let err = decoder.next().unwrap().unwrap_err();
+        assert_eq!(err.line(), 5);
+        assert!(decoder.next().is_none());
+    }
+
+    #[test]
+    fn wrong_version_and_encoding() {
+        let mut decoder = SliceDecoder::new(br#"<roblox version="3"></roblox>"#);
+        assert!(decoder.next().unwrap().is_err());
+
+        let mut decoder = SliceDecoder::new(b"<roblox version=\"4\">\xFF</roblox>");
+        assert!(decoder.next().unwrap().is_err());
+    }
+}
@@ -0,0 +1,448 @@
+use std::{collections::HashSet, io::Read};
+
+use rbx_dom_weak::types::{SharedString, Variant};
+
+use crate::{
+    deserializer_core::{XmlEventReader, XmlReadEvent},
+    error::{DecodeError, DecodeErrorKind},
+    types::read_plain_value_xml,
+};
+
+/// An event produced by a [`StreamDecoder`].
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum StreamEvent {
+    /// An instance has started. All events until the matching `EndInstance`
+    /// describe this instance, its properties, and its descendants.
+    StartInstance {
+        /// The instance's class name.
+        class: String,
+
+        /// The instance's referent in the file, if it has one.
+        referent: Option<String>,
+    },
+
+    /// The current instance has a property with the given value.
+    ///
+    /// Names and values are exactly as they appear in the file, including the
+    /// instance's `Name`.
+    Property {
+        /// The name of the property.
+        name: String,
+
+        /// The value of the property.
+        value: Variant,
+    },
+
+    /// The current instance has a `Ref` property. Refs can point to instances
+    /// later in the file, so they are given as referents in the file instead of
+    /// as `Ref` values.
+    RefProperty {
+        /// The name of the property.
+        name: String,
+
+        /// The referent of the instance the property points to, or `None` if
+        /// the property is null.
+        referent: Option<String>,
+    },
+
+    /// The current instance has a `SharedString` property. The contents of
+    /// shared strings are stored at the end of the file, so they are given as
+    /// the hash that keys a later `SharedString` event.
+    SharedStringProperty {
+        /// The name of the property.
+        name: String,
+
+        /// The hash of the shared string, as it appears in the file.
+        hash: String,
+    },
+
+    /// The current instance has ended.
+    EndInstance,
+
+    /// An entry in the file's shared string dictionary.
+    SharedString {
+        /// The hash of the shared string, as it appears in the file.
+        hash: String,
+
+        /// The contents of the shared string.
+        value: SharedString,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Context {
+    Root,
+    Item,
+    Properties,
+    SharedStrings,
+}
+
+/// Decodes an XML-format model or place as a stream of [`StreamEvent`]s,
+/// without ever building a `WeakDom`.
+///
+/// This is useful for tools that only need to look at part of a file, since
+/// only the current property is kept in memory. No reflection information is
+/// used, so property names and types appear exactly as they do in the file.
+/// Properties of unknown types are skipped.
+///
+/// After an error is returned, the decoder produces no more events.
+///
+/// ## Example
+/// ```
+/// use rbx_xml::{StreamDecoder, StreamEvent};
+///
+/// let document = r#"
+/// <roblox version="4">
+///     <Item class="Folder" referent="RBX0">
+///         <Properties>
+///             <string name="Name">Stuff</string>
+///         </Properties>
+///         <Item class="Part" referent="RBX1"></Item>
+///     </Item>
+/// </roblox>
+/// "#;
+///
+/// let mut instance_count = 0;
+/// for event in StreamDecoder::new(document.as_bytes()) {
+///     if let StreamEvent::StartInstance { .. } = event? {
+///         instance_count += 1;
+///     }
+/// }
+///
+/// assert_eq!(instance_count, 2);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct StreamDecoder<R: Read> {
+    reader: XmlEventReader<R>,
+    stack: Vec<Context>,
+    started: bool,
+    finished: bool,
+
+    /// Contains all of the unknown types that have been found so far, so that
+    /// we only output a warning once per type.
+    unknown_type_names: HashSet<String>,
+}
+
+impl<R: Read> StreamDecoder<R> {
+    /// Creates a `StreamDecoder` that reads from the given source.
+    pub fn new(source: R) -> Self {
+        StreamDecoder {
+            reader: XmlEventReader::from_source(source),
+            stack: Vec::new(),
+            started: false,
+            finished: false,
+            unknown_type_names: HashSet::new(),
+        }
+    }
+
+    /// The number of bytes that have been read from the source so far, which
+    /// can be used to report progress. This can be slightly ahead of the last
+    /// event returned, since the source is read in chunks.
+    pub fn bytes_read(&self) -> u64 {
+        self.reader.bytes_read()
+    }
+
+    fn start(&mut self) -> Result<(), DecodeError> {
+        match self.reader.expect_next()? {
+            XmlReadEvent::StartDocument { .. } => {}
+            _ => unreachable!(),
+        }
+
+        let doc_attributes = self.reader.expect_start_with_name("roblox")?;
+
+        let doc_version = doc_attributes
+            .into_iter()
+            .find(|attribute| attribute.name.local_name == "version")
+            .map(|attribute| attribute.value)
+            .ok_or_else(|| {
+                self.reader
+                    .error(DecodeErrorKind::MissingAttribute("version"))
+            })?;
+
+        if doc_version != "4" {
+            return Err(self
+                .reader
+                .error(DecodeErrorKind::WrongDocVersion(doc_version)));
+        }
+
+        self.stack.push(Context::Root);
+        Ok(())
+    }
+
+    fn next_event(&mut self) -> Result<Option<StreamEvent>, DecodeError> {
+        if !self.started {
+            self.started = true;
+            self.start()?;
+        }
+
+        loop {
+            let context = match self.stack.last() {
+                Some(context) => *context,
+                None => return Ok(None),
+            };
+
+            let (start_name, end_name) = match self.reader.expect_peek()? {
+                XmlReadEvent::StartElement { name, .. } => (Some(name.local_name.clone()), None),
+                XmlReadEvent::EndElement { name } => (None, Some(name.local_name.clone())),
+                XmlReadEvent::EndDocument if context == Context::Root => return Ok(None),
+                _ => return Err(self.unexpected_event()),
+            };
+
+            match (context, start_name.as_deref(), end_name.as_deref()) {
+                (Context::Root | Context::Item, Some("Item"), _) => {
+                    return self.start_instance().map(Some);
+                }
+                (Context::Root, Some("External" | "Meta"), _) => {
+                    self.reader.eat_unknown_tag()?;
+                }
+                (Context::Root, Some("SharedStrings"), _) => {
+                    self.reader.expect_start_with_name("SharedStrings")?;
+                    self.stack.push(Context::SharedStrings);
+                }
+                (Context::Root, _, Some("roblox")) => {
+                    self.reader.expect_next()?;
+                    self.stack.pop();
+                }
+                (Context::Item, Some("Properties"), _) => {
+                    self.reader.expect_start_with_name("Properties")?;
+                    self.stack.push(Context::Properties);
+                }
+                (Context::Item, _, Some("Item")) => {
+                    self.reader.expect_next()?;
+                    self.stack.pop();
+                    return Ok(Some(StreamEvent::EndInstance));
+                }
+                (Context::Properties, Some(_), _) => {
+                    if let Some(event) = self.property()? {
+                        return Ok(Some(event));
+                    }
+                }
+                (Context::Properties, _, Some("Properties"))
+                | (Context::SharedStrings, _, Some("SharedStrings")) => {
+                    self.reader.expect_next()?;
+                    self.stack.pop();
+                }
+                (Context::SharedStrings, Some("SharedString"), _) => {
+                    return self.shared_string().map(Some);
+                }
+                _ => return Err(self.unexpected_event()),
+            }
+        }
+    }
+
+    fn unexpected_event(&mut self) -> DecodeError {
+        match self.reader.expect_next() {
+            Ok(event) => self
+                .reader
+                .error(DecodeErrorKind::UnexpectedXmlEvent(event)),
+            Err(err) => err,
+        }
+    }
+
+    fn start_instance(&mut self) -> Result<StreamEvent, DecodeError> {
+        let mut class = None;
+        let mut referent = None;
+
+        for attribute in self.reader.expect_start_with_name("Item")? {
+            match attribute.name.local_name.as_str() {
+                "class" => class = Some(attribute.value),
+                "referent" => referent = Some(attribute.value),
+                _ => {}
+            }
+        }
+
+        let class = class.ok_or_else(|| {
+            self.reader
+                .error(DecodeErrorKind::MissingAttribute("class"))
+        })?;
+
+        self.stack.push(Context::Item);
+        Ok(StreamEvent::StartInstance { class, referent })
+    }
+
+    /// Reads the next property, returning `None` if it had an unknown type and
+    /// was skipped.
+    fn property(&mut self) -> Result<Option<StreamEvent>, DecodeError> {
+        let (type_name, name) = match self.reader.expect_peek()? {
+            XmlReadEvent::StartElement {
+                name, attributes, ..
+            } => {
+                let property_name = attributes
+                    .iter()
+                    .find(|attribute| attribute.name.local_name == "name")
+                    .map(|attribute| attribute.value.clone());
+
+                (name.local_name.clone(), property_name)
+            }
+            _ => unreachable!(),
+        };
+
+        let name =
+            name.ok_or_else(|| self.reader.error(DecodeErrorKind::MissingAttribute("name")))?;
+
+        match type_name.as_str() {
+            "Ref" => {
+                let contents = self.reader.read_tag_contents("Ref")?;
+                let referent = if contents == "null" {
+                    None
+                } else {
+                    Some(contents)
+                };
+
+                Ok(Some(StreamEvent::RefProperty { name, referent }))
+            }
+            "SharedString" => {
+                let hash = self.reader.read_tag_contents("SharedString")?;
+                Ok(Some(StreamEvent::SharedStringProperty { name, hash }))
+            }
+            _ => match read_plain_value_xml(&mut self.reader, &type_name)? {
+                Some(value) => Ok(Some(StreamEvent::Property { name, value })),
+                None => {
+                    if self.unknown_type_names.insert(type_name.clone()) {
+                        log::warn!(
+                            "Unknown value type name \"{}\" in Roblox XML model file. \
+                             Found in property {}.",
+                            type_name,
+                            name,
+                        );
+                    }
+
+                    self.reader.eat_unknown_tag()?;
+                    Ok(None)
+                }
+            },
+        }
+    }
+
+    fn shared_string(&mut self) -> Result<StreamEvent, DecodeError> {
+        let hash = self
+            .reader
+            .expect_start_with_name("SharedString")?
+            .into_iter()
+            .find(|attribute| attribute.name.local_name == "md5")
+            .map(|attribute| attribute.value)
+            .ok_or_else(|| self.reader.error(DecodeErrorKind::MissingAttribute("md5")))?;
+
+        let buffer = self.reader.read_base64_characters()?;
+        self.reader.expect_end_with_name("SharedString")?;
+
+        Ok(StreamEvent::SharedString {
+            hash,
+            value: SharedString::new(buffer),
+        })
+    }
+}
+
+impl<R: Read> Iterator for StreamDecoder<R> {
+    type Item = Result<StreamEvent, DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+
+        match self.next_event() {
+            Ok(Some(event)) => Some(Ok(event)),
+            Ok(None) => {
+                self.finished = true;
+                None
+            }
+            Err(err) => {
+                self.finished = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn events(document: &str) -> Vec<StreamEvent> {
+        StreamDecoder::new(document.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn nested_instances() {
+        let document = r#"
+            <roblox version="4">
+                <External>null</External>
+                <Item class="Model" referent="RBX0">
+                    <Properties>
+                        <string name="Name">Car</string>
+                        <Ref name="PrimaryPart">RBX1</Ref>
+                        <FancyNewType name="Skipped"><X>1</X></FancyNewType>
+                    </Properties>
+                    <Item class="Part" referent="RBX1">
+                        <Properties>
+                            <Ref name="Nothing">null</Ref>
+                            <SharedString name="Data">aGFzaA==</SharedString>
+                        </Properties>
+                    </Item>
+                </Item>
+                <SharedStrings>
+                    <SharedString md5="aGFzaA==">AQID</SharedString>
+                </SharedStrings>
+            </roblox>
+        "#;
+
+        assert_eq!(
+            events(document),
+            [
+                StreamEvent::StartInstance {
+                    class: "Model".to_owned(),
+                    referent: Some("RBX0".to_owned()),
+                },
+                StreamEvent::Property {
+                    name: "Name".to_owned(),
+                    value: Variant::String("Car".to_owned()),
+                },
+                StreamEvent::RefProperty {
+                    name: "PrimaryPart".to_owned(),
+                    referent: Some("RBX1".to_owned()),
+                },
+                StreamEvent::StartInstance {
+                    class: "Part".to_owned(),
+                    referent: Some("RBX1".to_owned()),
+                },
+                StreamEvent::RefProperty {
+                    name: "Nothing".to_owned(),
+                    referent: None,
+                },
+                StreamEvent::SharedStringProperty {
+                    name: "Data".to_owned(),
+                    hash: "aGFzaA==".to_owned(),
+                },
+                StreamEvent::EndInstance,
+                StreamEvent::EndInstance,
+                StreamEvent::SharedString {
+                    hash: "aGFzaA==".to_owned(),
+                    value: SharedString::new(vec![1, 2, 3]),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn stops_after_error() {
+        let document = r#"
+            <roblox version="4">
+                <Item referent="RBX0"></Item>
+            </roblox>
+        "#;
+
+        let mut decoder = StreamDecoder::new(document.as_bytes());
+        assert!(decoder.next().unwrap().is_err());
+        assert!(decoder.next().is_none());
+    }
+
+    #[test]
+    fn wrong_version() {
+        let mut decoder = StreamDecoder::new(r#"<roblox version="3"></roblox>"#.as_bytes());
+        assert!(decoder.next().unwrap().is_err());
+    }
+}
@@ -0,0 +1,288 @@
+use std::io::Write;
+
+use rbx_dom_weak::types::{Ref, Variant};
+
+use crate::{
+    error::EncodeError,
+    serializer::{
+        serialize_comments, serialize_metadata, serialize_property, serialize_raw_properties,
+        serialize_shared_strings, EmitState, EncodeOptions,
+    },
+    serializer_core::{XmlEventWriter, XmlWriteEvent},
+    types::write_name,
+};
+
+/// Encodes an XML-format model or place one instance at a time, without first
+/// building a `WeakDom`.
+///
+/// Instances are written by calling [`start_instance`][Self::start_instance],
+/// then [`write_property`][Self::write_property] for each of the instance's
+/// properties, then the same for each of its children, and finally
+/// [`end_instance`][Self::end_instance]. Properties are encoded the same way as
+/// they are by [`to_writer`][crate::to_writer], using the given
+/// [`EncodeOptions`]. This includes the raw properties and comments that
+/// [`EncodeOptions::metadata`] holds for each instance's `Ref`.
+///
+/// Each instance is identified by a `Ref` chosen by the caller, which `Ref`
+/// properties can point to whether or not the instance has been written yet.
+///
+/// ## Example
+/// ```
+/// use rbx_dom_weak::types::Ref;
+/// use rbx_xml::{EncodeOptions, StreamEncoder};
+///
+/// let mut encoder = StreamEncoder::new(Vec::new(), EncodeOptions::new())?;
+///
+/// encoder.start_instance(Ref::new(), "Folder", "Parts")?;
+/// for i in 0..3 {
+///     encoder.start_instance(Ref::new(), "Part", &format!("Part{}", i))?;
+///     encoder.write_property("Anchored", &true.into())?;
+///     encoder.end_instance()?;
+/// }
+/// encoder.end_instance()?;
+///
+/// let output = encoder.finish()?;
+/// let dom = rbx_xml::from_reader_default(output.as_slice())?;
+///
+/// let folder = dom.get_by_ref(dom.root().children()[0]).unwrap();
+/// assert_eq!(folder.children().len(), 3);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct StreamEncoder<'db, W: Write> {
+    writer: XmlEventWriter<W>,
+    state: EmitState<'db>,
+
+    /// The referents and class names of the instances that have been started
+    /// but not ended, from outermost to innermost.
+    open_instances: Vec<(Ref, String)>,
+
+    /// Whether the innermost open instance can still have properties written,
+    /// which is true until its first child is started.
+    properties_open: bool,
+}
+
+impl<'db, W: Write> StreamEncoder<'db, W> {
+    /// Creates a `StreamEncoder` that writes to the given output, and writes
+    /// the start of the document.
+    pub fn new(output: W, options: EncodeOptions<'db>) -> Result<Self, EncodeError> {
+        let mut writer = options.create_writer(output);
+        writer.write(XmlWriteEvent::start_element("roblox").attr("version", "4"))?;
+
+        let mut state = EmitState::new(options);
+        serialize_metadata(&mut writer, &mut state)?;
+
+        Ok(StreamEncoder {
+            writer,
+            state,
+            open_instances: Vec::new(),
+            properties_open: false,
+        })
+    }
+
+    /// Starts a new instance. If another instance is open, the new instance is
+    /// its child.
+    pub fn start_instance(
+        &mut self,
+        referent: Ref,
+        class_name: &str,
+        name: &str,
+    ) -> Result<(), EncodeError> {
+        self.close_properties()?;
+
+        if let Some(metadata) = self.state.metadata().instance(referent) {
+            serialize_comments(&mut self.writer, metadata.leading_comments())?;
+        }
+
+        let mapped_id = self.state.map_id(referent);
+
+        self.writer.write(
+            XmlWriteEvent::start_element("Item")
+                .attr("class", class_name)
+                .attr("referent", &mapped_id),
+        )?;
+        self.writer
+            .write(XmlWriteEvent::start_element("Properties"))?;
+
+        write_name(&mut self.writer, name)?;
+
+        self.open_instances.push((referent, class_name.to_owned()));
+        self.properties_open = true;
+
+        Ok(())
+    }
+
+    /// Writes a property of the current instance.
+    ///
+    /// ## Panics
+    /// Panics if there is no open instance, or if a child of the current
+    /// instance has already been started.
+    pub fn write_property(&mut self, name: &str, value: &Variant) -> Result<(), EncodeError> {
+        let (_, class_name) = self
+            .open_instances
+            .last()
+            .expect("cannot write a property without an open instance");
+
+        assert!(
+            self.properties_open,
+            "cannot write a property of an instance after starting one of its children"
+        );
+
+        serialize_property(&mut self.writer, &mut self.state, class_name, name, value)
+    }
+
+    /// Ends the current instance.
+    ///
+    /// ## Panics
+    /// Panics if there is no open instance.
+    pub fn end_instance(&mut self) -> Result<(), EncodeError> {
+        assert!(
+            !self.open_instances.is_empty(),
+            "cannot end an instance without an open instance"
+        );
+
+        self.close_properties()?;
+
+        let (referent, _) = self.open_instances.pop().unwrap();
+        if let Some(metadata) = self.state.metadata().instance(referent) {
+            serialize_comments(&mut self.writer, metadata.trailing_comments())?;
+        }
+
+        self.writer.end_element()?;
+        self.state.instance_finished(self.writer.bytes_written());
+
+        Ok(())
+    }
+
+    /// Writes the end of the document, including any shared strings that were
+    /// written, and returns the output.
+    ///
+    /// ## Panics
+    /// Panics if any instances have not been ended.
+    pub fn finish(mut self) -> Result<W, EncodeError> {
+        assert!(
+            self.open_instances.is_empty(),
+            "cannot finish encoding while instances are still open"
+        );
+
+        serialize_shared_strings(&mut self.writer, &mut self.state)?;
+        serialize_comments(&mut self.writer, self.state.metadata().comments())?;
+        self.writer.end_element()?;
+
+        Ok(self.writer.into_inner())
+    }
+
+    fn close_properties(&mut self) -> Result<(), EncodeError> {
+        if self.properties_open {
+            let (referent, class_name) = self.open_instances.last().unwrap();
+            if let Some(metadata) = self.state.metadata().instance(*referent) {
+                serialize_raw_properties(&mut self.writer, &self.state, class_name, metadata)?;
+            }
+
+            self.writer.end_element()?;
+            self.properties_open = false;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use rbx_dom_weak::types::SharedString;
+
+    use crate::{DecodeOptions, DecodePropertyBehavior, DocumentMetadata, EncodePropertyBehavior};
+
+    #[test]
+    fn nested_instances_and_refs() {
+        let model_ref = Ref::new();
+        let part_ref = Ref::new();
+
+        let options = EncodeOptions::new().property_behavior(EncodePropertyBehavior::NoReflection);
+        let mut encoder = StreamEncoder::new(Vec::new(), options).unwrap();
+        encoder.start_instance(model_ref, "Model", "Car").unwrap();
+        encoder
+            .write_property("PrimaryPart", &part_ref.into())
+            .unwrap();
+        encoder.start_instance(part_ref, "Part", "Body").unwrap();
+        encoder
+            .write_property("Anchored", &Variant::Bool(true))
+            .unwrap();
+        encoder.end_instance().unwrap();
+        encoder.end_instance().unwrap();
+        encoder.start_instance(Ref::new(), "Part", "Wheel").unwrap();
+        encoder
+            .write_property("Data", &SharedString::new(vec![1, 2, 3]).into())
+            .unwrap();
+        encoder.end_instance().unwrap();
+        let output = encoder.finish().unwrap();
+
+        let options = DecodeOptions::new().property_behavior(DecodePropertyBehavior::NoReflection);
+        let dom = crate::from_reader(output.as_slice(), options).unwrap();
+        let top_level = dom.root().children();
+        assert_eq!(top_level.len(), 2);
+
+        let model = dom.get_by_ref(top_level[0]).unwrap();
+        assert_eq!(model.name, "Car");
+
+        let part = dom.get_by_ref(model.children()[0]).unwrap();
+        assert_eq!(part.name, "Body");
+        assert_eq!(part.properties.get("Anchored"), Some(&Variant::Bool(true)));
+        assert_eq!(
+            model.properties.get("PrimaryPart"),
+            Some(&Variant::Ref(part.referent()))
+        );
+
+        let wheel = dom.get_by_ref(top_level[1]).unwrap();
+        assert_eq!(
+            wheel.properties.get("Data"),
+            Some(&SharedString::new(vec![1, 2, 3]).into())
+        );
+    }
+
+    #[test]
+    fn instance_metadata() {
+        let folder_ref = Ref::new();
+        let mut metadata = DocumentMetadata::new();
+        metadata.push_comment(" end ");
+
+        let folder = metadata.instance_mut(folder_ref);
+        folder.push_leading_comment(" before ");
+        folder.push_trailing_comment(" after ");
+        folder.insert_raw_property("Future", &br#"<NewType name="Future">1</NewType>"#[..]);
+
+        let options = EncodeOptions::new().metadata(metadata);
+        let mut encoder = StreamEncoder::new(Vec::new(), options).unwrap();
+        encoder.start_instance(folder_ref, "Folder", "A").unwrap();
+        encoder.start_instance(Ref::new(), "Folder", "B").unwrap();
+        encoder.end_instance().unwrap();
+        encoder.end_instance().unwrap();
+        let output = String::from_utf8(encoder.finish().unwrap()).unwrap();
+
+        let options = DecodeOptions::new()
+            .preserve_unknown_types(true)
+            .preserve_comments(true);
+        let (dom, decoded) = crate::from_reader_with_metadata(output.as_bytes(), options).unwrap();
+        let folder = decoded.instance(dom.root().children()[0]).unwrap();
+
+        assert_eq!(folder.leading_comments(), [" before "]);
+        assert_eq!(folder.trailing_comments(), [" after "]);
+        assert_eq!(
+            folder.raw_property("Future"),
+            Some(&br#"<NewType name="Future">1</NewType>"#[..])
+        );
+        assert_eq!(decoded.comments(), [" end "]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn property_after_child() {
+        let mut encoder = StreamEncoder::new(Vec::new(), EncodeOptions::new()).unwrap();
+        encoder.start_instance(Ref::new(), "Folder", "A").unwrap();
+        encoder.start_instance(Ref::new(), "Folder", "B").unwrap();
+        encoder.end_instance().unwrap();
+
+        let _ = encoder.write_property("Archivable", &Variant::Bool(true));
+    }
+}
@@ -25,7 +25,7 @@ impl XmlType for ColorSequence {
 
             // Envelope is always 0 for ColorSequenceKeypoint. This value isn't
             // exposed to developers but serializes in the XML format.
-            writer.write_characters(0)?;
+            writer.write_integer(0)?;
             writer.write(XmlWriteEvent::characters(" "))?;
         }
 
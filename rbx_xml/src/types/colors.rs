@@ -6,7 +6,7 @@ use crate::{
     core::XmlType,
     deserializer_core::XmlEventReader,
     error::{DecodeError, EncodeError},
-    serializer_core::{XmlEventWriter, XmlWriteEvent},
+    serializer_core::XmlEventWriter,
 };
 
 impl XmlType for Color3 {
@@ -54,9 +54,7 @@ impl XmlType for Color3uint8 {
 
     fn write_xml<W: Write>(&self, writer: &mut XmlEventWriter<W>) -> Result<(), EncodeError> {
         let encoded = encode_packed_color3(*self);
-        writer.write(XmlWriteEvent::characters(&encoded.to_string()))?;
-
-        Ok(())
+        writer.write_integer(encoded)
     }
 
     fn read_xml<R: Read>(reader: &mut XmlEventReader<R>) -> Result<Self, DecodeError> {
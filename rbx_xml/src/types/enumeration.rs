@@ -13,7 +13,7 @@ impl XmlType for Enum {
     const XML_TAG_NAME: &'static str = "token";
 
     fn write_xml<W: Write>(&self, writer: &mut XmlEventWriter<W>) -> Result<(), EncodeError> {
-        writer.write_characters(self.to_u32())
+        writer.write_integer(self.to_u32())
     }
 
     fn read_xml<R: Read>(reader: &mut XmlEventReader<R>) -> Result<Self, DecodeError> {
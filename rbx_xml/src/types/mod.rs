@@ -1,5 +1,5 @@
 //! This file packs up all of the type implementations in rbx_xml and exposes
-//! them through two methods, `read_value_xml` and `write_value_xml`.
+//! them through `read_value_xml`, `read_plain_value_xml`, and `write_value_xml`.
 //!
 //! To support a new type in rbx_xml:
 //!
@@ -18,6 +18,7 @@ mod content;
 mod enumeration;
 mod faces;
 mod font;
+mod material_colors;
 mod number_range;
 mod number_sequence;
 mod numbers;
@@ -26,7 +27,9 @@ mod physical_properties;
 mod ray;
 mod rect;
 mod referent;
+mod security_capabilities;
 mod shared_string;
+mod smooth_grid;
 mod strings;
 mod tags;
 mod udims;
@@ -37,8 +40,8 @@ use std::io::{Read, Write};
 
 use rbx_dom_weak::types::{
     Axes, BinaryString, CFrame, Color3, Color3uint8, ColorSequence, Content, Enum, Faces, Font,
-    NumberRange, NumberSequence, PhysicalProperties, Ray, Rect, Ref, UDim, UDim2, UniqueId,
-    Variant, Vector2, Vector2int16, Vector3, Vector3int16,
+    NumberRange, NumberSequence, PhysicalProperties, Ray, Rect, Ref, SecurityCapabilities, UDim,
+    UDim2, UniqueId, Variant, Vector2, Vector2int16, Vector3, Vector3int16,
 };
 
 use crate::{
@@ -50,10 +53,15 @@ use crate::{
     serializer_core::XmlEventWriter,
 };
 
+pub use self::referent::{parse_unique_id_referent, unique_id_referent};
+pub use self::strings::{write_name, write_protected_string, PROTECTED_STRING_PROPERTIES};
+
 use self::{
     attributes::write_attributes,
+    material_colors::write_material_colors,
     referent::{read_ref, write_ref},
     shared_string::{read_shared_string, write_shared_string},
+    smooth_grid::write_smooth_grid,
     tags::write_tags,
 };
 
@@ -71,28 +79,67 @@ macro_rules! declare_rbx_types {
             xml_type_name: &str,
             instance_id: Ref,
             property_name: &str,
+        ) -> Result<Option<Variant>, DecodeError> {
+            match xml_type_name {
+                self::referent::XML_TAG_NAME => Ok(read_ref(reader, instance_id, property_name, state)?.map(Variant::Ref)),
+                self::shared_string::XML_TAG_NAME => read_shared_string(reader, instance_id, property_name, state).map(Some),
+
+                _ => {
+                    if let Some(value) = read_plain_value_xml(reader, xml_type_name)? {
+                        return Ok(Some(value));
+                    }
+
+                    if state.preserves_unknown_types() {
+                        let xml = reader.read_raw_element()?;
+                        state.add_raw_property(instance_id, property_name, xml);
+                    } else {
+                        state.unknown_type_visited(instance_id, property_name, xml_type_name);
+                        reader.eat_unknown_tag()?;
+                    }
+
+                    Ok(None)
+                },
+            }
+        }
+
+        /// Reads a Roblox property value with the given type from the XML event
+        /// stream if it's a type that can be read without any extra state.
+        ///
+        /// Returns `Ok(None)` without consuming anything for Refs,
+        /// SharedStrings, and unknown types.
+        pub fn read_plain_value_xml<R: Read>(
+            reader: &mut XmlEventReader<R>,
+            xml_type_name: &str,
         ) -> Result<Option<Variant>, DecodeError> {
             match xml_type_name {
                 $(<$inner_type>::XML_TAG_NAME => Ok(Some(Variant::$variant_name(<$inner_type>::read_outer_xml(reader)?))),)*
 
                 // Protected strings are only read, never written
-                self::strings::ProtectedStringDummy::XML_TAG_NAME => {
-                    let value = self::strings::ProtectedStringDummy::read_outer_xml(reader)?;
+                self::strings::ProtectedString::XML_TAG_NAME => {
+                    let value = self::strings::ProtectedString::read_outer_xml(reader)?;
                     Ok(Some(Variant::String(value.0)))
                 },
 
-                self::referent::XML_TAG_NAME => Ok(Some(Variant::Ref(read_ref(reader, instance_id, property_name, state)?))),
-                self::shared_string::XML_TAG_NAME => read_shared_string(reader, instance_id, property_name, state).map(Some),
-
-                _ => {
-                    state.unknown_type_visited(instance_id, property_name, xml_type_name);
-                    reader.eat_unknown_tag()?;
+                legacy_name => match current_type_name(legacy_name) {
+                    $(Some(<$inner_type>::XML_TAG_NAME) => {
+                        let value = <$inner_type>::read_outer_xml_with_name(reader, legacy_name)?;
+                        Ok(Some(Variant::$variant_name(value)))
+                    },)*
 
-                    Ok(None)
+                    _ => Ok(None),
                 },
             }
         }
 
+        /// Returns whether values of the given type can be read by
+        /// `read_plain_value_xml`.
+        pub fn is_plain_type(xml_type_name: &str) -> bool {
+            matches!(
+                xml_type_name,
+                $(<$inner_type>::XML_TAG_NAME)|* | self::strings::ProtectedString::XML_TAG_NAME
+            ) || current_type_name(xml_type_name).is_some()
+        }
+
         /// Writes a Roblox property value with the given XML name to the XML
         /// stream.
         pub fn write_value_xml<W: Write>(
@@ -112,6 +159,8 @@ macro_rules! declare_rbx_types {
                 Variant::Ref(value) => write_ref(writer, xml_property_name, *value, state),
                 Variant::SharedString(value) => write_shared_string(writer, xml_property_name, value, state),
                 Variant::Tags(value) => write_tags(writer, xml_property_name, value),
+                Variant::MaterialColors(value) => write_material_colors(writer, xml_property_name, value),
+                Variant::SmoothGrid(value) => write_smooth_grid(writer, xml_property_name, value),
                 Variant::Attributes(value) => write_attributes(writer, xml_property_name, value),
 
                 unknown => {
@@ -122,6 +171,26 @@ macro_rules! declare_rbx_types {
     }
 }
 
+/// Type names that older versions of Roblox and other tools have written for
+/// types that now have a different name, paired with their current names.
+/// Values written under any of these names are read as if they had used the
+/// current name, but are always written using the current name.
+pub(crate) const LEGACY_TYPE_NAMES: &[(&str, &str)] = &[
+    ("CFrame", "CoordinateFrame"),
+    ("Enum", "token"),
+    ("OptionalCFrame", "OptionalCoordinateFrame"),
+    ("Rect", "Rect2D"),
+];
+
+/// Returns the current name of a type with a legacy name, or `None` if the
+/// given name isn't a legacy type name.
+fn current_type_name(xml_type_name: &str) -> Option<&'static str> {
+    LEGACY_TYPE_NAMES
+        .iter()
+        .find(|(legacy_name, _)| *legacy_name == xml_type_name)
+        .map(|(_, current_name)| *current_name)
+}
+
 declare_rbx_types! {
     Axes: Axes,
     BinaryString: BinaryString,
@@ -144,6 +213,7 @@ declare_rbx_types! {
     PhysicalProperties: PhysicalProperties,
     Ray: Ray,
     Rect: Rect,
+    SecurityCapabilities: SecurityCapabilities,
     String: String,
     UDim2: UDim2,
     UDim: UDim,
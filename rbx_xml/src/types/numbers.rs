@@ -50,7 +50,7 @@ macro_rules! int_type {
                 &self,
                 writer: &mut XmlEventWriter<W>,
             ) -> Result<(), EncodeError> {
-                writer.write_characters(*self)
+                writer.write_integer(*self)
             }
 
             fn read_xml<R: Read>(reader: &mut XmlEventReader<R>) -> Result<Self, DecodeError> {
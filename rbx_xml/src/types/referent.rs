@@ -11,18 +11,39 @@
 
 use std::io::{Read, Write};
 
-use rbx_dom_weak::types::Ref;
+use rbx_dom_weak::types::{Ref, UniqueId};
 
 use crate::{
-    deserializer::ParseState,
+    deserializer::{DecodeRefBehavior, ParseState},
     deserializer_core::XmlEventReader,
-    error::{DecodeError, EncodeError},
+    error::{DecodeError, DecodeErrorKind, EncodeError},
     serializer::EmitState,
     serializer_core::{XmlEventWriter, XmlWriteEvent},
 };
 
 pub const XML_TAG_NAME: &str = "Ref";
 
+/// The prefix Roblox Studio puts in front of the referents it generates, which
+/// we also use for referents made from UniqueIds.
+const UNIQUE_ID_REFERENT_PREFIX: &str = "RBX";
+
+/// Returns the referent written for an instance with the given UniqueId when
+/// `EncodeOptions::unique_id_referents` is enabled.
+pub fn unique_id_referent(unique_id: UniqueId) -> String {
+    format!(
+        "{}{}",
+        UNIQUE_ID_REFERENT_PREFIX,
+        unique_id.to_string().to_uppercase()
+    )
+}
+
+/// Reads a UniqueId back out of a referent written by `unique_id_referent`.
+pub fn parse_unique_id_referent(referent: &str) -> Option<UniqueId> {
+    referent
+        .strip_prefix(UNIQUE_ID_REFERENT_PREFIX)
+        .and_then(|unique_id| unique_id.parse().ok())
+}
+
 pub fn write_ref<W: Write>(
     writer: &mut XmlEventWriter<W>,
     xml_property_name: &str,
@@ -33,6 +54,8 @@ pub fn write_ref<W: Write>(
 
     if value.is_none() {
         writer.write(XmlWriteEvent::characters("null"))?;
+    } else if let Some(external) = state.external_ref(value) {
+        writer.write_string(&external)?;
     } else {
         writer.write_characters(state.map_id(value))?;
     }
@@ -47,17 +70,33 @@ pub fn read_ref<R: Read>(
     id: Ref,
     property_name: &str,
     state: &mut ParseState,
-) -> Result<Ref, DecodeError> {
+) -> Result<Option<Ref>, DecodeError> {
     let ref_contents = reader.read_tag_contents(XML_TAG_NAME)?;
 
-    if ref_contents != "null" {
-        // We need to rewrite this property as part of a follow-up pass.
-        //
-        // We might not know which ID this referent points to yet, so instead of
-        // trying to handle the case where we do here, we just let all referents
-        // get written later.
-        state.add_referent_rewrite(id, property_name.to_owned(), ref_contents);
+    if ref_contents == "null" {
+        return match state.null_ref_behavior() {
+            DecodeRefBehavior::KeepNull => Ok(Some(Ref::none())),
+            DecodeRefBehavior::DropProperty => Ok(None),
+            DecodeRefBehavior::ErrorOnRef => Err(reader.error(DecodeErrorKind::NullReferent)),
+        };
     }
 
-    Ok(Ref::none())
+    // We need to rewrite this property as part of a follow-up pass.
+    //
+    // We might not know which ID this referent points to yet, so instead of
+    // trying to handle the case where we do here, we just let all referents
+    // get written later.
+    //
+    // If it might need to be reported, we also note where this referent was
+    // read in case it doesn't end up pointing to anything.
+    let unresolved_error = if state.reports_dangling_refs() {
+        let error = reader.error(DecodeErrorKind::UnresolvedReferent(ref_contents.clone()));
+        Some(state.with_context(error))
+    } else {
+        None
+    };
+
+    state.add_referent_rewrite(id, property_name.to_owned(), ref_contents, unresolved_error);
+
+    Ok(Some(Ref::none()))
 }
@@ -0,0 +1,56 @@
+use std::io::{Read, Write};
+
+use rbx_dom_weak::types::SecurityCapabilities;
+
+use crate::{
+    core::XmlType,
+    deserializer_core::XmlEventReader,
+    error::{DecodeError, EncodeError},
+    serializer_core::XmlEventWriter,
+};
+
+impl XmlType for SecurityCapabilities {
+    const XML_TAG_NAME: &'static str = "SecurityCapabilities";
+
+    fn write_xml<W: Write>(&self, writer: &mut XmlEventWriter<W>) -> Result<(), EncodeError> {
+        writer.write_integer(self.bits())
+    }
+
+    fn read_xml<R: Read>(reader: &mut XmlEventReader<R>) -> Result<Self, DecodeError> {
+        let value: u64 = reader
+            .read_characters()?
+            .parse()
+            .map_err(|e| reader.error(e))?;
+
+        Ok(SecurityCapabilities::from_bits(value))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::test_util;
+
+    #[test]
+    fn round_trip_security_capabilities() {
+        test_util::test_xml_round_trip(&SecurityCapabilities::from_bits(0));
+        test_util::test_xml_round_trip(&SecurityCapabilities::from_bits(u64::MAX));
+    }
+
+    #[test]
+    fn deserialize_security_capabilities() {
+        test_util::test_xml_deserialize(
+            r#"<SecurityCapabilities name="Capabilities">12884901888</SecurityCapabilities>"#,
+            &SecurityCapabilities::from_bits(12884901888),
+        );
+    }
+
+    #[test]
+    fn serialize_security_capabilities() {
+        test_util::test_xml_serialize(
+            r#"<SecurityCapabilities name="foo">3</SecurityCapabilities>"#,
+            &SecurityCapabilities::from_bits(3),
+        );
+    }
+}
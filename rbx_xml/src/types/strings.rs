@@ -4,7 +4,7 @@ use crate::{
     core::XmlType,
     deserializer_core::XmlEventReader,
     error::{DecodeError, EncodeError},
-    serializer_core::XmlEventWriter,
+    serializer_core::{XmlEventWriter, XmlWriteEvent},
 };
 
 impl XmlType for String {
@@ -19,24 +19,55 @@ impl XmlType for String {
     }
 }
 
+/// The properties that Roblox serializes as ProtectedStrings, as pairs of the
+/// class that defines each property and its name.
+pub const PROTECTED_STRING_PROPERTIES: &[(&str, &str)] =
+    &[("Script", "Source"), ("ModuleScript", "Source")];
+
+/// A string that Roblox Studio always writes inside of a CDATA section, like
+/// the source of a script. rbx_xml reads these as regular strings.
 #[derive(Debug, PartialEq, Eq)]
-pub struct ProtectedStringDummy(pub String);
+pub struct ProtectedString(pub String);
 
-impl XmlType for ProtectedStringDummy {
+impl XmlType for ProtectedString {
     const XML_TAG_NAME: &'static str = "ProtectedString";
 
-    fn write_xml<W: Write>(&self, _writer: &mut XmlEventWriter<W>) -> Result<(), EncodeError> {
-        panic!("ProtectedString values are only read, never written.");
+    fn write_xml<W: Write>(&self, writer: &mut XmlEventWriter<W>) -> Result<(), EncodeError> {
+        // Any `]]>` in the string is split across two CDATA sections by the
+        // writer, which is the same thing Studio does.
+        writer.write_cdata(&self.0)
     }
 
     fn read_xml<R: Read>(reader: &mut XmlEventReader<R>) -> Result<Self, DecodeError> {
-        Ok(ProtectedStringDummy(reader.read_characters()?))
+        Ok(ProtectedString(reader.read_characters()?))
     }
 }
 
+/// Writes an instance's name as its `Name` property. This avoids copying the
+/// name into a `Variant` first, since every instance has one.
+pub fn write_name<W: Write>(writer: &mut XmlEventWriter<W>, name: &str) -> Result<(), EncodeError> {
+    writer.write(XmlWriteEvent::start_element(String::XML_TAG_NAME).attr("name", "Name"))?;
+    writer.write_string(name)?;
+    writer.write(XmlWriteEvent::end_element())
+}
+
+/// Writes a string as a ProtectedString. This avoids copying the string into a
+/// `ProtectedString` first, since scripts can be large.
+pub fn write_protected_string<W: Write>(
+    writer: &mut XmlEventWriter<W>,
+    property_name: &str,
+    value: &str,
+) -> Result<(), EncodeError> {
+    writer.write(
+        XmlWriteEvent::start_element(ProtectedString::XML_TAG_NAME).attr("name", property_name),
+    )?;
+    writer.write_cdata(value)?;
+    writer.write(XmlWriteEvent::end_element())
+}
+
 #[cfg(test)]
 mod test {
-    use super::ProtectedStringDummy;
+    use super::ProtectedString;
 
     use crate::test_util;
 
@@ -83,6 +114,31 @@ mod test {
             test_value
         );
 
-        test_util::test_xml_deserialize(&test_source, &ProtectedStringDummy(test_value.to_owned()));
+        test_util::test_xml_deserialize(&test_source, &ProtectedString(test_value.to_owned()));
+    }
+
+    #[test]
+    fn serialize_protected_string() {
+        test_util::test_xml_serialize(
+            r#"<ProtectedString name="foo"><![CDATA[print("Hello!")]]></ProtectedString>"#,
+            &ProtectedString("print(\"Hello!\")".to_owned()),
+        );
+    }
+
+    #[test]
+    fn round_trip_protected_string_cdata_end() {
+        test_util::test_xml_round_trip(&ProtectedString("local a = b[c[1]]>2".to_owned()));
+    }
+
+    #[test]
+    fn round_trip_protected_string_control_characters() {
+        test_util::test_xml_round_trip(&ProtectedString(
+            "print(\"\u{1}\u{8}\u{1b}[0m\u{7f}\u{85}\")\r\n".to_owned(),
+        ));
+    }
+
+    #[test]
+    fn round_trip_string_control_characters() {
+        test_util::test_xml_round_trip(&"a\u{1}b\u{80}c".to_owned());
     }
 }
@@ -19,7 +19,7 @@ impl XmlType for UniqueId {
     }
 
     fn write_xml<W: Write>(&self, writer: &mut XmlEventWriter<W>) -> Result<(), EncodeError> {
-        writer.write_value(&self.to_string())
+        writer.write_characters(self)
     }
 }
 
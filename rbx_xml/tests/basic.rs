@@ -1,9 +1,9 @@
 //! Temporary tests while re-bootstrapping rbx_xml
 
 use rbx_dom_weak::types::{
-    Attributes, BinaryString, BrickColor, Color3, ColorSequence, ColorSequenceKeypoint,
-    NumberRange, NumberSequence, NumberSequenceKeypoint, Rect, Tags, UDim, UDim2, UniqueId,
-    Variant, Vector2, Vector3,
+    Attributes, BinaryString, BrickColor, CFrame, Color3, ColorSequence, ColorSequenceKeypoint,
+    Enum, Font, Matrix3, NumberRange, NumberSequence, NumberSequenceKeypoint, Rect, Ref, Tags,
+    UDim, UDim2, UniqueId, Variant, Vector2, Vector3,
 };
 use rbx_dom_weak::{InstanceBuilder, WeakDom};
 
@@ -189,6 +189,41 @@ fn read_attributes() {
     }
 }
 
+#[test]
+fn unsupported_attributes() {
+    let _ = env_logger::try_init();
+
+    // One attribute named "A" with a value type that rbx_types doesn't know.
+    let document = r#"
+        <roblox version="4">
+            <Item class="Folder" referent="RBX0">
+                <Properties>
+                    <BinaryString name="AttributesSerialize">AQAAAAEAAABBAf8=</BinaryString>
+                </Properties>
+            </Item>
+        </roblox>
+    "#;
+
+    let decoded =
+        rbx_xml::from_reader_document(document.as_bytes(), rbx_xml::DecodeOptions::new()).unwrap();
+    assert_eq!(decoded.warnings.len(), 1);
+
+    let folder = decoded.tree.get_by_ref(decoded.roots[0]).unwrap();
+    assert_eq!(
+        folder.properties.get("Attributes"),
+        Some(&Variant::BinaryString(
+            vec![1, 0, 0, 0, 1, 0, 0, 0, 0x41, 0x01, 0xff].into()
+        ))
+    );
+
+    let mut encoded = Vec::new();
+    rbx_xml::to_writer_default(&mut encoded, &decoded.tree, &decoded.roots).unwrap();
+    let encoded = String::from_utf8(encoded).unwrap();
+    assert!(encoded.contains(
+        r#"<BinaryString name="AttributesSerialize"><![CDATA[AQAAAAEAAABBAf8=]]></BinaryString>"#
+    ));
+}
+
 #[test]
 fn read_unique_id() {
     let _ = env_logger::try_init();
@@ -230,3 +265,1587 @@ fn read_unique_id() {
         )))
     );
 }
+
+#[test]
+fn child_order() {
+    let _ = env_logger::try_init();
+
+    let mut tree = WeakDom::new(InstanceBuilder::new("Folder").with_children(vec![
+        InstanceBuilder::new("Folder").with_name("C"),
+        InstanceBuilder::new("Folder").with_name("A"),
+        InstanceBuilder::new("Folder").with_name("B"),
+    ]));
+    let root_ref = tree.root_ref();
+    tree.sort_children_by(root_ref, |a, b| b.name.cmp(&a.name));
+
+    let mut encoded = Vec::new();
+    rbx_xml::to_writer_default(&mut encoded, &tree, &[root_ref]).expect("failed to encode model");
+
+    let decoded = rbx_xml::from_reader_default(encoded.as_slice()).expect("failed to decode model");
+    let folder = decoded.get_by_ref(decoded.root().children()[0]).unwrap();
+    let names: Vec<&str> = folder
+        .children()
+        .iter()
+        .map(|child| decoded.get_by_ref(*child).unwrap().name.as_str())
+        .collect();
+
+    assert_eq!(names, ["C", "B", "A"]);
+}
+
+#[test]
+fn preserve_unknown_types() {
+    let _ = env_logger::try_init();
+
+    let document = r#"
+        <roblox version="4">
+            <Item class="Folder" referent="hello">
+                <Properties>
+                    <string name="Name">Hello</string>
+                    <FancyNewType name="Fancy">
+                        <Part kind="a">1</Part>
+                        <Part kind="b"><![CDATA[ two ]]></Part>
+                    </FancyNewType>
+                </Properties>
+            </Item>
+        </roblox>
+    "#;
+
+    let skipped = rbx_xml::from_str_default(document).unwrap();
+    let folder = skipped.get_by_ref(skipped.root().children()[0]).unwrap();
+    assert!(folder.properties.is_empty());
+
+    let options = || rbx_xml::DecodeOptions::new().preserve_unknown_types(true);
+    let (tree, metadata) =
+        rbx_xml::from_reader_with_metadata(document.as_bytes(), options()).unwrap();
+    let folder_ref = tree.root().children()[0];
+    let folder = tree.get_by_ref(folder_ref).unwrap();
+
+    // The raw XML is kept alongside the tree instead of in its properties.
+    assert_eq!(folder.properties.len(), 0);
+    let raw = metadata
+        .instance(folder_ref)
+        .and_then(|folder| folder.raw_property("Fancy"))
+        .expect("raw property should be kept in the metadata");
+
+    let mut encoded = Vec::new();
+    let encode_options = rbx_xml::EncodeOptions::new().metadata(metadata.clone());
+    rbx_xml::to_writer(&mut encoded, &tree, &[folder_ref], encode_options).unwrap();
+
+    let encoded = String::from_utf8(encoded).unwrap();
+    assert!(encoded.contains(r#"<FancyNewType name="Fancy">"#));
+    assert!(encoded.contains(r#"<Part kind="b"><![CDATA[ two ]]></Part>"#));
+
+    let (decoded, decoded_metadata) =
+        rbx_xml::from_reader_with_metadata(encoded.as_bytes(), options()).unwrap();
+    let decoded_folder_ref = decoded.root().children()[0];
+    assert_eq!(
+        decoded_metadata
+            .instance(decoded_folder_ref)
+            .and_then(|folder| folder.raw_property("Fancy")),
+        Some(raw)
+    );
+
+    // Without the metadata, the property is left out.
+    let mut encoded = Vec::new();
+    rbx_xml::to_writer_default(&mut encoded, &tree, &[folder_ref]).unwrap();
+    assert!(!String::from_utf8(encoded).unwrap().contains("FancyNewType"));
+}
+
+#[test]
+fn unknown_class_behavior() {
+    let _ = env_logger::try_init();
+
+    let document = r#"
+        <roblox version="4">
+            <Item class="Folder" referent="RBX0">
+                <Item class="NotARealClass" referent="RBX1">
+                    <Item class="Folder" referent="RBX2"></Item>
+                </Item>
+                <Item class="ObjectValue" referent="RBX3">
+                    <Properties>
+                        <Ref name="Value">RBX1</Ref>
+                    </Properties>
+                </Item>
+            </Item>
+        </roblox>
+    "#;
+
+    let read = rbx_xml::from_str_default(document).unwrap();
+    let folder = read.get_by_ref(read.root().children()[0]).unwrap();
+    assert_eq!(folder.children().len(), 2);
+
+    let options =
+        rbx_xml::DecodeOptions::new().class_behavior(rbx_xml::DecodeClassBehavior::IgnoreUnknown);
+    let ignored = rbx_xml::from_str(document, options).unwrap();
+    let folder = ignored.get_by_ref(ignored.root().children()[0]).unwrap();
+    assert_eq!(folder.children().len(), 1);
+
+    let value = ignored.get_by_ref(folder.children()[0]).unwrap();
+    assert_eq!(value.class, "ObjectValue");
+    assert_eq!(
+        value.properties.get("Value"),
+        Some(&Variant::Ref(Ref::none()))
+    );
+
+    let options =
+        rbx_xml::DecodeOptions::new().class_behavior(rbx_xml::DecodeClassBehavior::ErrorOnUnknown);
+    assert!(rbx_xml::from_str(document, options).is_err());
+}
+
+#[test]
+fn class_filter() {
+    let _ = env_logger::try_init();
+
+    let document = r#"
+        <roblox version="4">
+            <Item class="Folder" referent="RBX0">
+                <Properties>
+                    <string name="Name">Scripts</string>
+                </Properties>
+                <Item class="Script" referent="RBX1">
+                    <Item class="ModuleScript" referent="RBX2"></Item>
+                </Item>
+                <Item class="ObjectValue" referent="RBX3">
+                    <Properties>
+                        <Ref name="Value">RBX1</Ref>
+                    </Properties>
+                </Item>
+            </Item>
+            <Item class="Script" referent="RBX4"></Item>
+        </roblox>
+    "#;
+
+    let is_script = |class: &str| class.ends_with("Script");
+    let class_names = |tree: &WeakDom| {
+        let mut names = Vec::new();
+        let mut stack: Vec<Ref> = tree.root().children().iter().rev().copied().collect();
+
+        while let Some(id) = stack.pop() {
+            let instance = tree.get_by_ref(id).unwrap();
+            names.push(instance.class.clone());
+            stack.extend(instance.children().iter().rev());
+        }
+
+        names
+    };
+
+    let options = rbx_xml::DecodeOptions::new().class_filter(&is_script);
+    let skipped = rbx_xml::from_str(document, options).unwrap();
+    assert_eq!(class_names(&skipped), ["Script"]);
+
+    let options = rbx_xml::DecodeOptions::new()
+        .class_filter(&is_script)
+        .filter_behavior(rbx_xml::DecodeFilterBehavior::KeepDescendants);
+    let kept = rbx_xml::from_str(document, options).unwrap();
+    assert_eq!(class_names(&kept), ["Script", "ModuleScript", "Script"]);
+
+    // Every kept script from inside the Folder ends up in the root.
+    assert_eq!(kept.root().children().len(), 2);
+}
+
+#[test]
+fn multiple_roots() {
+    let _ = env_logger::try_init();
+
+    let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+    let root_ref = tree.root_ref();
+    let first = tree.insert(root_ref, InstanceBuilder::new("Folder").with_name("First"));
+    let child = tree.insert(first, InstanceBuilder::new("Folder").with_name("Child"));
+    let second = tree.insert(root_ref, InstanceBuilder::new("Folder").with_name("Second"));
+
+    let mut encoded = Vec::new();
+    rbx_xml::to_writer_default(&mut encoded, &tree, &[second, first]).unwrap();
+
+    let document =
+        rbx_xml::from_reader_document(encoded.as_slice(), rbx_xml::DecodeOptions::new()).unwrap();
+    assert_eq!(document.single_root(), None);
+
+    let names: Vec<&str> = document
+        .roots
+        .iter()
+        .map(|id| document.tree.get_by_ref(*id).unwrap().name.as_str())
+        .collect();
+    assert_eq!(names, ["Second", "First"]);
+
+    let mut encoded = Vec::new();
+    rbx_xml::to_writer_default(&mut encoded, &tree, &[child]).unwrap();
+    let document =
+        rbx_xml::from_reader_document(encoded.as_slice(), rbx_xml::DecodeOptions::new()).unwrap();
+    let single_root = document.single_root().unwrap();
+    assert_eq!(document.tree.get_by_ref(single_root).unwrap().name, "Child");
+
+    // Instances can't be written more than once.
+    for ids in [[first, first], [first, child], [child, first]] {
+        assert!(rbx_xml::to_writer_default(Vec::new(), &tree, &ids).is_err());
+    }
+
+    assert!(rbx_xml::to_writer_default(Vec::new(), &tree, &[Ref::new()]).is_err());
+}
+
+#[test]
+fn mismatch_behavior() {
+    let _ = env_logger::try_init();
+
+    let document = r#"
+        <roblox version="4">
+            <Item class="Folder" referent="RBX0">
+                <Properties>
+                    <BinaryString name="Tags">/w==</BinaryString>
+                </Properties>
+            </Item>
+        </roblox>
+    "#;
+
+    assert!(rbx_xml::from_str_default(document).is_err());
+
+    let options = rbx_xml::DecodeOptions::new()
+        .mismatch_behavior(rbx_xml::DecodeMismatchBehavior::IgnoreMismatch);
+    let ignored = rbx_xml::from_str(document, options).unwrap();
+    let folder = ignored.get_by_ref(ignored.root().children()[0]).unwrap();
+    assert_eq!(folder.properties.get("Tags"), None);
+
+    let options = rbx_xml::DecodeOptions::new()
+        .mismatch_behavior(rbx_xml::DecodeMismatchBehavior::ReadOriginal);
+    let read = rbx_xml::from_str(document, options).unwrap();
+    let folder = read.get_by_ref(read.root().children()[0]).unwrap();
+    assert_eq!(
+        folder.properties.get("Tags"),
+        Some(&Variant::BinaryString(vec![0xff].into()))
+    );
+}
+
+#[test]
+fn strip_defaults() {
+    let _ = env_logger::try_init();
+
+    let tree = WeakDom::new(InstanceBuilder::new("Folder").with_children(vec![
+            InstanceBuilder::new("Part")
+                .with_name("Default")
+                .with_property("Anchored", false)
+                .with_property("Transparency", 0.0f32),
+            InstanceBuilder::new("Part")
+                .with_name("Changed")
+                .with_property("Anchored", true)
+                .with_property("Transparency", 0.5f32),
+        ]));
+
+    let options = rbx_xml::EncodeOptions::new().strip_defaults(true);
+    let mut encoded = Vec::new();
+    rbx_xml::to_writer(&mut encoded, &tree, &[tree.root_ref()], options).unwrap();
+
+    let decoded = rbx_xml::from_reader_default(encoded.as_slice()).unwrap();
+    let folder = decoded.get_by_ref(decoded.root().children()[0]).unwrap();
+
+    let default = decoded.get_by_ref(folder.children()[0]).unwrap();
+    assert_eq!(default.name, "Default");
+    assert!(default.properties.is_empty());
+
+    let changed = decoded.get_by_ref(folder.children()[1]).unwrap();
+    assert_eq!(
+        changed.properties.get("Anchored"),
+        Some(&Variant::Bool(true))
+    );
+    assert_eq!(
+        changed.properties.get("Transparency"),
+        Some(&Variant::Float32(0.5))
+    );
+}
+
+#[test]
+fn property_allowlist() {
+    let _ = env_logger::try_init();
+
+    let tree = WeakDom::new(
+        InstanceBuilder::new("Part")
+            .with_property("Anchored", true)
+            .with_property("Transparency", 0.5f32)
+            .with_child(InstanceBuilder::new("BoolValue").with_property("Value", true)),
+    );
+
+    let options = rbx_xml::EncodeOptions::new().allow_properties("BasePart", ["Anchored"]);
+    let mut encoded = Vec::new();
+    rbx_xml::to_writer(&mut encoded, &tree, &[tree.root_ref()], options).unwrap();
+
+    let decoded = rbx_xml::from_reader_default(encoded.as_slice()).unwrap();
+    let part = decoded.get_by_ref(decoded.root().children()[0]).unwrap();
+    assert_eq!(part.name, "Part");
+    assert_eq!(part.properties.len(), 1);
+    assert_eq!(part.properties.get("Anchored"), Some(&Variant::Bool(true)));
+
+    let value = decoded.get_by_ref(part.children()[0]).unwrap();
+    assert_eq!(value.properties.get("Value"), Some(&Variant::Bool(true)));
+}
+
+#[test]
+fn custom_reflection_database() {
+    let _ = env_logger::try_init();
+
+    let mut database = rbx_reflection_database::get().clone();
+    database
+        .classes
+        .get_mut("BoolValue")
+        .unwrap()
+        .properties
+        .remove("Value");
+
+    let tree = WeakDom::new(InstanceBuilder::new("BoolValue").with_property("Value", true));
+
+    let options = rbx_xml::EncodeOptions::new().reflection_database(&database);
+    let mut encoded = Vec::new();
+    rbx_xml::to_writer(&mut encoded, &tree, &[tree.root_ref()], options).unwrap();
+
+    let decoded = rbx_xml::from_reader_default(encoded.as_slice()).unwrap();
+    let value = decoded.get_by_ref(decoded.root().children()[0]).unwrap();
+    assert!(!value.properties.contains_key("Value"));
+
+    let mut encoded = Vec::new();
+    rbx_xml::to_writer_default(&mut encoded, &tree, &[tree.root_ref()]).unwrap();
+
+    let options = rbx_xml::DecodeOptions::new().reflection_database(&database);
+    let decoded = rbx_xml::from_reader(encoded.as_slice(), options).unwrap();
+    let value = decoded.get_by_ref(decoded.root().children()[0]).unwrap();
+    assert!(!value.properties.contains_key("Value"));
+
+    let decoded = rbx_xml::from_reader_default(encoded.as_slice()).unwrap();
+    let value = decoded.get_by_ref(decoded.root().children()[0]).unwrap();
+    assert_eq!(value.properties.get("Value"), Some(&Variant::Bool(true)));
+}
+
+#[test]
+fn decode_error_context() {
+    let _ = env_logger::try_init();
+
+    let document = r#"
+        <roblox version="4">
+            <Item class="Model" referent="1">
+                <Properties>
+                    <string name="Name">Car</string>
+                </Properties>
+                <Item class="Part" referent="2">
+                    <Properties>
+                        <string name="Name">Body</string>
+                        <Vector3 name="size">
+                            <X>1</X>
+                            <Y>oops</Y>
+                            <Z>1</Z>
+                        </Vector3>
+                    </Properties>
+                </Item>
+            </Item>
+        </roblox>
+    "#;
+
+    let err = rbx_xml::from_str_default(document).unwrap_err();
+    assert_eq!(err.line(), 12);
+    assert_eq!(err.instance_path(), ["Car", "Body"]);
+    assert_eq!(err.property_name(), Some("size"));
+    assert!(err.to_string().starts_with(&format!(
+        "line 12, column {}, in Car.Body, property size: ",
+        err.column()
+    )));
+}
+
+#[test]
+fn lenient_decode() {
+    let _ = env_logger::try_init();
+
+    let document = r#"
+        <roblox version="4">
+            <Item class="Model" referent="1">
+                <Properties>
+                    <string name="Name">Car</string>
+                    <Ref name="PrimaryPart">missing</Ref>
+                </Properties>
+                <Item class="Part" referent="2">
+                    <Properties>
+                        <string name="Name">Body</string>
+                        <BinaryString name="Tags">not base64!</BinaryString>
+                        <token name="Material">123456</token>
+                        <float name="Transparency">oops</float>
+                        <bool name="Anchored">true</bool>
+                    </Properties>
+                </Item>
+            </Item>
+        </roblox>
+    "#;
+
+    assert!(rbx_xml::from_str_default(document).is_err());
+
+    let options = rbx_xml::DecodeOptions::new().lenient(true);
+    let (tree, warnings) =
+        rbx_xml::from_reader_with_warnings(document.as_bytes(), options).unwrap();
+
+    let model = tree.get_by_ref(tree.root().children()[0]).unwrap();
+    assert_eq!(
+        model.properties.get("PrimaryPart"),
+        Some(&Variant::Ref(Ref::none()))
+    );
+
+    let part = tree.get_by_ref(model.children()[0]).unwrap();
+    assert_eq!(part.name, "Body");
+    assert_eq!(
+        part.properties.get("Tags"),
+        Some(&Variant::Tags(Tags::new()))
+    );
+    assert_eq!(
+        part.properties.get("Material"),
+        Some(&Variant::Enum(rbx_dom_weak::types::Enum::from_u32(256)))
+    );
+    assert_eq!(
+        part.properties.get("Transparency"),
+        Some(&Variant::Float32(0.0))
+    );
+    assert_eq!(part.properties.get("Anchored"), Some(&Variant::Bool(true)));
+
+    let mut properties: Vec<_> = warnings
+        .iter()
+        .map(|warning| {
+            (
+                warning.line(),
+                warning.instance_path().join("."),
+                warning.property_name(),
+            )
+        })
+        .collect();
+    properties.sort();
+
+    assert_eq!(
+        properties,
+        [
+            (6, "Car".to_owned(), Some("PrimaryPart")),
+            (11, "Car.Body".to_owned(), Some("Tags")),
+            (12, "Car.Body".to_owned(), Some("Material")),
+            (13, "Car.Body".to_owned(), Some("Transparency")),
+        ]
+    );
+}
+
+#[test]
+fn metadata_round_trip() {
+    let _ = env_logger::try_init();
+
+    let document = r#"
+        <roblox version="4">
+            <Meta name="ExplicitAutoJoints">true</Meta>
+            <External>null</External>
+            <External>nil</External>
+            <Item class="Folder" referent="1">
+                <Properties>
+                    <string name="Name">Stuff</string>
+                </Properties>
+            </Item>
+        </roblox>
+    "#;
+
+    let (tree, metadata) =
+        rbx_xml::from_reader_with_metadata(document.as_bytes(), rbx_xml::DecodeOptions::new())
+            .unwrap();
+    assert_eq!(metadata.get("ExplicitAutoJoints"), Some("true"));
+    assert_eq!(metadata.externals(), ["null", "nil"]);
+
+    let options = rbx_xml::EncodeOptions::new().metadata(metadata.clone());
+    let mut encoded = Vec::new();
+    rbx_xml::to_writer(&mut encoded, &tree, tree.root().children(), options).unwrap();
+
+    let (decoded, decoded_metadata) =
+        rbx_xml::from_reader_with_metadata(encoded.as_slice(), rbx_xml::DecodeOptions::new())
+            .unwrap();
+    assert_eq!(decoded_metadata, metadata);
+    assert_eq!(decoded.root().children().len(), 1);
+}
+
+#[test]
+fn script_source_protected_string() {
+    let _ = env_logger::try_init();
+
+    let source = "local s = \"]]>\"\n\tprint(s, \"\u{1}\u{1b}[0m\")\r\n";
+    let tree = WeakDom::new(InstanceBuilder::new("LocalScript").with_property("Source", source));
+
+    let mut encoded = Vec::new();
+    rbx_xml::to_writer_default(&mut encoded, &tree, &[tree.root_ref()]).unwrap();
+
+    let encoded_str = std::str::from_utf8(&encoded).unwrap();
+    assert!(encoded_str.contains("<ProtectedString name=\"Source\"><![CDATA[local s ="));
+
+    let decoded = rbx_xml::from_reader_default(encoded.as_slice()).unwrap();
+    let script = decoded.get_by_ref(decoded.root().children()[0]).unwrap();
+    assert_eq!(
+        script.properties.get("Source"),
+        Some(&Variant::String(source.to_owned()))
+    );
+}
+
+#[test]
+fn preserve_comments() {
+    let _ = env_logger::try_init();
+
+    let document = r#"
+        <roblox version="4">
+            <!-- The folder with everything in it -->
+            <Item class="Folder" referent="RBX0">
+                <Properties>
+                    <string name="Name">Stuff</string>
+                </Properties>
+                <!-- A value -->
+                <Item class="IntValue" referent="RBX1"></Item>
+                <!-- That's all -->
+            </Item>
+            <!-- End of the document -->
+        </roblox>
+    "#;
+
+    let (_, ignored) =
+        rbx_xml::from_reader_with_metadata(document.as_bytes(), rbx_xml::DecodeOptions::new())
+            .unwrap();
+    assert!(ignored.is_empty());
+
+    let options = || rbx_xml::DecodeOptions::new().preserve_comments(true);
+    let (tree, metadata) =
+        rbx_xml::from_reader_with_metadata(document.as_bytes(), options()).unwrap();
+    let folder_ref = tree.root().children()[0];
+    let value_ref = tree.get_by_ref(folder_ref).unwrap().children()[0];
+
+    let folder = metadata.instance(folder_ref).unwrap();
+    let value = metadata.instance(value_ref).unwrap();
+    assert_eq!(
+        folder.leading_comments(),
+        [" The folder with everything in it "]
+    );
+    assert_eq!(folder.trailing_comments(), [" That's all "]);
+    assert_eq!(value.leading_comments(), [" A value "]);
+    assert_eq!(value.trailing_comments(), [] as [String; 0]);
+    assert_eq!(metadata.comments(), [" End of the document "]);
+
+    // Comments aren't stored in the tree itself.
+    assert!(tree.get_by_ref(value_ref).unwrap().properties.is_empty());
+
+    let mut encoded = Vec::new();
+    let encode_options = rbx_xml::EncodeOptions::new().metadata(metadata.clone());
+    rbx_xml::to_writer(&mut encoded, &tree, tree.root().children(), encode_options).unwrap();
+
+    let (decoded, decoded_metadata) =
+        rbx_xml::from_reader_with_metadata(encoded.as_slice(), options()).unwrap();
+    let decoded_folder_ref = decoded.root().children()[0];
+    let decoded_value_ref = decoded.get_by_ref(decoded_folder_ref).unwrap().children()[0];
+
+    assert_eq!(decoded_metadata.instance(decoded_folder_ref), Some(folder));
+    assert_eq!(decoded_metadata.instance(decoded_value_ref), Some(value));
+    assert_eq!(decoded_metadata.comments(), metadata.comments());
+}
+
+#[test]
+fn invalid_char_behavior() {
+    use rbx_xml::EncodeInvalidCharBehavior;
+
+    let _ = env_logger::try_init();
+
+    let value = "\u{1}a\u{1b}[0m \u{85}";
+    let tree = WeakDom::new(
+        InstanceBuilder::new("Folder").with_child(
+            InstanceBuilder::new("StringValue")
+                .with_property("Value", value)
+                .with_child(InstanceBuilder::new("Script").with_property("Source", value)),
+        ),
+    );
+
+    let encode = |behavior, tree: &WeakDom| {
+        let mut encoded = Vec::new();
+        let options = rbx_xml::EncodeOptions::new().invalid_char_behavior(behavior);
+        rbx_xml::to_writer(&mut encoded, tree, tree.root().children(), options)
+            .map(|()| String::from_utf8(encoded).unwrap())
+    };
+    let decoded_values = |encoded: &str| {
+        let decoded = rbx_xml::from_str_default(encoded).unwrap();
+        let string_value = decoded.get_by_ref(decoded.root().children()[0]).unwrap();
+        let script = decoded.get_by_ref(string_value.children()[0]).unwrap();
+
+        [
+            string_value.properties.get("Value").cloned(),
+            script.properties.get("Source").cloned(),
+        ]
+    };
+
+    for behavior in [
+        EncodeInvalidCharBehavior::Cdata,
+        EncodeInvalidCharBehavior::Escape,
+    ] {
+        let encoded = encode(behavior, &tree).unwrap();
+        let expected = Some(Variant::String(value.to_owned()));
+        assert_eq!(decoded_values(&encoded), [expected.clone(), expected]);
+    }
+
+    let escaped = encode(EncodeInvalidCharBehavior::Escape, &tree).unwrap();
+    assert!(escaped.contains("&#1;a&#27;[0m \u{85}"));
+    assert!(!escaped.contains('\u{1}'));
+
+    let replaced = encode(EncodeInvalidCharBehavior::Replace, &tree).unwrap();
+    let expected = Some(Variant::String("\u{FFFD}a\u{FFFD}[0m \u{85}".to_owned()));
+    assert_eq!(decoded_values(&replaced), [expected.clone(), expected]);
+
+    assert!(encode(EncodeInvalidCharBehavior::Error, &tree).is_err());
+
+    // NUL can't be written at all, unless it's replaced.
+    let tree = WeakDom::new(
+        InstanceBuilder::new("Folder")
+            .with_child(InstanceBuilder::new("StringValue").with_property("Value", "a\0b")),
+    );
+    assert!(encode(EncodeInvalidCharBehavior::Cdata, &tree).is_err());
+    assert!(encode(EncodeInvalidCharBehavior::Escape, &tree).is_err());
+    assert!(encode(EncodeInvalidCharBehavior::Replace, &tree).is_ok());
+}
+
+#[test]
+fn output_formatting() {
+    let _ = env_logger::try_init();
+
+    let tree =
+        WeakDom::new(InstanceBuilder::new("Folder").with_child(InstanceBuilder::new("BoolValue")));
+
+    let encode = |options: rbx_xml::EncodeOptions| {
+        let mut encoded = Vec::new();
+        rbx_xml::to_writer(&mut encoded, &tree, &[tree.root_ref()], options).unwrap();
+        String::from_utf8(encoded).unwrap()
+    };
+
+    let default = encode(rbx_xml::EncodeOptions::new());
+    assert!(default.contains("\n  <Item class=\"Folder\" referent=\"0\">\n"));
+
+    let tabs = encode(
+        rbx_xml::EncodeOptions::new()
+            .indentation(rbx_xml::EncodeIndentation::Tabs(1))
+            .newline_style(rbx_xml::EncodeNewlineStyle::CrLf),
+    );
+    assert!(tabs.contains("\r\n\t<Item class=\"Folder\" referent=\"0\">\r\n\t\t<Properties>"));
+
+    let minimized =
+        encode(rbx_xml::EncodeOptions::new().indentation(rbx_xml::EncodeIndentation::None));
+    assert!(!minimized.contains('\n'));
+    assert!(minimized.starts_with("<roblox version=\"4\"><Item class=\"Folder\""));
+
+    for output in [&default, &tabs, &minimized] {
+        let decoded = rbx_xml::from_str_default(output).unwrap();
+        let folder = decoded.get_by_ref(decoded.root().children()[0]).unwrap();
+        assert_eq!(folder.class, "Folder");
+        assert_eq!(folder.children().len(), 1);
+    }
+}
+
+#[test]
+fn alphabetical_attribute_order() {
+    let _ = env_logger::try_init();
+
+    let document = r#"
+        <roblox version="4">
+            <Item class="Folder" referent="1">
+                <Properties>
+                    <NewType name="Thing" zeta="1" alpha="2">hi</NewType>
+                </Properties>
+            </Item>
+        </roblox>
+    "#;
+
+    let options = rbx_xml::DecodeOptions::new()
+        .property_behavior(rbx_xml::DecodePropertyBehavior::ReadUnknown)
+        .preserve_unknown_types(true);
+    let (tree, metadata) =
+        rbx_xml::from_reader_with_metadata(document.as_bytes(), options).unwrap();
+
+    let options = rbx_xml::EncodeOptions::new()
+        .property_behavior(rbx_xml::EncodePropertyBehavior::WriteUnknown)
+        .attribute_order(rbx_xml::EncodeAttributeOrder::Alphabetical)
+        .metadata(metadata);
+    let mut encoded = Vec::new();
+    rbx_xml::to_writer(&mut encoded, &tree, tree.root().children(), options).unwrap();
+
+    let encoded = String::from_utf8(encoded).unwrap();
+    assert!(encoded.contains(r#"<NewType alpha="2" name="Thing" zeta="1">hi</NewType>"#));
+}
+
+#[test]
+fn deterministic_output() {
+    let _ = env_logger::try_init();
+
+    let build = |reverse: bool| {
+        let mut properties = vec![
+            ("Anchored", Variant::Bool(true)),
+            ("Transparency", Variant::Float32(0.5)),
+            ("CanCollide", Variant::Bool(false)),
+            ("Tags", Variant::Tags(Tags::from(vec!["a".to_owned()]))),
+            ("Size", Variant::Vector3(Vector3::new(1.0, 2.0, 3.0))),
+        ];
+        if reverse {
+            properties.reverse();
+        }
+
+        let mut part = InstanceBuilder::new("Part");
+        for (name, value) in properties {
+            part = part.with_property(name, value);
+        }
+
+        let part_ref = part.referent();
+        let model = InstanceBuilder::new("Model")
+            .with_property("PrimaryPart", part_ref)
+            .with_child(part)
+            .with_child(InstanceBuilder::new("Folder"));
+
+        WeakDom::new(model)
+    };
+
+    let encode = |tree: &WeakDom| {
+        let mut encoded = Vec::new();
+        rbx_xml::to_writer_default(&mut encoded, tree, &[tree.root_ref()]).unwrap();
+        encoded
+    };
+
+    let first = encode(&build(false));
+    assert_eq!(first, encode(&build(false)));
+    assert_eq!(first, encode(&build(true)));
+}
+
+#[test]
+fn wrap_base64() {
+    let _ = env_logger::try_init();
+
+    let data: Vec<u8> = (0..=255).collect();
+    let tree = WeakDom::new(
+        InstanceBuilder::new("Folder")
+            .with_property("Data", BinaryString::from(data.clone()))
+            .with_property(
+                "Shared",
+                rbx_dom_weak::types::SharedString::new(data.clone()),
+            ),
+    );
+
+    let options = rbx_xml::EncodeOptions::new()
+        .property_behavior(rbx_xml::EncodePropertyBehavior::NoReflection)
+        .wrap_base64(true);
+    let mut encoded = Vec::new();
+    rbx_xml::to_writer(&mut encoded, &tree, &[tree.root_ref()], options).unwrap();
+
+    let encoded_str = std::str::from_utf8(&encoded).unwrap();
+    let full = base64::encode(&data);
+    assert!(encoded_str.contains(&full[..72]));
+    assert!(!encoded_str.contains(&full[..73]));
+
+    let options = rbx_xml::DecodeOptions::new()
+        .property_behavior(rbx_xml::DecodePropertyBehavior::NoReflection);
+    let decoded = rbx_xml::from_reader(encoded.as_slice(), options).unwrap();
+    let folder = decoded.get_by_ref(decoded.root().children()[0]).unwrap();
+    assert_eq!(
+        folder.properties.get("Data"),
+        Some(&Variant::BinaryString(data.clone().into()))
+    );
+    assert_eq!(
+        folder.properties.get("Shared"),
+        Some(&Variant::SharedString(
+            rbx_dom_weak::types::SharedString::new(data)
+        ))
+    );
+}
+
+#[test]
+fn ref_behavior() {
+    let _ = env_logger::try_init();
+
+    let document = r#"
+        <roblox version="4">
+            <Item class="ObjectValue" referent="1">
+                <Properties>
+                    <string name="Name">Null</string>
+                    <Ref name="Value">null</Ref>
+                </Properties>
+            </Item>
+            <Item class="ObjectValue" referent="2">
+                <Properties>
+                    <string name="Name">Dangling</string>
+                    <Ref name="Value">RBX123</Ref>
+                </Properties>
+            </Item>
+        </roblox>
+    "#;
+
+    let decode = |options: rbx_xml::DecodeOptions| {
+        rbx_xml::from_str(document, options).map(|tree| {
+            tree.root()
+                .children()
+                .iter()
+                .map(|referent| {
+                    let instance = tree.get_by_ref(*referent).unwrap();
+                    instance.properties.get("Value").cloned()
+                })
+                .collect::<Vec<_>>()
+        })
+    };
+
+    let null = Some(Variant::Ref(Ref::none()));
+
+    assert_eq!(
+        decode(rbx_xml::DecodeOptions::new()).unwrap(),
+        [null.clone(), null.clone()]
+    );
+
+    let options = rbx_xml::DecodeOptions::new()
+        .null_ref_behavior(rbx_xml::DecodeRefBehavior::DropProperty)
+        .dangling_ref_behavior(rbx_xml::DecodeRefBehavior::DropProperty);
+    assert_eq!(decode(options).unwrap(), [None, None]);
+
+    let options =
+        rbx_xml::DecodeOptions::new().null_ref_behavior(rbx_xml::DecodeRefBehavior::ErrorOnRef);
+    let err = decode(options).unwrap_err();
+    assert_eq!(err.line(), 6);
+
+    let options =
+        rbx_xml::DecodeOptions::new().dangling_ref_behavior(rbx_xml::DecodeRefBehavior::ErrorOnRef);
+    let err = decode(options).unwrap_err();
+    assert_eq!(err.line(), 12);
+    assert_eq!(err.instance_path(), ["Dangling"]);
+    assert_eq!(err.property_name(), Some("Value"));
+}
+
+#[test]
+fn external_refs() {
+    let _ = env_logger::try_init();
+
+    // A place split into two files, where an ObjectValue in one file points
+    // to a Part in the other.
+    let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+    let part = tree.insert(
+        tree.root_ref(),
+        InstanceBuilder::new("Part").with_name("Target"),
+    );
+    let value = tree.insert(
+        tree.root_ref(),
+        InstanceBuilder::new("ObjectValue")
+            .with_name("Pointer")
+            .with_property("Value", part),
+    );
+
+    let to_external = |referent: Ref| (referent == part).then(|| "ID-Target".to_owned());
+
+    let mut part_file = Vec::new();
+    rbx_xml::to_writer_default(&mut part_file, &tree, &[part]).unwrap();
+
+    let mut value_file = Vec::new();
+    let options = rbx_xml::EncodeOptions::new().external_refs(&to_external);
+    rbx_xml::to_writer(&mut value_file, &tree, &[value], options).unwrap();
+    assert!(String::from_utf8(value_file.clone())
+        .unwrap()
+        .contains(r#"<Ref name="Value">ID-Target</Ref>"#));
+
+    let part_tree = rbx_xml::from_reader_default(part_file.as_slice()).unwrap();
+    let new_part = part_tree.root().children()[0];
+
+    let from_external = |id: &str| (id == "ID-Target").then_some(new_part);
+    let options = rbx_xml::DecodeOptions::new().external_refs(&from_external);
+    let value_tree = rbx_xml::from_reader(value_file.as_slice(), options).unwrap();
+    let new_value = value_tree
+        .get_by_ref(value_tree.root().children()[0])
+        .unwrap();
+    assert_eq!(
+        new_value.properties.get("Value"),
+        Some(&Variant::Ref(new_part))
+    );
+
+    // Without the hook, the referent doesn't point to anything.
+    let value_tree = rbx_xml::from_reader_default(value_file.as_slice()).unwrap();
+    let new_value = value_tree
+        .get_by_ref(value_tree.root().children()[0])
+        .unwrap();
+    assert_eq!(
+        new_value.properties.get("Value"),
+        Some(&Variant::Ref(Ref::none()))
+    );
+}
+
+#[test]
+fn legacy_type_names() {
+    let _ = env_logger::try_init();
+
+    let document = r#"
+        <roblox version="4">
+            <Item class="Part" referent="1">
+                <Properties>
+                    <string name="Name">Old Part</string>
+                    <CFrame name="CFrame">
+                        <X>1</X>
+                        <Y>2</Y>
+                        <Z>3</Z>
+                        <R00>1</R00>
+                        <R01>0</R01>
+                        <R02>0</R02>
+                        <R10>0</R10>
+                        <R11>1</R11>
+                        <R12>0</R12>
+                        <R20>0</R20>
+                        <R21>0</R21>
+                        <R22>1</R22>
+                    </CFrame>
+                    <Enum name="Material">256</Enum>
+                </Properties>
+            </Item>
+        </roblox>
+    "#;
+
+    let tree = rbx_xml::from_str_default(document).unwrap();
+    let part = tree.get_by_ref(tree.root().children()[0]).unwrap();
+
+    assert_eq!(
+        part.properties.get("CFrame"),
+        Some(&Variant::CFrame(rbx_dom_weak::types::CFrame::new(
+            Vector3::new(1.0, 2.0, 3.0),
+            rbx_dom_weak::types::Matrix3::identity(),
+        )))
+    );
+    assert_eq!(
+        part.properties.get("Material"),
+        Some(&Variant::Enum(rbx_dom_weak::types::Enum::from_u32(256)))
+    );
+
+    // Legacy names are only accepted when decoding.
+    let mut encoded = Vec::new();
+    rbx_xml::to_writer_default(&mut encoded, &tree, &[part.referent()]).unwrap();
+    let encoded = String::from_utf8(encoded).unwrap();
+    assert!(encoded.contains(r#"<CoordinateFrame name="CFrame">"#));
+    assert!(encoded.contains(r#"<token name="Material">256</token>"#));
+}
+
+#[test]
+fn serialized_property_names() {
+    let _ = env_logger::try_init();
+
+    let document = r#"
+        <roblox version="4">
+            <Item class="Part" referent="1">
+                <Properties>
+                    <string name="Name">Part</string>
+                    <Vector3 name="size">
+                        <X>4</X>
+                        <Y>1</Y>
+                        <Z>2</Z>
+                    </Vector3>
+                    <Color3uint8 name="Color3uint8">4294901760</Color3uint8>
+                </Properties>
+            </Item>
+        </roblox>
+    "#;
+
+    let size = Variant::Vector3(Vector3::new(4.0, 1.0, 2.0));
+
+    let tree = rbx_xml::from_str_default(document).unwrap();
+    let part = tree.get_by_ref(tree.root().children()[0]).unwrap();
+    assert_eq!(part.properties.get("Size"), Some(&size));
+    assert!(part.properties.contains_key("Color"));
+    assert!(!part.properties.contains_key("size"));
+
+    let options = rbx_xml::DecodeOptions::new().canonical_property_names(false);
+    let tree = rbx_xml::from_str(document, options).unwrap();
+    let part = tree.get_by_ref(tree.root().children()[0]).unwrap();
+    assert_eq!(part.properties.get("size"), Some(&size));
+    assert!(part.properties.contains_key("Color3uint8"));
+    assert!(!part.properties.contains_key("Size"));
+
+    // With serialized names turned off on both ends, the names in the tree
+    // are written as-is.
+    let mut encoded = Vec::new();
+    let options = rbx_xml::EncodeOptions::new().serialized_property_names(false);
+    rbx_xml::to_writer(&mut encoded, &tree, &[part.referent()], options).unwrap();
+    let encoded = String::from_utf8(encoded).unwrap();
+    assert!(encoded.contains(r#"<Vector3 name="size">"#));
+    assert!(encoded.contains(r#"<Color3uint8 name="Color3uint8">"#));
+}
+
+#[test]
+fn decode_and_encode_file() {
+    let _ = env_logger::try_init();
+
+    let directory = std::env::temp_dir().join(format!("rbx_xml-file-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let path = directory.join("model.rbxmx");
+
+    let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+    let value = tree.insert(
+        tree.root_ref(),
+        InstanceBuilder::new("StringValue")
+            .with_name("Greeting")
+            .with_property("Value", "Hello!"),
+    );
+
+    rbx_xml::encode_file(&tree, &[value], &path).unwrap();
+    let decoded = rbx_xml::decode_file(&path).unwrap();
+    let decoded_value = decoded.get_by_ref(decoded.root().children()[0]).unwrap();
+    assert_eq!(decoded_value.name, "Greeting");
+    assert_eq!(
+        decoded_value.properties.get("Value"),
+        Some(&Variant::String("Hello!".to_owned()))
+    );
+
+    let missing = directory.join("missing.rbxmx");
+    let err = rbx_xml::decode_file(&missing).unwrap_err();
+    assert_eq!(err.file_path(), Some(missing.as_path()));
+    assert!(err.to_string().starts_with(&missing.display().to_string()));
+
+    let binary = directory.join("model.rbxm");
+    let err = rbx_xml::encode_file(&tree, &[value], &binary).unwrap_err();
+    assert_eq!(err.file_path(), Some(binary.as_path()));
+    assert!(!binary.exists());
+    assert!(rbx_xml::decode_file(&binary).is_err());
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn progress() {
+    let _ = env_logger::try_init();
+
+    let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+    let folder = tree.insert(tree.root_ref(), InstanceBuilder::new("Folder"));
+    for i in 0..3 {
+        tree.insert(
+            folder,
+            InstanceBuilder::new("StringValue").with_property("Value", "x".repeat(i * 100)),
+        );
+    }
+
+    let updates = std::sync::Mutex::new(Vec::new());
+    let record = |progress: rbx_xml::Progress| updates.lock().unwrap().push(progress);
+
+    let mut encoded = Vec::new();
+    let options = rbx_xml::EncodeOptions::new().progress(&record);
+    rbx_xml::to_writer(&mut encoded, &tree, &[folder], options).unwrap();
+
+    let encode_updates = std::mem::take(&mut *updates.lock().unwrap());
+    assert_eq!(
+        encode_updates
+            .iter()
+            .map(|progress| progress.instances)
+            .collect::<Vec<_>>(),
+        [1, 2, 3, 4]
+    );
+    assert!(encode_updates
+        .windows(2)
+        .all(|pair| pair[0].bytes < pair[1].bytes));
+    assert!(encode_updates.last().unwrap().bytes <= encoded.len() as u64);
+
+    let options = rbx_xml::DecodeOptions::new().progress(&record);
+    rbx_xml::from_reader(encoded.as_slice(), options).unwrap();
+
+    let decode_updates = std::mem::take(&mut *updates.lock().unwrap());
+    assert_eq!(decode_updates.len(), 4);
+    assert_eq!(decode_updates.last().unwrap().instances, 4);
+    assert!(decode_updates
+        .windows(2)
+        .all(|pair| pair[0].bytes <= pair[1].bytes));
+    assert!(decode_updates.last().unwrap().bytes <= encoded.len() as u64);
+}
+
+#[test]
+fn document_encodings() {
+    let _ = env_logger::try_init();
+
+    // Long enough to be transcoded in more than one chunk, with characters
+    // outside of the Basic Multilingual Plane that might be split up.
+    let long_value = "Hello, 🌍! ".repeat(1000);
+
+    let document = format!(
+        r#"<roblox version="4">
+            <Item class="StringValue" referent="1">
+                <Properties>
+                    <string name="Name">Värde</string>
+                    <string name="Value">{}</string>
+                </Properties>
+            </Item>
+        </roblox>"#,
+        long_value
+    );
+    let declared = format!(r#"<?xml version="1.0" encoding="UTF-16"?>{}"#, document);
+
+    let utf16_le = |text: &str| {
+        text.encode_utf16()
+            .flat_map(|unit| unit.to_le_bytes())
+            .collect::<Vec<u8>>()
+    };
+    let utf16_be = |text: &str| {
+        text.encode_utf16()
+            .flat_map(|unit| unit.to_be_bytes())
+            .collect::<Vec<u8>>()
+    };
+
+    let inputs = [
+        (
+            "UTF-8 with BOM",
+            [&b"\xEF\xBB\xBF"[..], document.as_bytes()].concat(),
+        ),
+        (
+            "UTF-16 LE with BOM",
+            [&[0xFF, 0xFE][..], &utf16_le(&declared)].concat(),
+        ),
+        (
+            "UTF-16 BE with BOM",
+            [&[0xFE, 0xFF][..], &utf16_be(&document)].concat(),
+        ),
+        ("UTF-16 LE without BOM", utf16_le(&declared)),
+        ("UTF-16 BE without BOM", utf16_be(&document)),
+    ];
+
+    for (description, input) in inputs {
+        let tree = rbx_xml::from_reader_default(input.as_slice())
+            .unwrap_or_else(|err| panic!("could not decode {}: {}", description, err));
+        let value = tree.get_by_ref(tree.root().children()[0]).unwrap();
+
+        assert_eq!(value.name, "Värde", "{}", description);
+        assert_eq!(
+            value.properties.get("Value"),
+            Some(&Variant::String(long_value.clone())),
+            "{}",
+            description
+        );
+    }
+
+    // A document that's cut off in the middle of a character is an error.
+    let mut truncated = utf16_le(&document);
+    truncated.pop();
+    assert!(rbx_xml::from_reader_default(truncated.as_slice()).is_err());
+}
+
+#[test]
+fn terrain_smooth_grid() {
+    use rbx_dom_weak::types::{SmoothGrid, TerrainMaterial, Voxel};
+
+    let _ = env_logger::try_init();
+
+    let mut grid = SmoothGrid::new();
+    grid.set_voxel([0, 0, 0], Voxel::full(TerrainMaterial::Grass));
+    grid.set_voxel([0, 1, 0], Voxel::full(TerrainMaterial::Snow));
+
+    // PhysicsGrid isn't decoded, so it should be passed through unchanged.
+    let physics_grid = BinaryString::from(b"\x02\x03Physics".to_vec());
+
+    let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+    let terrain = tree.insert(
+        tree.root_ref(),
+        InstanceBuilder::new("Terrain")
+            .with_property("SmoothGrid", grid.clone())
+            .with_property("PhysicsGrid", physics_grid.clone()),
+    );
+
+    // SmoothGrid is stored as a BinaryString.
+    let mut encoded = Vec::new();
+    rbx_xml::to_writer_default(&mut encoded, &tree, &[terrain]).unwrap();
+
+    let encoded_str = std::str::from_utf8(&encoded).unwrap();
+    assert!(encoded_str.contains(r#"<BinaryString name="SmoothGrid">"#));
+
+    let decoded = rbx_xml::from_reader_default(encoded.as_slice()).unwrap();
+    let terrain = decoded.get_by_ref(decoded.root().children()[0]).unwrap();
+
+    assert_eq!(
+        terrain.properties.get("SmoothGrid"),
+        Some(&Variant::SmoothGrid(grid.clone()))
+    );
+    assert_eq!(
+        terrain.properties.get("PhysicsGrid"),
+        Some(&Variant::BinaryString(physics_grid.clone()))
+    );
+
+    // The raw bytes are accepted too, and written the same way.
+    let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+    let terrain = tree.insert(
+        tree.root_ref(),
+        InstanceBuilder::new("Terrain")
+            .with_property("SmoothGrid", BinaryString::from(grid.encode()))
+            .with_property("PhysicsGrid", physics_grid),
+    );
+
+    let mut raw_encoded = Vec::new();
+    rbx_xml::to_writer_default(&mut raw_encoded, &tree, &[terrain]).unwrap();
+    assert_eq!(raw_encoded, encoded);
+}
+
+#[test]
+fn terrain_material_colors() {
+    use rbx_dom_weak::types::{Color3uint8, MaterialColors, TerrainMaterial};
+
+    let _ = env_logger::try_init();
+
+    let mut colors = MaterialColors::default();
+    colors.set(TerrainMaterial::Snow, Color3uint8::new(250, 250, 255));
+
+    let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+    let terrain = tree.insert(
+        tree.root_ref(),
+        InstanceBuilder::new("Terrain").with_property("MaterialColors", colors),
+    );
+
+    let mut encoded = Vec::new();
+    rbx_xml::to_writer_default(&mut encoded, &tree, &[terrain]).unwrap();
+
+    // MaterialColors are stored as a BinaryString.
+    let encoded_str = std::str::from_utf8(&encoded).unwrap();
+    assert!(encoded_str.contains(r#"<BinaryString name="MaterialColors">"#));
+
+    let decoded = rbx_xml::from_reader_default(encoded.as_slice()).unwrap();
+    let terrain = decoded.get_by_ref(decoded.root().children()[0]).unwrap();
+
+    assert_eq!(
+        terrain.properties.get("MaterialColors"),
+        Some(&Variant::MaterialColors(colors))
+    );
+}
+
+#[test]
+fn unique_id_referents() {
+    let _ = env_logger::try_init();
+
+    let first_id = UniqueId::new(1, 2, 3);
+    let second_id = UniqueId::new(4, 5, 6);
+
+    let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+    let first = tree.insert(
+        tree.root_ref(),
+        InstanceBuilder::new("ObjectValue").with_property("UniqueId", first_id),
+    );
+    tree.insert(
+        first,
+        InstanceBuilder::new("ObjectValue").with_property("UniqueId", second_id),
+    );
+    tree.insert(
+        first,
+        InstanceBuilder::new("Folder").with_property("UniqueId", second_id),
+    );
+    let unidentified = tree.insert(first, InstanceBuilder::new("Folder"));
+    tree.get_by_ref_mut(first)
+        .unwrap()
+        .properties
+        .insert("Value".to_owned(), Variant::Ref(unidentified));
+
+    let options = rbx_xml::EncodeOptions::new().unique_id_referents(true);
+    let mut encoded = Vec::new();
+    rbx_xml::to_writer(&mut encoded, &tree, &[first], options).unwrap();
+    let encoded = String::from_utf8(encoded).unwrap();
+
+    assert!(encoded.contains(r#"referent="RBX00000000000000030000000200000001""#));
+
+    // Instances with the same UniqueId, or without one, are numbered.
+    assert!(!encoded.contains(r#"referent="RBX00000000000000060000000500000004""#));
+    assert_eq!(encoded.matches(r#"referent="0""#).count(), 1);
+    assert_eq!(encoded.matches(r#"referent="1""#).count(), 1);
+    assert_eq!(encoded.matches(r#"referent="2""#).count(), 1);
+
+    let options = rbx_xml::DecodeOptions::new().unique_id_referents(true);
+    let decoded = rbx_xml::from_reader_document(encoded.as_bytes(), options).unwrap();
+    let decoded_first = decoded.tree.get_by_ref(decoded.roots[0]).unwrap();
+    assert_eq!(
+        decoded_first.properties.get("UniqueId"),
+        Some(&Variant::UniqueId(first_id))
+    );
+
+    let decoded_value = match decoded_first.properties.get("Value") {
+        Some(Variant::Ref(value)) => *value,
+        other => panic!("Value was not a Ref: {:?}", other),
+    };
+    assert_eq!(decoded_first.children().len(), 3);
+    assert_eq!(decoded_value, decoded_first.children()[2]);
+}
+
+#[test]
+fn unique_id_from_referent() {
+    let _ = env_logger::try_init();
+
+    let document = r#"
+        <roblox version="4">
+            <Item class="Folder" referent="RBX00000000000000030000000200000001">
+                <Properties />
+            </Item>
+        </roblox>
+    "#;
+
+    let options = rbx_xml::DecodeOptions::new().unique_id_referents(true);
+    let dom = rbx_xml::from_str(document, options).unwrap();
+    let folder = dom.get_by_ref(dom.root().children()[0]).unwrap();
+    assert_eq!(
+        folder.properties.get("UniqueId"),
+        Some(&Variant::UniqueId(UniqueId::new(1, 2, 3)))
+    );
+
+    let dom = rbx_xml::from_str_default(document).unwrap();
+    let folder = dom.get_by_ref(dom.root().children()[0]).unwrap();
+    assert_eq!(folder.properties.get("UniqueId"), None);
+}
+
+#[test]
+fn strict_types() {
+    let _ = env_logger::try_init();
+
+    let document = r#"
+        <roblox version="4">
+            <Item class="BoolValue" referent="RBX0">
+                <Properties>
+                    <string name="Value">true</string>
+                </Properties>
+            </Item>
+        </roblox>
+    "#;
+
+    let dom = rbx_xml::from_str_default(document).unwrap();
+    let value = dom.get_by_ref(dom.root().children()[0]).unwrap();
+    assert_eq!(
+        value.properties.get("Value"),
+        Some(&Variant::String("true".to_owned()))
+    );
+
+    let options = rbx_xml::DecodeOptions::new().strict_types(true);
+    let error = rbx_xml::from_str(document, options).unwrap_err();
+    assert!(error.to_string().contains("BoolValue.Value"));
+
+    let options = rbx_xml::DecodeOptions::new()
+        .strict_types(true)
+        .mismatch_behavior(rbx_xml::DecodeMismatchBehavior::ReadOriginal);
+    let unsupported_attributes = r#"
+        <roblox version="4">
+            <Item class="Folder" referent="RBX0">
+                <Properties>
+                    <BinaryString name="AttributesSerialize">AQAAAAEAAABBAf8=</BinaryString>
+                </Properties>
+            </Item>
+        </roblox>
+    "#;
+    assert!(rbx_xml::from_str(unsupported_attributes, options).is_err());
+
+    let options = rbx_xml::DecodeOptions::new().strict_types(true);
+    let valid = r#"
+        <roblox version="4">
+            <Item class="BoolValue" referent="RBX0">
+                <Properties>
+                    <bool name="Value">true</bool>
+                </Properties>
+            </Item>
+        </roblox>
+    "#;
+    assert!(rbx_xml::from_str(valid, options).is_ok());
+}
+
+#[test]
+fn in_memory_round_trip() {
+    let _ = env_logger::try_init();
+
+    let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+    let value = tree.insert(
+        tree.root_ref(),
+        InstanceBuilder::new("StringValue").with_property("Value", "Hello!"),
+    );
+
+    let bytes = rbx_xml::encode_to_vec(&tree, &[value], rbx_xml::EncodeOptions::new()).unwrap();
+    let string = rbx_xml::encode_to_string(&tree, &[value], rbx_xml::EncodeOptions::new()).unwrap();
+    assert_eq!(bytes, string.as_bytes());
+
+    let decoded = rbx_xml::decode_str(&string, rbx_xml::DecodeOptions::new()).unwrap();
+    let decoded_value = decoded.get_by_ref(decoded.root().children()[0]).unwrap();
+    assert_eq!(
+        decoded_value.properties.get("Value"),
+        Some(&Variant::String("Hello!".to_owned()))
+    );
+}
+
+#[test]
+fn place_document_kind() {
+    let _ = env_logger::try_init();
+
+    let mut tree = WeakDom::new(InstanceBuilder::new("DataModel"));
+    let workspace = tree.insert(tree.root_ref(), InstanceBuilder::new("Workspace"));
+    tree.insert(tree.root_ref(), InstanceBuilder::new("Lighting"));
+    tree.insert(workspace, InstanceBuilder::new("Part"));
+
+    let place = || rbx_xml::EncodeOptions::new().document_kind(rbx_xml::EncodeDocumentKind::Place);
+
+    let encoded = rbx_xml::encode_to_string(&tree, &[tree.root_ref()], place()).unwrap();
+    assert!(encoded.contains(r#"<Meta name="ExplicitAutoJoints">true</Meta>"#));
+    assert!(!encoded.contains(r#"class="DataModel""#));
+
+    let decoded = rbx_xml::decode_str(&encoded, rbx_xml::DecodeOptions::new()).unwrap();
+    let classes: Vec<_> = decoded
+        .root()
+        .children()
+        .iter()
+        .map(|&id| decoded.get_by_ref(id).unwrap().class.as_str())
+        .collect();
+    assert_eq!(classes, ["Workspace", "Lighting"]);
+
+    // Models don't get any of this.
+    let encoded =
+        rbx_xml::encode_to_string(&tree, &[workspace], rbx_xml::EncodeOptions::new()).unwrap();
+    assert!(!encoded.contains("ExplicitAutoJoints"));
+
+    let folder = tree.insert(tree.root_ref(), InstanceBuilder::new("Folder"));
+    assert!(rbx_xml::encode_to_string(&tree, &[workspace, folder], place()).is_err());
+
+    let second_workspace = tree.insert(tree.root_ref(), InstanceBuilder::new("Workspace"));
+    assert!(rbx_xml::encode_to_string(&tree, &[workspace, second_workspace], place()).is_err());
+}
+
+#[test]
+fn duplicate_referents() {
+    let _ = env_logger::try_init();
+
+    let document = r#"
+        <roblox version="4">
+            <Item class="ObjectValue" referent="RBX0">
+                <Properties>
+                    <string name="Name">First</string>
+                    <Ref name="Value">RBX1</Ref>
+                </Properties>
+            </Item>
+            <Item class="Folder" referent="RBX1">
+                <Properties>
+                    <string name="Name">Original</string>
+                </Properties>
+            </Item>
+            <Item class="Folder" referent="RBX1">
+                <Properties>
+                    <string name="Name">Duplicate</string>
+                </Properties>
+            </Item>
+        </roblox>
+    "#;
+
+    let target_name = |behavior| {
+        let options = rbx_xml::DecodeOptions::new().duplicate_referent_behavior(behavior);
+        let decoded = rbx_xml::from_reader_document(document.as_bytes(), options).unwrap();
+        assert_eq!(decoded.warnings.len(), 1);
+
+        let value = decoded.tree.get_by_ref(decoded.roots[0]).unwrap();
+        let target = match value.properties.get("Value") {
+            Some(Variant::Ref(target)) => *target,
+            other => panic!("Value was not a Ref: {:?}", other),
+        };
+
+        decoded.tree.get_by_ref(target).unwrap().name.clone()
+    };
+
+    assert_eq!(
+        target_name(rbx_xml::DecodeDuplicateReferentBehavior::LastWins),
+        "Duplicate"
+    );
+    assert_eq!(
+        target_name(rbx_xml::DecodeDuplicateReferentBehavior::Renumber),
+        "Original"
+    );
+
+    let options = rbx_xml::DecodeOptions::new()
+        .duplicate_referent_behavior(rbx_xml::DecodeDuplicateReferentBehavior::ErrorOnDuplicate);
+    let error = rbx_xml::from_str(document, options).unwrap_err();
+    assert!(error.to_string().contains("RBX1"));
+}
+
+#[test]
+fn model_world_pivot() {
+    let _ = env_logger::try_init();
+
+    let document = r#"
+        <roblox version="4">
+            <Item class="Model" referent="RBX0">
+                <Properties>
+                    <OptionalCoordinateFrame name="WorldPivotData">
+                        <CFrame>
+                            <X>1</X><Y>2</Y><Z>3</Z>
+                            <R00>1</R00><R01>0</R01><R02>0</R02>
+                            <R10>0</R10><R11>1</R11><R12>0</R12>
+                            <R20>0</R20><R21>0</R21><R22>1</R22>
+                        </CFrame>
+                    </OptionalCoordinateFrame>
+                </Properties>
+            </Item>
+            <Item class="Model" referent="RBX1">
+                <Properties>
+                    <OptionalCoordinateFrame name="WorldPivotData"></OptionalCoordinateFrame>
+                </Properties>
+            </Item>
+            <Item class="Model" referent="RBX2">
+                <Properties>
+                    <OptionalCFrame name="WorldPivotData"/>
+                </Properties>
+            </Item>
+        </roblox>
+    "#;
+
+    let pivots = |tree: &WeakDom| -> Vec<Option<Variant>> {
+        tree.root()
+            .children()
+            .iter()
+            .map(|&id| {
+                let instance = tree.get_by_ref(id).unwrap();
+                instance.properties.get("WorldPivotData").cloned()
+            })
+            .collect()
+    };
+
+    let tree = rbx_xml::from_str_default(document).unwrap();
+    let expected = vec![
+        Some(Variant::OptionalCFrame(Some(CFrame::new(
+            Vector3::new(1.0, 2.0, 3.0),
+            Matrix3::identity(),
+        )))),
+        Some(Variant::OptionalCFrame(None)),
+        Some(Variant::OptionalCFrame(None)),
+    ];
+    assert_eq!(pivots(&tree), expected);
+
+    let encoded =
+        rbx_xml::encode_to_string(&tree, tree.root().children(), rbx_xml::EncodeOptions::new())
+            .unwrap();
+    assert!(encoded.contains(r#"<OptionalCoordinateFrame name="WorldPivotData">"#));
+
+    let decoded = rbx_xml::decode_str(&encoded, rbx_xml::DecodeOptions::new()).unwrap();
+    assert_eq!(pivots(&decoded), expected);
+}
+
+#[test]
+fn migrate_properties() {
+    let _ = env_logger::try_init();
+
+    let document = |font: u32| {
+        format!(
+            r#"
+            <roblox version="4">
+                <Item class="TextLabel" referent="RBX0">
+                    <Properties>
+                        <token name="Font">{}</token>
+                    </Properties>
+                </Item>
+            </roblox>
+            "#,
+            font
+        )
+    };
+
+    let decoded =
+        rbx_xml::from_reader_document(document(3).as_bytes(), rbx_xml::DecodeOptions::new())
+            .unwrap();
+    let label = decoded.tree.get_by_ref(decoded.roots[0]).unwrap();
+    assert_eq!(
+        label.properties.get("FontFace"),
+        Some(&Variant::Font(Font::regular(
+            "rbxasset://fonts/families/SourceSansPro.json"
+        )))
+    );
+    assert!(!label.properties.contains_key("Font"));
+    assert_eq!(decoded.warnings.len(), 1);
+
+    let options = rbx_xml::DecodeOptions::new().migrate_properties(false);
+    let decoded = rbx_xml::from_reader_document(document(3).as_bytes(), options).unwrap();
+    let label = decoded.tree.get_by_ref(decoded.roots[0]).unwrap();
+    assert_eq!(
+        label.properties.get("Font"),
+        Some(&Variant::Enum(Enum::from_u32(3)))
+    );
+    assert!(!label.properties.contains_key("FontFace"));
+    assert!(decoded.warnings.is_empty());
+
+    // Values that can't be migrated are errors unless decoding is lenient.
+    assert!(rbx_xml::decode_str(&document(999), rbx_xml::DecodeOptions::new()).is_err());
+
+    let options = rbx_xml::DecodeOptions::new().lenient(true);
+    let decoded = rbx_xml::from_reader_document(document(999).as_bytes(), options).unwrap();
+    let label = decoded.tree.get_by_ref(decoded.roots[0]).unwrap();
+    assert!(!label.properties.contains_key("Font"));
+    assert_eq!(decoded.warnings.len(), 1);
+}